@@ -36,7 +36,30 @@ fn build_with_artifacts_in_dir(path: &Path) {
     let cargo_metadata_path = path.join("cargo_metadata.txt");
     let metadata = std::fs::read_to_string(&cargo_metadata_path)
         .unwrap_or_else(|_| panic!("failed to read {}", cargo_metadata_path.display()));
-    println!("{}", metadata);
+
+    for line in metadata.lines() {
+        println!("{}", resolve_artifact_relative_path(path, line));
+    }
+}
+
+/// Resolve a path in a cargo metadata line relative to the artifacts directory.
+///
+/// Relocatable artifacts (e.g. a vendored directory) refer to the files
+/// they provide by relative paths. Cargo would resolve these against an
+/// unspecified directory, so make them absolute.
+fn resolve_artifact_relative_path(artifacts_dir: &Path, line: &str) -> String {
+    for prefix in &[
+        "cargo:rustc-link-search=native=",
+        "cargo:default-python-config-rs=",
+    ] {
+        if let Some(value) = line.strip_prefix(prefix) {
+            if Path::new(value).is_relative() {
+                return format!("{}{}", prefix, artifacts_dir.join(value).display());
+            }
+        }
+    }
+
+    line.to_string()
 }
 
 /// Build by calling a `pyoxidizer` executable to generate build artifacts.
@@ -134,6 +157,9 @@ fn main() {
     );
 
     println!("cargo:rustc-cfg=library_mode=\"{}\"", library_mode);
+    // Declare the cfg so toolchains checking cfg names don't warn about it.
+    // Older Cargo versions ignore this.
+    println!("cargo:rustc-check-cfg=cfg(library_mode, values(\"extension\", \"pyembed\"))");
 
     // Re-export the path to the configured Python interpreter. Tests can
     // use this to derive a useful default config that leverages it.
@@ -0,0 +1,38 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Run Python work from a tokio runtime without blocking runtime threads.
+
+use pyembed::{MainPythonInterpreter, OxidizedPythonInterpreterConfig};
+
+fn main() {
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config.interpreter_config.parse_argv = Some(false);
+
+    let mut interp = MainPythonInterpreter::new(config).unwrap();
+
+    let tasks = (0..8)
+        .map(|i| {
+            interp.spawn_blocking_py(move |py| {
+                py.eval(&format!("sum(range({}))", i * 1000), None, None)
+                    .and_then(|v| v.extract::<i64>(py))
+                    .unwrap_or(-1)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        for (i, task) in tasks.into_iter().enumerate() {
+            match task.await {
+                Ok(value) => println!("sum(range({})) = {}", i * 1000, value),
+                Err(e) => eprintln!("error: {}", e),
+            }
+        }
+    });
+}
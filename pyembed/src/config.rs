@@ -5,20 +5,37 @@
 //! Data structures for configuring a Python interpreter.
 
 use {
-    crate::NewInterpreterError,
+    crate::{
+        encryption::ResourcesKeyProvider, python_shim::python_shim_app_path,
+        startup_audit::startup_audit_path_from_env, NewInterpreterError,
+    },
     python3_sys as pyffi,
     python_packaging::interpreter::{
-        MemoryAllocatorBackend, MultiprocessingStartMethod, PythonInterpreterConfig,
-        PythonInterpreterProfile, TerminfoResolution,
+        InitFailureAction, MemoryAllocatorBackend, MultiprocessingStartMethod,
+        PythonInterpreterConfig, PythonInterpreterProfile, PythonRunMode, SysExecutableMode,
+        TerminfoResolution,
     },
     std::{
         convert::TryFrom,
-        ffi::{CString, OsString},
+        ffi::{CString, OsStr, OsString},
         ops::Deref,
-        path::PathBuf,
+        path::{Path, PathBuf},
     },
 };
 
+/// Name of the file marking a directory as a filesystem resources root.
+///
+/// The file is written next to the executable when a build configures a
+/// filesystem resources root. It must be present in a root set via
+/// [OxidizedPythonInterpreterConfig::filesystem_resources_root] or
+/// [OxidizedPythonInterpreterConfig::resources_root_env].
+pub const FILESYSTEM_RESOURCES_ROOT_MARKER: &str = ".pyoxidizer-resources-root";
+
+/// Default of [OxidizedPythonInterpreterConfig::failure_exit_code_base].
+///
+/// This is `EX_SOFTWARE` from `sysexits.h`.
+pub const DEFAULT_FAILURE_EXIT_CODE_BASE: i32 = 70;
+
 /// Defines an extra extension module to load.
 #[derive(Clone, Debug)]
 pub struct ExtensionModule {
@@ -181,6 +198,15 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// [`pkgutil`]: https://docs.python.org/3/library/pkgutil.html
     pub filesystem_importer: bool,
 
+    /// Whether to serve filesystem APIs on paths of in-memory package resources.
+    ///
+    /// If set, `os.listdir()`, `os.scandir()`, `os.stat()`, `os.lstat()`, and
+    /// `open()` are replaced by wrappers serving paths derived from the
+    /// `__path__` of in-memory packages from the resources index. This allows
+    /// code performing filesystem I/O on resources next to a package to work.
+    /// Requires `oxidized_importer=true`.
+    pub resources_filesystem_shim: bool,
+
     /// References to packed resources data.
     ///
     /// The format of the data is defined by the ``python-packed-resources``
@@ -195,6 +221,50 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// relative to the process's current working directory.
     pub packed_resources: Vec<PackedResourcesSource<'a>>,
 
+    /// Directory that filesystem-relative resource paths are resolved against.
+    ///
+    /// Resources installed relative to the executable, such as modules in a
+    /// `lib` directory next to it, are found by joining their relative path
+    /// to this directory. `None` resolves them against `$ORIGIN`.
+    ///
+    /// `$ORIGIN` in the path is expanded to the directory of the current
+    /// executable. If set, the directory must contain a
+    /// [FILESYSTEM_RESOURCES_ROOT_MARKER] file or interpreter initialization
+    /// fails.
+    pub filesystem_resources_root: Option<PathBuf>,
+
+    /// Environment variable overriding `filesystem_resources_root`.
+    ///
+    /// If this value is set and the environment variable it refers to is set
+    /// to a non-empty value, that value is used as the filesystem resources
+    /// root. This allows relocating resources to a directory unrelated to the
+    /// executable without rebuilding it.
+    pub resources_root_env: Option<String>,
+
+    /// The Python bytecode magic number that embedded resources were compiled for.
+    ///
+    /// If set, interpreter initialization will fail if `libpython` reports a
+    /// different magic number via `PyImport_GetMagicNumber()` or if any
+    /// `packed_resources` data records a different magic number. This
+    /// catches mixing bytecode and `libpython` from different Python
+    /// distributions, which would otherwise fail at import time with a
+    /// *bad magic number* error.
+    pub bytecode_magic_number: Option<u32>,
+
+    /// Function providing the key to decrypt encrypted resources payloads.
+    ///
+    /// Packed resources data may contain encrypted module source and
+    /// bytecode. When such a module is first loaded, this function is
+    /// called to obtain the secret key and payloads are then decrypted on
+    /// demand. Decryption requires this crate's `encrypted-resources`
+    /// feature. If no provider is registered, importing an encrypted module
+    /// raises `ImportError`.
+    ///
+    /// Decrypted payloads are kept in locked memory that is zeroed after use.
+    /// But the plaintext is copied into Python `bytes` objects for the import
+    /// machinery. These copies are neither locked nor zeroed.
+    pub resources_key_provider: Option<ResourcesKeyProvider>,
+
     /// Extra extension modules to make available to the interpreter.
     ///
     /// The values will effectively be passed to ``PyImport_ExtendInitTab()``.
@@ -236,6 +306,18 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// of setting this attribute.
     pub sys_meipass: bool,
 
+    /// How to set `sys.executable`.
+    ///
+    /// With [SysExecutableMode::PythonShim], a `<name>-python` executable is
+    /// created next to the current executable. When the current executable
+    /// is invoked through it, `run_*` settings of `.interpreter_config` are
+    /// ignored and process arguments are parsed like `python` would.
+    ///
+    /// With [SysExecutableMode::Invoked], `.interpreter_config.executable`
+    /// defaults to the path the executable was invoked as, if it refers to
+    /// the current executable.
+    pub sys_executable_mode: SysExecutableMode,
+
     /// How to resolve the `terminfo` database.
     pub terminfo_resolution: TerminfoResolution,
 
@@ -255,6 +337,91 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// the directory specified containing a ``\n`` delimited list of modules
     /// loaded in ``sys.modules``.
     pub write_modules_directory_env: Option<String>,
+
+    /// Path of a file to write a startup audit to.
+    ///
+    /// If set, files opened, directories listed, and sockets used by Python
+    /// from interpreter initialization until it starts running what it is
+    /// configured to run are recorded in this file. Each line holds a kind
+    /// (`open`, `listdir`, `socket`, or `env`) and a detail separated by a
+    /// tab. A final `end` entry names the event ending the recording.
+    ///
+    /// `$ORIGIN` in the path is expanded to the directory of the current
+    /// executable.
+    pub startup_audit_path: Option<PathBuf>,
+
+    /// Python callable to call after the interpreter is initialized.
+    ///
+    /// The value is a dotted name of the form `<module>.<attribute>`. The
+    /// callable is called without arguments. If it raises, interpreter
+    /// creation fails.
+    pub post_init_callable: Option<String>,
+
+    /// Python callable to call before the interpreter is finalized.
+    ///
+    /// The value is a dotted name of the form `<module>.<attribute>`. The
+    /// callable is resolved when the interpreter is initialized and is
+    /// registered as an `atexit` handler.
+    pub pre_finalize_callable: Option<String>,
+
+    /// What to run.
+    ///
+    /// With [PythonRunMode::Repl], [crate::MainPythonInterpreter::run()]
+    /// runs an interactive REPL in the `__main__` namespace instead of what
+    /// the `run_*` fields of `.interpreter_config` specify. If the `readline`
+    /// module is available, it provides line editing, tab completion, and
+    /// persistent history. `terminfo` is resolved as if `terminfo_resolution`
+    /// were [TerminfoResolution::Dynamic] when it is [TerminfoResolution::None].
+    pub run_mode: PythonRunMode,
+
+    /// Banner to print when the REPL starts.
+    ///
+    /// `None` prints the banner of the `python` REPL. An empty string
+    /// prints nothing.
+    pub repl_banner: Option<String>,
+
+    /// Python code to run in the `__main__` namespace before the REPL prompts.
+    ///
+    /// This is suitable for importing modules the REPL should have available.
+    /// An exception is printed and does not prevent the REPL from starting.
+    pub repl_startup_code: Option<String>,
+
+    /// Path of the file holding REPL input history.
+    ///
+    /// `None` uses `.<executable name>_history` in the home directory of the
+    /// current user. `$ORIGIN` in the path is expanded to the directory of
+    /// the current executable and a leading `~` to the home directory of the
+    /// current user. History requires the `readline` module.
+    pub repl_history_path: Option<PathBuf>,
+
+    /// Names of `encodings` submodules excluded from the application.
+    ///
+    /// If non-empty, a codec search function is registered after
+    /// interpreter initialization. Looking up a codec implemented by one of
+    /// these modules raises a `LookupError` explaining that the codec was
+    /// excluded at build time, rather than just that it is unknown.
+    pub excluded_encodings: Vec<String>,
+
+    /// Exit code used when the interpreter fails to initialize.
+    ///
+    /// [crate::InitFailureHandler] exits with this code when interpreter
+    /// initialization fails and with this code plus 1 when resources are
+    /// invalid. Exit codes of Python code are passed through unchanged, so
+    /// this can be changed to avoid colliding with exit codes of the
+    /// application.
+    pub failure_exit_code_base: i32,
+
+    /// What to do when the interpreter fails to initialize.
+    ///
+    /// This is honored by [crate::InitFailureHandler].
+    pub on_init_failure: InitFailureAction,
+
+    /// Command to run for [InitFailureAction::RunFallbackCommand].
+    ///
+    /// The first element is the program and the rest are its arguments.
+    /// Arguments of the current process are appended. `$ORIGIN` in the
+    /// program is expanded to the directory of the current executable.
+    pub init_failure_fallback_command: Option<Vec<String>>,
 }
 
 impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
@@ -277,7 +444,12 @@ impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
             set_missing_path_configuration: true,
             oxidized_importer: false,
             filesystem_importer: true,
+            resources_filesystem_shim: false,
             packed_resources: vec![],
+            filesystem_resources_root: None,
+            resources_root_env: None,
+            bytecode_magic_number: None,
+            resources_key_provider: None,
             extra_extension_modules: None,
             argv: None,
             argvb: false,
@@ -285,9 +457,21 @@ impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
             multiprocessing_start_method: MultiprocessingStartMethod::Auto,
             sys_frozen: false,
             sys_meipass: false,
+            sys_executable_mode: SysExecutableMode::App,
             terminfo_resolution: TerminfoResolution::Dynamic,
             tcl_library: None,
             write_modules_directory_env: None,
+            startup_audit_path: None,
+            post_init_callable: None,
+            pre_finalize_callable: None,
+            run_mode: PythonRunMode::Default,
+            repl_banner: None,
+            repl_startup_code: None,
+            repl_history_path: None,
+            excluded_encodings: vec![],
+            failure_exit_code_base: DEFAULT_FAILURE_EXIT_CODE_BASE,
+            on_init_failure: InitFailureAction::Exit,
+            init_failure_fallback_command: None,
         }
     }
 }
@@ -295,16 +479,8 @@ impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
 impl<'a> OxidizedPythonInterpreterConfig<'a> {
     /// Create a new type with all values resolved.
     pub fn resolve(
-        self,
+        mut self,
     ) -> Result<ResolvedOxidizedPythonInterpreterConfig<'a>, NewInterpreterError> {
-        let argv = if let Some(args) = self.argv {
-            Some(args)
-        } else if self.interpreter_config.argv.is_some() {
-            None
-        } else {
-            Some(std::env::args_os().collect::<Vec<_>>())
-        };
-
         let exe = if let Some(exe) = self.exe {
             exe
         } else {
@@ -318,6 +494,42 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
         let exe = dunce::canonicalize(exe)
             .map_err(|_| NewInterpreterError::Simple("could not obtain current executable path"))?;
 
+        // When invoked through the python shim, behave like `python`: arguments
+        // from the process are parsed and determine what is run.
+        if self.sys_executable_mode == SysExecutableMode::PythonShim
+            && python_shim_app_path(&exe).is_some()
+        {
+            self.interpreter_config.parse_argv = Some(true);
+            self.interpreter_config.argv = None;
+            self.interpreter_config.run_command = None;
+            self.interpreter_config.run_filename = None;
+            self.interpreter_config.run_module = None;
+            self.run_mode = PythonRunMode::Default;
+        }
+
+        let argv = if let Some(args) = self.argv {
+            Some(args)
+        } else if self.interpreter_config.argv.is_some() {
+            None
+        } else {
+            Some(std::env::args_os().collect::<Vec<_>>())
+        };
+
+        let executable = match &self.interpreter_config.executable {
+            None if self.sys_executable_mode == SysExecutableMode::Invoked => {
+                let arg0 = argv
+                    .as_ref()
+                    .or(self.interpreter_config.argv.as_ref())
+                    .and_then(|args| args.first());
+
+                Some(
+                    arg0.and_then(|arg0| invoked_exe_path(arg0, &exe))
+                        .unwrap_or_else(|| exe.clone()),
+                )
+            }
+            executable => executable.clone(),
+        };
+
         let origin = if let Some(origin) = self.origin {
             origin
         } else {
@@ -360,23 +572,90 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
             .as_ref()
             .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
 
+        let repl_history_path = self
+            .repl_history_path
+            .as_ref()
+            .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
+
+        let startup_audit_path = match startup_audit_path_from_env() {
+            Some(path) => Some(path),
+            None => self
+                .startup_audit_path
+                .as_ref()
+                .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string))),
+        };
+
+        let filesystem_resources_root = match self
+            .resources_root_env
+            .as_ref()
+            .and_then(std::env::var_os)
+            .filter(|value| !value.is_empty())
+        {
+            Some(value) => Some(PathBuf::from(value)),
+            None => self
+                .filesystem_resources_root
+                .as_ref()
+                .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string))),
+        };
+
+        if let Some(root) = &filesystem_resources_root {
+            if !root.join(FILESYSTEM_RESOURCES_ROOT_MARKER).is_file() {
+                return Err(NewInterpreterError::Resources(format!(
+                    "filesystem resources root {} does not contain a {} file; \
+                    is it a copy of the directory containing the application's resources?",
+                    root.display(),
+                    FILESYSTEM_RESOURCES_ROOT_MARKER
+                )));
+            }
+        }
+
         Ok(ResolvedOxidizedPythonInterpreterConfig {
             inner: Self {
                 exe: Some(exe),
                 origin: Some(origin),
+                filesystem_resources_root,
                 interpreter_config: PythonInterpreterConfig {
+                    executable,
                     module_search_paths,
                     ..self.interpreter_config
                 },
                 argv,
                 packed_resources,
                 tcl_library,
+                startup_audit_path,
+                repl_history_path,
                 ..self
             },
         })
     }
 }
 
+/// Resolve the path an executable was invoked as from `argv[0]`.
+///
+/// `argv[0]` without a directory is searched for in `PATH`, like a shell
+/// would. Symbolic links aren't resolved. Returns `None` if the path can't be
+/// found or doesn't refer to `exe`.
+fn invoked_exe_path(arg0: &OsStr, exe: &Path) -> Option<PathBuf> {
+    let arg0 = Path::new(arg0);
+
+    let candidates = if arg0.components().count() > 1 {
+        vec![std::env::current_dir().ok()?.join(arg0)]
+    } else {
+        let mut name = arg0.as_os_str().to_os_string();
+        if arg0.extension().is_none() {
+            name.push(std::env::consts::EXE_SUFFIX);
+        }
+
+        std::env::split_paths(&std::env::var_os("PATH")?)
+            .map(|dir| dir.join(&name))
+            .collect::<Vec<_>>()
+    };
+
+    candidates
+        .into_iter()
+        .find(|path| path.is_file() && dunce::canonicalize(path).ok().as_deref() == Some(exe))
+}
+
 /// An `OxidizedPythonInterpreterConfig` that has fields resolved.
 pub struct ResolvedOxidizedPythonInterpreterConfig<'a> {
     inner: OxidizedPythonInterpreterConfig<'a>,
@@ -414,6 +693,14 @@ impl<'a> ResolvedOxidizedPythonInterpreterConfig<'a> {
             .expect("origin should have a value")
     }
 
+    /// Obtain the directory filesystem-relative resources are resolved against.
+    pub fn filesystem_resources_root(&self) -> &Path {
+        match &self.inner.filesystem_resources_root {
+            Some(root) => root,
+            None => self.origin(),
+        }
+    }
+
     /// Resolve the effective value of `sys.argv`.
     pub fn resolve_sys_argv(&self) -> &[OsString] {
         if let Some(args) = &self.inner.argv {
@@ -441,6 +728,110 @@ impl<'a> ResolvedOxidizedPythonInterpreterConfig<'a> {
 mod tests {
     use {super::*, anyhow::Result};
 
+    #[test]
+    fn test_python_shim_invocation() -> Result<()> {
+        let temp_dir = tempfile::Builder::new().prefix("pyembed-test").tempdir()?;
+        let app_exe = temp_dir
+            .path()
+            .join(format!("myapp{}", std::env::consts::EXE_SUFFIX));
+        let shim = temp_dir
+            .path()
+            .join(format!("myapp-python{}", std::env::consts::EXE_SUFFIX));
+        std::fs::write(&app_exe, b"app")?;
+        std::fs::write(&shim, b"app")?;
+
+        let mut config = OxidizedPythonInterpreterConfig::default();
+        config.sys_executable_mode = SysExecutableMode::PythonShim;
+        config.interpreter_config.parse_argv = Some(false);
+        config.interpreter_config.run_module = Some("myapp".to_string());
+        config.run_mode = PythonRunMode::Repl;
+
+        // Invoking the application executable runs the application.
+        config.exe = Some(app_exe);
+        let resolved = config.clone().resolve()?;
+        assert_eq!(resolved.interpreter_config.parse_argv, Some(false));
+        assert_eq!(
+            resolved.interpreter_config.run_module,
+            Some("myapp".to_string())
+        );
+        assert_eq!(resolved.run_mode, PythonRunMode::Repl);
+
+        // Invoking the shim parses arguments like python.
+        config.exe = Some(shim.clone());
+        let resolved = config.clone().resolve()?;
+        assert_eq!(resolved.interpreter_config.parse_argv, Some(true));
+        assert_eq!(resolved.interpreter_config.run_module, None);
+        assert_eq!(resolved.run_mode, PythonRunMode::Default);
+
+        // But only if the shim mode is active.
+        config.sys_executable_mode = SysExecutableMode::App;
+        let resolved = config.resolve()?;
+        assert_eq!(
+            resolved.interpreter_config.run_module,
+            Some("myapp".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sys_executable_invoked() -> Result<()> {
+        let temp_dir = tempfile::Builder::new().prefix("pyembed-test").tempdir()?;
+        let temp_dir = dunce::canonicalize(temp_dir.path())?;
+        let exe = temp_dir.join("python3.9");
+        let link = temp_dir.join("venv").join("bin").join("python");
+        let other = temp_dir.join("other");
+        std::fs::write(&exe, b"python")?;
+        std::fs::write(&other, b"other")?;
+        std::fs::create_dir_all(link.parent().unwrap())?;
+        std::os::unix::fs::symlink(&exe, &link)?;
+
+        let mut config = OxidizedPythonInterpreterConfig::default();
+        config.exe = Some(exe.clone());
+        config.argv = Some(vec![link.clone().into_os_string()]);
+
+        // Without the mode, the executable is left for Python to resolve.
+        let resolved = config.clone().resolve()?;
+        assert_eq!(resolved.interpreter_config.executable, None);
+
+        // Symbolic links aren't resolved.
+        config.sys_executable_mode = SysExecutableMode::Invoked;
+        let resolved = config.clone().resolve()?;
+        assert_eq!(resolved.interpreter_config.executable, Some(link));
+
+        // argv[0] not referring to the executable is ignored.
+        config.argv = Some(vec![other.into_os_string()]);
+        let resolved = config.clone().resolve()?;
+        assert_eq!(resolved.interpreter_config.executable, Some(exe));
+
+        // An explicit executable has precedence.
+        config.interpreter_config.executable = Some(PathBuf::from("/usr/bin/python3"));
+        let resolved = config.resolve()?;
+        assert_eq!(
+            resolved.interpreter_config.executable,
+            Some(PathBuf::from("/usr/bin/python3"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repl_history_path() -> Result<()> {
+        let mut config = OxidizedPythonInterpreterConfig::default();
+        config.origin = Some(PathBuf::from("/other/origin"));
+
+        assert_eq!(config.clone().resolve()?.repl_history_path, None);
+
+        config.repl_history_path = Some(PathBuf::from("$ORIGIN/history"));
+        assert_eq!(
+            config.resolve()?.repl_history_path,
+            Some(PathBuf::from("/other/origin/history"))
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_packed_resources_implicit_origin() -> Result<()> {
         let mut config = OxidizedPythonInterpreterConfig::default();
@@ -483,4 +874,54 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_filesystem_resources_root() -> Result<()> {
+        let temp_dir = tempfile::Builder::new().prefix("pyembed-test").tempdir()?;
+        let origin = temp_dir.path().join("app");
+        let moved = temp_dir.path().join("shared");
+        std::fs::create_dir_all(origin.join("lib"))?;
+        std::fs::create_dir_all(&moved)?;
+
+        let mut config = OxidizedPythonInterpreterConfig::default();
+        config.origin = Some(origin.clone());
+
+        // Defaults to the origin.
+        let resolved = config.clone().resolve()?;
+        assert_eq!(resolved.filesystem_resources_root(), origin.as_path());
+
+        // An explicit root must carry the marker file.
+        config.filesystem_resources_root = Some(PathBuf::from("$ORIGIN/lib"));
+        let err = config.clone().resolve().err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("does not contain a .pyoxidizer-resources-root file"));
+
+        std::fs::write(
+            origin.join("lib").join(FILESYSTEM_RESOURCES_ROOT_MARKER),
+            b"",
+        )?;
+        let resolved = config.clone().resolve()?;
+        assert_eq!(
+            resolved.filesystem_resources_root(),
+            origin.join("lib").as_path()
+        );
+
+        // The environment variable overrides the configured root.
+        std::fs::write(moved.join(FILESYSTEM_RESOURCES_ROOT_MARKER), b"")?;
+        config.resources_root_env = Some("PYEMBED_TEST_RESOURCES_ROOT".to_string());
+        std::env::set_var("PYEMBED_TEST_RESOURCES_ROOT", &moved);
+        let resolved = config.clone().resolve();
+        std::env::remove_var("PYEMBED_TEST_RESOURCES_ROOT");
+        assert_eq!(resolved?.filesystem_resources_root(), moved.as_path());
+
+        // Unset, the configured root applies again.
+        let resolved = config.resolve()?;
+        assert_eq!(
+            resolved.filesystem_resources_root(),
+            origin.join("lib").as_path()
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,212 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Decryption of encrypted packed resources payloads.
+
+Decryption support requires the `encrypted-resources` feature. Without it,
+encountering an encrypted payload results in an error.
+*/
+
+use {
+    python_packed_resources::{data::ResourceField, encryption::is_encrypted_payload},
+    std::{fmt, ops::Deref},
+};
+
+#[cfg(feature = "encrypted-resources")]
+use {
+    once_cell::sync::OnceCell,
+    python_packed_resources::encryption::{decrypted_payload_length, ResourcesDecryptor},
+    zeroize::Zeroize,
+};
+
+/// Function providing the secret key used to decrypt resources payloads.
+///
+/// The function must return the 32 byte X25519 secret key corresponding to
+/// the public key resources were encrypted to. Errors are surfaced as an
+/// `ImportError` when an encrypted module is loaded.
+pub type ResourcesKeyProvider = fn() -> Result<Vec<u8>, String>;
+
+/// Holds state for decrypting resources payloads.
+///
+/// The key provider is called lazily, the first time an encrypted payload
+/// is encountered.
+#[derive(Default)]
+pub(crate) struct ResourcesDecryption {
+    key_provider: Option<ResourcesKeyProvider>,
+
+    #[cfg(feature = "encrypted-resources")]
+    decryptor: OnceCell<Result<ResourcesDecryptor, String>>,
+}
+
+impl fmt::Debug for ResourcesDecryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Avoid exposing any key material.
+        f.debug_struct("ResourcesDecryption")
+            .field("key_provider", &self.key_provider.is_some())
+            .finish()
+    }
+}
+
+/// Resolved data for a resources payload.
+pub(crate) enum PayloadData<'a> {
+    /// Payload was not encrypted and references the original data.
+    Plaintext(&'a [u8]),
+
+    /// Payload was decrypted.
+    #[cfg(feature = "encrypted-resources")]
+    Decrypted(LockedBuffer),
+}
+
+impl<'a> Deref for PayloadData<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Plaintext(data) => data,
+            #[cfg(feature = "encrypted-resources")]
+            Self::Decrypted(data) => &data.data,
+        }
+    }
+}
+
+/// Human readable name of an encrypted field.
+fn field_name(field: ResourceField) -> &'static str {
+    match field {
+        ResourceField::InMemorySource => "source",
+        ResourceField::InMemoryBytecode => "bytecode",
+        ResourceField::InMemoryBytecodeOpt1 => "opt1 bytecode",
+        ResourceField::InMemoryBytecodeOpt2 => "opt2 bytecode",
+        _ => "data",
+    }
+}
+
+impl ResourcesDecryption {
+    pub fn new(key_provider: Option<ResourcesKeyProvider>) -> Self {
+        Self {
+            key_provider,
+            #[cfg(feature = "encrypted-resources")]
+            decryptor: OnceCell::new(),
+        }
+    }
+
+    /// Resolve the plaintext of a payload belonging to a named resource field.
+    ///
+    /// Unencrypted payloads are returned as-is. Error messages name the
+    /// resource but never contain payload data.
+    pub fn resolve<'a>(
+        &self,
+        name: &str,
+        field: ResourceField,
+        data: &'a [u8],
+    ) -> Result<PayloadData<'a>, String> {
+        if !is_encrypted_payload(data) {
+            return Ok(PayloadData::Plaintext(data));
+        }
+
+        self.decrypt(name, field, data)
+            .map_err(|e| format!("unable to decrypt {} of {}: {}", field_name(field), name, e))
+    }
+
+    #[cfg(not(feature = "encrypted-resources"))]
+    fn decrypt<'a>(
+        &self,
+        _name: &str,
+        _field: ResourceField,
+        _data: &'a [u8],
+    ) -> Result<PayloadData<'a>, String> {
+        Err("pyembed was built without the encrypted-resources feature".to_string())
+    }
+
+    #[cfg(feature = "encrypted-resources")]
+    fn decrypt<'a>(
+        &self,
+        name: &str,
+        field: ResourceField,
+        data: &'a [u8],
+    ) -> Result<PayloadData<'a>, String> {
+        let decryptor = self
+            .decryptor
+            .get_or_init(|| {
+                let provider = self
+                    .key_provider
+                    .ok_or_else(|| "no resources key provider is registered".to_string())?;
+
+                let mut key = provider()?;
+                let decryptor = ResourcesDecryptor::new(&key).map_err(|e| e.to_string());
+                key.zeroize();
+
+                decryptor
+            })
+            .as_ref()
+            .map_err(|e| e.clone())?;
+
+        let len = decrypted_payload_length(data).ok_or("malformed encrypted payload")?;
+        let mut buffer = LockedBuffer::new(len);
+
+        decryptor.decrypt_payload(name, field, data, &mut buffer.data)?;
+
+        Ok(PayloadData::Decrypted(buffer))
+    }
+}
+
+/// A heap buffer that is locked in memory and zeroed on drop.
+///
+/// Locking prevents decrypted data from being paged to swap. Locking is
+/// best effort: if the operating system refuses to lock the memory (e.g.
+/// due to `RLIMIT_MEMLOCK`), the buffer is still usable.
+///
+/// This only protects the plaintext while pyembed holds it. Python needs
+/// the data as a `bytes` object, so callers copy it into memory managed by
+/// Python, which is neither locked nor zeroed when freed.
+#[cfg(feature = "encrypted-resources")]
+pub(crate) struct LockedBuffer {
+    data: Vec<u8>,
+    locked: bool,
+}
+
+#[cfg(feature = "encrypted-resources")]
+impl LockedBuffer {
+    fn new(len: usize) -> Self {
+        let data = vec![0u8; len];
+        let locked = !data.is_empty() && lock_memory(&data);
+
+        Self { data, locked }
+    }
+}
+
+#[cfg(feature = "encrypted-resources")]
+impl Drop for LockedBuffer {
+    fn drop(&mut self) {
+        self.data.as_mut_slice().zeroize();
+
+        if self.locked {
+            unlock_memory(&self.data);
+        }
+    }
+}
+
+#[cfg(all(feature = "encrypted-resources", unix))]
+fn lock_memory(data: &[u8]) -> bool {
+    unsafe { libc::mlock(data.as_ptr() as *const _, data.len()) == 0 }
+}
+
+#[cfg(all(feature = "encrypted-resources", unix))]
+fn unlock_memory(data: &[u8]) {
+    unsafe {
+        libc::munlock(data.as_ptr() as *const _, data.len());
+    }
+}
+
+#[cfg(all(feature = "encrypted-resources", windows))]
+fn lock_memory(data: &[u8]) -> bool {
+    unsafe { winapi::um::memoryapi::VirtualLock(data.as_ptr() as *mut _, data.len()) != 0 }
+}
+
+#[cfg(all(feature = "encrypted-resources", windows))]
+fn unlock_memory(data: &[u8]) {
+    unsafe {
+        winapi::um::memoryapi::VirtualUnlock(data.as_ptr() as *mut _, data.len());
+    }
+}
@@ -15,7 +15,7 @@ use {
 ///
 /// This is meant to be called during interpreter initialization. We can't
 /// call PyErr_Print() because sys.stdout may not be available yet.
-fn format_pyerr(py: Python, err: PyErr) -> Result<String, &'static str> {
+pub(crate) fn format_pyerr(py: Python, err: PyErr) -> Result<String, &'static str> {
     let type_repr = err
         .ptype
         .repr(py)
@@ -43,6 +43,8 @@ fn format_pyerr(py: Python, err: PyErr) -> Result<String, &'static str> {
 pub enum NewInterpreterError {
     Simple(&'static str),
     Dynamic(String),
+    /// Resources could not be loaded or don't match the interpreter.
+    Resources(String),
 }
 
 impl From<&'static str> for NewInterpreterError {
@@ -56,6 +58,7 @@ impl Display for NewInterpreterError {
         match &self {
             NewInterpreterError::Simple(value) => value.fmt(f),
             NewInterpreterError::Dynamic(value) => value.fmt(f),
+            NewInterpreterError::Resources(value) => write!(f, "invalid resources: {}", value),
         }
     }
 }
@@ -63,6 +66,17 @@ impl Display for NewInterpreterError {
 impl std::error::Error for NewInterpreterError {}
 
 impl NewInterpreterError {
+    /// Obtain the process exit code for this error.
+    ///
+    /// `base` is returned for most errors. Errors loading resources return
+    /// `base + 1`.
+    pub fn exit_code(&self, base: i32) -> i32 {
+        match self {
+            NewInterpreterError::Resources(_) => base + 1,
+            _ => base,
+        }
+    }
+
     pub fn new_from_pyerr(py: Python, err: PyErr, context: &str) -> Self {
         match format_pyerr(py, err) {
             Ok(value) => NewInterpreterError::Dynamic(format!("during {}: {}", context, value)),
@@ -0,0 +1,53 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Reporting of codecs excluded at build time.
+
+When `encodings` submodules are excluded, looking up their codecs fails with
+an unhelpful `unknown encoding` error. The codec search function registered
+here explains why the codec is missing.
+*/
+
+use cpython::{PyDict, PyResult, Python};
+
+/// Python code registering the codec search function.
+///
+/// It is run with `excluded` defined as a list of `encodings` submodule names.
+/// The function is registered after the `encodings` search function, so it
+/// is only consulted for codecs that couldn't be found.
+const REGISTER_SOURCE: &str = r#"
+import codecs
+import encodings
+from encodings.aliases import aliases
+
+def register(excluded):
+    excluded = frozenset(excluded)
+
+    def search_function(encoding):
+        norm = encodings.normalize_encoding(encoding).lower()
+
+        for name in (aliases.get(norm), aliases.get(norm.replace(".", "_")), norm):
+            if name in excluded:
+                raise LookupError(
+                    "unknown encoding: %s (the encodings.%s module was excluded "
+                    "when this application was built; see "
+                    "PythonPackagingPolicy.minimal_encodings())" % (encoding, name)
+                )
+
+        return None
+
+    codecs.register(search_function)
+
+register(excluded)
+"#;
+
+/// Register a codec search function reporting excluded `encodings` submodules.
+pub(crate) fn register_excluded_encodings(py: Python, excluded: &[String]) -> PyResult<()> {
+    let globals = PyDict::new(py);
+    globals.set_item(py, "__builtins__", py.import("builtins")?)?;
+    globals.set_item(py, "excluded", excluded)?;
+
+    py.run(REGISTER_SOURCE, Some(&globals), None)
+}
@@ -0,0 +1,415 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Run Python work on a pool of threads without blocking the caller.
+
+Hosts using an async runtime like tokio shouldn't block runtime threads
+waiting on the GIL or on Python code. [PythonExecutor] owns a small pool of
+threads that acquire the GIL and run submitted closures. Each submission
+returns a [PyTask], which is a [Future] that can be `.await`ed or waited on
+synchronously.
+
+The executor doesn't own the interpreter. `MainPythonInterpreter` shuts its
+executor down before finalizing the interpreter.
+*/
+
+use {
+    cpython::{GILGuard, Python},
+    std::{
+        fmt::{Display, Formatter},
+        future::Future,
+        panic::{catch_unwind, AssertUnwindSafe},
+        pin::Pin,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc::{channel, Receiver, Sender},
+            Arc, Condvar, Mutex,
+        },
+        task::{Context, Poll, Waker},
+        thread::JoinHandle,
+    },
+};
+
+/// Default number of threads in a [PythonExecutor].
+pub const DEFAULT_EXECUTOR_THREADS: usize = 4;
+
+/// Represents an error from work submitted to a [PythonExecutor].
+#[derive(Clone, Debug, PartialEq)]
+pub enum PyTaskError {
+    /// The Python code raised an exception.
+    Python(String),
+
+    /// The submitted closure panicked.
+    Panic(String),
+
+    /// The executor shut down before the work ran.
+    Shutdown,
+
+    /// Arguments or a return value couldn't be converted.
+    Conversion(String),
+}
+
+impl Display for PyTaskError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Python(msg) => write!(f, "Python error: {}", msg),
+            Self::Panic(msg) => write!(f, "panic running Python work: {}", msg),
+            Self::Shutdown => f.write_str("Python executor shut down before work ran"),
+            Self::Conversion(msg) => write!(f, "conversion error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PyTaskError {}
+
+struct TaskState<T> {
+    result: Option<Result<T, PyTaskError>>,
+    waker: Option<Waker>,
+}
+
+struct TaskShared<T> {
+    state: Mutex<TaskState<T>>,
+    ready: Condvar,
+}
+
+/// Completes a [PyTask].
+///
+/// If dropped without completing, the task resolves to
+/// [PyTaskError::Shutdown].
+struct TaskCompleter<T> {
+    shared: Arc<TaskShared<T>>,
+    completed: bool,
+}
+
+impl<T> TaskCompleter<T> {
+    fn complete(mut self, result: Result<T, PyTaskError>) {
+        self.set(result);
+        self.completed = true;
+    }
+
+    fn set(&self, result: Result<T, PyTaskError>) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.shared.ready.notify_all();
+    }
+}
+
+impl<T> Drop for TaskCompleter<T> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.set(Err(PyTaskError::Shutdown));
+        }
+    }
+}
+
+/// The pending result of work submitted to a [PythonExecutor].
+///
+/// Resolve it by `.await`ing it from async code or by calling
+/// [PyTask::wait] from synchronous code.
+pub struct PyTask<T> {
+    shared: Arc<TaskShared<T>>,
+}
+
+fn new_task<T>() -> (PyTask<T>, TaskCompleter<T>) {
+    let shared = Arc::new(TaskShared {
+        state: Mutex::new(TaskState {
+            result: None,
+            waker: None,
+        }),
+        ready: Condvar::new(),
+    });
+
+    (
+        PyTask {
+            shared: shared.clone(),
+        },
+        TaskCompleter {
+            shared,
+            completed: false,
+        },
+    )
+}
+
+impl<T> PyTask<T> {
+    /// Block the current thread until the work completes.
+    pub fn wait(self) -> Result<T, PyTaskError> {
+        let mut state = self.shared.state.lock().unwrap();
+
+        loop {
+            if let Some(result) = state.result.take() {
+                return result;
+            }
+
+            state = self.shared.ready.wait(state).unwrap();
+        }
+    }
+}
+
+impl<T> Future for PyTask<T> {
+    type Output = Result<T, PyTaskError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock().unwrap();
+
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A pool of threads running closures while holding the GIL.
+///
+/// The interpreter must be initialized and the GIL must not be held by
+/// another thread indefinitely, or submitted work won't run.
+pub struct PythonExecutor {
+    sender: Option<Mutex<Sender<Job>>>,
+    shutdown: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl PythonExecutor {
+    /// Start an executor with the given number of threads.
+    pub fn new(threads: usize) -> Self {
+        let (sender, receiver) = channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let workers = (0..threads.max(1))
+            .map(|i| {
+                let receiver = receiver.clone();
+                let shutdown = shutdown.clone();
+
+                std::thread::Builder::new()
+                    .name(format!("pyembed-executor-{}", i))
+                    .spawn(move || worker(receiver, shutdown))
+                    .expect("failed to spawn Python executor thread")
+            })
+            .collect();
+
+        Self {
+            sender: Some(Mutex::new(sender)),
+            shutdown,
+            workers,
+        }
+    }
+
+    /// Run a closure on an executor thread while holding the GIL.
+    ///
+    /// Panics in the closure are caught and reported as
+    /// [PyTaskError::Panic]. The executor thread remains usable.
+    pub fn spawn<F, T>(&self, f: F) -> PyTask<T>
+    where
+        F: FnOnce(Python) -> Result<T, PyTaskError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (task, completer) = new_task();
+
+        let job: Job = Box::new(move || {
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                let gil = GILGuard::acquire();
+                f(gil.python())
+            }));
+
+            completer.complete(match result {
+                Ok(result) => result,
+                Err(payload) => Err(PyTaskError::Panic(panic_message(payload))),
+            });
+        });
+
+        // If sending fails the job is dropped, which resolves the task as shut down.
+        if let Some(sender) = &self.sender {
+            let _ = sender.lock().unwrap().send(job);
+        }
+
+        task
+    }
+
+    /// Stop the executor.
+    ///
+    /// Work already running is allowed to finish. Queued work doesn't run
+    /// and resolves to [PyTaskError::Shutdown]. The calling thread must not
+    /// hold the GIL, or running work can't finish.
+    pub fn shutdown(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.sender.take();
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for PythonExecutor {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn worker(receiver: Arc<Mutex<Receiver<Job>>>, shutdown: Arc<AtomicBool>) {
+    loop {
+        let job = match receiver.lock().unwrap().recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+
+        // Dropping the job resolves its task as shut down.
+        if shutdown.load(Ordering::SeqCst) {
+            drop(job);
+            continue;
+        }
+
+        job();
+    }
+}
+
+#[cfg(feature = "serialization")]
+mod serde_call {
+    use {
+        super::PyTaskError,
+        crate::error::format_pyerr,
+        cpython::{
+            ObjectProtocol, PyDict, PyErr, PyObject, PyString, PyTuple, Python, PythonObject,
+        },
+    };
+
+    fn python_error(py: Python, err: PyErr) -> PyTaskError {
+        PyTaskError::Python(format_pyerr(py, err).unwrap_or_else(|e| e.to_string()))
+    }
+
+    /// Resolve a callable from a dotted name like `package.module.function`.
+    ///
+    /// The longest importable module prefix is imported and remaining
+    /// components are resolved as attributes.
+    fn resolve_callable(py: Python, name: &str) -> Result<PyObject, PyTaskError> {
+        let parts = name.split('.').collect::<Vec<_>>();
+
+        for split in (1..parts.len()).rev() {
+            let module = parts[0..split].join(".");
+
+            let mut value = match py.import(&module) {
+                Ok(module) => module.into_object(),
+                Err(_) => continue,
+            };
+
+            for attr in &parts[split..] {
+                value = value.getattr(py, *attr).map_err(|e| python_error(py, e))?;
+            }
+
+            return Ok(value);
+        }
+
+        Err(PyTaskError::Python(format!(
+            "unable to resolve {}: no importable module",
+            name
+        )))
+    }
+
+    /// Call the Python function `name` with JSON encoded arguments.
+    ///
+    /// A JSON array is passed as positional arguments, an object as keyword
+    /// arguments, and anything else as a single positional argument. The
+    /// return value is JSON encoded.
+    pub(super) fn call_json(py: Python, name: &str, args: &str) -> Result<String, PyTaskError> {
+        let json = py.import("json").map_err(|e| python_error(py, e))?;
+        let callable = resolve_callable(py, name)?;
+
+        let args = json
+            .call(py, "loads", (PyString::new(py, args),), None)
+            .map_err(|e| python_error(py, e))?;
+
+        let result = if let Ok(kwargs) = args.cast_as::<PyDict>(py) {
+            callable.call(py, PyTuple::empty(py), Some(kwargs))
+        } else if args.get_type(py).name(py) == "list" {
+            let args = PyTuple::new(
+                py,
+                &args
+                    .iter(py)
+                    .map_err(|e| python_error(py, e))?
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| python_error(py, e))?,
+            );
+            callable.call(py, args, None)
+        } else {
+            callable.call(py, (args,), None)
+        }
+        .map_err(|e| python_error(py, e))?;
+
+        json.call(py, "dumps", (result,), None)
+            .and_then(|s| s.extract::<String>(py))
+            .map_err(|e| python_error(py, e))
+    }
+}
+
+#[cfg(feature = "serialization")]
+impl PythonExecutor {
+    /// Call a Python function by its dotted name with serializable arguments.
+    ///
+    /// Arguments are converted to Python values via JSON. A sequence (e.g. a
+    /// tuple or `Vec`) is passed as positional arguments, a map or struct as
+    /// keyword arguments, and anything else as a single argument. The
+    /// function's return value is converted back via JSON.
+    pub fn call<A, R>(&self, name: &str, args: &A) -> PyTask<R>
+    where
+        A: serde::Serialize,
+        R: serde::de::DeserializeOwned + Send + 'static,
+    {
+        let name = name.to_string();
+        let args = serde_json::to_string(args);
+
+        self.spawn(move |py| {
+            let args = args.map_err(|e| PyTaskError::Conversion(e.to_string()))?;
+            let result = serde_call::call_json(py, &name, &args)?;
+
+            serde_json::from_str(&result).map_err(|e| PyTaskError::Conversion(e.to_string()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_completion() {
+        let (task, completer) = new_task();
+        completer.complete(Ok(42));
+        assert_eq!(task.wait(), Ok(42));
+
+        let (task, completer) = new_task::<()>();
+        drop(completer);
+        assert_eq!(task.wait(), Err(PyTaskError::Shutdown));
+    }
+
+    #[test]
+    fn test_task_wait_across_threads() {
+        let (task, completer) = new_task();
+
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            completer.complete(Ok("done"));
+        });
+
+        assert_eq!(task.wait(), Ok("done"));
+        handle.join().unwrap();
+    }
+}
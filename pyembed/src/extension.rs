@@ -6,7 +6,10 @@
 
 use {
     crate::{
-        importer::{OxidizedFinder, OxidizedPathEntryFinder, OxidizedResourceReader},
+        importer::{
+            entry_points, pyoxr_path_hook, OxidizedFinder, OxidizedPathEntryFinder,
+            OxidizedResourceReader,
+        },
         pkg_resources::{
             pkg_resources_find_distributions, register_pkg_resources_with_module,
             OxidizedPkgResourcesProvider,
@@ -170,11 +173,17 @@ fn module_init(py: Python, m: &PyModule) -> PyResult<()> {
             decode_source(io_module: &PyModule, source_bytes: PyObject)
         ),
     )?;
+    m.add(py, "entry_points", py_fn!(py, entry_points(group: &str)))?;
     m.add(
         py,
         "find_resources_in_path",
         py_fn!(py, find_resources_in_path(path: PyObject)),
     )?;
+    m.add(
+        py,
+        "pyoxr_path_hook",
+        py_fn!(py, pyoxr_path_hook(path: PyObject)),
+    )?;
     m.add(
         py,
         "register_pkg_resources",
@@ -246,5 +255,15 @@ fn module_init(py: Python, m: &PyModule) -> PyResult<()> {
         py.get_type::<crate::python_resource_types::PythonExtensionModule>(),
     )?;
 
+    // When loaded into a stock Python interpreter, make packed resources files
+    // on `sys.path` importable, like zip files.
+    #[cfg(library_mode = "extension")]
+    sys_module.get(py, "path_hooks")?.call_method(
+        py,
+        "insert",
+        (0, m.get(py, "pyoxr_path_hook")?),
+        None,
+    )?;
+
     Ok(())
 }
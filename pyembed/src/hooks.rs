@@ -0,0 +1,137 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Callbacks run at points in the interpreter lifecycle.
+
+use {
+    crate::{config::OxidizedPythonInterpreterConfig, error::NewInterpreterError},
+    cpython::{py_fn, NoArgs, ObjectProtocol, PyObject, PyResult, Python},
+    once_cell::sync::Lazy,
+    std::sync::Mutex,
+};
+
+/// Callback run before the interpreter is initialized.
+pub type PreInitHook<'a> =
+    Box<dyn FnOnce(&mut OxidizedPythonInterpreterConfig<'a>) -> Result<(), String> + 'a>;
+
+/// Callback run after the interpreter is initialized.
+pub type PostInitHook<'a> = Box<dyn FnOnce(Python) -> PyResult<()> + 'a>;
+
+/// Callback run before the interpreter is finalized.
+pub type PreFinalizeHook = Box<dyn FnOnce(Python) + Send>;
+
+/// Callbacks for the Rust host to run at points in the interpreter lifecycle.
+///
+/// Hooks are passed to [crate::MainPythonInterpreter::new_with_hooks()].
+#[derive(Default)]
+pub struct InterpreterHooks<'a> {
+    /// Called before the interpreter is initialized.
+    ///
+    /// Receives the config so it can be modified. An error aborts
+    /// interpreter creation.
+    pub pre_init: Option<PreInitHook<'a>>,
+
+    /// Called after the interpreter is initialized and before anything is run.
+    ///
+    /// This is suitable for injecting objects into `builtins`. An error aborts
+    /// interpreter creation.
+    pub post_init: Option<PostInitHook<'a>>,
+
+    /// Called before the interpreter is finalized.
+    ///
+    /// This runs as an `atexit` handler, so it also runs when the interpreter
+    /// is finalized by `Py_RunMain()`. It runs after `atexit` handlers
+    /// registered by Python code.
+    pub pre_finalize: Option<PreFinalizeHook>,
+}
+
+static PRE_FINALIZE_HOOK: Lazy<Mutex<Option<PreFinalizeHook>>> = Lazy::new(|| Mutex::new(None));
+
+fn run_pre_finalize_hook(py: Python) -> PyResult<PyObject> {
+    let hook = PRE_FINALIZE_HOOK.lock().unwrap().take();
+
+    if let Some(hook) = hook {
+        hook(py);
+    }
+
+    Ok(py.None())
+}
+
+/// Resolve a Python callable from a dotted name like `package.module.function`.
+fn resolve_callable(py: Python, name: &str) -> Result<PyObject, NewInterpreterError> {
+    let context = format!("resolving callable {}", name);
+
+    let mut parts = name.rsplitn(2, '.');
+
+    let (module, attr) = match (parts.next(), parts.next()) {
+        (Some(attr), Some(module)) => (module, attr),
+        _ => {
+            return Err(NewInterpreterError::Dynamic(format!(
+                "during {}: name must be of the form <module>.<attribute>",
+                context
+            )))
+        }
+    };
+
+    py.import(module)
+        .and_then(|m| m.get(py, attr))
+        .map_err(|e| NewInterpreterError::new_from_pyerr(py, e, &context))
+}
+
+/// Run the post-init hook and the named post-init callable, in that order.
+pub(crate) fn run_post_init(
+    py: Python,
+    hook: Option<PostInitHook>,
+    callable: Option<&str>,
+) -> Result<(), NewInterpreterError> {
+    if let Some(hook) = hook {
+        hook(py).map_err(|e| NewInterpreterError::new_from_pyerr(py, e, "post-init hook"))?;
+    }
+
+    if let Some(name) = callable {
+        resolve_callable(py, name)?
+            .call(py, NoArgs, None)
+            .map_err(|e| {
+                NewInterpreterError::new_from_pyerr(py, e, &format!("calling {}", name))
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Register the pre-finalize hook and the named pre-finalize callable with `atexit`.
+///
+/// `atexit` runs handlers in reverse order of registration, so the named
+/// callable runs before the hook.
+pub(crate) fn register_pre_finalize(
+    py: Python,
+    hook: Option<PreFinalizeHook>,
+    callable: Option<&str>,
+) -> Result<(), NewInterpreterError> {
+    let atexit = py
+        .import("atexit")
+        .map_err(|e| NewInterpreterError::new_from_pyerr(py, e, "importing atexit"))?;
+
+    if let Some(hook) = hook {
+        PRE_FINALIZE_HOOK.lock().unwrap().replace(hook);
+
+        atexit
+            .call(py, "register", (py_fn!(py, run_pre_finalize_hook()),), None)
+            .map_err(|e| {
+                NewInterpreterError::new_from_pyerr(py, e, "registering pre-finalize hook")
+            })?;
+    }
+
+    if let Some(name) = callable {
+        let callable = resolve_callable(py, name)?;
+
+        atexit
+            .call(py, "register", (callable,), None)
+            .map_err(|e| {
+                NewInterpreterError::new_from_pyerr(py, e, &format!("registering {}", name))
+            })?;
+    }
+
+    Ok(())
+}
@@ -24,16 +24,17 @@ use {
             name_at_package_hierarchy, pyobject_to_resource, resource_to_pyobject, ModuleFlavor,
             OptimizeLevel, OxidizedResource, PythonResourcesState,
         },
+        resources_filesystem_shim::install_resources_filesystem_shim,
     },
     cpython::{
-        exc::{FileNotFoundError, ImportError, ValueError},
+        exc::{FileNotFoundError, ImportError, NotADirectoryError, ValueError},
         {
             py_class, NoArgs, ObjectProtocol, PyBytes, PyCapsule, PyClone, PyDict, PyErr, PyList,
             PyModule, PyObject, PyResult, PyString, PyTuple, Python, PythonObject, ToPyObject,
         },
     },
     python3_sys as pyffi,
-    std::sync::Arc,
+    std::{path::PathBuf, sync::Arc},
 };
 
 #[cfg(windows)]
@@ -533,6 +534,10 @@ py_class!(pub(crate) class OxidizedFinder |py| {
         self.find_distributions_impl(py, context)
     }
 
+    def entry_points(&self, group: &str) -> PyResult<PyObject> {
+        self.entry_points_impl(py, group)
+    }
+
     // pkgutil methods.
     def iter_modules(&self, prefix: Option<PyString> = None) -> PyResult<PyObject> {
         self.iter_modules_impl(py, prefix)
@@ -591,6 +596,11 @@ py_class!(pub(crate) class OxidizedFinder |py| {
     def serialize_indexed_resources(&self, ignore_builtin: bool = true, ignore_frozen: bool = true) -> PyResult<PyObject> {
         self.serialize_indexed_resources_impl(py, ignore_builtin, ignore_frozen)
     }
+
+    def install_resources_filesystem_shim(&self) -> PyResult<PyObject> {
+        install_resources_filesystem_shim(py, self.state(py))?;
+        Ok(py.None())
+    }
 });
 
 // importlib.abc.MetaPathFinder interface.
@@ -605,6 +615,17 @@ impl OxidizedFinder {
         let state = self.state(py);
         let key = fullname.to_string(py)?;
 
+        // Aliases are loaded by `create_module()`, which imports the target.
+        if state
+            .get_resources_state()
+            .resolve_module_alias(&key)
+            .is_some()
+        {
+            return state
+                .module_spec_type
+                .call(py, (fullname, self.as_object()), None);
+        }
+
         let module = match state
             .get_resources_state()
             .resolve_importable_module(&key, state.optimize_level)
@@ -664,6 +685,22 @@ impl OxidizedFinder {
         let name = spec.getattr(py, "name")?;
         let key = name.extract::<String>(py)?;
 
+        // The module object of an alias is the module object of its target,
+        // so both names share an identity in `sys.modules`. Module creation
+        // replaces the target's `__spec__` with the alias spec. So we stash
+        // the original spec on the alias spec for `exec_module()` to restore.
+        if let Some(target) = state.get_resources_state().resolve_module_alias(&key) {
+            let target = target.to_string();
+            let module = py.import(&target)?;
+            spec.setattr(
+                py,
+                "loader_state",
+                module.as_object().getattr(py, "__spec__")?,
+            )?;
+
+            return Ok(module.into_object());
+        }
+
         let module = match state
             .get_resources_state()
             .resolve_importable_module(&key, state.optimize_level)
@@ -709,6 +746,23 @@ impl OxidizedFinder {
 
     fn exec_module_impl(&self, py: Python, module: &PyObject) -> PyResult<PyObject> {
         let state = self.state(py);
+
+        // The module of an alias was executed when its target was imported.
+        let spec = module.getattr(py, "__spec__")?;
+        if spec != py.None() {
+            let spec_name = spec.getattr(py, "name")?.extract::<String>(py)?;
+
+            if state
+                .get_resources_state()
+                .resolve_module_alias(&spec_name)
+                .is_some()
+            {
+                module.setattr(py, "__spec__", spec.getattr(py, "loader_state")?)?;
+
+                return Ok(py.None());
+            }
+        }
+
         let name = module.getattr(py, "__name__")?;
         let key = name.extract::<String>(py)?;
 
@@ -933,6 +987,59 @@ impl OxidizedFinder {
 
         crate::package_metadata::find_distributions(py, state.clone(), name, path)
     }
+
+    /// def entry_points(group)
+    ///
+    /// Return the entry points in `group` as a list of
+    /// `(name, value, distribution)` tuples.
+    ///
+    /// Entry points are read from an index computed when resources were
+    /// packaged, so distribution metadata isn't read.
+    fn entry_points_impl(&self, py: Python, group: &str) -> PyResult<PyObject> {
+        let entry_points = self
+            .state(py)
+            .get_resources_state()
+            .entry_points(group)
+            .unwrap_or(&[]);
+
+        Ok(entry_points
+            .iter()
+            .map(|ep| {
+                (
+                    ep.name.as_ref(),
+                    ep.value.as_ref(),
+                    ep.distribution.as_ref(),
+                )
+            })
+            .collect::<Vec<_>>()
+            .to_py_object(py)
+            .into_object())
+    }
+}
+
+/// oxidized_importer.entry_points(group)
+///
+/// Return the entry points in `group` indexed by the `OxidizedFinder`
+/// instances on `sys.meta_path`, in `sys.meta_path` order.
+pub(crate) fn entry_points(py: Python, group: &str) -> PyResult<PyObject> {
+    let meta_path = py.import("sys")?.get(py, "meta_path")?;
+
+    let res = PyList::new(py, &[]);
+
+    for finder in meta_path.iter(py)? {
+        let finder = finder?;
+
+        if let Ok(finder) = finder.cast_as::<OxidizedFinder>(py) {
+            res.as_object().call_method(
+                py,
+                "extend",
+                (finder.entry_points_impl(py, group)?,),
+                None,
+            )?;
+        }
+    }
+
+    Ok(res.into_object())
 }
 
 // pkgutil support.
@@ -1166,6 +1273,78 @@ fn oxidized_finder_new(
     Ok(importer)
 }
 
+/// Filename extension of packed resources files serviced by [pyoxr_path_hook].
+pub(crate) const PACKED_RESOURCES_EXTENSION: &str = ".pyoxr";
+
+/// Resolve the packed resources file a `sys.path` entry refers to.
+///
+/// Entries are either the path to a `.pyoxr` file or a virtual
+/// sub-directory beneath one, e.g. `/path/to/app.pyoxr/package`.
+fn packed_resources_path(path: &str) -> Option<PathBuf> {
+    let mut search_start = 0;
+
+    while let Some(index) = path[search_start..].find(PACKED_RESOURCES_EXTENSION) {
+        let end = search_start + index + PACKED_RESOURCES_EXTENSION.len();
+
+        if matches!(path[end..].chars().next(), None | Some('/') | Some('\\')) {
+            let candidate = PathBuf::from(&path[..end]);
+
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+
+        search_start = end;
+    }
+
+    None
+}
+
+/// oxidized_importer.pyoxr_path_hook(path)
+///
+/// A `sys.path_hooks` entry servicing packed resources files, like
+/// `zipimport` services zip files. Raises `ImportError` for paths that
+/// don't refer to a `.pyoxr` file.
+pub(crate) fn pyoxr_path_hook(py: Python, path: PyObject) -> PyResult<OxidizedPathEntryFinder> {
+    let archive = match path.cast_as::<PyString>(py) {
+        Ok(s) => packed_resources_path(&s.to_string(py)?),
+        Err(_) => None,
+    }
+    .ok_or_else(|| PyErr::new::<ImportError, _>(py, "not a packed resources file path"))?;
+
+    let m = py.import(OXIDIZED_IMPORTER_NAME_STR)?;
+    let bootstrap_module = py.import("_frozen_importlib")?;
+
+    // Paths beneath the archive are treated as virtual sub-directories, just
+    // like paths beneath the current executable for other finders.
+    let mut resources_state = Box::new(PythonResourcesState::default());
+    resources_state.current_exe = archive.clone();
+    resources_state.origin = archive
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_default();
+    resources_state.expected_bytecode_magic_number =
+        Some(unsafe { pyffi::PyImport_GetMagicNumber() } as u32);
+
+    resources_state
+        .index_path_memory_mapped(&archive)
+        .map_err(|e| {
+            PyErr::new::<ImportError, _>(py, format!("error indexing {}: {}", archive.display(), e))
+        })?;
+
+    let finder = OxidizedFinder::create_instance(
+        py,
+        Arc::new(ImporterState::new(
+            py,
+            &m,
+            &bootstrap_module,
+            resources_state,
+        )?),
+    )?;
+
+    finder.path_hook_impl(py, path)
+}
+
 impl OxidizedFinder {
     pub(crate) fn get_state(&self, py: Python) -> Arc<ImporterState> {
         self.state(py).clone()
@@ -1403,6 +1582,15 @@ py_class!(pub(crate) class OxidizedResourceReader |py| {
     def contents(&self) -> PyResult<PyObject> {
         self.contents_impl(py)
     }
+
+    def files(&self) -> PyResult<PyOxidizerTraversable> {
+        PyOxidizerTraversable::new_instance(
+            py,
+            self.state(py).clone(),
+            self.package(py).clone(),
+            "".to_string(),
+        )
+    }
 });
 
 impl OxidizedResourceReader {
@@ -1474,10 +1662,27 @@ impl OxidizedResourceReader {
 // Path-like object facilitating Python resource access.
 //
 // This implements importlib.abc.Traversable.
+//
+// Instances are bound to a package and a path to a resource or directory of
+// resources within it. The empty path denotes the package directory.
 py_class!(pub(crate) class PyOxidizerTraversable |py| {
     data state: Arc<ImporterState>;
+    data package: String;
     data path: String;
 
+    def __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "<PyOxidizerTraversable package=\"{}\" path=\"{}\">",
+            self.package(py),
+            self.path(py)
+        ))
+    }
+
+    // The base name of this object without any parent references.
+    @property def name(&self) -> PyResult<String> {
+        Ok(self.name_impl(py))
+    }
+
     // Yield Traversable objects in self.
     def iterdir(&self) -> PyResult<PyObject> {
         self.iterdir_impl(py)
@@ -1489,28 +1694,23 @@ py_class!(pub(crate) class PyOxidizerTraversable |py| {
     }
 
     // Read contents of self as text.
-    def read_text(&self) -> PyResult<PyObject> {
-        self.read_text_impl(py)
+    def read_text(&self, encoding: Option<PyObject> = None, errors: Option<PyObject> = None) -> PyResult<PyObject> {
+        self.read_text_impl(py, encoding, errors)
     }
 
     // Return True if self is a dir.
-    def is_dir(&self) -> PyResult<PyObject> {
+    def is_dir(&self) -> PyResult<bool> {
         Ok(self.is_dir_impl(py))
     }
 
     // Return True if self is a file.
-    def is_file(&self) -> PyResult<PyObject> {
-        self.is_file_impl(py)
+    def is_file(&self) -> PyResult<bool> {
+        Ok(self.is_file_impl(py))
     }
 
     // Return Traversable child in self.
-    def joinpath(&self, child: &PyObject) -> PyResult<PyObject> {
-        self.joinpath_impl(py, child)
-    }
-
-    /// Return Traversable child in self.
-    def __truediv__(&self, child: &PyObject) -> PyResult<PyObject> {
-        self.joinpath_impl(py, child)
+    def joinpath(&self, *args, **kwargs) -> PyResult<PyOxidizerTraversable> {
+        self.joinpath_impl(py, args)
     }
 
     // mode may be 'r' or 'rb' to open as text or binary. Return a handle
@@ -1523,54 +1723,238 @@ py_class!(pub(crate) class PyOxidizerTraversable |py| {
     }
 });
 
+/// Implements the `/` operator of [PyOxidizerTraversable].
+///
+/// `py_class!` doesn't support `__truediv__`, so the number protocol slot is
+/// installed on the type object when the first instance is created.
+unsafe extern "C" fn traversable_true_divide(
+    lhs: *mut pyffi::PyObject,
+    rhs: *mut pyffi::PyObject,
+) -> *mut pyffi::PyObject {
+    let py = Python::assume_gil_acquired();
+    let lhs = PyObject::from_borrowed_ptr(py, lhs);
+    let rhs = PyObject::from_borrowed_ptr(py, rhs);
+
+    let traversable = match lhs.cast_as::<PyOxidizerTraversable>(py) {
+        Ok(traversable) => traversable,
+        Err(_) => {
+            let not_implemented = pyffi::Py_NotImplemented();
+            pyffi::Py_INCREF(not_implemented);
+            return not_implemented;
+        }
+    };
+
+    match traversable.joinpath_impl(py, &PyTuple::new(py, &[rhs])) {
+        Ok(child) => child.into_object().steal_ptr(),
+        Err(err) => {
+            err.restore(py);
+            std::ptr::null_mut()
+        }
+    }
+}
+
 impl PyOxidizerTraversable {
-    fn iterdir_impl(&self, _py: Python) -> PyResult<PyObject> {
-        unimplemented!();
+    /// Construct an instance, ensuring the `/` operator is available.
+    ///
+    /// Other instances are derived from instances constructed this way.
+    fn new_instance(
+        py: Python,
+        state: Arc<ImporterState>,
+        package: String,
+        path: String,
+    ) -> PyResult<PyOxidizerTraversable> {
+        let type_object = py.get_type::<PyOxidizerTraversable>();
+
+        unsafe {
+            let type_ptr = type_object.as_type_ptr();
+            if (*type_ptr).tp_as_number.is_null() {
+                (*type_ptr).tp_as_number = Box::into_raw(Box::new(pyffi::PyNumberMethods {
+                    nb_true_divide: Some(traversable_true_divide),
+                    ..pyffi::PyNumberMethods_INIT
+                }));
+            }
+        }
+
+        PyOxidizerTraversable::create_instance(py, state, package, path)
     }
 
-    fn read_bytes_impl(&self, _py: Python) -> PyResult<PyObject> {
-        unimplemented!();
+    fn name_impl(&self, py: Python) -> String {
+        let path = self.path(py);
+
+        if path.is_empty() {
+            self.package(py)
+                .rsplit('.')
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        } else {
+            path.rsplit('/').next().unwrap_or_default().to_string()
+        }
     }
 
-    fn read_text_impl(&self, _py: Python) -> PyResult<PyObject> {
-        unimplemented!();
+    fn iterdir_impl(&self, py: Python) -> PyResult<PyObject> {
+        if !self.is_dir_impl(py) {
+            return Err(PyErr::new::<NotADirectoryError, _>(
+                py,
+                format!("not a directory: {}", self.path(py)),
+            ));
+        }
+
+        let state = self.state(py);
+        let package = self.package(py);
+        let path = self.path(py);
+
+        let children = state
+            .get_resources_state()
+            .package_resources_list_directory(package, path)
+            .into_iter()
+            .map(|name| {
+                let child = if path.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", path, name)
+                };
+
+                Ok(PyOxidizerTraversable::create_instance(
+                    py,
+                    state.clone(),
+                    package.clone(),
+                    child,
+                )?
+                .into_object())
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+
+        Ok(PyList::new(py, &children).into_object())
     }
 
-    fn is_dir_impl(&self, py: Python) -> PyObject {
+    fn read_bytes_impl(&self, py: Python) -> PyResult<PyObject> {
         let state = self.state(py);
+        let package = self.package(py);
         let path = self.path(py);
 
-        // We are a directory if the current path is a known package.
-        // TODO We may need to expand this definition in the future to cover
-        // virtual subdirectories in addressable resources. But this will require
-        // changes to the resources data format to capture said annotations.
-        if let Some(entry) = state
+        match state
             .get_resources_state()
-            .resolve_importable_module(&path, state.optimize_level)
+            .get_package_resource_file(py, package, path)?
         {
-            if entry.is_package {
-                return py.True().into_object();
-            }
+            Some(file) => file.call_method(py, "read", NoArgs, None),
+            None => Err(PyErr::new::<FileNotFoundError, _>(
+                py,
+                format!("resource not found: {}", path),
+            )),
         }
+    }
 
-        py.False().into_object()
+    fn read_text_impl(
+        &self,
+        py: Python,
+        encoding: Option<PyObject>,
+        errors: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let kwargs = PyDict::new(py);
+        kwargs.set_item(py, "encoding", encoding)?;
+        kwargs.set_item(py, "errors", errors)?;
+
+        self.open_impl(
+            py,
+            &PyTuple::new(py, &["r".to_py_object(py).into_object()]),
+            Some(&kwargs),
+        )?
+        .call_method(py, "read", NoArgs, None)
     }
 
-    fn is_file_impl(&self, _py: Python) -> PyResult<PyObject> {
-        unimplemented!();
+    fn is_dir_impl(&self, py: Python) -> bool {
+        let state = self.state(py);
+        let path = self.path(py);
+
+        // The package directory is always a directory. Other paths are
+        // directories if resources exist under them.
+        path.is_empty()
+            || state
+                .get_resources_state()
+                .is_package_resource_directory(self.package(py), path)
     }
 
-    fn joinpath_impl(&self, _py: Python, _child: &PyObject) -> PyResult<PyObject> {
-        unimplemented!();
+    fn is_file_impl(&self, py: Python) -> bool {
+        self.state(py)
+            .get_resources_state()
+            .is_package_resource(self.package(py), self.path(py))
+    }
+
+    fn joinpath_impl(&self, py: Python, children: &PyTuple) -> PyResult<PyOxidizerTraversable> {
+        let mut parts = self
+            .path(py)
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .map(|part| part.to_string())
+            .collect::<Vec<_>>();
+
+        for child in children.iter(py) {
+            let child = py
+                .import("os")?
+                .call(py, "fspath", (child,), None)?
+                .extract::<String>(py)?;
+
+            // Internally resources are normalized to POSIX separators.
+            for part in child.replace('\\', "/").split('/') {
+                match part {
+                    "" | "." => {}
+                    ".." => {
+                        return Err(PyErr::new::<ValueError, _>(
+                            py,
+                            "cannot traverse outside of the package",
+                        ));
+                    }
+                    part => parts.push(part.to_string()),
+                }
+            }
+        }
+
+        PyOxidizerTraversable::create_instance(
+            py,
+            self.state(py).clone(),
+            self.package(py).clone(),
+            parts.join("/"),
+        )
     }
 
-    fn open_impl(
-        &self,
-        _py: Python,
-        _args: &PyTuple,
-        _kwargs: Option<&PyDict>,
-    ) -> PyResult<PyObject> {
-        unimplemented!();
+    fn open_impl(&self, py: Python, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+        let kwargs = match kwargs {
+            Some(kwargs) => kwargs.copy(py)?,
+            None => PyDict::new(py),
+        };
+
+        let mode = if args.len(py) > 0 {
+            args.get_item(py, 0).extract::<String>(py)?
+        } else if let Some(mode) = kwargs.get_item(py, "mode") {
+            kwargs.del_item(py, "mode")?;
+            mode.extract::<String>(py)?
+        } else {
+            "r".to_string()
+        };
+
+        let data = self.read_bytes_impl(py)?;
+        let io_module = py.import("io")?;
+        let bytes_io = io_module.call(py, "BytesIO", (data,), None)?;
+
+        match mode.as_str() {
+            "rb" | "br" => Ok(bytes_io),
+            "r" | "rt" | "tr" => {
+                let mut text_args = vec![bytes_io];
+                text_args.extend(args.iter(py).skip(1).map(|arg| arg.clone_ref(py)));
+
+                io_module.call(
+                    py,
+                    "TextIOWrapper",
+                    PyTuple::new(py, &text_args),
+                    Some(&kwargs),
+                )
+            }
+            _ => Err(PyErr::new::<ValueError, _>(
+                py,
+                format!("unsupported mode: {}", mode),
+            )),
+        }
     }
 }
 
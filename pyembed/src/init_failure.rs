@@ -0,0 +1,243 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Handling of embedded interpreter initialization failures.
+
+Executables embedding Python exit with a documented exit code so a
+supervising process can tell why they exited:
+
+* Exit codes of Python code are passed through. e.g. `0` on success, `1`
+  for an uncaught exception, and `n` for `SystemExit(n)`.
+* [OxidizedPythonInterpreterConfig::failure_exit_code_base] (`70` by
+  default) when the interpreter fails to initialize.
+* [OxidizedPythonInterpreterConfig::failure_exit_code_base] plus 1 (`71`
+  by default) when resources can't be loaded or don't match the interpreter.
+
+The error is printed to stderr as a single line of the form
+`error instantiating embedded Python interpreter: <message>`. The message of
+resource errors starts with `invalid resources: `.
+*/
+
+use {
+    crate::{config::OxidizedPythonInterpreterConfig, error::NewInterpreterError},
+    python_packaging::interpreter::InitFailureAction,
+    std::{path::PathBuf, process::Command},
+};
+
+/// Environment variable holding the error message when running a fallback command.
+pub const INIT_FAILURE_MESSAGE_ENV: &str = "PYEMBED_INIT_FAILURE_MESSAGE";
+
+/// Environment variable holding the exit code a fallback command replaces.
+pub const INIT_FAILURE_EXIT_CODE_ENV: &str = "PYEMBED_INIT_FAILURE_EXIT_CODE";
+
+/// Handles errors constructing a [crate::MainPythonInterpreter].
+///
+/// Constructing an interpreter consumes its config. So this is created from
+/// the config before the interpreter is.
+#[derive(Clone, Debug)]
+pub struct InitFailureHandler {
+    action: InitFailureAction,
+    exit_code_base: i32,
+    fallback_command: Option<Vec<String>>,
+    origin: Option<PathBuf>,
+}
+
+impl<'a> From<&OxidizedPythonInterpreterConfig<'a>> for InitFailureHandler {
+    fn from(config: &OxidizedPythonInterpreterConfig<'a>) -> Self {
+        Self {
+            action: config.on_init_failure,
+            exit_code_base: config.failure_exit_code_base,
+            fallback_command: config.init_failure_fallback_command.clone(),
+            origin: config.origin.clone(),
+        }
+    }
+}
+
+impl InitFailureHandler {
+    /// Obtain the exit code for an error.
+    pub fn exit_code(&self, err: &NewInterpreterError) -> i32 {
+        err.exit_code(self.exit_code_base)
+    }
+
+    /// Obtain the message printed for an error.
+    pub fn message(&self, err: &NewInterpreterError) -> String {
+        format!("error instantiating embedded Python interpreter: {}", err)
+    }
+
+    /// Report an error according to the configured action.
+    ///
+    /// Returns the exit code the process should exit with.
+    pub fn handle(&self, err: &NewInterpreterError) -> i32 {
+        let message = self.message(err);
+        let exit_code = self.exit_code(err);
+
+        eprintln!("{}", message);
+
+        match self.action {
+            InitFailureAction::Exit => exit_code,
+            InitFailureAction::MessageBox => {
+                show_message_box(&message);
+                exit_code
+            }
+            InitFailureAction::RunFallbackCommand => {
+                match self.run_fallback_command(&message, exit_code) {
+                    Ok(code) => code,
+                    Err(e) => {
+                        eprintln!("error running init failure fallback command: {}", e);
+                        exit_code
+                    }
+                }
+            }
+        }
+    }
+
+    /// Run the fallback command and obtain its exit code.
+    ///
+    /// Arguments of the current process are appended to the command. The
+    /// error message and the exit code it replaces are passed in environment
+    /// variables.
+    fn run_fallback_command(&self, message: &str, exit_code: i32) -> Result<i32, String> {
+        let command = match &self.fallback_command {
+            Some(command) if !command.is_empty() => command,
+            _ => return Err("no fallback command is configured".to_string()),
+        };
+
+        let origin = match &self.origin {
+            Some(origin) => origin.clone(),
+            None => std::env::current_exe()
+                .map_err(|e| format!("unable to obtain current executable: {}", e))?
+                .parent()
+                .ok_or_else(|| "unable to get executable parent".to_string())?
+                .to_path_buf(),
+        };
+        let program = command[0].replace("$ORIGIN", &origin.display().to_string());
+
+        let status = Command::new(&program)
+            .args(&command[1..])
+            .args(std::env::args_os().skip(1))
+            .env(INIT_FAILURE_MESSAGE_ENV, message)
+            .env(INIT_FAILURE_EXIT_CODE_ENV, exit_code.to_string())
+            .status()
+            .map_err(|e| format!("{}: {}", program, e))?;
+
+        status
+            .code()
+            .ok_or_else(|| format!("{} was terminated by a signal", program))
+    }
+}
+
+#[cfg(windows)]
+fn show_message_box(message: &str) {
+    use {
+        std::{ffi::OsStr, os::windows::ffi::OsStrExt},
+        winapi::um::winuser::{MessageBoxW, MB_ICONERROR, MB_OK},
+    };
+
+    let title = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.file_stem().map(|stem| stem.to_os_string()))
+        .unwrap_or_else(|| "Python".into());
+
+    let wide = |s: &OsStr| {
+        s.encode_wide()
+            .chain(std::iter::once(0))
+            .collect::<Vec<u16>>()
+    };
+    let title = wide(&title);
+    let message = wide(OsStr::new(message));
+
+    unsafe {
+        MessageBoxW(
+            std::ptr::null_mut(),
+            message.as_ptr(),
+            title.as_ptr(),
+            MB_OK | MB_ICONERROR,
+        );
+    }
+}
+
+#[cfg(not(windows))]
+fn show_message_box(_message: &str) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler(action: InitFailureAction) -> InitFailureHandler {
+        let config = OxidizedPythonInterpreterConfig {
+            on_init_failure: action,
+            ..OxidizedPythonInterpreterConfig::default()
+        };
+
+        InitFailureHandler::from(&config)
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let mut config = OxidizedPythonInterpreterConfig::default();
+        let err = NewInterpreterError::Simple("oops");
+        let resources_err = NewInterpreterError::Resources("corrupt".to_string());
+
+        let handler = InitFailureHandler::from(&config);
+        assert_eq!(handler.exit_code(&err), 70);
+        assert_eq!(handler.exit_code(&resources_err), 71);
+        assert_eq!(
+            handler.message(&err),
+            "error instantiating embedded Python interpreter: oops"
+        );
+        assert_eq!(
+            handler.message(&resources_err),
+            "error instantiating embedded Python interpreter: invalid resources: corrupt"
+        );
+        assert_eq!(handler.handle(&resources_err), 71);
+
+        config.failure_exit_code_base = 200;
+        let handler = InitFailureHandler::from(&config);
+        assert_eq!(handler.exit_code(&err), 200);
+        assert_eq!(handler.exit_code(&resources_err), 201);
+    }
+
+    #[test]
+    fn test_message_box_exit_code() {
+        // Message boxes are only shown on Windows, where this would block.
+        if !cfg!(windows) {
+            let handler = handler(InitFailureAction::MessageBox);
+            assert_eq!(handler.handle(&NewInterpreterError::Simple("oops")), 70);
+        }
+    }
+
+    #[test]
+    fn test_fallback_command_missing() {
+        let handler = handler(InitFailureAction::RunFallbackCommand);
+        assert_eq!(handler.handle(&NewInterpreterError::Simple("oops")), 70);
+
+        let mut handler = handler.clone();
+        handler.fallback_command = Some(vec!["/does/not/exist".to_string()]);
+        assert_eq!(
+            handler.handle(&NewInterpreterError::Resources("corrupt".to_string())),
+            71
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fallback_command() {
+        let mut handler = handler(InitFailureAction::RunFallbackCommand);
+        handler.fallback_command = Some(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            format!(
+                "test \"${}\" = 71 && \
+                test \"${}\" = 'error instantiating embedded Python interpreter: invalid resources: corrupt' \
+                && exit 9",
+                INIT_FAILURE_EXIT_CODE_ENV, INIT_FAILURE_MESSAGE_ENV
+            ),
+        ]);
+
+        assert_eq!(
+            handler.handle(&NewInterpreterError::Resources("corrupt".to_string())),
+            9
+        );
+    }
+}
@@ -9,7 +9,10 @@ use {
         config::{OxidizedPythonInterpreterConfig, ResolvedOxidizedPythonInterpreterConfig},
         conversion::osstring_to_bytes,
         error::NewInterpreterError,
+        excluded_encodings::register_excluded_encodings,
+        executor::{PyTask, PythonExecutor, DEFAULT_EXECUTOR_THREADS},
         extension::{PyInit_oxidized_importer, OXIDIZED_IMPORTER_NAME, OXIDIZED_IMPORTER_NAME_STR},
+        hooks::{register_pre_finalize, run_post_init, InterpreterHooks},
         importer::{
             install_path_hook, remove_external_importers, replace_meta_path_importers,
             ImporterState,
@@ -17,6 +20,12 @@ use {
         osutils::resolve_terminfo_dirs,
         pyalloc::PythonMemoryAllocator,
         python_resources::PythonResourcesState,
+        python_shim::{ensure_python_shim, python_shim_app_path},
+        repl::run_repl,
+        startup_audit::{
+            install_startup_audit_hook, record_startup_access, start_startup_audit,
+            stop_startup_audit,
+        },
     },
     cpython::{
         exc::RuntimeError, GILGuard, NoArgs, ObjectProtocol, PyDict, PyErr, PyList, PyResult,
@@ -24,7 +33,9 @@ use {
     },
     once_cell::sync::Lazy,
     python3_sys as pyffi,
-    python_packaging::interpreter::{MultiprocessingStartMethod, TerminfoResolution},
+    python_packaging::interpreter::{
+        MultiprocessingStartMethod, PythonRunMode, SysExecutableMode, TerminfoResolution,
+    },
     std::{
         collections::BTreeSet,
         convert::{TryFrom, TryInto},
@@ -61,6 +72,13 @@ pub struct MainPythonInterpreter<'python, 'interpreter: 'python, 'resources: 'in
     py: Option<Python<'python>>,
     /// File to write containing list of modules when the interpreter finalizes.
     write_modules_path: Option<PathBuf>,
+    /// Thread pool for running Python work off the calling thread.
+    executor: Option<PythonExecutor>,
+    /// State of the initializing thread while it has released the GIL.
+    ///
+    /// The GIL is held since initialization without a `GILGuard`, so
+    /// releasing it requires `PyEval_SaveThread()`.
+    saved_thread_state: Option<*mut pyffi::PyThreadState>,
 }
 
 impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpreter, 'resources> {
@@ -70,8 +88,30 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
     pub fn new(
         config: OxidizedPythonInterpreterConfig<'resources>,
     ) -> Result<MainPythonInterpreter<'python, 'interpreter, 'resources>, NewInterpreterError> {
+        Self::new_with_hooks(config, InterpreterHooks::default())
+    }
+
+    /// Construct a Python interpreter from a configuration and lifecycle hooks.
+    ///
+    /// Behaves like [Self::new()] but runs the callbacks in `hooks` at
+    /// their respective points in the interpreter lifecycle. An error from
+    /// a hook aborts interpreter creation.
+    pub fn new_with_hooks(
+        mut config: OxidizedPythonInterpreterConfig<'resources>,
+        hooks: InterpreterHooks<'resources>,
+    ) -> Result<MainPythonInterpreter<'python, 'interpreter, 'resources>, NewInterpreterError> {
+        if let Some(pre_init) = hooks.pre_init {
+            pre_init(&mut config).map_err(|e| {
+                NewInterpreterError::Dynamic(format!("during pre-init hook: {}", e))
+            })?;
+        }
+
         let config: ResolvedOxidizedPythonInterpreterConfig<'resources> = config.try_into()?;
 
+        if let Some(path) = &config.startup_audit_path {
+            start_startup_audit(path)?;
+        }
+
         match config.terminfo_resolution {
             TerminfoResolution::Dynamic => {
                 if let Some(v) = resolve_terminfo_dirs() {
@@ -81,6 +121,12 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
             TerminfoResolution::Static(ref v) => {
                 env::set_var("TERMINFO_DIRS", v);
             }
+            // Line editing in the REPL needs a terminfo database.
+            TerminfoResolution::None if config.run_mode == PythonRunMode::Repl => {
+                if let Some(v) = resolve_terminfo_dirs() {
+                    env::set_var("TERMINFO_DIRS", &v);
+                }
+            }
             TerminfoResolution::None => {}
         }
 
@@ -91,10 +137,19 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
             _gil: None,
             py: None,
             write_modules_path: None,
+            executor: None,
+            saved_thread_state: None,
         };
 
         res.init()?;
 
+        let post_init_callable = res.config.post_init_callable.clone();
+        let pre_finalize_callable = res.config.pre_finalize_callable.clone();
+
+        let py = res.acquire_gil();
+        run_post_init(py, hooks.post_init, post_init_callable.as_deref())?;
+        register_pre_finalize(py, hooks.pre_finalize, pre_finalize_callable.as_deref())?;
+
         Ok(res)
     }
 
@@ -177,6 +232,10 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
         // our custom importer before Python attempts any imports.
         py_config._init_main = 0;
 
+        if self.config.startup_audit_path.is_some() {
+            install_startup_audit_hook()?;
+        }
+
         let status = unsafe { pyffi::Py_InitializeFromConfig(&py_config) };
         if unsafe { pyffi::PyStatus_Exception(status) } != 0 {
             return Err(NewInterpreterError::new_from_pystatus(
@@ -193,6 +252,19 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
         let py = unsafe { Python::assume_gil_acquired() };
         self.py = Some(py);
 
+        if let Some(expected) = self.config.bytecode_magic_number {
+            let actual = unsafe { pyffi::PyImport_GetMagicNumber() } as u32;
+
+            if actual != expected {
+                return Err(NewInterpreterError::Resources(format!(
+                    "bytecode magic number mismatch: libpython reports {:#010x} but the \
+                    interpreter config expects {:#010x}; libpython is from a different Python \
+                    distribution than the embedded resources",
+                    actual, expected
+                )));
+            }
+        }
+
         let oxidized_finder = if self.config.oxidized_importer {
             let resources_state = Box::new(PythonResourcesState::try_from(&self.config)?);
 
@@ -294,6 +366,18 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
                     "installing OxidizedFinder in sys.path_hooks",
                 )
             })?;
+
+            if self.config.resources_filesystem_shim {
+                finder
+                    .call_method(py, "install_resources_filesystem_shim", NoArgs, None)
+                    .map_err(|err| {
+                        NewInterpreterError::new_from_pyerr(
+                            py,
+                            err,
+                            "installing resources filesystem shim",
+                        )
+                    })?;
+            }
         }
 
         if self.config.argvb {
@@ -353,7 +437,49 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
             }
         }
 
+        let executable = match self.config.sys_executable_mode {
+            // The path the executable was invoked as is set on the PyConfig
+            // so `site` sees it when looking for a virtual environment.
+            SysExecutableMode::App | SysExecutableMode::Invoked => None,
+            SysExecutableMode::None => Some(py.None()),
+            SysExecutableMode::PythonShim => {
+                let exe = self.config.exe();
+
+                // A shim that can't be created (e.g. because the directory
+                // isn't writable) leaves sys.executable pointing at the app.
+                if python_shim_app_path(exe).is_some() {
+                    Some(exe.clone())
+                } else {
+                    ensure_python_shim(exe).ok()
+                }
+                .map(|path| PyString::new(py, &path.display().to_string()).into_object())
+            }
+        };
+
+        if let Some(value) = executable {
+            let key = b"executable\0";
+
+            match value.with_borrowed_ptr(py, |py_value| unsafe {
+                pyffi::PySys_SetObject(key.as_ptr() as *const i8, py_value)
+            }) {
+                0 => (),
+                _ => return Err(NewInterpreterError::Simple("unable to set sys.executable")),
+            }
+        }
+
+        if !self.config.excluded_encodings.is_empty() {
+            register_excluded_encodings(py, &self.config.excluded_encodings).map_err(|err| {
+                NewInterpreterError::new_from_pyerr(
+                    py,
+                    err,
+                    "registering excluded encodings codec search function",
+                )
+            })?;
+        }
+
         if let Some(key) = &self.config.write_modules_directory_env {
+            record_startup_access("env", key);
+
             if let Ok(path) = std::env::var(key) {
                 let path = PathBuf::from(path);
 
@@ -396,8 +522,12 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
 
     /// Ensure the Python GIL is released.
     pub fn release_gil(&mut self) {
-        self.py = None;
-        self._gil = None;
+        if self._gil.take().is_some() {
+            self.py = None;
+        } else if self.py.take().is_some() {
+            // The GIL has been held since initialization.
+            self.saved_thread_state = Some(unsafe { pyffi::PyEval_SaveThread() });
+        }
     }
 
     /// Ensure the Python GIL is acquired, returning a handle on the interpreter.
@@ -410,10 +540,13 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
         match self.py {
             Some(py) => py,
             None => {
-                let gil = GILGuard::acquire();
-                let py = unsafe { Python::assume_gil_acquired() };
+                if let Some(state) = self.saved_thread_state.take() {
+                    unsafe { pyffi::PyEval_RestoreThread(state) };
+                } else {
+                    self._gil = Some(GILGuard::acquire());
+                }
 
-                self._gil = Some(gil);
+                let py = unsafe { Python::assume_gil_acquired() };
                 self.py = Some(py);
 
                 py
@@ -421,6 +554,50 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
         }
     }
 
+    /// Obtain the executor for running Python work on background threads.
+    ///
+    /// The executor is started on first use. The GIL is released from the
+    /// current thread so executor threads can acquire it.
+    /// [Self::acquire_gil()] takes it back.
+    ///
+    /// The executor is shut down when the interpreter is dropped, before the
+    /// interpreter is finalized.
+    pub fn executor(&mut self) -> &PythonExecutor {
+        self.release_gil();
+
+        if self.executor.is_none() {
+            self.executor = Some(PythonExecutor::new(DEFAULT_EXECUTOR_THREADS));
+        }
+
+        self.executor.as_ref().unwrap()
+    }
+
+    /// Run a closure on a background thread while holding the GIL.
+    ///
+    /// This is suitable for calling from async code: the returned
+    /// [PyTask] is a `Future` and the calling thread doesn't block on the GIL.
+    /// Panics in the closure are reported as errors on the returned task.
+    pub fn spawn_blocking_py<F, T>(&mut self, f: F) -> PyTask<T>
+    where
+        F: FnOnce(Python) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.executor().spawn(move |py| Ok(f(py)))
+    }
+
+    /// Call a Python function by its dotted name on a background thread.
+    ///
+    /// Arguments and the return value are converted via JSON. See
+    /// [PythonExecutor::call] for how arguments are passed.
+    #[cfg(feature = "serialization")]
+    pub fn call_py_function<A, R>(&mut self, name: &str, args: &A) -> PyTask<R>
+    where
+        A: serde::Serialize,
+        R: serde::de::DeserializeOwned + Send + 'static,
+    {
+        self.executor().call(name, args)
+    }
+
     /// Runs `Py_RunMain()` and finalizes the interpreter.
     ///
     /// This will execute whatever is configured by the Python interpreter config
@@ -505,13 +682,45 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
         argv.len() >= 2 && argv[1] == "--multiprocessing-fork"
     }
 
+    /// Run an interactive REPL.
+    ///
+    /// The REPL is configured by the `repl_*` fields of the config and runs
+    /// until it is exited. End of input results in exit code 0. `exit()`
+    /// and `SystemExit` result in the exit code `python` would use.
+    ///
+    /// Returns an integer suitable for use as a process exit code. The
+    /// interpreter is finalized when this instance is dropped, which runs
+    /// `atexit` handlers, including the pre-finalize hook.
+    pub fn run_repl(&mut self) -> i32 {
+        let banner = self.config.repl_banner.clone();
+        let startup_code = self.config.repl_startup_code.clone();
+        let history_path = self.config.repl_history_path.clone();
+        let app_name = self
+            .config
+            .exe()
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "python".to_string());
+
+        let py = self.acquire_gil();
+
+        run_repl(
+            py,
+            banner.as_deref(),
+            startup_code.as_deref(),
+            history_path.as_deref(),
+            &app_name,
+        )
+    }
+
     /// Runs the Python interpreter.
     ///
     /// If multiprocessing dispatch is enabled, this will check if the
     /// current process invocation appears to be a spawned multiprocessing worker
     /// and dispatch to multiprocessing accordingly.
     ///
-    /// Otherwise, this delegates to [Self::py_runmain].
+    /// Otherwise, this delegates to [Self::run_repl] if the run mode is
+    /// [PythonRunMode::Repl] and to [Self::py_runmain] if not.
     pub fn run(mut self) -> i32 {
         if self.config.multiprocessing_auto_dispatch && self.is_multiprocessing() {
             match self.run_multiprocessing() {
@@ -522,6 +731,8 @@ impl<'python, 'interpreter, 'resources> MainPythonInterpreter<'python, 'interpre
                     1
                 }
             }
+        } else if self.config.run_mode == PythonRunMode::Repl {
+            self.run_repl()
         } else {
             self.py_runmain()
         }
@@ -638,12 +849,25 @@ impl<'python, 'interpreter, 'resources> Drop
     for MainPythonInterpreter<'python, 'interpreter, 'resources>
 {
     fn drop(&mut self) {
+        // Executor threads need the GIL to finish running work.
+        if let Some(mut executor) = self.executor.take() {
+            self.release_gil();
+            executor.shutdown();
+        }
+
+        // Finalization requires the initializing thread to hold the GIL.
+        if self.saved_thread_state.is_some() {
+            self.acquire_gil();
+        }
+
         if let Some(path) = self.write_modules_path.clone() {
             if let Err(msg) = write_modules_to_path(self.acquire_gil(), &path) {
                 eprintln!("error writing modules file: {}", msg);
             }
         }
 
+        stop_startup_audit();
+
         let _ = unsafe { pyffi::Py_FinalizeEx() };
     }
 }
@@ -30,6 +30,10 @@ and [`MainPythonInterpreter`](struct.MainPythonInterpreter.html). An
 behave. A `MainPythonInterpreter` creates and manages that interpreter and
 serves as a high-level interface for running code in the interpreter.
 
+[`PackedResourcesReader`](struct.PackedResourcesReader.html) reads packed
+resources data without starting an interpreter, so hosts can inspect embedded
+resources before deciding whether to start Python.
+
 # Dependencies
 
 Under the hood, `pyembed` makes direct use of the `python3-sys` crate for
@@ -44,7 +48,8 @@ At this time, we have required direct dependencies on published versions of the
 list is extended by `memory-module-sys` and `winapi`, which are required to
 support loading DLLs from memory. We also have an optional direct dependency
 on the `jemalloc-sys`, `libmimalloc-sys`, and `snmalloc-sys` crates for custom
-memory allocators.
+memory allocators. The optional `serialization` feature adds dependencies on the
+`serde` and `serde_json` crates.
 
 This crate requires linking against a library providing CPython C symbols.
 (This dependency is via the `python3-sys` crate.) On Windows, this library
@@ -58,6 +63,9 @@ from Python is a run-time configuration option controlled by the
 `OxidizedPythonInterpreterConfig` type and having `jemalloc` compiled into the
 binary does not mean it is being used!
 
+The optional `serialization` feature enables calling Python functions by name with
+serializable arguments via `MainPythonInterpreter::call_py_function()`.
+
 The optional `mimalloc` feature controls support for using
 [mimalloc](https://github.com/microsoft/mimalloc) as Python's memory allocator.
 The feature behaves similarly to `jemalloc`, which is documented above.
@@ -66,6 +74,13 @@ The optional `snmalloc` feature controls support for using
 [snmalloc](https://github.com/microsoft/snmalloc) as Python's memory allocator.
 The feature behaves similarly to `jemalloc`, which is documented above.
 
+The optional `encrypted-resources` feature controls support for decrypting
+encrypted module source and bytecode in packed resources data. The secret
+key is obtained from the
+`OxidizedPythonInterpreterConfig.resources_key_provider` callback. This
+feature adds a dependency on the `zeroize` crate and the cryptography crates
+used by `python-packed-resources`.
+
 There exist mutually exclusive `build-mode-*` features to control how the
 `build.rs` build script works.
 
@@ -96,12 +111,22 @@ That crate's build script will attempt to find a `libpython` from the
 #[allow(unused)]
 mod config;
 mod conversion;
+mod encryption;
 mod error;
+#[cfg(not(library_mode = "extension"))]
+mod excluded_encodings;
+#[cfg(not(library_mode = "extension"))]
+mod executor;
 #[allow(clippy::manual_strip, clippy::transmute_ptr_to_ptr, clippy::zero_ptr)]
 mod extension;
+#[cfg(not(library_mode = "extension"))]
+#[allow(clippy::manual_strip)]
+mod hooks;
 #[allow(clippy::manual_strip, clippy::transmute_ptr_to_ptr, clippy::zero_ptr)]
 mod importer;
 #[cfg(not(library_mode = "extension"))]
+mod init_failure;
+#[cfg(not(library_mode = "extension"))]
 mod interpreter;
 #[cfg(not(library_mode = "extension"))]
 mod interpreter_config;
@@ -111,6 +136,7 @@ mod memory_dll;
 mod osutils;
 #[allow(clippy::manual_strip, clippy::transmute_ptr_to_ptr, clippy::zero_ptr)]
 mod package_metadata;
+mod packed_resources_reader;
 #[allow(clippy::manual_strip, clippy::transmute_ptr_to_ptr, clippy::zero_ptr)]
 mod pkg_resources;
 #[cfg(not(library_mode = "extension"))]
@@ -126,18 +152,42 @@ mod python_resource_collector;
 mod python_resource_types;
 #[allow(clippy::manual_strip, clippy::transmute_ptr_to_ptr, clippy::zero_ptr)]
 mod python_resources;
+#[allow(unused)]
+mod python_shim;
+#[cfg(not(library_mode = "extension"))]
+mod repl;
 mod resource_scanning;
+#[allow(
+    unknown_lints,
+    non_local_definitions,
+    unused_variables,
+    clippy::manual_strip,
+    clippy::transmute_ptr_to_ptr,
+    clippy::zero_ptr
+)]
+mod resources_filesystem_shim;
+#[allow(unused)]
+mod startup_audit;
 #[cfg(not(library_mode = "extension"))]
 pub mod technotes;
 #[cfg(test)]
 mod test;
 
-pub use crate::{config::PackedResourcesSource, error::NewInterpreterError};
+pub use crate::{
+    config::PackedResourcesSource, encryption::ResourcesKeyProvider, error::NewInterpreterError,
+    packed_resources_reader::PackedResourcesReader,
+};
 
 #[cfg(not(library_mode = "extension"))]
 #[allow(unused_imports)]
 pub use crate::{
-    config::{ExtensionModule, OxidizedPythonInterpreterConfig},
+    config::{
+        ExtensionModule, OxidizedPythonInterpreterConfig, DEFAULT_FAILURE_EXIT_CODE_BASE,
+        FILESYSTEM_RESOURCES_ROOT_MARKER,
+    },
+    executor::{PyTask, PyTaskError, PythonExecutor},
+    hooks::{InterpreterHooks, PostInitHook, PreFinalizeHook, PreInitHook},
+    init_failure::{InitFailureHandler, INIT_FAILURE_EXIT_CODE_ENV, INIT_FAILURE_MESSAGE_ENV},
     interpreter::MainPythonInterpreter,
 };
 
@@ -148,9 +198,9 @@ pub use crate::extension::PyInit_oxidized_importer;
 #[allow(unused_imports)]
 pub use python_packaging::{
     interpreter::{
-        Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, MemoryAllocatorBackend,
-        MultiprocessingStartMethod, PythonInterpreterConfig, PythonInterpreterProfile,
-        TerminfoResolution,
+        Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, InitFailureAction,
+        MemoryAllocatorBackend, MultiprocessingStartMethod, PythonInterpreterConfig,
+        PythonInterpreterProfile, PythonRunMode, SysExecutableMode, TerminfoResolution,
     },
     resource::BytecodeOptimizationLevel,
 };
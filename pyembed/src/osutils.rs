@@ -3,6 +3,7 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
+    crate::startup_audit::record_startup_access,
     once_cell::sync::Lazy,
     std::path::{Path, PathBuf},
 };
@@ -69,6 +70,7 @@ struct OsInfo {
 fn resolve_linux_distro() -> LinuxDistroVariant {
     // Attempt to resolve the Linux distro by parsing /etc files.
     let os_release = Path::new("/etc/os-release");
+    record_startup_access("open", &os_release.display().to_string());
 
     if let Ok(data) = std::fs::read_to_string(os_release) {
         for line in data.split('\n') {
@@ -103,6 +105,7 @@ fn resolve_os_info() -> OsInfo {
 /// no environment variable should be set.
 pub fn resolve_terminfo_dirs() -> Option<String> {
     // Always respect an environment variable, if present.
+    record_startup_access("env", "TERMINFO_DIRS");
     if std::env::var("TERMINFO_DIRS").is_ok() {
         return None;
     }
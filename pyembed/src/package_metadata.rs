@@ -312,19 +312,24 @@ pub(crate) fn find_distributions(
             vec![]
         }
     } else {
-        // Return all distributions.
+        // Return all distributions, ordered by name so iteration order is stable.
+        let mut names = resources
+            .iter()
+            .filter(|(_, v)| {
+                v.is_package
+                    && (v.in_memory_distribution_resources.is_some()
+                        || v.relative_path_distribution_resources.is_some())
+            })
+            .map(|(k, _)| k.to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+
         let mut distributions = Vec::new();
 
-        for (k, v) in resources.iter() {
-            if v.is_package
-                && (v.in_memory_distribution_resources.is_some()
-                    || v.relative_path_distribution_resources.is_some())
-            {
-                distributions.push(
-                    OxidizedDistribution::create_instance(py, state.clone(), k.to_string())?
-                        .into_object(),
-                );
-            }
+        for name in names {
+            distributions.push(
+                OxidizedDistribution::create_instance(py, state.clone(), name)?.into_object(),
+            );
         }
 
         distributions
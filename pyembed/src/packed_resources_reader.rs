@@ -0,0 +1,250 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Read packed resources data without a Python interpreter.
+
+[PackedResourcesReader] indexes packed resources data, such as the blob
+embedded in a built application, so a host can inspect and read resources
+before deciding whether to start Python. It uses no Python symbols and
+doesn't require the GIL.
+
+Payloads are returned as slices into the indexed data: nothing is copied.
+
+A reader is immutable once constructed and is `Send` and `Sync`, so a
+single instance can be shared by reference across threads.
+*/
+
+use {
+    python_packed_resources::data::Resource,
+    std::{
+        borrow::Cow,
+        collections::{hash_map::Entry, HashMap},
+    },
+};
+
+/// Read-only index of packed resources data.
+#[derive(Clone, Debug, Default)]
+pub struct PackedResourcesReader<'a> {
+    resources: HashMap<Cow<'a, str>, Resource<'a, u8>>,
+    /// Resource names in the order they were first seen.
+    names: Vec<Cow<'a, str>>,
+}
+
+impl<'a> PackedResourcesReader<'a> {
+    /// Construct an instance indexing packed resources data.
+    pub fn new(data: &'a [u8]) -> Result<Self, &'static str> {
+        let mut reader = Self::default();
+        reader.add_data(data)?;
+
+        Ok(reader)
+    }
+
+    /// Index additional packed resources data.
+    ///
+    /// Fields of resources already indexed are replaced by fields defined by
+    /// the new data, as happens when multiple packed resources sources are
+    /// given to the interpreter.
+    pub fn add_data(&mut self, data: &'a [u8]) -> Result<(), &'static str> {
+        let resources = python_packed_resources::parser::load_resources(data)?;
+        self.resources.reserve(resources.expected_resources_count());
+
+        for resource in resources {
+            let resource = resource?;
+
+            match self.resources.entry(resource.name.clone()) {
+                Entry::Occupied(existing) => {
+                    existing.into_mut().merge_from(resource)?;
+                }
+                Entry::Vacant(vacant) => {
+                    self.names.push(resource.name.clone());
+                    vacant.insert(resource);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The number of indexed resources.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Whether no resources are indexed.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Iterate over indexed resources in the order they were first seen.
+    ///
+    /// Each resource exposes its `name`, `flavor`, and `is_*` type fields.
+    pub fn iter(&self) -> impl Iterator<Item = &Resource<'a, u8>> + '_ {
+        self.names.iter().map(move |name| &self.resources[name])
+    }
+
+    /// Obtain the resource with the given name.
+    pub fn get(&self, name: &str) -> Option<&Resource<'a, u8>> {
+        self.resources.get(name)
+    }
+
+    /// Whether a module with the given fully qualified name exists.
+    ///
+    /// Builtin, frozen, and extension modules count as modules.
+    pub fn has_module(&self, name: &str) -> bool {
+        match self.resources.get(name) {
+            Some(resource) => {
+                resource.is_module
+                    || resource.is_builtin_extension_module
+                    || resource.is_frozen_module
+                    || resource.is_extension_module
+            }
+            None => false,
+        }
+    }
+
+    /// Obtain the in-memory source code of a module.
+    pub fn module_source(&self, name: &str) -> Option<&[u8]> {
+        self.resources
+            .get(name)
+            .and_then(|resource| resource.in_memory_source.as_deref())
+    }
+
+    /// Obtain the data of an in-memory resource file of a package.
+    ///
+    /// `resource_name` is the name of the resource relative to the package
+    /// directory, as used by `importlib.resources`.
+    pub fn package_resource(&self, package: &str, resource_name: &str) -> Option<&[u8]> {
+        self.resources
+            .get(package)
+            .and_then(|resource| resource.in_memory_package_resources.as_ref())
+            .and_then(|resources| resources.get(resource_name))
+            .map(|data| data.as_ref())
+    }
+
+    /// Obtain the data of an in-memory package distribution metadata file.
+    pub fn distribution_resource(&self, package: &str, resource_name: &str) -> Option<&[u8]> {
+        self.resources
+            .get(package)
+            .and_then(|resource| resource.in_memory_distribution_resources.as_ref())
+            .and_then(|resources| resources.get(resource_name))
+            .map(|data| data.as_ref())
+    }
+
+    /// Obtain the data of an embedded file.
+    pub fn file_data(&self, path: &str) -> Option<&[u8]> {
+        self.resources
+            .get(path)
+            .filter(|resource| resource.is_utf8_filename_data)
+            .and_then(|resource| resource.file_data_embedded.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        python_packed_resources::{data::ResourceFlavor, writer::write_packed_resources_v3},
+    };
+
+    fn packed_data(resources: &[Resource<u8>]) -> Vec<u8> {
+        let mut data = vec![];
+        write_packed_resources_v3(resources, &mut data, None).unwrap();
+
+        data
+    }
+
+    #[test]
+    fn test_reader() -> Result<(), &'static str> {
+        let mut package_resources = HashMap::new();
+        package_resources.insert(
+            Cow::from("templates/index.html"),
+            Cow::from(b"<html/>".as_ref()),
+        );
+
+        let data = packed_data(&[
+            Resource {
+                flavor: ResourceFlavor::Module,
+                name: Cow::from("app"),
+                is_module: true,
+                is_package: true,
+                in_memory_source: Some(Cow::from(b"import os".as_ref())),
+                in_memory_package_resources: Some(package_resources),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("_io"),
+                is_builtin_extension_module: true,
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("lib/data.txt"),
+                is_utf8_filename_data: true,
+                file_data_embedded: Some(Cow::from(b"data".as_ref())),
+                ..Resource::default()
+            },
+        ]);
+
+        let reader = PackedResourcesReader::new(&data)?;
+        assert_eq!(reader.len(), 3);
+        assert_eq!(
+            reader.iter().map(|r| r.name.as_ref()).collect::<Vec<_>>(),
+            vec!["app", "_io", "lib/data.txt"]
+        );
+        assert_eq!(reader.get("app").unwrap().flavor, ResourceFlavor::Module);
+
+        assert!(reader.has_module("app"));
+        assert!(reader.has_module("_io"));
+        assert!(!reader.has_module("lib/data.txt"));
+        assert!(!reader.has_module("missing"));
+
+        assert_eq!(reader.module_source("app"), Some(b"import os".as_ref()));
+        assert_eq!(
+            reader.package_resource("app", "templates/index.html"),
+            Some(b"<html/>".as_ref())
+        );
+        assert_eq!(reader.package_resource("app", "missing"), None);
+        assert_eq!(reader.distribution_resource("app", "METADATA"), None);
+        assert_eq!(reader.file_data("lib/data.txt"), Some(b"data".as_ref()));
+        assert_eq!(reader.file_data("app"), None);
+
+        // Payloads point into the source data.
+        let payload = reader.file_data("lib/data.txt").unwrap();
+        let range = data.as_ptr() as usize..data.as_ptr() as usize + data.len();
+        assert!(range.contains(&(payload.as_ptr() as usize)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_data_merges() -> Result<(), &'static str> {
+        let first = packed_data(&[Resource {
+            name: Cow::from("app"),
+            is_module: true,
+            in_memory_source: Some(Cow::from(b"old".as_ref())),
+            ..Resource::default()
+        }]);
+        let second = packed_data(&[Resource {
+            name: Cow::from("app"),
+            is_module: true,
+            in_memory_source: Some(Cow::from(b"new".as_ref())),
+            ..Resource::default()
+        }]);
+
+        let mut reader = PackedResourcesReader::new(&first)?;
+        reader.add_data(&second)?;
+
+        assert_eq!(reader.len(), 1);
+        assert_eq!(reader.module_source("app"), Some(b"new".as_ref()));
+
+        assert!(PackedResourcesReader::new(b"bogus").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<PackedResourcesReader>();
+    }
+}
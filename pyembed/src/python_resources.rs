@@ -14,7 +14,9 @@ use {
             pyobject_optional_resources_map_to_pathbuf, pyobject_to_owned_bytes_optional,
             pyobject_to_pathbuf_optional,
         },
+        encryption::{PayloadData, ResourcesDecryption},
         error::NewInterpreterError,
+        startup_audit::record_startup_access,
     },
     anyhow::Result,
     cpython::{
@@ -24,7 +26,7 @@ use {
         PyResult, PyString, PyTuple, Python, PythonObject, ToPyObject,
     },
     python3_sys as pyffi,
-    python_packed_resources::data::Resource,
+    python_packed_resources::data::{EntryPoint, Resource, ResourceField},
     std::{
         borrow::Cow,
         cell::RefCell,
@@ -131,6 +133,9 @@ where
     /// Path from which relative paths should be interpreted.
     origin: &'a Path,
 
+    /// Decrypts encrypted in-memory payloads.
+    decryption: &'a ResourcesDecryption,
+
     /// The type of importable module.
     pub flavor: ModuleFlavor,
     /// Whether this module is a package.
@@ -138,6 +143,18 @@ where
 }
 
 impl<'a> ImportablePythonModule<'a, u8> {
+    /// Resolve the plaintext of an in-memory payload, decrypting if necessary.
+    fn resolve_payload<'data>(
+        &self,
+        py: Python,
+        field: ResourceField,
+        data: &'data [u8],
+    ) -> PyResult<PayloadData<'data>> {
+        self.decryption
+            .resolve(&self.resource.name, field, data)
+            .map_err(|e| PyErr::new::<ImportError, _>(py, (e, self.resource.name.clone())))
+    }
+
     /// Attempt to resolve a Python `bytes` for the source code behind this module.
     ///
     /// Will return a PyErr if an error occurs resolving source. If there is no source,
@@ -150,10 +167,15 @@ impl<'a> ImportablePythonModule<'a, u8> {
         io_module: &PyModule,
     ) -> PyResult<Option<PyObject>> {
         let bytes = if let Some(data) = &self.resource.in_memory_source {
-            Some(PyBytes::new(py, data))
+            let data = self.resolve_payload(py, ResourceField::InMemorySource, data)?;
+
+            // Decrypted source leaves locked memory here: the `bytes` is
+            // ordinary Python memory, freed without being zeroed.
+            Some(PyBytes::new(py, &data))
         } else if let Some(relative_path) = &self.resource.relative_path_module_source {
             let path = self.origin.join(relative_path);
 
+            record_startup_access("open", &path.display().to_string());
             let source = std::fs::read(&path).map_err(|e| {
                 PyErr::new::<ImportError, _>(
                     py,
@@ -191,23 +213,44 @@ impl<'a> ImportablePythonModule<'a, u8> {
         decode_source: &PyObject,
         io_module: &PyModule,
     ) -> PyResult<Option<PyObject>> {
-        if let Some(data) = match optimize_level {
-            OptimizeLevel::Zero => &self.resource.in_memory_bytecode,
-            OptimizeLevel::One => &self.resource.in_memory_bytecode_opt1,
-            OptimizeLevel::Two => &self.resource.in_memory_bytecode_opt2,
-        } {
-            let ptr = unsafe {
-                pyffi::PyMemoryView_FromMemory(
-                    data.as_ptr() as _,
-                    data.len() as _,
-                    pyffi::PyBUF_READ,
-                )
-            };
+        let (field, data) = match optimize_level {
+            OptimizeLevel::Zero => (
+                ResourceField::InMemoryBytecode,
+                &self.resource.in_memory_bytecode,
+            ),
+            OptimizeLevel::One => (
+                ResourceField::InMemoryBytecodeOpt1,
+                &self.resource.in_memory_bytecode_opt1,
+            ),
+            OptimizeLevel::Two => (
+                ResourceField::InMemoryBytecodeOpt2,
+                &self.resource.in_memory_bytecode_opt2,
+            ),
+        };
 
-            Ok(unsafe { PyObject::from_owned_ptr_opt(py, ptr) })
+        if let Some(data) = data {
+            match self.resolve_payload(py, field, data)? {
+                PayloadData::Plaintext(data) => {
+                    let ptr = unsafe {
+                        pyffi::PyMemoryView_FromMemory(
+                            data.as_ptr() as _,
+                            data.len() as _,
+                            pyffi::PyBUF_READ,
+                        )
+                    };
+
+                    Ok(unsafe { PyObject::from_owned_ptr_opt(py, ptr) })
+                }
+                // Decrypted data is only alive for the duration of this call,
+                // so we need to copy it into a Python object. The copy is
+                // ordinary Python memory, which isn't locked or zeroed.
+                #[allow(unreachable_patterns)]
+                data => Ok(Some(PyBytes::new(py, &data).into_object())),
+            }
         } else if let Some(path) = self.bytecode_path(optimize_level) {
             // TODO we could potentially avoid the double allocation for bytecode
             // by reading directly into a buffer transferred to Python.
+            record_startup_access("open", &path.display().to_string());
             let bytecode = std::fs::read(&path).map_err(|e| {
                 PyErr::new::<ImportError, _>(
                     py,
@@ -401,7 +444,8 @@ where
 
     /// Directory from which relative paths should be evaluated.
     ///
-    /// Probably the directory of `current_exe`.
+    /// Probably the directory of `current_exe`, unless a filesystem
+    /// resources root is configured.
     pub origin: PathBuf,
 
     /// Named resources available for loading.
@@ -415,6 +459,14 @@ where
 
     /// Holds memory mapped file instances that resources data came from.
     backing_mmaps: Vec<memmap::Mmap>,
+
+    /// Bytecode magic number that indexed resources data must have been written with.
+    ///
+    /// If `None`, the bytecode magic number of indexed data is not validated.
+    pub expected_bytecode_magic_number: Option<u32>,
+
+    /// Decrypts encrypted resources payloads.
+    decryption: ResourcesDecryption,
 }
 
 impl<'a> Default for PythonResourcesState<'a, u8> {
@@ -425,6 +477,8 @@ impl<'a> Default for PythonResourcesState<'a, u8> {
             resources: HashMap::new(),
             backing_py_objects: vec![],
             backing_mmaps: vec![],
+            expected_bytecode_magic_number: None,
+            decryption: ResourcesDecryption::default(),
         }
     }
 }
@@ -439,7 +493,9 @@ impl<'a, 'config: 'a> TryFrom<&ResolvedOxidizedPythonInterpreterConfig<'config>>
     ) -> Result<Self, Self::Error> {
         let mut state = Self {
             current_exe: config.exe().clone(),
-            origin: config.origin().clone(),
+            origin: config.filesystem_resources_root().to_path_buf(),
+            expected_bytecode_magic_number: config.bytecode_magic_number,
+            decryption: ResourcesDecryption::new(config.resources_key_provider),
             ..Default::default()
         };
 
@@ -448,12 +504,12 @@ impl<'a, 'config: 'a> TryFrom<&ResolvedOxidizedPythonInterpreterConfig<'config>>
                 PackedResourcesSource::Memory(data) => {
                     state
                         .index_data(data)
-                        .map_err(NewInterpreterError::Simple)?;
+                        .map_err(|e| NewInterpreterError::Resources(e.to_string()))?;
                 }
                 PackedResourcesSource::MemoryMappedPath(path) => {
                     state
                         .index_path_memory_mapped(path)
-                        .map_err(NewInterpreterError::Dynamic)?;
+                        .map_err(NewInterpreterError::Resources)?;
                 }
             }
         }
@@ -488,7 +544,19 @@ impl<'a> PythonResourcesState<'a, u8> {
     /// on the incoming entry will overwrite fields on the existing entry.
     ///
     /// If an entry doesn't exist, the resource will be inserted as-is.
+    ///
+    /// If `expected_bytecode_magic_number` is set and the data records a
+    /// different bytecode magic number, an error is returned.
     pub fn index_data(&mut self, data: &'a [u8]) -> Result<(), &'static str> {
+        if let Some(expected) = self.expected_bytecode_magic_number {
+            if let Some(actual) = python_packed_resources::parser::read_bytecode_magic_number(data)?
+            {
+                if actual != expected {
+                    return Err("packed resources data has a bytecode magic number not matching the interpreter config; the packed resources data is stale");
+                }
+            }
+        }
+
         let resources = python_packed_resources::parser::load_resources(data)?;
 
         // Reserve space for expected number of incoming items so we can avoid extra
@@ -514,6 +582,7 @@ impl<'a> PythonResourcesState<'a, u8> {
     /// Load resources data from a filesystem path using memory mapped I/O.
     pub fn index_path_memory_mapped(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
         let path = path.as_ref();
+        record_startup_access("open", &path.display().to_string());
         let f = std::fs::File::open(path).map_err(|e| e.to_string())?;
 
         let mapped = unsafe { memmap::Mmap::map(&f) }.map_err(|e| e.to_string())?;
@@ -699,6 +768,7 @@ impl<'a> PythonResourcesState<'a, u8> {
                 resource,
                 current_exe: &self.current_exe,
                 origin: &self.origin,
+                decryption: &self.decryption,
                 flavor: ModuleFlavor::Builtin,
                 is_package: resource.is_package,
             })
@@ -707,6 +777,7 @@ impl<'a> PythonResourcesState<'a, u8> {
                 resource,
                 current_exe: &self.current_exe,
                 origin: &self.origin,
+                decryption: &self.decryption,
                 flavor: ModuleFlavor::Frozen,
                 is_package: resource.is_package,
             })
@@ -715,6 +786,7 @@ impl<'a> PythonResourcesState<'a, u8> {
                 resource,
                 current_exe: &self.current_exe,
                 origin: &self.origin,
+                decryption: &self.decryption,
                 flavor: ModuleFlavor::Extension,
                 is_package: resource.is_package,
             })
@@ -724,6 +796,7 @@ impl<'a> PythonResourcesState<'a, u8> {
                     resource,
                     current_exe: &self.current_exe,
                     origin: &self.origin,
+                    decryption: &self.decryption,
                     flavor: ModuleFlavor::SourceBytecode,
                     is_package: resource.is_package,
                 })
@@ -735,6 +808,27 @@ impl<'a> PythonResourcesState<'a, u8> {
         }
     }
 
+    /// Resolve the name of the module a module alias refers to.
+    ///
+    /// Returns `None` if `name` isn't a registered module alias.
+    pub fn resolve_module_alias(&self, name: &str) -> Option<&str> {
+        let name = name.strip_suffix(".__init__").unwrap_or(name);
+
+        self.resources
+            .get(name)
+            .and_then(|resource| resource.module_alias_target.as_deref())
+    }
+
+    /// Obtain the indexed entry points of a group.
+    ///
+    /// Returns `None` if no package distribution defines entry points in
+    /// the group.
+    pub fn entry_points(&self, group: &str) -> Option<&[EntryPoint<'a>]> {
+        self.resources
+            .get(group)
+            .and_then(|resource| resource.entry_points.as_deref())
+    }
+
     /// Obtain a single named resource in a package.
     ///
     /// Err occurs if loading the resource data fails. `Ok(None)` is returned
@@ -778,6 +872,15 @@ impl<'a> PythonResourcesState<'a, u8> {
         Ok(None)
     }
 
+    /// Obtain the data of a package resource registered for in-memory loading.
+    pub fn in_memory_package_resource(&self, package: &str, resource_name: &str) -> Option<&[u8]> {
+        self.resources
+            .get(package)
+            .and_then(|entry| entry.in_memory_package_resources.as_ref())
+            .and_then(|resources| resources.get(resource_name))
+            .map(|data| data.as_ref())
+    }
+
     /// Determines whether a specific package + name pair is a known Python package resource.
     pub fn is_package_resource(&self, package: &str, resource_name: &str) -> bool {
         if let Some(entry) = self.resources.get(package) {
@@ -799,24 +902,12 @@ impl<'a> PythonResourcesState<'a, u8> {
 
     /// Obtain the resources available in a Python package, as a Python list.
     ///
-    /// The names are returned in sorted order.
+    /// Only entries in the package directory itself are returned: resources
+    /// in sub-directories are represented by the sub-directory name. The
+    /// names are returned in sorted order.
     pub fn package_resource_names(&self, py: Python, package: &str) -> PyResult<PyObject> {
-        let entry = match self.resources.get(package) {
-            Some(entry) => entry,
-            None => return Ok(PyList::new(py, &[]).into_object()),
-        };
-
-        let mut names = if let Some(resources) = &entry.in_memory_package_resources {
-            resources.keys().collect()
-        } else if let Some(resources) = &entry.relative_path_package_resources {
-            resources.keys().collect()
-        } else {
-            vec![]
-        };
-
-        names.sort();
-
-        let names = names
+        let names = self
+            .package_resources_list_directory(package, "")
             .iter()
             .map(|x| x.to_py_object(py).into_object())
             .collect::<Vec<PyObject>>();
@@ -866,27 +957,17 @@ impl<'a> PythonResourcesState<'a, u8> {
             Some(format!("{}/", name))
         };
 
+        // Resources in sub-directories are represented by the sub-directory name.
         let filter_map_resource = |path: &'_ Cow<'_, str>| -> Option<String> {
-            match &prefix {
-                Some(prefix) => {
-                    if let Some(name) = path.strip_prefix(prefix) {
-                        if name.contains('/') {
-                            None
-                        } else {
-                            Some(name.to_string())
-                        }
-                    } else {
-                        None
-                    }
-                }
-                None => {
-                    // Empty string input matches root directory.
-                    if path.contains('/') {
-                        None
-                    } else {
-                        Some(path.to_string())
-                    }
-                }
+            let name = match &prefix {
+                Some(prefix) => path.strip_prefix(prefix.as_str())?,
+                // Empty string input matches root directory.
+                None => path.as_ref(),
+            };
+
+            match name.find('/') {
+                Some(index) => Some(name[0..index].to_string()),
+                None => Some(name.to_string()),
             }
         };
 
@@ -905,6 +986,39 @@ impl<'a> PythonResourcesState<'a, u8> {
         entries.into_iter().collect::<Vec<_>>()
     }
 
+    /// Resolve a path to an in-memory package or package resource.
+    ///
+    /// Paths prefixed with the current executable are interpreted the same
+    /// way as by [Self::resolve_resource_data_from_path]. Returns the package
+    /// name and the resource name within it. The resource name is empty if the
+    /// path refers to the package directory itself.
+    ///
+    /// `None` is returned if the path isn't prefixed with the current
+    /// executable or doesn't resolve to a known package.
+    pub fn resolve_in_memory_path(&self, path: &Path) -> Option<(String, String)> {
+        let relative_path = path.strip_prefix(&self.current_exe).ok()?;
+
+        let components = relative_path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
+
+        for split in (1..=components.len()).rev() {
+            let package_name = components[0..split].join(".");
+
+            if let Some(entry) = self.resources.get(package_name.as_str()) {
+                if !entry.is_package {
+                    return None;
+                }
+
+                // Internally resources are normalized to POSIX separators.
+                return Some((package_name, components[split..].join("/")));
+            }
+        }
+
+        None
+    }
+
     /// Attempt to resolve a PyBytes for resource data given a relative path.
     ///
     /// Raises OSerror on failure.
@@ -1391,6 +1505,46 @@ py_class!(pub(crate) class OxidizedResource |py| {
         }
     }
 
+    @property def module_alias_target(&self) -> PyResult<Option<String>> {
+        Ok(self.resource(py).borrow().module_alias_target.as_ref().map(|x| x.to_string()))
+    }
+
+    @module_alias_target.setter def set_module_alias_target(&self, value: Option<Option<String>>) -> PyResult<()> {
+        if let Some(value) = value {
+            self.resource(py).borrow_mut().module_alias_target = value.map(Cow::Owned);
+
+            Ok(())
+        } else {
+            Err(PyErr::new::<TypeError, _>(py, "cannot delete module_alias_target"))
+        }
+    }
+
+    @property def entry_points(&self) -> PyResult<Option<Vec<(String, String, String)>>> {
+        Ok(self.resource(py).borrow().entry_points.as_ref().map(|x| {
+            x.iter()
+                .map(|ep| (ep.name.to_string(), ep.value.to_string(), ep.distribution.to_string()))
+                .collect()
+        }))
+    }
+
+    @entry_points.setter def set_entry_points(&self, value: Option<Option<Vec<(String, String, String)>>>) -> PyResult<()> {
+        if let Some(value) = value {
+            self.resource(py).borrow_mut().entry_points = value.map(|x| {
+                x.into_iter()
+                    .map(|(name, value, distribution)| EntryPoint {
+                        distribution: Cow::Owned(distribution),
+                        name: Cow::Owned(name),
+                        value: Cow::Owned(value),
+                    })
+                    .collect()
+            });
+
+            Ok(())
+        } else {
+            Err(PyErr::new::<TypeError, _>(py, "cannot delete entry_points"))
+        }
+    }
+
     @property def relative_path_module_source(&self) -> PyResult<PyObject> {
         self.resource(py).borrow().relative_path_module_source.as_ref().map_or_else(
             || Ok(py.None()),
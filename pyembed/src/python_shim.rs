@@ -0,0 +1,157 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Support for a `python` compatible shim next to the current executable.
+
+Packages like `pip` and `ipykernel` re-invoke `sys.executable` with
+`python` arguments (e.g. `-m` and `-c`). This fails when `sys.executable`
+is an application executable with its own argument semantics.
+
+The shim is a hard link to (or a copy of) the application executable named
+`<name>-python`. When the executable detects that it was invoked through the
+shim, it behaves like `python`.
+*/
+
+use std::{
+    env::consts::EXE_SUFFIX,
+    path::{Path, PathBuf},
+};
+
+/// Suffix added to the file stem of an application executable to name its shim.
+const PYTHON_SHIM_SUFFIX: &str = "-python";
+
+/// Obtain the path of the python shim for an application executable.
+pub fn python_shim_path(app_exe: &Path) -> Option<PathBuf> {
+    let stem = app_exe.file_stem()?.to_str()?;
+
+    Some(app_exe.with_file_name(format!("{}{}{}", stem, PYTHON_SHIM_SUFFIX, EXE_SUFFIX)))
+}
+
+/// Resolve the application executable that a python shim belongs to.
+///
+/// Returns `None` if `exe` isn't a python shim. A shim must have a sibling
+/// file having its name without the `-python` suffix.
+pub fn python_shim_app_path(exe: &Path) -> Option<PathBuf> {
+    let stem = exe.file_stem()?.to_str()?;
+    let app_stem = stem.strip_suffix(PYTHON_SHIM_SUFFIX)?;
+
+    if app_stem.is_empty() {
+        return None;
+    }
+
+    let app_exe = exe.with_file_name(format!("{}{}", app_stem, EXE_SUFFIX));
+
+    if app_exe.is_file() {
+        Some(app_exe)
+    } else {
+        None
+    }
+}
+
+/// Whether the shim at `shim` is missing or doesn't match `app_exe`.
+///
+/// Hard links share metadata with the application executable. Copies are
+/// newer than it. So a shim older than the application executable or having
+/// a different size was left over from a previous version.
+fn is_stale(app_exe: &Path, shim: &Path) -> std::io::Result<bool> {
+    let shim_metadata = match std::fs::metadata(shim) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(true),
+        Err(e) => return Err(e),
+    };
+    let app_metadata = std::fs::metadata(app_exe)?;
+
+    Ok(shim_metadata.len() != app_metadata.len()
+        || shim_metadata.modified()? < app_metadata.modified()?)
+}
+
+/// Ensure the python shim for an application executable exists and is current.
+///
+/// The shim is created as a hard link if possible, falling back to a copy.
+/// Returns the path of the shim.
+pub fn ensure_python_shim(app_exe: &Path) -> std::io::Result<PathBuf> {
+    let shim = python_shim_path(app_exe).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "unable to derive python shim path",
+        )
+    })?;
+
+    if !is_stale(app_exe, &shim)? {
+        return Ok(shim);
+    }
+
+    if shim.exists() {
+        std::fs::remove_file(&shim)?;
+    }
+
+    if std::fs::hard_link(app_exe, &shim).is_err() {
+        // Copy to a temporary file and rename so concurrent processes never
+        // see a partially written shim.
+        let temp = shim.with_file_name(format!(
+            ".{}.{}",
+            shim.file_name()
+                .map(|x| x.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            std::process::id()
+        ));
+
+        std::fs::copy(app_exe, &temp)?;
+        std::fs::rename(&temp, &shim)?;
+    }
+
+    Ok(shim)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, anyhow::Result};
+
+    #[test]
+    fn test_shim_paths() -> Result<()> {
+        let temp_dir = tempfile::Builder::new().prefix("pyembed-test").tempdir()?;
+
+        let app_exe = temp_dir.path().join(format!("myapp{}", EXE_SUFFIX));
+        let shim = temp_dir.path().join(format!("myapp-python{}", EXE_SUFFIX));
+
+        assert_eq!(python_shim_path(&app_exe), Some(shim.clone()));
+
+        // The application executable must exist for a shim to be recognized.
+        assert_eq!(python_shim_app_path(&shim), None);
+        std::fs::write(&app_exe, b"app")?;
+        assert_eq!(python_shim_app_path(&shim), Some(app_exe.clone()));
+
+        assert_eq!(python_shim_app_path(&app_exe), None);
+        assert_eq!(
+            python_shim_app_path(&temp_dir.path().join(format!("-python{}", EXE_SUFFIX))),
+            None
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ensure_python_shim() -> Result<()> {
+        let temp_dir = tempfile::Builder::new().prefix("pyembed-test").tempdir()?;
+
+        let app_exe = temp_dir.path().join(format!("myapp{}", EXE_SUFFIX));
+        std::fs::write(&app_exe, b"version 1")?;
+
+        let shim = ensure_python_shim(&app_exe)?;
+        assert_eq!(python_shim_path(&app_exe), Some(shim.clone()));
+        assert_eq!(std::fs::read(&shim)?, b"version 1");
+
+        // Calling again leaves the shim alone.
+        ensure_python_shim(&app_exe)?;
+        assert_eq!(std::fs::read(&shim)?, b"version 1");
+
+        // Replacing the application executable replaces the shim.
+        std::fs::remove_file(&app_exe)?;
+        std::fs::write(&app_exe, b"version 2 is longer")?;
+        ensure_python_shim(&app_exe)?;
+        assert_eq!(std::fs::read(&shim)?, b"version 2 is longer");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Interactive REPL for `PythonRunMode::Repl`.
+
+The REPL is implemented with the `code` module. It runs in the `__main__`
+namespace like the `python` REPL does. When the `readline` module is
+available, it is configured for tab completion via `rlcompleter` and for
+persistent history.
+*/
+
+use {
+    cpython::{exc::SystemExit, ObjectProtocol, PyDict, PyErr, PyResult, Python},
+    std::path::Path,
+};
+
+/// Python code running the REPL.
+///
+/// It is run with `banner`, `startup_code`, `history_path`, and `app_name`
+/// defined as globals.
+const REPL_SOURCE: &str = r#"
+import code
+import os
+import sys
+import traceback
+
+import __main__
+
+namespace = __main__.__dict__
+
+try:
+    import readline
+except ImportError:
+    readline = None
+
+if readline is not None:
+    import atexit
+    import rlcompleter
+
+    readline.set_completer(rlcompleter.Completer(namespace).complete)
+    # libedit emulates readline with a different configuration syntax.
+    if "libedit" in (readline.__doc__ or ""):
+        readline.parse_and_bind("bind ^I rl_complete")
+    else:
+        readline.parse_and_bind("tab: complete")
+
+    if history_path is None:
+        history_path = os.path.join("~", ".%s_history" % app_name)
+    history_path = os.path.expanduser(history_path)
+
+    try:
+        readline.read_history_file(history_path)
+    except OSError:
+        pass
+
+    def write_history():
+        try:
+            readline.write_history_file(history_path)
+        except OSError:
+            pass
+
+    atexit.register(write_history)
+
+if startup_code:
+    try:
+        exec(compile(startup_code, "<repl_startup_code>", "exec"), namespace)
+    except SystemExit:
+        raise
+    except BaseException:
+        traceback.print_exc()
+
+if banner is None:
+    banner = 'Python %s on %s\nType "help", "copyright", "credits" or "license" for more information.' % (
+        sys.version,
+        sys.platform,
+    )
+
+code.InteractiveConsole(namespace).interact(banner=banner, exitmsg="")
+"#;
+
+/// Resolve the process exit code for an uncaught exception.
+///
+/// This mirrors how `python` handles `SystemExit`. Other exceptions are
+/// printed and result in exit code 1.
+fn exit_code_from_error(py: Python, err: PyErr) -> i32 {
+    if !err.matches(py, py.get_type::<SystemExit>()) {
+        err.print(py);
+        return 1;
+    }
+
+    let mut err = err;
+    let code = match err.instance(py).getattr(py, "code") {
+        Ok(code) => code,
+        Err(_) => return 1,
+    };
+
+    if code == py.None() {
+        0
+    } else if let Ok(value) = code.extract::<i32>(py) {
+        value
+    } else {
+        eprintln!("{}", code);
+        1
+    }
+}
+
+/// Run an interactive REPL until it is exited.
+///
+/// `history_path` of `None` uses `~/.<app_name>_history`. Returns a value
+/// suitable for use as a process exit code. End of input results in 0.
+pub(crate) fn run_repl(
+    py: Python,
+    banner: Option<&str>,
+    startup_code: Option<&str>,
+    history_path: Option<&Path>,
+    app_name: &str,
+) -> i32 {
+    let run = || -> PyResult<()> {
+        let globals = PyDict::new(py);
+        globals.set_item(py, "__builtins__", py.import("builtins")?)?;
+        globals.set_item(py, "banner", banner)?;
+        globals.set_item(py, "startup_code", startup_code)?;
+        globals.set_item(
+            py,
+            "history_path",
+            history_path.map(|p| p.display().to_string()),
+        )?;
+        globals.set_item(py, "app_name", app_name)?;
+
+        py.run(REPL_SOURCE, Some(&globals), None)
+    };
+
+    match run() {
+        Ok(()) => 0,
+        Err(err) => exit_code_from_error(py, err),
+    }
+}
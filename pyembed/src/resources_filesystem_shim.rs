@@ -0,0 +1,396 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Serve filesystem APIs from in-memory package resources.
+
+Packages imported from memory have a `__path__` formed by joining the
+current executable path and the package name. e.g. `/usr/bin/myapp/foo`.
+This path doesn't exist. Code deriving resource paths from it and
+performing filesystem I/O, such as `os.listdir()` on a templates directory,
+fails.
+
+The shim replaces `os.listdir()`, `os.scandir()`, `os.stat()`, `os.lstat()`,
+and `open()` with wrappers that serve paths under the current executable
+from the resources index, the same way `get_data()` does. All other calls are
+passed to the original functions. Functions built on top of these, such as
+`os.path.isdir()`, `os.path.getmtime()`, and `os.walk()`, work as a result.
+
+Python audit hooks can observe these calls but can't replace their results,
+which is why the functions are wrapped instead.
+
+The shim is opt-in: it changes global functions and only serves resources
+registered for in-memory loading.
+*/
+
+use {
+    crate::importer::ImporterState,
+    cpython::{
+        exc::{FileNotFoundError, IsADirectoryError, NotADirectoryError},
+        py_class, ObjectProtocol, PyBytes, PyClone, PyDict, PyErr, PyList, PyObject, PyResult,
+        PyString, PyTuple, Python, PythonObject, PythonObjectWithTypeObject, ToPyObject,
+    },
+    std::{cell::RefCell, collections::VecDeque, os::raw::c_int, path::PathBuf, sync::Arc},
+};
+
+const ENOENT: c_int = 2;
+const ENOTDIR: c_int = 20;
+const EISDIR: c_int = 21;
+
+/// `st_mode` reported for directories of resources.
+const DIRECTORY_MODE: u32 = 0o040_555;
+/// `st_mode` reported for resource files.
+const FILE_MODE: u32 = 0o100_444;
+
+/// A function replaced by the shim.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ShimFunction {
+    Listdir,
+    Scandir,
+    Stat,
+    Open,
+}
+
+/// Functions replaced by the shim, as (module, attribute, function).
+const SHIMMED_FUNCTIONS: &[(&str, &str, ShimFunction)] = &[
+    ("os", "listdir", ShimFunction::Listdir),
+    ("os", "scandir", ShimFunction::Scandir),
+    ("os", "stat", ShimFunction::Stat),
+    ("os", "lstat", ShimFunction::Stat),
+    ("builtins", "open", ShimFunction::Open),
+    ("io", "open", ShimFunction::Open),
+];
+
+/// What a path resolves to in the resources index.
+enum ResolvedPath {
+    Directory(Vec<String>),
+    File(Vec<u8>),
+    Missing,
+}
+
+/// Install the shim for resources indexed by an `OxidizedFinder`.
+///
+/// Each call wraps the current functions, so the shims of multiple finders
+/// can be installed.
+pub(crate) fn install_resources_filesystem_shim(
+    py: Python,
+    state: &Arc<ImporterState>,
+) -> PyResult<()> {
+    let mut wrapped: Vec<(PyObject, OxidizedFilesystemShim)> = vec![];
+
+    for (module_name, attribute, function) in SHIMMED_FUNCTIONS {
+        let module = py.import(module_name)?;
+        let original = module.get(py, attribute)?;
+
+        // Modules can share a function, e.g. `builtins.open` and `io.open`.
+        // Share the wrapper too so identity comparisons keep working.
+        let shim = match wrapped.iter().find(|(o, _)| o == &original) {
+            Some((_, shim)) => shim.clone_ref(py),
+            None => {
+                let shim = OxidizedFilesystemShim::create_instance(
+                    py,
+                    state.clone(),
+                    *function,
+                    original.clone_ref(py),
+                )?;
+                wrapped.push((original, shim.clone_ref(py)));
+
+                shim
+            }
+        };
+
+        module.add(py, attribute, shim)?;
+    }
+
+    Ok(())
+}
+
+// Wrapper around a filesystem function serving in-memory resources.
+py_class!(pub(crate) class OxidizedFilesystemShim |py| {
+    data state: Arc<ImporterState>;
+    data function: ShimFunction;
+    data original: PyObject;
+
+    def __call__(&self, *args, **kwargs) -> PyResult<PyObject> {
+        self.call_impl(py, args, kwargs)
+    }
+
+    @property def __wrapped__(&self) -> PyResult<PyObject> {
+        Ok(self.original(py).clone_ref(py))
+    }
+
+    def __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "<OxidizedFilesystemShim wrapping {}>",
+            self.original(py).repr(py)?.to_string_lossy(py)
+        ))
+    }
+});
+
+impl OxidizedFilesystemShim {
+    fn call_impl(&self, py: Python, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+        let function = *self.function(py);
+
+        let path_keyword = if function == ShimFunction::Open {
+            "file"
+        } else {
+            "path"
+        };
+
+        let path = if args.len(py) > 0 {
+            Some(args.get_item(py, 0))
+        } else {
+            kwargs.and_then(|kwargs| kwargs.get_item(py, path_keyword))
+        };
+
+        let resolved = match path {
+            Some(path) => self.resolve(py, &path)?,
+            None => None,
+        };
+
+        let (path, resolved) = match resolved {
+            Some(resolved) => resolved,
+            None => return self.original(py).call(py, args, kwargs),
+        };
+
+        match (function, resolved) {
+            (ShimFunction::Open, resolved) => {
+                let mode = if args.len(py) > 1 {
+                    args.get_item(py, 1).extract::<String>(py)?
+                } else if let Some(mode) = kwargs.and_then(|kwargs| kwargs.get_item(py, "mode")) {
+                    mode.extract::<String>(py)?
+                } else {
+                    "r".to_string()
+                };
+
+                // Writing is left to the original function, which will fail.
+                if mode.contains(|c| c != 'r' && c != 'b' && c != 't') {
+                    return self.original(py).call(py, args, kwargs);
+                }
+
+                match resolved {
+                    ResolvedPath::File(data) => {
+                        open_resource_data(py, &data, mode.contains('b'), args, kwargs)
+                    }
+                    ResolvedPath::Directory(_) => Err(os_error::<IsADirectoryError>(
+                        py,
+                        EISDIR,
+                        "Is a directory",
+                        &path,
+                    )),
+                    ResolvedPath::Missing => Err(os_error::<FileNotFoundError>(
+                        py,
+                        ENOENT,
+                        "No such file or directory",
+                        &path,
+                    )),
+                }
+            }
+            (_, ResolvedPath::Missing) => Err(os_error::<FileNotFoundError>(
+                py,
+                ENOENT,
+                "No such file or directory",
+                &path,
+            )),
+            (ShimFunction::Stat, ResolvedPath::Directory(_)) => stat_result(py, DIRECTORY_MODE, 0),
+            (ShimFunction::Stat, ResolvedPath::File(data)) => {
+                stat_result(py, FILE_MODE, data.len())
+            }
+            (ShimFunction::Listdir, ResolvedPath::Directory(entries)) => {
+                let entries = entries
+                    .iter()
+                    .map(|name| name.to_py_object(py).into_object())
+                    .collect::<Vec<_>>();
+
+                Ok(PyList::new(py, &entries).into_object())
+            }
+            (ShimFunction::Scandir, ResolvedPath::Directory(entries)) => {
+                let entries = entries
+                    .into_iter()
+                    .map(|name| {
+                        let child = format!("{}{}{}", path, std::path::MAIN_SEPARATOR, name);
+                        let is_dir = matches!(
+                            self.resolve(py, &child.to_py_object(py).into_object())?,
+                            Some((_, ResolvedPath::Directory(_)))
+                        );
+
+                        Ok(OxidizedDirEntry::create_instance(py, name, child, is_dir)?
+                            .into_object())
+                    })
+                    .collect::<PyResult<VecDeque<_>>>()?;
+
+                Ok(
+                    OxidizedScandirIterator::create_instance(py, RefCell::new(entries))?
+                        .into_object(),
+                )
+            }
+            (_, ResolvedPath::File(_)) => Err(os_error::<NotADirectoryError>(
+                py,
+                ENOTDIR,
+                "Not a directory",
+                &path,
+            )),
+        }
+    }
+
+    /// Resolve a path argument against the resources index.
+    ///
+    /// Returns `None` for arguments that aren't paths, such as file
+    /// descriptors, or that don't refer to in-memory resources.
+    fn resolve(&self, py: Python, path: &PyObject) -> PyResult<Option<(String, ResolvedPath)>> {
+        let path = match py.import("os")?.call(py, "fspath", (path,), None) {
+            Ok(path) => path,
+            Err(_) => return Ok(None),
+        };
+
+        // Only `str` paths are served, so returned names don't need encoding.
+        let path = match path.cast_as::<PyString>(py) {
+            Ok(path) => path.to_string(py)?.to_string(),
+            Err(_) => return Ok(None),
+        };
+
+        let resources = self.state(py).get_resources_state();
+
+        let (package, name) = match resources.resolve_in_memory_path(&PathBuf::from(&path)) {
+            Some(resolved) => resolved,
+            None => return Ok(None),
+        };
+
+        let resolved = if let Some(data) = resources.in_memory_package_resource(&package, &name) {
+            ResolvedPath::File(data.to_vec())
+        } else if name.is_empty() || resources.is_package_resource_directory(&package, &name) {
+            ResolvedPath::Directory(resources.package_resources_list_directory(&package, &name))
+        } else {
+            ResolvedPath::Missing
+        };
+
+        Ok(Some((path, resolved)))
+    }
+}
+
+/// Construct an `OSError` subclass from an errno, message, and filename.
+fn os_error<T: PythonObjectWithTypeObject>(
+    py: Python,
+    errno: c_int,
+    message: &str,
+    path: &str,
+) -> PyErr {
+    PyErr::new::<T, _>(py, (errno, message, path))
+}
+
+/// Construct an `os.stat_result` for a resource.
+fn stat_result(py: Python, mode: u32, size: usize) -> PyResult<PyObject> {
+    // (st_mode, st_ino, st_dev, st_nlink, st_uid, st_gid, st_size, st_atime, st_mtime, st_ctime)
+    let values = [u64::from(mode), 0, 0, 1, 0, 0, size as u64, 0, 0, 0]
+        .iter()
+        .map(|value| value.to_py_object(py).into_object())
+        .collect::<Vec<_>>();
+
+    py.import("os")?
+        .call(py, "stat_result", (PyTuple::new(py, &values),), None)
+}
+
+/// Open resource data like `open()` would.
+///
+/// `args` and `kwargs` are the arguments to `open()`. Text encoding
+/// arguments are passed to `io.TextIOWrapper`.
+fn open_resource_data(
+    py: Python,
+    data: &[u8],
+    binary: bool,
+    args: &PyTuple,
+    kwargs: Option<&PyDict>,
+) -> PyResult<PyObject> {
+    let io_module = py.import("io")?;
+    let bytes_io = io_module.call(py, "BytesIO", (PyBytes::new(py, data),), None)?;
+
+    if binary {
+        return Ok(bytes_io);
+    }
+
+    // open(file, mode, buffering, encoding, errors, newline, ...)
+    let text_kwargs = PyDict::new(py);
+    for (index, keyword) in [(3, "encoding"), (4, "errors"), (5, "newline")].iter() {
+        let value = if args.len(py) > *index {
+            Some(args.get_item(py, *index))
+        } else {
+            kwargs.and_then(|kwargs| kwargs.get_item(py, keyword))
+        };
+
+        if let Some(value) = value {
+            text_kwargs.set_item(py, keyword, value)?;
+        }
+    }
+
+    io_module.call(py, "TextIOWrapper", (bytes_io,), Some(&text_kwargs))
+}
+
+// Minimal os.DirEntry for in-memory resources.
+py_class!(pub(crate) class OxidizedDirEntry |py| {
+    data entry_name: String;
+    data entry_path: String;
+    data directory: bool;
+
+    @property def name(&self) -> PyResult<String> {
+        Ok(self.entry_name(py).clone())
+    }
+
+    @property def path(&self) -> PyResult<String> {
+        Ok(self.entry_path(py).clone())
+    }
+
+    def is_dir(&self, follow_symlinks: bool = true) -> PyResult<bool> {
+        Ok(*self.directory(py))
+    }
+
+    def is_file(&self, follow_symlinks: bool = true) -> PyResult<bool> {
+        Ok(!*self.directory(py))
+    }
+
+    def is_symlink(&self) -> PyResult<bool> {
+        Ok(false)
+    }
+
+    def inode(&self) -> PyResult<u64> {
+        Ok(0)
+    }
+
+    def stat(&self, follow_symlinks: bool = true) -> PyResult<PyObject> {
+        py.import("os")?.call(py, "stat", (self.entry_path(py),), None)
+    }
+
+    def __fspath__(&self) -> PyResult<String> {
+        Ok(self.entry_path(py).clone())
+    }
+
+    def __repr__(&self) -> PyResult<String> {
+        Ok(format!("<OxidizedDirEntry '{}'>", self.entry_name(py)))
+    }
+});
+
+// Iterator returned by the shimmed os.scandir().
+py_class!(pub(crate) class OxidizedScandirIterator |py| {
+    data entries: RefCell<VecDeque<PyObject>>;
+
+    def __iter__(&self) -> PyResult<OxidizedScandirIterator> {
+        Ok(self.clone_ref(py))
+    }
+
+    def __next__(&self) -> PyResult<Option<PyObject>> {
+        Ok(self.entries(py).borrow_mut().pop_front())
+    }
+
+    def __enter__(&self) -> PyResult<OxidizedScandirIterator> {
+        Ok(self.clone_ref(py))
+    }
+
+    def __exit__(&self, _exc_type: PyObject, _exc_value: PyObject, _traceback: PyObject) -> PyResult<bool> {
+        self.entries(py).borrow_mut().clear();
+        Ok(false)
+    }
+
+    def close(&self) -> PyResult<PyObject> {
+        self.entries(py).borrow_mut().clear();
+        Ok(py.None())
+    }
+});
@@ -0,0 +1,268 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Record filesystem, environment, and network access during startup.
+
+When enabled, a Python audit hook is installed before the interpreter is
+initialized. It records files opened, directories listed, and socket
+operations until the interpreter starts running what it is configured to
+run. For `run_module`, the window includes the import of the module.
+
+With the `startup-trace` feature, filesystem and environment access
+performed by pyembed itself is also recorded and the audit can be enabled
+by the `PYEMBED_STARTUP_AUDIT_PATH` environment variable, which takes
+precedence over the interpreter config.
+
+The audit is written to a file as it happens, one entry per line. Each
+entry is a kind and a detail separated by a tab. The final entry has kind
+`end` and names the event that ended the startup window.
+*/
+
+use {
+    crate::error::NewInterpreterError,
+    cpython::{ObjectProtocol, PyObject, PyTuple, Python, PythonObject},
+    once_cell::sync::Lazy,
+    python3_sys as pyffi,
+    std::{
+        ffi::CStr,
+        fs::File,
+        io::Write,
+        os::raw::{c_char, c_int, c_void},
+        path::{Path, PathBuf},
+        sync::Mutex,
+    },
+};
+
+/// Environment variable enabling the audit in builds with the `startup-trace` feature.
+pub const STARTUP_AUDIT_PATH_ENV: &str = "PYEMBED_STARTUP_AUDIT_PATH";
+
+type AuditHookFunction =
+    unsafe extern "C" fn(*const c_char, *mut pyffi::PyObject, *mut c_void) -> c_int;
+
+extern "C" {
+    fn PySys_AddAuditHook(hook: AuditHookFunction, user_data: *mut c_void) -> c_int;
+}
+
+/// Audit events marking the start of running what the interpreter is configured to run.
+const END_EVENTS: &[&str] = &[
+    "cpython.run_command",
+    "cpython.run_file",
+    "cpython.run_interactivehook",
+    "cpython.run_stdin",
+];
+
+struct StartupAudit {
+    fh: File,
+    /// Whether `cpython.run_module` was seen.
+    ///
+    /// The window then ends when `runpy` starts executing the imported module.
+    running_module: bool,
+}
+
+impl StartupAudit {
+    fn write(&mut self, kind: &str, detail: &str) {
+        // Errors are ignored: auditing must never break the application.
+        let _ = self.fh.write_all(format_entry(kind, detail).as_bytes());
+    }
+}
+
+static STARTUP_AUDIT: Lazy<Mutex<Option<StartupAudit>>> = Lazy::new(|| Mutex::new(None));
+
+/// Format an audit entry as a line.
+///
+/// Backslashes, tabs, and newlines in the detail are escaped so each entry
+/// occupies exactly one line.
+fn format_entry(kind: &str, detail: &str) -> String {
+    let detail = detail
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n");
+
+    format!("{}\t{}\n", kind, detail)
+}
+
+/// Resolve the audit path from the environment.
+///
+/// Always `None` unless the `startup-trace` feature is enabled.
+pub(crate) fn startup_audit_path_from_env() -> Option<PathBuf> {
+    if cfg!(feature = "startup-trace") {
+        std::env::var_os(STARTUP_AUDIT_PATH_ENV).map(PathBuf::from)
+    } else {
+        None
+    }
+}
+
+/// Start recording to a file, replacing any existing content.
+pub(crate) fn start_startup_audit(path: &Path) -> Result<(), NewInterpreterError> {
+    let fh = File::create(path).map_err(|e| {
+        NewInterpreterError::Dynamic(format!(
+            "error creating startup audit file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+
+    STARTUP_AUDIT.lock().unwrap().replace(StartupAudit {
+        fh,
+        running_module: false,
+    });
+
+    Ok(())
+}
+
+/// Install the audit hook.
+///
+/// Must be called after the memory allocators are configured, as the hook
+/// entry is allocated with, and freed during finalization by, the raw
+/// allocator.
+pub(crate) fn install_startup_audit_hook() -> Result<(), NewInterpreterError> {
+    if unsafe { PySys_AddAuditHook(audit_hook, std::ptr::null_mut()) } != 0 {
+        Err(NewInterpreterError::Simple(
+            "unable to install startup audit hook",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Stop recording.
+pub(crate) fn stop_startup_audit() {
+    STARTUP_AUDIT.lock().unwrap().take();
+}
+
+/// Record access performed by pyembed itself.
+///
+/// This is a no-op unless the `startup-trace` feature is enabled and the
+/// audit is recording.
+pub(crate) fn record_startup_access(kind: &str, detail: &str) {
+    if cfg!(feature = "startup-trace") {
+        if let Ok(mut state) = STARTUP_AUDIT.try_lock() {
+            if let Some(audit) = state.as_mut() {
+                audit.write(kind, detail);
+            }
+        }
+    }
+}
+
+/// Whether the currently executing Python frame is `runpy._run_code()`.
+unsafe fn in_runpy_run_code(py: Python) -> bool {
+    let frame = pyffi::PyEval_GetFrame();
+    if frame.is_null() || (*frame).f_code.is_null() {
+        return false;
+    }
+
+    let name = PyObject::from_borrowed_ptr(py, (*(*frame).f_code).co_name);
+
+    matches!(name.extract::<String>(py), Ok(name) if name == "_run_code")
+}
+
+/// Render the first audit event argument, which is a path for filesystem events.
+fn first_arg(py: Python, args: &PyTuple) -> String {
+    if args.len(py) == 0 {
+        return String::new();
+    }
+
+    match args.get_item(py, 0).str(py) {
+        Ok(s) => s.to_string_lossy(py).to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+unsafe extern "C" fn audit_hook(
+    event: *const c_char,
+    args: *mut pyffi::PyObject,
+    _user_data: *mut c_void,
+) -> c_int {
+    let event = match CStr::from_ptr(event).to_str() {
+        Ok(event) => event,
+        Err(_) => return 0,
+    };
+
+    // Rendering arguments can run Python code, which can raise events of its
+    // own. Those are ignored rather than deadlocking.
+    let mut state = match STARTUP_AUDIT.try_lock() {
+        Ok(state) => state,
+        Err(_) => return 0,
+    };
+    let audit = match state.as_mut() {
+        Some(audit) => audit,
+        None => return 0,
+    };
+
+    let py = Python::assume_gil_acquired();
+
+    if END_EVENTS.contains(&event)
+        || (event == "exec" && audit.running_module && in_runpy_run_code(py))
+    {
+        audit.write("end", event);
+        state.take();
+        return 0;
+    }
+
+    let kind = match event {
+        "cpython.run_module" => {
+            audit.running_module = true;
+            return 0;
+        }
+        "open" => "open",
+        "os.listdir" | "os.scandir" => "listdir",
+        _ if event.starts_with("socket.") => "socket",
+        _ => return 0,
+    };
+
+    let args = PyObject::from_borrowed_ptr(py, args);
+    let args = match args.cast_into::<PyTuple>(py) {
+        Ok(args) => args,
+        Err(_) => return 0,
+    };
+
+    let detail = if kind == "socket" {
+        let rendered = args
+            .as_object()
+            .repr(py)
+            .map(|s| s.to_string_lossy(py).to_string())
+            .unwrap_or_default();
+        format!("{} {}", event, rendered)
+    } else {
+        first_arg(py, &args)
+    };
+
+    audit.write(kind, &detail);
+
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_entry() {
+        assert_eq!(format_entry("open", "/etc/hosts"), "open\t/etc/hosts\n");
+        assert_eq!(
+            format_entry("open", "C:\\a\tb\nc"),
+            "open\tC:\\\\a\\tb\\nc\n"
+        );
+    }
+
+    #[test]
+    fn test_record_startup_access() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempfile::Builder::new().prefix("pyembed-test").tempdir()?;
+        let path = temp_dir.path().join("audit");
+
+        start_startup_audit(&path)?;
+        record_startup_access("env", "TERMINFO_DIRS");
+        stop_startup_audit();
+        record_startup_access("env", "IGNORED");
+
+        let expected = if cfg!(feature = "startup-trace") {
+            "env\tTERMINFO_DIRS\n"
+        } else {
+            ""
+        };
+        assert_eq!(std::fs::read_to_string(&path)?, expected);
+
+        Ok(())
+    }
+}
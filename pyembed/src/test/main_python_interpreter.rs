@@ -4,8 +4,21 @@
 
 use {
     super::{default_interpreter_config, run_py_test},
-    crate::MainPythonInterpreter,
+    crate::{
+        InitFailureHandler, InterpreterHooks, MainPythonInterpreter, PackedResourcesSource,
+        PyTaskError, PythonRunMode, SysExecutableMode, FILESYSTEM_RESOURCES_ROOT_MARKER,
+    },
+    cpython::ObjectProtocol,
+    python_packed_resources::{data::Resource, writer::write_packed_resources_v3},
     rusty_fork::rusty_fork_test,
+    std::{
+        borrow::Cow,
+        path::Path,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    },
 };
 
 rusty_fork_test! {
@@ -17,8 +30,405 @@ rusty_fork_test! {
         py.import("sys").unwrap();
     }
 
+    #[cfg(feature = "serialization")]
+    #[test]
+    fn test_call_py_function() {
+        let config = default_interpreter_config();
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+
+        let task = interp.call_py_function::<_, String>("os.path.join", &("a", "b"));
+        assert_eq!(task.wait(), Ok(format!("a{}b", std::path::MAIN_SEPARATOR)));
+
+        let task = interp.call_py_function::<_, i64>("math.isqrt", &"bad");
+        assert!(matches!(task.wait(), Err(PyTaskError::Python(_))));
+
+        let task = interp.call_py_function::<_, i64>("does_not_exist.func", &());
+        assert!(matches!(task.wait(), Err(PyTaskError::Python(_))));
+    }
+
+    #[test]
+    fn test_hooks_ordering() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let pre_init_counter = counter.clone();
+        let post_init_counter = counter.clone();
+        let pre_finalize_counter = counter.clone();
+
+        let mut config = default_interpreter_config();
+        config.post_init_callable = Some("builtins.hook_post_init".to_string());
+
+        let hooks = InterpreterHooks {
+            pre_init: Some(Box::new(move |config| {
+                assert_eq!(pre_init_counter.fetch_add(1, Ordering::SeqCst), 0);
+                config.sys_frozen = true;
+                Ok(())
+            })),
+            post_init: Some(Box::new(move |py| {
+                assert_eq!(post_init_counter.fetch_add(1, Ordering::SeqCst), 1);
+                py.run(
+                    "import builtins\n\
+                     def hook_post_init():\n    builtins.hook_called = True\n\
+                     builtins.hook_post_init = hook_post_init",
+                    None,
+                    None,
+                )
+            })),
+            pre_finalize: Some(Box::new(move |_| {
+                assert_eq!(pre_finalize_counter.fetch_add(1, Ordering::SeqCst), 2);
+            })),
+        };
+
+        let mut interp = MainPythonInterpreter::new_with_hooks(config, hooks).unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+
+        let py = interp.acquire_gil();
+        let sys = py.import("sys").unwrap();
+        assert!(sys.get(py, "frozen").unwrap().extract::<bool>(py).unwrap());
+        let builtins = py.import("builtins").unwrap();
+        assert!(builtins.get(py, "hook_called").unwrap().extract::<bool>(py).unwrap());
+
+        drop(interp);
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_hooks_errors_abort() {
+        let hooks = InterpreterHooks {
+            pre_init: Some(Box::new(|_| Err("nope".to_string()))),
+            ..InterpreterHooks::default()
+        };
+
+        let err = MainPythonInterpreter::new_with_hooks(default_interpreter_config(), hooks)
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "during pre-init hook: nope");
+    }
+
+    #[test]
+    fn test_post_init_callable_missing() {
+        let mut config = default_interpreter_config();
+        config.post_init_callable = Some("does_not_exist.func".to_string());
+
+        let err = MainPythonInterpreter::new(config).err().unwrap();
+        assert!(err.to_string().contains("resolving callable does_not_exist.func"));
+    }
+
+    #[test]
+    fn test_init_failure_exit_code() {
+        let mut config = default_interpreter_config();
+        config.post_init_callable = Some("does_not_exist.func".to_string());
+
+        let handler = InitFailureHandler::from(&config);
+        let err = MainPythonInterpreter::new(config).err().unwrap();
+        assert_eq!(handler.exit_code(&err), 70);
+        assert!(handler
+            .message(&err)
+            .starts_with("error instantiating embedded Python interpreter: during resolving callable"));
+    }
+
+    #[test]
+    fn test_invalid_resources_exit_code() {
+        let mut config = default_interpreter_config();
+        config.oxidized_importer = true;
+        config.failure_exit_code_base = 100;
+        config
+            .packed_resources
+            .push(PackedResourcesSource::Memory(b"not packed resources"));
+
+        let handler = InitFailureHandler::from(&config);
+        let err = MainPythonInterpreter::new(config).err().unwrap();
+        assert_eq!(handler.exit_code(&err), 101);
+        assert!(handler.message(&err).starts_with(
+            "error instantiating embedded Python interpreter: invalid resources: "
+        ));
+    }
+
+    #[test]
+    fn test_python_exit_code_passthrough() {
+        let mut config = default_interpreter_config();
+        config.interpreter_config.run_command = Some("raise SystemExit(2)".to_string());
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+        assert_eq!(interp.run(), 2);
+    }
+
+    #[test]
+    fn test_sys_executable_none() {
+        let mut config = default_interpreter_config();
+        config.sys_executable_mode = SysExecutableMode::None;
+
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+        let py = interp.acquire_gil();
+        let sys = py.import("sys").unwrap();
+        assert!(sys.get(py, "executable").unwrap().is_none(py));
+    }
+
+    #[test]
+    fn test_sys_executable_python_shim() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyembed-test")
+            .tempdir()
+            .unwrap();
+        let app_exe = temp_dir
+            .path()
+            .join(format!("myapp{}", std::env::consts::EXE_SUFFIX));
+        std::fs::write(&app_exe, b"app").unwrap();
+
+        let mut config = default_interpreter_config();
+        config.exe = Some(app_exe);
+        config.sys_executable_mode = SysExecutableMode::PythonShim;
+
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+        let py = interp.acquire_gil();
+        let sys = py.import("sys").unwrap();
+        let executable = sys
+            .get(py, "executable")
+            .unwrap()
+            .extract::<String>(py)
+            .unwrap();
+
+        let shim = dunce::canonicalize(temp_dir.path())
+            .unwrap()
+            .join(format!("myapp-python{}", std::env::consts::EXE_SUFFIX));
+        assert_eq!(executable, shim.display().to_string());
+        assert_eq!(std::fs::read(&shim).unwrap(), b"app");
+    }
+
+    #[test]
+    fn test_startup_audit() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyembed-test")
+            .tempdir()
+            .unwrap();
+        let audit_path = temp_dir.path().join("audit");
+        let startup_path = temp_dir.path().join("startup.txt");
+        let run_path = temp_dir.path().join("run.txt");
+        std::fs::write(&startup_path, b"").unwrap();
+        std::fs::write(&run_path, b"").unwrap();
+
+        let mut config = default_interpreter_config();
+        config.startup_audit_path = Some(audit_path.clone());
+        config.interpreter_config.run_command = Some(format!(
+            "open({:?}).close()",
+            run_path.display().to_string()
+        ));
+
+        let startup_code = format!("open({:?}).close()", startup_path.display().to_string());
+        let hooks = InterpreterHooks {
+            post_init: Some(Box::new(move |py| py.run(&startup_code, None, None))),
+            ..InterpreterHooks::default()
+        };
+
+        let interp = MainPythonInterpreter::new_with_hooks(config, hooks).unwrap();
+        assert_eq!(interp.py_runmain(), 0);
+
+        let audit = std::fs::read_to_string(&audit_path).unwrap();
+        let lines = audit.lines().collect::<Vec<_>>();
+
+        // Backslashes in recorded paths are escaped.
+        let escaped = |p: &std::path::Path| p.display().to_string().replace('\\', "\\\\");
+
+        // Access before the run command is recorded. Access after isn't.
+        assert!(lines.contains(&format!("open\t{}", escaped(&startup_path)).as_str()));
+        assert!(!audit.contains(&escaped(&run_path)));
+        assert_eq!(lines.last(), Some(&"end\tcpython.run_command"));
+    }
+
+    #[test]
+    fn test_filesystem_resources_root_env() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyembed-test")
+            .tempdir()
+            .unwrap();
+        let origin = temp_dir.path().join("app");
+        let shared = temp_dir.path().join("shared");
+        std::fs::create_dir_all(origin.join("lib")).unwrap();
+        std::fs::create_dir_all(&shared).unwrap();
+        std::fs::write(origin.join("lib").join("relocated.py"), b"VALUE = 42\n").unwrap();
+
+        let mut packed = vec![];
+        write_packed_resources_v3(
+            &[Resource {
+                name: Cow::from("relocated"),
+                is_module: true,
+                relative_path_module_source: Some(Cow::from(Path::new("lib/relocated.py"))),
+                ..Resource::default()
+            }],
+            &mut packed,
+            None,
+        )
+        .unwrap();
+
+        // Move the resources to a location unrelated to the executable.
+        std::fs::rename(origin.join("lib"), shared.join("lib")).unwrap();
+        std::fs::write(shared.join(FILESYSTEM_RESOURCES_ROOT_MARKER), b"").unwrap();
+        std::env::set_var("PYEMBED_TEST_RESOURCES_ROOT", &shared);
+
+        let mut config = default_interpreter_config();
+        config.origin = Some(origin);
+        config.oxidized_importer = true;
+        config.resources_root_env = Some("PYEMBED_TEST_RESOURCES_ROOT".to_string());
+        config.packed_resources = vec![PackedResourcesSource::Memory(&packed)];
+
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+        let py = interp.acquire_gil();
+
+        let module = py.import("relocated").unwrap();
+        assert_eq!(
+            module.get(py, "VALUE").unwrap().extract::<i64>(py).unwrap(),
+            42
+        );
+
+        let file = module
+            .get(py, "__file__")
+            .unwrap()
+            .extract::<String>(py)
+            .unwrap();
+        assert_eq!(
+            Path::new(&file),
+            shared.join("lib").join("relocated.py").as_path()
+        );
+    }
+
+    #[test]
+    fn test_run_repl_startup_code_exit() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyembed-test")
+            .tempdir()
+            .unwrap();
+
+        let mut config = default_interpreter_config();
+        config.run_mode = PythonRunMode::Repl;
+        config.repl_banner = Some(String::new());
+        config.repl_startup_code = Some("value = 5\nraise SystemExit(value)".to_string());
+        config.repl_history_path = Some(temp_dir.path().join("history"));
+
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+        assert_eq!(interp.run_repl(), 5);
+
+        // Startup code runs in the __main__ namespace.
+        let py = interp.acquire_gil();
+        let main = py.import("__main__").unwrap();
+        assert_eq!(main.get(py, "value").unwrap().extract::<i64>(py).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_excluded_encodings() {
+        let mut config = default_interpreter_config();
+        config.excluded_encodings = vec!["fictional_codec".to_string()];
+
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+        let py = interp.acquire_gil();
+
+        py.run(
+            "import codecs\n\
+            codecs.lookup('utf-8')\n\
+            try:\n    codecs.lookup('Fictional-Codec')\n\
+            except LookupError as e:\n    assert 'minimal_encodings()' in str(e), str(e)\n\
+            try:\n    codecs.lookup('unknown-codec')\n\
+            except LookupError as e:\n    assert 'minimal_encodings()' not in str(e), str(e)\n",
+            None,
+            None,
+        )
+        .unwrap();
+    }
+
     #[test]
     fn multiprocessing_py() {
         run_py_test("test_multiprocessing.py").unwrap()
     }
 }
+
+// Executor threads block on the GIL if the main thread doesn't release it.
+// The timeout turns such a deadlock into a test failure.
+rusty_fork_test! {
+    #![rusty_fork(timeout_ms = 60000)]
+
+    #[test]
+    fn test_spawn_blocking_py_concurrent() {
+        let config = default_interpreter_config();
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+
+        let tasks = (0..16)
+            .map(|i| {
+                interp.spawn_blocking_py(move |py| {
+                    py.eval(&format!("{} * 2", i), None, None)
+                        .unwrap()
+                        .extract::<i64>(py)
+                        .unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let results = tasks
+            .into_iter()
+            .map(|task| task.wait().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(results, (0..16).map(|i| i * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_spawn_blocking_py_panic() {
+        let config = default_interpreter_config();
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+
+        let task = interp.spawn_blocking_py(|_| -> i32 { panic!("oops") });
+        assert_eq!(task.wait(), Err(PyTaskError::Panic("oops".to_string())));
+
+        // The executor remains usable after a panic.
+        let task = interp.spawn_blocking_py(|py| py.import("sys").is_ok());
+        assert_eq!(task.wait(), Ok(true));
+    }
+
+    #[test]
+    fn test_spawn_blocking_py_outstanding_on_drop() {
+        let config = default_interpreter_config();
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+
+        // Hold the GIL on the main thread until the executor takes over.
+        interp.acquire_gil().import("sys").unwrap();
+
+        let tasks = (0..32)
+            .map(|_| {
+                interp.spawn_blocking_py(|py| {
+                    py.run("import time; time.sleep(0.01)", None, None).is_ok()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        drop(interp);
+
+        for task in tasks {
+            match task.wait() {
+                Ok(value) => assert!(value),
+                Err(e) => assert_eq!(e, PyTaskError::Shutdown),
+            }
+        }
+    }
+
+    #[test]
+    fn test_spawn_blocking_py_main_thread_interleaved() {
+        let config = default_interpreter_config();
+        let mut interp = MainPythonInterpreter::new(config).unwrap();
+
+        for i in 0..4 {
+            let task = interp.spawn_blocking_py(move |py| {
+                py.eval(&format!("{} + 1", i), None, None)
+                    .unwrap()
+                    .extract::<i64>(py)
+                    .unwrap()
+            });
+            assert_eq!(task.wait(), Ok(i + 1));
+
+            // The main thread can take the GIL back between handoffs.
+            let py = interp.acquire_gil();
+            assert_eq!(
+                py.eval("2 * 3", None, None)
+                    .unwrap()
+                    .extract::<i64>(py)
+                    .unwrap(),
+                6
+            );
+        }
+    }
+}
@@ -16,6 +16,7 @@ import sys
 import tempfile
 import unittest
 
+import oxidized_importer
 from oxidized_importer import (
     OxidizedDistribution,
     OxidizedFinder,
@@ -195,6 +196,60 @@ class TestImporterMetadata(unittest.TestCase):
         self.assertEqual(ep.value, "my_package:module")
         self.assertEqual(ep.group, "console_scripts")
 
+    def _write_plugin_distribution(self, name, entry_points):
+        dist_info = self.td / ("%s-1.0.dist-info" % name)
+        dist_info.mkdir()
+
+        with (dist_info / "METADATA").open("w", encoding="utf-8") as fh:
+            fh.write("Name: %s\n" % name)
+            fh.write("Version: 1.0\n")
+
+        with (dist_info / "entry_points.txt").open("w", encoding="utf-8") as fh:
+            fh.write(entry_points)
+
+    def test_entry_points_index(self):
+        self._write_plugin_distribution(
+            "myapp_foo", "[myapp.plugins]\nfoo = myapp_foo.plugin:Plugin\n"
+        )
+        self._write_plugin_distribution(
+            "myapp_bar",
+            "[myapp.plugins]\nbar = myapp_bar:Plugin\nfoo = myapp_foo.plugin:Plugin\n",
+        )
+
+        f = self._finder_from_td()
+
+        # Distributions are ordered by name.
+        self.assertEqual(
+            [d.metadata["Name"] for d in f.find_distributions()],
+            ["myapp_bar", "myapp_foo"],
+        )
+
+        expected = [
+            ("bar", "myapp_bar:Plugin", "myapp_bar"),
+            ("foo", "myapp_foo.plugin:Plugin", "myapp_bar"),
+        ]
+
+        self.assertEqual(f.entry_points("myapp.plugins"), expected)
+        self.assertEqual(f.entry_points("missing"), [])
+
+        resources = {r.name: r for r in f.indexed_resources()}
+        self.assertEqual(resources["myapp.plugins"].entry_points, expected)
+
+        sys.meta_path.insert(0, f)
+
+        self.assertEqual(oxidized_importer.entry_points("myapp.plugins"), expected)
+
+        eps = importlib.metadata.entry_points()
+        if hasattr(eps, "select"):
+            eps = eps.select(group="myapp.plugins")
+        else:
+            eps = eps.get("myapp.plugins", ())
+
+        self.assertEqual(
+            sorted({(ep.name, ep.value) for ep in eps}),
+            [(name, value) for name, value, _ in expected],
+        )
+
     def test_requires_missing(self):
         self._write_metadata()
         f = self._finder_from_td()
@@ -14,6 +14,7 @@ import unittest
 
 from oxidized_importer import (
     OxidizedFinder,
+    OxidizedResource,
     OxidizedResourceCollector,
     find_resources_in_path,
 )
@@ -182,6 +183,43 @@ class TestImporterModuleLoading(unittest.TestCase):
         self.assertIn("dotinit.bar", sys.modules)
         self.assertNotIn("dotinit.__init__", sys.modules)
 
+    def test_module_alias(self):
+        p = self._make_package("aliastarget")
+
+        with (p / "__init__.py").open("wb") as fh:
+            fh.write(b"loads = []\nloads.append(1)\n")
+
+        f = self._finder_from_td()
+
+        r = OxidizedResource()
+        r.name = "aliasname"
+        r.module_alias_target = "aliastarget"
+        self.assertEqual(r.module_alias_target, "aliastarget")
+        f.add_resource(r)
+
+        spec = f.find_spec("aliasname", None)
+        self.assertIsInstance(spec, importlib.machinery.ModuleSpec)
+        self.assertEqual(spec.name, "aliasname")
+        self.assertIsInstance(spec.loader, OxidizedFinder)
+
+        sys.meta_path.insert(0, f)
+
+        self.assertNotIn("aliasname", sys.modules)
+        self.assertNotIn("aliastarget", sys.modules)
+
+        alias = importlib.import_module("aliasname")
+        target = importlib.import_module("aliastarget")
+
+        self.assertIs(alias, target)
+        self.assertIs(sys.modules["aliasname"], sys.modules["aliastarget"])
+        self.assertEqual(target.__name__, "aliastarget")
+        self.assertEqual(target.__spec__.name, "aliastarget")
+        # The module was only executed once.
+        self.assertEqual(target.loads, [1])
+
+        del sys.modules["aliasname"]
+        del sys.modules["aliastarget"]
+
 
 if __name__ == "__main__":
     unittest.main()
@@ -267,10 +267,16 @@ class TestImporterPkgResources(unittest.TestCase):
         self.assertTrue(provider.resource_isdir("subdir\\grandchild\\"))
 
         self.assertEqual(provider.resource_listdir("missing"), [])
-        self.assertEqual(provider.resource_listdir(""), ["resource0.txt"])
-        self.assertEqual(provider.resource_listdir("subdir"), ["child0.txt"])
-        self.assertEqual(provider.resource_listdir("subdir/"), ["child0.txt"])
-        self.assertEqual(provider.resource_listdir("subdir\\"), ["child0.txt"])
+        self.assertEqual(provider.resource_listdir(""), ["resource0.txt", "subdir"])
+        self.assertEqual(
+            provider.resource_listdir("subdir"), ["child0.txt", "grandchild"]
+        )
+        self.assertEqual(
+            provider.resource_listdir("subdir/"), ["child0.txt", "grandchild"]
+        )
+        self.assertEqual(
+            provider.resource_listdir("subdir\\"), ["child0.txt", "grandchild"]
+        )
         self.assertEqual(
             provider.resource_listdir("subdir/grandchild"), ["grandchild.txt"]
         )
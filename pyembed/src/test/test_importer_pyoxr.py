@@ -0,0 +1,148 @@
+# This Source Code Form is subject to the terms of the Mozilla Public
+# License, v. 2.0. If a copy of the MPL was not distributed with this
+# file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+import importlib
+import importlib.machinery
+import importlib.resources
+import os
+import pathlib
+import pkgutil
+import sys
+import tempfile
+import unittest
+
+from oxidized_importer import (
+    OxidizedFinder,
+    OxidizedPathEntryFinder,
+    OxidizedResourceCollector,
+    find_resources_in_path,
+    pyoxr_path_hook,
+)
+
+
+class TestImporterPyoxr(unittest.TestCase):
+    def setUp(self):
+        self.raw_temp_dir = tempfile.TemporaryDirectory(
+            prefix="oxidized_importer-test-"
+        )
+        self.td = pathlib.Path(self.raw_temp_dir.name)
+
+        self.old_finders = list(sys.meta_path)
+        self.old_path = list(sys.path)
+        self.old_path_hooks = list(sys.path_hooks)
+        self.old_modules = set(sys.modules)
+
+        # Stock interpreters have PathFinder, which consults sys.path_hooks.
+        if importlib.machinery.PathFinder not in sys.meta_path:
+            sys.meta_path.append(importlib.machinery.PathFinder)
+
+        # The hook is registered automatically when loaded into a stock
+        # interpreter. But not when embedded.
+        if pyoxr_path_hook not in sys.path_hooks:
+            sys.path_hooks.insert(0, pyoxr_path_hook)
+
+        sys.path_importer_cache.clear()
+
+    def tearDown(self):
+        sys.path[:] = self.old_path
+        sys.path_hooks[:] = self.old_path_hooks
+        sys.meta_path[:] = self.old_finders
+        sys.path_importer_cache.clear()
+
+        for name in set(sys.modules) - self.old_modules:
+            del sys.modules[name]
+
+        self.raw_temp_dir.cleanup()
+        del self.raw_temp_dir
+        del self.td
+
+    def _make_bundle(self):
+        source_dir = self.td / "src"
+        package_path = source_dir / "pyoxr_pkg"
+        (package_path / "sub").mkdir(parents=True)
+
+        (package_path / "__init__.py").write_text("VALUE = 'pkg'\n")
+        (package_path / "mod.py").write_text("VALUE = 'mod'\n")
+        (package_path / "sub" / "__init__.py").write_text("VALUE = 'sub'\n")
+        (package_path / "data.txt").write_text("resource data")
+
+        collector = OxidizedResourceCollector(allowed_locations=["in-memory"])
+        for r in find_resources_in_path(source_dir):
+            collector.add_in_memory(r)
+
+        f = OxidizedFinder()
+        f.add_resources(collector.oxidize()[0])
+
+        bundle = self.td / "bundle.pyoxr"
+        bundle.write_bytes(f.serialize_indexed_resources())
+
+        return bundle
+
+    def test_hook_rejects_other_paths(self):
+        bundle = self._make_bundle()
+
+        for path in (
+            str(self.td),
+            str(self.td / "missing.pyoxr"),
+            str(bundle) + "x",
+            str(bundle) + ".zip",
+            b"bytes.pyoxr",
+        ):
+            with self.assertRaises(ImportError):
+                pyoxr_path_hook(path)
+
+    def test_hook_accepts_bundle_paths(self):
+        bundle = self._make_bundle()
+
+        f = pyoxr_path_hook(str(bundle))
+        self.assertIsInstance(f, OxidizedPathEntryFinder)
+        self.assertIsNone(f._package)
+
+        f = pyoxr_path_hook(os.path.join(str(bundle), "pyoxr_pkg", "sub"))
+        self.assertEqual(f._package, "pyoxr_pkg.sub")
+
+    def test_import(self):
+        bundle = self._make_bundle()
+        sys.path.insert(0, str(bundle))
+
+        import pyoxr_pkg
+        import pyoxr_pkg.mod
+        import pyoxr_pkg.sub
+
+        self.assertEqual(pyoxr_pkg.VALUE, "pkg")
+        self.assertEqual(pyoxr_pkg.mod.VALUE, "mod")
+        self.assertEqual(pyoxr_pkg.sub.VALUE, "sub")
+        self.assertEqual(
+            pyoxr_pkg.__path__, [os.path.join(str(bundle), "pyoxr_pkg")]
+        )
+
+    def test_iter_modules(self):
+        bundle = self._make_bundle()
+
+        self.assertEqual(
+            [m.name for m in pkgutil.iter_modules([str(bundle)])], ["pyoxr_pkg"]
+        )
+
+        self.assertEqual(
+            sorted(
+                (m.name, m.ispkg)
+                for m in pkgutil.iter_modules(
+                    [os.path.join(str(bundle), "pyoxr_pkg")]
+                )
+            ),
+            [("mod", False), ("sub", True)],
+        )
+
+    def test_resources(self):
+        bundle = self._make_bundle()
+        sys.path.insert(0, str(bundle))
+
+        self.assertEqual(
+            importlib.resources.read_text("pyoxr_pkg", "data.txt"), "resource data"
+        )
+        self.assertTrue(importlib.resources.is_resource("pyoxr_pkg", "data.txt"))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -2,9 +2,12 @@
 # License, v. 2.0. If a copy of the MPL was not distributed with this
 # file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+import builtins
+import importlib.util
 import io
 import os
 import pathlib
+import sys
 import tempfile
 import unittest
 
@@ -120,11 +123,195 @@ class TestImporterResourceReading(unittest.TestCase):
         self.assertTrue(r.is_resource("child0/a.txt"))
         self.assertTrue(r.is_resource("child1/b.txt"))
 
-        self.assertEqual(r.contents(), ["child0/a.txt", "child1/b.txt"])
+        self.assertEqual(r.contents(), ["child0", "child1"])
 
         self.assertEqual(r.open_resource("child0/a.txt").getvalue(), b"a")
         self.assertEqual(r.open_resource("child1/b.txt").getvalue(), b"b")
 
+    def _make_templates_package(self):
+        p = self._make_package("my_package")
+
+        (p / "templates" / "layouts").mkdir(parents=True)
+
+        with (p / "resource.txt").open("wb") as fh:
+            fh.write(b"my resource")
+        with (p / "templates" / "index.html").open("wb") as fh:
+            fh.write(b"{% extends 'layouts/base.html' %}{% block body %}{{ name }}{% endblock %}")
+        with (p / "templates" / "layouts" / "base.html").open("wb") as fh:
+            fh.write(b"<p>{% block body %}{% endblock %}</p>")
+
+    def _install_finder(self, f):
+        sys.meta_path.insert(0, f)
+
+        def cleanup():
+            sys.meta_path.remove(f)
+            sys.modules.pop("my_package", None)
+
+        self.addCleanup(cleanup)
+
+    def _install_shim(self, f):
+        originals = [
+            (os, "listdir", os.listdir),
+            (os, "scandir", os.scandir),
+            (os, "stat", os.stat),
+            (os, "lstat", os.lstat),
+            (builtins, "open", builtins.open),
+            (io, "open", io.open),
+        ]
+
+        def cleanup():
+            for module, attr, value in originals:
+                setattr(module, attr, value)
+
+        self.addCleanup(cleanup)
+
+        f.install_resources_filesystem_shim()
+
+    def test_nested_directories(self):
+        self._make_templates_package()
+
+        f = self._finder_from_td()
+        r = f.get_resource_reader("my_package")
+
+        self.assertEqual(r.contents(), ["resource.txt", "templates"])
+        self.assertTrue(r.is_resource("templates/layouts/base.html"))
+
+    def test_files(self):
+        self._make_templates_package()
+
+        f = self._finder_from_td()
+        root = f.get_resource_reader("my_package").files()
+
+        self.assertTrue(root.is_dir())
+        self.assertFalse(root.is_file())
+        self.assertEqual(root.name, "my_package")
+        self.assertEqual(
+            [(p.name, p.is_dir()) for p in root.iterdir()],
+            [("resource.txt", False), ("templates", True)],
+        )
+
+        templates = root / "templates"
+        self.assertTrue(templates.is_dir())
+        self.assertEqual(
+            [p.name for p in templates.iterdir()], ["index.html", "layouts"]
+        )
+
+        base = root.joinpath("templates", "layouts/base.html")
+        self.assertEqual(base.name, "base.html")
+        self.assertTrue(base.is_file())
+        self.assertFalse(base.is_dir())
+        self.assertEqual(
+            base.read_bytes(), b"<p>{% block body %}{% endblock %}</p>"
+        )
+        self.assertEqual(
+            base.read_text(encoding="utf-8"), "<p>{% block body %}{% endblock %}</p>"
+        )
+
+        with base.open("rb") as fh:
+            self.assertEqual(fh.read(), b"<p>{% block body %}{% endblock %}</p>")
+        with (root / "resource.txt").open("r", encoding="utf-8") as fh:
+            self.assertEqual(fh.read(), "my resource")
+
+        missing = templates / "missing.html"
+        self.assertFalse(missing.is_file())
+        self.assertFalse(missing.is_dir())
+        with self.assertRaises(FileNotFoundError):
+            missing.read_bytes()
+
+        with self.assertRaises(NotADirectoryError):
+            base.iterdir()
+
+        with self.assertRaises(ValueError):
+            root / ".."
+
+    def test_filesystem_shim(self):
+        self._make_templates_package()
+
+        f = self._finder_from_td()
+        self._install_finder(f)
+
+        spec = importlib.util.find_spec("my_package")
+        package_path = spec.submodule_search_locations[0]
+        templates = os.path.join(package_path, "templates")
+        base = os.path.join(templates, "layouts", "base.html")
+
+        # The synthetic path doesn't exist without the shim.
+        self.assertFalse(os.path.isdir(templates))
+
+        self._install_shim(f)
+
+        self.assertTrue(os.path.isdir(package_path))
+        self.assertTrue(os.path.isdir(templates))
+        self.assertFalse(os.path.isfile(templates))
+        self.assertTrue(os.path.isfile(base))
+        self.assertFalse(os.path.exists(os.path.join(templates, "missing.html")))
+        self.assertEqual(os.path.getsize(base), 37)
+
+        self.assertEqual(os.listdir(package_path), ["resource.txt", "templates"])
+        self.assertEqual(os.listdir(templates), ["index.html", "layouts"])
+        with self.assertRaises(NotADirectoryError):
+            os.listdir(base)
+        with self.assertRaises(FileNotFoundError):
+            os.listdir(os.path.join(templates, "missing"))
+
+        with os.scandir(templates) as it:
+            entries = sorted((e.name, e.is_dir(), e.path) for e in it)
+        self.assertEqual(
+            entries,
+            [
+                ("index.html", False, os.path.join(templates, "index.html")),
+                ("layouts", True, os.path.join(templates, "layouts")),
+            ],
+        )
+
+        self.assertEqual(
+            sorted(
+                os.path.relpath(os.path.join(root, name), templates)
+                for root, _, files in os.walk(templates)
+                for name in files
+            ),
+            ["index.html", os.path.join("layouts", "base.html")],
+        )
+
+        with open(base, "rb") as fh:
+            self.assertEqual(fh.read(), b"<p>{% block body %}{% endblock %}</p>")
+        with open(os.path.join(package_path, "resource.txt"), encoding="utf-8") as fh:
+            self.assertEqual(fh.read(), "my resource")
+        with self.assertRaises(IsADirectoryError):
+            open(templates, "rb")
+        with self.assertRaises(FileNotFoundError):
+            open(os.path.join(templates, "missing.html"))
+
+        # Other paths are passed through.
+        self.assertEqual(
+            sorted(os.listdir(self.td)), ["__pycache__", "my_package"]
+            if (self.td / "__pycache__").exists()
+            else ["my_package"],
+        )
+        with open(self.td / "my_package" / "resource.txt", "rb") as fh:
+            self.assertEqual(fh.read(), b"my resource")
+
+    @unittest.skipIf(importlib.util.find_spec("jinja2") is None, "jinja2 not available")
+    def test_jinja2_package_loader(self):
+        import jinja2
+
+        self._make_templates_package()
+
+        f = self._finder_from_td()
+        self._install_finder(f)
+        self._install_shim(f)
+
+        env = jinja2.Environment(
+            loader=jinja2.PackageLoader("my_package", "templates")
+        )
+
+        self.assertEqual(
+            sorted(env.list_templates()), ["index.html", "layouts/base.html"]
+        )
+        self.assertEqual(
+            env.get_template("index.html").render(name="world"), "<p>world</p>"
+        )
+
 
 if __name__ == "__main__":
     unittest.main()
@@ -4,6 +4,7 @@
 
 use {
     crate::{
+        cli_generate,
         environment::{default_target_triple, PYOXIDIZER_VERSION},
         logging, project_building, project_layout, projectmgmt,
     },
@@ -11,6 +12,7 @@ use {
     clap::{App, AppSettings, Arg, ArgMatches, SubCommand},
     std::{
         collections::HashMap,
+        ffi::OsString,
         path::{Path, PathBuf},
     },
 };
@@ -41,6 +43,11 @@ existing PyOxidizer enabled project.
 
 This command will invoke Rust's build system tool (Cargo) to build
 the project.
+
+With --sandbox, the config file is evaluated with restricted privileges:
+filesystem reads are limited to the config file's directory, operations
+spawning subprocesses are disabled, and network access is disabled.
+Additional privileges can be granted via --trust-file.
 ";
 
 const INIT_RUST_PROJECT_ABOUT: &str = "\
@@ -62,6 +69,57 @@ they were created with.
 On success, instructions on potential next steps are printed.
 ";
 
+const INSTALL_ABOUT: &str = "\
+Build a target and install its files into a directory.
+
+The target is resolved (building it if needed) and the files in its build
+output directory are copied into the directory given by --prefix. Existing
+files are replaced.
+
+A receipt listing every installed file and its SHA-256 digest is written
+to the prefix directory, or to the path given by --receipt. The receipt
+can be passed to `pyoxidizer uninstall` to remove the installed files.
+
+Files that can't be installed, e.g. due to missing permissions, are
+reported individually and don't prevent other files from being installed.
+";
+
+const UNINSTALL_ABOUT: &str = "\
+Remove files installed by `pyoxidizer install`.
+
+Every file listed in the receipt is removed, along with directories left
+empty. Files whose content changed since they were installed are kept and
+reported. The receipt is removed once no installed files remain.
+";
+
+const STRIP_RESOURCES_ABOUT: &str = "\
+Remove unused payloads from built packed resources data.
+
+The input is either a standalone packed resources file or an executable
+embedding packed resources data. Bytecode for optimization levels not given
+to --keep-bytecode-levels is removed, as is module source when
+--strip-source is given.
+
+Stripping a packed resources file writes a new packed resources file.
+Stripping an executable writes a copy of the executable in which the
+embedded data is rewritten in place and padded to its original size. Such
+copies need to be signed again if the original executable was signed.
+";
+
+const RESOURCES_DIFF_ABOUT: &str = "\
+Show differences between the packed resources of two builds.
+
+Each input is an executable embedding packed resources data, a standalone
+packed resources file or a report written by `pyoxidizer analyze
+--resources-report`.
+
+Added, removed and changed resources are printed along with the payloads
+that changed within them, such as source, bytecode for an optimization
+level or a package resource. Payloads are compared by content hash and by
+location. The content of payloads loaded from the filesystem isn't part of
+packed resources data, so only their locations are compared.
+";
+
 const RUN_BUILD_SCRIPT_ABOUT: &str = "\
 Runs a crate build script to generate Python artifacts.
 
@@ -122,6 +180,41 @@ be `None` instead of a `string`.
 If a Starlark variable is defined multiple times, an error occurs.
 ";
 
+const FEATURE_HELP: &str = "\
+Enables a named feature flag.
+
+Enabled features are available to Starlark as the sorted FEATURES list.
+`PythonPackagingPolicy.require_feature()` drops resources of packages
+requiring a feature that isn't enabled.
+
+This argument can be specified multiple times.
+";
+
+const STRICT_CONFIG_HELP: &str = "\
+Evaluate the configuration file in strict mode.
+
+Setting an unknown attribute on a value is an error suggesting the closest
+known attribute. Resources returned by functions like
+`PythonExecutable.pip_install()` that are never added to anything are
+reported as warnings when evaluation finishes. Deprecation warnings are
+errors.
+
+Strict mode can also be enabled from Starlark via `set_strict(True)`.
+";
+
+const GENERATE_COMPLETIONS_ABOUT: &str = "\
+Print a shell completion script for pyoxidizer.
+
+Completions are derived from the command line definitions. The bash and
+fish scripts also complete target names for `build`, `install`, and
+`run --target` when the current directory contains a pyoxidizer.bzl file,
+by running `pyoxidizer list-targets --format names`.
+
+e.g. to install bash completions:
+
+  pyoxidizer generate-completions bash > /usr/share/bash-completion/completions/pyoxidizer
+";
+
 fn add_env_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
     app.arg(
         Arg::with_name("vars")
@@ -139,6 +232,27 @@ fn add_env_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
             .help("Define an environment variable in Starlark environment")
             .long_help(ENV_VAR_HELP),
     )
+    .arg(
+        Arg::with_name("features")
+            .long("feature")
+            .value_name("name")
+            .multiple(true)
+            .number_of_values(1)
+            .help("Enable a feature flag in Starlark environment")
+            .long_help(FEATURE_HELP),
+    )
+    .arg(
+        Arg::with_name("strict_config")
+            .long("strict-config")
+            .help("Evaluate the configuration file in strict mode")
+            .long_help(STRICT_CONFIG_HELP),
+    )
+}
+
+fn starlark_features(args: &ArgMatches) -> Vec<String> {
+    args.values_of("features")
+        .map(|values| values.map(|x| x.to_string()).collect())
+        .unwrap_or_default()
 }
 
 fn starlark_vars(args: &ArgMatches) -> Result<HashMap<String, Option<String>>> {
@@ -167,13 +281,12 @@ fn starlark_vars(args: &ArgMatches) -> Result<HashMap<String, Option<String>>> {
     Ok(res)
 }
 
-pub fn run_cli() -> Result<()> {
-    let mut env = crate::environment::Environment::new()?;
-
-    let matches = App::new("PyOxidizer")
+/// Construct the definition of the command line interface.
+pub fn app(long_version: &str) -> App<'_, '_> {
+    App::new("PyOxidizer")
         .setting(AppSettings::ArgRequiredElseHelp)
         .version(PYOXIDIZER_VERSION)
-        .long_version(env.pyoxidizer_source.version_long().as_str())
+        .long_version(long_version)
         .author("Gregory Szorc <gregory.szorc@gmail.com>")
         .long_about("Build and distribute Python applications")
         .arg(
@@ -204,7 +317,14 @@ pub fn run_cli() -> Result<()> {
             SubCommand::with_name("analyze")
                 .about("Analyze a built binary")
                 .setting(AppSettings::ArgRequiredElseHelp)
-                .arg(Arg::with_name("path").help("Path to executable to analyze")),
+                .arg(Arg::with_name("path").help("Path to executable to analyze"))
+                .arg(
+                    Arg::with_name("resources_report")
+                        .long("resources-report")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .help("Write a JSON report of embedded packed resources to this path"),
+                ),
         )
         .subcommand(add_env_args(
             SubCommand::with_name("build")
@@ -230,6 +350,46 @@ pub fn run_cli() -> Result<()> {
                         .value_name("PATH")
                         .help("Directory containing project to build"),
                 )
+                .arg(
+                    Arg::with_name("sandbox")
+                        .long("sandbox")
+                        .help("Restrict privileged operations when evaluating the config file"),
+                )
+                .arg(
+                    Arg::with_name("trust_file")
+                        .long("trust-file")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .requires("sandbox")
+                        .help("File granting privileges to sandboxed evaluation"),
+                )
+                .arg(
+                    Arg::with_name("emit_depfile")
+                        .long("emit-depfile")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .help("Write a Makefile style file listing the build's input files"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["human", "json"])
+                        .default_value("human")
+                        .help("Format of the build timings summary"),
+                )
+                .arg(
+                    Arg::with_name("trace_out")
+                        .long("trace-out")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .help("Write build timings to a Chrome trace-event file"),
+                )
+                .arg(
+                    Arg::with_name("verify_vendored")
+                        .long("verify-vendored")
+                        .help("Verify vendored projects are up to date instead of writing them"),
+                )
                 .arg(
                     Arg::with_name("targets")
                         .value_name("TARGET")
@@ -238,7 +398,34 @@ pub fn run_cli() -> Result<()> {
                 ),
         ))
         .subcommand(
-            SubCommand::with_name("cache-clear").about("Clear PyOxidizer's user-specific cache"),
+            SubCommand::with_name("cache-clear")
+                .about("Clear PyOxidizer's user-specific cache")
+                .arg(
+                    Arg::with_name("named_caches")
+                        .long("named-cache")
+                        .value_name("NAME")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Only remove the named cache with this name"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compatibility")
+                .about("Show versions of components compatible with this PyOxidizer")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["human", "json"])
+                        .default_value("human")
+                        .help("Output format"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .value_name("PATH")
+                        .help("Path to a Rust project to check for compatibility"),
+                ),
         )
         .subcommand(
             SubCommand::with_name("find-resources")
@@ -278,6 +465,29 @@ pub fn run_cli() -> Result<()> {
                     "Filesystem path to scan for resources. Must be a directory or Python wheel",
                 )),
         )
+        .subcommand(
+            SubCommand::with_name("generate-completions")
+                .about("Print a shell completion script")
+                .long_about(GENERATE_COMPLETIONS_ABOUT)
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .arg(
+                    Arg::with_name("shell")
+                        .required(true)
+                        .possible_values(&cli_generate::SHELLS)
+                        .help("Shell to generate completions for"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("generate-man-pages")
+                .about("Write man pages for pyoxidizer and its subcommands")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .arg(
+                    Arg::with_name("dest_dir")
+                        .required(true)
+                        .value_name("DIR")
+                        .help("Directory to write man pages to"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("init-config-file")
                 .setting(AppSettings::ArgRequiredElseHelp)
@@ -315,10 +525,66 @@ pub fn run_cli() -> Result<()> {
                         .help("Path of project directory to create"),
                 ),
         )
+        .subcommand(add_env_args(
+            SubCommand::with_name("install")
+                .about("Build a target and install its files into a directory")
+                .long_about(INSTALL_ABOUT)
+                .arg(
+                    Arg::with_name("target_triple")
+                        .long("target-triple")
+                        .takes_value(true)
+                        .help("Rust target triple to build for"),
+                )
+                .arg(
+                    Arg::with_name("release")
+                        .long("release")
+                        .help("Install a release build"),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .long("path")
+                        .takes_value(true)
+                        .default_value(".")
+                        .value_name("PATH")
+                        .help("Directory containing project to build"),
+                )
+                .arg(
+                    Arg::with_name("prefix")
+                        .long("prefix")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("PATH")
+                        .help("Directory to install files into"),
+                )
+                .arg(
+                    Arg::with_name("receipt")
+                        .long("receipt")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .help("Where to write the install receipt"),
+                )
+                .arg(
+                    Arg::with_name("dry_run")
+                        .long("dry-run")
+                        .help("Print what would be installed without installing"),
+                )
+                .arg(
+                    Arg::with_name("target")
+                        .value_name("TARGET")
+                        .help("Target to install"),
+                ),
+        ))
         .subcommand(
             SubCommand::with_name("list-targets")
-                .setting(AppSettings::ArgRequiredElseHelp)
                 .about("List targets available to resolve in a configuration file")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["human", "json", "names"])
+                        .default_value("human")
+                        .help("Output format"),
+                )
                 .arg(
                     Arg::with_name("path")
                         .default_value(".")
@@ -367,6 +633,38 @@ pub fn run_cli() -> Result<()> {
                         .help("Path to Python distribution to analyze"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("resources-diff")
+                .about("Show differences between the packed resources of two builds")
+                .long_about(RESOURCES_DIFF_ABOUT)
+                .arg(
+                    Arg::with_name("old")
+                        .required(true)
+                        .value_name("OLD")
+                        .help("Executable, packed resources file or resources report to compare from"),
+                )
+                .arg(
+                    Arg::with_name("new")
+                        .required(true)
+                        .value_name("NEW")
+                        .help("Executable, packed resources file or resources report to compare to"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["human", "json"])
+                        .default_value("human")
+                        .help("Output format"),
+                )
+                .arg(
+                    Arg::with_name("only_packages")
+                        .long("only-packages")
+                        .takes_value(true)
+                        .value_name("PACKAGES")
+                        .help("Comma delimited packages to limit the comparison to"),
+                ),
+        )
         .subcommand(add_env_args(
             SubCommand::with_name("run-build-script")
                 .setting(AppSettings::ArgRequiredElseHelp)
@@ -384,6 +682,56 @@ pub fn run_cli() -> Result<()> {
                         .help("The config file target to resolve"),
                 ),
         ))
+        .subcommand(
+            SubCommand::with_name("strip-resources")
+                .about("Remove unused payloads from built packed resources data")
+                .long_about(STRIP_RESOURCES_ABOUT)
+                .arg(
+                    Arg::with_name("path")
+                        .required(true)
+                        .value_name("PATH")
+                        .help("Executable or packed resources file to strip"),
+                )
+                .arg(
+                    Arg::with_name("keep_bytecode_levels")
+                        .long("keep-bytecode-levels")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("LEVELS")
+                        .help("Comma delimited bytecode optimization levels to keep (0, 1, 2)"),
+                )
+                .arg(
+                    Arg::with_name("strip_source")
+                        .long("strip-source")
+                        .help("Remove in-memory module source"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("PATH")
+                        .help("Path to write the stripped file to"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("uninstall")
+                .about("Remove files installed by `pyoxidizer install`")
+                .long_about(UNINSTALL_ABOUT)
+                .arg(
+                    Arg::with_name("receipt")
+                        .long("receipt")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("PATH")
+                        .help("Install receipt listing the files to remove"),
+                )
+                .arg(
+                    Arg::with_name("dry_run")
+                        .long("dry-run")
+                        .help("Print what would be removed without removing"),
+                ),
+        )
         .subcommand(add_env_args(
             SubCommand::with_name("run")
                 .setting(AppSettings::TrailingVarArg)
@@ -412,9 +760,41 @@ pub fn run_cli() -> Result<()> {
                         .takes_value(true)
                         .help("Build target to run"),
                 )
-                .arg(Arg::with_name("extra").multiple(true)),
+                .arg(
+                    Arg::with_name("cwd")
+                        .long("cwd")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .help(
+                            "Working directory to run the target from (defaults to the current directory)",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("trace_startup")
+                        .long("trace-startup")
+                        .help("Report files, environment variables, and sockets accessed during startup"),
+                )
+                .arg(
+                    Arg::with_name("wasm_runtime")
+                        .long("wasm-runtime")
+                        .takes_value(true)
+                        .value_name("COMMAND")
+                        .help("Command running executables built for WASI targets (defaults to wasmtime)"),
+                )
+                .arg(
+                    Arg::with_name("extra")
+                        .multiple(true)
+                        .last(true)
+                        .help("Arguments to pass to the target (after --)"),
+                ),
         ))
-        .get_matches();
+}
+
+pub fn run_cli() -> Result<()> {
+    let mut env = crate::environment::Environment::new()?;
+    let long_version = env.pyoxidizer_source.version_long();
+
+    let matches = app(&long_version).get_matches();
 
     let verbose = matches.is_present("verbose");
 
@@ -440,19 +820,28 @@ pub fn run_cli() -> Result<()> {
         ("analyze", Some(args)) => {
             let path = args.value_of("path").unwrap();
             let path = PathBuf::from(path);
-            tugger_binary_analysis::analyze_file(path);
+            let resources_report = args.value_of("resources_report").map(Path::new);
+            tugger_binary_analysis::analyze_file(path.clone());
 
-            Ok(())
+            projectmgmt::analyze_packed_resources(&path, resources_report)
         }
 
         ("build", Some(args)) => {
             let starlark_vars = starlark_vars(args)?;
+            let features = starlark_features(args);
+            let strict = args.is_present("strict_config");
             let release = args.is_present("release");
             let target_triple = args.value_of("target_triple");
             let path = args.value_of("path").unwrap();
             let resolve_targets = args
                 .values_of("targets")
                 .map(|values| values.map(|x| x.to_string()).collect());
+            let sandbox = args.is_present("sandbox");
+            let trust_file = args.value_of("trust_file").map(Path::new);
+            let emit_depfile = args.value_of("emit_depfile").map(Path::new);
+            let format = args.value_of("format").unwrap();
+            let trace_out = args.value_of("trace_out").map(Path::new);
+            let verify_vendored = args.is_present("verify_vendored");
 
             projectmgmt::build(
                 &env,
@@ -461,12 +850,34 @@ pub fn run_cli() -> Result<()> {
                 target_triple,
                 resolve_targets,
                 starlark_vars,
+                features,
+                strict,
                 release,
                 verbose,
+                sandbox,
+                trust_file,
+                emit_depfile,
+                format,
+                trace_out,
+                verify_vendored,
             )
         }
 
-        ("cache-clear", Some(_)) => projectmgmt::cache_clear(&env),
+        ("cache-clear", Some(args)) => {
+            let named_caches = args
+                .values_of("named_caches")
+                .map(|x| x.collect::<Vec<_>>())
+                .unwrap_or_default();
+
+            projectmgmt::cache_clear(&env, &named_caches)
+        }
+
+        ("compatibility", Some(args)) => {
+            let path = args.value_of("path").map(Path::new);
+            let format = args.value_of("format").unwrap();
+
+            projectmgmt::compatibility(path, format)
+        }
 
         ("find-resources", Some(args)) => {
             let path = args.value_of("path").map(|value| Path::new(value));
@@ -493,6 +904,22 @@ pub fn run_cli() -> Result<()> {
             }
         }
 
+        ("generate-completions", Some(args)) => {
+            let shell = args.value_of("shell").unwrap();
+
+            cli_generate::write_completions(&mut app(&long_version), shell, &mut std::io::stdout())
+        }
+
+        ("generate-man-pages", Some(args)) => {
+            let dest_dir = args.value_of("dest_dir").unwrap();
+
+            for path in cli_generate::write_man_pages(&app(&long_version), Path::new(dest_dir))? {
+                println!("wrote {}", path.display());
+            }
+
+            Ok(())
+        }
+
         ("init-config-file", Some(args)) => {
             let code = args.value_of("python-code");
             let pip_install = if args.is_present("pip-install") {
@@ -506,10 +933,40 @@ pub fn run_cli() -> Result<()> {
             projectmgmt::init_config_file(&env.pyoxidizer_source, &config_path, code, &pip_install)
         }
 
+        ("install", Some(args)) => {
+            let starlark_vars = starlark_vars(args)?;
+            let features = starlark_features(args);
+            let strict = args.is_present("strict_config");
+            let target_triple = args.value_of("target_triple");
+            let release = args.is_present("release");
+            let path = args.value_of("path").unwrap();
+            let target = args.value_of("target");
+            let prefix = args.value_of("prefix").unwrap();
+            let receipt = args.value_of("receipt").map(Path::new);
+            let dry_run = args.is_present("dry_run");
+
+            projectmgmt::install(
+                &env,
+                &logger_context.logger,
+                Path::new(path),
+                target_triple,
+                release,
+                target,
+                starlark_vars,
+                features,
+                strict,
+                verbose,
+                Path::new(prefix),
+                receipt,
+                dry_run,
+            )
+        }
+
         ("list-targets", Some(args)) => {
             let path = args.value_of("path").unwrap();
+            let format = args.value_of("format").unwrap();
 
-            projectmgmt::list_targets(&env, &logger_context.logger, Path::new(path))
+            projectmgmt::list_targets(&env, &logger_context.logger, Path::new(path), format)
         }
 
         ("init-rust-project", Some(args)) => {
@@ -547,8 +1004,28 @@ pub fn run_cli() -> Result<()> {
             projectmgmt::python_distribution_licenses(path)
         }
 
+        ("resources-diff", Some(args)) => {
+            let old = args.value_of("old").unwrap();
+            let new = args.value_of("new").unwrap();
+            let format = args.value_of("format").unwrap();
+            let only_packages = args
+                .value_of("only_packages")
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(|x| x.trim())
+                        .filter(|x| !x.is_empty())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+            projectmgmt::resources_diff(Path::new(old), Path::new(new), format, &only_packages)
+        }
+
         ("run-build-script", Some(args)) => {
             let starlark_vars = starlark_vars(args)?;
+            let features = starlark_features(args);
+            let strict = args.is_present("strict_config");
             let build_script = args.value_of("build-script-name").unwrap();
             let target = args.value_of("target");
 
@@ -558,18 +1035,66 @@ pub fn run_cli() -> Result<()> {
                 build_script,
                 target,
                 starlark_vars,
+                features,
+                strict,
             )
         }
 
+        ("strip-resources", Some(args)) => {
+            let path = args.value_of("path").unwrap();
+            let output = args.value_of("output").unwrap();
+            let strip_source = args.is_present("strip_source");
+            let keep_bytecode_levels = args
+                .value_of("keep_bytecode_levels")
+                .unwrap()
+                .split(',')
+                .map(|x| x.trim())
+                .filter(|x| !x.is_empty())
+                .map(|x| match x {
+                    "0" => Ok(0),
+                    "1" => Ok(1),
+                    "2" => Ok(2),
+                    _ => Err(anyhow!("invalid bytecode optimization level: {}", x)),
+                })
+                .collect::<Result<Vec<u8>>>()?;
+
+            projectmgmt::strip_resources(
+                Path::new(path),
+                Path::new(output),
+                &keep_bytecode_levels,
+                strip_source,
+            )
+        }
+
+        ("uninstall", Some(args)) => {
+            let receipt = args.value_of("receipt").unwrap();
+            let dry_run = args.is_present("dry_run");
+
+            projectmgmt::uninstall(Path::new(receipt), dry_run)
+        }
+
         ("run", Some(args)) => {
             let starlark_vars = starlark_vars(args)?;
+            let features = starlark_features(args);
+            let strict = args.is_present("strict_config");
             let target_triple = args.value_of("target_triple");
             let release = args.is_present("release");
             let path = args.value_of("path").unwrap();
             let target = args.value_of("target");
-            let extra: Vec<&str> = args.values_of("extra").unwrap_or_default().collect();
+            let trace_startup = args.is_present("trace_startup");
+            let wasm_runtime = args.value_of("wasm_runtime");
+            let extra: Vec<OsString> = args
+                .values_of_os("extra")
+                .unwrap_or_default()
+                .map(|v| v.to_os_string())
+                .collect();
+            let cwd = if let Some(cwd) = args.value_of_os("cwd") {
+                PathBuf::from(cwd)
+            } else {
+                std::env::current_dir()?
+            };
 
-            projectmgmt::run(
+            let code = projectmgmt::run(
                 &env,
                 &logger_context.logger,
                 Path::new(path),
@@ -577,9 +1102,21 @@ pub fn run_cli() -> Result<()> {
                 release,
                 target,
                 starlark_vars,
+                features,
+                strict,
                 &extra,
+                &cwd,
                 verbose,
-            )
+                trace_startup,
+                wasm_runtime,
+            )?;
+
+            // Propagate the exit code of the launched process.
+            if code != 0 {
+                std::process::exit(code);
+            }
+
+            Ok(())
         }
 
         _ => Err(anyhow!("invalid sub-command")),
@@ -0,0 +1,280 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Generate shell completions and man pages from the CLI definitions. */
+
+use {
+    crate::environment::PYOXIDIZER_VERSION,
+    anyhow::{anyhow, Context, Result},
+    clap::{App, Shell},
+    std::{io::Write, path::Path, str::FromStr},
+};
+
+/// Name of the installed executable.
+const BIN_NAME: &str = "pyoxidizer";
+
+/// Shells we can generate completion scripts for.
+pub const SHELLS: [&str; 4] = ["bash", "zsh", "fish", "powershell"];
+
+/// Subcommands whose positional arguments are target names.
+const POSITIONAL_TARGET_SUBCOMMANDS: [&str; 2] = ["build", "install"];
+
+/// Completes target names when invoked in a project directory.
+///
+/// `@OPTIONS@` is replaced by the options of `POSITIONAL_TARGET_SUBCOMMANDS`
+/// that take a value, which must not be followed by a target name.
+const BASH_TARGETS: &str = r#"
+_pyoxidizer_targets() {
+    local i cur prev subcommand complete_targets
+    cur="${COMP_WORDS[COMP_CWORD]}"
+    prev="${COMP_WORDS[COMP_CWORD-1]}"
+
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        if [[ "${COMP_WORDS[i]}" != -* ]]; then
+            subcommand="${COMP_WORDS[i]}"
+            break
+        fi
+    done
+
+    if [[ -f pyoxidizer.bzl && "${cur}" != -* ]]; then
+        case "${subcommand}" in
+            build|install)
+                case "${prev}" in
+                    @OPTIONS@)
+                        ;;
+                    *)
+                        complete_targets=1
+                        ;;
+                esac
+                ;;
+            run)
+                if [[ "${prev}" == --target ]]; then
+                    complete_targets=1
+                fi
+                ;;
+        esac
+    fi
+
+    if [[ -n "${complete_targets}" ]]; then
+        COMPREPLY=( $(compgen -W "$(pyoxidizer list-targets --format names 2>/dev/null)" -- "${cur}") )
+        return 0
+    fi
+
+    _pyoxidizer "$@"
+}
+
+complete -F _pyoxidizer_targets -o bashdefault -o default pyoxidizer
+"#;
+
+/// Completes target names when invoked in a project directory.
+const FISH_TARGETS: &str = r#"
+function __pyoxidizer_targets
+    test -f pyoxidizer.bzl; and pyoxidizer list-targets --format names 2>/dev/null
+end
+
+complete -c pyoxidizer -n "__fish_seen_subcommand_from build install" -f -a "(__pyoxidizer_targets)"
+complete -c pyoxidizer -n "__fish_seen_subcommand_from run" -l target -x -a "(__pyoxidizer_targets)"
+"#;
+
+/// Obtain the long names of options of target subcommands that take a value.
+fn target_subcommand_value_options(app: &App) -> Vec<String> {
+    let mut options = app
+        .p
+        .subcommands
+        .iter()
+        .filter(|sub| POSITIONAL_TARGET_SUBCOMMANDS.contains(&sub.p.meta.name.as_str()))
+        .flat_map(|sub| sub.p.opts.iter())
+        .filter_map(|opt| opt.s.long)
+        .map(|long| format!("--{}", long))
+        .collect::<Vec<_>>();
+
+    options.sort();
+    options.dedup();
+
+    options
+}
+
+/// Write a completion script for `shell` to `out`.
+///
+/// The bash and fish scripts additionally complete target names by running
+/// `pyoxidizer list-targets` when a configuration file is in the current
+/// directory.
+pub fn write_completions(app: &mut App, shell: &str, out: &mut impl Write) -> Result<()> {
+    let parsed = Shell::from_str(shell).map_err(|e| anyhow!("{}", e))?;
+
+    let value_options = target_subcommand_value_options(app);
+
+    app.gen_completions_to(BIN_NAME, parsed, out);
+
+    match parsed {
+        Shell::Bash => {
+            out.write_all(
+                BASH_TARGETS
+                    .replace("@OPTIONS@", &value_options.join("|"))
+                    .as_bytes(),
+            )?;
+        }
+        Shell::Fish => {
+            out.write_all(FISH_TARGETS.as_bytes())?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Escape text so roff renders it literally.
+fn roff_escape(text: &str) -> String {
+    text.replace('\\', "\\e")
+        .lines()
+        .map(|line| {
+            if line.starts_with('.') || line.starts_with('\'') {
+                format!("\\&{}", line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Render a man page for a command.
+///
+/// The body is the command's long help, rendered without filling.
+fn render_man_page(app: &mut App, page_name: &str, command: &str) -> Result<String> {
+    let about = app
+        .p
+        .meta
+        .about
+        .map(|about| about.to_string())
+        .unwrap_or_default();
+
+    let mut help = vec![];
+    app.write_long_help(&mut help)?;
+    let help = String::from_utf8(help)?;
+
+    let mut page = String::new();
+    page.push_str(&format!(
+        ".TH \"{}\" \"1\" \"\" \"pyoxidizer {}\"\n",
+        page_name.to_uppercase(),
+        PYOXIDIZER_VERSION
+    ));
+    page.push_str(".SH NAME\n");
+    page.push_str(&format!("{} \\- {}\n", page_name, roff_escape(&about)));
+    page.push_str(".SH SYNOPSIS\n");
+    page.push_str(&format!(".B {}\n", command));
+    page.push_str(".SH DESCRIPTION\n");
+    page.push_str(".nf\n");
+    page.push_str(&roff_escape(help.trim_end()));
+    page.push_str("\n.fi\n");
+
+    Ok(page)
+}
+
+/// Write man pages for `pyoxidizer` and each of its subcommands to `dest_dir`.
+///
+/// Returns the paths of written files.
+pub fn write_man_pages(app: &App, dest_dir: &Path) -> Result<Vec<std::path::PathBuf>> {
+    std::fs::create_dir_all(dest_dir)
+        .with_context(|| format!("creating {}", dest_dir.display()))?;
+
+    let mut pages = vec![(
+        BIN_NAME.to_string(),
+        BIN_NAME.to_string(),
+        app.clone().bin_name(BIN_NAME),
+    )];
+
+    for sub in &app.p.subcommands {
+        let name = sub.p.meta.name.clone();
+        let command = format!("{} {}", BIN_NAME, name);
+
+        pages.push((
+            format!("{}-{}", BIN_NAME, name),
+            command.clone(),
+            sub.clone().bin_name(command),
+        ));
+    }
+
+    let mut paths = vec![];
+
+    for (page_name, command, mut page_app) in pages {
+        let path = dest_dir.join(format!("{}.1", page_name));
+
+        let page = render_man_page(&mut page_app, &page_name, &command)?;
+        std::fs::write(&path, page).with_context(|| format!("writing {}", path.display()))?;
+
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::cli::app};
+
+    #[test]
+    fn test_bash_completions_snapshot() -> Result<()> {
+        let mut out = vec![];
+        write_completions(&mut app(PYOXIDIZER_VERSION), "bash", &mut out)?;
+
+        // Regenerate with `pyoxidizer generate-completions bash` when the CLI changes.
+        assert_eq!(
+            String::from_utf8(out)?,
+            include_str!("cli_generate_bash.snapshot")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_completions() -> Result<()> {
+        for shell in &SHELLS {
+            let mut out = vec![];
+            write_completions(&mut app(PYOXIDIZER_VERSION), shell, &mut out)?;
+            assert!(!out.is_empty());
+        }
+
+        let mut out = vec![];
+        write_completions(&mut app(PYOXIDIZER_VERSION), "fish", &mut out)?;
+        assert!(String::from_utf8(out)?.contains("function __pyoxidizer_targets"));
+
+        assert!(write_completions(&mut app(PYOXIDIZER_VERSION), "csh", &mut vec![]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_man_pages() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+
+        let app = app(PYOXIDIZER_VERSION);
+        let paths = write_man_pages(&app, temp_dir.path())?;
+
+        assert_eq!(paths.len(), app.p.subcommands.len() + 1);
+        assert!(temp_dir.path().join("pyoxidizer.1").exists());
+
+        for sub in &app.p.subcommands {
+            let path = temp_dir
+                .path()
+                .join(format!("pyoxidizer-{}.1", sub.p.meta.name));
+            let page = std::fs::read_to_string(&path)?;
+
+            assert!(page.starts_with(".TH \"PYOXIDIZER-"));
+            assert!(page.contains(&format!("pyoxidizer {}", sub.p.meta.name)));
+        }
+
+        let page = std::fs::read_to_string(temp_dir.path().join("pyoxidizer-build.1"))?;
+        assert!(page.contains("pyoxidizer-build \\- Build a PyOxidizer enabled project"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_roff_escape() {
+        assert_eq!(roff_escape("a\\b\n.foo\n'bar"), "a\\eb\n\\&.foo\n\\&'bar");
+    }
+}
@@ -0,0 +1,393 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Compatibility between PyOxidizer, Python distributions, and pyembed.
+
+A PyOxidizer release only understands certain versions of the
+`PYTHON.json` metadata in standalone Python distributions and only
+generates artifacts consumable by a specific release series of the
+`pyembed` crate. [COMPATIBILITY_MATRIX] records these relationships.
+
+[CompatibilityCheck] collects the versions of components encountered
+and reports every incompatibility in a single error, along with what
+to do about it.
+*/
+
+use {
+    crate::environment::{PYEMBED_CRATE_VERSION, PYOXIDIZER_VERSION},
+    anyhow::{anyhow, Context, Result},
+    serde::Serialize,
+    std::{
+        fmt,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Describes the components a PyOxidizer release series is compatible with.
+#[derive(Clone, Debug, Serialize)]
+pub struct ReleaseCompatibility {
+    /// PyOxidizer release series. e.g. `0.17`.
+    pub pyoxidizer: &'static str,
+
+    /// `PYTHON.json` format versions of supported standalone distributions.
+    pub distribution_formats: &'static [&'static str],
+
+    /// Release series of the `pyembed` crate artifacts are generated for.
+    pub pyembed: &'static str,
+}
+
+/// Known PyOxidizer release series, newest first.
+///
+/// The first entry describes this release.
+pub static COMPATIBILITY_MATRIX: &[ReleaseCompatibility] = &[
+    ReleaseCompatibility {
+        pyoxidizer: "0.17",
+        distribution_formats: &["7"],
+        pyembed: "0.17",
+    },
+    ReleaseCompatibility {
+        pyoxidizer: "0.16",
+        distribution_formats: &["7"],
+        pyembed: "0.16",
+    },
+];
+
+/// Obtain the compatibility of the running PyOxidizer release.
+pub fn current_release() -> &'static ReleaseCompatibility {
+    &COMPATIBILITY_MATRIX[0]
+}
+
+/// Obtain the `major.minor` release series of a version or version requirement.
+fn version_series(version: &str) -> Option<(u64, u64)> {
+    let version = version.trim_start_matches(|c: char| "=^~<> ".contains(c));
+    let mut parts = version
+        .split(|c: char| !c.is_ascii_digit())
+        .take(2)
+        .map(|s| s.parse::<u64>());
+
+    let major = parts.next()?.ok()?;
+    let minor = match parts.next() {
+        Some(minor) => minor.ok()?,
+        None => 0,
+    };
+
+    Some((major, minor))
+}
+
+/// Join release names for display. e.g. `0.16.x or 0.15.x`.
+fn join_releases(releases: &[&ReleaseCompatibility]) -> String {
+    releases
+        .iter()
+        .map(|r| format!("{}.x", r.pyoxidizer))
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+/// A component that isn't compatible with this PyOxidizer release.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Incompatibility {
+    /// A Python distribution has an unsupported `PYTHON.json` format version.
+    DistributionFormat { source: String, format: String },
+
+    /// A Rust project references a `pyembed` of another release series.
+    Pyembed { source: PathBuf, version: String },
+}
+
+impl Incompatibility {
+    /// Describe the action resolving this incompatibility.
+    pub fn action(&self) -> String {
+        let current = current_release();
+
+        match self {
+            Self::DistributionFormat { format, .. } => {
+                let others = COMPATIBILITY_MATRIX[1..]
+                    .iter()
+                    .filter(|r| r.distribution_formats.contains(&format.as_str()))
+                    .collect::<Vec<_>>();
+
+                let newest_supported = current
+                    .distribution_formats
+                    .iter()
+                    .filter_map(|f| f.parse::<u64>().ok())
+                    .max()
+                    .unwrap_or(0);
+
+                let mut action = match format.parse::<u64>() {
+                    Ok(v) if v > newest_supported => format!(
+                        "upgrade PyOxidizer to a release supporting distribution format {}",
+                        format
+                    ),
+                    _ => format!(
+                        "use a Python distribution with format version {}, such as \
+                        default_python_distribution()",
+                        current.distribution_formats.join(" or ")
+                    ),
+                };
+
+                if !others.is_empty() {
+                    action.push_str(&format!(" or use PyOxidizer {}", join_releases(&others)));
+                }
+
+                action
+            }
+            Self::Pyembed { source, version } => {
+                let series = version_series(version);
+                let others = COMPATIBILITY_MATRIX[1..]
+                    .iter()
+                    .filter(|r| version_series(r.pyembed) == series)
+                    .collect::<Vec<_>>();
+
+                let mut action = if series > version_series(current.pyembed) {
+                    format!(
+                        "upgrade PyOxidizer to a release using pyembed {} or change the pyembed \
+                        dependency to {}",
+                        version, PYEMBED_CRATE_VERSION
+                    )
+                } else if source.file_name().and_then(|s| s.to_str()) == Some("Cargo.lock") {
+                    format!(
+                        "run `cargo update -p pyembed` to move to pyembed {}",
+                        PYEMBED_CRATE_VERSION
+                    )
+                } else {
+                    format!("change the pyembed dependency to {}", PYEMBED_CRATE_VERSION)
+                };
+
+                if !others.is_empty() {
+                    action.push_str(&format!(" or use PyOxidizer {}", join_releases(&others)));
+                }
+
+                action
+            }
+        }
+    }
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let current = current_release();
+
+        match self {
+            Self::DistributionFormat { source, format } => write!(
+                f,
+                "Python distribution {} has format version {}; PyOxidizer {} supports format {}; {}",
+                source,
+                format,
+                PYOXIDIZER_VERSION,
+                current.distribution_formats.join(", "),
+                self.action()
+            ),
+            Self::Pyembed { source, version } => write!(
+                f,
+                "{} references pyembed {}; PyOxidizer {} requires pyembed {}.x; {}",
+                source.display(),
+                version,
+                PYOXIDIZER_VERSION,
+                current.pyembed,
+                self.action()
+            ),
+        }
+    }
+}
+
+/// Collects component versions and checks them against [COMPATIBILITY_MATRIX].
+#[derive(Clone, Debug, Default)]
+pub struct CompatibilityCheck {
+    incompatibilities: Vec<Incompatibility>,
+}
+
+impl CompatibilityCheck {
+    /// Record the `PYTHON.json` format version of a Python distribution.
+    pub fn add_distribution_format(&mut self, source: impl ToString, format: &str) {
+        if !current_release().distribution_formats.contains(&format) {
+            self.incompatibilities
+                .push(Incompatibility::DistributionFormat {
+                    source: source.to_string(),
+                    format: format.to_string(),
+                });
+        }
+    }
+
+    /// Record a `pyembed` version or version requirement.
+    ///
+    /// Requirements not naming a version (e.g. `*`) are ignored.
+    pub fn add_pyembed_version(&mut self, source: impl AsRef<Path>, version: &str) {
+        let series = match version_series(version) {
+            Some(series) => series,
+            None => return,
+        };
+
+        if Some(series) != version_series(current_release().pyembed) {
+            self.incompatibilities.push(Incompatibility::Pyembed {
+                source: source.as_ref().to_path_buf(),
+                version: version.to_string(),
+            });
+        }
+    }
+
+    /// Record the `pyembed` versions referenced by a Rust project.
+    ///
+    /// The `pyembed` dependency in `Cargo.toml` and the locked `pyembed`
+    /// version in `Cargo.lock` are examined, if present.
+    pub fn add_rust_project(&mut self, project_path: &Path) -> Result<()> {
+        let manifest_path = project_path.join("Cargo.toml");
+        if manifest_path.exists() {
+            let data = std::fs::read(&manifest_path)
+                .with_context(|| format!("reading {}", manifest_path.display()))?;
+            let manifest = cargo_toml::Manifest::from_slice(&data)
+                .with_context(|| format!("parsing {}", manifest_path.display()))?;
+
+            if let Some(dependency) = manifest.dependencies.get("pyembed") {
+                self.add_pyembed_version(&manifest_path, dependency.req());
+            }
+        }
+
+        let lock_path = project_path.join("Cargo.lock");
+        if lock_path.exists() {
+            let lock = cargo_lock::Lockfile::load(&lock_path)
+                .with_context(|| format!("parsing {}", lock_path.display()))?;
+
+            for package in lock.packages {
+                if package.name.as_str() == "pyembed" {
+                    self.add_pyembed_version(&lock_path, &package.version.to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Obtain the incompatibilities found so far.
+    pub fn incompatibilities(&self) -> &[Incompatibility] {
+        &self.incompatibilities
+    }
+
+    /// Convert to an error listing every incompatibility, if there are any.
+    pub fn into_result(self) -> Result<()> {
+        if self.incompatibilities.is_empty() {
+            return Ok(());
+        }
+
+        Err(anyhow!(
+            "incompatible components detected:\n{}\nrun `pyoxidizer compatibility` to see supported versions",
+            self.incompatibilities
+                .iter()
+                .map(|i| format!("  - {}", i))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ))
+    }
+}
+
+/// Check that a Rust project is compatible with this PyOxidizer release.
+pub fn check_rust_project(project_path: &Path) -> Result<()> {
+    let mut check = CompatibilityCheck::default();
+    check.add_rust_project(project_path)?;
+    check.into_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> Result<tempfile::TempDir> {
+        Ok(tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?)
+    }
+
+    #[test]
+    fn test_current_release() {
+        assert_eq!(
+            version_series(current_release().pyoxidizer),
+            version_series(env!("CARGO_PKG_VERSION"))
+        );
+        assert_eq!(
+            version_series(current_release().pyembed),
+            version_series(PYEMBED_CRATE_VERSION)
+        );
+    }
+
+    #[test]
+    fn test_version_series() {
+        assert_eq!(version_series("0.17.0-pre"), Some((0, 17)));
+        assert_eq!(version_series("=0.16.1"), Some((0, 16)));
+        assert_eq!(version_series("^1"), Some((1, 0)));
+        assert_eq!(version_series("*"), None);
+    }
+
+    #[test]
+    fn test_distribution_too_new() -> Result<()> {
+        let mut check = CompatibilityCheck::default();
+        check.add_distribution_format("/dist/python/PYTHON.json", "7");
+        assert!(check.incompatibilities().is_empty());
+
+        check.add_distribution_format("/dist/python/PYTHON.json", "8");
+        let err = check.into_result().unwrap_err().to_string();
+        assert!(err.contains("PYTHON.json has format version 8"));
+        assert!(err.contains("upgrade PyOxidizer to a release supporting distribution format 8"));
+
+        let mut check = CompatibilityCheck::default();
+        check.add_distribution_format("old", "5");
+        assert!(check.incompatibilities()[0]
+            .action()
+            .starts_with("use a Python distribution with format version 7"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_rust_project() -> Result<()> {
+        let temp_dir = temp_dir()?;
+        let project = temp_dir.path();
+
+        check_rust_project(project)?;
+
+        std::fs::write(
+            project.join("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+            [dependencies]\npyembed = \"0.16.0\"\n",
+        )?;
+        std::fs::write(
+            project.join("Cargo.lock"),
+            "[[package]]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+            [[package]]\nname = \"pyembed\"\nversion = \"0.16.0\"\n",
+        )?;
+
+        let mut check = CompatibilityCheck::default();
+        check.add_rust_project(project)?;
+        check.add_distribution_format("dist", "8");
+        assert_eq!(check.incompatibilities().len(), 3);
+
+        let err = check.into_result().unwrap_err().to_string();
+        assert_eq!(err.lines().filter(|l| l.starts_with("  - ")).count(), 3);
+        assert!(err.contains("Cargo.toml references pyembed 0.16.0"));
+        assert!(err.contains(&format!(
+            "change the pyembed dependency to {} or use PyOxidizer 0.16.x",
+            PYEMBED_CRATE_VERSION
+        )));
+        assert!(err.contains("Cargo.lock references pyembed 0.16.0"));
+        assert!(err.contains("cargo update -p pyembed"));
+
+        std::fs::write(
+            project.join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+                [dependencies]\npyembed = {{ version = \"{}\", path = \"pyembed\" }}\n",
+                PYEMBED_CRATE_VERSION
+            ),
+        )?;
+        std::fs::remove_file(project.join("Cargo.lock"))?;
+        check_rust_project(project)?;
+
+        std::fs::write(
+            project.join("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+            [dependencies]\npyembed = \"0.99\"\n",
+        )?;
+        let err = check_rust_project(project).unwrap_err().to_string();
+        assert!(err.contains("upgrade PyOxidizer to a release using pyembed 0.99"));
+
+        Ok(())
+    }
+}
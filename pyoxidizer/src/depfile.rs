@@ -0,0 +1,249 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Makefile style dependency files describing build inputs.
+
+Build systems like Bazel, Buck, and Ninja need to know every file a build
+reads in order to cache it. [DepFile] records the inputs of a build and
+serializes them in the Makefile syntax emitted by compilers and Cargo.
+
+Inputs are collected from the filesystem paths recorded by the Starlark
+build targets context. See
+[starlark_dialect_build_targets::EnvironmentContext::record_read_path].
+*/
+
+use {
+    crate::starlark::eval::EvaluationContext,
+    anyhow::{anyhow, Context, Result},
+    sha2::{Digest, Sha256},
+    std::{
+        collections::BTreeSet,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Escape a path for use in a Makefile rule.
+fn escape_path(path: &Path) -> String {
+    path.display()
+        .to_string()
+        .replace('$', "$$")
+        .replace('#', "\\#")
+        .replace(' ', "\\ ")
+}
+
+/// Describes the outputs of a build and the files they were derived from.
+#[derive(Clone, Debug, Default)]
+pub struct DepFile {
+    targets: Vec<PathBuf>,
+    inputs: BTreeSet<PathBuf>,
+}
+
+impl DepFile {
+    /// Create an instance describing the given output paths.
+    pub fn new(targets: Vec<PathBuf>) -> Self {
+        Self {
+            targets,
+            inputs: BTreeSet::new(),
+        }
+    }
+
+    /// Record a path as an input.
+    ///
+    /// Directories are expanded to the files beneath them. Paths that don't
+    /// exist and paths beneath any of `exclude` are ignored.
+    pub fn add_input(&mut self, path: &Path, exclude: &[PathBuf]) -> Result<()> {
+        let excluded = |p: &Path| exclude.iter().any(|e| p.starts_with(e));
+
+        if excluded(path) || !path.exists() {
+            return Ok(());
+        }
+
+        if path.is_dir() {
+            for entry in
+                walkdir::WalkDir::new(path).sort_by(|a, b| a.file_name().cmp(b.file_name()))
+            {
+                let entry = entry?;
+
+                if entry.file_type().is_file() && !excluded(entry.path()) {
+                    self.inputs.insert(entry.path().to_path_buf());
+                }
+            }
+        } else {
+            self.inputs.insert(path.to_path_buf());
+        }
+
+        Ok(())
+    }
+
+    /// Files recorded as inputs, sorted.
+    pub fn inputs(&self) -> impl Iterator<Item = &Path> {
+        self.inputs.iter().map(|p| p.as_path())
+    }
+
+    /// Serialize to a Makefile rule.
+    ///
+    /// Output is deterministic: inputs are sorted and each input also gets
+    /// an empty rule so deleted inputs don't break `make`.
+    pub fn to_makefile(&self) -> String {
+        let targets = self
+            .targets
+            .iter()
+            .map(|p| escape_path(p))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut s = format!("{}:", targets);
+        for input in &self.inputs {
+            s.push_str(" \\\n  ");
+            s.push_str(&escape_path(input));
+        }
+        s.push('\n');
+
+        for input in &self.inputs {
+            s.push_str(&format!("\n{}:\n", escape_path(input)));
+        }
+
+        s
+    }
+
+    /// Write the Makefile rule to a file.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(path, self.to_makefile())
+            .with_context(|| format!("writing depfile {}", path.display()))
+    }
+
+    /// Compute a SHA-256 digest of the paths and content of all inputs.
+    ///
+    /// The digest only changes if an input is added, removed, or modified,
+    /// which makes it suitable as a cache key.
+    pub fn digest(&self) -> Result<String> {
+        let mut hasher = Sha256::new();
+
+        for input in &self.inputs {
+            let data = std::fs::read(input)
+                .with_context(|| format!("reading input {}", input.display()))?;
+
+            hasher.update(input.display().to_string().as_bytes());
+            hasher.update([0]);
+            hasher.update((data.len() as u64).to_le_bytes());
+            hasher.update(&data);
+        }
+
+        Ok(hex::encode(hasher.finalize()))
+    }
+}
+
+/// Construct a [DepFile] from the paths read by an evaluation context.
+///
+/// Files beneath the context's build path are outputs and are excluded.
+pub fn depfile_from_context(context: &EvaluationContext, targets: Vec<PathBuf>) -> Result<DepFile> {
+    let build_path = context
+        .build_path()
+        .map_err(|e| anyhow!("unable to resolve build path: {:?}", e))?;
+
+    let exclude = vec![build_path];
+    let mut depfile = DepFile::new(targets);
+
+    for path in context.read_paths()? {
+        depfile.add_input(&path, &exclude)?;
+    }
+
+    Ok(depfile)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            environment::default_target_triple, starlark::eval::EvaluationContextBuilder,
+            testutil::*,
+        },
+    };
+
+    #[test]
+    fn test_escape_path() {
+        assert_eq!(
+            escape_path(Path::new("/a b/$c#d")),
+            "/a\\ b/$$c\\#d".to_string()
+        );
+    }
+
+    #[test]
+    fn test_depfile_from_evaluation() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let root = temp_dir.path().canonicalize()?;
+        let env = get_env()?;
+        let logger = get_logger()?;
+
+        let data_dir = root.join("data");
+        std::fs::create_dir(&data_dir)?;
+        let data_path = data_dir.join("a.txt");
+        std::fs::write(&data_path, b"data")?;
+
+        // A file that nothing reads.
+        let unread_path = root.join("unread.txt");
+        std::fs::write(&unread_path, b"unread")?;
+
+        let load_path = root.join("load.bzl");
+        std::fs::write(
+            &load_path,
+            format!(
+                "def make_files():\n    return glob(['{}'], strip_prefix = '{}/')\n",
+                data_dir
+                    .join("*.txt")
+                    .display()
+                    .to_string()
+                    .escape_default(),
+                data_dir.display().to_string().escape_default()
+            ),
+        )?;
+
+        let main_path = root.join("main.bzl");
+        std::fs::write(
+            &main_path,
+            format!(
+                "load('{}', 'make_files')\nmake_files()\n",
+                load_path.display().to_string().escape_default()
+            ),
+        )?;
+
+        let mut context = EvaluationContextBuilder::new(
+            &env,
+            logger,
+            main_path.clone(),
+            default_target_triple().to_string(),
+        )
+        .into_context()?;
+        context.evaluate_file(&main_path)?;
+
+        let depfile = depfile_from_context(&context, vec![root.join("out")])?;
+
+        let inputs = depfile.inputs().collect::<Vec<_>>();
+        assert!(inputs.contains(&main_path.as_path()));
+        assert!(inputs.contains(&load_path.as_path()));
+        assert!(inputs.contains(&data_path.as_path()));
+        assert!(!inputs.contains(&unread_path.as_path()));
+
+        let makefile = depfile.to_makefile();
+        assert!(makefile.starts_with(&format!("{}:", escape_path(&root.join("out")))));
+        assert!(makefile.contains(&escape_path(&data_path)));
+        assert!(!makefile.contains("unread.txt"));
+
+        // Modifying an input changes the digest. Modifying other files doesn't.
+        let digest = depfile.digest()?;
+        std::fs::write(&unread_path, b"modified")?;
+        assert_eq!(depfile.digest()?, digest);
+        std::fs::write(&data_path, b"modified")?;
+        assert_ne!(depfile.digest()?, digest);
+
+        Ok(())
+    }
+}
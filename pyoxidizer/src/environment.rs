@@ -24,7 +24,7 @@ use {
 const PYOXIDIZER_CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Version string of pyembed crate from its Cargo.toml.
-const PYEMBED_CRATE_VERSION: &str = "0.17.0-pre";
+pub const PYEMBED_CRATE_VERSION: &str = "0.17.0-pre";
 
 /// URL of Git repository we were built from.
 const GIT_REPO_URL: &str = env!("GIT_REPO_URL");
@@ -118,6 +118,31 @@ pub static WINDOWS_TARGET_TRIPLES: Lazy<Vec<&'static str>> = Lazy::new(|| {
     ]
 });
 
+/// Target triples for WebAssembly with the WebAssembly System Interface (WASI).
+///
+/// Support for these targets is experimental.
+pub static WASI_TARGET_TRIPLES: Lazy<Vec<&'static str>> = Lazy::new(|| vec!["wasm32-wasi"]);
+
+/// Resolve the pointer width in bits of a target triple.
+///
+/// This is derived from the architecture component, taking ABIs running
+/// 64-bit architectures with 32-bit pointers (e.g. `x86_64-unknown-linux-gnux32`)
+/// into account.
+pub fn target_pointer_width(target_triple: &str) -> u32 {
+    let mut parts = target_triple.split('-');
+    let arch = parts.next().unwrap_or_default();
+
+    if parts.any(|part| part.ends_with("x32") || part.ends_with("ilp32")) {
+        return 32;
+    }
+
+    match arch {
+        "s390x" | "sparcv9" => 64,
+        _ if arch.contains("64") => 64,
+        _ => 32,
+    }
+}
+
 pub fn canonicalize_path(path: &Path) -> Result<PathBuf, std::io::Error> {
     let mut p = path.canonicalize()?;
 
@@ -279,6 +304,11 @@ impl Environment {
         self.cache_dir.join("python_distributions")
     }
 
+    /// Directory to hold named caches populated by configuration files.
+    pub fn named_caches_dir(&self) -> PathBuf {
+        self.cache_dir.join("named")
+    }
+
     /// Directory to hold Rust toolchains.
     pub fn rust_dir(&self) -> PathBuf {
         self.cache_dir.join("rust")
@@ -588,3 +618,33 @@ pub fn resolve_apple_sdk(
         Ok(candidate_sdks[0].clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_pointer_width() {
+        for triple in &[
+            "i586-pc-windows-msvc",
+            "i686-unknown-linux-gnu",
+            "armv7-unknown-linux-gnueabihf",
+            "thumbv7neon-linux-androideabi",
+            "wasm32-wasi",
+            "x86_64-unknown-linux-gnux32",
+            "aarch64-unknown-linux-gnu_ilp32",
+        ] {
+            assert_eq!(target_pointer_width(triple), 32, "{}", triple);
+        }
+
+        for triple in &[
+            "x86_64-pc-windows-msvc",
+            "aarch64-apple-darwin",
+            "powerpc64le-unknown-linux-gnu",
+            "riscv64gc-unknown-linux-gnu",
+            "s390x-unknown-linux-gnu",
+        ] {
+            assert_eq!(target_pointer_width(triple), 64, "{}", triple);
+        }
+    }
+}
@@ -0,0 +1,383 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Installing built targets into a prefix directory.
+
+Installing copies the files of a built target into a prefix directory and
+writes an [InstallReceipt] recording every installed path and its content
+digest. The receipt allows removing exactly what was installed later on,
+without touching files that were modified since.
+
+Failures to install or remove individual files don't abort the operation.
+They are collected so a summary of all failures can be reported.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    serde::{Deserialize, Serialize},
+    sha2::{Digest, Sha256},
+    std::{
+        collections::BTreeSet,
+        convert::TryFrom,
+        path::{Path, PathBuf},
+    },
+    tugger_file_manifest::{FileEntry, FileManifest},
+};
+
+/// Filename of the receipt written to the prefix directory by default.
+pub const RECEIPT_FILENAME: &str = "pyoxidizer-install-receipt.json";
+
+/// Compute the hex encoded SHA-256 digest of data.
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Collect the files beneath a directory into a [FileManifest].
+///
+/// Symlinks are recorded as links rather than followed.
+pub fn manifest_from_dir(path: &Path) -> Result<FileManifest> {
+    let mut manifest = FileManifest::new_with_links();
+
+    for entry in walkdir::WalkDir::new(path).sort_by(|a, b| a.file_name().cmp(b.file_name())) {
+        let entry = entry?;
+        let rel_path = entry.path().strip_prefix(path)?;
+
+        if entry.file_type().is_symlink() {
+            manifest.add_symlink(rel_path, std::fs::read_link(entry.path())?)?;
+        } else if entry.file_type().is_file() {
+            manifest.add_file_entry(rel_path, FileEntry::try_from(entry.path())?)?;
+        }
+    }
+
+    Ok(manifest)
+}
+
+/// A file recorded in an [InstallReceipt].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct InstalledFile {
+    /// Path relative to the prefix.
+    pub path: PathBuf,
+    /// SHA-256 of the file content. `None` for symlinks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Target of the symlink, if the file is a symlink.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<PathBuf>,
+}
+
+impl InstalledFile {
+    /// Whether the file at `path` still matches what was installed.
+    fn matches(&self, path: &Path) -> Result<bool> {
+        if let Some(link) = &self.link {
+            Ok(std::fs::read_link(path)? == *link)
+        } else {
+            let data = std::fs::read(path)?;
+            Ok(self.sha256.as_deref() == Some(sha256_hex(&data).as_str()))
+        }
+    }
+}
+
+/// Records the files installed into a prefix.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct InstallReceipt {
+    /// The directory files were installed into.
+    pub prefix: PathBuf,
+    /// Installed files, sorted by path.
+    pub files: Vec<InstalledFile>,
+}
+
+impl InstallReceipt {
+    /// Read a receipt from a file.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)
+            .with_context(|| format!("reading install receipt {}", path.display()))?;
+
+        serde_json::from_slice(&data)
+            .with_context(|| format!("parsing install receipt {}", path.display()))
+    }
+
+    /// Write the receipt to a file.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("writing install receipt {}", path.display()))
+    }
+}
+
+/// An operation on a single file that failed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileFailure {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Result of installing files into a prefix.
+#[derive(Clone, Debug, Default)]
+pub struct InstallOutcome {
+    /// Files that were installed.
+    pub receipt: InstallReceipt,
+    /// Files that couldn't be installed.
+    pub failures: Vec<FileFailure>,
+}
+
+/// Describe what installing a manifest into a prefix would do.
+pub fn install_plan(manifest: &FileManifest, prefix: &Path) -> Vec<String> {
+    manifest
+        .iter_entries()
+        .map(|(path, entry)| {
+            let dest = prefix.join(path);
+
+            match (entry.link_target(), dest.exists()) {
+                (Some(link), _) => format!("link {} -> {}", dest.display(), link.display()),
+                (None, true) => format!("replace {}", dest.display()),
+                (None, false) => format!("install {}", dest.display()),
+            }
+        })
+        .collect()
+}
+
+/// Install the files of a manifest into a prefix.
+///
+/// Existing files are replaced. Failures are recorded per file and don't
+/// stop remaining files from being installed.
+pub fn install_manifest(manifest: &FileManifest, prefix: &Path) -> InstallOutcome {
+    let mut outcome = InstallOutcome {
+        receipt: InstallReceipt {
+            prefix: prefix.to_path_buf(),
+            files: vec![],
+        },
+        failures: vec![],
+    };
+
+    for (path, entry) in manifest.iter_entries() {
+        let dest = prefix.join(path);
+
+        let res = (|| -> Result<InstalledFile> {
+            // Symlinks can't be created over existing files.
+            if entry.link_target().is_some() && dest.symlink_metadata().is_ok() {
+                std::fs::remove_file(&dest)?;
+            }
+
+            let sha256 = if entry.link_target().is_some() {
+                None
+            } else {
+                Some(sha256_hex(&entry.resolve_content()?))
+            };
+
+            entry.write_to_path(&dest)?;
+
+            Ok(InstalledFile {
+                path: path.clone(),
+                sha256,
+                link: entry.link_target().map(|p| p.to_path_buf()),
+            })
+        })();
+
+        match res {
+            Ok(file) => outcome.receipt.files.push(file),
+            Err(e) => outcome.failures.push(FileFailure {
+                path: dest,
+                error: format!("{:#}", e),
+            }),
+        }
+    }
+
+    outcome
+}
+
+/// Result of removing the files recorded in a receipt.
+#[derive(Clone, Debug, Default)]
+pub struct UninstallOutcome {
+    /// Files that were (or, for a dry run, would be) removed.
+    pub removed: Vec<PathBuf>,
+    /// Files that were kept because they changed since they were installed.
+    pub modified: Vec<PathBuf>,
+    /// Files that no longer exist.
+    pub missing: Vec<PathBuf>,
+    /// Files that couldn't be removed.
+    pub failures: Vec<FileFailure>,
+}
+
+/// Remove the files recorded in a receipt.
+///
+/// Files whose content changed since installation are kept. Directories
+/// left empty by removing files are removed as well, up to the prefix.
+pub fn uninstall_receipt(receipt: &InstallReceipt, dry_run: bool) -> UninstallOutcome {
+    let mut outcome = UninstallOutcome::default();
+    let mut directories = BTreeSet::new();
+
+    for file in &receipt.files {
+        let path = receipt.prefix.join(&file.path);
+
+        if path.symlink_metadata().is_err() {
+            outcome.missing.push(path);
+            continue;
+        }
+
+        let res = file.matches(&path).and_then(|matches| {
+            if matches && !dry_run {
+                std::fs::remove_file(&path)?;
+            }
+
+            Ok(matches)
+        });
+
+        match res {
+            Ok(true) => {
+                let mut parent = path.parent();
+                while let Some(p) = parent {
+                    if p == receipt.prefix || !p.starts_with(&receipt.prefix) {
+                        break;
+                    }
+                    directories.insert(p.to_path_buf());
+                    parent = p.parent();
+                }
+
+                outcome.removed.push(path);
+            }
+            Ok(false) => outcome.modified.push(path),
+            Err(e) => outcome.failures.push(FileFailure {
+                path,
+                error: format!("{:#}", e),
+            }),
+        }
+    }
+
+    if !dry_run {
+        // Deepest directories sort last. Directories that aren't empty are kept.
+        for dir in directories.iter().rev() {
+            let _ = std::fs::remove_dir(dir);
+        }
+    }
+
+    outcome
+}
+
+/// Render failures as a summary, returning an error if there were any.
+pub fn check_failures(operation: &str, failures: &[FileFailure]) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    for failure in failures {
+        eprintln!("error: {}: {}", failure.path.display(), failure.error);
+    }
+
+    Err(anyhow!(
+        "failed to {} {} file(s); see errors above",
+        operation,
+        failures.len()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tempfile::TempDir};
+
+    fn temp_dir() -> Result<TempDir> {
+        Ok(tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?)
+    }
+
+    fn manifest() -> Result<FileManifest> {
+        let mut manifest = FileManifest::default();
+        manifest.add_file_entry("bin/app", FileEntry::new_from_data(b"app".to_vec(), true))?;
+        manifest.add_file_entry("lib/foo.py", b"foo".to_vec())?;
+        manifest.add_file_entry("lib/sub/bar.py", b"bar".to_vec())?;
+
+        Ok(manifest)
+    }
+
+    #[test]
+    fn test_manifest_from_dir() -> Result<()> {
+        let td = temp_dir()?;
+        manifest()?.materialize_files(td.path())?;
+
+        let m = manifest_from_dir(td.path())?;
+        assert_eq!(
+            m.iter_entries().map(|(p, _)| p.clone()).collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("bin/app"),
+                PathBuf::from("lib/foo.py"),
+                PathBuf::from("lib/sub/bar.py")
+            ]
+        );
+        assert_eq!(m.get("lib/foo.py").unwrap().resolve_content()?, b"foo");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_install_uninstall() -> Result<()> {
+        let td = temp_dir()?;
+        let prefix = td.path().join("prefix");
+        let manifest = manifest()?;
+
+        assert_eq!(
+            install_plan(&manifest, &prefix)[0],
+            format!("install {}", prefix.join("bin/app").display())
+        );
+
+        let outcome = install_manifest(&manifest, &prefix);
+        assert!(outcome.failures.is_empty());
+        assert_eq!(outcome.receipt.files.len(), 3);
+        assert_eq!(std::fs::read(prefix.join("lib/sub/bar.py"))?, b"bar");
+
+        let receipt_path = prefix.join(RECEIPT_FILENAME);
+        outcome.receipt.write(&receipt_path)?;
+        let receipt = InstallReceipt::from_path(&receipt_path)?;
+        assert_eq!(receipt, outcome.receipt);
+
+        // Modified files are kept. Unrelated files are untouched.
+        std::fs::write(prefix.join("lib/foo.py"), b"changed")?;
+        std::fs::write(prefix.join("lib/other.py"), b"other")?;
+        std::fs::remove_file(prefix.join("bin/app"))?;
+
+        let outcome = uninstall_receipt(&receipt, true);
+        assert_eq!(outcome.removed, vec![prefix.join("lib/sub/bar.py")]);
+        assert!(prefix.join("lib/sub/bar.py").exists());
+
+        let outcome = uninstall_receipt(&receipt, false);
+        assert_eq!(outcome.removed, vec![prefix.join("lib/sub/bar.py")]);
+        assert_eq!(outcome.modified, vec![prefix.join("lib/foo.py")]);
+        assert_eq!(outcome.missing, vec![prefix.join("bin/app")]);
+        assert!(outcome.failures.is_empty());
+
+        assert!(!prefix.join("lib/sub").exists());
+        assert_eq!(std::fs::read(prefix.join("lib/foo.py"))?, b"changed");
+        assert!(prefix.join("lib/other.py").exists());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_install_failures() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let td = temp_dir()?;
+        let prefix = td.path().join("prefix");
+        std::fs::create_dir_all(prefix.join("lib"))?;
+        std::fs::set_permissions(prefix.join("lib"), std::fs::Permissions::from_mode(0o555))?;
+
+        // Permissions aren't enforced for root.
+        if std::fs::write(prefix.join("lib/probe"), b"").is_ok() {
+            return Ok(());
+        }
+
+        let outcome = install_manifest(&manifest()?, &prefix);
+        assert_eq!(outcome.receipt.files.len(), 1);
+        assert_eq!(outcome.failures.len(), 2);
+        assert_eq!(outcome.failures[0].path, prefix.join("lib/foo.py"));
+        assert!(check_failures("install", &outcome.failures).is_err());
+
+        std::fs::set_permissions(prefix.join("lib"), std::fs::Permissions::from_mode(0o755))?;
+
+        Ok(())
+    }
+}
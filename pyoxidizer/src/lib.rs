@@ -11,14 +11,24 @@ distribution and embedding it in a larger binary, oftentimes an executable.
 This library exposes that functionality to other tools.
 */
 
+pub mod compatibility;
+pub mod depfile;
 pub mod environment;
+pub mod install;
 pub mod logging;
 pub mod project_building;
 pub mod project_layout;
 pub mod projectmgmt;
 pub mod py_packaging;
 pub mod python_distributions;
+pub mod resource_stripping;
+pub mod resources_report;
 pub mod starlark;
+pub mod startup_audit;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod timing;
+pub mod vendored_project;
 
 #[cfg(test)]
 mod testutil;
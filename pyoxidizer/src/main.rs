@@ -28,16 +28,27 @@ a rather effective and powerful tool.
 */
 
 mod cli;
+mod cli_generate;
+mod compatibility;
+mod depfile;
 mod environment;
+mod install;
 mod logging;
 mod project_building;
 mod project_layout;
 mod projectmgmt;
 mod py_packaging;
 mod python_distributions;
+mod resource_stripping;
+mod resources_report;
 pub mod starlark;
+mod startup_audit;
+#[cfg(test)]
+mod testing;
 #[cfg(test)]
 mod testutil;
+mod timing;
+mod vendored_project;
 
 fn main() {
     std::process::exit(match cli::run_cli() {
@@ -4,6 +4,7 @@
 
 use {
     crate::{
+        compatibility::check_rust_project,
         environment::{canonicalize_path, Environment, RustEnvironment},
         project_layout::initialize_project,
         py_packaging::{
@@ -11,6 +12,7 @@ use {
             distribution::AppleSdkInfo,
         },
         starlark::eval::{EvaluationContext, EvaluationContextBuilder},
+        timing,
     },
     anyhow::{anyhow, Context, Result},
     duct::cmd,
@@ -20,7 +22,7 @@ use {
         collections::HashMap,
         convert::TryInto,
         fs::create_dir_all,
-        io::{BufRead, BufReader},
+        io::{BufRead, BufReader, Read},
         path::{Path, PathBuf},
     },
 };
@@ -128,15 +130,20 @@ impl BuildEnvironment {
 
         let mut rust_flags = vec![];
 
-        // If linking against an existing dynamic library on Windows, add the path to that
-        // library so the linker can find it.
+        // If linking against an existing dynamic library, add the path to that
+        // library so the linker can find it. The library is installed next to the
+        // binary. Outside Windows, the binary needs an rpath to find it there.
         if let Some(libpython_filename) = libpython_filename {
-            if target_triple.contains("-windows-") {
-                let libpython_dir = libpython_filename
-                    .parent()
-                    .ok_or_else(|| anyhow!("unable to find parent directory of python DLL"))?;
+            let libpython_dir = libpython_filename
+                .parent()
+                .ok_or_else(|| anyhow!("unable to find parent directory of python DLL"))?;
 
-                rust_flags.push(format!("-L{}", libpython_dir.display()));
+            rust_flags.push(format!("-L{}", libpython_dir.display()));
+
+            if target_triple.contains("-apple-") {
+                rust_flags.push("-Clink-arg=-Wl,-rpath,@executable_path".to_string());
+            } else if !target_triple.contains("-windows-") {
+                rust_flags.push("-Clink-arg=-Wl,-rpath,$ORIGIN".to_string());
             }
         }
 
@@ -254,6 +261,46 @@ pub struct BuiltExecutable<'a> {
     pub binary_data: EmbeddedPythonContext<'a>,
 }
 
+/// Resolve the Cargo features of a generated Rust project building an executable.
+///
+/// The features select pre-built artifacts for the `pyembed` crate and how to
+/// link against libpython and memory allocators.
+pub fn prebuilt_artifacts_cargo_features(
+    exe: &dyn PythonBinaryBuilder,
+    embedded_data: &EmbeddedPythonContext,
+) -> Vec<&'static str> {
+    let mut features = vec!["build-mode-prebuilt-artifacts"];
+
+    // If we have a real libpython, let cpython crate link against it. Otherwise
+    // leave symbols unresolved, as we'll provide them.
+    features.push(if embedded_data.linking_info.libpython_filename.is_some() {
+        "cpython-link-default"
+    } else {
+        "cpython-link-unresolved-static"
+    });
+
+    if exe.requires_jemalloc() {
+        features.push("global-allocator-jemalloc");
+        features.push("allocator-jemalloc");
+    }
+    if exe.requires_mimalloc() {
+        features.push("global-allocator-mimalloc");
+        features.push("allocator-mimalloc");
+    }
+    if exe.requires_snmalloc() {
+        features.push("global-allocator-snmalloc");
+        features.push("allocator-snmalloc");
+    }
+    if exe.trace_startup() {
+        features.push("startup-trace");
+    }
+    if exe.resources_encryption().is_some() {
+        features.push("encrypted-resources");
+    }
+
+    features
+}
+
 /// Build an executable embedding Python using an existing Rust project.
 ///
 /// The path to the produced executable is returned.
@@ -271,15 +318,20 @@ pub fn build_executable_with_rust_project<'a>(
     release: bool,
     locked: bool,
 ) -> Result<BuiltExecutable<'a>> {
+    check_rust_project(project_path)?;
+
     create_dir_all(&artifacts_path).context("creating directory for PyOxidizer build artifacts")?;
 
     // Derive and write the artifacts needed to build a binary embedding Python.
     let embedded_data = exe
         .to_embedded_python_context(logger, env, opt_level)
         .context("obtaining embedded python context")?;
-    embedded_data
-        .write_files(&artifacts_path)
-        .context("writing embedded python context files")?;
+    {
+        let _span = timing::span("write build artifacts");
+        embedded_data
+            .write_files(&artifacts_path)
+            .context("writing embedded python context files")?;
+    }
 
     let build_env = BuildEnvironment::new(
         env,
@@ -322,36 +374,15 @@ pub fn build_executable_with_rust_project<'a>(
     }
 
     args.push("--no-default-features");
-    let mut features = vec!["build-mode-prebuilt-artifacts"];
-
-    // If we have a real libpython, let cpython crate link against it. Otherwise
-    // leave symbols unresolved, as we'll provide them.
-    features.push(if embedded_data.linking_info.libpython_filename.is_some() {
-        "cpython-link-default"
-    } else {
-        "cpython-link-unresolved-static"
-    });
-
-    if exe.requires_jemalloc() {
-        features.push("global-allocator-jemalloc");
-        features.push("allocator-jemalloc");
-    }
-    if exe.requires_mimalloc() {
-        features.push("global-allocator-mimalloc");
-        features.push("allocator-mimalloc");
-    }
-    if exe.requires_snmalloc() {
-        features.push("global-allocator-snmalloc");
-        features.push("allocator-snmalloc");
-    }
-
-    let features = features.join(" ");
+    let features = prebuilt_artifacts_cargo_features(exe, &embedded_data).join(" ");
 
     if !features.is_empty() {
         args.push("--features");
         args.push(&features);
     }
 
+    let cargo_span = timing::span("cargo build");
+
     // TODO force cargo to colorize output under certain circumstances?
     let command = cmd(&build_env.rust_environment.cargo_exe, &args)
         .dir(&project_path)
@@ -372,9 +403,12 @@ pub fn build_executable_with_rust_project<'a>(
     if !output.status.success() {
         return Err(anyhow!("cargo build failed"));
     }
+    drop(cargo_span);
 
     let exe_name = if target_triple.contains("pc-windows") {
         format!("{}.exe", bin_name)
+    } else if crate::environment::WASI_TARGET_TRIPLES.contains(&target_triple) {
+        format!("{}.wasm", bin_name)
     } else {
         bin_name.to_string()
     };
@@ -409,6 +443,14 @@ pub fn build_python_executable<'a>(
     opt_level: &str,
     release: bool,
 ) -> Result<BuiltExecutable<'a>> {
+    // The temporary project has no way to obtain the decryption key.
+    if exe.resources_encryption().is_some() {
+        return Err(anyhow!(
+            "encrypted resources require a Rust project defining resources_key_provider(); \
+            create one with `pyoxidizer init-rust-project` and build it instead"
+        ));
+    }
+
     let cargo_exe = env
         .ensure_rust_toolchain(logger, Some(target_triple))
         .context("resolving Rust toolchain")?
@@ -469,6 +511,8 @@ pub fn build_pyembed_artifacts(
     artifacts_path: &Path,
     resolve_target: Option<&str>,
     extra_vars: HashMap<String, Option<String>>,
+    features: Vec<String>,
+    strict: bool,
     target_triple: &str,
     release: bool,
     verbose: bool,
@@ -488,6 +532,8 @@ pub fn build_pyembed_artifacts(
         target_triple.to_string(),
     )
     .extra_vars(extra_vars)
+    .features(features)
+    .strict(strict)
     .release(release)
     .verbose(verbose)
     .resolve_target_optional(resolve_target)
@@ -556,6 +602,8 @@ pub fn run_from_build(
     build_script: &str,
     resolve_target: Option<&str>,
     extra_vars: HashMap<String, Option<String>>,
+    features: Vec<String>,
+    strict: bool,
 ) -> Result<()> {
     // Adding our our rerun-if-changed lines will overwrite the default, so
     // we need to emit the build script name explicitly.
@@ -595,6 +643,8 @@ pub fn run_from_build(
         &dest_dir,
         resolve_target,
         extra_vars,
+        features,
+        strict,
         &target,
         profile == "release",
         false,
@@ -641,6 +691,42 @@ fn dependency_current(
     }
 }
 
+/// Determines whether packed resources files in a directory have the expected bytecode magic.
+fn packed_resources_current(logger: &slog::Logger, artifacts_path: &Path, magic: u32) -> bool {
+    let entries = match std::fs::read_dir(artifacts_path) {
+        Ok(entries) => entries,
+        Err(_) => {
+            warn!(logger, "error reading {}", artifacts_path.display());
+            return false;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        let mut header = [0u8; 12];
+        let read = std::fs::File::open(&path).and_then(|mut fh| fh.read_exact(&mut header));
+        if read.is_err() {
+            continue;
+        }
+
+        match python_packed_resources::parser::read_bytecode_magic_number(&header) {
+            Ok(Some(actual)) if actual != magic => {
+                warn!(
+                    logger,
+                    "building artifacts because {} has stale bytecode magic number {:#010x}",
+                    path.display(),
+                    actual
+                );
+                return false;
+            }
+            _ => {}
+        }
+    }
+
+    true
+}
+
 /// Determines whether PyOxidizer artifacts are current.
 fn artifacts_current(logger: &slog::Logger, config_path: &Path, artifacts_path: &Path) -> bool {
     let metadata_path = artifacts_path.join("cargo_metadata.txt");
@@ -689,6 +775,18 @@ fn artifacts_current(logger: &slog::Logger, config_path: &Path, artifacts_path:
             if !dependency_current(logger, &path, built_time) {
                 return false;
             }
+        } else if let Some(magic) = line.strip_prefix("cargo:python-bytecode-magic-number=") {
+            let magic = match u32::from_str_radix(magic.trim_start_matches("0x"), 16) {
+                Ok(magic) => magic,
+                Err(_) => {
+                    warn!(logger, "error parsing bytecode magic number {}", magic);
+                    return false;
+                }
+            };
+
+            if !packed_resources_current(logger, artifacts_path, magic) {
+                return false;
+            }
         }
     }
 
@@ -712,9 +810,17 @@ mod tests {
         crate::{
             environment::default_target_triple,
             py_packaging::standalone_builder::tests::StandalonePythonExecutableBuilderOptions,
+            startup_audit::{StartupAuditReport, STARTUP_AUDIT_PATH_ENV},
             testutil::*,
         },
-        python_packaging::interpreter::MemoryAllocatorBackend,
+        python_packaging::{
+            interpreter::{MemoryAllocatorBackend, PythonRunMode, SysExecutableMode},
+            location::ConcreteResourceLocation,
+        },
+        std::{
+            io::Write,
+            process::{Command, Stdio},
+        },
     };
 
     #[cfg(target_env = "msvc")]
@@ -917,4 +1023,499 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_sys_executable_python_shim() -> Result<()> {
+        let env = get_env()?;
+        let logger = get_logger()?;
+
+        let mut options = StandalonePythonExecutableBuilderOptions::default();
+        options.config.sys_executable_mode = SysExecutableMode::PythonShim;
+        options.config.config.run_command = Some(
+            "import subprocess, sys; \
+            p = subprocess.run([sys.executable, '-c', 'import sys; print(sys.argv[1:])', 'arg'], \
+            stdout=subprocess.PIPE, check=True); \
+            sys.stdout.write(p.stdout.decode())"
+                .to_string(),
+        );
+
+        let pre_built = options.new_builder()?;
+
+        let build = build_python_executable(
+            &env,
+            &logger,
+            "myapp",
+            pre_built.as_ref(),
+            default_target_triple(),
+            "0",
+            false,
+        )?;
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let exe_path = temp_dir.path().join(&build.exe_name);
+        {
+            let mut fh = std::fs::File::create(&exe_path)?;
+            fh.write_all(&build.exe_data)?;
+            tugger_file_manifest::set_executable(&mut fh)?;
+        }
+
+        let output = std::process::Command::new(&exe_path).output()?;
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "['arg']");
+
+        let shim_name = format!(
+            "{}-python{}",
+            exe_path.file_stem().unwrap().to_string_lossy(),
+            std::env::consts::EXE_SUFFIX
+        );
+        assert!(temp_dir.path().join(shim_name).is_file());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_test_packages() -> Result<()> {
+        let env = get_env()?;
+        let logger = get_logger()?;
+
+        let mut options = StandalonePythonExecutableBuilderOptions {
+            // test_ssl locates its certificates relative to __file__.
+            resources_location: Some(ConcreteResourceLocation::RelativePath("lib".to_string())),
+            resources_location_fallback: Some(None),
+            include_test_packages: Some(vec![
+                "test.support".to_string(),
+                "test.test_ssl".to_string(),
+            ]),
+            ..StandalonePythonExecutableBuilderOptions::default()
+        };
+        options.config.config.run_command = Some(
+            "import sys, unittest; \
+            from test import test_ssl; \
+            suite = unittest.defaultTestLoader.loadTestsFromTestCase(test_ssl.BasicSocketTests); \
+            result = unittest.TextTestRunner(verbosity=0).run(suite); \
+            sys.exit(0 if result.wasSuccessful() and result.testsRun else 1)"
+                .to_string(),
+        );
+
+        let pre_built = options.new_builder()?;
+
+        let build = build_python_executable(
+            &env,
+            &logger,
+            "myapp",
+            pre_built.as_ref(),
+            default_target_triple(),
+            "0",
+            false,
+        )?;
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        build
+            .binary_data
+            .extra_files
+            .materialize_files(temp_dir.path())?;
+        let exe_path = temp_dir.path().join(&build.exe_name);
+        {
+            let mut fh = std::fs::File::create(&exe_path)?;
+            fh.write_all(&build.exe_data)?;
+            tugger_file_manifest::set_executable(&mut fh)?;
+        }
+
+        let lib_test = temp_dir.path().join("lib").join("test");
+        assert!(lib_test.join("support").is_dir());
+        assert!(lib_test.join("test_ssl.py").is_file());
+        assert!(!lib_test.join("test_os.py").exists());
+
+        let output = std::process::Command::new(&exe_path).output()?;
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_python_interpreter_executable() -> Result<()> {
+        let env = get_env()?;
+        let logger = get_logger()?;
+
+        let mut options = StandalonePythonExecutableBuilderOptions::default();
+        options.config.configure_as_python_interpreter();
+
+        let pre_built = options.new_builder()?;
+
+        let build = build_python_executable(
+            &env,
+            &logger,
+            "python",
+            pre_built.as_ref(),
+            default_target_triple(),
+            "0",
+            false,
+        )?;
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        build
+            .binary_data
+            .extra_files
+            .materialize_files(temp_dir.path())?;
+        let exe_path = temp_dir.path().join(&build.exe_name);
+        {
+            let mut fh = std::fs::File::create(&exe_path)?;
+            fh.write_all(&build.exe_data)?;
+            tugger_file_manifest::set_executable(&mut fh)?;
+        }
+
+        let output = Command::new(&exe_path)
+            .arg("-c")
+            .arg("import sys; print(sys.argv[1:])")
+            .arg("arg")
+            .output()?;
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "['arg']");
+
+        let mut child = Command::new(&exe_path)
+            .arg("-m")
+            .arg("json.tool")
+            .arg("--sort-keys")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"{\"b\": 1, \"a\": [true]}")?;
+        let output = child.wait_with_output()?;
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            "{\n    \"a\": [\n        true\n    ],\n    \"b\": 1\n}"
+        );
+
+        // Virtual environments symlink their python to the executable.
+        #[cfg(unix)]
+        {
+            let venv = temp_dir.path().join("venv");
+
+            let output = Command::new(&exe_path)
+                .arg("-m")
+                .arg("venv")
+                .arg("--without-pip")
+                .arg(&venv)
+                .output()?;
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+
+            let output = Command::new(venv.join("bin").join("python"))
+                .arg("-c")
+                .arg("import sys; print(sys.prefix != sys.base_prefix); print(sys.prefix)")
+                .output()?;
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let lines = stdout.lines().collect::<Vec<_>>();
+            assert_eq!(lines[0], "True");
+            assert_eq!(
+                std::fs::canonicalize(lines[1])?,
+                std::fs::canonicalize(&venv)?
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Build and run a hello world executable for wasm32-wasi.
+    ///
+    /// `PYOXIDIZER_WASI_DISTRIBUTION` must be the path to a wasm32-wasi standalone
+    /// Python distribution archive. `PYOXIDIZER_WASM_RUNTIME` is the command
+    /// running the executable and defaults to `wasmtime`.
+    #[cfg(feature = "wasi-e2e")]
+    #[test]
+    fn test_wasi_hello_world() -> Result<()> {
+        use {
+            crate::py_packaging::distribution::{
+                BinaryLibpythonLinkMode, DistributionFlavor, PythonDistribution,
+                PythonDistributionLocation,
+            },
+            sha2::{Digest, Sha256},
+            starlark_dialect_build_targets::{exit_code, ResolvedTarget, RunMode},
+        };
+
+        let env = get_env()?;
+        let logger = get_logger()?;
+
+        let local_path = std::env::var("PYOXIDIZER_WASI_DISTRIBUTION")
+            .context("PYOXIDIZER_WASI_DISTRIBUTION must be set")?;
+        let sha256 = hex::encode(Sha256::digest(&std::fs::read(&local_path)?));
+        let target_distribution =
+            get_distribution(&PythonDistributionLocation::Local { local_path, sha256 })?;
+
+        let host_record = crate::python_distributions::PYTHON_DISTRIBUTIONS
+            .find_distribution(
+                default_target_triple(),
+                &DistributionFlavor::Standalone,
+                Some(&target_distribution.python_major_minor_version()),
+            )
+            .ok_or_else(|| anyhow!("could not find host Python distribution"))?;
+        let host_distribution = get_distribution(&host_record.location)?;
+
+        let mut config = target_distribution.create_python_interpreter_config()?;
+        config.config.run_command = Some("print('hello, world')".to_string());
+
+        let mut builder = target_distribution.as_python_executable_builder(
+            &logger,
+            default_target_triple(),
+            "wasm32-wasi",
+            "hello",
+            BinaryLibpythonLinkMode::Default,
+            &target_distribution.create_packaging_policy()?,
+            &config,
+            Some(host_distribution),
+        )?;
+        builder.add_distribution_resources(None)?;
+
+        let build = build_python_executable(
+            &env,
+            &logger,
+            "hello",
+            builder.as_ref(),
+            "wasm32-wasi",
+            "0",
+            false,
+        )?;
+        assert_eq!(build.exe_name, "hello.wasm");
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let path = temp_dir.path().join(&build.exe_name);
+        std::fs::write(&path, &build.exe_data)?;
+
+        let runtime = std::env::var("PYOXIDIZER_WASM_RUNTIME")
+            .unwrap_or_else(|_| "wasmtime".to_string())
+            .split_whitespace()
+            .map(|x| x.to_string())
+            .collect();
+        let target = ResolvedTarget {
+            run_mode: RunMode::Runtime { runtime, path },
+            output_path: temp_dir.path().to_path_buf(),
+        };
+
+        let no_args: &[&str] = &[];
+        let output = target
+            .run_command(no_args, temp_dir.path())
+            .unwrap()
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()?;
+        assert_eq!(
+            exit_code(output.status),
+            0,
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            "hello, world"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_startup_in_memory() -> Result<()> {
+        let env = get_env()?;
+        let logger = get_logger()?;
+
+        let mut options = StandalonePythonExecutableBuilderOptions::default();
+        options.config.config.run_command = Some("import json".to_string());
+
+        let mut pre_built = options.new_builder()?;
+        pre_built.set_trace_startup(true);
+
+        let build = build_python_executable(
+            &env,
+            &logger,
+            "myapp",
+            pre_built.as_ref(),
+            default_target_triple(),
+            "0",
+            false,
+        )?;
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let exe_path = temp_dir.path().join(&build.exe_name);
+        {
+            let mut fh = std::fs::File::create(&exe_path)?;
+            fh.write_all(&build.exe_data)?;
+            tugger_file_manifest::set_executable(&mut fh)?;
+        }
+
+        let audit_path = temp_dir.path().join("startup-audit.txt");
+        let output = std::process::Command::new(&exe_path)
+            .env(STARTUP_AUDIT_PATH_ENV, &audit_path)
+            .output()?;
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        // Resources are embedded in the binary, so nothing is read from the
+        // filesystem or the network.
+        let report = StartupAuditReport::from_path(&audit_path)?;
+        assert_eq!(report.end, Some("cpython.run_command".to_string()));
+        assert!(report.files.is_empty(), "{}", report.to_text());
+        assert!(report.sockets.is_empty(), "{}", report.to_text());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_stdlib_false() -> Result<()> {
+        let env = get_env()?;
+        let logger = get_logger()?;
+
+        let mut options = StandalonePythonExecutableBuilderOptions::default();
+        options.config.config.run_command = Some("print(1+1)".to_string());
+
+        let mut pre_built = options.new_builder()?;
+        pre_built.set_include_stdlib(false)?;
+
+        let build = build_python_executable(
+            &env,
+            &logger,
+            "myapp",
+            pre_built.as_ref(),
+            default_target_triple(),
+            "0",
+            false,
+        )?;
+
+        assert!(build.exe_data.len() < 10 * 1024 * 1024);
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let exe_path = temp_dir.path().join(&build.exe_name);
+        {
+            let mut fh = std::fs::File::create(&exe_path)?;
+            fh.write_all(&build.exe_data)?;
+            tugger_file_manifest::set_executable(&mut fh)?;
+        }
+
+        let output = std::process::Command::new(&exe_path).output()?;
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "2");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_mode_repl() -> Result<()> {
+        let env = get_env()?;
+        let logger = get_logger()?;
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+
+        let mut options = StandalonePythonExecutableBuilderOptions::default();
+        options.config.run_mode = PythonRunMode::Repl;
+        options.config.repl_banner = Some("ops console".to_string());
+        options.config.repl_startup_code =
+            Some("import atexit; answer = 42; atexit.register(print, 'finalized')".to_string());
+        options.config.repl_history_path = Some(temp_dir.path().join("history"));
+        // The REPL ignores what would otherwise run.
+        options.config.config.run_command = Some("print('not run')".to_string());
+
+        let mut pre_built = options.new_builder()?;
+        pre_built.set_include_stdlib(false)?;
+
+        let build = build_python_executable(
+            &env,
+            &logger,
+            "myapp",
+            pre_built.as_ref(),
+            default_target_triple(),
+            "0",
+            false,
+        )?;
+
+        let exe_path = temp_dir.path().join(&build.exe_name);
+        {
+            let mut fh = std::fs::File::create(&exe_path)?;
+            fh.write_all(&build.exe_data)?;
+            tugger_file_manifest::set_executable(&mut fh)?;
+        }
+
+        let run = |input: &[u8]| -> Result<std::process::Output> {
+            let mut child = Command::new(&exe_path)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            // Closing stdin after the input sends EOF.
+            child.stdin.take().unwrap().write_all(input)?;
+
+            Ok(child.wait_with_output()?)
+        };
+
+        let output = run(b"print(answer * 2)\nfor i in range(2):\n    print(i)\n\n")?;
+        let stdout = String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{}", stderr);
+        assert!(stderr.starts_with("ops console"), "{}", stderr);
+        assert!(
+            stdout.starts_with(">>> 84\n>>> ... ... 0\n1\n>>> "),
+            "{}",
+            stdout
+        );
+        assert!(!stdout.contains("not run"));
+        // atexit handlers, which include pre-finalize hooks, run on EOF.
+        assert!(stdout.ends_with(">>> finalized\n"), "{}", stdout);
+
+        let output = run(b"raise SystemExit(3)\n")?;
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim_end(),
+            ">>> finalized"
+        );
+
+        Ok(())
+    }
 }
@@ -91,6 +91,7 @@ struct TemplateData {
     program_name: Option<String>,
     code: Option<String>,
     pip_install_simple: Vec<String>,
+    default_features: Vec<String>,
 }
 
 impl TemplateData {
@@ -106,6 +107,7 @@ impl TemplateData {
             program_name: None,
             code: None,
             pip_install_simple: Vec::new(),
+            default_features: Vec::new(),
         }
     }
 }
@@ -169,6 +171,14 @@ pub fn write_new_cargo_config(project_path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Obtain the `Cargo.lock` used by newly generated Rust projects.
+///
+/// This describes the Rust crates compiled into binaries built from
+/// generated projects.
+pub fn new_project_cargo_lock() -> Result<cargo_lock::Lockfile> {
+    Ok(cargo_lock::Lockfile::from_str(NEW_PROJECT_CARGO_LOCK)?)
+}
+
 /// Write a Cargo.lock file for a project path.
 ///
 /// The Cargo.lock content is under version control and is automatically
@@ -184,7 +194,7 @@ pub fn write_new_cargo_lock(
 ) -> Result<()> {
     // Add this project's entry to the lock file contents, otherwise the
     // lock file will need updating on first use.
-    let mut lock_file = cargo_lock::Lockfile::from_str(NEW_PROJECT_CARGO_LOCK)?;
+    let mut lock_file = new_project_cargo_lock()?;
 
     let dependencies = NEW_PROJECT_DEPENDENCIES
         .iter()
@@ -455,6 +465,45 @@ pub fn update_new_cargo_toml(path: &Path, pyembed_location: &PyembedLocation) ->
     Ok(())
 }
 
+/// Write a Cargo.toml for a standalone Rust project building with pre-built artifacts.
+///
+/// Unlike [update_new_cargo_toml], the manifest is written from scratch and
+/// `default_features` become the default Cargo features, so a plain
+/// `cargo build` selects the same features `pyoxidizer build` would.
+pub fn write_prebuilt_artifacts_cargo_toml(
+    path: &Path,
+    project_name: &str,
+    pyembed_location: &PyembedLocation,
+    default_features: &[&str],
+) -> Result<()> {
+    let mut content = format!(
+        "[package]\n\
+         name = \"{}\"\n\
+         version = \"0.1.0\"\n\
+         edition = \"2018\"\n\
+         build = \"build.rs\"\n\
+         \n\
+         [dependencies]\n\
+         pyembed = {{ {}, default-features = false }}\n\
+         \n",
+        project_name,
+        pyembed_location.cargo_manifest_fields()
+    );
+
+    let mut data = TemplateData::new();
+    data.default_features = default_features.iter().map(|x| x.to_string()).collect();
+    content.push_str(
+        &HANDLEBARS
+            .render("cargo-extra.toml", &data)
+            .context("rendering cargo-extra.toml template")?,
+    );
+
+    println!("writing {}", path.display());
+    std::fs::write(path, content)?;
+
+    Ok(())
+}
+
 /// Initialize a new Rust project using PyOxidizer.
 ///
 /// The created binary application will have the name of the final
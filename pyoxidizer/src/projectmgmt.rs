@@ -6,7 +6,13 @@
 
 use {
     crate::{
+        compatibility::{CompatibilityCheck, COMPATIBILITY_MATRIX},
+        depfile::depfile_from_context,
         environment::{default_target_triple, Environment, PyOxidizerSource},
+        install::{
+            check_failures, install_manifest, install_plan, manifest_from_dir, uninstall_receipt,
+            InstallReceipt, RECEIPT_FILENAME,
+        },
         project_building::find_pyoxidizer_config_file_env,
         project_layout::{initialize_project, write_new_pyoxidizer_config_file},
         py_packaging::{
@@ -16,14 +22,25 @@ use {
             },
             standalone_distribution::StandaloneDistribution,
         },
-        starlark::eval::EvaluationContextBuilder,
+        resource_stripping::{
+            find_packed_resources, strip_resources_file, PackedResourcesSizes, StripDestination,
+            StripOptions,
+        },
+        resources_report::{ResourcesDiff, ResourcesReport},
+        starlark::{eval::EvaluationContextBuilder, named_cache::validate_named_cache_name},
+        startup_audit::{StartupAuditReport, STARTUP_AUDIT_PATH_ENV},
+        timing,
     },
     anyhow::{anyhow, Context, Result},
     python_packaging::{
         filesystem_scanning::find_python_resources, resource::PythonResource, wheel::WheelArchive,
     },
+    serde::Serialize,
+    slog::warn,
+    starlark_dialect_build_targets::sandbox::SandboxPolicy,
     std::{
         collections::HashMap,
+        ffi::OsString,
         fs::create_dir_all,
         io::{Cursor, Read},
         path::{Path, PathBuf},
@@ -58,7 +75,24 @@ pub fn resolve_target(target: Option<&str>) -> Result<String> {
     }
 }
 
-pub fn list_targets(env: &Environment, logger: &slog::Logger, project_path: &Path) -> Result<()> {
+/// Describes a registered target for `list-targets` output.
+#[derive(Clone, Debug, Serialize)]
+struct TargetListing {
+    name: String,
+    description: Option<String>,
+    aliases: Vec<String>,
+    default: bool,
+}
+
+/// Print targets registered by a config file.
+///
+/// `format` is either `human` or `json`.
+pub fn list_targets(
+    env: &Environment,
+    logger: &slog::Logger,
+    project_path: &Path,
+    format: &str,
+) -> Result<()> {
     let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
         anyhow!(
             "unable to find PyOxidizder config file at {}",
@@ -75,18 +109,111 @@ pub fn list_targets(env: &Environment, logger: &slog::Logger, project_path: &Pat
 
     context.evaluate_file(&config_path)?;
 
-    if context.default_target()?.is_none() {
-        println!("(no targets defined)");
-        return Ok(());
+    let default = context.default_target()?;
+    let aliases = context.target_aliases()?;
+
+    let mut targets = vec![];
+    for name in context.target_names()? {
+        targets.push(TargetListing {
+            description: context.target_description(&name)?,
+            aliases: aliases
+                .iter()
+                .filter(|(_, target)| **target == name)
+                .map(|(alias, _)| alias.clone())
+                .collect(),
+            default: Some(&name) == default.as_ref(),
+            name,
+        });
     }
 
-    for target in context.target_names()? {
-        let prefix = if Some(target.clone()) == context.default_target()? {
-            "*"
-        } else {
-            ""
-        };
-        println!("{}{}", prefix, target);
+    match format {
+        "json" => {
+            println!("{}", serde_json::to_string_pretty(&targets)?);
+        }
+        "human" => {
+            if targets.is_empty() {
+                println!("(no targets defined)");
+            }
+
+            for target in targets {
+                let mut line = format!("{}{}", if target.default { "*" } else { "" }, target.name);
+
+                if !target.aliases.is_empty() {
+                    line.push_str(&format!(" (aliases: {})", target.aliases.join(", ")));
+                }
+
+                if let Some(description) = &target.description {
+                    line.push_str(&format!(" - {}", description));
+                }
+
+                println!("{}", line);
+            }
+        }
+        "names" => {
+            for target in targets {
+                println!("{}", target.name);
+
+                for alias in target.aliases {
+                    println!("{}", alias);
+                }
+            }
+        }
+        _ => return Err(anyhow!("unknown output format: {}", format)),
+    }
+
+    Ok(())
+}
+
+/// Print the compatibility matrix of PyOxidizer releases.
+///
+/// If `project_path` is given, the Rust project there is also checked
+/// for compatibility with this release. `format` is either `human` or
+/// `json`.
+pub fn compatibility(project_path: Option<&Path>, format: &str) -> Result<()> {
+    let mut check = CompatibilityCheck::default();
+    if let Some(path) = project_path {
+        check.add_rust_project(path)?;
+    }
+
+    match format {
+        "json" => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&serde_json::json!({
+                    "releases": COMPATIBILITY_MATRIX,
+                    "incompatibilities": check
+                        .incompatibilities()
+                        .iter()
+                        .map(|i| i.to_string())
+                        .collect::<Vec<_>>(),
+                }))?
+            );
+        }
+        "human" => {
+            println!("{:<12}{:<24}pyembed", "PyOxidizer", "Distribution formats");
+
+            for (i, release) in COMPATIBILITY_MATRIX.iter().enumerate() {
+                println!(
+                    "{:<12}{:<24}{}.x",
+                    format!("{}.x{}", release.pyoxidizer, if i == 0 { " *" } else { "" }),
+                    release.distribution_formats.join(", "),
+                    release.pyembed
+                );
+            }
+
+            if let Some(path) = project_path {
+                println!();
+
+                if check.incompatibilities().is_empty() {
+                    println!("{} is compatible", path.display());
+                }
+
+                for incompatibility in check.incompatibilities() {
+                    println!("{}", incompatibility);
+                }
+            }
+        }
+        _ => return Err(anyhow!("unknown output format: {}", format)),
     }
 
     Ok(())
@@ -104,8 +231,16 @@ pub fn build(
     target_triple: Option<&str>,
     resolve_targets: Option<Vec<String>>,
     extra_vars: HashMap<String, Option<String>>,
+    features: Vec<String>,
+    strict: bool,
     release: bool,
     verbose: bool,
+    sandbox: bool,
+    trust_file: Option<&Path>,
+    emit_depfile: Option<&Path>,
+    format: &str,
+    trace_out: Option<&Path>,
+    verify_vendored: bool,
 ) -> Result<()> {
     let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
         anyhow!(
@@ -115,23 +250,72 @@ pub fn build(
     })?;
     let target_triple = resolve_target(target_triple)?;
 
+    let sandbox = if sandbox {
+        let mut policy = SandboxPolicy::new(&config_path);
+
+        if let Some(path) = trust_file {
+            policy.load_trust_file(path)?;
+        }
+
+        Some(policy)
+    } else {
+        None
+    };
+
     let mut context =
         EvaluationContextBuilder::new(env, logger.clone(), config_path.clone(), target_triple)
             .extra_vars(extra_vars)
+            .features(features)
+            .strict(strict)
             .release(release)
             .verbose(verbose)
             .resolve_targets_optional(resolve_targets)
+            .sandbox(sandbox)
+            .verify_vendored(verify_vendored)
             .into_context()?;
 
-    context.evaluate_file(&config_path)?;
+    let timings = timing::enable();
 
+    {
+        let _span = timing::span("evaluate config");
+        context.evaluate_file(&config_path)?;
+    }
+
+    let mut outputs = vec![];
     for target in context.targets_to_resolve()? {
-        context.build_resolved_target(&target)?;
+        let _span = timing::span(format!("build target {}", target));
+        outputs.push(context.build_resolved_target(&target)?.output_path);
+    }
+
+    timing::disable();
+
+    if let Some(path) = emit_depfile {
+        let depfile = depfile_from_context(&context, outputs)?;
+        depfile.write(path)?;
+        warn!(
+            logger,
+            "wrote depfile listing {} inputs to {} (inputs digest: {})",
+            depfile.inputs().count(),
+            path.display(),
+            depfile.digest()?
+        );
+    }
+
+    if let Some(path) = trace_out {
+        timings.write_chrome_trace(path)?;
+    }
+
+    let report = timings.report();
+    match format {
+        "human" => print!("{}", report.to_text()),
+        "json" => println!("{}", serde_json::to_string_pretty(&report)?),
+        _ => return Err(anyhow!("unknown output format: {}", format)),
     }
 
     Ok(())
 }
 
+/// Build and run a target, returning the exit code of the launched process.
 #[allow(clippy::too_many_arguments)]
 pub fn run(
     env: &Environment,
@@ -141,8 +325,78 @@ pub fn run(
     release: bool,
     target: Option<&str>,
     extra_vars: HashMap<String, Option<String>>,
-    _extra_args: &[&str],
+    features: Vec<String>,
+    strict: bool,
+    extra_args: &[OsString],
+    cwd: &Path,
+    verbose: bool,
+    trace_startup: bool,
+    wasm_runtime: Option<&str>,
+) -> Result<i32> {
+    let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
+        anyhow!(
+            "unable to find PyOxidizer config file at {}",
+            project_path.display()
+        )
+    })?;
+    let target_triple = resolve_target(target_triple)?;
+
+    let mut context =
+        EvaluationContextBuilder::new(env, logger.clone(), config_path.clone(), target_triple)
+            .extra_vars(extra_vars)
+            .features(features)
+            .strict(strict)
+            .release(release)
+            .verbose(verbose)
+            .trace_startup(trace_startup)
+            .wasm_runtime(wasm_runtime)
+            .resolve_target_optional(target)
+            .into_context()?;
+
+    context.evaluate_file(&config_path)?;
+
+    if !trace_startup {
+        return context.run_target(target, extra_args, cwd);
+    }
+
+    let temp_dir = tempfile::Builder::new()
+        .prefix("pyoxidizer-trace-startup")
+        .tempdir()?;
+    let audit_path = temp_dir.path().join("startup-audit.txt");
+
+    // The built application inherits our environment.
+    std::env::set_var(STARTUP_AUDIT_PATH_ENV, &audit_path);
+    let code = context.run_target(target, extra_args, cwd);
+    std::env::remove_var(STARTUP_AUDIT_PATH_ENV);
+    let code = code?;
+
+    if !audit_path.exists() {
+        return Err(anyhow!(
+            "target did not write a startup audit; only Python executables can be traced"
+        ));
+    }
+
+    eprint!("{}", StartupAuditReport::from_path(&audit_path)?.to_text());
+
+    Ok(code)
+}
+
+/// Build a target and install its files into a prefix directory.
+#[allow(clippy::too_many_arguments)]
+pub fn install(
+    env: &Environment,
+    logger: &slog::Logger,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    release: bool,
+    target: Option<&str>,
+    extra_vars: HashMap<String, Option<String>>,
+    features: Vec<String>,
+    strict: bool,
     verbose: bool,
+    prefix: &Path,
+    receipt_path: Option<&Path>,
+    dry_run: bool,
 ) -> Result<()> {
     let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
         anyhow!(
@@ -155,6 +409,8 @@ pub fn run(
     let mut context =
         EvaluationContextBuilder::new(env, logger.clone(), config_path.clone(), target_triple)
             .extra_vars(extra_vars)
+            .features(features)
+            .strict(strict)
             .release(release)
             .verbose(verbose)
             .resolve_target_optional(target)
@@ -162,10 +418,180 @@ pub fn run(
 
     context.evaluate_file(&config_path)?;
 
-    context.run_target(target)
+    let target = match target {
+        Some(target) => target.to_string(),
+        None => context
+            .default_target()?
+            .ok_or_else(|| anyhow!("no target specified and no default target defined"))?,
+    };
+
+    let resolved = context.build_resolved_target(&target)?;
+    let manifest = manifest_from_dir(&resolved.output_path)?;
+
+    let prefix = std::env::current_dir()?.join(prefix);
+    let receipt_path = receipt_path
+        .map(|p| p.to_path_buf())
+        .unwrap_or_else(|| prefix.join(RECEIPT_FILENAME));
+
+    if dry_run {
+        for line in install_plan(&manifest, &prefix) {
+            println!("{}", line);
+        }
+        println!("write receipt {}", receipt_path.display());
+
+        return Ok(());
+    }
+
+    let outcome = install_manifest(&manifest, &prefix);
+
+    // Record what was installed even if some files failed, so it can be removed.
+    outcome.receipt.write(&receipt_path)?;
+
+    println!(
+        "installed {} file(s) to {}; receipt written to {}",
+        outcome.receipt.files.len(),
+        prefix.display(),
+        receipt_path.display()
+    );
+
+    check_failures("install", &outcome.failures)
 }
 
-pub fn cache_clear(env: &Environment) -> Result<()> {
+/// Remove files recorded in an install receipt.
+pub fn uninstall(receipt_path: &Path, dry_run: bool) -> Result<()> {
+    let receipt = InstallReceipt::from_path(receipt_path)?;
+
+    let outcome = uninstall_receipt(&receipt, dry_run);
+
+    for path in &outcome.removed {
+        println!("remove {}", path.display());
+    }
+    for path in &outcome.modified {
+        println!("keeping modified file {}", path.display());
+    }
+    for path in &outcome.missing {
+        println!("already removed {}", path.display());
+    }
+
+    if dry_run {
+        return check_failures("remove", &outcome.failures);
+    }
+
+    println!(
+        "removed {} file(s); kept {} modified file(s)",
+        outcome.removed.len(),
+        outcome.modified.len()
+    );
+
+    check_failures("remove", &outcome.failures)?;
+
+    // The receipt is only useful while files it records remain.
+    if outcome.modified.is_empty() {
+        std::fs::remove_file(receipt_path)
+            .with_context(|| format!("removing {}", receipt_path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Print the sizes of packed resources data embedded in a file, if any.
+pub fn analyze_packed_resources(path: &Path, resources_report: Option<&Path>) -> Result<()> {
+    let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+    if let Some(range) = find_packed_resources(&data) {
+        print!(
+            "{}",
+            PackedResourcesSizes::from_data(&data[range.clone()])?.to_text()
+        );
+
+        if let Some(report_path) = resources_report {
+            let report = ResourcesReport::from_data(&data[range])?;
+            std::fs::write(report_path, serde_json::to_vec_pretty(&report)?)
+                .with_context(|| format!("writing {}", report_path.display()))?;
+            println!("wrote resources report to {}", report_path.display());
+        }
+    } else if resources_report.is_some() {
+        return Err(anyhow!(
+            "no packed resources data found in {}",
+            path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Show differences between the packed resources of two builds.
+pub fn resources_diff(old: &Path, new: &Path, format: &str, only_packages: &[&str]) -> Result<()> {
+    let mut old = ResourcesReport::from_path(old)?;
+    let mut new = ResourcesReport::from_path(new)?;
+
+    if !only_packages.is_empty() {
+        old.retain_packages(only_packages);
+        new.retain_packages(only_packages);
+    }
+
+    let diff = ResourcesDiff::new(&old, &new);
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&diff)?),
+        "human" => print!("{}", diff.to_text()),
+        _ => return Err(anyhow!("unknown output format: {}", format)),
+    }
+
+    Ok(())
+}
+
+pub fn strip_resources(
+    input: &Path,
+    output: &Path,
+    keep_bytecode_levels: &[u8],
+    strip_source: bool,
+) -> Result<()> {
+    let options = StripOptions {
+        keep_bytecode_levels: keep_bytecode_levels.iter().copied().collect(),
+        strip_source,
+    };
+
+    let (destination, stripped) = strip_resources_file(input, output, &options)?;
+
+    println!("before:\n{}", stripped.before.to_text());
+    println!("after:\n{}", stripped.after.to_text());
+
+    for name in &stripped.modules_without_code {
+        println!(
+            "warning: {} no longer has in-memory source or bytecode",
+            name
+        );
+    }
+
+    match destination {
+        StripDestination::Blob => {
+            println!("wrote packed resources to {}", output.display());
+        }
+        StripDestination::PatchedBinary => {
+            println!("wrote patched executable to {}", output.display());
+            println!("code signatures of the original executable are no longer valid");
+        }
+    }
+
+    Ok(())
+}
+
+pub fn cache_clear(env: &Environment, named_caches: &[&str]) -> Result<()> {
+    if !named_caches.is_empty() {
+        for name in named_caches {
+            validate_named_cache_name(name)?;
+
+            let path = env.named_caches_dir().join(name);
+            if path.exists() {
+                println!("removing {}", path.display());
+                remove_dir_all::remove_dir_all(&path)?;
+            }
+        }
+
+        return Ok(());
+    }
+
     let cache_dir = env.cache_dir();
 
     println!("removing {}", cache_dir.display());
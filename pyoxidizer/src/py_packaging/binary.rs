@@ -7,19 +7,21 @@ Defining and manipulating binaries embedding Python.
 */
 
 use {
-    super::{config::PyembedPythonInterpreterConfig, distribution::AppleSdkInfo},
-    crate::environment::Environment,
+    super::{config::PyembedPythonInterpreterConfig, distribution::AppleSdkInfo, sbom::Sbom},
+    crate::{environment::Environment, timing},
     anyhow::{anyhow, Context, Result},
     python_packaging::{
         policy::PythonPackagingPolicy,
         resource::{
-            PythonExtensionModule, PythonModuleSource, PythonPackageDistributionResource,
-            PythonPackageResource, PythonResource,
+            PythonExtensionModule, PythonModuleBytecode, PythonModuleSource,
+            PythonPackageDistributionResource, PythonPackageResource, PythonResource,
         },
         resource_collection::{
             CompiledResourcesCollection, PrePackagedResource, PythonResourceAddCollectionContext,
+            PythonResourceCollector,
         },
     },
+    python_packed_resources::encryption::EncryptionScheme,
     std::{
         collections::HashMap,
         convert::TryFrom,
@@ -42,6 +44,15 @@ pub enum LibpythonLinkMode {
     Dynamic,
 }
 
+impl std::fmt::Display for LibpythonLinkMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Static => "static",
+            Self::Dynamic => "dynamic",
+        })
+    }
+}
+
 /// Determines how packed resources are loaded by the generated binary.
 ///
 /// This effectively controls how resources file are written to disk
@@ -148,6 +159,16 @@ impl TryFrom<&str> for WindowsRuntimeDllsMode {
     }
 }
 
+/// Describes how packed resources payloads should be encrypted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResourcesEncryption {
+    /// The encryption scheme to use.
+    pub scheme: EncryptionScheme,
+
+    /// X25519 public key of the recipient who can decrypt payloads.
+    pub recipient_public_key: Vec<u8>,
+}
+
 /// A callable that can influence PythonResourceAddCollectionContext.
 pub type ResourceAddCollectionContextCallback<'a> = Box<
     dyn Fn(
@@ -234,6 +255,33 @@ pub trait PythonBinaryBuilder {
     /// Set how packed Python resources will be loaded by the binary.
     fn set_packed_resources_load_mode(&mut self, load_mode: PackedResourcesLoadMode);
 
+    /// How packed Python resources payloads will be encrypted, if at all.
+    fn resources_encryption(&self) -> &Option<ResourcesEncryption>;
+
+    /// Set how packed Python resources payloads will be encrypted.
+    fn set_resources_encryption(&mut self, value: Option<ResourcesEncryption>);
+
+    /// Whether the binary is built with the `startup-trace` pyembed feature.
+    fn trace_startup(&self) -> bool;
+
+    /// Set whether the binary is built with the `startup-trace` pyembed feature.
+    fn set_trace_startup(&mut self, value: bool);
+
+    /// Whether resources from the Python distribution are included.
+    fn include_stdlib(&self) -> bool;
+
+    /// Set whether resources from the Python distribution are included.
+    ///
+    /// Disabling removes resources added by `add_distribution_resources()`
+    /// except those in `required_stdlib_resources()`. Removed resources can't
+    /// be restored, so attempting to enable again is an error.
+    fn set_include_stdlib(&mut self, value: bool) -> Result<()>;
+
+    /// Names of distribution resources retained when `include_stdlib()` is false.
+    ///
+    /// These are required for the interpreter to start.
+    fn required_stdlib_resources(&self) -> Vec<String>;
+
     /// Obtain an iterator over all resource entries that will be embedded in the binary.
     ///
     /// This likely does not return extension modules that are statically linked
@@ -242,6 +290,11 @@ pub trait PythonBinaryBuilder {
         &'a self,
     ) -> Box<dyn Iterator<Item = (&'a String, &'a PrePackagedResource)> + 'a>;
 
+    /// Obtain the collector of resources for modifying already added resources.
+    ///
+    /// Extension modules linked into libpython are not tracked by the collector.
+    fn resources_collector_mut(&mut self) -> &mut PythonResourceCollector;
+
     /// Resolve license metadata from an iterable of `PythonResource` and store that data.
     ///
     /// The resolved license data can later be used to ensure packages conform
@@ -334,6 +387,16 @@ pub trait PythonBinaryBuilder {
         add_context: Option<PythonResourceAddCollectionContext>,
     ) -> Result<()>;
 
+    /// Add a `PythonModuleBytecode` to the resources collection.
+    ///
+    /// The bytecode is only added if the add context requests bytecode at
+    /// its optimization level.
+    fn add_python_module_bytecode(
+        &mut self,
+        module: &PythonModuleBytecode,
+        add_context: Option<PythonResourceAddCollectionContext>,
+    ) -> Result<()>;
+
     /// Add a `PythonPackageResource` to the resources collection.
     ///
     /// The location to load the resource from is optional. If specified, it will
@@ -398,6 +461,13 @@ pub trait PythonBinaryBuilder {
     /// Whether the binary requires the Snmalloc library.
     fn requires_snmalloc(&self) -> bool;
 
+    /// Obtain a software bill of materials describing components of the binary.
+    ///
+    /// This includes the Python distribution, Python package distributions
+    /// having `.dist-info` or `.egg-info` metadata, native libraries, and the
+    /// Rust crates of the generated Rust project.
+    fn software_bill_of_materials(&self) -> Result<Sbom>;
+
     /// Obtain an `EmbeddedPythonContext` instance from this one.
     fn to_embedded_python_context(
         &self,
@@ -500,11 +570,46 @@ impl<'a> EmbeddedPythonContext<'a> {
             self.interpreter_config_rs_path(dest_dir).display()
         ));
 
+        // Record the bytecode magic number so cached artifacts can be
+        // invalidated if they were produced for a different Python.
+        if let Some(magic) = self.config.bytecode_magic_number {
+            lines.push(format!(
+                "cargo:python-bytecode-magic-number={:#010x}",
+                magic
+            ));
+        }
+
         lines
     }
 
+    /// Verify that packed resources are compatible with the interpreter config.
+    ///
+    /// Errors if bytecode in any pending packed resources was compiled for
+    /// a different Python than the one the interpreter config expects.
+    pub fn verify_bytecode_magic_number(&self) -> Result<()> {
+        let expected = match self.config.bytecode_magic_number {
+            Some(magic) => magic,
+            None => return Ok(()),
+        };
+
+        for (collection, path) in &self.pending_resources {
+            collection
+                .verify_bytecode_magic_number(expected)
+                .with_context(|| {
+                    format!(
+                        "verifying packed resources {} against interpreter config",
+                        path.display()
+                    )
+                })?;
+        }
+
+        Ok(())
+    }
+
     /// Ensure packed resources files are written.
     pub fn write_packed_resources(&self, dest_dir: impl AsRef<Path>) -> Result<()> {
+        let _span = timing::span("write packed resources");
+
         for (collection, path) in &self.pending_resources {
             let dest_path = dest_dir.as_ref().join(path);
 
@@ -554,6 +659,8 @@ impl<'a> EmbeddedPythonContext<'a> {
 
     /// Write out files needed to build a binary against our configuration.
     pub fn write_files(&self, dest_dir: &Path) -> Result<()> {
+        self.verify_bytecode_magic_number()
+            .context("verify_bytecode_magic_number()")?;
         self.write_packed_resources(&dest_dir)
             .context("write_packed_resources()")?;
         self.write_libpython(&dest_dir)
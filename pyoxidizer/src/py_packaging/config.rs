@@ -11,9 +11,9 @@ use {
     itertools::Itertools,
     python_packaging::{
         interpreter::{
-            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, MemoryAllocatorBackend,
-            MultiprocessingStartMethod, PythonInterpreterConfig, PythonInterpreterProfile,
-            TerminfoResolution,
+            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, InitFailureAction,
+            MemoryAllocatorBackend, MultiprocessingStartMethod, PythonInterpreterConfig,
+            PythonInterpreterProfile, PythonRunMode, SysExecutableMode, TerminfoResolution,
         },
         resource::BytecodeOptimizationLevel,
     },
@@ -23,13 +23,21 @@ use {
     },
 };
 
+/// Name of the file marking a directory as a filesystem resources root.
+///
+/// This mirrors `pyembed::FILESYSTEM_RESOURCES_ROOT_MARKER`.
+pub const FILESYSTEM_RESOURCES_ROOT_MARKER: &str = ".pyoxidizer-resources-root";
+
 /// Determine the default memory allocator for a target triple.
 pub fn default_memory_allocator(target_triple: &str) -> MemoryAllocatorBackend {
-    // Jemalloc doesn't work on Windows.
+    // Jemalloc doesn't work on Windows or WebAssembly.
     //
     // We don't use Jemalloc by default in the test environment because it slows down
     // builds of test projects.
-    if target_triple.ends_with("-pc-windows-msvc") || cfg!(test) {
+    if target_triple.ends_with("-pc-windows-msvc")
+        || crate::environment::WASI_TARGET_TRIPLES.contains(&target_triple)
+        || cfg!(test)
+    {
         MemoryAllocatorBackend::Default
     } else {
         MemoryAllocatorBackend::Jemalloc
@@ -126,15 +134,33 @@ pub struct PyembedPythonInterpreterConfig {
     pub set_missing_path_configuration: bool,
     pub oxidized_importer: bool,
     pub filesystem_importer: bool,
+    pub resources_filesystem_shim: bool,
     pub packed_resources: Vec<PyembedPackedResourcesSource>,
+    pub filesystem_resources_root: Option<PathBuf>,
+    pub resources_root_env: Option<String>,
+    pub bytecode_magic_number: Option<u32>,
+    /// Path of a Rust function providing the key to decrypt resources.
+    pub resources_key_provider: Option<String>,
     pub argvb: bool,
     pub multiprocessing_auto_dispatch: bool,
     pub multiprocessing_start_method: MultiprocessingStartMethod,
     pub sys_frozen: bool,
     pub sys_meipass: bool,
+    pub sys_executable_mode: SysExecutableMode,
     pub terminfo_resolution: TerminfoResolution,
     pub tcl_library: Option<PathBuf>,
     pub write_modules_directory_env: Option<String>,
+    pub startup_audit_path: Option<PathBuf>,
+    pub post_init_callable: Option<String>,
+    pub pre_finalize_callable: Option<String>,
+    pub run_mode: PythonRunMode,
+    pub repl_banner: Option<String>,
+    pub repl_startup_code: Option<String>,
+    pub repl_history_path: Option<PathBuf>,
+    pub excluded_encodings: Vec<String>,
+    pub failure_exit_code_base: i32,
+    pub on_init_failure: InitFailureAction,
+    pub init_failure_fallback_command: Option<Vec<String>>,
 }
 
 impl Default for PyembedPythonInterpreterConfig {
@@ -161,20 +187,76 @@ impl Default for PyembedPythonInterpreterConfig {
             set_missing_path_configuration: true,
             oxidized_importer: true,
             filesystem_importer: false,
+            resources_filesystem_shim: false,
             packed_resources: vec![],
+            filesystem_resources_root: None,
+            resources_root_env: None,
+            bytecode_magic_number: None,
+            resources_key_provider: None,
             argvb: false,
             multiprocessing_auto_dispatch: true,
             multiprocessing_start_method: MultiprocessingStartMethod::Auto,
             sys_frozen: true,
             sys_meipass: false,
+            sys_executable_mode: SysExecutableMode::App,
             terminfo_resolution: TerminfoResolution::None,
             tcl_library: None,
             write_modules_directory_env: None,
+            startup_audit_path: None,
+            post_init_callable: None,
+            pre_finalize_callable: None,
+            run_mode: PythonRunMode::Default,
+            repl_banner: None,
+            repl_startup_code: None,
+            repl_history_path: None,
+            excluded_encodings: vec![],
+            failure_exit_code_base: 70,
+            on_init_failure: InitFailureAction::Exit,
+            init_failure_fallback_command: None,
         }
     }
 }
 
 impl PyembedPythonInterpreterConfig {
+    /// Resolve where to install the filesystem resources root marker file.
+    ///
+    /// The returned path is relative to the directory of the executable.
+    /// `None` is returned if no root is configured or if the configured root
+    /// isn't relative to `$ORIGIN`, in which case the marker has to be
+    /// put in place when resources are deployed.
+    pub fn filesystem_resources_root_marker_path(&self) -> Option<PathBuf> {
+        let root = match &self.filesystem_resources_root {
+            Some(root) => root.display().to_string(),
+            None if self.resources_root_env.is_some() => "$ORIGIN".to_string(),
+            None => return None,
+        };
+
+        if !root.starts_with("$ORIGIN") {
+            return None;
+        }
+
+        let relative = root["$ORIGIN".len()..].trim_start_matches(&['/', '\\'][..]);
+
+        Some(Path::new(relative).join(FILESYSTEM_RESOURCES_ROOT_MARKER))
+    }
+
+    /// Configure the interpreter to behave like a `python` executable.
+    ///
+    /// Process arguments are parsed like `python` would, the environment is
+    /// honored, and modules can be imported from the filesystem, e.g. from
+    /// the `site-packages` of a virtual environment.
+    pub fn configure_as_python_interpreter(&mut self) {
+        self.config.profile = PythonInterpreterProfile::Python;
+        self.config.parse_argv = None;
+        self.config.run_command = None;
+        self.config.run_filename = None;
+        self.config.run_module = None;
+        self.filesystem_importer = true;
+        self.sys_frozen = false;
+        self.sys_executable_mode = SysExecutableMode::Invoked;
+        self.run_mode = PythonRunMode::Default;
+    }
+
     /// Convert the instance to Rust code that constructs a `pyembed::OxidizedPythonInterpreterConfig`.
     pub fn to_oxidized_python_interpreter_config_rs(&self) -> Result<String> {
         let code = format!(
@@ -248,7 +330,12 @@ impl PyembedPythonInterpreterConfig {
             set_missing_path_configuration: {},\n    \
             oxidized_importer: {},\n    \
             filesystem_importer: {},\n    \
+            resources_filesystem_shim: {},\n    \
             packed_resources: {},\n    \
+            filesystem_resources_root: {},\n    \
+            resources_root_env: {},\n    \
+            bytecode_magic_number: {},\n    \
+            resources_key_provider: {},\n    \
             extra_extension_modules: None,\n    \
             argv: None,\n    \
             argvb: {},\n    \
@@ -256,9 +343,21 @@ impl PyembedPythonInterpreterConfig {
             multiprocessing_start_method: {},\n    \
             sys_frozen: {},\n    \
             sys_meipass: {},\n    \
+            sys_executable_mode: {},\n    \
             terminfo_resolution: {},\n    \
             tcl_library: {},\n    \
             write_modules_directory_env: {},\n    \
+            startup_audit_path: {},\n    \
+            post_init_callable: {},\n    \
+            pre_finalize_callable: {},\n    \
+            run_mode: {},\n    \
+            repl_banner: {},\n    \
+            repl_startup_code: {},\n    \
+            repl_history_path: {},\n    \
+            excluded_encodings: {},\n    \
+            failure_exit_code_base: {},\n    \
+            on_init_failure: {},\n    \
+            init_failure_fallback_command: {},\n    \
             }}\n\
             ",
             match self.config.profile {
@@ -380,6 +479,7 @@ impl PyembedPythonInterpreterConfig {
             self.set_missing_path_configuration,
             self.oxidized_importer,
             self.filesystem_importer,
+            self.resources_filesystem_shim,
             format!(
                 "vec![{}]",
                 self.packed_resources
@@ -387,6 +487,16 @@ impl PyembedPythonInterpreterConfig {
                     .map(|e| e.to_string())
                     .join(", ")
             ),
+            optional_pathbuf_to_string(&self.filesystem_resources_root),
+            optional_string_to_string(&self.resources_root_env),
+            match self.bytecode_magic_number {
+                Some(value) => format!("Some({:#010x})", value),
+                None => "None".to_string(),
+            },
+            match &self.resources_key_provider {
+                Some(path) => format!("Some({})", path),
+                None => "None".to_string(),
+            },
             self.argvb,
             self.multiprocessing_auto_dispatch,
             match self.multiprocessing_start_method {
@@ -403,6 +513,12 @@ impl PyembedPythonInterpreterConfig {
             },
             self.sys_frozen,
             self.sys_meipass,
+            match self.sys_executable_mode {
+                SysExecutableMode::App => "pyembed::SysExecutableMode::App",
+                SysExecutableMode::None => "pyembed::SysExecutableMode::None",
+                SysExecutableMode::PythonShim => "pyembed::SysExecutableMode::PythonShim",
+                SysExecutableMode::Invoked => "pyembed::SysExecutableMode::Invoked",
+            },
             match self.terminfo_resolution {
                 TerminfoResolution::Dynamic => "pyembed::TerminfoResolution::Dynamic".to_string(),
                 TerminfoResolution::None => "pyembed::TerminfoResolution::None".to_string(),
@@ -412,6 +528,31 @@ impl PyembedPythonInterpreterConfig {
             },
             optional_pathbuf_to_string(&self.tcl_library),
             optional_string_to_string(&self.write_modules_directory_env),
+            optional_pathbuf_to_string(&self.startup_audit_path),
+            optional_string_to_string(&self.post_init_callable),
+            optional_string_to_string(&self.pre_finalize_callable),
+            match self.run_mode {
+                PythonRunMode::Default => "pyembed::PythonRunMode::Default",
+                PythonRunMode::Repl => "pyembed::PythonRunMode::Repl",
+            },
+            optional_string_to_string(&self.repl_banner),
+            optional_string_to_string(&self.repl_startup_code),
+            optional_pathbuf_to_string(&self.repl_history_path),
+            format!(
+                "vec![{}]",
+                self.excluded_encodings
+                    .iter()
+                    .map(|s| format!("\"{}\".to_string()", s.escape_default()))
+                    .join(", ")
+            ),
+            self.failure_exit_code_base,
+            match self.on_init_failure {
+                InitFailureAction::Exit => "pyembed::InitFailureAction::Exit",
+                InitFailureAction::MessageBox => "pyembed::InitFailureAction::MessageBox",
+                InitFailureAction::RunFallbackCommand =>
+                    "pyembed::InitFailureAction::RunFallbackCommand",
+            },
+            optional_vec_string_to_string(&self.init_failure_fallback_command),
         );
 
         Ok(code)
@@ -511,6 +652,201 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_startup_audit_path() -> Result<()> {
+        let mut config = PyembedPythonInterpreterConfig::default();
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(&code, "startup_audit_path: None,")?;
+
+        config.startup_audit_path = Some(PathBuf::from("$ORIGIN/startup-audit.txt"));
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(
+            &code,
+            "startup_audit_path: Some(std::path::PathBuf::from(\"$ORIGIN/startup-audit.txt\")),",
+        )
+    }
+
+    #[test]
+    fn test_filesystem_resources_root() -> Result<()> {
+        let mut config = PyembedPythonInterpreterConfig::default();
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(&code, "filesystem_resources_root: None,")?;
+        assert_contains(&code, "resources_root_env: None,")?;
+
+        config.filesystem_resources_root = Some(PathBuf::from("$ORIGIN/lib"));
+        config.resources_root_env = Some("MYAPP_RESOURCE_ROOT".to_string());
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(
+            &code,
+            "filesystem_resources_root: Some(std::path::PathBuf::from(\"$ORIGIN/lib\")),",
+        )?;
+        assert_contains(
+            &code,
+            "resources_root_env: Some(\"MYAPP_RESOURCE_ROOT\".to_string()),",
+        )
+    }
+
+    #[test]
+    fn test_filesystem_resources_root_marker_path() {
+        let mut config = PyembedPythonInterpreterConfig::default();
+        assert_eq!(config.filesystem_resources_root_marker_path(), None);
+
+        config.resources_root_env = Some("MYAPP_RESOURCE_ROOT".to_string());
+        assert_eq!(
+            config.filesystem_resources_root_marker_path(),
+            Some(PathBuf::from(FILESYSTEM_RESOURCES_ROOT_MARKER))
+        );
+
+        config.filesystem_resources_root = Some(PathBuf::from("$ORIGIN/lib"));
+        assert_eq!(
+            config.filesystem_resources_root_marker_path(),
+            Some(PathBuf::from("lib").join(FILESYSTEM_RESOURCES_ROOT_MARKER))
+        );
+
+        config.filesystem_resources_root = Some(PathBuf::from("/mnt/shared/myapp"));
+        assert_eq!(config.filesystem_resources_root_marker_path(), None);
+    }
+
+    #[test]
+    fn test_lifecycle_callables() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            post_init_callable: Some("app.post_init".to_string()),
+            ..PyembedPythonInterpreterConfig::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(
+            &code,
+            "post_init_callable: Some(\"app.post_init\".to_string()),",
+        )?;
+        assert_contains(&code, "pre_finalize_callable: None,")
+    }
+
+    #[test]
+    fn test_resources_key_provider() -> Result<()> {
+        let mut config = PyembedPythonInterpreterConfig::default();
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(&code, "resources_key_provider: None,")?;
+
+        config.resources_key_provider = Some("resources_key_provider".to_string());
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(
+            &code,
+            "resources_key_provider: Some(resources_key_provider),",
+        )
+    }
+
+    #[test]
+    fn test_run_mode_repl() -> Result<()> {
+        let mut config = PyembedPythonInterpreterConfig::default();
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(&code, "run_mode: pyembed::PythonRunMode::Default,")?;
+        assert_contains(&code, "repl_history_path: None,")?;
+
+        config.run_mode = PythonRunMode::Repl;
+        config.repl_banner = Some("My \"Console\"".to_string());
+        config.repl_startup_code = Some("import os\nimport sys".to_string());
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(&code, "run_mode: pyembed::PythonRunMode::Repl,")?;
+        assert_contains(
+            &code,
+            "repl_banner: Some(\"My \\\"Console\\\"\".to_string()),",
+        )?;
+        assert_contains(
+            &code,
+            "repl_startup_code: Some(\"import os\\nimport sys\".to_string()),",
+        )
+    }
+
+    #[test]
+    fn test_excluded_encodings() -> Result<()> {
+        let mut config = PyembedPythonInterpreterConfig::default();
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(&code, "excluded_encodings: vec![],")?;
+
+        config.excluded_encodings = vec!["cp1252".to_string(), "shift_jis".to_string()];
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(
+            &code,
+            "excluded_encodings: vec![\"cp1252\".to_string(), \"shift_jis\".to_string()],",
+        )
+    }
+
+    #[test]
+    fn test_init_failure() -> Result<()> {
+        let mut config = PyembedPythonInterpreterConfig::default();
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(&code, "failure_exit_code_base: 70,")?;
+        assert_contains(&code, "on_init_failure: pyembed::InitFailureAction::Exit,")?;
+        assert_contains(&code, "init_failure_fallback_command: None,")?;
+
+        config.failure_exit_code_base = 100;
+        config.on_init_failure = InitFailureAction::RunFallbackCommand;
+        config.init_failure_fallback_command =
+            Some(vec!["$ORIGIN/fallback".to_string(), "--safe".to_string()]);
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(&code, "failure_exit_code_base: 100,")?;
+        assert_contains(
+            &code,
+            "on_init_failure: pyembed::InitFailureAction::RunFallbackCommand,",
+        )?;
+        assert_contains(
+            &code,
+            "init_failure_fallback_command: Some(vec![\"$ORIGIN/fallback\".to_string(), \"--safe\".to_string()]),",
+        )
+    }
+
+    #[test]
+    fn test_configure_as_python_interpreter() -> Result<()> {
+        let mut config = PyembedPythonInterpreterConfig::default();
+        config.config.run_module = Some("myapp".to_string());
+        config.run_mode = PythonRunMode::Repl;
+
+        config.configure_as_python_interpreter();
+        assert_eq!(config.config.profile, PythonInterpreterProfile::Python);
+        assert_eq!(config.config.run_module, None);
+        assert_eq!(config.run_mode, PythonRunMode::Default);
+        assert!(config.filesystem_importer);
+        assert!(!config.sys_frozen);
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(&code, "profile: pyembed::PythonInterpreterProfile::Python,")?;
+        assert_contains(
+            &code,
+            "sys_executable_mode: pyembed::SysExecutableMode::Invoked,",
+        )
+    }
+
+    #[test]
+    fn test_sys_executable_mode() -> Result<()> {
+        let mut config = PyembedPythonInterpreterConfig::default();
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(
+            &code,
+            "sys_executable_mode: pyembed::SysExecutableMode::App,",
+        )?;
+
+        config.sys_executable_mode = SysExecutableMode::PythonShim;
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(
+            &code,
+            "sys_executable_mode: pyembed::SysExecutableMode::PythonShim,",
+        )
+    }
+
     // TODO enable once CI has a linkable Python.
     #[test]
     #[ignore]
@@ -588,18 +924,35 @@ mod tests {
             set_missing_path_configuration: false,
             oxidized_importer: true,
             filesystem_importer: true,
+            resources_filesystem_shim: true,
             packed_resources: vec![
                 PyembedPackedResourcesSource::MemoryIncludeBytes(PathBuf::from("packed-resources")),
                 PyembedPackedResourcesSource::MemoryMappedPath(PathBuf::from(
                     "$ORIGIN/packed-resources",
                 )),
             ],
+            filesystem_resources_root: Some("$ORIGIN".into()),
+            resources_root_env: Some("env".into()),
+            bytecode_magic_number: Some(0x0a0d0d55),
+            resources_key_provider: None,
             argvb: true,
             sys_frozen: false,
             sys_meipass: true,
+            sys_executable_mode: SysExecutableMode::PythonShim,
             terminfo_resolution: TerminfoResolution::Dynamic,
             tcl_library: Some("path".into()),
             write_modules_directory_env: Some("env".into()),
+            startup_audit_path: Some("$ORIGIN/startup-audit.txt".into()),
+            post_init_callable: Some("app.post_init".into()),
+            pre_finalize_callable: Some("app.pre_finalize".into()),
+            run_mode: PythonRunMode::Repl,
+            repl_banner: Some("banner".into()),
+            repl_startup_code: Some("import os".into()),
+            repl_history_path: Some("history".into()),
+            excluded_encodings: vec!["cp1252".into()],
+            failure_exit_code_base: 100,
+            on_init_failure: InitFailureAction::RunFallbackCommand,
+            init_failure_fallback_command: Some(vec!["$ORIGIN/fallback".into(), "--safe".into()]),
             multiprocessing_auto_dispatch: false,
             multiprocessing_start_method: MultiprocessingStartMethod::Spawn,
         };
@@ -12,7 +12,7 @@ use {
         config::PyembedPythonInterpreterConfig,
         standalone_distribution::StandaloneDistribution,
     },
-    crate::python_distributions::PYTHON_DISTRIBUTIONS,
+    crate::{python_distributions::PYTHON_DISTRIBUTIONS, timing},
     anyhow::{anyhow, Context, Result},
     fs2::FileExt,
     python_packaging::{
@@ -48,6 +48,32 @@ pub enum BinaryLibpythonLinkMode {
     Dynamic,
 }
 
+impl std::fmt::Display for BinaryLibpythonLinkMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Default => "auto",
+            Self::Static => "static",
+            Self::Dynamic => "dynamic",
+        })
+    }
+}
+
+impl TryFrom<&str> for BinaryLibpythonLinkMode {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "auto" => Ok(Self::Default),
+            "static" => Ok(Self::Static),
+            "dynamic" => Ok(Self::Dynamic),
+            _ => Err(format!(
+                "{} is not a valid libpython link mode; must be 'auto', 'static', or 'dynamic'",
+                value
+            )),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum PythonDistributionLocation {
     Local { local_path: String, sha256: String },
@@ -279,8 +305,8 @@ fn sha256_path(path: &Path) -> Vec<u8> {
 /// Ensure a Python distribution at a URL is available in a local directory.
 ///
 /// The path to the downloaded and validated file is returned.
-pub fn download_distribution(url: &str, sha256: &str, cache_dir: &Path) -> Result<PathBuf> {
-    let expected_hash = hex::decode(sha256)?;
+/// Resolve the path a distribution downloaded from `url` is cached at.
+pub fn download_cache_path(url: &str, cache_dir: &Path) -> Result<PathBuf> {
     let u = Url::parse(url)?;
 
     let basename = u
@@ -290,7 +316,14 @@ pub fn download_distribution(url: &str, sha256: &str, cache_dir: &Path) -> Resul
         .unwrap()
         .to_string();
 
-    let cache_path = cache_dir.join(basename);
+    Ok(cache_dir.join(basename))
+}
+
+pub fn download_distribution(url: &str, sha256: &str, cache_dir: &Path) -> Result<PathBuf> {
+    let expected_hash = hex::decode(sha256)?;
+    let u = Url::parse(url)?;
+
+    let cache_path = download_cache_path(url, cache_dir)?;
 
     if cache_path.exists() {
         let file_hash = sha256_path(&cache_path);
@@ -471,6 +504,55 @@ impl DistributionCache {
         }
     }
 
+    /// Register an already resolved distribution with the cache.
+    ///
+    /// Subsequent resolutions of `location` into `dest_dir` will return `dist`.
+    pub fn insert(
+        &self,
+        location: &PythonDistributionLocation,
+        dest_dir: &Path,
+        dist: Arc<StandaloneDistribution>,
+    ) -> Result<()> {
+        let mut lock = self
+            .cache
+            .lock()
+            .map_err(|e| anyhow!("cannot obtain distribution cache lock: {}", e))?;
+
+        lock.insert(
+            (dest_dir.to_path_buf(), location.clone()),
+            Arc::new(Mutex::new(Some(dist))),
+        );
+
+        Ok(())
+    }
+
+    /// Whether a distribution has already been resolved into a storage location.
+    ///
+    /// Resolving such a distribution again doesn't perform any I/O.
+    pub fn is_resolved(
+        &self,
+        location: &PythonDistributionLocation,
+        dest_dir: Option<&Path>,
+    ) -> bool {
+        let dest_dir = match (dest_dir, &self.default_dest_dir) {
+            (Some(p), _) => p,
+            (None, Some(p)) => p,
+            (None, None) => return false,
+        };
+
+        let entry = match self.cache.lock() {
+            Ok(lock) => lock
+                .get(&(dest_dir.to_path_buf(), location.clone()))
+                .cloned(),
+            Err(_) => None,
+        };
+
+        match entry {
+            Some(entry) => matches!(entry.try_lock(), Ok(value) if value.is_some()),
+            None => false,
+        }
+    }
+
     /// Resolve a `PythonDistribution` given its source and storage locations.
     pub fn resolve_distribution(
         &self,
@@ -545,6 +627,7 @@ impl DistributionCache {
         if let Some(dist) = value {
             Ok(dist.clone())
         } else {
+            let _span = timing::span("acquire Python distribution");
             let dist = Arc::new(StandaloneDistribution::from_location(
                 logger, location, &dest_dir,
             )?);
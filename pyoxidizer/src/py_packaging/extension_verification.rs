@@ -0,0 +1,230 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Verification of shared library extension modules.
+
+An extension module that is misnamed or built for a different Python or
+machine only fails when it is imported. The checks in this module find
+these problems when the extension module is packaged.
+*/
+
+use {
+    python_packaging::resource::PythonExtensionModule,
+    tugger_binary_analysis::{find_shared_library_exports, normalize_architecture},
+};
+
+/// Whether exported symbols include the init function of an extension module.
+///
+/// Returns the expected symbol name and whether it was found. Modules with
+/// non-ASCII names export `PyInitU_` followed by the punycode encoded name,
+/// so any symbol with that prefix is accepted for them.
+fn find_init_symbol<'a>(
+    name: &str,
+    symbols: impl IntoIterator<Item = &'a String>,
+) -> (String, bool) {
+    let leaf = name.rsplit('.').next().unwrap_or(name);
+
+    if leaf.is_ascii() {
+        let expected = format!("PyInit_{}", leaf);
+        let found = symbols.into_iter().any(|s| s == &expected);
+
+        (expected, found)
+    } else {
+        let found = symbols.into_iter().any(|s| s.starts_with("PyInitU_"));
+
+        ("PyInitU_<punycode name>".to_string(), found)
+    }
+}
+
+/// Verify the shared library of an extension module targets the expected interpreter.
+///
+/// `extension_suffixes` are the filename suffixes of extension modules
+/// recognized by the target distribution. An empty slice disables the
+/// suffix check.
+///
+/// Returns a description of each problem found. Extension modules without
+/// a shared library are not verified.
+pub fn verify_extension_module(
+    module: &PythonExtensionModule,
+    target_triple: &str,
+    extension_suffixes: &[String],
+) -> Vec<String> {
+    let data = match &module.shared_library {
+        Some(data) => data,
+        None => return vec![],
+    };
+
+    let mut problems = vec![];
+
+    if !module.extension_file_suffix.is_empty()
+        && !extension_suffixes.is_empty()
+        && !extension_suffixes.contains(&module.extension_file_suffix)
+    {
+        problems.push(format!(
+            "filename suffix: expected one of {}; found {}",
+            extension_suffixes.join(", "),
+            module.extension_file_suffix
+        ));
+    }
+
+    let exports = match data
+        .resolve_content()
+        .map_err(anyhow::Error::from)
+        .and_then(|data| find_shared_library_exports(&data))
+    {
+        Ok(exports) => exports,
+        Err(e) => {
+            problems.push(format!("unable to analyze shared library: {}", e));
+            return problems;
+        }
+    };
+
+    let (expected, found) = find_init_symbol(&module.name, &exports.symbols);
+    if !found {
+        let init_symbols = exports
+            .symbols
+            .iter()
+            .filter(|s| s.starts_with("PyInit"))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        problems.push(format!(
+            "init function: expected {}; found {}",
+            expected,
+            if init_symbols.is_empty() {
+                "no PyInit symbols".to_string()
+            } else {
+                init_symbols.join(", ")
+            }
+        ));
+    }
+
+    let architecture = normalize_architecture(target_triple);
+    if !exports.architectures.contains(&architecture) {
+        problems.push(format!(
+            "{} architecture: expected {} (from {}); found {}",
+            exports.format,
+            architecture,
+            target_triple,
+            exports.architectures.join(", ")
+        ));
+    }
+
+    problems
+}
+
+/// Format problems found by `verify_extension_module()` for display.
+pub fn format_extension_module_problems(
+    module: &PythonExtensionModule,
+    problems: &[String],
+) -> String {
+    format!(
+        "extension module {} (shared library {}{}) failed verification: {}",
+        module.name,
+        module.name.rsplit('.').next().unwrap_or(&module.name),
+        module.extension_file_suffix,
+        problems.join("; ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, anyhow::Result, tugger_file_manifest::FileData};
+
+    fn extension_module(name: &str, suffix: &str, data: Vec<u8>) -> PythonExtensionModule {
+        PythonExtensionModule {
+            name: name.to_string(),
+            init_fn: None,
+            extension_file_suffix: suffix.to_string(),
+            shared_library: Some(FileData::Memory(data)),
+            object_file_data: vec![],
+            is_package: false,
+            link_libraries: vec![],
+            is_stdlib: false,
+            builtin_default: false,
+            required: false,
+            variant: None,
+            license: None,
+        }
+    }
+
+    #[test]
+    fn test_find_init_symbol() {
+        let symbols = vec!["PyInit_bar".to_string(), "other".to_string()];
+
+        assert_eq!(
+            find_init_symbol("foo.bar", &symbols),
+            ("PyInit_bar".to_string(), true)
+        );
+        assert_eq!(
+            find_init_symbol("foo", &symbols),
+            ("PyInit_foo".to_string(), false)
+        );
+        assert!(find_init_symbol("café", &["PyInitU_caf_dma".to_string()]).1);
+    }
+
+    #[test]
+    fn test_no_shared_library() {
+        let mut module = extension_module("foo", ".so", vec![]);
+        module.shared_library = None;
+
+        assert!(verify_extension_module(&module, "x86_64-unknown-linux-gnu", &[]).is_empty());
+    }
+
+    #[test]
+    fn test_unparseable() {
+        let module = extension_module("foo", ".cpython-38-x86_64-linux-gnu.so", b"junk".to_vec());
+
+        let problems = verify_extension_module(
+            &module,
+            "x86_64-unknown-linux-gnu",
+            &[
+                ".cpython-39-x86_64-linux-gnu.so".to_string(),
+                ".abi3.so".to_string(),
+                ".so".to_string(),
+            ],
+        );
+
+        assert_eq!(problems.len(), 2);
+        assert_eq!(
+            problems[0],
+            "filename suffix: expected one of .cpython-39-x86_64-linux-gnu.so, .abi3.so, .so; found .cpython-38-x86_64-linux-gnu.so"
+        );
+        assert!(problems[1].starts_with("unable to analyze shared library"));
+
+        assert!(
+            format_extension_module_problems(&module, &problems).starts_with(
+                "extension module foo (shared library foo.cpython-38-x86_64-linux-gnu.so) failed verification: filename suffix"
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn test_wrong_binary() -> Result<()> {
+        // The test binary is an ELF executable lacking a PyInit symbol.
+        let data = std::fs::read(std::env::current_exe()?)?;
+        let module = extension_module("foo", ".so", data);
+
+        let problems = verify_extension_module(&module, "aarch64-unknown-linux-gnu", &[]);
+
+        assert_eq!(
+            problems,
+            vec![
+                "init function: expected PyInit_foo; found no PyInit symbols".to_string(),
+                "elf architecture: expected aarch64 (from aarch64-unknown-linux-gnu); found x86_64"
+                    .to_string(),
+            ]
+        );
+
+        assert!(
+            verify_extension_module(&module, "x86_64-unknown-linux-gnu", &[])
+                .iter()
+                .all(|p| !p.contains("architecture"))
+        );
+
+        Ok(())
+    }
+}
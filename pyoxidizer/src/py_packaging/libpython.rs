@@ -7,7 +7,7 @@ Building a native binary containing Python.
 */
 
 use {
-    crate::{environment::Environment, py_packaging::distribution::AppleSdkInfo},
+    crate::{environment::Environment, py_packaging::distribution::AppleSdkInfo, timing},
     anyhow::{anyhow, Context, Result},
     python_packaging::libpython::LibPythonBuildContext,
     slog::warn,
@@ -68,6 +68,8 @@ pub fn link_libpython(
     opt_level: &str,
     apple_sdk_info: Option<&AppleSdkInfo>,
 ) -> Result<LibpythonInfo> {
+    let _span = timing::span("link libpython");
+
     let mut cargo_metadata: Vec<String> = Vec::new();
 
     let temp_dir = tempfile::Builder::new().prefix("libpython").tempdir()?;
@@ -0,0 +1,217 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Verification of `encodings` packages trimmed by `PythonPackagingPolicy.minimal_encodings()`.
+
+The interpreter looks up a few codecs during initialization. Which ones
+depends on the target platform and the interpreter configuration. If a
+needed codec was excluded, the interpreter fails to start. The checks in
+this module find these problems when the application is built.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    python_packaging::{interpreter::PythonInterpreterConfig, policy::normalize_encoding_name},
+    std::{
+        collections::BTreeSet,
+        path::Path,
+        process::{Command, Stdio},
+    },
+    tugger_file_manifest::FileData,
+};
+
+/// Codecs only implemented on Windows.
+const WINDOWS_ONLY_ENCODINGS: &[&str] = &["mbcs", "oem"];
+
+/// Python code resolving codecs as `encodings` does, without excluded modules.
+///
+/// Arguments are a comma delimited list of kept `encodings` submodules
+/// followed by encoding names. Encoding names that can't be resolved are
+/// printed.
+const BOOT_CHECK_SOURCE: &str = r#"
+import encodings
+import sys
+
+keep = set(sys.argv[1].split(","))
+
+
+def excluded(name):
+    parts = name.split(".")
+    return parts[0] == "encodings" and len(parts) > 1 and parts[1] not in keep
+
+
+class ExcludedFinder:
+    @classmethod
+    def find_spec(cls, fullname, path=None, target=None):
+        if excluded(fullname):
+            raise ImportError("%s is excluded" % fullname)
+        return None
+
+
+sys.meta_path.insert(0, ExcludedFinder)
+for name in list(sys.modules):
+    if excluded(name):
+        del sys.modules[name]
+encodings._cache.clear()
+
+for name in sys.argv[2:]:
+    # codecs.lookup() normalizes names like this before searching.
+    if encodings.search_function(name.lower().replace(" ", "_")) is None:
+        print(name)
+"#;
+
+/// Resolve the names of codecs the interpreter looks up during initialization.
+///
+/// Without UTF-8 mode, the locale of the machine running the application
+/// determines the encoding of the standard streams. On Windows, this is the
+/// ANSI code page, which can't be known at build time and isn't included.
+/// On other platforms, `ascii` is included for the `C` locale.
+pub fn boot_encodings(config: &PythonInterpreterConfig, target_triple: &str) -> Vec<String> {
+    let mut encodings = vec!["utf-8".to_string(), "latin-1".to_string()];
+
+    encodings.extend(config.filesystem_encoding.iter().cloned());
+    encodings.extend(config.stdio_encoding.iter().cloned());
+
+    if config.utf8_mode != Some(true) {
+        if target_triple.contains("pc-windows") {
+            if config.legacy_windows_fs_encoding == Some(true) {
+                encodings.push("mbcs".to_string());
+            }
+        } else {
+            encodings.push("ascii".to_string());
+        }
+    }
+
+    let mut seen = BTreeSet::new();
+    encodings.retain(|name| seen.insert(normalize_encoding_name(name)));
+
+    encodings
+}
+
+/// Find encoding names that can't be resolved using only kept `encodings` submodules.
+///
+/// An interpreter is run to perform the lookups like the `encodings` package
+/// does at run-time. Windows only codecs can't be looked up on other
+/// platforms, so they only need to be kept.
+pub fn find_unresolvable_encodings(
+    python_exe: &Path,
+    keep: &BTreeSet<String>,
+    encodings: &[String],
+) -> Result<Vec<String>> {
+    let mut missing = vec![];
+    let mut lookup = vec![];
+
+    for name in encodings {
+        let normalized = normalize_encoding_name(name);
+
+        if !cfg!(windows) && WINDOWS_ONLY_ENCODINGS.contains(&normalized.as_str()) {
+            if !keep.contains(&normalized) {
+                missing.push(name.clone());
+            }
+        } else {
+            lookup.push(name.clone());
+        }
+    }
+
+    if lookup.is_empty() {
+        return Ok(missing);
+    }
+
+    let output = Command::new(python_exe)
+        .arg("-I")
+        .arg("-S")
+        .arg("-c")
+        .arg(BOOT_CHECK_SOURCE)
+        .arg(keep.iter().cloned().collect::<Vec<_>>().join(","))
+        .args(&lookup)
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("running {}", python_exe.display()))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "error resolving encodings with {}: {}",
+            python_exe.display(),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    missing.extend(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string()),
+    );
+
+    Ok(missing)
+}
+
+/// Total size in bytes of file data.
+pub fn file_data_size<'a>(data: impl IntoIterator<Item = &'a FileData>) -> Result<u64> {
+    let mut size = 0;
+
+    for data in data {
+        size += match data {
+            FileData::Memory(data) => data.len() as u64,
+            FileData::Path(path) => std::fs::metadata(path)
+                .with_context(|| format!("reading metadata of {}", path.display()))?
+                .len(),
+        };
+    }
+
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boot_encodings() {
+        let mut config = PythonInterpreterConfig::default();
+
+        assert_eq!(
+            boot_encodings(&config, "x86_64-unknown-linux-gnu"),
+            vec!["utf-8", "latin-1", "ascii"]
+        );
+        assert_eq!(
+            boot_encodings(&config, "x86_64-pc-windows-msvc"),
+            vec!["utf-8", "latin-1"]
+        );
+
+        config.utf8_mode = Some(true);
+        config.stdio_encoding = Some("UTF-8".to_string());
+        config.filesystem_encoding = Some("cp1252".to_string());
+        assert_eq!(
+            boot_encodings(&config, "x86_64-unknown-linux-gnu"),
+            vec!["utf-8", "latin-1", "cp1252"]
+        );
+
+        config.utf8_mode = None;
+        config.legacy_windows_fs_encoding = Some(true);
+        assert_eq!(
+            boot_encodings(&config, "x86_64-pc-windows-msvc"),
+            vec!["utf-8", "latin-1", "cp1252", "mbcs"]
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_windows_only_encodings() -> Result<()> {
+        // Windows only codecs aren't looked up, so no interpreter is needed.
+        let keep = ["mbcs".to_string()].iter().cloned().collect();
+
+        assert_eq!(
+            find_unresolvable_encodings(
+                Path::new("/does/not/exist"),
+                &keep,
+                &["MBCS".to_string(), "oem".to_string()]
+            )?,
+            vec!["oem".to_string()]
+        );
+
+        Ok(())
+    }
+}
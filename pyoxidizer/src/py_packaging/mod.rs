@@ -12,9 +12,12 @@ pub mod binary;
 pub mod config;
 pub mod distribution;
 pub mod distutils;
+pub mod extension_verification;
 pub mod filtering;
 pub mod libpython;
+pub mod minimal_encodings;
 pub mod packaging_tool;
 pub mod resource;
+pub mod sbom;
 pub mod standalone_builder;
 pub mod standalone_distribution;
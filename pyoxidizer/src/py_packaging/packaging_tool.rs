@@ -11,11 +11,12 @@ use {
         binary::LibpythonLinkMode, distribution::PythonDistribution,
         distutils::read_built_extensions, standalone_distribution::resolve_python_paths,
     },
+    crate::timing,
     anyhow::{anyhow, Result},
     duct::cmd,
     python_packaging::{
-        filesystem_scanning::find_python_resources, policy::PythonPackagingPolicy,
-        resource::PythonResource, wheel::WheelArchive,
+        filesystem_scanning::find_python_resources, package_metadata::PythonRequirement,
+        policy::PythonPackagingPolicy, resource::PythonResource, wheel::WheelArchive,
     },
     slog::warn,
     std::{
@@ -90,6 +91,8 @@ pub fn pip_download<'a>(
     verbose: bool,
     args: &[String],
 ) -> Result<Vec<PythonResource<'a>>> {
+    let _span = timing::span(format!("pip download {}", args.join(" ")));
+
     let temp_dir = tempfile::Builder::new()
         .prefix("pyoxidizer-pip-download")
         .tempdir()?;
@@ -191,6 +194,8 @@ pub fn pip_install<'a, S: BuildHasher>(
     install_args: &[String],
     extra_envs: &HashMap<String, String, S>,
 ) -> Result<Vec<PythonResource<'a>>> {
+    let _span = timing::span(format!("pip install {}", install_args.join(" ")));
+
     let temp_dir = tempfile::Builder::new()
         .prefix("pyoxidizer-pip-install")
         .tempdir()?;
@@ -251,6 +256,66 @@ pub fn pip_install<'a, S: BuildHasher>(
     find_resources(dist, policy, &target_dir, state_dir)
 }
 
+/// `pip install` options whose value is passed as the following argument.
+const PIP_INSTALL_VALUE_OPTIONS: &[&str] = &[
+    "-c",
+    "--constraint",
+    "-e",
+    "--editable",
+    "-r",
+    "--requirement",
+    "-t",
+    "--target",
+    "-i",
+    "--index-url",
+    "--extra-index-url",
+    "-f",
+    "--find-links",
+    "--platform",
+    "--python-version",
+    "--implementation",
+    "--abi",
+    "--root",
+    "--prefix",
+    "--src",
+    "--upgrade-strategy",
+    "--install-option",
+    "--global-option",
+    "--no-binary",
+    "--only-binary",
+    "--progress-bar",
+    "--trusted-host",
+    "--cache-dir",
+    "--log",
+    "--proxy",
+    "--retries",
+    "--timeout",
+    "--exists-action",
+    "--cert",
+    "--client-cert",
+];
+
+/// Obtain the requirements named directly in `pip install` arguments.
+///
+/// Options and arguments that aren't requirements, such as paths to
+/// local wheels, are ignored.
+pub fn pip_install_requirements(install_args: &[String]) -> Vec<PythonRequirement> {
+    let mut res = vec![];
+    let mut skip_next = false;
+
+    for arg in install_args {
+        if skip_next {
+            skip_next = false;
+        } else if arg.starts_with('-') {
+            skip_next = PIP_INSTALL_VALUE_OPTIONS.contains(&arg.as_str());
+        } else if let Ok(requirement) = PythonRequirement::parse(arg) {
+            res.push(requirement);
+        }
+    }
+
+    res
+}
+
 /// Discover Python resources from a populated virtualenv directory.
 pub fn read_virtualenv<'a>(
     dist: &dyn PythonDistribution,
@@ -281,6 +346,8 @@ pub fn setup_py_install<'a, S: BuildHasher>(
         ));
     }
 
+    let _span = timing::span(format!("setup.py install {}", package_path.display()));
+
     let temp_dir = tempfile::Builder::new()
         .prefix("pyoxidizer-setup-py-install")
         .tempdir()?;
@@ -363,6 +430,35 @@ mod tests {
         std::{collections::BTreeSet, ops::Deref},
     };
 
+    #[test]
+    fn test_pip_install_requirements() {
+        let args = [
+            "myapp[postgres,redis]>=1.0",
+            "-r",
+            "requirements.txt",
+            "--no-deps",
+            "--index-url=https://example.com/simple",
+            "./dist/local.whl",
+            "six",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+
+        let requirements = pip_install_requirements(&args);
+
+        assert_eq!(
+            requirements
+                .iter()
+                .map(|r| (r.name.as_str(), r.extras.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("myapp", vec!["postgres".to_string(), "redis".to_string()]),
+                ("six", vec![]),
+            ]
+        );
+    }
+
     #[test]
     fn test_install_black() -> Result<()> {
         let logger = get_logger()?;
@@ -0,0 +1,702 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Software bill of materials (SBOM) generation.
+
+An [Sbom] describes the software components constituting a built binary.
+It can be serialized to SPDX 2.3 and CycloneDX 1.4 JSON documents.
+*/
+
+use {
+    anyhow::{anyhow, Result},
+    chrono::{DateTime, SecondsFormat, TimeZone, Utc},
+    serde_json::{json, Value},
+    std::convert::TryFrom,
+    tugger_licensing::{ComponentFlavor, LicenseFlavor, LicensedComponent},
+};
+
+/// SPDX value for information that wasn't determined.
+const NOASSERTION: &str = "NOASSERTION";
+
+/// Format of a serialized SBOM.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SbomFormat {
+    /// SPDX 2.3 JSON.
+    SpdxJson,
+    /// CycloneDX 1.4 JSON.
+    CycloneDxJson,
+}
+
+impl TryFrom<&str> for SbomFormat {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "spdx-json" => Ok(Self::SpdxJson),
+            "cyclonedx-json" => Ok(Self::CycloneDxJson),
+            _ => Err(format!(
+                "{} is not a valid SBOM format; use spdx-json or cyclonedx-json",
+                value
+            )),
+        }
+    }
+}
+
+impl SbomFormat {
+    /// Conventional file name suffix for documents in this format.
+    pub fn file_suffix(&self) -> &'static str {
+        match self {
+            Self::SpdxJson => ".spdx.json",
+            Self::CycloneDxJson => ".cdx.json",
+        }
+    }
+}
+
+/// A file belonging to an SBOM component.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SbomFile {
+    /// Path of the file, relative to the install location of its component.
+    pub path: String,
+
+    /// Hex encoded SHA-256 digest of the file content.
+    pub sha256: String,
+}
+
+/// The kind of a software component in an SBOM.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SbomComponentKind {
+    /// The Python distribution providing the interpreter.
+    PythonDistribution,
+    /// An installed Python package distribution.
+    PythonPackage,
+    /// A native library.
+    Library,
+    /// A Rust crate.
+    RustCrate,
+}
+
+/// A software component in an SBOM.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SbomComponent {
+    pub name: String,
+    pub version: Option<String>,
+    pub kind: SbomComponentKind,
+    pub license: LicenseFlavor,
+
+    /// Hex encoded SHA-256 digest of the component's source archive.
+    pub sha256: Option<String>,
+
+    pub files: Vec<SbomFile>,
+}
+
+impl SbomComponent {
+    /// Construct an instance with no known license.
+    pub fn new(name: &str, version: Option<&str>, kind: SbomComponentKind) -> Self {
+        Self {
+            name: name.to_string(),
+            version: version.map(|v| v.to_string()),
+            kind,
+            license: LicenseFlavor::None,
+            sha256: None,
+            files: vec![],
+        }
+    }
+
+    /// Construct an instance from a [LicensedComponent].
+    pub fn from_licensed_component(component: &LicensedComponent, version: Option<&str>) -> Self {
+        let kind = match component.flavor() {
+            ComponentFlavor::PythonPackage => SbomComponentKind::PythonPackage,
+            ComponentFlavor::RustCrate => SbomComponentKind::RustCrate,
+            ComponentFlavor::Generic | ComponentFlavor::Library => SbomComponentKind::Library,
+        };
+
+        Self {
+            license: component.license().clone(),
+            ..Self::new(component.name(), version, kind)
+        }
+    }
+
+    /// The SPDX license expression of this component, if it is known.
+    ///
+    /// Expressions containing license identifiers not known to SPDX are
+    /// treated as unknown.
+    pub fn spdx_license(&self) -> Option<String> {
+        match &self.license {
+            LicenseFlavor::Spdx(expression) => Some(expression.as_ref().to_string()),
+            LicenseFlavor::None
+            | LicenseFlavor::OtherExpression(_)
+            | LicenseFlavor::PublicDomain
+            | LicenseFlavor::Unknown(_) => None,
+        }
+    }
+
+    /// Human readable license terms that couldn't be expressed in SPDX.
+    fn license_comment(&self) -> Option<String> {
+        match &self.license {
+            LicenseFlavor::OtherExpression(expression) => Some(expression.as_ref().to_string()),
+            LicenseFlavor::PublicDomain => Some("public domain".to_string()),
+            LicenseFlavor::Unknown(terms) if !terms.is_empty() => Some(terms.join(", ")),
+            LicenseFlavor::None | LicenseFlavor::Spdx(_) | LicenseFlavor::Unknown(_) => None,
+        }
+    }
+
+    /// Package URL identifying this component, if one can be derived.
+    fn purl(&self) -> Option<String> {
+        let version = self.version.as_ref()?;
+
+        match self.kind {
+            SbomComponentKind::PythonPackage => Some(format!(
+                "pkg:pypi/{}@{}",
+                self.name.to_lowercase().replace('_', "-"),
+                version
+            )),
+            SbomComponentKind::RustCrate => Some(format!("pkg:cargo/{}@{}", self.name, version)),
+            SbomComponentKind::PythonDistribution | SbomComponentKind::Library => None,
+        }
+    }
+}
+
+/// Parse the `RECORD` file of a Python package distribution.
+///
+/// Entries without a SHA-256 digest (e.g. the `RECORD` file itself) are
+/// ignored.
+pub fn parse_record_file(data: &[u8]) -> Result<Vec<SbomFile>> {
+    let data = String::from_utf8_lossy(data);
+    let mut files = vec![];
+
+    for line in data.lines() {
+        // Paths containing commas are quoted. The digest and size fields
+        // never contain commas, so split from the end.
+        let mut parts = line.rsplitn(3, ',');
+        let _size = parts.next();
+        let digest = parts.next().unwrap_or_default();
+        let path = parts.next().unwrap_or_default().trim_matches('"');
+
+        if path.is_empty() || !digest.starts_with("sha256=") {
+            continue;
+        }
+
+        let digest = base64::decode_config(&digest["sha256=".len()..], base64::URL_SAFE_NO_PAD)
+            .map_err(|e| anyhow!("invalid digest for {} in RECORD: {}", path, e))?;
+
+        files.push(SbomFile {
+            path: path.to_string(),
+            sha256: hex::encode(digest),
+        });
+    }
+
+    Ok(files)
+}
+
+/// Obtain the creation time for SBOM documents.
+///
+/// Honors `SOURCE_DATE_EPOCH` so builds can be reproducible.
+pub fn sbom_creation_time() -> DateTime<Utc> {
+    if let Ok(value) = std::env::var("SOURCE_DATE_EPOCH") {
+        if let Ok(seconds) = value.parse::<i64>() {
+            return Utc.timestamp(seconds, 0);
+        }
+    }
+
+    Utc::now()
+}
+
+/// Make a string safe for use in an SPDX identifier.
+fn spdx_id_fragment(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// A software bill of materials for a built binary.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sbom {
+    /// Name of the binary being described.
+    pub name: String,
+
+    /// Components in the binary.
+    pub components: Vec<SbomComponent>,
+
+    /// Feature flags enabled when building the binary.
+    pub features: Vec<String>,
+}
+
+impl Sbom {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            components: vec![],
+            features: vec![],
+        }
+    }
+
+    /// Derive a stable UUID from the document content.
+    fn content_uuid(&self) -> uuid::Uuid {
+        let mut key = self.name.clone();
+
+        for c in &self.components {
+            key.push_str(&format!(
+                "\n{}@{}",
+                c.name,
+                c.version.as_deref().unwrap_or_default()
+            ));
+        }
+
+        for feature in &self.features {
+            key.push_str(&format!("\nfeature:{}", feature));
+        }
+
+        uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_URL, key.as_bytes())
+    }
+
+    /// Serialize to an SPDX 2.3 JSON document.
+    pub fn to_spdx_json(&self, created: &DateTime<Utc>) -> Result<Vec<u8>> {
+        let root_id = format!("SPDXRef-Package-{}", spdx_id_fragment(&self.name));
+
+        let mut packages = vec![json!({
+            "SPDXID": root_id,
+            "name": self.name,
+            "downloadLocation": NOASSERTION,
+            "filesAnalyzed": false,
+            "licenseConcluded": NOASSERTION,
+            "licenseDeclared": NOASSERTION,
+            "copyrightText": NOASSERTION,
+            "primaryPackagePurpose": "APPLICATION",
+        })];
+        let mut files = vec![];
+        let mut relationships = vec![json!({
+            "spdxElementId": "SPDXRef-DOCUMENT",
+            "relationshipType": "DESCRIBES",
+            "relatedSpdxElement": root_id,
+        })];
+
+        for (i, component) in self.components.iter().enumerate() {
+            let id = format!(
+                "SPDXRef-Package-{}-{}",
+                i,
+                spdx_id_fragment(&component.name)
+            );
+            let license = component
+                .spdx_license()
+                .unwrap_or_else(|| NOASSERTION.to_string());
+
+            let mut package = json!({
+                "SPDXID": id,
+                "name": component.name,
+                "downloadLocation": NOASSERTION,
+                "filesAnalyzed": false,
+                "licenseConcluded": license,
+                "licenseDeclared": license,
+                "copyrightText": NOASSERTION,
+            });
+            let o = package.as_object_mut().unwrap();
+
+            if let Some(version) = &component.version {
+                o.insert("versionInfo".to_string(), json!(version));
+            }
+            if let Some(comment) = component.license_comment() {
+                o.insert("licenseComments".to_string(), json!(comment));
+            }
+            if let Some(sha256) = &component.sha256 {
+                o.insert(
+                    "checksums".to_string(),
+                    json!([{"algorithm": "SHA256", "checksumValue": sha256}]),
+                );
+            }
+            if let Some(purl) = component.purl() {
+                o.insert(
+                    "externalRefs".to_string(),
+                    json!([{
+                        "referenceCategory": "PACKAGE-MANAGER",
+                        "referenceType": "purl",
+                        "referenceLocator": purl,
+                    }]),
+                );
+            }
+
+            packages.push(package);
+            relationships.push(json!({
+                "spdxElementId": root_id,
+                "relationshipType": "CONTAINS",
+                "relatedSpdxElement": id,
+            }));
+
+            for (j, file) in component.files.iter().enumerate() {
+                let file_id = format!("SPDXRef-File-{}-{}", i, j);
+
+                files.push(json!({
+                    "SPDXID": file_id,
+                    "fileName": format!("./{}", file.path),
+                    "checksums": [{"algorithm": "SHA256", "checksumValue": file.sha256}],
+                    "licenseConcluded": NOASSERTION,
+                    "copyrightText": NOASSERTION,
+                }));
+                relationships.push(json!({
+                    "spdxElementId": id,
+                    "relationshipType": "CONTAINS",
+                    "relatedSpdxElement": file_id,
+                }));
+            }
+        }
+
+        let mut doc = json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": self.name,
+            "documentNamespace": format!(
+                "https://pyoxidizer.readthedocs.io/spdx/{}-{}",
+                spdx_id_fragment(&self.name),
+                self.content_uuid()
+            ),
+            "creationInfo": {
+                "created": created.to_rfc3339_opts(SecondsFormat::Secs, true),
+                "creators": [format!("Tool: pyoxidizer-{}", env!("CARGO_PKG_VERSION"))],
+            },
+            "packages": packages,
+            "files": files,
+            "relationships": relationships,
+        });
+
+        if !self.features.is_empty() {
+            doc["creationInfo"].as_object_mut().unwrap().insert(
+                "comment".to_string(),
+                json!(format!("Features: {}", self.features.join(", "))),
+            );
+        }
+
+        Ok(serde_json::to_vec_pretty(&doc)?)
+    }
+
+    /// Serialize to a CycloneDX 1.4 JSON document.
+    pub fn to_cyclonedx_json(&self, created: &DateTime<Utc>) -> Result<Vec<u8>> {
+        let components = self
+            .components
+            .iter()
+            .enumerate()
+            .map(|(i, component)| {
+                let licenses = if let Some(expression) = component.spdx_license() {
+                    json!([{ "expression": expression }])
+                } else {
+                    json!([{
+                        "license": {
+                            "name": component
+                                .license_comment()
+                                .unwrap_or_else(|| NOASSERTION.to_string())
+                        }
+                    }])
+                };
+
+                let mut value = json!({
+                    "type": match component.kind {
+                        SbomComponentKind::PythonDistribution => "framework",
+                        _ => "library",
+                    },
+                    "bom-ref": format!("component-{}-{}", i, component.name),
+                    "name": component.name,
+                    "licenses": licenses,
+                });
+                let o = value.as_object_mut().unwrap();
+
+                if let Some(version) = &component.version {
+                    o.insert("version".to_string(), json!(version));
+                }
+                if let Some(sha256) = &component.sha256 {
+                    o.insert(
+                        "hashes".to_string(),
+                        json!([{"alg": "SHA-256", "content": sha256}]),
+                    );
+                }
+                if let Some(purl) = component.purl() {
+                    o.insert("purl".to_string(), json!(purl));
+                }
+                if !component.files.is_empty() {
+                    o.insert(
+                        "components".to_string(),
+                        Value::Array(
+                            component
+                                .files
+                                .iter()
+                                .map(|file| {
+                                    json!({
+                                        "type": "file",
+                                        "name": file.path,
+                                        "hashes": [{"alg": "SHA-256", "content": file.sha256}],
+                                    })
+                                })
+                                .collect(),
+                        ),
+                    );
+                }
+
+                value
+            })
+            .collect::<Vec<_>>();
+
+        let mut doc = json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.4",
+            "serialNumber": format!("urn:uuid:{}", self.content_uuid()),
+            "version": 1,
+            "metadata": {
+                "timestamp": created.to_rfc3339_opts(SecondsFormat::Secs, true),
+                "tools": [{
+                    "vendor": "PyOxidizer",
+                    "name": "pyoxidizer",
+                    "version": env!("CARGO_PKG_VERSION"),
+                }],
+                "component": {
+                    "type": "application",
+                    "bom-ref": "application",
+                    "name": self.name,
+                },
+            },
+            "components": components,
+        });
+
+        if !self.features.is_empty() {
+            doc["metadata"].as_object_mut().unwrap().insert(
+                "properties".to_string(),
+                Value::Array(
+                    self.features
+                        .iter()
+                        .map(|feature| json!({"name": "pyoxidizer:feature", "value": feature}))
+                        .collect(),
+                ),
+            );
+        }
+
+        Ok(serde_json::to_vec_pretty(&doc)?)
+    }
+
+    /// Serialize to a document of the given format.
+    pub fn to_json(&self, format: SbomFormat, created: &DateTime<Utc>) -> Result<Vec<u8>> {
+        match format {
+            SbomFormat::SpdxJson => self.to_spdx_json(created),
+            SbomFormat::CycloneDxJson => self.to_cyclonedx_json(created),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_sbom() -> Result<Sbom> {
+        let mut sbom = Sbom::new("myapp");
+
+        let mut python = SbomComponent::new(
+            "python",
+            Some("3.9.5"),
+            SbomComponentKind::PythonDistribution,
+        );
+        python.license = LicensedComponent::new_spdx("python", "Python-2.0")?
+            .license()
+            .clone();
+        sbom.components.push(python);
+
+        let mut package = SbomComponent::from_licensed_component(
+            &LicensedComponent::new_unknown("foo_bar", vec!["Custom".to_string()]),
+            Some("1.0"),
+        );
+        package.kind = SbomComponentKind::PythonPackage;
+        package.files = parse_record_file(
+            b"foo_bar/__init__.py,sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU,0\n\
+              foo_bar-1.0.dist-info/RECORD,,\n",
+        )?;
+        sbom.components.push(package);
+
+        let mut krate = SbomComponent::from_licensed_component(
+            &LicensedComponent::new_spdx("anyhow", "Apache-2.0 OR MIT")?,
+            Some("1.0.40"),
+        );
+        krate.kind = SbomComponentKind::RustCrate;
+        krate.sha256 = Some("28b2".to_string());
+        sbom.components.push(krate);
+
+        sbom.components.push(SbomComponent::new(
+            "libfoo.so",
+            None,
+            SbomComponentKind::Library,
+        ));
+
+        Ok(sbom)
+    }
+
+    #[test]
+    fn test_parse_record_file() -> Result<()> {
+        let files = parse_record_file(
+            b"\"a,b.py\",sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU,0\n\
+              x.pyc,,\n\
+              \n",
+        )?;
+
+        assert_eq!(
+            files,
+            vec![SbomFile {
+                path: "a,b.py".to_string(),
+                sha256: "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+                    .to_string(),
+            }]
+        );
+
+        assert!(parse_record_file(b"a.py,sha256=!!!,1\n").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_spdx_json() -> Result<()> {
+        let sbom = sample_sbom()?;
+        let created = Utc.timestamp(1_600_000_000, 0);
+
+        let doc: Value = serde_json::from_slice(&sbom.to_spdx_json(&created)?)?;
+
+        assert_eq!(doc["spdxVersion"], "SPDX-2.3");
+        assert_eq!(doc["dataLicense"], "CC0-1.0");
+        assert_eq!(doc["SPDXID"], "SPDXRef-DOCUMENT");
+        assert_eq!(doc["creationInfo"]["created"], "2020-09-13T12:26:40Z");
+        assert!(doc["documentNamespace"]
+            .as_str()
+            .unwrap()
+            .starts_with("https://"));
+
+        // Document generation is deterministic.
+        assert_eq!(sbom.to_spdx_json(&created)?, sbom.to_spdx_json(&created)?);
+
+        let packages = doc["packages"].as_array().unwrap();
+        assert_eq!(packages.len(), 5);
+
+        let id_re = |s: &str| {
+            s.starts_with("SPDXRef-")
+                && s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+        };
+
+        for package in packages {
+            for key in &[
+                "SPDXID",
+                "name",
+                "downloadLocation",
+                "licenseConcluded",
+                "licenseDeclared",
+                "copyrightText",
+            ] {
+                assert!(package[key].is_string(), "{} missing from {}", key, package);
+            }
+            assert!(id_re(package["SPDXID"].as_str().unwrap()));
+        }
+
+        assert_eq!(packages[1]["licenseDeclared"], "Python-2.0");
+        assert_eq!(packages[1]["versionInfo"], "3.9.5");
+        // Unknown licenses aren't dropped.
+        assert_eq!(packages[2]["licenseDeclared"], NOASSERTION);
+        assert_eq!(packages[2]["licenseComments"], "Custom");
+        assert_eq!(
+            packages[2]["externalRefs"][0]["referenceLocator"],
+            "pkg:pypi/foo-bar@1.0"
+        );
+        assert_eq!(packages[3]["licenseDeclared"], "Apache-2.0 OR MIT");
+        assert_eq!(packages[3]["checksums"][0]["checksumValue"], "28b2");
+        assert_eq!(packages[4]["licenseDeclared"], NOASSERTION);
+        assert!(packages[4].get("versionInfo").is_none());
+
+        let files = doc["files"].as_array().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0]["fileName"], "./foo_bar/__init__.py");
+        assert_eq!(files[0]["checksums"][0]["algorithm"], "SHA256");
+        assert!(id_re(files[0]["SPDXID"].as_str().unwrap()));
+
+        // Every relationship refers to defined elements.
+        let ids = packages
+            .iter()
+            .chain(files.iter())
+            .map(|x| x["SPDXID"].as_str().unwrap())
+            .chain(std::iter::once("SPDXRef-DOCUMENT"))
+            .collect::<Vec<_>>();
+        let relationships = doc["relationships"].as_array().unwrap();
+        assert_eq!(relationships.len(), 6);
+        for r in relationships {
+            assert!(ids.contains(&r["spdxElementId"].as_str().unwrap()));
+            assert!(ids.contains(&r["relatedSpdxElement"].as_str().unwrap()));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cyclonedx_json() -> Result<()> {
+        let sbom = sample_sbom()?;
+        let created = Utc.timestamp(1_600_000_000, 0);
+
+        let doc: Value = serde_json::from_slice(&sbom.to_cyclonedx_json(&created)?)?;
+
+        assert_eq!(doc["bomFormat"], "CycloneDX");
+        assert_eq!(doc["specVersion"], "1.4");
+        assert_eq!(doc["version"], 1);
+        assert!(doc["serialNumber"]
+            .as_str()
+            .unwrap()
+            .starts_with("urn:uuid:"));
+        assert_eq!(doc["metadata"]["component"]["name"], "myapp");
+
+        let components = doc["components"].as_array().unwrap();
+        assert_eq!(components.len(), 4);
+
+        assert_eq!(components[0]["type"], "framework");
+        assert_eq!(components[0]["licenses"][0]["expression"], "Python-2.0");
+        assert_eq!(components[1]["licenses"][0]["license"]["name"], "Custom");
+        assert_eq!(components[1]["purl"], "pkg:pypi/foo-bar@1.0");
+        assert_eq!(components[1]["components"][0]["type"], "file");
+        assert_eq!(components[2]["purl"], "pkg:cargo/anyhow@1.0.40");
+        assert_eq!(components[2]["hashes"][0]["alg"], "SHA-256");
+        assert_eq!(components[3]["licenses"][0]["license"]["name"], NOASSERTION);
+
+        let mut refs = components
+            .iter()
+            .map(|c| c["bom-ref"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        refs.sort_unstable();
+        refs.dedup();
+        assert_eq!(refs.len(), components.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_features() -> Result<()> {
+        let mut sbom = sample_sbom()?;
+        let created = Utc.timestamp(1_600_000_000, 0);
+
+        let doc: Value = serde_json::from_slice(&sbom.to_spdx_json(&created)?)?;
+        assert!(doc["creationInfo"].get("comment").is_none());
+        let doc: Value = serde_json::from_slice(&sbom.to_cyclonedx_json(&created)?)?;
+        assert!(doc["metadata"].get("properties").is_none());
+        let lite_uuid = sbom.content_uuid();
+
+        sbom.features = vec!["beta".to_string(), "pro".to_string()];
+        assert_ne!(sbom.content_uuid(), lite_uuid);
+
+        let doc: Value = serde_json::from_slice(&sbom.to_spdx_json(&created)?)?;
+        assert_eq!(doc["creationInfo"]["comment"], "Features: beta, pro");
+
+        let doc: Value = serde_json::from_slice(&sbom.to_cyclonedx_json(&created)?)?;
+        assert_eq!(
+            doc["metadata"]["properties"],
+            serde_json::json!([
+                {"name": "pyoxidizer:feature", "value": "beta"},
+                {"name": "pyoxidizer:feature", "value": "pro"},
+            ])
+        );
+
+        Ok(())
+    }
+}
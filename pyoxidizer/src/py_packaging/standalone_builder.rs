@@ -7,35 +7,43 @@ use {
         binary::{
             pyembed_licenses, EmbeddedPythonContext, LibpythonLinkMode, PackedResourcesLoadMode,
             PythonBinaryBuilder, PythonLinkingInfo, ResourceAddCollectionContextCallback,
-            WindowsRuntimeDllsMode,
+            ResourcesEncryption, WindowsRuntimeDllsMode,
         },
         config::{PyembedPackedResourcesSource, PyembedPythonInterpreterConfig},
         distribution::{AppleSdkInfo, BinaryLibpythonLinkMode, PythonDistribution},
+        extension_verification::{format_extension_module_problems, verify_extension_module},
         filtering::{filter_btreemap, resolve_resource_names_from_files},
         libpython::link_libpython,
+        minimal_encodings::{boot_encodings, file_data_size, find_unresolvable_encodings},
         packaging_tool::{
             find_resources, pip_download, pip_install, read_virtualenv, setup_py_install,
         },
+        sbom::{parse_record_file, Sbom, SbomComponent, SbomComponentKind},
         standalone_distribution::StandaloneDistribution,
     },
-    crate::environment::Environment,
+    crate::{environment::Environment, project_layout::new_project_cargo_lock, timing},
     anyhow::{anyhow, Context, Result},
     once_cell::sync::Lazy,
     python_packaging::{
         bytecode::BytecodeCompiler,
-        interpreter::MemoryAllocatorBackend,
+        interpreter::{
+            MemoryAllocatorBackend, PythonInterpreterProfile, PythonRunMode, SysExecutableMode,
+        },
         libpython::LibPythonBuildContext,
         licensing::derive_package_license_infos,
-        location::AbstractResourceLocation,
-        policy::PythonPackagingPolicy,
+        location::{AbstractResourceLocation, ConcreteResourceLocation},
+        package_metadata::PythonPackageMetadata,
+        policy::{ExtensionModuleVerification, PythonPackagingPolicy},
         resource::{
-            PythonExtensionModule, PythonModuleSource, PythonPackageDistributionResource,
+            PythonExtensionModule, PythonModuleBytecode, PythonModuleSource,
+            PythonPackageDistributionResource, PythonPackageDistributionResourceFlavor,
             PythonPackageResource, PythonResource,
         },
         resource_collection::{
             PrePackagedResource, PythonResourceAddCollectionContext, PythonResourceCollector,
         },
     },
+    python_packed_resources::{data::PackedResourcesLimits, encryption::ResourcesEncryptor},
     slog::warn,
     std::{
         collections::{BTreeMap, BTreeSet, HashMap},
@@ -48,6 +56,61 @@ use {
     tugger_windows::{find_visual_cpp_redistributable, VcRedistributablePlatform},
 };
 
+/// Python modules required for an interpreter to start.
+///
+/// This includes `site` and its imports, as `site` is imported by default.
+/// `importlib` bootstrap modules are frozen into libpython and aren't listed.
+pub const REQUIRED_STDLIB_MODULES: &[&str] = &[
+    "_collections_abc",
+    "_sitebuiltins",
+    "abc",
+    "codecs",
+    "encodings",
+    "encodings.aliases",
+    "encodings.ascii",
+    "encodings.latin_1",
+    "encodings.mbcs",
+    "encodings.utf_8",
+    "genericpath",
+    "io",
+    "ntpath",
+    "os",
+    "posixpath",
+    "site",
+    "stat",
+];
+
+/// Python modules used by the REPL of `PythonRunMode::Repl`.
+///
+/// This includes the modules these import. `readline` is an extension module
+/// and is only included if the packaging policy allows it, as it may be
+/// GPL licensed.
+pub const REPL_STDLIB_MODULES: &[&str] = &[
+    "__future__",
+    "code",
+    "codeop",
+    "collections",
+    "collections.abc",
+    "copyreg",
+    "enum",
+    "functools",
+    "heapq",
+    "keyword",
+    "linecache",
+    "operator",
+    "re",
+    "reprlib",
+    "rlcompleter",
+    "sre_compile",
+    "sre_constants",
+    "sre_parse",
+    "token",
+    "tokenize",
+    "traceback",
+    "types",
+    "warnings",
+];
+
 /// Libraries that we should not link against on Linux.
 static LINUX_IGNORE_LIBRARIES: Lazy<Vec<&'static str>> = Lazy::new(|| vec!["dl", "m"]);
 
@@ -65,6 +128,34 @@ fn ignored_libraries_for_target(target_triple: &str) -> Vec<&'static str> {
     }
 }
 
+/// Validate that a packaging policy can be used to build for a WASI target.
+///
+/// WebAssembly modules have no access to the filesystem unless the runtime
+/// grants it. So all resources must be loaded from memory.
+pub fn validate_wasi_packaging_policy(policy: &PythonPackagingPolicy) -> Result<()> {
+    if policy.resources_location() != &ConcreteResourceLocation::InMemory {
+        return Err(anyhow!(
+            "WASI targets only support in-memory resources; PythonPackagingPolicy.resources_location must be \"in-memory\", not \"{}\"",
+            policy.resources_location().to_string()
+        ));
+    }
+
+    if let Some(fallback) = policy.resources_location_fallback() {
+        return Err(anyhow!(
+            "WASI targets only support in-memory resources; PythonPackagingPolicy.resources_location_fallback must be None, not \"{}\"",
+            fallback.to_string()
+        ));
+    }
+
+    if policy.allow_files() {
+        return Err(anyhow!(
+            "WASI targets only support in-memory resources; PythonPackagingPolicy.allow_files must be False"
+        ));
+    }
+
+    Ok(())
+}
+
 /// A self-contained Python executable before it is compiled.
 #[derive(Clone)]
 pub struct StandalonePythonExecutableBuilder {
@@ -99,6 +190,9 @@ pub struct StandalonePythonExecutableBuilder {
     /// How packed resources will be loaded at run-time.
     resources_load_mode: PackedResourcesLoadMode,
 
+    /// How packed resources payloads will be encrypted.
+    resources_encryption: Option<ResourcesEncryption>,
+
     /// Holds state necessary to link libpython.
     core_build_context: LibPythonBuildContext,
 
@@ -122,6 +216,22 @@ pub struct StandalonePythonExecutableBuilder {
 
     /// Describes how Windows runtime DLLs should be handled during builds.
     windows_runtime_dlls_mode: WindowsRuntimeDllsMode,
+
+    /// Whether to build with the `startup-trace` pyembed feature.
+    trace_startup: bool,
+
+    /// Whether resources from the Python distribution are included.
+    include_stdlib: bool,
+
+    /// Names of resource entries added by `add_distribution_resources()`.
+    distribution_resource_names: BTreeSet<String>,
+
+    /// Extension module verification problems to report as warnings.
+    extension_module_warnings: Vec<String>,
+
+    /// Number and total size in bytes of distribution modules excluded by
+    /// `PythonPackagingPolicy.minimal_encodings()`.
+    minimal_encodings_savings: (usize, u64),
 }
 
 impl StandalonePythonExecutableBuilder {
@@ -139,6 +249,10 @@ impl StandalonePythonExecutableBuilder {
         let host_python_exe = host_distribution.python_exe_path().to_path_buf();
         let cache_tag = target_distribution.cache_tag.clone();
 
+        if crate::environment::WASI_TARGET_TRIPLES.contains(&target_triple.as_str()) {
+            validate_wasi_packaging_policy(&packaging_policy)?;
+        }
+
         let (supports_static_libpython, supports_dynamic_libpython) =
             target_distribution.libpython_link_support();
 
@@ -215,6 +329,7 @@ impl StandalonePythonExecutableBuilder {
             resources_load_mode: PackedResourcesLoadMode::EmbeddedInBinary(
                 "packed-resources".to_string(),
             ),
+            resources_encryption: None,
             core_build_context: LibPythonBuildContext::default(),
             extension_build_contexts: BTreeMap::new(),
             config,
@@ -222,8 +337,22 @@ impl StandalonePythonExecutableBuilder {
             windows_subsystem: "console".to_string(),
             tcl_files_path: None,
             windows_runtime_dlls_mode: WindowsRuntimeDllsMode::WhenPresent,
+            trace_startup: false,
+            include_stdlib: true,
+            distribution_resource_names: BTreeSet::new(),
+            extension_module_warnings: vec![],
+            minimal_encodings_savings: (0, 0),
         });
 
+        // 32-bit targets can't map more than 2 GiB of resources data.
+        let pointer_width = crate::environment::target_pointer_width(&builder.target_triple);
+        builder
+            .resources_collector
+            .set_limits(PackedResourcesLimits {
+                allow_chunking: packaging_policy.allow_chunked_resources(),
+                ..PackedResourcesLimits::for_pointer_width(pointer_width)
+            });
+
         builder.add_distribution_core_state()?;
 
         Ok(builder)
@@ -360,6 +489,9 @@ impl StandalonePythonExecutableBuilder {
             }
         }
 
+        // Record the link mode so build scripts can see how libpython is linked.
+        cargo_metadata.push(format!("cargo:libpython-link-mode={}", self.link_mode));
+
         Ok(PythonLinkingInfo {
             libpythonxy_filename,
             libpythonxy_data,
@@ -429,14 +561,11 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
     }
 
     fn vc_runtime_requirements(&self) -> Option<(String, VcRedistributablePlatform)> {
-        let platform = if self.target_triple.starts_with("i686-") {
-            VcRedistributablePlatform::X86
-        } else if self.target_triple.starts_with("x86_64-") {
-            VcRedistributablePlatform::X64
-        } else if self.target_triple.starts_with("aarch64-") {
-            VcRedistributablePlatform::Arm64
-        } else {
-            return None;
+        let platform = match self.target_triple.split('-').next() {
+            Some("i586") | Some("i686") => VcRedistributablePlatform::X86,
+            Some("x86_64") => VcRedistributablePlatform::X64,
+            Some("aarch64") => VcRedistributablePlatform::Arm64,
+            _ => return None,
         };
 
         self.target_distribution
@@ -459,7 +588,12 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
     }
 
     fn target_python_exe_path(&self) -> &Path {
-        &self.target_distribution.python_exe_path()
+        // The interpreter of a WASI distribution can't be executed natively.
+        if crate::environment::WASI_TARGET_TRIPLES.contains(&self.target_triple.as_str()) {
+            &self.host_python_exe
+        } else {
+            &self.target_distribution.python_exe_path()
+        }
     }
 
     fn apple_sdk_info(&self) -> Option<&AppleSdkInfo> {
@@ -504,6 +638,74 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         Ok(())
     }
 
+    fn trace_startup(&self) -> bool {
+        self.trace_startup
+    }
+
+    fn set_trace_startup(&mut self, value: bool) {
+        self.trace_startup = value;
+    }
+
+    fn include_stdlib(&self) -> bool {
+        self.include_stdlib
+    }
+
+    fn set_include_stdlib(&mut self, value: bool) -> Result<()> {
+        if value == self.include_stdlib {
+            return Ok(());
+        }
+
+        if value {
+            return Err(anyhow!(
+                "distribution resources can't be restored once include_stdlib is disabled"
+            ));
+        }
+
+        let required = self
+            .required_stdlib_resources()
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        let removed = self
+            .distribution_resource_names
+            .difference(&required)
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        self.resources_collector
+            .filter_resources_mut(|resource| !removed.contains(&resource.name))?;
+        self.extension_build_contexts = std::mem::take(&mut self.extension_build_contexts)
+            .into_iter()
+            .filter(|(name, _)| !removed.contains(name))
+            .collect();
+
+        self.include_stdlib = false;
+
+        Ok(())
+    }
+
+    fn required_stdlib_resources(&self) -> Vec<String> {
+        let required_extensions = self
+            .target_distribution
+            .extension_modules
+            .values()
+            .flat_map(|variants| variants.iter())
+            .filter(|em| em.is_minimally_required())
+            .map(|em| em.name.as_str())
+            .collect::<BTreeSet<_>>();
+
+        self.distribution_resource_names
+            .iter()
+            .filter(|name| {
+                REQUIRED_STDLIB_MODULES.contains(&name.as_str())
+                    || required_extensions.contains(name.as_str())
+                    || (self.config.run_mode == PythonRunMode::Repl
+                        && (REPL_STDLIB_MODULES.contains(&name.as_str())
+                            || name.as_str() == "readline"))
+            })
+            .cloned()
+            .collect()
+    }
+
     fn packed_resources_load_mode(&self) -> &PackedResourcesLoadMode {
         &self.resources_load_mode
     }
@@ -512,12 +714,24 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         self.resources_load_mode = load_mode;
     }
 
+    fn resources_encryption(&self) -> &Option<ResourcesEncryption> {
+        &self.resources_encryption
+    }
+
+    fn set_resources_encryption(&mut self, value: Option<ResourcesEncryption>) {
+        self.resources_encryption = value;
+    }
+
     fn iter_resources<'a>(
         &'a self,
     ) -> Box<dyn Iterator<Item = (&'a String, &'a PrePackagedResource)> + 'a> {
         Box::new(self.resources_collector.iter_resources())
     }
 
+    fn resources_collector_mut(&mut self) -> &mut PythonResourceCollector {
+        &mut self.resources_collector
+    }
+
     fn index_package_license_info_from_resources<'a>(
         &mut self,
         resources: &[PythonResource<'a>],
@@ -682,6 +896,19 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             }
 
             self.add_python_extension_module(&ext, Some(add_context))?;
+            self.distribution_resource_names.insert(ext.name.clone());
+        }
+
+        for variants in self.target_distribution.extension_modules.values() {
+            let ext = variants.default_variant();
+
+            if self.packaging_policy.excludes_encodings_module(&ext.name)
+                && !self.distribution_resource_names.contains(&ext.name)
+            {
+                self.minimal_encodings_savings.0 += 1;
+                self.minimal_encodings_savings.1 +=
+                    file_data_size(ext.shared_library.iter().chain(ext.object_file_data.iter()))?;
+            }
         }
 
         for resource in self
@@ -710,6 +937,22 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
 
             match resource {
                 PythonResource::ModuleSource(source) => {
+                    if self.config.run_mode == PythonRunMode::Repl
+                        && REPL_STDLIB_MODULES.contains(&source.name.as_str())
+                    {
+                        add_context.include = true;
+                    }
+
+                    if !add_context.include
+                        && self
+                            .packaging_policy
+                            .excludes_encodings_module(&source.name)
+                    {
+                        self.minimal_encodings_savings.0 += 1;
+                        self.minimal_encodings_savings.1 +=
+                            file_data_size(std::iter::once(&source.source))?;
+                    }
+
                     let mut component = LicensedComponent::new_spdx(
                         source.top_level_package(),
                         &core_component
@@ -721,9 +964,12 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
 
                     self.resources_collector.add_licensed_component(component)?;
                     self.add_python_module_source(source, Some(add_context))?;
+                    self.distribution_resource_names.insert(source.name.clone());
                 }
                 PythonResource::PackageResource(r) => {
                     self.add_python_package_resource(r, Some(add_context))?;
+                    self.distribution_resource_names
+                        .insert(r.leaf_package.clone());
                 }
                 _ => panic!("should not get here since resources should be filtered above"),
             }
@@ -746,6 +992,20 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             .add_python_module_source_with_context(module, &add_context)
     }
 
+    fn add_python_module_bytecode(
+        &mut self,
+        module: &PythonModuleBytecode,
+        add_context: Option<PythonResourceAddCollectionContext>,
+    ) -> Result<()> {
+        let add_context = add_context.unwrap_or_else(|| {
+            self.packaging_policy
+                .derive_add_collection_context(&module.into())
+        });
+
+        self.resources_collector
+            .add_python_module_bytecode_with_context(module, &add_context)
+    }
+
     fn add_python_package_resource(
         &mut self,
         resource: &PythonPackageResource,
@@ -784,6 +1044,40 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
                 .derive_add_collection_context(&extension_module.into())
         });
 
+        // Only extension modules compiled into the distribution's libpython work
+        // in WebAssembly.
+        if crate::environment::WASI_TARGET_TRIPLES.contains(&self.target_triple.as_str())
+            && !extension_module.is_stdlib
+        {
+            return Err(anyhow!(
+                "extension module {} cannot be added: WASI targets only support extension modules built into the Python distribution",
+                extension_module.name
+            ));
+        }
+
+        // Extension modules from the distribution are known to be compatible.
+        let verification = self.packaging_policy.extension_module_verification();
+        if verification != ExtensionModuleVerification::Off && !extension_module.is_stdlib {
+            let problems = verify_extension_module(
+                extension_module,
+                &self.target_triple,
+                &self.target_distribution.python_module_suffixes()?.extension,
+            );
+
+            if !problems.is_empty() {
+                let message = format_extension_module_problems(extension_module, &problems);
+
+                if verification == ExtensionModuleVerification::Error {
+                    return Err(anyhow!(
+                        "{} (set PythonPackagingPolicy.extension_module_verification = \"warn\" to ignore)",
+                        message
+                    ));
+                }
+
+                self.extension_module_warnings.push(message);
+            }
+        }
+
         if let Some(mut build_context) = self
             .resources_collector
             .add_python_extension_module_with_context(extension_module, &add_context)?
@@ -865,12 +1159,133 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         self.config.allocator_backend == MemoryAllocatorBackend::Snmalloc
     }
 
+    fn software_bill_of_materials(&self) -> Result<Sbom> {
+        let mut sbom = Sbom::new(&self.exe_name);
+
+        let mut python = SbomComponent::new(
+            "python",
+            Some(&self.target_distribution.version),
+            SbomComponentKind::PythonDistribution,
+        );
+        if let Some(component) = &self.target_distribution.core_license {
+            python.license = component.license().clone();
+        }
+        sbom.components.push(python);
+
+        for (name, resource) in self.resources_collector.iter_resources() {
+            let mut files = BTreeMap::new();
+            if let Some(resources) = &resource.in_memory_distribution_resources {
+                files.extend(resources.iter().map(|(k, v)| (k.clone(), v.clone())));
+            }
+            if let Some(resources) = &resource.relative_path_distribution_resources {
+                files.extend(resources.iter().map(|(k, (_, v))| (k.clone(), v.clone())));
+            }
+
+            if files.is_empty() {
+                continue;
+            }
+
+            let metadata = match files.get("METADATA").or_else(|| files.get("PKG-INFO")) {
+                Some(data) => Some(
+                    PythonPackageMetadata::from_metadata(&data.resolve_content()?)
+                        .with_context(|| format!("parsing metadata of {}", name))?,
+                ),
+                None => None,
+            };
+            let package = metadata
+                .as_ref()
+                .and_then(|m| m.name())
+                .unwrap_or(name)
+                .to_string();
+            let version = metadata.as_ref().and_then(|m| m.version());
+
+            let resources = files
+                .iter()
+                .map(|(k, data)| {
+                    PythonResource::from(PythonPackageDistributionResource {
+                        location: PythonPackageDistributionResourceFlavor::DistInfo,
+                        package: package.clone(),
+                        version: version.unwrap_or_default().to_string(),
+                        name: k.clone(),
+                        data: data.clone(),
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut component = match derive_package_license_infos(resources.iter())?
+                .into_iter()
+                .next()
+            {
+                Some(info) => SbomComponent::from_licensed_component(&info.try_into()?, version),
+                None => SbomComponent::new(&package, version, SbomComponentKind::PythonPackage),
+            };
+
+            if let Some(record) = files.get("RECORD") {
+                component.files = parse_record_file(&record.resolve_content()?)
+                    .with_context(|| format!("parsing RECORD of {}", package))?;
+            }
+
+            sbom.components.push(component);
+        }
+
+        // Native code linked into the binary is tracked via the extension
+        // modules that require it.
+        for context in self.extension_build_contexts.values() {
+            for component in context.licensed_components.iter_components() {
+                let mut component = SbomComponent::from_licensed_component(component, None);
+                component.kind = SbomComponentKind::Library;
+                sbom.components.push(component);
+            }
+        }
+
+        for (name, resource) in self.resources_collector.iter_resources() {
+            let is_shared_library_extension =
+                resource.in_memory_extension_module_shared_library.is_some()
+                    || resource
+                        .relative_path_extension_module_shared_library
+                        .is_some();
+
+            if resource.is_shared_library || is_shared_library_extension {
+                sbom.components
+                    .push(SbomComponent::new(name, None, SbomComponentKind::Library));
+            }
+        }
+
+        let rust_licenses = pyembed_licenses()?;
+        for package in new_project_cargo_lock()?.packages {
+            let mut component = match rust_licenses
+                .iter()
+                .find(|c| c.name() == package.name.as_str())
+            {
+                Some(c) => {
+                    SbomComponent::from_licensed_component(c, Some(&package.version.to_string()))
+                }
+                None => SbomComponent::new(
+                    package.name.as_str(),
+                    Some(&package.version.to_string()),
+                    SbomComponentKind::RustCrate,
+                ),
+            };
+            component.sha256 = package.checksum.map(|c| c.to_string());
+
+            sbom.components.push(component);
+        }
+
+        Ok(sbom)
+    }
+
     fn to_embedded_python_context(
         &self,
         logger: &slog::Logger,
         env: &Environment,
         opt_level: &str,
     ) -> Result<EmbeddedPythonContext> {
+        let collect_span = timing::span("collect resources");
+
+        for message in &self.extension_module_warnings {
+            warn!(logger, "warning: {}", message);
+        }
+
         let mut file_seen = false;
         for module in self.resources_collector.find_dunder_file()? {
             file_seen = true;
@@ -889,6 +1304,72 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             );
         }
 
+        if self.config.run_mode == PythonRunMode::Repl
+            && !self
+                .resources_collector
+                .iter_resources()
+                .any(|(name, _)| name == "readline")
+        {
+            warn!(
+                logger,
+                "warning: the readline extension module is not included; the REPL will lack line editing, tab completion, and history"
+            );
+        }
+
+        if let Some(minimal) = self.packaging_policy.minimal_encodings() {
+            let missing = find_unresolvable_encodings(
+                self.host_python_exe_path(),
+                &minimal.keep,
+                &boot_encodings(&self.config.config, &self.target_triple),
+            )
+            .context("verifying encodings kept by PythonPackagingPolicy.minimal_encodings()")?;
+
+            if !missing.is_empty() {
+                return Err(anyhow!(
+                    "PythonPackagingPolicy.minimal_encodings() excludes codecs needed to start the interpreter: {}; keep them or set PythonInterpreterConfig.utf8_mode = True",
+                    missing.join(", ")
+                ));
+            }
+
+            if self.target_triple.contains("pc-windows")
+                && self.config.config.utf8_mode != Some(true)
+            {
+                warn!(
+                    logger,
+                    "warning: without PythonInterpreterConfig.utf8_mode, redirected standard streams use the ANSI code page of the running machine, whose codec may be excluded by PythonPackagingPolicy.minimal_encodings()"
+                );
+            }
+
+            warn!(
+                logger,
+                "PythonPackagingPolicy.minimal_encodings() excluded {} distribution modules totaling {} bytes",
+                self.minimal_encodings_savings.0,
+                self.minimal_encodings_savings.1
+            );
+        }
+
+        // Executables configured as python interpreters are python themselves.
+        let is_python = self.config.sys_executable_mode == SysExecutableMode::Invoked
+            && self.config.config.profile == PythonInterpreterProfile::Python;
+
+        if self.config.sys_executable_mode != SysExecutableMode::PythonShim && !is_python {
+            let modules = self
+                .resources_collector
+                .find_sys_executable_reinvocation()?;
+
+            if !modules.is_empty() {
+                warn!(
+                    logger,
+                    "modules appear to run sys.executable as python: {}",
+                    modules.into_iter().collect::<Vec<_>>().join(", ")
+                );
+                warn!(
+                    logger,
+                    "sys.executable is not a python interpreter unless PythonInterpreterConfig.sys_executable_mode is set to \"python-shim\""
+                );
+            }
+        }
+
         let license_report = self.resources_collector.generate_license_report()?;
         if license_report.no_license_packages.is_empty() {
             warn!(logger, "All Python packages have license metadata");
@@ -923,12 +1404,47 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             warn!(logger, "license: {}; packages: {:?}", license, packages);
         }
 
-        let compiled_resources = {
+        let mut compiled_resources = {
+            let _span = timing::span("compile bytecode");
             let temp_dir = tempfile::TempDir::new()?;
             let mut compiler = BytecodeCompiler::new(self.host_python_exe_path(), temp_dir.path())?;
             self.resources_collector.compile_resources(&mut compiler)?
         };
 
+        if let Some(magic) = self.target_distribution.python_bytecode_magic_number {
+            compiled_resources
+                .verify_bytecode_magic_number(magic)
+                .context("verifying compiled bytecode against target Python distribution")?;
+        }
+
+        if let Some(encryption) = &self.resources_encryption {
+            let encryptor =
+                ResourcesEncryptor::new(encryption.scheme, &encryption.recipient_public_key)
+                    .context("initializing resources encryption")?;
+
+            let mut filesystem_modules = 0;
+
+            for resource in compiled_resources.resources.values_mut() {
+                *resource = encryptor.encrypt_resource(resource)?;
+
+                if resource.relative_path_module_source.is_some()
+                    || resource.relative_path_module_bytecode.is_some()
+                    || resource.relative_path_module_bytecode_opt1.is_some()
+                    || resource.relative_path_module_bytecode_opt2.is_some()
+                {
+                    filesystem_modules += 1;
+                }
+            }
+
+            if filesystem_modules > 0 {
+                warn!(
+                    logger,
+                    "{} Python modules are installed on the filesystem and will not be encrypted",
+                    filesystem_modules
+                );
+            }
+        }
+
         let mut pending_resources = vec![];
         let mut extra_files = FileManifest::default();
 
@@ -940,6 +1456,28 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         }
 
         let mut config = self.config.clone();
+        config.bytecode_magic_number = compiled_resources.bytecode_magic_number;
+
+        // Generated projects define this function when the
+        // `encrypted-resources` feature is enabled.
+        if self.resources_encryption.is_some() {
+            config.resources_key_provider = Some("resources_key_provider".to_string());
+        }
+
+        if let Some(minimal) = self.packaging_policy.minimal_encodings() {
+            config.excluded_encodings = self
+                .target_distribution
+                .py_modules
+                .keys()
+                .filter_map(|name| name.strip_prefix("encodings."))
+                .filter(|name| !name.contains('.') && !minimal.keep.contains(*name))
+                .map(|name| name.to_string())
+                .collect();
+        }
+
+        if let Some(path) = config.filesystem_resources_root_marker_path() {
+            extra_files.add_file_entry(&path, Vec::<u8>::new())?;
+        }
 
         match &self.resources_load_mode {
             PackedResourcesLoadMode::None => {}
@@ -953,6 +1491,7 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             }
             PackedResourcesLoadMode::BinaryRelativePathMemoryMapped(path) => {
                 // We need to materialize the file in extra_files. So compile now.
+                let _span = timing::span("write packed resources");
                 let mut buffer = vec![];
                 compiled_resources
                     .write_packed_resources(&mut buffer)
@@ -967,6 +1506,9 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             }
         }
 
+        drop(collect_span);
+
+        warn!(logger, "libpython link mode: {}", self.link_mode);
         let linking_info = self.resolve_python_linking_info(logger, env, opt_level)?;
 
         if self.link_mode == LibpythonLinkMode::Dynamic {
@@ -1017,12 +1559,15 @@ pub mod tests {
         super::*,
         crate::{
             environment::{default_target_triple, MACOS_TARGET_TRIPLES},
-            py_packaging::distribution::{BinaryLibpythonLinkMode, DistributionFlavor},
+            py_packaging::{
+                config::FILESYSTEM_RESOURCES_ROOT_MARKER,
+                distribution::{BinaryLibpythonLinkMode, DistributionFlavor},
+            },
             python_distributions::PYTHON_DISTRIBUTIONS,
             testutil::*,
         },
         once_cell::sync::Lazy,
-        python_packaging::{location::ConcreteResourceLocation, policy::ExtensionModuleFilter},
+        python_packaging::policy::ExtensionModuleFilter,
         std::ops::DerefMut,
         tugger_licensing::LicensedComponents,
     };
@@ -1099,6 +1644,7 @@ pub mod tests {
         pub resources_location: Option<ConcreteResourceLocation>,
         pub resources_location_fallback: Option<Option<ConcreteResourceLocation>>,
         pub allow_in_memory_shared_library_loading: Option<bool>,
+        pub include_test_packages: Option<Vec<String>>,
         pub config: PyembedPythonInterpreterConfig,
     }
 
@@ -1115,6 +1661,7 @@ pub mod tests {
                 resources_location: None,
                 resources_location_fallback: None,
                 allow_in_memory_shared_library_loading: None,
+                include_test_packages: None,
                 config: PyembedPythonInterpreterConfig::default(),
             }
         }
@@ -1158,6 +1705,9 @@ pub mod tests {
             if let Some(value) = &self.allow_in_memory_shared_library_loading {
                 policy.set_allow_in_memory_shared_library_loading(*value);
             }
+            if let Some(names) = &self.include_test_packages {
+                policy.set_include_test_packages(names.clone());
+            }
 
             let mut builder = StandalonePythonExecutableBuilder::from_distribution(
                 host_distribution,
@@ -1317,6 +1867,107 @@ pub mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_filesystem_resources_root_marker() -> Result<()> {
+        let logger = get_logger()?;
+        let mut options = StandalonePythonExecutableBuilderOptions::default();
+        options.config.filesystem_resources_root = Some("$ORIGIN/lib".into());
+        options.config.resources_root_env = Some("MYAPP_RESOURCE_ROOT".into());
+        let exe = options.new_builder()?;
+
+        let embedded = exe.to_embedded_python_context(&logger, &get_env()?, "0")?;
+
+        assert_eq!(
+            embedded.config.resources_root_env,
+            Some("MYAPP_RESOURCE_ROOT".to_string())
+        );
+        let marker = Path::new("lib").join(FILESYSTEM_RESOURCES_ROOT_MARKER);
+        assert!(embedded.extra_files.has_path(marker));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repl_stdlib_modules() -> Result<()> {
+        let mut options = StandalonePythonExecutableBuilderOptions::default();
+        options.config.run_mode = PythonRunMode::Repl;
+        let mut exe = options.new_builder()?;
+        exe.set_include_stdlib(false)?;
+
+        let names = exe
+            .iter_resources()
+            .map(|(name, _)| name.clone())
+            .collect::<BTreeSet<_>>();
+
+        for name in REPL_STDLIB_MODULES {
+            assert!(names.contains(*name), "{} is retained", name);
+        }
+        assert!(!names.contains("json"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_software_bill_of_materials() -> Result<()> {
+        let options = StandalonePythonExecutableBuilderOptions::default();
+        let mut exe = options.new_builder()?;
+
+        for (name, data) in [
+            (
+                "METADATA",
+                "Metadata-Version: 2.1\nName: foo\nVersion: 1.0\nLicense: MIT\n",
+            ),
+            (
+                "RECORD",
+                "foo/__init__.py,sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU,0\n\
+                foo-1.0.dist-info/RECORD,,\n",
+            ),
+        ]
+        .iter()
+        {
+            exe.add_python_package_distribution_resource(
+                &PythonPackageDistributionResource {
+                    location: PythonPackageDistributionResourceFlavor::DistInfo,
+                    package: "foo".to_string(),
+                    version: "1.0".to_string(),
+                    name: name.to_string(),
+                    data: FileData::Memory(data.as_bytes().to_vec()),
+                },
+                None,
+            )?;
+        }
+
+        let sbom = exe.software_bill_of_materials()?;
+
+        let python = sbom
+            .components
+            .iter()
+            .find(|c| c.kind == SbomComponentKind::PythonDistribution)
+            .unwrap();
+        assert_eq!(python.name, "python");
+        assert_eq!(
+            python.version.as_deref(),
+            Some(exe.target_distribution.version.as_str())
+        );
+
+        let foo = sbom.components.iter().find(|c| c.name == "foo").unwrap();
+        assert_eq!(foo.version.as_deref(), Some("1.0"));
+        assert_eq!(foo.spdx_license().as_deref(), Some("MIT"));
+        assert_eq!(foo.files.len(), 1);
+        assert_eq!(foo.files[0].path, "foo/__init__.py");
+        assert_eq!(
+            foo.files[0].sha256,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+
+        assert!(sbom
+            .components
+            .iter()
+            .any(|c| c.kind == SbomComponentKind::RustCrate && c.name == "anyhow"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_minimal_extensions_present() -> Result<()> {
         let options = StandalonePythonExecutableBuilderOptions::default();
@@ -3125,4 +3776,33 @@ pub mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_validate_wasi_packaging_policy() -> Result<()> {
+        let mut policy = PythonPackagingPolicy::default();
+        validate_wasi_packaging_policy(&policy)?;
+
+        policy.set_resources_location_fallback(Some(ConcreteResourceLocation::RelativePath(
+            "lib".to_string(),
+        )));
+        assert_eq!(
+            validate_wasi_packaging_policy(&policy).unwrap_err().to_string(),
+            "WASI targets only support in-memory resources; PythonPackagingPolicy.resources_location_fallback must be None, not \"filesystem-relative:lib\""
+        );
+
+        policy.set_resources_location(ConcreteResourceLocation::RelativePath("lib".to_string()));
+        assert_eq!(
+            validate_wasi_packaging_policy(&policy).unwrap_err().to_string(),
+            "WASI targets only support in-memory resources; PythonPackagingPolicy.resources_location must be \"in-memory\", not \"filesystem-relative:lib\""
+        );
+
+        let mut policy = PythonPackagingPolicy::default();
+        policy.set_allow_files(true);
+        assert_eq!(
+            validate_wasi_packaging_policy(&policy).unwrap_err().to_string(),
+            "WASI targets only support in-memory resources; PythonPackagingPolicy.allow_files must be False"
+        );
+
+        Ok(())
+    }
 }
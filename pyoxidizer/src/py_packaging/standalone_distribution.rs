@@ -15,7 +15,10 @@ use {
         distutils::prepare_hacked_distutils,
         standalone_builder::StandalonePythonExecutableBuilder,
     },
-    crate::environment::{LINUX_TARGET_TRIPLES, MACOS_TARGET_TRIPLES},
+    crate::{
+        compatibility::CompatibilityCheck,
+        environment::{LINUX_TARGET_TRIPLES, MACOS_TARGET_TRIPLES},
+    },
     anyhow::{anyhow, Context, Result},
     duct::cmd,
     once_cell::sync::Lazy,
@@ -233,12 +236,9 @@ fn parse_python_json(path: &Path) -> Result<PythonJsonMain> {
                 .as_str()
                 .ok_or_else(|| anyhow!("unable to parse version as a string"))?;
 
-            if version != "7" {
-                return Err(anyhow!(
-                    "expected version 7 standalone distribution; found version {}",
-                    version
-                ));
-            }
+            let mut check = CompatibilityCheck::default();
+            check.add_distribution_format(path.display(), version);
+            check.into_result()?;
         }
         None => return Err(anyhow!("version key not present in PYTHON.json")),
     }
@@ -404,6 +404,11 @@ pub struct StandaloneDistribution {
     /// Symbol visibility for Python symbols.
     pub python_symbol_visibility: String,
 
+    /// Magic number of bytecode produced by this distribution.
+    ///
+    /// This is the little endian integer form of `importlib.util.MAGIC_NUMBER`.
+    pub python_bytecode_magic_number: Option<u32>,
+
     /// Capabilities of distribution to load extension modules.
     extension_module_loading: Vec<String>,
 
@@ -946,6 +951,13 @@ impl StandaloneDistribution {
 
         let inittab_object = python_path.join(pi.build_info.inittab_object);
 
+        let python_bytecode_magic_number = match hex::decode(&pi.python_bytecode_magic_number) {
+            Ok(data) if data.len() == 4 => {
+                Some(u32::from_le_bytes([data[0], data[1], data[2], data[3]]))
+            }
+            _ => None,
+        };
+
         Ok(Self {
             base_dir: dist_dir.to_path_buf(),
             target_triple: pi.target_triple,
@@ -959,6 +971,7 @@ impl StandaloneDistribution {
             stdlib_test_packages: pi.python_stdlib_test_packages,
             link_mode,
             python_symbol_visibility: pi.python_symbol_visibility,
+            python_bytecode_magic_number,
             extension_module_loading: pi.python_extension_module_loading,
             apple_sdk_info,
             core_license,
@@ -987,6 +1000,69 @@ impl StandaloneDistribution {
         })
     }
 
+    /// Construct a synthetic distribution for use in tests.
+    ///
+    /// The distribution has no usable Python interpreter, headers, or object
+    /// files. It is only suitable for exercising code that inspects
+    /// distribution metadata and resources. See [crate::testing].
+    #[cfg(any(test, feature = "testing"))]
+    pub fn fixture(
+        base_dir: &Path,
+        target_triple: &str,
+        py_modules: BTreeMap<String, PathBuf>,
+        resources: BTreeMap<String, BTreeMap<String, PathBuf>>,
+        extension_modules: BTreeMap<String, PythonExtensionModuleVariants>,
+    ) -> Result<Self> {
+        let python_dir = base_dir.join("python");
+
+        let mut core_license = LicensedComponent::new_spdx("cpython", "Python-2.0")?;
+        core_license.set_flavor(ComponentFlavor::Library);
+
+        Ok(Self {
+            base_dir: base_dir.to_path_buf(),
+            target_triple: target_triple.to_string(),
+            python_implementation: "cpython".to_string(),
+            python_tag: "cp39".to_string(),
+            python_abi_tag: Some("cp39".to_string()),
+            python_platform_tag: "fixture".to_string(),
+            version: "3.9.5".to_string(),
+            python_exe: python_dir.join("install").join("bin").join("python3"),
+            stdlib_path: python_dir.join("install").join("lib").join("python3.9"),
+            stdlib_test_packages: vec!["test".to_string()],
+            link_mode: StandaloneDistributionLinkMode::Static,
+            python_symbol_visibility: "global-default".to_string(),
+            python_bytecode_magic_number: None,
+            extension_module_loading: vec!["builtin".to_string()],
+            apple_sdk_info: None,
+            core_license: Some(core_license),
+            licenses: Some(vec!["Python-2.0".to_string()]),
+            license_path: None,
+            tcl_library_path: None,
+            tcl_library_paths: None,
+            objs_core: BTreeMap::new(),
+            links_core: vec![],
+            libpython_shared_library: None,
+            extension_modules,
+            frozen_c: vec![],
+            includes: BTreeMap::new(),
+            libraries: BTreeMap::new(),
+            py_modules,
+            resources,
+            venv_base: python_dir.join("venv"),
+            inittab_object: python_dir.join("inittab.o"),
+            inittab_cflags: vec![],
+            cache_tag: "cpython-39".to_string(),
+            module_suffixes: PythonModuleSuffixes {
+                source: vec![".py".to_string()],
+                bytecode: vec![".pyc".to_string()],
+                debug_bytecode: vec![],
+                optimized_bytecode: vec![".opt-1.pyc".to_string(), ".opt-2.pyc".to_string()],
+                extension: vec![".so".to_string()],
+            },
+            crt_features: vec![],
+        })
+    }
+
     /// Determines support for building a libpython from this distribution.
     ///
     /// Returns a tuple of bools indicating whether this distribution can
@@ -1009,12 +1085,67 @@ impl StandaloneDistribution {
         } else if self.target_triple.contains("linux-musl") {
             // Musl binaries don't support dynamic linking.
             (true, false)
+        } else if crate::environment::WASI_TARGET_TRIPLES.contains(&self.target_triple.as_str()) {
+            // WebAssembly modules can't load shared libraries.
+            (true, false)
         } else {
             // Elsewhere we can choose which link mode to use.
             (true, true)
         }
     }
 
+    /// Validate that a libpython link mode can be used with this distribution.
+    ///
+    /// Errors describe which distribution flavor supports the requested mode.
+    pub fn validate_libpython_link_mode(&self, mode: &BinaryLibpythonLinkMode) -> Result<()> {
+        let (supports_static, supports_dynamic) = self.libpython_link_support();
+        let windows = self.target_triple.contains("pc-windows");
+
+        match mode {
+            BinaryLibpythonLinkMode::Default => Ok(()),
+            BinaryLibpythonLinkMode::Static => {
+                if supports_static {
+                    Ok(())
+                } else if windows {
+                    Err(anyhow!(
+                        "Python distribution for {} does not support statically linking libpython; \
+                        use a distribution with flavor standalone_static",
+                        self.target_triple
+                    ))
+                } else {
+                    Err(anyhow!(
+                        "Python distribution for {} does not support statically linking libpython",
+                        self.target_triple
+                    ))
+                }
+            }
+            BinaryLibpythonLinkMode::Dynamic => {
+                if !supports_dynamic {
+                    if windows {
+                        Err(anyhow!(
+                            "Python distribution for {} does not support dynamically linking libpython; \
+                            use a distribution with flavor standalone_dynamic",
+                            self.target_triple
+                        ))
+                    } else {
+                        Err(anyhow!(
+                            "{} can only link libpython statically; use libpython_link_mode \"static\"",
+                            self.target_triple
+                        ))
+                    }
+                } else if self.libpython_shared_library.is_none() {
+                    Err(anyhow!(
+                        "Python distribution for {} does not provide a shared libpython; \
+                        use libpython_link_mode \"static\" or a distribution built with a shared libpython",
+                        self.target_triple
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
     /// Duplicate the python distribution, with distutils hacked
     #[allow(unused)]
     pub fn create_hacked_base(&self, logger: &slog::Logger) -> PythonPaths {
@@ -1245,6 +1376,10 @@ impl PythonDistribution for StandaloneDistribution {
     fn create_packaging_policy(&self) -> Result<PythonPackagingPolicy> {
         let mut policy = PythonPackagingPolicy::default();
 
+        // The first suffix is the most specific one, which is what the
+        // interpreter uses for extension modules it builds.
+        policy.set_extension_module_suffix(self.module_suffixes.extension.first().cloned());
+
         // In-memory shared library loading is brittle. Disable this configuration
         // even if supported because it leads to pain.
         if self.supports_in_memory_shared_library_loading() {
@@ -1301,6 +1436,8 @@ impl PythonDistribution for StandaloneDistribution {
         config: &PyembedPythonInterpreterConfig,
         host_distribution: Option<Arc<dyn PythonDistribution>>,
     ) -> Result<Box<dyn PythonBinaryBuilder>> {
+        self.validate_libpython_link_mode(&libpython_link_mode)?;
+
         // TODO can we avoid these clones?
         let target_distribution = Arc::new(self.clone());
         let host_distribution: Arc<dyn PythonDistribution> =
@@ -1453,6 +1590,70 @@ pub mod tests {
         std::collections::BTreeSet,
     };
 
+    #[test]
+    fn test_python_json_too_new() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let path = temp_dir.path().join("PYTHON.json");
+        std::fs::write(&path, r#"{"version": "8"}"#)?;
+
+        let err = parse_python_json(&path).unwrap_err().to_string();
+        assert!(err.starts_with("incompatible components detected:"));
+        assert!(err.contains("has format version 8"));
+        assert!(err.contains("upgrade PyOxidizer"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_libpython_link_mode() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let distribution =
+            crate::testing::fixture_distribution(temp_dir.path(), "x86_64-unknown-linux-gnu")?;
+
+        distribution.validate_libpython_link_mode(&BinaryLibpythonLinkMode::Default)?;
+        distribution.validate_libpython_link_mode(&BinaryLibpythonLinkMode::Static)?;
+        assert_eq!(
+            distribution
+                .validate_libpython_link_mode(&BinaryLibpythonLinkMode::Dynamic)
+                .unwrap_err()
+                .to_string(),
+            "Python distribution for x86_64-unknown-linux-gnu does not provide a shared libpython; \
+            use libpython_link_mode \"static\" or a distribution built with a shared libpython"
+        );
+
+        let mut musl = distribution.clone();
+        musl.target_triple = "x86_64-unknown-linux-musl".to_string();
+        assert_eq!(
+            musl.validate_libpython_link_mode(&BinaryLibpythonLinkMode::Dynamic)
+                .unwrap_err()
+                .to_string(),
+            "x86_64-unknown-linux-musl can only link libpython statically; use libpython_link_mode \"static\""
+        );
+
+        let mut windows = distribution.clone();
+        windows.target_triple = "x86_64-pc-windows-msvc".to_string();
+        windows.libpython_shared_library = Some(PathBuf::from("python39.dll"));
+        windows.validate_libpython_link_mode(&BinaryLibpythonLinkMode::Dynamic)?;
+        assert!(windows
+            .validate_libpython_link_mode(&BinaryLibpythonLinkMode::Static)
+            .unwrap_err()
+            .to_string()
+            .ends_with("use a distribution with flavor standalone_static"));
+
+        windows.libpython_shared_library = None;
+        assert!(windows
+            .validate_libpython_link_mode(&BinaryLibpythonLinkMode::Dynamic)
+            .unwrap_err()
+            .to_string()
+            .ends_with("use a distribution with flavor standalone_dynamic"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_stdlib_annotations() -> Result<()> {
         let distribution = get_default_distribution()?;
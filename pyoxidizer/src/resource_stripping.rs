@@ -0,0 +1,421 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Removal of payloads from built packed resources data.
+
+Built applications often carry bytecode for optimization levels that are
+never loaded at run-time. [strip_packed_resources] rewrites packed resources
+data without the unwanted bytecode payloads and, optionally, without module
+source. [strip_resources_file] applies this to a standalone packed resources
+file or to the packed resources data embedded in an executable.
+
+Packed resources data doesn't carry checksums, so nothing beyond the index
+needs to be recomputed. Modifying an executable does invalidate any code
+signature it carries, however.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    python_packed_resources::{
+        data::{PackedResourcesLimits, Resource, HEADER_V3, HEADER_V4},
+        parser::{load_resources, packed_resources_length, read_bytecode_magic_number},
+        writer::{write_packed_resources_v3, write_packed_resources_v4_with_limits},
+    },
+    std::{borrow::Cow, collections::BTreeSet, ops::Range, path::Path},
+};
+
+/// Sizes of in-memory module payloads in packed resources data.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PackedResourcesSizes {
+    /// Number of resources.
+    pub resources: usize,
+    /// Total bytes of module source.
+    pub source: u64,
+    /// Total bytes of bytecode, indexed by optimization level.
+    pub bytecode: [u64; 3],
+}
+
+impl PackedResourcesSizes {
+    /// Measure packed resources data.
+    pub fn from_data(data: &[u8]) -> Result<Self> {
+        let mut sizes = Self::default();
+
+        for resource in load_resources(data).map_err(|e| anyhow!(e))? {
+            let resource = resource.map_err(|e| anyhow!(e))?;
+
+            sizes.resources += 1;
+            sizes.source += payload_length(&resource.in_memory_source);
+            sizes.bytecode[0] += payload_length(&resource.in_memory_bytecode);
+            sizes.bytecode[1] += payload_length(&resource.in_memory_bytecode_opt1);
+            sizes.bytecode[2] += payload_length(&resource.in_memory_bytecode_opt2);
+        }
+
+        Ok(sizes)
+    }
+
+    /// Render a human readable summary.
+    pub fn to_text(&self) -> String {
+        let mut s = format!("packed resources ({} resources):\n", self.resources);
+        s.push_str(&format!("  source: {} bytes\n", self.source));
+        for (level, size) in self.bytecode.iter().enumerate() {
+            s.push_str(&format!(
+                "  bytecode (opt level {}): {} bytes\n",
+                level, size
+            ));
+        }
+
+        s
+    }
+}
+
+fn payload_length(payload: &Option<Cow<[u8]>>) -> u64 {
+    payload.as_ref().map(|x| x.len() as u64).unwrap_or(0)
+}
+
+/// Describes which payloads to remove from packed resources data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StripOptions {
+    /// Bytecode optimization levels whose payloads are retained.
+    pub keep_bytecode_levels: BTreeSet<u8>,
+    /// Whether to remove in-memory module source.
+    pub strip_source: bool,
+}
+
+impl Default for StripOptions {
+    fn default() -> Self {
+        Self {
+            keep_bytecode_levels: [0, 1, 2].iter().copied().collect(),
+            strip_source: false,
+        }
+    }
+}
+
+/// The result of stripping packed resources data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StrippedResources {
+    /// The rewritten packed resources data.
+    pub data: Vec<u8>,
+    /// Sizes of the original data.
+    pub before: PackedResourcesSizes,
+    /// Sizes of the rewritten data.
+    pub after: PackedResourcesSizes,
+    /// Modules which had in-memory code but no longer have any.
+    pub modules_without_code: Vec<String>,
+}
+
+fn has_in_memory_code(resource: &Resource<u8>) -> bool {
+    resource.in_memory_source.is_some()
+        || resource.in_memory_bytecode.is_some()
+        || resource.in_memory_bytecode_opt1.is_some()
+        || resource.in_memory_bytecode_opt2.is_some()
+}
+
+/// Rewrite packed resources data without unwanted payloads.
+///
+/// The format version of the original data is retained, as is the bytecode
+/// magic number recorded in version 4 data.
+pub fn strip_packed_resources(data: &[u8], options: &StripOptions) -> Result<StrippedResources> {
+    let magic = read_bytecode_magic_number(data).map_err(|e| anyhow!(e))?;
+
+    let mut resources = load_resources(data)
+        .map_err(|e| anyhow!(e))?
+        .collect::<Result<Vec<_>, &'static str>>()
+        .map_err(|e| anyhow!(e))?;
+
+    let mut modules_without_code = vec![];
+
+    for resource in resources.iter_mut() {
+        let had_code = has_in_memory_code(resource);
+
+        if options.strip_source {
+            resource.in_memory_source = None;
+        }
+        if !options.keep_bytecode_levels.contains(&0) {
+            resource.in_memory_bytecode = None;
+        }
+        if !options.keep_bytecode_levels.contains(&1) {
+            resource.in_memory_bytecode_opt1 = None;
+        }
+        if !options.keep_bytecode_levels.contains(&2) {
+            resource.in_memory_bytecode_opt2 = None;
+        }
+
+        if had_code && !has_in_memory_code(resource) {
+            modules_without_code.push(resource.name.to_string());
+        }
+    }
+
+    let mut stripped = vec![];
+
+    if data.starts_with(HEADER_V3) {
+        // Version 3 predates chunked payloads, so none need to be written.
+        write_packed_resources_v3(&resources, &mut stripped, None)?;
+    } else {
+        // Payloads were read from valid data, so chunked payloads can be
+        // written back as chunks.
+        let limits = PackedResourcesLimits {
+            allow_chunking: true,
+            ..PackedResourcesLimits::default()
+        };
+
+        write_packed_resources_v4_with_limits(
+            &resources,
+            &mut stripped,
+            None,
+            magic.unwrap_or(0),
+            &limits,
+        )?;
+    }
+
+    Ok(StrippedResources {
+        before: PackedResourcesSizes::from_data(data)?,
+        after: PackedResourcesSizes::from_data(&stripped)?,
+        data: stripped,
+        modules_without_code,
+    })
+}
+
+/// Find packed resources data embedded in other data, such as an executable.
+///
+/// Returns the byte range of the first region that parses as packed
+/// resources data containing at least one resource.
+pub fn find_packed_resources(data: &[u8]) -> Option<Range<usize>> {
+    let mut offset = 0;
+
+    while offset + HEADER_V3.len() <= data.len() {
+        let candidate = &data[offset..];
+
+        if candidate.starts_with(HEADER_V3) || candidate.starts_with(HEADER_V4) {
+            if let Ok(length) = packed_resources_length(candidate) {
+                let parsed = load_resources(&candidate[0..length])
+                    .map(|resources| resources.collect::<Result<Vec<_>, &'static str>>());
+
+                if let Ok(Ok(resources)) = parsed {
+                    if !resources.is_empty() {
+                        return Some(offset..offset + length);
+                    }
+                }
+            }
+        }
+
+        offset += 1;
+    }
+
+    None
+}
+
+/// Where stripped packed resources data was written.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StripDestination {
+    /// A standalone packed resources file.
+    Blob,
+    /// A copy of an executable with its embedded data patched in place.
+    PatchedBinary,
+}
+
+/// Strip packed resources data in a file, writing the result to another file.
+///
+/// If `input` is a standalone packed resources file, the stripped data is
+/// written to `output` as is. Otherwise the packed resources data embedded in
+/// `input` is stripped and `output` receives a copy of `input` in which the
+/// embedded data is overwritten and padded with null bytes to its original
+/// length.
+pub fn strip_resources_file(
+    input: &Path,
+    output: &Path,
+    options: &StripOptions,
+) -> Result<(StripDestination, StrippedResources)> {
+    let data = std::fs::read(input).with_context(|| format!("reading {}", input.display()))?;
+
+    let (destination, range) = match packed_resources_length(&data) {
+        Ok(length) if length == data.len() => (StripDestination::Blob, 0..length),
+        _ => (
+            StripDestination::PatchedBinary,
+            find_packed_resources(&data)
+                .ok_or_else(|| anyhow!("no packed resources data found in {}", input.display()))?,
+        ),
+    };
+
+    let stripped = strip_packed_resources(&data[range.clone()], options)
+        .with_context(|| format!("stripping packed resources in {}", input.display()))?;
+
+    let new_data = match destination {
+        StripDestination::Blob => stripped.data.clone(),
+        StripDestination::PatchedBinary => {
+            if stripped.data.len() > range.len() {
+                return Err(anyhow!(
+                    "stripped packed resources data ({} bytes) is larger than the original ({} bytes)",
+                    stripped.data.len(),
+                    range.len()
+                ));
+            }
+
+            let mut new_data = data.clone();
+            let region = &mut new_data[range];
+            region[0..stripped.data.len()].copy_from_slice(&stripped.data);
+            for b in region[stripped.data.len()..].iter_mut() {
+                *b = 0;
+            }
+
+            new_data
+        }
+    };
+
+    std::fs::write(output, &new_data).with_context(|| format!("writing {}", output.display()))?;
+
+    // Retain the executable bit and other permissions of the input.
+    let permissions = std::fs::metadata(input)?.permissions();
+    std::fs::set_permissions(output, permissions)
+        .with_context(|| format!("setting permissions of {}", output.display()))?;
+
+    Ok((destination, stripped))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, python_packed_resources::writer::write_packed_resources_v4};
+
+    fn resources() -> Vec<Resource<'static, u8>> {
+        vec![
+            Resource {
+                name: Cow::from("foo"),
+                is_module: true,
+                in_memory_source: Some(Cow::from(b"import io".as_ref())),
+                in_memory_bytecode: Some(Cow::from(b"bc0".as_ref())),
+                in_memory_bytecode_opt1: Some(Cow::from(b"bc1-".as_ref())),
+                in_memory_bytecode_opt2: Some(Cow::from(b"bc2--".as_ref())),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("bar"),
+                is_module: true,
+                in_memory_bytecode_opt1: Some(Cow::from(b"bc1".as_ref())),
+                ..Resource::default()
+            },
+        ]
+    }
+
+    fn packed_data() -> Vec<u8> {
+        let mut data = vec![];
+        write_packed_resources_v4(&resources(), &mut data, None, 42).unwrap();
+
+        data
+    }
+
+    #[test]
+    fn test_sizes() -> Result<()> {
+        let sizes = PackedResourcesSizes::from_data(&packed_data())?;
+
+        assert_eq!(
+            sizes,
+            PackedResourcesSizes {
+                resources: 2,
+                source: 9,
+                bytecode: [3, 7, 5],
+            }
+        );
+        assert!(sizes
+            .to_text()
+            .contains("  bytecode (opt level 1): 7 bytes\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip() -> Result<()> {
+        let data = packed_data();
+
+        let stripped = strip_packed_resources(
+            &data,
+            &StripOptions {
+                keep_bytecode_levels: [0].iter().copied().collect(),
+                strip_source: true,
+            },
+        )?;
+
+        assert!(stripped.data.len() < data.len());
+        assert_eq!(stripped.before.bytecode, [3, 7, 5]);
+        assert_eq!(stripped.after.source, 0);
+        assert_eq!(stripped.after.bytecode, [3, 0, 0]);
+        assert_eq!(stripped.modules_without_code, vec!["bar".to_string()]);
+        assert_eq!(read_bytecode_magic_number(&stripped.data), Ok(Some(42)));
+
+        let unchanged = strip_packed_resources(&data, &StripOptions::default())?;
+        assert_eq!(unchanged.after, unchanged.before);
+        assert!(unchanged.modules_without_code.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip_v3() -> Result<()> {
+        let mut data = vec![];
+        write_packed_resources_v3(&resources(), &mut data, None)?;
+
+        let stripped = strip_packed_resources(
+            &data,
+            &StripOptions {
+                keep_bytecode_levels: [0].iter().copied().collect(),
+                strip_source: false,
+            },
+        )?;
+
+        assert!(stripped.data.starts_with(HEADER_V3));
+        assert_eq!(stripped.after.bytecode, [3, 0, 0]);
+        assert_eq!(read_bytecode_magic_number(&stripped.data), Ok(None));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip_resources_file() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let data = packed_data();
+        let options = StripOptions {
+            keep_bytecode_levels: [1].iter().copied().collect(),
+            strip_source: false,
+        };
+
+        let blob_path = temp_dir.path().join("packed-resources");
+        let blob_output = temp_dir.path().join("packed-resources.stripped");
+        std::fs::write(&blob_path, &data)?;
+
+        let (destination, stripped) = strip_resources_file(&blob_path, &blob_output, &options)?;
+        assert_eq!(destination, StripDestination::Blob);
+        assert_eq!(std::fs::read(&blob_output)?, stripped.data);
+
+        // An executable with embedded data. A decoy header precedes the data.
+        let mut binary = b"\x7fELF pyembed\x04 junk ".to_vec();
+        let offset = binary.len();
+        binary.extend_from_slice(&data);
+        binary.extend_from_slice(b" trailer");
+
+        let binary_path = temp_dir.path().join("app");
+        let binary_output = temp_dir.path().join("app.stripped");
+        std::fs::write(&binary_path, &binary)?;
+
+        assert_eq!(
+            find_packed_resources(&binary),
+            Some(offset..offset + data.len())
+        );
+
+        let (destination, stripped) = strip_resources_file(&binary_path, &binary_output, &options)?;
+        assert_eq!(destination, StripDestination::PatchedBinary);
+        assert_eq!(stripped.after.bytecode, [0, 7, 0]);
+
+        let patched = std::fs::read(&binary_output)?;
+        assert_eq!(patched.len(), binary.len());
+        assert_eq!(&patched[0..offset], &binary[0..offset]);
+        assert!(patched.ends_with(b" trailer"));
+        assert_eq!(
+            PackedResourcesSizes::from_data(&patched[offset..])?,
+            stripped.after
+        );
+
+        std::fs::write(&binary_path, b"no resources here")?;
+        assert!(strip_resources_file(&binary_path, &binary_output, &options).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,667 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Reports of the contents of packed resources data.
+
+[ResourcesReport] describes each resource in packed resources data: its
+kind and the payloads it carries, where each payload is loaded from and a
+hash of in-memory payloads. Reports can be saved as JSON so builds can be
+compared without keeping the builds around.
+
+[ResourcesDiff] compares two reports. Payloads are compared by hash, so
+rebuilding identical content isn't reported as a change. The content of
+payloads loaded from the filesystem isn't part of packed resources data,
+so only their locations are compared.
+*/
+
+use {
+    crate::resource_stripping::find_packed_resources,
+    anyhow::{anyhow, Context, Result},
+    python_packed_resources::{
+        data::Resource,
+        parser::{load_resources, packed_resources_length},
+    },
+    serde::{Deserialize, Serialize},
+    sha2::{Digest, Sha256},
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, BTreeSet, HashMap},
+        path::Path,
+    },
+};
+
+/// Version of the JSON serialization of [ResourcesReport].
+pub const RESOURCES_REPORT_VERSION: u32 = 1;
+
+/// A payload of a resource.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PayloadReport {
+    /// Where the payload is loaded from.
+    ///
+    /// `in-memory` or `filesystem-relative:<path>`.
+    pub location: String,
+
+    /// Size of an in-memory payload in bytes.
+    ///
+    /// `0` for payloads loaded from the filesystem.
+    pub size: u64,
+
+    /// Hex encoded SHA-256 of an in-memory payload.
+    pub sha256: Option<String>,
+}
+
+impl PayloadReport {
+    fn in_memory(data: &[u8]) -> Self {
+        Self {
+            location: "in-memory".to_string(),
+            size: data.len() as u64,
+            sha256: Some(hex::encode(Sha256::digest(data))),
+        }
+    }
+
+    fn filesystem_relative(path: &Path) -> Self {
+        Self {
+            location: format!("filesystem-relative:{}", path.display()),
+            size: 0,
+            sha256: None,
+        }
+    }
+}
+
+/// A resource in packed resources data.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ResourceReport {
+    /// Name of the resource.
+    pub name: String,
+
+    /// Kind of the resource. e.g. `module` or `extension-module`.
+    pub kind: String,
+
+    /// Payloads keyed by what they hold.
+    ///
+    /// Keys are `source`, `bytecode-opt0`, `bytecode-opt1`,
+    /// `bytecode-opt2`, `extension-module`, `shared-library`, `file`,
+    /// `resource:<name>` and `distribution:<name>`.
+    pub payloads: BTreeMap<String, PayloadReport>,
+}
+
+impl ResourceReport {
+    /// Total size of in-memory payloads.
+    pub fn size(&self) -> u64 {
+        self.payloads.values().map(|p| p.size).sum()
+    }
+
+    fn from_resource(resource: &Resource<u8>) -> Self {
+        let mut payloads = BTreeMap::new();
+
+        let mut add = |key: &str, data: &Option<Cow<[u8]>>, path: Option<&Path>| {
+            if let Some(data) = data {
+                payloads.insert(key.to_string(), PayloadReport::in_memory(data));
+            } else if let Some(path) = path {
+                payloads.insert(key.to_string(), PayloadReport::filesystem_relative(path));
+            }
+        };
+
+        add(
+            "source",
+            &resource.in_memory_source,
+            resource.relative_path_module_source.as_deref(),
+        );
+        add(
+            "bytecode-opt0",
+            &resource.in_memory_bytecode,
+            resource.relative_path_module_bytecode.as_deref(),
+        );
+        add(
+            "bytecode-opt1",
+            &resource.in_memory_bytecode_opt1,
+            resource.relative_path_module_bytecode_opt1.as_deref(),
+        );
+        add(
+            "bytecode-opt2",
+            &resource.in_memory_bytecode_opt2,
+            resource.relative_path_module_bytecode_opt2.as_deref(),
+        );
+        add(
+            "extension-module",
+            &resource.in_memory_extension_module_shared_library,
+            resource
+                .relative_path_extension_module_shared_library
+                .as_deref(),
+        );
+        add("shared-library", &resource.in_memory_shared_library, None);
+        add(
+            "file",
+            &resource.file_data_embedded,
+            resource
+                .file_data_utf8_relative_path
+                .as_deref()
+                .map(Path::new),
+        );
+
+        add_named_payloads(
+            &mut payloads,
+            "resource",
+            &resource.in_memory_package_resources,
+            &resource.relative_path_package_resources,
+        );
+        add_named_payloads(
+            &mut payloads,
+            "distribution",
+            &resource.in_memory_distribution_resources,
+            &resource.relative_path_distribution_resources,
+        );
+
+        Self {
+            name: resource.name.to_string(),
+            kind: resource_kind(resource).to_string(),
+            payloads,
+        }
+    }
+}
+
+fn resource_kind(resource: &Resource<u8>) -> &'static str {
+    if resource.is_builtin_extension_module {
+        "builtin-extension-module"
+    } else if resource.is_frozen_module {
+        "frozen-module"
+    } else if resource.is_extension_module {
+        "extension-module"
+    } else if resource.is_module {
+        "module"
+    } else if resource.is_shared_library {
+        "shared-library"
+    } else if resource.file_data_embedded.is_some()
+        || resource.file_data_utf8_relative_path.is_some()
+    {
+        "file"
+    } else {
+        "other"
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn add_named_payloads(
+    payloads: &mut BTreeMap<String, PayloadReport>,
+    prefix: &str,
+    in_memory: &Option<HashMap<Cow<str>, Cow<[u8]>>>,
+    relative_path: &Option<HashMap<Cow<str>, Cow<Path>>>,
+) {
+    if let Some(paths) = relative_path {
+        for (name, path) in paths {
+            payloads.insert(
+                format!("{}:{}", prefix, name),
+                PayloadReport::filesystem_relative(path),
+            );
+        }
+    }
+
+    if let Some(data) = in_memory {
+        for (name, data) in data {
+            payloads.insert(
+                format!("{}:{}", prefix, name),
+                PayloadReport::in_memory(data),
+            );
+        }
+    }
+}
+
+/// Describes the resources in packed resources data.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ResourcesReport {
+    /// Version of the report format.
+    pub version: u32,
+
+    /// Resources, sorted by name.
+    pub resources: Vec<ResourceReport>,
+}
+
+impl ResourcesReport {
+    /// Construct an instance from packed resources data.
+    pub fn from_data(data: &[u8]) -> Result<Self> {
+        let mut resources = vec![];
+
+        for resource in load_resources(data).map_err(|e| anyhow!(e))? {
+            let resource = resource.map_err(|e| anyhow!(e))?;
+            resources.push(ResourceReport::from_resource(&resource));
+        }
+
+        resources.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self {
+            version: RESOURCES_REPORT_VERSION,
+            resources,
+        })
+    }
+
+    /// Construct an instance from a file.
+    ///
+    /// The file is either a standalone packed resources file, an
+    /// executable embedding packed resources data or a report previously
+    /// saved as JSON.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+        if data.starts_with(b"{") {
+            let report: Self = serde_json::from_slice(&data)
+                .with_context(|| format!("parsing resources report {}", path.display()))?;
+
+            if report.version != RESOURCES_REPORT_VERSION {
+                return Err(anyhow!(
+                    "unsupported resources report version {} in {}; expected {}",
+                    report.version,
+                    path.display(),
+                    RESOURCES_REPORT_VERSION
+                ));
+            }
+
+            return Ok(report);
+        }
+
+        let range = match packed_resources_length(&data) {
+            Ok(length) if length == data.len() => 0..length,
+            _ => find_packed_resources(&data)
+                .ok_or_else(|| anyhow!("no packed resources data found in {}", path.display()))?,
+        };
+
+        Self::from_data(&data[range])
+            .with_context(|| format!("reading packed resources in {}", path.display()))
+    }
+
+    /// Remove resources not belonging to any of the given packages.
+    ///
+    /// A resource belongs to a package if its name is the package name or
+    /// starts with the package name followed by `.`.
+    pub fn retain_packages(&mut self, packages: &[&str]) {
+        self.resources.retain(|resource| {
+            packages.iter().any(|package| {
+                resource.name == *package
+                    || (resource.name.starts_with(package)
+                        && resource.name[package.len()..].starts_with('.'))
+            })
+        });
+    }
+}
+
+/// How something changed between two reports.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl ChangeKind {
+    fn symbol(self) -> char {
+        match self {
+            Self::Added => '+',
+            Self::Removed => '-',
+            Self::Changed => '~',
+        }
+    }
+}
+
+/// A change to a payload of a resource.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PayloadChange {
+    /// Key of the payload. See [ResourceReport::payloads].
+    pub payload: String,
+    pub change: ChangeKind,
+    pub old_location: Option<String>,
+    pub new_location: Option<String>,
+    /// Change of the in-memory size in bytes.
+    pub size_delta: i64,
+}
+
+/// A change to a resource.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ResourceChange {
+    pub name: String,
+    pub change: ChangeKind,
+    pub old_kind: Option<String>,
+    pub new_kind: Option<String>,
+    /// Change of the total in-memory size in bytes.
+    pub size_delta: i64,
+    /// Changed payloads, sorted by key.
+    pub payloads: Vec<PayloadChange>,
+}
+
+/// Differences between two [ResourcesReport].
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ResourcesDiff {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    /// Change of the total in-memory size in bytes.
+    pub size_delta: i64,
+    /// Changed resources, sorted by name.
+    pub resources: Vec<ResourceChange>,
+}
+
+fn size_delta(old: u64, new: u64) -> i64 {
+    new as i64 - old as i64
+}
+
+fn diff_payloads(old: &ResourceReport, new: &ResourceReport) -> Vec<PayloadChange> {
+    let keys = old
+        .payloads
+        .keys()
+        .chain(new.payloads.keys())
+        .collect::<BTreeSet<_>>();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let old = old.payloads.get(key);
+            let new = new.payloads.get(key);
+
+            let change = match (old, new) {
+                (None, Some(_)) => ChangeKind::Added,
+                (Some(_), None) => ChangeKind::Removed,
+                (Some(old), Some(new)) if old != new => ChangeKind::Changed,
+                _ => return None,
+            };
+
+            Some(PayloadChange {
+                payload: key.clone(),
+                change,
+                old_location: old.map(|p| p.location.clone()),
+                new_location: new.map(|p| p.location.clone()),
+                size_delta: size_delta(
+                    old.map(|p| p.size).unwrap_or(0),
+                    new.map(|p| p.size).unwrap_or(0),
+                ),
+            })
+        })
+        .collect()
+}
+
+impl ResourcesDiff {
+    /// Compare two reports.
+    pub fn new(old: &ResourcesReport, new: &ResourcesReport) -> Self {
+        let old_resources = old
+            .resources
+            .iter()
+            .map(|r| (r.name.as_str(), r))
+            .collect::<BTreeMap<_, _>>();
+        let new_resources = new
+            .resources
+            .iter()
+            .map(|r| (r.name.as_str(), r))
+            .collect::<BTreeMap<_, _>>();
+
+        let names = old_resources
+            .keys()
+            .chain(new_resources.keys())
+            .collect::<BTreeSet<_>>();
+
+        let mut diff = Self::default();
+
+        for name in names {
+            let old = old_resources.get(name).copied();
+            let new = new_resources.get(name).copied();
+
+            let change = match (old, new) {
+                (None, Some(new)) => ResourceChange {
+                    name: name.to_string(),
+                    change: ChangeKind::Added,
+                    old_kind: None,
+                    new_kind: Some(new.kind.clone()),
+                    size_delta: size_delta(0, new.size()),
+                    payloads: diff_payloads(&empty_resource(new), new),
+                },
+                (Some(old), None) => ResourceChange {
+                    name: name.to_string(),
+                    change: ChangeKind::Removed,
+                    old_kind: Some(old.kind.clone()),
+                    new_kind: None,
+                    size_delta: size_delta(old.size(), 0),
+                    payloads: diff_payloads(old, &empty_resource(old)),
+                },
+                (Some(old), Some(new)) => {
+                    let payloads = diff_payloads(old, new);
+                    if payloads.is_empty() && old.kind == new.kind {
+                        continue;
+                    }
+
+                    ResourceChange {
+                        name: name.to_string(),
+                        change: ChangeKind::Changed,
+                        old_kind: Some(old.kind.clone()),
+                        new_kind: Some(new.kind.clone()),
+                        size_delta: size_delta(old.size(), new.size()),
+                        payloads,
+                    }
+                }
+                (None, None) => continue,
+            };
+
+            match change.change {
+                ChangeKind::Added => diff.added += 1,
+                ChangeKind::Removed => diff.removed += 1,
+                ChangeKind::Changed => diff.changed += 1,
+            }
+            diff.size_delta += change.size_delta;
+            diff.resources.push(change);
+        }
+
+        diff
+    }
+
+    /// Render a human readable description of the differences.
+    pub fn to_text(&self) -> String {
+        let mut s = String::new();
+
+        for resource in &self.resources {
+            match resource.change {
+                ChangeKind::Changed => s.push_str(&format!(
+                    "~ {} ({:+} bytes)\n",
+                    resource.name, resource.size_delta
+                )),
+                change => s.push_str(&format!(
+                    "{} {} ({}, {:+} bytes)\n",
+                    change.symbol(),
+                    resource.name,
+                    resource
+                        .new_kind
+                        .as_ref()
+                        .or(resource.old_kind.as_ref())
+                        .unwrap(),
+                    resource.size_delta
+                )),
+            }
+
+            if let (Some(old), Some(new)) = (&resource.old_kind, &resource.new_kind) {
+                if old != new {
+                    s.push_str(&format!("    ~ kind ({} -> {})\n", old, new));
+                }
+            }
+
+            for payload in &resource.payloads {
+                let location = match (&payload.old_location, &payload.new_location) {
+                    (Some(old), Some(new)) if old != new => format!("{} -> {}", old, new),
+                    (_, Some(location)) | (Some(location), None) => location.clone(),
+                    (None, None) => "".to_string(),
+                };
+
+                s.push_str(&format!(
+                    "    {} {} ({}, {:+} bytes)\n",
+                    payload.change.symbol(),
+                    payload.payload,
+                    location,
+                    payload.size_delta
+                ));
+            }
+        }
+
+        s.push_str(&format!(
+            "{} added, {} removed, {} changed; {:+} bytes in memory\n",
+            self.added, self.removed, self.changed, self.size_delta
+        ));
+
+        s
+    }
+}
+
+fn empty_resource(resource: &ResourceReport) -> ResourceReport {
+    ResourceReport {
+        name: resource.name.clone(),
+        kind: resource.kind.clone(),
+        payloads: BTreeMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, python_packed_resources::writer::write_packed_resources_v4};
+
+    fn write_blob(path: &Path, resources: &[Resource<u8>]) -> Result<()> {
+        let mut data = vec![];
+        write_packed_resources_v4(resources, &mut data, None, 42)?;
+        std::fs::write(path, &data)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_blobs() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let old_path = temp_dir.path().join("old");
+        let new_path = temp_dir.path().join("new");
+
+        write_blob(
+            &old_path,
+            &[
+                Resource {
+                    name: Cow::from("foo"),
+                    is_module: true,
+                    is_package: true,
+                    in_memory_source: Some(Cow::from(b"import io".as_ref())),
+                    in_memory_bytecode: Some(Cow::from(b"bc0".as_ref())),
+                    in_memory_bytecode_opt2: Some(Cow::from(b"bc2--".as_ref())),
+                    ..Resource::default()
+                },
+                Resource {
+                    name: Cow::from("foo.bar"),
+                    is_module: true,
+                    in_memory_bytecode: Some(Cow::from(b"bar".as_ref())),
+                    ..Resource::default()
+                },
+                Resource {
+                    name: Cow::from("foo.baz"),
+                    is_module: true,
+                    in_memory_bytecode: Some(Cow::from(b"baz".as_ref())),
+                    ..Resource::default()
+                },
+                Resource {
+                    name: Cow::from("removed"),
+                    is_module: true,
+                    in_memory_bytecode: Some(Cow::from(b"gone".as_ref())),
+                    ..Resource::default()
+                },
+                Resource {
+                    name: Cow::from("unchanged"),
+                    is_module: true,
+                    in_memory_bytecode: Some(Cow::from(b"same".as_ref())),
+                    ..Resource::default()
+                },
+            ],
+        )?;
+
+        let mut package_resources = HashMap::new();
+        package_resources.insert(Cow::from("data.txt"), Cow::from(b"data".as_ref()));
+
+        write_blob(
+            &new_path,
+            &[
+                Resource {
+                    name: Cow::from("foo"),
+                    is_module: true,
+                    is_package: true,
+                    in_memory_source: Some(Cow::from(b"import io, os".as_ref())),
+                    in_memory_bytecode: Some(Cow::from(b"bc0".as_ref())),
+                    in_memory_bytecode_opt1: Some(Cow::from(b"bc1-".as_ref())),
+                    in_memory_package_resources: Some(package_resources),
+                    ..Resource::default()
+                },
+                Resource {
+                    name: Cow::from("foo.bar"),
+                    is_module: true,
+                    relative_path_module_bytecode: Some(Cow::from(Path::new(
+                        "lib/foo/__pycache__/bar.pyc",
+                    ))),
+                    ..Resource::default()
+                },
+                Resource {
+                    name: Cow::from("foo.baz"),
+                    is_module: true,
+                    in_memory_bytecode: Some(Cow::from(b"BAZ".as_ref())),
+                    ..Resource::default()
+                },
+                Resource {
+                    name: Cow::from("added"),
+                    file_data_embedded: Some(Cow::from(b"file".as_ref())),
+                    ..Resource::default()
+                },
+                Resource {
+                    name: Cow::from("unchanged"),
+                    is_module: true,
+                    in_memory_bytecode: Some(Cow::from(b"same".as_ref())),
+                    ..Resource::default()
+                },
+            ],
+        )?;
+
+        let old = ResourcesReport::from_path(&old_path)?;
+        let new = ResourcesReport::from_path(&new_path)?;
+
+        let diff = ResourcesDiff::new(&old, &new);
+        // Update the snapshot when the output format is changed intentionally.
+        assert_eq!(diff.to_text(), include_str!("resources_diff.snapshot"));
+        assert_eq!((diff.added, diff.removed, diff.changed), (1, 1, 3));
+
+        assert!(ResourcesDiff::new(&new, &new).resources.is_empty());
+
+        // Saved reports and executables embedding the data give the same result.
+        let report_path = temp_dir.path().join("old.json");
+        std::fs::write(&report_path, serde_json::to_vec_pretty(&old)?)?;
+        assert_eq!(ResourcesReport::from_path(&report_path)?, old);
+
+        let mut binary = b"\x7fELF junk ".to_vec();
+        binary.extend_from_slice(&std::fs::read(&new_path)?);
+        binary.extend_from_slice(b" trailer");
+        let binary_path = temp_dir.path().join("app");
+        std::fs::write(&binary_path, &binary)?;
+        assert_eq!(ResourcesReport::from_path(&binary_path)?, new);
+
+        let mut old = old;
+        let mut new = new;
+        old.retain_packages(&["foo"]);
+        new.retain_packages(&["foo"]);
+        let diff = ResourcesDiff::new(&old, &new);
+        assert_eq!(
+            diff.resources
+                .iter()
+                .map(|r| r.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["foo", "foo.bar", "foo.baz"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_version() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().join("report.json");
+        std::fs::write(&path, br#"{"version": 2, "resources": []}"#)?;
+
+        assert!(ResourcesReport::from_path(&path)
+            .unwrap_err()
+            .to_string()
+            .contains("unsupported resources report version 2"));
+
+        Ok(())
+    }
+}
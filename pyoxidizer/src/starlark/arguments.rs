@@ -0,0 +1,114 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Checked extraction of resource arguments.
+
+This extends [FunctionArgs] with Python resource types.
+*/
+
+use {
+    super::python_resource::is_resource_value,
+    starlark::values::{error::ValueError, Value},
+    starlark_dialect_build_targets::arguments::FunctionArgs,
+};
+
+/// Extracts Python resource arguments of a Starlark function.
+pub trait ResourceArgs {
+    /// Obtain a resource value.
+    fn resource(&self, name: &str, value: &Value) -> Result<Value, ValueError>;
+
+    /// Obtain the members of an iterable of resource values.
+    fn resource_list(&self, name: &str, value: &Value) -> Result<Vec<Value>, ValueError>;
+}
+
+impl<'a> ResourceArgs for FunctionArgs<'a> {
+    fn resource(&self, name: &str, value: &Value) -> Result<Value, ValueError> {
+        if is_resource_value(value) {
+            Ok(value.clone())
+        } else {
+            Err(self.type_error(name, "a Python resource", self.received(value)))
+        }
+    }
+
+    fn resource_list(&self, name: &str, value: &Value) -> Result<Vec<Value>, ValueError> {
+        const EXPECTED: &str = "a list of Python resources";
+
+        let values = value
+            .iter()
+            .map_err(|_| self.type_error(name, EXPECTED, self.received(value)))?;
+
+        values
+            .iter()
+            .map(|v| {
+                if is_resource_value(&v) {
+                    Ok(v)
+                } else {
+                    Err(self.type_error(
+                        name,
+                        EXPECTED,
+                        &format!("{} containing {}", value.get_type(), v.get_type()),
+                    ))
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::super::testutil::*,
+        anyhow::Result,
+        codemap::CodeMap,
+        std::sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn test_error_messages_snapshot() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+        env.eval("m = exe.make_python_module_source('foo', '')")?;
+        env.eval("policy = dist.make_python_packaging_policy()")?;
+
+        let map = Arc::new(Mutex::new(CodeMap::new()));
+        let mut messages = String::new();
+
+        for code in &[
+            "exe.pip_download('foo')",
+            "exe.pip_install(['foo', 1])",
+            "exe.pip_install(['foo'], extra_envs=[])",
+            "exe.pip_install(['foo'], extra_envs={'A': 1})",
+            "exe.read_package_root('/tmp', 'foo')",
+            "exe.setup_py_install('/tmp', extra_global_arguments='foo')",
+            "exe.add_python_resource(1)",
+            "exe.add_python_resources([m, 'foo'])",
+            "exe.add_python_resources(1)",
+            "exe.to_embedded_python_files(path=True)",
+            "exe.filter_resources_from_files(glob_files=[None])",
+            "missing_resources([m], [1])",
+            "prefix_module_names([m], None)",
+            "set_include_by_extension([m], 'py', 'yes')",
+            "set_location_fallback_all([m], 1)",
+            "assign_locations([m], 'foo')",
+            "rebase_relative_locations([m], 'foo', 1)",
+            "size_bucket('foo')",
+            "total_emitted_size([m, 1])",
+            "m.add_include = 'yes'",
+            "m.add_location = 1",
+            "exe.apply(1)",
+            "policy.require_feature('pro', 'foo')",
+            "policy.set_bytecode_levels_for_scope('stdlib', ['foo'])",
+            "dist.to_python_executable('foo', config=1)",
+        ] {
+            let err = env.eval_diagnostic(&map, "<test>", code).expect_err(code);
+            messages.push_str(&format!("{}\n  {}\n", code, err.message));
+        }
+
+        // Update the snapshot when error messages are changed intentionally.
+        assert_eq!(messages, include_str!("arguments_errors.snapshot"));
+
+        Ok(())
+    }
+}
@@ -13,7 +13,9 @@ use {
             {Mutable, TypedValue, Value, ValueResult},
         },
     },
-    starlark_dialect_build_targets::{get_context_value, EnvironmentContext},
+    starlark_dialect_build_targets::{
+        get_context_value, sandbox::SandboxPolicy, EnvironmentContext,
+    },
     std::{
         collections::HashMap,
         convert::TryFrom,
@@ -54,6 +56,18 @@ pub struct PyOxidizerEnvironmentContext {
     /// Optimization level when building binaries.
     pub build_opt_level: String,
 
+    /// Whether to build executables with startup tracing support.
+    pub build_trace_startup: bool,
+
+    /// Names of enabled feature flags, sorted.
+    pub features: Vec<String>,
+
+    /// Whether to verify vendored projects are up to date instead of writing them.
+    pub verify_vendored: bool,
+
+    /// Program and arguments used to run executables built for WASI targets.
+    pub wasm_runtime: Vec<String>,
+
     /// Cache of ready-to-clone Python distribution objects.
     ///
     /// This exists because constructing a new instance can take a
@@ -104,6 +118,10 @@ impl PyOxidizerEnvironmentContext {
             build_target_triple: build_target_triple.to_string(),
             build_release,
             build_opt_level: build_opt_level.to_string(),
+            build_trace_startup: false,
+            features: vec![],
+            verify_vendored: false,
+            wasm_runtime: vec!["wasmtime".to_string()],
             distribution_cache,
             extra_vars,
         })
@@ -191,11 +209,24 @@ pub fn register_starlark_dialect(
 ) -> Result<(), EnvironmentError> {
     starlark_dialect_build_targets::register_starlark_dialect(env, type_values)?;
     tugger::starlark::register_starlark_dialect(env, type_values)?;
+    super::file::file_module(env, type_values);
     super::file_resource::file_resource_env(env, type_values);
+    super::named_cache::named_cache_module(env, type_values);
     super::python_distribution::python_distribution_module(env, type_values);
+    super::python_embedded_files::python_embedded_files_module(env, type_values);
     super::python_embedded_resources::python_embedded_resources_module(env, type_values);
     super::python_executable::python_executable_env(env, type_values);
+    super::python_extension_module::python_extension_module_module(env, type_values);
+    super::python_module_source::python_module_source_module(env, type_values);
+    super::python_package_distribution_resource::python_package_distribution_resource_module(
+        env,
+        type_values,
+    );
+    super::python_package_resource::python_package_resource_module(env, type_values);
     super::python_packaging_policy::python_packaging_policy_module(env, type_values);
+    super::python_resource::python_resource_env(env, type_values);
+    super::resource_selector::resource_selector_module(env, type_values);
+    super::strict::strict_module(env, type_values);
 
     Ok(())
 }
@@ -206,6 +237,7 @@ pub fn populate_environment(
     context: PyOxidizerEnvironmentContext,
     resolve_targets: Option<Vec<String>>,
     build_script_mode: bool,
+    sandbox: Option<SandboxPolicy>,
 ) -> Result<(), EnvironmentError> {
     let mut build_targets_context = EnvironmentContext::new(context.logger(), context.cwd.clone());
 
@@ -214,6 +246,7 @@ pub fn populate_environment(
     }
 
     build_targets_context.build_script_mode = build_script_mode;
+    build_targets_context.set_sandbox(sandbox);
 
     build_targets_context.set_target_build_path_prefix(Some(
         PathBuf::from(&context.build_target_triple).join(if context.build_release {
@@ -242,6 +275,16 @@ pub fn populate_environment(
     }
 
     env.set("VARS", Value::try_from(vars.get_content().clone()).unwrap())?;
+    env.set(
+        "FEATURES",
+        Value::from(
+            context
+                .features
+                .iter()
+                .map(|f| Value::from(f.as_str()))
+                .collect::<Vec<_>>(),
+        ),
+    )?;
     env.set("CWD", Value::from(context.cwd.display().to_string()))?;
     env.set(
         "CONFIG_PATH",
@@ -4,19 +4,23 @@
 
 use {
     crate::{
+        compatibility::check_rust_project,
         environment::default_target_triple,
         py_packaging::distribution::DistributionCache,
-        starlark::env::{
-            populate_environment, register_starlark_dialect, PyOxidizerContext,
-            PyOxidizerEnvironmentContext,
+        starlark::{
+            env::{
+                populate_environment, register_starlark_dialect, PyOxidizerContext,
+                PyOxidizerEnvironmentContext,
+            },
+            strict,
         },
     },
     anyhow::{anyhow, Result},
     codemap::CodeMap,
-    codemap_diagnostic::{Diagnostic, Emitter},
+    codemap_diagnostic::{Diagnostic, Emitter, Level},
     starlark::{
         environment::{Environment, EnvironmentError, TypeValues},
-        eval::call_stack::CallStack,
+        eval::{call_stack::CallStack, EvalException, FileLoader},
         syntax::dialect::Dialect,
         values::{
             error::{RuntimeError, ValueError},
@@ -24,16 +28,87 @@ use {
         },
     },
     starlark_dialect_build_targets::{
-        build_target, run_target, EnvironmentContext, ResolvedTarget,
+        build_target, check_capability, run_target,
+        sandbox::{Capability, SandboxPolicy},
+        EnvironmentContext, ResolvedTarget,
     },
     std::{
-        collections::HashMap,
+        cell::RefCell,
+        collections::{BTreeMap, HashMap},
         convert::TryFrom,
+        ffi::OsStr,
         path::{Path, PathBuf},
+        rc::Rc,
         sync::{Arc, Mutex},
     },
 };
 
+/// A `FileLoader` that records files loaded via `load()` as read inputs.
+///
+/// Loading is subject to the sandbox policy like any other read. This
+/// otherwise behaves like `starlark::eval::simple::SimpleFileLoader`.
+#[derive(Clone)]
+struct RecordingFileLoader {
+    loaded: Rc<RefCell<HashMap<String, Environment>>>,
+    parent_env: Environment,
+    codemap: Arc<Mutex<CodeMap>>,
+}
+
+impl RecordingFileLoader {
+    fn new(codemap: &Arc<Mutex<CodeMap>>, parent_env: Environment) -> Self {
+        Self {
+            loaded: Rc::new(RefCell::new(HashMap::new())),
+            parent_env,
+            codemap: codemap.clone(),
+        }
+    }
+}
+
+impl FileLoader for RecordingFileLoader {
+    fn load(&self, path: &str, type_values: &TypeValues) -> Result<Environment, EvalException> {
+        if let Some(env) = self.loaded.borrow().get(path) {
+            return Ok(env.clone());
+        }
+
+        // The path is resolved relative to the process's working directory.
+        let record_path = std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+
+        // Check before reading so the content of a disallowed file can't
+        // leak via parse errors.
+        check_capability(type_values, "load", &Capability::ReadPath(&record_path)).map_err(
+            |e| {
+                EvalException::DiagnosedError(Diagnostic {
+                    level: Level::Error,
+                    message: match e {
+                        ValueError::Runtime(e) => e.message,
+                        e => format!("{:?}", e),
+                    },
+                    code: Some("STARLARK_SANDBOX".to_string()),
+                    spans: vec![],
+                })
+            },
+        )?;
+
+        let mut env = self.parent_env.child(path);
+        starlark::eval::eval_file(
+            &self.codemap,
+            path,
+            Dialect::Bzl,
+            &mut env,
+            type_values,
+            self,
+        )
+        .map_err(EvalException::DiagnosedError)?;
+        env.freeze();
+
+        self.loaded
+            .borrow_mut()
+            .insert(path.to_string(), env.clone());
+
+        Ok(env)
+    }
+}
+
 /// Builder type to construct `EvaluationContext` instances.
 pub struct EvaluationContextBuilder {
     env: crate::environment::Environment,
@@ -42,11 +117,17 @@ pub struct EvaluationContextBuilder {
     build_target_triple: String,
     release: bool,
     verbose: bool,
+    trace_startup: bool,
     resolve_targets: Option<Vec<String>>,
     build_script_mode: bool,
     build_opt_level: String,
     distribution_cache: Option<Arc<DistributionCache>>,
     extra_vars: HashMap<String, Option<String>>,
+    features: Vec<String>,
+    sandbox: Option<SandboxPolicy>,
+    strict: bool,
+    verify_vendored: bool,
+    wasm_runtime: Option<String>,
 }
 
 impl EvaluationContextBuilder {
@@ -63,11 +144,17 @@ impl EvaluationContextBuilder {
             build_target_triple: build_target_triple.to_string(),
             release: false,
             verbose: false,
+            trace_startup: false,
             resolve_targets: None,
             build_script_mode: false,
             build_opt_level: "0".to_string(),
             distribution_cache: None,
             extra_vars: HashMap::new(),
+            features: vec![],
+            sandbox: None,
+            strict: false,
+            verify_vendored: false,
+            wasm_runtime: None,
         }
     }
 
@@ -96,6 +183,12 @@ impl EvaluationContextBuilder {
         self
     }
 
+    /// Build executables with the `startup-trace` pyembed feature.
+    pub fn trace_startup(mut self, value: bool) -> Self {
+        self.trace_startup = value;
+        self
+    }
+
     pub fn resolve_targets_optional(mut self, targets: Option<Vec<impl ToString>>) -> Self {
         self.resolve_targets =
             targets.map(|targets| targets.iter().map(|x| x.to_string()).collect());
@@ -131,6 +224,38 @@ impl EvaluationContextBuilder {
         self.extra_vars = extra_vars;
         self
     }
+
+    /// Enable named feature flags, exposed to Starlark as `FEATURES`.
+    pub fn features(mut self, features: Vec<String>) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Restrict privileged operations performed by builtins to those granted by a policy.
+    pub fn sandbox(mut self, policy: Option<SandboxPolicy>) -> Self {
+        self.sandbox = policy;
+        self
+    }
+
+    /// Evaluate in strict mode. See the `strict` module.
+    pub fn strict(mut self, value: bool) -> Self {
+        self.strict = value;
+        self
+    }
+
+    /// Verify vendored projects are up to date instead of writing them.
+    pub fn verify_vendored(mut self, value: bool) -> Self {
+        self.verify_vendored = value;
+        self
+    }
+
+    /// Command used to run executables built for WASI targets.
+    ///
+    /// The command is split on whitespace. Defaults to `wasmtime`.
+    pub fn wasm_runtime(mut self, value: Option<impl ToString>) -> Self {
+        self.wasm_runtime = value.map(|x| x.to_string());
+        self
+    }
 }
 
 /// Interface to evaluate Starlark configuration files.
@@ -156,7 +281,7 @@ impl TryFrom<EvaluationContextBuilder> for EvaluationContext {
 
 impl EvaluationContext {
     pub fn from_builder(builder: EvaluationContextBuilder) -> Result<Self> {
-        let context = PyOxidizerEnvironmentContext::new(
+        let mut context = PyOxidizerEnvironmentContext::new(
             &builder.env,
             builder.logger,
             builder.verbose,
@@ -168,6 +293,20 @@ impl EvaluationContext {
             builder.distribution_cache,
             builder.extra_vars,
         )?;
+        context.build_trace_startup = builder.trace_startup;
+        context.features = builder.features;
+        context.features.sort();
+        context.features.dedup();
+        context.verify_vendored = builder.verify_vendored;
+        if let Some(runtime) = builder.wasm_runtime {
+            context.wasm_runtime = runtime.split_whitespace().map(|x| x.to_string()).collect();
+
+            if context.wasm_runtime.is_empty() {
+                return Err(anyhow!("wasm runtime command must not be empty"));
+            }
+        }
+
+        strict::reset(builder.strict);
 
         let (mut parent_env, mut type_values) = starlark::stdlib::global_environment();
 
@@ -184,6 +323,7 @@ impl EvaluationContext {
             context,
             builder.resolve_targets,
             builder.build_script_mode,
+            builder.sandbox,
         )
         .map_err(|e| anyhow!("error populating Starlark environment: {:?}", e))?;
 
@@ -207,15 +347,21 @@ impl EvaluationContext {
     /// Evaluate a Starlark configuration file, returning a Diagnostic on error.
     pub fn evaluate_file_diagnostic(&mut self, config_path: &Path) -> Result<(), Diagnostic> {
         let map = Arc::new(Mutex::new(CodeMap::new()));
-        let file_loader_env = self.parent_env.clone();
+        let file_loader = RecordingFileLoader::new(&map, self.parent_env.clone());
+
+        if let Ok(raw_context) = self.build_targets_context_value() {
+            if let Some(context) = raw_context.downcast_ref::<EnvironmentContext>() {
+                context.record_read_path(config_path);
+            }
+        }
 
-        starlark::eval::simple::eval_file(
+        starlark::eval::eval_file(
             &map,
             &config_path.display().to_string(),
             Dialect::Bzl,
             &mut self.child_env,
             &self.type_values,
-            file_loader_env,
+            &file_loader,
         )
         .map_err(|e| {
             if let Ok(raw_context) = self.build_targets_context_value() {
@@ -235,11 +381,26 @@ impl EvaluationContext {
             e
         })?;
 
+        if let Ok(raw_context) = self.build_targets_context_value() {
+            if let Some(context) = raw_context.downcast_ref::<EnvironmentContext>() {
+                for warning in strict::unused_values_warnings() {
+                    slog::warn!(context.logger(), "{}", warning);
+                }
+            }
+        }
+
         Ok(())
     }
 
     /// Evaluate a Starlark configuration file, returning an anyhow Result.
+    ///
+    /// A Rust project in the config file's directory is first checked for
+    /// compatibility with this PyOxidizer release.
     pub fn evaluate_file(&mut self, config_path: &Path) -> Result<()> {
+        if let Some(parent) = config_path.parent() {
+            check_rust_project(parent)?;
+        }
+
         self.evaluate_file_diagnostic(config_path)
             .map_err(|d| anyhow!(d.message))
     }
@@ -251,16 +412,16 @@ impl EvaluationContext {
         path: &str,
         code: &str,
     ) -> Result<Value, Diagnostic> {
-        let file_loader_env = self.child_env.clone();
+        let file_loader = RecordingFileLoader::new(map, self.child_env.clone());
 
-        starlark::eval::simple::eval(
+        starlark::eval::eval(
             &map,
             path,
             code,
             Dialect::Bzl,
             &mut self.child_env,
             &self.type_values,
-            file_loader_env,
+            &file_loader,
         )
     }
 
@@ -346,6 +507,28 @@ impl EvaluationContext {
             .collect::<Vec<_>>())
     }
 
+    /// Obtain registered target aliases, mapping alias names to target names.
+    pub fn target_aliases(&self) -> Result<BTreeMap<String, String>> {
+        let raw_context = self.build_targets_context_value()?;
+        let context = raw_context
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or_else(|| anyhow!("context has incorrect type"))?;
+
+        Ok(context.aliases().clone())
+    }
+
+    /// Obtain the description of a registered target.
+    pub fn target_description(&self, target: &str) -> Result<Option<String>> {
+        let raw_context = self.build_targets_context_value()?;
+        let context = raw_context
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or_else(|| anyhow!("context has incorrect type"))?;
+
+        Ok(context
+            .get_target(target)
+            .and_then(|t| t.description.clone()))
+    }
+
     /// Obtain targets that should be resolved.
     pub fn targets_to_resolve(&self) -> Result<Vec<String>> {
         let raw_context = self.build_targets_context_value()?;
@@ -356,6 +539,18 @@ impl EvaluationContext {
         Ok(context.targets_to_resolve())
     }
 
+    /// Filesystem paths read during evaluation and target resolution.
+    ///
+    /// Paths may refer to files or directories.
+    pub fn read_paths(&self) -> Result<Vec<PathBuf>> {
+        let raw_context = self.build_targets_context_value()?;
+        let context = raw_context
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or_else(|| anyhow!("context has incorrect type"))?;
+
+        Ok(context.read_paths())
+    }
+
     pub fn build_resolved_target(&mut self, target: &str) -> Result<ResolvedTarget> {
         let mut call_stack = CallStack::default();
 
@@ -367,7 +562,13 @@ impl EvaluationContext {
         )
     }
 
-    pub fn run_target(&mut self, target: Option<&str>) -> Result<()> {
+    /// Build and run a target, returning the exit code of the process.
+    pub fn run_target(
+        &mut self,
+        target: Option<&str>,
+        args: &[impl AsRef<OsStr>],
+        cwd: &Path,
+    ) -> Result<i32> {
         let mut call_stack = CallStack::default();
 
         run_target(
@@ -375,13 +576,22 @@ impl EvaluationContext {
             &self.type_values,
             &mut call_stack,
             target,
+            args,
+            cwd,
         )
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use {super::*, crate::testutil::*, starlark::values::dict::Dictionary};
+    use {
+        super::*,
+        crate::{
+            starlark::testutil::{eval_assert, test_evaluation_context_builder},
+            testutil::*,
+        },
+        starlark::values::dict::Dictionary,
+    };
 
     #[test]
     fn test_load() -> Result<()> {
@@ -420,6 +630,50 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_load_sandbox() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+
+        let project = temp_dir.path().join("project");
+        std::fs::create_dir(&project)?;
+        std::fs::write(project.join("inside.bzl"), "VALUE = 42\n")?;
+
+        // Not valid Starlark, so its content would appear in a parse error.
+        let outside = temp_dir.path().join("outside.bzl");
+        std::fs::write(&outside, "secret content\n")?;
+
+        let main_path = project.join("pyoxidizer.bzl");
+        let load = |path: &Path| -> Result<()> {
+            std::fs::write(
+                &main_path,
+                format!(
+                    "load('{}', 'VALUE')\n",
+                    path.display().to_string().escape_default()
+                ),
+            )?;
+
+            test_evaluation_context_builder()?
+                .config_path(&main_path)
+                .sandbox(Some(SandboxPolicy::new(&main_path)))
+                .into_context()?
+                .evaluate_file(&main_path)
+        };
+
+        load(&project.join("inside.bzl"))?;
+
+        let err = load(&outside).unwrap_err().to_string();
+        assert!(
+            err.contains("sandbox violation: load is not allowed to read"),
+            "{}",
+            err
+        );
+        assert!(!err.contains("secret"), "{}", err);
+
+        Ok(())
+    }
+
     #[test]
     fn test_register_target() -> Result<()> {
         let temp_dir = tempfile::Builder::new()
@@ -480,4 +734,22 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn features() -> Result<()> {
+        let mut context = test_evaluation_context_builder()?
+            .features(vec![
+                "pro".to_string(),
+                "beta".to_string(),
+                "pro".to_string(),
+            ])
+            .into_context()?;
+
+        eval_assert(&mut context, "FEATURES == ['beta', 'pro']")?;
+
+        let mut context = test_evaluation_context_builder()?.into_context()?;
+        eval_assert(&mut context, "FEATURES == []")?;
+
+        Ok(())
+    }
 }
@@ -3,13 +3,29 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    super::python_resource::ResourceCollectionContext,
+    super::{
+        python_resource::{
+            policy_allowed_locations, size_bucket_value, starlark_add_collection_context,
+            starlark_as_struct, starlark_duplicate_to_locations, starlark_lock,
+            starlark_plan_entry, starlark_set_collection_context, starlark_settable_attributes,
+            starlark_to_canonical_string, starlark_with_include, ResourceCollectionContext,
+        },
+        resource_selector::{SelectorCandidate, SizeBucketThresholds},
+        strict::unknown_attribute_error,
+    },
     python_packaging::{
+        location::AbstractResourceLocation, policy::PythonPackagingPolicy,
         resource::PythonResource, resource_collection::PythonResourceAddCollectionContext,
     },
-    starlark::values::{
-        error::{RuntimeError, UnsupportedOperation, ValueError},
-        {Mutable, TypedValue, Value, ValueResult},
+    starlark::{
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
     },
     std::sync::{Arc, Mutex, MutexGuard},
     tugger_file_manifest::File,
@@ -19,6 +35,12 @@ use {
 pub struct FileWrapper {
     pub file: File,
     pub add_context: Option<PythonResourceAddCollectionContext>,
+    /// Whether `add_*` attributes can no longer be modified.
+    pub locked: bool,
+    /// Resource locations allowed by the packaging policy.
+    ///
+    /// Captured from the packaging policy when it is applied.
+    pub allowed_locations: Option<Vec<AbstractResourceLocation>>,
 }
 
 /// Starlark value wrapper for `File`.
@@ -36,6 +58,8 @@ impl FileValue {
             inner: Arc::new(Mutex::new(FileWrapper {
                 file,
                 add_context: None,
+                locked: false,
+                allowed_locations: None,
             })),
             path,
         }
@@ -53,6 +77,18 @@ impl FileValue {
 }
 
 impl ResourceCollectionContext for FileValue {
+    fn resource_attrs(&self) -> Vec<&'static str> {
+        vec![
+            "is_locked",
+            "will_be_included",
+            "context_hash",
+            "effective_location",
+            "path",
+            "is_executable",
+            "size_bucket",
+        ]
+    }
+
     fn add_collection_context(
         &self,
     ) -> Result<Option<PythonResourceAddCollectionContext>, ValueError> {
@@ -72,11 +108,54 @@ impl ResourceCollectionContext for FileValue {
             .replace(context))
     }
 
+    fn independent_copy(&self) -> Result<Self, ValueError> {
+        const LABEL: &str = "File.independent_copy()";
+
+        let inner = self.inner(LABEL)?;
+
+        let value = FileValue::new(inner.file.clone());
+        {
+            let mut copy = value.inner(LABEL)?;
+            copy.add_context = inner.add_context.clone();
+            copy.allowed_locations = inner.allowed_locations.clone();
+        }
+
+        Ok(value)
+    }
+
+    fn independent_copy_value(&self) -> ValueResult {
+        Ok(Value::new(self.independent_copy()?))
+    }
+
     fn as_python_resource(&self) -> Result<PythonResource<'_>, ValueError> {
         Ok(PythonResource::from(
             self.inner("File.as_python_resource()")?.file.clone(),
         ))
     }
+
+    fn capture_policy_state(&mut self, policy: &PythonPackagingPolicy) -> Result<(), ValueError> {
+        self.inner("File.capture_policy_state()")?.allowed_locations =
+            Some(policy_allowed_locations(policy));
+
+        Ok(())
+    }
+
+    fn allowed_locations(&self) -> Result<Option<Vec<AbstractResourceLocation>>, ValueError> {
+        Ok(self
+            .inner("File.allowed_locations()")?
+            .allowed_locations
+            .clone())
+    }
+
+    fn is_locked(&self) -> Result<bool, ValueError> {
+        Ok(self.inner("File.is_locked()")?.locked)
+    }
+
+    fn lock(&mut self) -> Result<(), ValueError> {
+        self.inner("File.lock()")?.locked = true;
+
+        Ok(())
+    }
 }
 
 impl TypedValue for FileValue {
@@ -99,6 +178,24 @@ impl TypedValue for FileValue {
         let inner = self.inner(&format!("File.{}", attribute))?;
 
         let v = match attribute {
+            "size_bucket" => size_bucket_value(
+                &SelectorCandidate::file(&inner.file),
+                &SizeBucketThresholds::default(),
+                "size_bucket",
+            )?,
+            "is_locked" => Value::from(inner.locked),
+            "will_be_included" => {
+                drop(inner);
+                Value::from(self.will_be_included()?)
+            }
+            "context_hash" => {
+                drop(inner);
+                self.add_collection_context_hash()?
+            }
+            "effective_location" => {
+                drop(inner);
+                self.effective_location_value()?
+            }
             "path" => Value::from(inner.file.path_string()),
             "is_executable" => Value::from(inner.file.entry().is_executable()),
             attr => {
@@ -120,14 +217,57 @@ impl TypedValue for FileValue {
     }
 
     fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
-        Ok(match attribute {
-            "path" => true,
-            "is_executable" => true,
-            attr => self.add_collection_context_attrs().contains(&attr),
-        })
+        Ok(self.resource_attrs().contains(&attribute)
+            || self.add_collection_context_attrs().contains(&attribute))
     }
 
     fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
-        self.set_attr_add_collection_context(attribute, value)
+        if self.add_collection_context_attrs().contains(&attribute) {
+            self.set_attr_add_collection_context(attribute, value)
+        } else {
+            Err(unknown_attribute_error(
+                Self::TYPE,
+                attribute,
+                &self.settable_attrs(),
+            ))
+        }
+    }
+}
+
+starlark_module! { file_module =>
+    File.add_collection_context(this) {
+        starlark_add_collection_context::<FileValue>(&this)
+    }
+
+    File.lock(this) {
+        starlark_lock::<FileValue>(&this)
+    }
+
+    File.set_collection_context(this, context) {
+        starlark_set_collection_context::<FileValue>(&this, &context)
+    }
+
+    File.duplicate_to_locations(this, locations) {
+        starlark_duplicate_to_locations::<FileValue>(&this, &locations)
+    }
+
+    File.with_include(this, include: bool) {
+        starlark_with_include::<FileValue>(&this, include)
+    }
+
+    File.plan_entry(this) {
+        starlark_plan_entry::<FileValue>(&this)
+    }
+
+    File.settable_attributes(this) {
+        starlark_settable_attributes::<FileValue>(&this)
+    }
+
+    File.to_canonical_string(this) {
+        starlark_to_canonical_string::<FileValue>(&this)
+    }
+
+    File.as_struct(this) {
+        starlark_as_struct::<FileValue>(&this)
     }
 }
@@ -10,6 +10,7 @@ use {
         python_module_source::PythonModuleSourceValue,
         python_package_distribution_resource::PythonPackageDistributionResourceValue,
         python_package_resource::PythonPackageResourceValue,
+        strict,
     },
     crate::{
         project_building::build_python_executable,
@@ -91,6 +92,8 @@ pub fn file_manifest_add_python_resource(
         .downcast_ref::<PyOxidizerEnvironmentContext>()
         .ok_or(ValueError::IncorrectParameterType)?;
 
+    strict::record_added(resource)?;
+
     match resource.get_type() {
         "PythonModuleSource" => {
             let m = match resource.downcast_ref::<PythonModuleSourceValue>() {
@@ -327,7 +330,7 @@ mod tests {
 
     #[test]
     fn test_add_python_executable() -> Result<()> {
-        let mut env = test_evaluation_context_builder()?.into_context()?;
+        let mut env = real_distribution_evaluation_context_builder()?.into_context()?;
         add_exe(&mut env)?;
 
         let m = FileManifestValue::new_from_args().unwrap();
@@ -342,7 +345,7 @@ mod tests {
     // Python 3.8 not supported on aarch64.
     #[cfg(not(target_arch = "aarch64"))]
     fn test_add_python_executable_38() -> Result<()> {
-        let mut env = test_evaluation_context_builder()?.into_context()?;
+        let mut env = real_distribution_evaluation_context_builder()?.into_context()?;
 
         env.eval("dist = default_python_distribution(python_version='3.8')")?;
         env.eval("exe = dist.to_python_executable('testapp')")?;
@@ -357,7 +360,7 @@ mod tests {
 
     #[test]
     fn test_install() -> Result<()> {
-        let mut env = test_evaluation_context_builder()?.into_context()?;
+        let mut env = real_distribution_evaluation_context_builder()?.into_context()?;
         add_exe(&mut env)?;
 
         let m = FileManifestValue::new_from_args().unwrap();
@@ -8,11 +8,14 @@ The `starlark` module and related sub-modules define the
 define Oxidized Python binaries.
 */
 
+pub mod arguments;
 pub mod env;
 pub mod eval;
 pub mod file;
 pub mod file_resource;
+pub mod named_cache;
 pub mod python_distribution;
+pub mod python_embedded_files;
 pub mod python_embedded_resources;
 pub mod python_executable;
 pub mod python_extension_module;
@@ -22,6 +25,9 @@ pub mod python_package_distribution_resource;
 pub mod python_package_resource;
 pub mod python_packaging_policy;
 pub mod python_resource;
+pub mod resource_selector;
+pub mod strict;
+pub mod structs;
 #[cfg(test)]
 mod testutil;
 pub mod util;
@@ -0,0 +1,306 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Persistent named caches for expensive build steps.
+
+Configuration files can obtain a directory keyed by a name and an arbitrary
+key via `get_named_cache()`. The directory persists across builds in the
+PyOxidizer cache, allowing steps like code generation to be skipped when
+their inputs haven't changed.
+*/
+
+use {
+    crate::{
+        environment::PYOXIDIZER_VERSION,
+        starlark::env::{get_context, PyOxidizerEnvironmentContext},
+    },
+    anyhow::{anyhow, Context, Result},
+    sha2::{Digest, Sha256},
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    std::path::{Path, PathBuf},
+};
+
+/// File marking a named cache directory as fully populated.
+const POPULATED_MARKER: &str = ".pyoxidizer-populated";
+
+/// Resolve the directory holding the entry for `key` in the cache `name`.
+///
+/// The key is salted with the PyOxidizer version so entries written by a
+/// different version are never reused.
+pub fn named_cache_path(root: &Path, name: &str, key: &str) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(PYOXIDIZER_VERSION.as_bytes());
+    hasher.update([0]);
+    hasher.update(key.as_bytes());
+
+    root.join(name).join(hex::encode(hasher.finalize()))
+}
+
+/// Validate the name of a named cache.
+///
+/// Names are used as directory names, so only a conservative set of
+/// characters is allowed.
+pub fn validate_named_cache_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        Err(anyhow!(
+            "invalid named cache name {:?}: must be non-empty, not begin with '.', and only contain ASCII letters, digits, '-', '_', and '.'",
+            name
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Starlark value representing an entry in a named cache.
+#[derive(Clone, Debug)]
+pub struct NamedCacheValue {
+    pub name: String,
+    pub key: String,
+    pub path: PathBuf,
+    /// Whether the entry was populated by a previous run.
+    pub warm: bool,
+}
+
+impl NamedCacheValue {
+    /// Open the entry for `key` in the cache `name` under `root`.
+    ///
+    /// An entry that wasn't marked as populated may be left over from a
+    /// failed run. Its directory is emptied so the caller starts fresh.
+    pub fn open(root: &Path, name: &str, key: &str) -> Result<Self> {
+        validate_named_cache_name(name)?;
+
+        let path = named_cache_path(root, name, key);
+        let warm = path.join(POPULATED_MARKER).exists();
+
+        if !warm {
+            if path.exists() {
+                remove_dir_all::remove_dir_all(&path)
+                    .with_context(|| format!("removing {}", path.display()))?;
+            }
+
+            std::fs::create_dir_all(&path)
+                .with_context(|| format!("creating {}", path.display()))?;
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            key: key.to_string(),
+            path,
+            warm,
+        })
+    }
+
+    /// Mark the entry as populated so future runs see it as warm.
+    pub fn mark_populated(&self) -> Result<()> {
+        let marker = self.path.join(POPULATED_MARKER);
+
+        std::fs::write(&marker, PYOXIDIZER_VERSION.as_bytes())
+            .with_context(|| format!("writing {}", marker.display()))
+    }
+}
+
+impl TypedValue for NamedCacheValue {
+    type Holder = Mutable<NamedCacheValue>;
+    const TYPE: &'static str = "NamedCache";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn to_str(&self) -> String {
+        format!(
+            "NamedCache<name={}, path={}>",
+            self.name,
+            self.path.display()
+        )
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        Ok(match attribute {
+            "name" => Value::from(self.name.as_str()),
+            "key" => Value::from(self.key.as_str()),
+            "path" => Value::from(self.path.display().to_string()),
+            "warm" => Value::from(self.warm),
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::GetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        })
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(matches!(attribute, "name" | "key" | "path" | "warm"))
+    }
+}
+
+// Starlark functions.
+impl NamedCacheValue {
+    /// get_named_cache(name, key)
+    fn get_named_cache(type_values: &TypeValues, name: String, key: String) -> ValueResult {
+        let pyoxidizer_context_value = get_context(type_values)?;
+        let pyoxidizer_context = pyoxidizer_context_value
+            .downcast_ref::<PyOxidizerEnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let cache =
+            Self::open(&pyoxidizer_context.env().named_caches_dir(), &name, &key).map_err(|e| {
+                ValueError::from(RuntimeError {
+                    code: "PYOXIDIZER_NAMED_CACHE",
+                    message: format!("{:?}", e),
+                    label: "get_named_cache()".to_string(),
+                })
+            })?;
+
+        Ok(Value::new(cache))
+    }
+
+    /// NamedCache.mark_populated()
+    fn mark_populated_starlark(&self) -> ValueResult {
+        self.mark_populated().map_err(|e| {
+            ValueError::from(RuntimeError {
+                code: "PYOXIDIZER_NAMED_CACHE",
+                message: format!("{:?}", e),
+                label: "NamedCache.mark_populated()".to_string(),
+            })
+        })?;
+
+        Ok(Value::from(NoneType::None))
+    }
+}
+
+starlark_module! { named_cache_module =>
+    get_named_cache(env env, name: String, key: String) {
+        NamedCacheValue::get_named_cache(env, name, key)
+    }
+
+    NamedCache.mark_populated(this) {
+        let this = this.downcast_ref::<NamedCacheValue>().unwrap();
+        this.mark_populated_starlark()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{starlark::testutil::*, testutil::get_env},
+    };
+
+    #[test]
+    fn test_named_cache_path() {
+        let root = Path::new("/cache");
+
+        let path = named_cache_path(root, "protoc-gen", "abc");
+        assert!(path.starts_with("/cache/protoc-gen"));
+        assert_eq!(path, named_cache_path(root, "protoc-gen", "abc"));
+        assert_ne!(path, named_cache_path(root, "protoc-gen", "abd"));
+
+        // The key is salted with the PyOxidizer version.
+        assert_ne!(
+            path.file_name().unwrap().to_string_lossy(),
+            hex::encode(Sha256::digest(b"abc"))
+        );
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_named_cache_name("protoc-gen_1.0").is_ok());
+        assert!(validate_named_cache_name("").is_err());
+        assert!(validate_named_cache_name(".hidden").is_err());
+        assert!(validate_named_cache_name("../escape").is_err());
+        assert!(validate_named_cache_name("a/b").is_err());
+    }
+
+    #[test]
+    fn test_open_discards_unpopulated() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+
+        let cache = NamedCacheValue::open(temp_dir.path(), "gen", "key")?;
+        assert!(!cache.warm);
+        std::fs::write(cache.path.join("partial"), b"partial")?;
+
+        // Without being marked as populated, content is discarded.
+        let cache = NamedCacheValue::open(temp_dir.path(), "gen", "key")?;
+        assert!(!cache.warm);
+        assert!(!cache.path.join("partial").exists());
+
+        std::fs::write(cache.path.join("complete"), b"complete")?;
+        cache.mark_populated()?;
+
+        let cache = NamedCacheValue::open(temp_dir.path(), "gen", "key")?;
+        assert!(cache.warm);
+        assert!(cache.path.join("complete").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_named_cache() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let name = temp_dir
+            .path()
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let cache_root = get_env()?.named_caches_dir().join(&name);
+
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        // A fake generator that writes output to the cache if it isn't warm.
+        env.eval(&format!(
+            "def generate(key):\n    cache = get_named_cache('{}', key = key)\n    if cache.warm:\n        return False\n    FileContent(filename = 'out.py', content = 'x = 1').write_to_directory(cache.path)\n    cache.mark_populated()\n    return True\n",
+            name
+        ))?;
+
+        let first = env.eval("generate('inputs-v1')");
+        let second = env.eval("generate('inputs-v1')");
+        let changed = env.eval("generate('inputs-v2')");
+        let out_exists = named_cache_path(&get_env()?.named_caches_dir(), &name, "inputs-v1")
+            .join("out.py")
+            .exists();
+
+        remove_dir_all::remove_dir_all(&cache_root)?;
+
+        assert!(first?.to_bool());
+        assert!(!second?.to_bool());
+        assert!(changed?.to_bool());
+        assert!(out_exists);
+
+        let err = env
+            .eval("get_named_cache('../escape', key = 'key')")
+            .unwrap_err()
+            .to_string();
+        assert!(err.as_str().contains("invalid named cache name"), "{}", err);
+
+        Ok(())
+    }
+}
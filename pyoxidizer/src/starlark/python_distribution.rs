@@ -9,12 +9,13 @@ use {
         python_interpreter_config::PythonInterpreterConfigValue,
         python_packaging_policy::PythonPackagingPolicyValue,
         python_resource::{add_context_for_value, python_resource_to_value},
+        strict,
     },
     crate::py_packaging::{
         distribution::BinaryLibpythonLinkMode,
         distribution::{
-            default_distribution_location, DistributionFlavor, PythonDistribution,
-            PythonDistributionLocation,
+            default_distribution_location, download_cache_path, DistributionFlavor,
+            PythonDistribution, PythonDistributionLocation,
         },
     },
     anyhow::{anyhow, Result},
@@ -26,6 +27,7 @@ use {
         environment::TypeValues,
         eval::call_stack::CallStack,
         values::{
+            dict::Dictionary,
             error::{RuntimeError, ValueError, INCORRECT_PARAMETER_TYPE_ERROR_CODE},
             none::NoneType,
             {Mutable, TypedValue, Value, ValueResult},
@@ -35,8 +37,11 @@ use {
             starlark_signature_extraction, starlark_signatures,
         },
     },
-    starlark_dialect_build_targets::{optional_str_arg, optional_type_arg},
-    std::{convert::TryFrom, ops::Deref, sync::Arc},
+    starlark_dialect_build_targets::{
+        arguments::FunctionArgs, check_capability, record_read_path, sandbox::Capability,
+    },
+    std::{collections::BTreeMap, convert::TryFrom, ops::Deref, path::Path, sync::Arc},
+    url::Url,
 };
 
 /// A Starlark Value wrapper for `PythonDistribution` traits.
@@ -71,6 +76,13 @@ impl PythonDistributionValue {
 
             let dest_dir = pyoxidizer_context.python_distributions_path()?;
 
+            if !pyoxidizer_context
+                .distribution_cache
+                .is_resolved(&self.source, Some(&dest_dir))
+            {
+                self.check_source_capability(type_values, label, &dest_dir)?;
+            }
+
             self.distribution = Some(
                 pyoxidizer_context
                     .distribution_cache
@@ -94,6 +106,44 @@ impl PythonDistributionValue {
     }
 }
 
+impl PythonDistributionValue {
+    /// Ensure the sandbox allows obtaining the distribution from its source.
+    fn check_source_capability(
+        &self,
+        type_values: &TypeValues,
+        label: &str,
+        dest_dir: &Path,
+    ) -> Result<(), ValueError> {
+        match &self.source {
+            PythonDistributionLocation::Local { local_path, .. } => check_capability(
+                type_values,
+                label,
+                &Capability::ReadPath(Path::new(local_path)),
+            ),
+            PythonDistributionLocation::Url { url, .. } => {
+                let cache_path = download_cache_path(url, dest_dir);
+
+                // The downloaded archive is an input to the build.
+                if let Ok(path) = &cache_path {
+                    record_read_path(type_values, path)?;
+                }
+
+                // Previously downloaded archives are reused.
+                if matches!(cache_path, Ok(p) if p.exists()) {
+                    return Ok(());
+                }
+
+                let host = Url::parse(url)
+                    .ok()
+                    .and_then(|u| u.host_str().map(|h| h.to_string()))
+                    .unwrap_or_else(|| url.clone());
+
+                check_capability(type_values, label, &Capability::Network(&host))
+            }
+        }
+    }
+}
+
 impl TypedValue for PythonDistributionValue {
     type Holder = Mutable<PythonDistributionValue>;
     const TYPE: &'static str = "PythonDistribution";
@@ -116,8 +166,9 @@ impl PythonDistributionValue {
         build_target: &Value,
         python_version: &Value,
     ) -> ValueResult {
-        let build_target = optional_str_arg("build_target", build_target)?;
-        let python_version = optional_str_arg("python_version", &python_version)?;
+        let args = FunctionArgs::new("default_python_distribution()");
+        let build_target = args.optional_str("build_target", build_target)?;
+        let python_version = args.optional_str("python_version", python_version)?;
 
         let pyoxidizer_context_value = get_context(type_values)?;
         let pyoxidizer_context = pyoxidizer_context_value
@@ -153,8 +204,9 @@ impl PythonDistributionValue {
 
     /// PythonDistribution()
     fn from_args(sha256: String, local_path: &Value, url: &Value, flavor: String) -> ValueResult {
-        optional_str_arg("local_path", local_path)?;
-        optional_str_arg("url", url)?;
+        let args = FunctionArgs::new("PythonDistribution()");
+        args.optional_str("local_path", local_path)?;
+        args.optional_str("url", url)?;
 
         if local_path.get_type() != "NoneType" && url.get_type() != "NoneType" {
             return Err(ValueError::from(RuntimeError {
@@ -226,6 +278,7 @@ impl PythonDistributionValue {
     ///     name,
     ///     packaging_policy=None,
     ///     config=None,
+    ///     libpython_link_mode="auto",
     /// )
     #[allow(clippy::too_many_arguments, clippy::wrong_self_convention)]
     fn to_python_executable_starlark(
@@ -235,15 +288,26 @@ impl PythonDistributionValue {
         name: String,
         packaging_policy: &Value,
         config: &Value,
+        libpython_link_mode: String,
     ) -> ValueResult {
         const LABEL: &str = "PythonDistribution.to_python_executable()";
 
-        optional_type_arg(
+        let libpython_link_mode = BinaryLibpythonLinkMode::try_from(libpython_link_mode.as_str())
+            .map_err(|e| {
+            ValueError::from(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e,
+                label: LABEL.to_string(),
+            })
+        })?;
+
+        let args = FunctionArgs::new(LABEL);
+        args.optional_value(
             "packaging_policy",
             "PythonPackagingPolicy",
             &packaging_policy,
         )?;
-        optional_type_arg("config", "PythonInterpreterConfig", &config)?;
+        args.optional_value("config", "PythonInterpreterConfig", config)?;
 
         let dist = self.resolve_distribution(type_values, "resolve_distribution()")?;
 
@@ -333,8 +397,7 @@ impl PythonDistributionValue {
                 &pyoxidizer_context.build_host_triple,
                 &pyoxidizer_context.build_target_triple,
                 &name,
-                // TODO make configurable
-                BinaryLibpythonLinkMode::Default,
+                libpython_link_mode,
                 policy.inner(LABEL)?.deref(),
                 config.inner(LABEL)?.deref(),
                 host_distribution,
@@ -393,6 +456,80 @@ impl PythonDistributionValue {
         Ok(Value::new(PythonExecutableValue::new(builder, policy)))
     }
 
+    /// PythonDistribution.to_python_interpreter_executable(
+    ///     name,
+    ///     packaging_policy=None,
+    ///     config=None,
+    ///     libpython_link_mode="auto",
+    /// )
+    #[allow(clippy::wrong_self_convention)]
+    fn to_python_interpreter_executable_starlark(
+        &mut self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        name: String,
+        packaging_policy: &Value,
+        config: &Value,
+        libpython_link_mode: String,
+    ) -> ValueResult {
+        FunctionArgs::new("PythonDistribution.to_python_interpreter_executable()").optional_value(
+            "config",
+            "PythonInterpreterConfig",
+            config,
+        )?;
+
+        let config = if config.get_type() == "NoneType" {
+            let dist = self.resolve_distribution(type_values, "resolve_distribution()")?;
+
+            let mut config = dist.create_python_interpreter_config().map_err(|e| {
+                ValueError::from(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: format!("{:?}", e),
+                    label: "to_python_interpreter_executable()".to_string(),
+                })
+            })?;
+            config.configure_as_python_interpreter();
+
+            Value::new(PythonInterpreterConfigValue::new(config))
+        } else {
+            config.clone()
+        };
+
+        self.to_python_executable_starlark(
+            type_values,
+            call_stack,
+            name,
+            packaging_policy,
+            &config,
+            libpython_link_mode,
+        )
+    }
+
+    /// PythonDistribution.ssl_library_variants()
+    pub fn ssl_library_variants_starlark(&mut self, type_values: &TypeValues) -> ValueResult {
+        let dist = self.resolve_distribution(type_values, "resolve_distribution")?;
+
+        let mut variants: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+        for resource in dist.python_resources() {
+            if let PythonResource::ExtensionModule(em) = resource {
+                if em.links_ssl_library() {
+                    variants
+                        .entry(em.name.clone())
+                        .or_default()
+                        .push(em.variant.clone().unwrap_or_else(|| "default".to_string()));
+                }
+            }
+        }
+
+        let mut d = Dictionary::default();
+        for (name, variants) in variants {
+            d.insert(Value::from(name), Value::from(variants))?;
+        }
+
+        Value::try_from(d.get_content().clone())
+    }
+
     pub fn python_resources_starlark(
         &mut self,
         type_values: &TypeValues,
@@ -418,6 +555,8 @@ impl PythonDistributionValue {
             })
             .collect::<Result<Vec<Value>, ValueError>>()?;
 
+        strict::record_enumerated(LABEL, &values)?;
+
         Ok(Value::from(values))
     }
 }
@@ -438,6 +577,11 @@ starlark_module! { python_distribution_module =>
         this.make_python_interpreter_config_starlark(&env)
     }
 
+    PythonDistribution.ssl_library_variants(env env, this) {
+        let mut this = this.downcast_mut::<PythonDistributionValue>().unwrap().unwrap();
+        this.ssl_library_variants_starlark(env)
+    }
+
     PythonDistribution.python_resources(env env, call_stack cs, this) {
         let mut this = this.downcast_mut::<PythonDistributionValue>().unwrap().unwrap();
         this.python_resources_starlark(&env, cs)
@@ -449,7 +593,8 @@ starlark_module! { python_distribution_module =>
         this,
         name: String,
         packaging_policy=NoneType::None,
-        config=NoneType::None
+        config=NoneType::None,
+        libpython_link_mode: String = "auto".to_string()
     ) {
         let mut this = this.downcast_mut::<PythonDistributionValue>().unwrap().unwrap();
         this.to_python_executable_starlark(
@@ -458,6 +603,27 @@ starlark_module! { python_distribution_module =>
             name,
             &packaging_policy,
             &config,
+            libpython_link_mode,
+        )
+    }
+
+    PythonDistribution.to_python_interpreter_executable(
+        env env,
+        call_stack cs,
+        this,
+        name: String,
+        packaging_policy=NoneType::None,
+        config=NoneType::None,
+        libpython_link_mode: String = "auto".to_string()
+    ) {
+        let mut this = this.downcast_mut::<PythonDistributionValue>().unwrap().unwrap();
+        this.to_python_interpreter_executable_starlark(
+            env,
+            cs,
+            name,
+            &packaging_policy,
+            &config,
+            libpython_link_mode,
         )
     }
 
@@ -484,6 +650,7 @@ mod tests {
                 python_module_source::PythonModuleSourceValue,
                 python_package_resource::PythonPackageResourceValue, testutil::*,
             },
+            testing::{use_real_distribution, FIXTURE_STDLIB_MODULES},
         },
     };
 
@@ -616,6 +783,54 @@ mod tests {
         assert_eq!(config.get_type(), "PythonInterpreterConfig");
     }
 
+    #[test]
+    fn test_to_python_interpreter_executable() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.eval("dist = default_python_distribution()")?;
+
+        let exe = env.eval("dist.to_python_interpreter_executable('python')")?;
+        assert_eq!(exe.get_type(), "PythonExecutable");
+
+        env.eval("config = dist.make_python_interpreter_config()")?;
+        let exe = env.eval("dist.to_python_interpreter_executable('python', config = config)")?;
+        assert_eq!(exe.get_type(), "PythonExecutable");
+
+        assert!(env
+            .eval("dist.to_python_interpreter_executable('python', config = 'bad')")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_python_executable_libpython_link_mode() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.eval("dist = default_python_distribution()")?;
+
+        env.eval("exe = dist.to_python_executable('myapp', libpython_link_mode = 'auto')")?;
+        eval_assert(&mut env, "exe.libpython_link_mode in ('static', 'dynamic')")?;
+
+        // The default Windows distribution only supports dynamic linking.
+        if !cfg!(windows) {
+            env.eval("exe = dist.to_python_executable('myapp', libpython_link_mode = 'static')")?;
+            eval_assert(&mut env, "exe.libpython_link_mode == 'static'")?;
+        }
+
+        assert!(env.eval("exe.libpython_link_mode = 'dynamic'").is_err());
+
+        let err = env
+            .eval("dist.to_python_executable('myapp', libpython_link_mode = 'bad')")
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .as_str()
+            .contains("bad is not a valid libpython link mode"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_python_resources() {
         let resources = starlark_ok("default_python_distribution().python_resources()");
@@ -623,7 +838,11 @@ mod tests {
 
         let values = resources.iter().unwrap().to_vec();
 
-        assert!(values.len() > 100);
+        if use_real_distribution() {
+            assert!(values.len() > 100);
+        } else {
+            assert_eq!(values.len(), FIXTURE_STDLIB_MODULES.len() + 2);
+        }
 
         assert!(values
             .iter()
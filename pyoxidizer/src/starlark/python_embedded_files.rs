@@ -0,0 +1,165 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{
+        py_packaging::binary::PythonBinaryBuilder,
+        starlark::env::{get_context, PyOxidizerEnvironmentContext},
+        vendored_project::{verify_vendored_project, write_vendored_project},
+    },
+    anyhow::{anyhow, Context, Result},
+    slog::warn,
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, ValueError},
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{ResolvedTarget, ResolvedTargetValue, RunMode},
+    std::{path::PathBuf, sync::Arc},
+};
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "PYOXIDIZER_PYTHON_EMBEDDED_FILES",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+pub struct PythonEmbeddedFilesValue {
+    pub exe: Arc<dyn PythonBinaryBuilder>,
+
+    /// Directory to write the vendored project to.
+    ///
+    /// Relative paths are relative to the directory of the config file.
+    /// If `None`, the target's build output path is used.
+    pub path: Option<PathBuf>,
+}
+
+impl TypedValue for PythonEmbeddedFilesValue {
+    type Holder = Mutable<PythonEmbeddedFilesValue>;
+    const TYPE: &'static str = "PythonEmbeddedFiles";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+}
+
+impl PythonEmbeddedFilesValue {
+    /// Obtain the `pyoxidizer build` command building `target`.
+    fn build_command(
+        context: &PyOxidizerEnvironmentContext,
+        target: &str,
+        verify_vendored: bool,
+    ) -> String {
+        let mut args = vec!["pyoxidizer".to_string(), "build".to_string()];
+
+        if context.build_target_triple != context.build_host_triple {
+            args.push("--target-triple".to_string());
+            args.push(context.build_target_triple.clone());
+        }
+        if context.build_release {
+            args.push("--release".to_string());
+        }
+        if verify_vendored {
+            args.push("--verify-vendored".to_string());
+        }
+        args.push(target.to_string());
+
+        args.join(" ")
+    }
+
+    fn build(
+        &self,
+        type_values: &TypeValues,
+        target: &str,
+        context: &PyOxidizerEnvironmentContext,
+    ) -> Result<ResolvedTarget> {
+        let output_path = match &self.path {
+            Some(path) => context.cwd.join(path),
+            None => context
+                .get_output_path(type_values, target)
+                .map_err(|_| anyhow!("unable to resolve output path"))?,
+        };
+
+        let regenerate_command = Self::build_command(context, target, false);
+        let verify_command = Self::build_command(context, target, true);
+
+        if context.verify_vendored {
+            warn!(
+                context.logger(),
+                "verifying vendored project in {}",
+                output_path.display()
+            );
+
+            verify_vendored_project(
+                context.env(),
+                context.logger(),
+                self.exe.as_ref(),
+                &context.build_opt_level,
+                &regenerate_command,
+                &verify_command,
+                &output_path,
+            )?;
+        } else {
+            warn!(
+                context.logger(),
+                "writing vendored project to {}",
+                output_path.display()
+            );
+
+            std::fs::create_dir_all(&output_path)
+                .with_context(|| format!("creating output directory: {}", output_path.display()))?;
+            write_vendored_project(
+                context.env(),
+                context.logger(),
+                self.exe.as_ref(),
+                &context.build_opt_level,
+                &regenerate_command,
+                &verify_command,
+                &output_path,
+            )?;
+        }
+
+        Ok(ResolvedTarget {
+            run_mode: RunMode::None,
+            output_path,
+        })
+    }
+
+    fn build_starlark(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        let pyoxidizer_context_value = get_context(type_values)?;
+        let pyoxidizer_context = pyoxidizer_context_value
+            .downcast_ref::<PyOxidizerEnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let inner = error_context("PythonEmbeddedFiles.build()", || {
+            self.build(type_values, &target, &pyoxidizer_context)
+        })?;
+
+        Ok(Value::new(ResolvedTargetValue { inner }))
+    }
+}
+
+starlark_module! { python_embedded_files_module =>
+    PythonEmbeddedFiles.build(
+        env env,
+        this,
+        target: String
+    ) {
+        let this = this.downcast_ref::<PythonEmbeddedFilesValue>().unwrap();
+        this.build_starlark(env, target)
+    }
+}
@@ -4,30 +4,53 @@
 
 use {
     super::{
+        arguments::ResourceArgs,
         env::{get_context, PyOxidizerEnvironmentContext},
         file::FileValue,
         file_resource::file_manifest_add_python_executable,
+        python_embedded_files::PythonEmbeddedFilesValue,
         python_embedded_resources::PythonEmbeddedResourcesValue,
         python_extension_module::PythonExtensionModuleValue,
         python_module_source::PythonModuleSourceValue,
         python_package_distribution_resource::PythonPackageDistributionResourceValue,
         python_package_resource::PythonPackageResourceValue,
         python_packaging_policy::PythonPackagingPolicyValue,
-        python_resource::{is_resource_starlark_compatible, python_resource_to_value},
+        python_resource::{
+            add_context_for_value, is_resource_starlark_compatible, python_resource_to_value,
+            OptionalResourceLocation,
+        },
+        resource_selector::{
+            collected_candidates, CollectedResource, ResourceChanges, ResourceSelectorValue,
+            ResourceSelectors, SelectorCandidate,
+        },
+        strict::{self, unknown_attribute_error},
     },
     crate::{
         project_building::build_python_executable,
         py_packaging::binary::PythonBinaryBuilder,
-        py_packaging::binary::{PackedResourcesLoadMode, WindowsRuntimeDllsMode},
+        py_packaging::binary::{
+            PackedResourcesLoadMode, ResourcesEncryption, WindowsRuntimeDllsMode,
+        },
+        py_packaging::packaging_tool::pip_install_requirements,
+        py_packaging::sbom::{sbom_creation_time, SbomFormat},
     },
     anyhow::{anyhow, Context, Result},
     linked_hash_map::LinkedHashMap,
-    python_packaging::resource::PythonModuleSource,
+    python_packaging::{
+        install_report::InstallReport,
+        location::ConcreteResourceLocation,
+        resource::{
+            BytecodeOptimizationLevel, PythonModuleBytecode, PythonModuleSource, PythonResource,
+        },
+        resource_collection::PythonResourceAddCollectionContext,
+    },
+    python_packed_resources::encryption::{EncryptionScheme, KEY_LENGTH},
     slog::{info, warn},
     starlark::{
         environment::TypeValues,
         eval::call_stack::CallStack,
         values::{
+            dict::Dictionary,
             error::{
                 RuntimeError, UnsupportedOperation, ValueError, INCORRECT_PARAMETER_TYPE_ERROR_CODE,
             },
@@ -40,11 +63,12 @@ use {
         },
     },
     starlark_dialect_build_targets::{
-        optional_dict_arg, optional_list_arg, optional_type_arg, required_list_arg, ResolvedTarget,
-        ResolvedTargetValue, RunMode, ToOptional,
+        arguments::FunctionArgs,
+        check_capability,
+        sandbox::{glob_base, Capability},
+        ResolvedTarget, ResolvedTargetValue, RunMode, ToOptional,
     },
     std::{
-        collections::HashMap,
         convert::TryFrom,
         io::Write,
         ops::Deref,
@@ -53,12 +77,13 @@ use {
     },
     tugger::starlark::{
         code_signing::{handle_signable_event, SigningAction, SigningContext},
+        file_content::FileContentWrapper,
         file_manifest::FileManifestValue,
         wix_bundle_builder::WiXBundleBuilderValue,
         wix_msi_builder::WiXMsiBuilderValue,
     },
     tugger_code_signing::SigningDestination,
-    tugger_file_manifest::FileData,
+    tugger_file_manifest::{FileData, FileEntry},
 };
 
 fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
@@ -75,11 +100,15 @@ where
 }
 
 pub fn build_internal(
-    exe: MutexGuard<Box<dyn PythonBinaryBuilder>>,
+    mut exe: MutexGuard<Box<dyn PythonBinaryBuilder>>,
     type_values: &TypeValues,
     target: &str,
     context: &PyOxidizerEnvironmentContext,
 ) -> Result<(ResolvedTarget, PathBuf)> {
+    if context.build_trace_startup {
+        exe.set_trace_startup(true);
+    }
+
     // Build an executable by writing out a temporary Rust project
     // and building it.
     let build = build_python_executable(
@@ -108,11 +137,23 @@ pub fn build_internal(
         .context(format!("writing {}", dest_path.display()))?;
     tugger_file_manifest::set_executable(&mut fh).context("making binary executable")?;
 
+    // WebAssembly modules are executed by a runtime.
+    let run_mode = if crate::environment::WASI_TARGET_TRIPLES
+        .contains(&context.build_target_triple.as_str())
+    {
+        RunMode::Runtime {
+            runtime: context.wasm_runtime.clone(),
+            path: dest_path.clone(),
+        }
+    } else {
+        RunMode::Path {
+            path: dest_path.clone(),
+        }
+    };
+
     Ok((
         ResolvedTarget {
-            run_mode: RunMode::Path {
-                path: dest_path.clone(),
-            },
+            run_mode,
             output_path,
         },
         dest_path,
@@ -130,6 +171,12 @@ pub struct PythonExecutableValue {
     // values_for_descendant_check_and_freeze() without the borrow checker
     // complaining due to a temporary vec/array.
     policy: Vec<Value>,
+
+    /// Resource selectors registered via `apply()`.
+    resource_selectors: ResourceSelectors,
+
+    /// Attribution of distributions installed by the last `pip_install()`.
+    last_install_report: Option<InstallReport>,
 }
 
 impl PythonExecutableValue {
@@ -137,6 +184,8 @@ impl PythonExecutableValue {
         Self {
             exe: Arc::new(Mutex::new(exe)),
             policy: vec![Value::new(policy)],
+            resource_selectors: ResourceSelectors::default(),
+            last_install_report: None,
         }
     }
 
@@ -160,8 +209,83 @@ impl PythonExecutableValue {
             .unwrap()
             .clone()
     }
+
+    /// Describe why the distribution providing a package was installed.
+    ///
+    /// Only distributions installed by the last `pip_install()` are known.
+    fn install_attribution(&self, package: Option<&str>) -> Option<String> {
+        let report = self.last_install_report.as_ref()?;
+
+        report.describe(report.distribution_for_package(package?)?)
+    }
+
+    /// Resolve the add collection context for a resource being added.
+    ///
+    /// Registered resource selectors matching the resource are applied unless
+    /// the resource's add attributes are locked.
+    fn selected_add_context(
+        &mut self,
+        label: &str,
+        candidate: &SelectorCandidate,
+        resource: &PythonResource,
+        add_context: Option<PythonResourceAddCollectionContext>,
+        locked: bool,
+    ) -> Result<Option<PythonResourceAddCollectionContext>, ValueError> {
+        if locked
+            || error_context(label, || self.resource_selectors.matching(candidate))?.is_empty()
+        {
+            return Ok(add_context);
+        }
+
+        let mut add_context = match add_context {
+            Some(add_context) => add_context,
+            None => self
+                .inner(label)?
+                .python_packaging_policy()
+                .derive_add_collection_context(resource),
+        };
+
+        error_context(label, || {
+            self.resource_selectors
+                .apply_to_added(candidate, &mut add_context)
+        })?;
+
+        Ok(Some(add_context))
+    }
+
+    /// Whether a resource is dropped because it requires a disabled feature.
+    ///
+    /// This is checked when adding because not every resource type honors
+    /// the `include` flag the packaging policy sets for these resources.
+    fn feature_excluded(
+        &self,
+        context: &PyOxidizerEnvironmentContext,
+        resource: &PythonResource,
+    ) -> bool {
+        if self.python_packaging_policy().is_feature_excluded(resource) {
+            info!(
+                context.logger(),
+                "not adding {} because a required feature is disabled",
+                resource.full_name()
+            );
+            true
+        } else {
+            false
+        }
+    }
 }
 
+/// Attributes of `PythonExecutable`.
+const ATTRIBUTES: &[&str] = &[
+    "include_stdlib",
+    "libpython_link_mode",
+    "packed_resources_load_mode",
+    "required_stdlib_resources",
+    "tcl_files_path",
+    "windows_runtime_dlls_mode",
+    "windows_subsystem",
+];
+
 impl TypedValue for PythonExecutableValue {
     type Holder = Mutable<PythonExecutableValue>;
     const TYPE: &'static str = "PythonExecutable";
@@ -176,9 +300,12 @@ impl TypedValue for PythonExecutableValue {
         let exe = self.inner(&format!("PythonExecutable.{}", attribute))?;
 
         match attribute {
+            "include_stdlib" => Ok(Value::from(exe.include_stdlib())),
+            "libpython_link_mode" => Ok(Value::from(exe.libpython_link_mode().to_string())),
             "packed_resources_load_mode" => {
                 Ok(Value::from(exe.packed_resources_load_mode().to_string()))
             }
+            "required_stdlib_resources" => Ok(Value::from(exe.required_stdlib_resources())),
             "tcl_files_path" => match exe.tcl_files_path() {
                 Some(value) => Ok(Value::from(value.to_string())),
                 None => Ok(Value::from(NoneType::None)),
@@ -196,19 +323,24 @@ impl TypedValue for PythonExecutableValue {
     }
 
     fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
-        Ok(matches!(
-            attribute,
-            "packed_resources_load_mode"
-                | "tcl_files_path"
-                | "windows_runtime_dlls_mode"
-                | "windows_subsystem"
-        ))
+        Ok(ATTRIBUTES.contains(&attribute))
     }
 
     fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
         let mut exe = self.inner(&format!("PythonExecutable.{}", attribute))?;
 
         match attribute {
+            "include_stdlib" => {
+                exe.set_include_stdlib(value.to_bool()).map_err(|e| {
+                    ValueError::from(RuntimeError {
+                        code: "PYOXIDIZER_BUILD",
+                        message: format!("{:?}", e),
+                        label: format!("{}.{}", Self::TYPE, attribute),
+                    })
+                })?;
+
+                Ok(())
+            }
             "packed_resources_load_mode" => {
                 exe.set_packed_resources_load_mode(
                     PackedResourcesLoadMode::try_from(value.to_string().as_str()).map_err(|e| {
@@ -252,11 +384,7 @@ impl TypedValue for PythonExecutableValue {
 
                 Ok(())
             }
-            _ => Err(ValueError::OperationNotSupported {
-                op: UnsupportedOperation::SetAttr(attribute.to_string()),
-                left: Self::TYPE.to_string(),
-                right: None,
-            }),
+            _ => Err(unknown_attribute_error(Self::TYPE, attribute, ATTRIBUTES)),
         }
     }
 }
@@ -344,9 +472,8 @@ impl PythonExecutableValue {
     ) -> ValueResult {
         const LABEL: &str = "PythonExecutable.pip_download()";
 
-        required_list_arg("args", "string", &args)?;
-
-        let args: Vec<String> = args.iter()?.iter().map(|x| x.to_string()).collect();
+        let args = FunctionArgs::new(LABEL).list_of_str("args", args)?;
+        check_capability(type_values, LABEL, &Capability::Subprocess)?;
 
         let pyoxidizer_context_value = get_context(type_values)?;
         let pyoxidizer_context = pyoxidizer_context_value
@@ -379,6 +506,8 @@ impl PythonExecutableValue {
             })
             .collect::<Result<Vec<Value>, ValueError>>()?;
 
+        strict::record_enumerated(LABEL, &resources)?;
+
         Ok(Value::from(resources))
     }
 
@@ -392,24 +521,12 @@ impl PythonExecutableValue {
     ) -> ValueResult {
         const LABEL: &str = "PythonExecutable.pip_install()";
 
-        required_list_arg("args", "string", &args)?;
-        optional_dict_arg("extra_envs", "string", "string", &extra_envs)?;
-
-        let args: Vec<String> = args.iter()?.iter().map(|x| x.to_string()).collect();
-
-        let extra_envs = match extra_envs.get_type() {
-            "dict" => extra_envs
-                .iter()?
-                .iter()
-                .map(|key| {
-                    let k = key.to_string();
-                    let v = extra_envs.at(key).unwrap().to_string();
-                    (k, v)
-                })
-                .collect(),
-            "NoneType" => HashMap::new(),
-            _ => panic!("should have validated type above"),
-        };
+        let arguments = FunctionArgs::new(LABEL);
+        let args = arguments.list_of_str("args", args)?;
+        let extra_envs = arguments
+            .optional_dict_of_str_str("extra_envs", extra_envs)?
+            .unwrap_or_default();
+        check_capability(type_values, LABEL, &Capability::Subprocess)?;
 
         let pyoxidizer_context_value = get_context(type_values)?;
         let pyoxidizer_context = pyoxidizer_context_value
@@ -429,6 +546,10 @@ impl PythonExecutableValue {
             )
         })?;
 
+        let report = error_context(LABEL, || {
+            InstallReport::from_resources(&pip_install_requirements(&args), &resources)
+        })?;
+
         let resources = resources
             .iter()
             .filter(|r| is_resource_starlark_compatible(r))
@@ -443,6 +564,11 @@ impl PythonExecutableValue {
             })
             .collect::<Result<Vec<Value>, ValueError>>()?;
 
+        drop(exe);
+        self.last_install_report = Some(report);
+
+        strict::record_enumerated(LABEL, &resources)?;
+
         Ok(Value::from(resources))
     }
 
@@ -456,13 +582,9 @@ impl PythonExecutableValue {
     ) -> ValueResult {
         const LABEL: &str = "PythonExecutable.read_package_root()";
 
-        required_list_arg("packages", "string", &packages)?;
+        let packages = FunctionArgs::new(LABEL).list_of_str("packages", packages)?;
 
-        let packages = packages
-            .iter()?
-            .iter()
-            .map(|x| x.to_string())
-            .collect::<Vec<String>>();
+        check_capability(type_values, LABEL, &Capability::ReadPath(Path::new(&path)))?;
 
         let pyoxidizer_context_value = get_context(type_values)?;
         let pyoxidizer_context = pyoxidizer_context_value
@@ -491,6 +613,8 @@ impl PythonExecutableValue {
             })
             .collect::<Result<Vec<Value>, ValueError>>()?;
 
+        strict::record_enumerated(LABEL, &resources)?;
+
         Ok(Value::from(resources))
     }
 
@@ -503,6 +627,8 @@ impl PythonExecutableValue {
     ) -> ValueResult {
         const LABEL: &str = "PythonExecutable.read_virtualenv()";
 
+        check_capability(type_values, LABEL, &Capability::ReadPath(Path::new(&path)))?;
+
         let pyoxidizer_context_value = get_context(type_values)?;
         let pyoxidizer_context = pyoxidizer_context_value
             .downcast_ref::<PyOxidizerEnvironmentContext>()
@@ -530,6 +656,8 @@ impl PythonExecutableValue {
             })
             .collect::<Result<Vec<Value>, ValueError>>()?;
 
+        strict::record_enumerated(LABEL, &resources)?;
+
         Ok(Value::from(resources))
     }
 
@@ -544,31 +672,13 @@ impl PythonExecutableValue {
     ) -> ValueResult {
         const LABEL: &str = "PythonExecutable.setup_py_install()";
 
-        optional_dict_arg("extra_envs", "string", "string", &extra_envs)?;
-        optional_list_arg("extra_global_arguments", "string", &extra_global_arguments)?;
-
-        let extra_envs = match extra_envs.get_type() {
-            "dict" => extra_envs
-                .iter()?
-                .iter()
-                .map(|key| {
-                    let k = key.to_string();
-                    let v = extra_envs.at(key).unwrap().to_string();
-                    (k, v)
-                })
-                .collect(),
-            "NoneType" => HashMap::new(),
-            _ => panic!("should have validated type above"),
-        };
-        let extra_global_arguments = match extra_global_arguments.get_type() {
-            "list" => extra_global_arguments
-                .iter()?
-                .iter()
-                .map(|x| x.to_string())
-                .collect(),
-            "NoneType" => Vec::new(),
-            _ => panic!("should have validated type above"),
-        };
+        let args = FunctionArgs::new(LABEL);
+        let extra_envs = args
+            .optional_dict_of_str_str("extra_envs", extra_envs)?
+            .unwrap_or_default();
+        let extra_global_arguments = args
+            .optional_list_of_str("extra_global_arguments", extra_global_arguments)?
+            .unwrap_or_default();
 
         let package_path = PathBuf::from(package_path);
 
@@ -583,6 +693,9 @@ impl PythonExecutableValue {
             PathBuf::from(&pyoxidizer_context.cwd).join(package_path)
         };
 
+        check_capability(type_values, LABEL, &Capability::ReadPath(&package_path))?;
+        check_capability(type_values, LABEL, &Capability::Subprocess)?;
+
         let python_packaging_policy = self.python_packaging_policy();
 
         let mut exe = self.inner(LABEL)?;
@@ -617,6 +730,8 @@ impl PythonExecutableValue {
             resources.len()
         );
 
+        strict::record_enumerated(LABEL, &resources)?;
+
         Ok(Value::from(resources))
     }
 
@@ -628,16 +743,95 @@ impl PythonExecutableValue {
     ) -> ValueResult {
         let inner = module.inner(label)?;
 
+        if self.feature_excluded(context, &(&inner.m).into()) {
+            return Ok(Value::new(NoneType::None));
+        }
+
         info!(
             context.logger(),
             "adding Python source module {}", inner.m.name;
         );
 
+        let add_context = self.selected_add_context(
+            label,
+            &SelectorCandidate::module_source(&inner.m),
+            &(&inner.m).into(),
+            inner.add_context.clone(),
+            inner.locked,
+        )?;
+
+        // Materialize the source now so the builder never reads through a
+        // symlink it was told not to follow.
+        let m = if inner.follow_symlinks {
+            inner.m.clone()
+        } else {
+            let mut m = inner.m.clone();
+            m.source = FileData::Memory(error_context(label, || {
+                inner
+                    .resolve_source()
+                    .with_context(|| format!("resolving source of {}", module.to_repr()))
+            })?);
+            m
+        };
+
         let mut exe = self.inner(label)?;
 
+        if inner.bytecode.is_empty() {
+            error_context(label, || {
+                exe.add_python_module_source(&m, add_context)
+                    .with_context(|| format!("adding {}", module.to_repr()))
+            })?;
+
+            return Ok(Value::new(NoneType::None));
+        }
+
+        // Provided bytecode replaces bytecode compiled from the source.
+        let add_context = add_context.unwrap_or_else(|| {
+            exe.python_packaging_policy()
+                .derive_add_collection_context(&(&m).into())
+        });
+
+        let mut source_context = add_context.clone();
+        for level in inner.bytecode.keys() {
+            match level {
+                0 => source_context.optimize_level_zero = false,
+                1 => source_context.optimize_level_one = false,
+                _ => source_context.optimize_level_two = false,
+            }
+        }
+
         error_context(label, || {
-            exe.add_python_module_source(&inner.m, inner.add_context.clone())
-                .with_context(|| format!("adding {}", module.to_repr()))
+            exe.add_python_module_source(&m, Some(source_context))
+                .with_context(|| format!("adding {}", module.to_repr()))?;
+
+            for (level, data) in &inner.bytecode {
+                let optimize_level =
+                    BytecodeOptimizationLevel::try_from(*level as i32).map_err(|e| anyhow!(e))?;
+
+                let mut bytecode = PythonModuleBytecode::new(
+                    &m.name,
+                    optimize_level,
+                    m.is_package,
+                    &m.cache_tag,
+                    &data[16..],
+                );
+                bytecode.is_stdlib = m.is_stdlib;
+                bytecode.is_test = m.is_test;
+
+                let mut bytecode_context = add_context.clone();
+                match optimize_level {
+                    BytecodeOptimizationLevel::Zero => bytecode_context.optimize_level_zero = true,
+                    BytecodeOptimizationLevel::One => bytecode_context.optimize_level_one = true,
+                    BytecodeOptimizationLevel::Two => bytecode_context.optimize_level_two = true,
+                }
+
+                exe.add_python_module_bytecode(&bytecode, Some(bytecode_context))
+                    .with_context(|| {
+                        format!("adding level {} bytecode of {}", level, module.to_repr())
+                    })?;
+            }
+
+            Ok(())
         })?;
 
         Ok(Value::new(NoneType::None))
@@ -649,7 +843,11 @@ impl PythonExecutableValue {
         label: &str,
         resource: &PythonPackageResourceValue,
     ) -> ValueResult {
-        let inner = resource.inner(label)?;
+        let mut inner = resource.inner(label)?;
+
+        if self.feature_excluded(context, &(&inner.r).into()) {
+            return Ok(Value::new(NoneType::None));
+        }
 
         info!(
             context.logger(),
@@ -657,13 +855,23 @@ impl PythonExecutableValue {
             inner.r.symbolic_name()
         );
 
+        let add_context = self.selected_add_context(
+            label,
+            &SelectorCandidate::package_resource(&inner.r),
+            &(&inner.r).into(),
+            inner.add_context.clone(),
+            inner.locked,
+        )?;
+
         let mut exe = self.inner(label)?;
 
         error_context(label, || {
-            exe.add_python_package_resource(&inner.r, inner.add_context.clone())
+            exe.add_python_package_resource(&inner.r, add_context)
                 .with_context(|| format!("adding {}", resource.to_repr()))
         })?;
 
+        inner.added = true;
+
         Ok(Value::new(NoneType::None))
     }
 
@@ -675,15 +883,27 @@ impl PythonExecutableValue {
     ) -> ValueResult {
         let inner = resource.inner(label)?;
 
+        if self.feature_excluded(context, &(&inner.r).into()) {
+            return Ok(Value::new(NoneType::None));
+        }
+
         info!(
             context.logger(),
             "adding package distribution resource {}:{}", inner.r.package, inner.r.name
         );
 
+        let add_context = self.selected_add_context(
+            label,
+            &SelectorCandidate::package_distribution_resource(&inner.r),
+            &(&inner.r).into(),
+            inner.add_context.clone(),
+            inner.locked,
+        )?;
+
         let mut exe = self.inner(label)?;
 
         error_context(label, || {
-            exe.add_python_package_distribution_resource(&inner.r, inner.add_context.clone())
+            exe.add_python_package_distribution_resource(&inner.r, add_context)
                 .with_context(|| format!("adding {}", resource.to_repr()))
         })?;
 
@@ -698,15 +918,27 @@ impl PythonExecutableValue {
     ) -> ValueResult {
         let inner = module.inner(label)?;
 
+        if self.feature_excluded(context, &(&inner.em).into()) {
+            return Ok(Value::new(NoneType::None));
+        }
+
         info!(
             context.logger(),
             "adding extension module {}", inner.em.name
         );
 
+        let add_context = self.selected_add_context(
+            label,
+            &SelectorCandidate::extension_module(&inner.em),
+            &(&inner.em).into(),
+            inner.add_context.clone(),
+            inner.locked,
+        )?;
+
         let mut exe = self.inner(label)?;
 
         error_context(label, || {
-            exe.add_python_extension_module(&inner.em, inner.add_context.clone())
+            exe.add_python_extension_module(&inner.em, add_context)
                 .with_context(|| format!("adding {}", module.to_repr()))
         })?;
 
@@ -727,10 +959,18 @@ impl PythonExecutableValue {
             inner.file.path().display()
         );
 
+        let add_context = self.selected_add_context(
+            label,
+            &SelectorCandidate::file(&inner.file),
+            &(&inner.file).into(),
+            inner.add_context.clone(),
+            inner.locked,
+        )?;
+
         let mut exe = self.inner(label)?;
 
         error_context(label, || {
-            exe.add_file_data(&inner.file, inner.add_context.clone())
+            exe.add_file_data(&inner.file, add_context)
                 .with_context(|| format!("adding {}", file.to_repr()))
         })?;
 
@@ -749,6 +989,8 @@ impl PythonExecutableValue {
             .downcast_ref::<PyOxidizerEnvironmentContext>()
             .ok_or(ValueError::IncorrectParameterType)?;
 
+        strict::record_added(resource)?;
+
         match resource.get_type() {
             FileValue::TYPE => {
                 let file = resource.downcast_ref::<FileValue>().unwrap();
@@ -780,11 +1022,7 @@ impl PythonExecutableValue {
                     .unwrap();
                 self.add_python_extension_module(pyoxidizer_context.deref(), label, module.deref())
             }
-            _ => Err(ValueError::from(RuntimeError {
-                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
-                message: "resource argument must be a Python resource type".to_string(),
-                label: ".add_python_resource()".to_string(),
-            })),
+            t => Err(FunctionArgs::new(label).type_error("resource", "a Python resource", t)),
         }
     }
 
@@ -794,8 +1032,55 @@ impl PythonExecutableValue {
         type_values: &TypeValues,
         resources: &Value,
     ) -> ValueResult {
+        const LABEL: &str = "PythonExecutable.add_python_resources()";
+
+        for resource in FunctionArgs::new(LABEL).resource_list("resources", resources)? {
+            self.add_python_resource(type_values, &resource, LABEL)?;
+        }
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    /// PythonExecutable.add_distribution(group, location=None)
+    ///
+    /// `group` is a value of the dict returned by
+    /// `group_resources_by_distribution()`. If `location` is set, it is
+    /// applied to the `add_location` of every resource before adding.
+    pub fn add_distribution(
+        &mut self,
+        type_values: &TypeValues,
+        group: &Value,
+        location: &Value,
+    ) -> ValueResult {
+        const LABEL: &str = "add_distribution()";
+
+        if !group.has_attr("resources")? {
+            return Err(ValueError::from(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!(
+                    "group must be a value returned by group_resources_by_distribution(); got {}",
+                    group.get_type()
+                ),
+                label: LABEL.to_string(),
+            }));
+        }
+        let resources = group.get_attr("resources")?;
+
+        // Validate the location up front so no resource is modified on error.
+        let location_set =
+            Option::<ConcreteResourceLocation>::from(OptionalResourceLocation::try_from(location)?)
+                .is_some();
+
+        if location_set {
+            for mut resource in &resources.iter()? {
+                if add_context_for_value(&resource, LABEL)?.is_some() {
+                    resource.set_attr("add_location", location.clone())?;
+                }
+            }
+        }
+
         for resource in &resources.iter()? {
-            self.add_python_resource(type_values, &resource, "add_python_resources()")?;
+            self.add_python_resource(type_values, &resource, LABEL)?;
         }
 
         Ok(Value::new(NoneType::None))
@@ -810,6 +1095,20 @@ impl PythonExecutableValue {
         }))
     }
 
+    /// PythonExecutable.to_embedded_python_files(path=None)
+    pub fn to_embedded_python_files(&self, path: &Value) -> ValueResult {
+        const LABEL: &str = "PythonExecutable.to_embedded_python_files()";
+
+        let path = FunctionArgs::new(LABEL)
+            .optional_str("path", path)?
+            .map(PathBuf::from);
+
+        Ok(Value::new(PythonEmbeddedFilesValue {
+            exe: self.inner(LABEL)?.clone_trait(),
+            path,
+        }))
+    }
+
     /// PythonExecutable.to_file_manifest(prefix)
     pub fn to_file_manifest(&self, type_values: &TypeValues, prefix: String) -> ValueResult {
         const LABEL: &str = "PythonExecutable.to_file_manifest()";
@@ -844,6 +1143,40 @@ impl PythonExecutableValue {
         Ok(manifest_value.clone())
     }
 
+    /// PythonExecutable.to_sbom(format="spdx-json")
+    pub fn to_sbom(&self, type_values: &TypeValues, format: String) -> ValueResult {
+        const LABEL: &str = "PythonExecutable.to_sbom()";
+
+        let format = SbomFormat::try_from(format.as_str()).map_err(|e| {
+            ValueError::from(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e,
+                label: LABEL.to_string(),
+            })
+        })?;
+
+        let pyoxidizer_context_value = get_context(type_values)?;
+        let pyoxidizer_context = pyoxidizer_context_value
+            .downcast_ref::<PyOxidizerEnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let exe = self.inner(LABEL)?;
+
+        let data = error_context(LABEL, || {
+            let mut sbom = exe
+                .software_bill_of_materials()
+                .context("resolving software bill of materials")?;
+            sbom.features = pyoxidizer_context.features.clone();
+
+            sbom.to_json(format, &sbom_creation_time())
+        })?;
+
+        Ok(Value::from(FileContentWrapper {
+            content: FileEntry::new_from_data(data, false),
+            filename: format!("{}{}", exe.name(), format.file_suffix()),
+        }))
+    }
+
     /// PythonExecutable.to_wix_bundle_builder(id_prefix, name, version, manufacturer, msi_builder_callback)
     #[allow(clippy::too_many_arguments)]
     pub fn to_wix_bundle_builder(
@@ -858,7 +1191,8 @@ impl PythonExecutableValue {
     ) -> ValueResult {
         const LABEL: &str = "PythonExecutable.to_wix_bundle_builder()";
 
-        optional_type_arg("msi_builder_callback", "function", &msi_builder_callback)?;
+        FunctionArgs::new(LABEL)
+            .optional_function("msi_builder_callback", &msi_builder_callback)?;
 
         let msi_builder_value = self.to_wix_msi_builder(
             type_values,
@@ -895,16 +1229,15 @@ impl PythonExecutableValue {
             .unwrap()
             .unwrap();
 
-        // Add the VC++ Redistributable for the target platform.
-        match self.inner(LABEL)?.target_triple() {
-            "i686-pc-windows-msvc" => {
-                bundle_builder.add_vc_redistributable(type_values, "x86".to_string())?;
-            }
-            "x86_64-pc-windows-msvc" => {
-                bundle_builder.add_vc_redistributable(type_values, "x64".to_string())?;
+        // Add the VC++ Redistributable if the binary requires the dynamic CRT
+        // and the CRT DLLs aren't unconditionally installed next to the binary.
+        let exe = self.inner(LABEL)?;
+        if let Some((_, platform)) = exe.vc_runtime_requirements() {
+            if exe.windows_runtime_dlls_mode() != &WindowsRuntimeDllsMode::Always {
+                bundle_builder.add_vc_redistributable(type_values, platform.to_string())?;
             }
-            _ => {}
         }
+        drop(exe);
 
         bundle_builder.add_wix_msi_builder(
             msi_builder.deref().clone(),
@@ -944,6 +1277,319 @@ impl PythonExecutableValue {
         Ok(builder_value.clone())
     }
 
+    /// PythonExecutable.encrypt_resources(recipient_pubkey, scheme="xchacha20poly1305")
+    pub fn encrypt_resources(&mut self, recipient_pubkey: String, scheme: String) -> ValueResult {
+        const LABEL: &str = "PythonExecutable.encrypt_resources()";
+
+        let scheme = EncryptionScheme::from_name(&scheme).ok_or_else(|| {
+            ValueError::from(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!("unsupported encryption scheme: {}", scheme),
+                label: LABEL.to_string(),
+            })
+        })?;
+
+        let recipient_public_key = hex::decode(&recipient_pubkey)
+            .ok()
+            .filter(|key| key.len() == KEY_LENGTH)
+            .ok_or_else(|| {
+                ValueError::from(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: format!(
+                        "recipient_pubkey must be a {} character hex encoded X25519 public key",
+                        KEY_LENGTH * 2
+                    ),
+                    label: LABEL.to_string(),
+                })
+            })?;
+
+        let mut exe = self.inner(LABEL)?;
+        exe.set_resources_encryption(Some(ResourcesEncryption {
+            scheme,
+            recipient_public_key,
+        }));
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    /// PythonExecutable.add_module_alias(alias, target)
+    pub fn add_module_alias(&mut self, alias: String, target: String) -> ValueResult {
+        const LABEL: &str = "PythonExecutable.add_module_alias()";
+
+        let mut exe = self.inner(LABEL)?;
+        error_context(LABEL, || {
+            exe.resources_collector_mut()
+                .add_module_alias(&alias, &target)
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    /// PythonExecutable.apply(selector, include=None, location=None, include_source=None)
+    pub fn apply(
+        &mut self,
+        type_values: &TypeValues,
+        selector: &Value,
+        include: &Value,
+        location: &Value,
+        include_source: &Value,
+    ) -> ValueResult {
+        const LABEL: &str = "PythonExecutable.apply()";
+
+        let args = FunctionArgs::new(LABEL);
+        args.required_value("selector", ResourceSelectorValue::TYPE, selector)?;
+        let include = args.optional_bool("include", include)?;
+        let include_source = args.optional_bool("include_source", include_source)?;
+        let location = match args.optional_str("location", location)? {
+            Some(s) => Some(
+                Option::from(OptionalResourceLocation::try_from(s.as_str())?).ok_or_else(|| {
+                    ValueError::from(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: "location must be `in-memory` or `filesystem-relative:*`"
+                            .to_string(),
+                        label: LABEL.to_string(),
+                    })
+                })?,
+            ),
+            None => None,
+        };
+
+        let changes = ResourceChanges {
+            include,
+            location,
+            include_source,
+        };
+
+        if changes.is_empty() {
+            return Err(ValueError::from(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: "at least one of include, location, or include_source must be defined"
+                    .to_string(),
+                label: LABEL.to_string(),
+            }));
+        }
+
+        let selector = selector
+            .downcast_ref::<ResourceSelectorValue>()
+            .unwrap()
+            .inner
+            .clone();
+
+        let pyoxidizer_context_value = get_context(type_values)?;
+        let pyoxidizer_context = pyoxidizer_context_value
+            .downcast_ref::<PyOxidizerEnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+        let logger = pyoxidizer_context.logger();
+
+        let mut exe = self.inner(LABEL)?;
+
+        let targets = error_context(LABEL, || {
+            let mut targets = vec![];
+
+            for (_, entry) in exe.iter_resources() {
+                for (candidate, resource) in collected_candidates(entry) {
+                    if selector.matches(&candidate)? {
+                        // Distribution resources are attributed by their
+                        // distribution name, everything else by import name.
+                        let package = match &resource {
+                            CollectedResource::PackageDistributionResource(package, _) => {
+                                Some(package.clone())
+                            }
+                            _ => candidate.top_level_package,
+                        };
+
+                        targets.push((candidate.name, package, resource));
+                    }
+                }
+            }
+
+            Ok(targets)
+        })?;
+
+        if changes.include_source == Some(true) && !targets.is_empty() {
+            warn!(
+                logger,
+                "{}: include_source=True only applies to resources added after apply()",
+                selector.description()
+            );
+        }
+
+        let collector = exe.resources_collector_mut();
+        let mut touched = vec![];
+
+        for (name, package, resource) in targets {
+            let changed = error_context(LABEL, || match &resource {
+                CollectedResource::ModuleSource(module) => {
+                    if changes.include == Some(false) {
+                        return Ok(collector.remove_python_module(module));
+                    }
+
+                    let mut changed = false;
+                    if changes.include_source == Some(false) {
+                        changed |= collector.remove_python_module_source(module);
+                    }
+                    if let Some(location) = &changes.location {
+                        changed |= collector.relocate_python_module(module, location)?;
+                    }
+
+                    Ok(changed)
+                }
+                CollectedResource::PackageResource(package, resource_name) => {
+                    if changes.include == Some(false) {
+                        Ok(collector.remove_python_package_resource(package, resource_name))
+                    } else if let Some(location) = &changes.location {
+                        collector.relocate_python_package_resource(package, resource_name, location)
+                    } else {
+                        Ok(false)
+                    }
+                }
+                CollectedResource::PackageDistributionResource(package, resource_name) => {
+                    if changes.include == Some(false) {
+                        Ok(collector
+                            .remove_python_package_distribution_resource(package, resource_name))
+                    } else {
+                        if changes.location.is_some() {
+                            warn!(
+                                logger,
+                                "{}: cannot change location of already added {}",
+                                selector.description(),
+                                name
+                            );
+                        }
+
+                        Ok(false)
+                    }
+                }
+                CollectedResource::ExtensionModule(_) | CollectedResource::File(_) => {
+                    warn!(
+                        logger,
+                        "{}: cannot change already added {}; only resources added after apply() are affected",
+                        selector.description(),
+                        name
+                    );
+
+                    Ok(false)
+                }
+            })?;
+
+            if changed {
+                if changes.include == Some(false) {
+                    if let Some(reason) = self.install_attribution(package.as_deref()) {
+                        warn!(
+                            logger,
+                            "{}: excluded {} ({})",
+                            selector.description(),
+                            name,
+                            reason
+                        );
+                    }
+                }
+
+                touched.push(name);
+            }
+        }
+
+        drop(exe);
+
+        for name in touched {
+            self.resource_selectors.record(&name, &selector);
+        }
+        self.resource_selectors.register(selector, changes);
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    /// PythonExecutable.resource_selector_report()
+    pub fn resource_selector_report(&self) -> ValueResult {
+        let mut d = Dictionary::default();
+
+        for (name, selectors) in self.resource_selectors.touched() {
+            d.insert(
+                Value::from(name.as_str()),
+                Value::from(
+                    selectors
+                        .iter()
+                        .map(|s| Value::from(s.as_str()))
+                        .collect::<Vec<_>>(),
+                ),
+            )?;
+        }
+
+        Value::try_from(d.get_content().clone())
+    }
+
+    /// PythonExecutable.last_install_report()
+    pub fn last_install_report(&self) -> ValueResult {
+        let report = match &self.last_install_report {
+            Some(report) => report,
+            None => return Ok(Value::from(NoneType::None)),
+        };
+
+        let optional_str = |s: &Option<String>| match s {
+            Some(s) => Value::from(s.as_str()),
+            None => Value::from(NoneType::None),
+        };
+
+        let mut distributions = Dictionary::default();
+
+        for (name, dist) in &report.distributions {
+            let required_by = dist
+                .attributions
+                .iter()
+                .map(|attribution| {
+                    let mut d = Dictionary::default();
+                    d.insert(
+                        Value::from("distribution"),
+                        optional_str(&attribution.required_by),
+                    )?;
+                    d.insert(Value::from("extra"), optional_str(&attribution.extra))?;
+
+                    Value::try_from(d.get_content().clone())
+                })
+                .collect::<Result<Vec<_>, ValueError>>()?;
+
+            let mut d = Dictionary::default();
+            d.insert(Value::from("version"), Value::from(dist.version.as_str()))?;
+            d.insert(Value::from("size"), Value::from(dist.size as i64))?;
+            d.insert(Value::from("required_by"), Value::from(required_by))?;
+            d.insert(
+                Value::from("via_extras"),
+                Value::from(
+                    dist.via_extras
+                        .iter()
+                        .map(|s| Value::from(s.as_str()))
+                        .collect::<Vec<_>>(),
+                ),
+            )?;
+            d.insert(
+                Value::from("reason"),
+                Value::from(report.describe(name).unwrap_or_default()),
+            )?;
+
+            distributions.insert(
+                Value::from(name.as_str()),
+                Value::try_from(d.get_content().clone())?,
+            )?;
+        }
+
+        let mut extras = Dictionary::default();
+        for (extra, size) in report.extra_sizes() {
+            extras.insert(Value::from(extra), Value::from(size as i64))?;
+        }
+
+        let mut d = Dictionary::default();
+        d.insert(
+            Value::from("distributions"),
+            Value::try_from(distributions.get_content().clone())?,
+        )?;
+        d.insert(
+            Value::from("extras"),
+            Value::try_from(extras.get_content().clone())?,
+        )?;
+
+        Value::try_from(d.get_content().clone())
+    }
+
     /// PythonExecutable.filter_resources_from_files(files=None, glob_files=None)
     pub fn filter_resources_from_files(
         &mut self,
@@ -953,24 +1599,27 @@ impl PythonExecutableValue {
     ) -> ValueResult {
         const LABEL: &str = "PythonExecutable.filter_resources_from_files()";
 
-        optional_list_arg("files", "string", &files)?;
-        optional_list_arg("glob_files", "string", &glob_files)?;
-
-        let files = match files.get_type() {
-            "list" => files
-                .iter()?
-                .iter()
-                .map(|x| PathBuf::from(x.to_string()))
-                .collect(),
-            "NoneType" => Vec::new(),
-            _ => panic!("type should have been validated above"),
-        };
-
-        let glob_files = match glob_files.get_type() {
-            "list" => glob_files.iter()?.iter().map(|x| x.to_string()).collect(),
-            "NoneType" => Vec::new(),
-            _ => panic!("type should have been validated above"),
-        };
+        let args = FunctionArgs::new(LABEL);
+        let files = args
+            .optional_list_of_str("files", files)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>();
+        let glob_files = args
+            .optional_list_of_str("glob_files", glob_files)?
+            .unwrap_or_default();
+
+        for path in &files {
+            check_capability(type_values, LABEL, &Capability::ReadPath(path))?;
+        }
+        for pattern in &glob_files {
+            check_capability(
+                type_values,
+                LABEL,
+                &Capability::ReadPath(&glob_base(pattern)),
+            )?;
+        }
 
         let files_refs = files.iter().map(|x| x.as_ref()).collect::<Vec<&Path>>();
         let glob_files_refs = glob_files.iter().map(|x| x.as_ref()).collect::<Vec<&str>>();
@@ -1066,6 +1715,18 @@ starlark_module! { python_executable_env =>
         this.setup_py_install(&env, cs, package_path, &extra_envs, &extra_global_arguments)
     }
 
+    PythonExecutable.apply(
+        env env,
+        this,
+        selector,
+        include=NoneType::None,
+        location=NoneType::None,
+        include_source=NoneType::None
+    ) {
+        let mut this = this.downcast_mut::<PythonExecutableValue>().unwrap().unwrap();
+        this.apply(env, &selector, &include, &location, &include_source)
+    }
+
     PythonExecutable.add_python_resource(
         env env,
         this,
@@ -1075,7 +1736,7 @@ starlark_module! { python_executable_env =>
         this.add_python_resource(
             &env,
             &resource,
-            "add_python_resource",
+            "PythonExecutable.add_python_resource()",
         )
     }
 
@@ -1091,6 +1752,34 @@ starlark_module! { python_executable_env =>
         )
     }
 
+    PythonExecutable.add_distribution(
+        env env,
+        this,
+        group,
+        location = NoneType::None
+    ) {
+        let mut this = this.downcast_mut::<PythonExecutableValue>().unwrap().unwrap();
+        this.add_distribution(
+            env,
+            &group,
+            &location,
+        )
+    }
+
+    PythonExecutable.encrypt_resources(
+        this,
+        recipient_pubkey: String,
+        scheme: String = "xchacha20poly1305".to_string())
+    {
+        let mut this = this.downcast_mut::<PythonExecutableValue>().unwrap().unwrap();
+        this.encrypt_resources(recipient_pubkey, scheme)
+    }
+
+    PythonExecutable.add_module_alias(this, alias: String, target: String) {
+        let mut this = this.downcast_mut::<PythonExecutableValue>().unwrap().unwrap();
+        this.add_module_alias(alias, target)
+    }
+
     PythonExecutable.filter_resources_from_files(
         env env,
         this,
@@ -1101,16 +1790,36 @@ starlark_module! { python_executable_env =>
         this.filter_resources_from_files(&env, &files, &glob_files)
     }
 
+    PythonExecutable.resource_selector_report(this) {
+        let this = this.downcast_ref::<PythonExecutableValue>().unwrap();
+        this.resource_selector_report()
+    }
+
+    PythonExecutable.last_install_report(this) {
+        let this = this.downcast_ref::<PythonExecutableValue>().unwrap();
+        this.last_install_report()
+    }
+
     PythonExecutable.to_embedded_resources(this) {
         let this = this.downcast_ref::<PythonExecutableValue>().unwrap();
         this.to_embedded_resources()
     }
 
+    PythonExecutable.to_embedded_python_files(this, path=NoneType::None) {
+        let this = this.downcast_ref::<PythonExecutableValue>().unwrap();
+        this.to_embedded_python_files(&path)
+    }
+
     PythonExecutable.to_file_manifest(env env, this, prefix: String) {
         let this = this.downcast_ref::<PythonExecutableValue>().unwrap();
         this.to_file_manifest(&env, prefix)
     }
 
+    PythonExecutable.to_sbom(env env, this, format: String = "spdx-json".to_string()) {
+        let this = this.downcast_ref::<PythonExecutableValue>().unwrap();
+        this.to_sbom(env, format)
+    }
+
     PythonExecutable.to_wix_bundle_builder(
         env env,
         call_stack cs,
@@ -1149,7 +1858,17 @@ starlark_module! { python_executable_env =>
 
 #[cfg(test)]
 mod tests {
-    use {super::super::testutil::*, super::*, crate::python_distributions::PYTHON_DISTRIBUTIONS};
+    use {
+        super::super::{python_resource::ResourceCollectionContext, testutil::*},
+        super::*,
+        crate::{
+            environment::default_target_triple,
+            python_distributions::PYTHON_DISTRIBUTIONS,
+            testutil::{get_env, get_logger},
+        },
+        starlark_dialect_build_targets::sandbox::SandboxPolicy,
+        std::collections::HashMap,
+    };
 
     #[test]
     fn test_default_values() -> Result<()> {
@@ -1237,7 +1956,7 @@ mod tests {
     #[test]
     fn test_pip_download_pyflakes() -> Result<()> {
         for target_triple in PYTHON_DISTRIBUTIONS.all_target_triples() {
-            let mut env = test_evaluation_context_builder()?
+            let mut env = real_distribution_evaluation_context_builder()?
                 .build_target_triple(target_triple)
                 .into_context()?;
 
@@ -1263,7 +1982,7 @@ mod tests {
 
     #[test]
     fn test_pip_install_simple() -> Result<()> {
-        let mut env = test_evaluation_context_builder()?.into_context()?;
+        let mut env = real_distribution_evaluation_context_builder()?.into_context()?;
 
         env.eval("dist = default_python_distribution()")?;
         env.eval("policy = dist.make_python_packaging_policy()")?;
@@ -1286,6 +2005,131 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_last_install_report() -> Result<()> {
+        let mut env = real_distribution_evaluation_context_builder()?.into_context()?;
+
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("exe = dist.to_python_executable('testapp')")?;
+
+        eval_assert(&mut env, "exe.last_install_report() == None")?;
+
+        env.eval("exe.pip_install(['black==19.10b0'])")?;
+        env.eval("report = exe.last_install_report()")?;
+
+        eval_assert(&mut env, "report['extras'] == {}")?;
+        eval_assert(
+            &mut env,
+            "report['distributions']['black']['reason'] == 'black requested directly'",
+        )?;
+        eval_assert(
+            &mut env,
+            "report['distributions']['appdirs']['required_by'] == \
+            [{'distribution': 'black', 'extra': None}]",
+        )?;
+        eval_assert(
+            &mut env,
+            "report['distributions']['appdirs']['reason'] == 'appdirs pulled in by black'",
+        )?;
+        eval_assert(&mut env, "report['distributions']['black']['size'] > 0")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sandbox() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+
+        let project = temp_dir.path().join("project");
+        std::fs::create_dir(&project)?;
+        std::fs::write(project.join("foo.py"), "# foo")?;
+
+        let outside = temp_dir.path().join("outside");
+        std::fs::create_dir(&outside)?;
+        std::fs::write(outside.join("bar.py"), "# bar")?;
+
+        let config_path = project.join("pyoxidizer.bzl");
+        let mut policy = SandboxPolicy::new(&config_path);
+        // Tests may use a real distribution, which may need to be downloaded.
+        policy.allow_network_host("github.com");
+
+        let mut env = test_evaluation_context_builder()?
+            .config_path(&config_path)
+            .sandbox(Some(policy))
+            .into_context()?;
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("exe = dist.to_python_executable('testapp')")?;
+
+        let resources = env.eval(&format!(
+            "exe.read_package_root({:?}, packages=['foo'])",
+            project.display().to_string()
+        ))?;
+        assert_eq!(resources.length().unwrap(), 1);
+        env.eval(&format!(
+            "glob(include=['*.py'], strip_prefix={:?})",
+            format!("{}/", project.display())
+        ))?;
+
+        let config_display = SandboxPolicy::new(&config_path)
+            .config_path()
+            .display()
+            .to_string();
+
+        for (code, operation, message) in &[
+            (
+                format!(
+                    "exe.read_package_root({:?}, packages=['bar'])",
+                    outside.display().to_string()
+                ),
+                "PythonExecutable.read_package_root",
+                "read",
+            ),
+            (
+                format!("exe.read_virtualenv({:?})", outside.display().to_string()),
+                "PythonExecutable.read_virtualenv",
+                "read",
+            ),
+            (
+                format!(
+                    "glob(include=[{:?}])",
+                    format!("{}/*.py", outside.display())
+                ),
+                "glob",
+                "read",
+            ),
+            (
+                "exe.pip_install(['foo'])".to_string(),
+                "PythonExecutable.pip_install",
+                "run a subprocess",
+            ),
+            (
+                "exe.pip_download(['foo'])".to_string(),
+                "PythonExecutable.pip_download",
+                "run a subprocess",
+            ),
+            (
+                "exe.setup_py_install('.')".to_string(),
+                "PythonExecutable.setup_py_install",
+                "run a subprocess",
+            ),
+        ] {
+            let err = env.eval(code).unwrap_err().to_string();
+            assert!(
+                err.as_str().contains(&format!(
+                    "sandbox violation: {} is not allowed to {}",
+                    operation, message
+                )),
+                "{}",
+                err
+            );
+            assert!(err.as_str().contains(&config_display), "{}", err);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_read_package_root_simple() -> Result<()> {
         let temp_dir = tempfile::Builder::new()
@@ -1424,9 +2268,323 @@ mod tests {
     }
 
     #[test]
-    fn test_to_wix_bundle_builder_callback() -> Result<()> {
+    fn test_encrypt_resources() -> Result<()> {
         let mut env = test_evaluation_context_builder()?.into_context()?;
         add_exe(&mut env)?;
+
+        let key = "09".repeat(32);
+
+        assert!(env.eval("exe.encrypt_resources('abcd')").is_err());
+        assert!(env
+            .eval(&format!("exe.encrypt_resources('{}', scheme='rot13')", key))
+            .is_err());
+
+        let value = env.eval(&format!("exe.encrypt_resources('{}')", key))?;
+        assert_eq!(value.get_type(), "NoneType");
+
+        let exe = env.eval("exe")?;
+        let exe = exe.downcast_ref::<PythonExecutableValue>().unwrap();
+        assert_eq!(
+            exe.inner("test").unwrap().resources_encryption(),
+            &Some(ResourcesEncryption {
+                scheme: EncryptionScheme::XChaCha20Poly1305,
+                recipient_public_key: vec![0x09; 32],
+            })
+        );
+
+        // Without a Rust project, nothing could provide the key.
+        let err = crate::project_building::build_python_executable(
+            &get_env()?,
+            &get_logger()?,
+            "testapp",
+            exe.inner("test").unwrap().deref().as_ref(),
+            default_target_triple(),
+            "0",
+            false,
+        )
+        .err()
+        .unwrap();
+        assert!(format!("{}", err)
+            .as_str()
+            .contains("resources_key_provider()"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_module_alias() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let value = env.eval("exe.add_module_alias('ruamel_yaml_shim', 'yaml')")?;
+        assert_eq!(value.get_type(), "NoneType");
+        assert!(env.eval("exe.add_module_alias('yaml', 'yaml')").is_err());
+
+        let exe = env.eval("exe")?;
+        let exe = exe.downcast_ref::<PythonExecutableValue>().unwrap();
+        let inner = exe.inner("test").unwrap();
+        let alias = inner
+            .iter_resources()
+            .find(|(name, _)| *name == "ruamel_yaml_shim")
+            .unwrap()
+            .1;
+        assert_eq!(alias.module_alias_target, Some("yaml".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_distribution() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("policy = dist.make_python_packaging_policy()")?;
+        env.eval("policy.resources_location_fallback = 'filesystem-relative:lib'")?;
+        env.eval("exe = dist.to_python_executable('testapp', packaging_policy=policy)")?;
+
+        let mut record = PythonPackageDistributionResourceValue::new(
+            python_packaging::resource::PythonPackageDistributionResource {
+                location:
+                    python_packaging::resource::PythonPackageDistributionResourceFlavor::DistInfo,
+                package: "PyYAML".to_string(),
+                version: "5.4".to_string(),
+                name: "RECORD".to_string(),
+                data: tugger_file_manifest::FileData::Memory(
+                    b"yaml/__init__.py,,\nyaml/resolver.py,,\n".to_vec(),
+                ),
+            },
+        );
+        record
+            .replace_add_collection_context(PythonResourceAddCollectionContext {
+                include: true,
+                location: ConcreteResourceLocation::InMemory,
+                location_fallback: None,
+                store_source: true,
+                optimize_level_zero: true,
+                optimize_level_one: false,
+                optimize_level_two: false,
+                bytecode_generation:
+                    python_packaging::resource_collection::BytecodeGeneration::Eager,
+            })
+            .unwrap();
+        env.set_var("record", Value::new(record)).unwrap();
+
+        env.eval("yaml = exe.make_python_module_source('yaml', '', True)")?;
+        env.eval("resolver = exe.make_python_module_source('yaml.resolver', '')")?;
+        env.eval("other = exe.make_python_module_source('other', '')")?;
+        env.eval("groups = group_resources_by_distribution([record, yaml, resolver, other])")?;
+
+        assert!(env.eval("exe.add_distribution([yaml])").is_err());
+        assert!(env
+            .eval("exe.add_distribution(groups['pyyaml'], location='bad')")
+            .is_err());
+        eval_assert(&mut env, "yaml.add_location == 'in-memory'")?;
+
+        env.eval("exe.add_distribution(groups['pyyaml'], location='filesystem-relative:lib')")?;
+        eval_assert(&mut env, "yaml.add_location == 'filesystem-relative:lib'")?;
+        eval_assert(&mut env, "other.add_location == 'in-memory'")?;
+
+        let exe = env.eval("exe")?;
+        let exe = exe.downcast_ref::<PythonExecutableValue>().unwrap();
+        let inner = exe.inner("test").unwrap();
+        let resources = inner.iter_resources().collect::<HashMap<_, _>>();
+
+        for name in &["yaml", "yaml.resolver"] {
+            let resource = resources.get(&name.to_string()).unwrap();
+            assert!(resource.in_memory_bytecode.is_none());
+            assert_eq!(resource.relative_path_bytecode.as_ref().unwrap().0, "lib");
+        }
+        assert!(!resources.contains_key(&"other".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("policy = dist.make_python_packaging_policy()")?;
+        env.eval("policy.resources_location_fallback = 'filesystem-relative:lib'")?;
+        env.eval("exe = dist.to_python_executable('testapp', packaging_policy=policy)")?;
+
+        env.eval("exe.add_python_resource(exe.make_python_module_source('foo.a', 'a = 1'))")?;
+        env.eval("exe.add_python_resource(exe.make_python_module_source('bar', 'b = 1'))")?;
+        env.eval("selector = ResourceSelector(name='foo.*', type='module-source')")?;
+
+        assert!(env.eval("exe.apply(selector)").is_err());
+        assert!(env.eval("exe.apply(selector, location='default')").is_err());
+
+        env.eval("exe.apply(selector, location='filesystem-relative:lib', include_source=False)")?;
+        env.eval("exe.add_python_resource(exe.make_python_module_source('foo.b', 'b = 1'))")?;
+        env.eval("exe.apply(ResourceSelector(name='foo.b'), location='in-memory')")?;
+
+        let exe = env.eval("exe")?;
+        let exe = exe.downcast_ref::<PythonExecutableValue>().unwrap();
+        let inner = exe.inner("test").unwrap();
+        let resources = inner.iter_resources().collect::<HashMap<_, _>>();
+
+        let a = resources.get(&"foo.a".to_string()).unwrap();
+        assert!(a.in_memory_source.is_none());
+        assert!(a.relative_path_module_source.is_none());
+        assert!(a.in_memory_bytecode.is_none());
+        assert_eq!(a.relative_path_bytecode.as_ref().unwrap().0, "lib");
+
+        let b = resources.get(&"foo.b".to_string()).unwrap();
+        assert!(b.in_memory_source.is_none());
+        assert!(b.relative_path_bytecode.is_none());
+        assert!(b.in_memory_bytecode.is_some());
+
+        let bar = resources.get(&"bar".to_string()).unwrap();
+        assert!(bar.in_memory_source.is_some());
+        drop(inner);
+
+        eval_assert(
+            &mut env,
+            "exe.resource_selector_report() == {\
+                'foo.a': ['ResourceSelector<name=foo.*, type=module-source>'], \
+                'foo.b': ['ResourceSelector<name=foo.*, type=module-source>', 'ResourceSelector<name=foo.b>'], \
+            }",
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_exclude() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        env.eval("exe.add_python_resource(exe.make_python_module_source('foo', 'a = 1'))")?;
+        env.eval(
+            "exe.apply(ResourceSelector(top_level_package='foo', max_size=100), include=False)",
+        )?;
+        env.eval("exe.add_python_resource(exe.make_python_module_source('foo.bar', 'b = 1'))")?;
+
+        env.eval("m = exe.make_python_module_source('foo.locked', 'c = 1')")?;
+        env.eval("m.add_include = True")?;
+        env.eval("m.lock()")?;
+        env.eval("exe.add_python_resource(m)")?;
+
+        let exe = env.eval("exe")?;
+        let exe = exe.downcast_ref::<PythonExecutableValue>().unwrap();
+        let inner = exe.inner("test").unwrap();
+        let names = inner
+            .iter_resources()
+            .map(|(k, _)| k.clone())
+            .collect::<Vec<_>>();
+        assert!(!names.contains(&"foo".to_string()));
+        assert!(!names.contains(&"foo.bar".to_string()));
+        assert!(names.contains(&"foo.locked".to_string()));
+        drop(inner);
+
+        eval_assert(
+            &mut env,
+            "sorted(exe.resource_selector_report().keys()) == ['foo', 'foo.bar']",
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_mutated_package_resource() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        env.set_var(
+            "r",
+            Value::new(PythonPackageResourceValue::new(
+                python_packaging::resource::PythonPackageResource {
+                    leaf_package: "foo".to_string(),
+                    relative_name: "default.json".to_string(),
+                    data: tugger_file_manifest::FileData::Memory(b"{}".to_vec()),
+                    is_stdlib: false,
+                    is_test: false,
+                },
+            )),
+        )
+        .unwrap();
+
+        env.eval("r.data = '{\"custom\": true}'")?;
+        env.eval("r.relative_name = 'config.json'")?;
+        env.eval("exe.add_python_resource(r)")?;
+
+        // Late mutation would not be reflected in the executable.
+        let err = env.eval("r.data = '{}'").unwrap_err();
+        assert!(err.to_string().as_str().contains("added to an executable"));
+
+        let exe = env.eval("exe")?;
+        let exe = exe.downcast_ref::<PythonExecutableValue>().unwrap();
+        let inner = exe.inner("test").unwrap();
+        let resources = inner.iter_resources().collect::<HashMap<_, _>>();
+
+        let foo = resources.get(&"foo".to_string()).unwrap();
+        let data = foo.in_memory_resources.as_ref().unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(
+            data.get("config.json").unwrap().resolve_content()?,
+            b"{\"custom\": true}".to_vec()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_stdlib() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        eval_assert(&mut env, "exe.include_stdlib == True")?;
+
+        env.eval("exe.include_stdlib = False")?;
+        eval_assert(&mut env, "exe.include_stdlib == False")?;
+        env.eval("exe.add_python_resource(exe.make_python_module_source('foo', 'x = 1'))")?;
+
+        let required = env.eval("exe.required_stdlib_resources")?;
+        assert_eq!(required.get_type(), "list");
+        let required = required
+            .iter()
+            .unwrap()
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+        for name in &[
+            "_fixture",
+            "codecs",
+            "encodings",
+            "encodings.utf_8",
+            "io",
+            "os",
+        ] {
+            assert!(required.contains(&name.to_string()), "{}", name);
+        }
+        assert!(!required.contains(&"collections".to_string()));
+
+        {
+            let exe = env.eval("exe")?;
+            let exe = exe.downcast_ref::<PythonExecutableValue>().unwrap();
+            let inner = exe.inner("test").unwrap();
+            let names = inner
+                .iter_resources()
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>();
+
+            for name in required.iter().chain(std::iter::once(&"foo".to_string())) {
+                assert!(names.contains(name), "{}", name);
+            }
+            for name in &["collections", "collections.abc", "test", "test.support"] {
+                assert!(!names.contains(&name.to_string()), "{}", name);
+            }
+        }
+
+        let err = env.eval("exe.include_stdlib = True").unwrap_err();
+        assert!(err.to_string().as_str().contains("can't be restored"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_wix_bundle_builder_callback() -> Result<()> {
+        let mut env = real_distribution_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
         env.eval("def modify(msi):\n msi.package_description = 'description'\n")?;
         let builder_value = env.eval("exe.to_wix_bundle_builder('id_prefix', 'product_name', '0.1', 'manufacturer', msi_builder_callback = modify)")?;
         let builder = builder_value
@@ -1447,6 +2605,64 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_to_embedded_python_files() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let vendored_path = temp_dir.path().join("vendored");
+
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let files = env.eval(&format!(
+            "exe.to_embedded_python_files(path = {:?})",
+            vendored_path.display().to_string()
+        ))?;
+        assert_eq!(files.get_type(), "PythonEmbeddedFiles");
+
+        env.eval(&format!(
+            "vendored = exe.to_embedded_python_files(path = {:?})",
+            vendored_path.display().to_string()
+        ))?;
+        env.eval("vendored.build('vendored')")?;
+
+        assert!(vendored_path.join("Cargo.toml").is_file());
+        assert!(vendored_path
+            .join("artifacts")
+            .join("cargo_metadata.txt")
+            .is_file());
+        let readme = std::fs::read_to_string(vendored_path.join("README.md"))?;
+        assert!(str::contains(
+            &readme,
+            "pyoxidizer build --verify-vendored vendored"
+        ));
+
+        assert!(env
+            .eval("exe.to_embedded_python_files(path = True)")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_sbom() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let sbom = env.eval("exe.to_sbom()")?;
+        assert_eq!(sbom.get_type(), "FileContent");
+        eval_assert(&mut env, "exe.to_sbom().filename == 'testapp.spdx.json'")?;
+        eval_assert(
+            &mut env,
+            "exe.to_sbom('cyclonedx-json').filename == 'testapp.cdx.json'",
+        )?;
+
+        assert!(env.eval("exe.to_sbom('bad')").is_err());
+
+        Ok(())
+    }
+
     #[cfg(windows)]
     #[test]
     fn test_to_wix_bundle_builder() -> Result<()> {
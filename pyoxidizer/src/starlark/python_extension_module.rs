@@ -3,14 +3,32 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    super::python_resource::ResourceCollectionContext,
+    super::{
+        python_resource::{
+            policy_allowed_locations, size_bucket_value, starlark_add_collection_context,
+            starlark_as_struct, starlark_duplicate_to_locations, starlark_lock,
+            starlark_plan_entry, starlark_set_collection_context, starlark_settable_attributes,
+            starlark_to_canonical_string, starlark_with_include, ResourceCollectionContext,
+        },
+        resource_selector::{SelectorCandidate, SizeBucketThresholds},
+        strict::unknown_attribute_error,
+    },
     python_packaging::{
+        location::{AbstractResourceLocation, ConcreteResourceLocation},
+        policy::PythonPackagingPolicy,
         resource::{PythonExtensionModule, PythonResource},
         resource_collection::PythonResourceAddCollectionContext,
     },
-    starlark::values::{
-        error::{RuntimeError, UnsupportedOperation, ValueError},
-        {Mutable, TypedValue, Value, ValueResult},
+    starlark::{
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
     },
     std::sync::{Arc, Mutex, MutexGuard},
 };
@@ -19,6 +37,20 @@ use {
 pub struct PythonExtensionModuleWrapper {
     pub em: PythonExtensionModule,
     pub add_context: Option<PythonResourceAddCollectionContext>,
+    /// Whether `add_*` attributes can no longer be modified.
+    pub locked: bool,
+    /// Resource locations allowed by the packaging policy.
+    ///
+    /// Captured from the packaging policy when it is applied.
+    pub allowed_locations: Option<Vec<AbstractResourceLocation>>,
+    /// Extension module filename suffix of the interpreter.
+    ///
+    /// Captured from the packaging policy when it is applied.
+    pub extension_suffix: Option<String>,
+    /// Whether shared library extension modules can be loaded from memory.
+    ///
+    /// Captured from the packaging policy when it is applied.
+    pub allow_in_memory_shared_library_loading: bool,
 }
 
 /// Starlark `Value` wrapper for `PythonExtensionModule`.
@@ -36,6 +68,10 @@ impl PythonExtensionModuleValue {
             inner: Arc::new(Mutex::new(PythonExtensionModuleWrapper {
                 em,
                 add_context: None,
+                locked: false,
+                allowed_locations: None,
+                extension_suffix: None,
+                allow_in_memory_shared_library_loading: false,
             })),
             name,
         }
@@ -53,9 +89,32 @@ impl PythonExtensionModuleValue {
             })
         })
     }
+
+    /// Rename the extension module.
+    pub fn set_name(&mut self, name: String, label: &str) -> Result<(), ValueError> {
+        self.inner(label)?.em.name = name.clone();
+        self.name = name;
+
+        Ok(())
+    }
 }
 
 impl ResourceCollectionContext for PythonExtensionModuleValue {
+    fn resource_attrs(&self) -> Vec<&'static str> {
+        vec![
+            "is_locked",
+            "will_be_included",
+            "context_hash",
+            "effective_location",
+            "extension_suffix",
+            "is_stdlib",
+            "name",
+            "ssl_library",
+            "variant",
+            "size_bucket",
+        ]
+    }
+
     fn add_collection_context(
         &self,
     ) -> Result<Option<PythonResourceAddCollectionContext>, ValueError> {
@@ -75,6 +134,28 @@ impl ResourceCollectionContext for PythonExtensionModuleValue {
             .replace(context))
     }
 
+    fn independent_copy(&self) -> Result<Self, ValueError> {
+        const LABEL: &str = "PythonExtensionModule.independent_copy()";
+
+        let inner = self.inner(LABEL)?;
+
+        let value = PythonExtensionModuleValue::new(inner.em.clone());
+        {
+            let mut copy = value.inner(LABEL)?;
+            copy.add_context = inner.add_context.clone();
+            copy.allowed_locations = inner.allowed_locations.clone();
+            copy.extension_suffix = inner.extension_suffix.clone();
+            copy.allow_in_memory_shared_library_loading =
+                inner.allow_in_memory_shared_library_loading;
+        }
+
+        Ok(value)
+    }
+
+    fn independent_copy_value(&self) -> ValueResult {
+        Ok(Value::new(self.independent_copy()?))
+    }
+
     fn as_python_resource(&self) -> Result<PythonResource<'_>, ValueError> {
         Ok(PythonResource::from(
             self.inner("PythonExtensionModule.as_python_resource()")?
@@ -82,6 +163,55 @@ impl ResourceCollectionContext for PythonExtensionModuleValue {
                 .clone(),
         ))
     }
+
+    // The resource collector doesn't consult `include` for extension modules.
+    fn force_include(&self) -> bool {
+        true
+    }
+
+    fn capture_policy_state(&mut self, policy: &PythonPackagingPolicy) -> Result<(), ValueError> {
+        let mut inner = self.inner("PythonExtensionModule.capture_policy_state()")?;
+        inner.extension_suffix = policy.extension_module_suffix().map(|s| s.to_string());
+        inner.allowed_locations = Some(policy_allowed_locations(policy));
+        inner.allow_in_memory_shared_library_loading =
+            policy.allow_in_memory_shared_library_loading();
+
+        Ok(())
+    }
+
+    fn allowed_locations(&self) -> Result<Option<Vec<AbstractResourceLocation>>, ValueError> {
+        Ok(self
+            .inner("PythonExtensionModule.allowed_locations()")?
+            .allowed_locations
+            .clone())
+    }
+
+    // Mirrors how the resource collector materializes extension modules.
+    fn supports_location(&self, location: &ConcreteResourceLocation) -> Result<bool, ValueError> {
+        let inner = self.inner("PythonExtensionModule.supports_location()")?;
+
+        // Built-in extension modules are linked into the binary.
+        let can_link_builtin = inner.em.in_libpython() || !inner.em.object_file_data.is_empty();
+        let has_shared_library = inner.em.shared_library.is_some();
+
+        Ok(match location {
+            ConcreteResourceLocation::InMemory => {
+                can_link_builtin
+                    || (has_shared_library && inner.allow_in_memory_shared_library_loading)
+            }
+            ConcreteResourceLocation::RelativePath(_) => can_link_builtin || has_shared_library,
+        })
+    }
+
+    fn is_locked(&self) -> Result<bool, ValueError> {
+        Ok(self.inner("PythonExtensionModule.is_locked()")?.locked)
+    }
+
+    fn lock(&mut self) -> Result<(), ValueError> {
+        self.inner("PythonExtensionModule.lock()")?.locked = true;
+
+        Ok(())
+    }
 }
 
 impl TypedValue for PythonExtensionModuleValue {
@@ -104,8 +234,38 @@ impl TypedValue for PythonExtensionModuleValue {
         let inner = self.inner(&format!("PythonExtensionModule.{}", attribute))?;
 
         let v = match attribute {
+            "size_bucket" => size_bucket_value(
+                &SelectorCandidate::extension_module(&inner.em),
+                &SizeBucketThresholds::default(),
+                "size_bucket",
+            )?,
+            "is_locked" => Value::from(inner.locked),
+            "will_be_included" => {
+                drop(inner);
+                Value::from(self.will_be_included()?)
+            }
+            "context_hash" => {
+                drop(inner);
+                self.add_collection_context_hash()?
+            }
+            "effective_location" => {
+                drop(inner);
+                self.effective_location_value()?
+            }
+            "extension_suffix" => match &inner.extension_suffix {
+                Some(suffix) => Value::from(suffix.clone()),
+                None => Value::from(NoneType::None),
+            },
             "is_stdlib" => Value::from(inner.em.is_stdlib),
             "name" => Value::new(inner.em.name.clone()),
+            "ssl_library" => match inner.em.ssl_library() {
+                Some(library) => Value::from(library),
+                None => Value::from(NoneType::None),
+            },
+            "variant" => match &inner.em.variant {
+                Some(variant) => Value::from(variant.clone()),
+                None => Value::from(NoneType::None),
+            },
             attr => {
                 drop(inner);
 
@@ -125,14 +285,57 @@ impl TypedValue for PythonExtensionModuleValue {
     }
 
     fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
-        Ok(match attribute {
-            "is_stdlib" => true,
-            "name" => true,
-            attr => self.add_collection_context_attrs().contains(&attr),
-        })
+        Ok(self.resource_attrs().contains(&attribute)
+            || self.add_collection_context_attrs().contains(&attribute))
     }
 
     fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
-        self.set_attr_add_collection_context(attribute, value)
+        if self.add_collection_context_attrs().contains(&attribute) {
+            self.set_attr_add_collection_context(attribute, value)
+        } else {
+            Err(unknown_attribute_error(
+                Self::TYPE,
+                attribute,
+                &self.settable_attrs(),
+            ))
+        }
+    }
+}
+
+starlark_module! { python_extension_module_module =>
+    PythonExtensionModule.add_collection_context(this) {
+        starlark_add_collection_context::<PythonExtensionModuleValue>(&this)
+    }
+
+    PythonExtensionModule.lock(this) {
+        starlark_lock::<PythonExtensionModuleValue>(&this)
+    }
+
+    PythonExtensionModule.set_collection_context(this, context) {
+        starlark_set_collection_context::<PythonExtensionModuleValue>(&this, &context)
+    }
+
+    PythonExtensionModule.duplicate_to_locations(this, locations) {
+        starlark_duplicate_to_locations::<PythonExtensionModuleValue>(&this, &locations)
+    }
+
+    PythonExtensionModule.with_include(this, include: bool) {
+        starlark_with_include::<PythonExtensionModuleValue>(&this, include)
+    }
+
+    PythonExtensionModule.plan_entry(this) {
+        starlark_plan_entry::<PythonExtensionModuleValue>(&this)
+    }
+
+    PythonExtensionModule.settable_attributes(this) {
+        starlark_settable_attributes::<PythonExtensionModuleValue>(&this)
+    }
+
+    PythonExtensionModule.to_canonical_string(this) {
+        starlark_to_canonical_string::<PythonExtensionModuleValue>(&this)
+    }
+
+    PythonExtensionModule.as_struct(this) {
+        starlark_as_struct::<PythonExtensionModuleValue>(&this)
     }
 }
@@ -3,12 +3,13 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    super::util::ToValue,
+    super::{strict::unknown_attribute_error, util::ToValue},
     crate::py_packaging::config::PyembedPythonInterpreterConfig,
     python_packaging::{
         interpreter::{
-            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, MemoryAllocatorBackend,
-            MultiprocessingStartMethod, PythonInterpreterProfile, TerminfoResolution,
+            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, InitFailureAction,
+            MemoryAllocatorBackend, MultiprocessingStartMethod, PythonInterpreterProfile,
+            PythonRunMode, SysExecutableMode, TerminfoResolution,
         },
         resource::BytecodeOptimizationLevel,
     },
@@ -135,6 +136,94 @@ impl PythonInterpreterConfigValue {
     }
 }
 
+/// Attributes of `PythonInterpreterConfig`.
+const ATTRIBUTES: &[&str] = &[
+    "config_profile",
+    "allocator",
+    "configure_locale",
+    "coerce_c_locale",
+    "coerce_c_locale_warn",
+    "development_mode",
+    "isolated",
+    "legacy_windows_fs_encoding",
+    "parse_argv",
+    "use_environment",
+    "utf8_mode",
+    "base_exec_prefix",
+    "base_executable",
+    "base_prefix",
+    "buffered_stdio",
+    "bytes_warning",
+    "check_hash_pycs_mode",
+    "configure_c_stdio",
+    "dump_refs",
+    "exec_prefix",
+    "executable",
+    "fault_handler",
+    "filesystem_encoding",
+    "filesystem_errors",
+    "hash_seed",
+    "home",
+    "import_time",
+    "inspect",
+    "install_signal_handlers",
+    "interactive",
+    "legacy_windows_stdio",
+    "malloc_stats",
+    "module_search_paths",
+    "optimization_level",
+    "parser_debug",
+    "pathconfig_warnings",
+    "prefix",
+    "program_name",
+    "pycache_prefix",
+    "python_path_env",
+    "quiet",
+    "run_command",
+    "run_filename",
+    "run_module",
+    "show_ref_count",
+    "site_import",
+    "skip_first_source_line",
+    "stdio_encoding",
+    "stdio_errors",
+    "tracemalloc",
+    "user_site_directory",
+    "verbose",
+    "warn_options",
+    "write_bytecode",
+    "x_options",
+    "allocator_backend",
+    "allocator_raw",
+    "allocator_mem",
+    "allocator_obj",
+    "allocator_pymalloc_arena",
+    "allocator_debug",
+    "oxidized_importer",
+    "filesystem_importer",
+    "resources_filesystem_shim",
+    "filesystem_resources_root",
+    "resources_root_env",
+    "argvb",
+    "multiprocessing_auto_dispatch",
+    "multiprocessing_start_method",
+    "sys_frozen",
+    "sys_meipass",
+    "sys_executable_mode",
+    "terminfo_resolution",
+    "write_modules_directory_env",
+    "startup_audit_path",
+    "post_init_callable",
+    "pre_finalize_callable",
+    "run_mode",
+    "repl_banner",
+    "repl_startup_code",
+    "repl_history_path",
+    "failure_exit_code_base",
+    "on_init_failure",
+    "init_failure_fallback_command",
+];
+
 impl TypedValue for PythonInterpreterConfigValue {
     type Holder = Mutable<PythonInterpreterConfigValue>;
     const TYPE: &'static str = "PythonInterpreterConfig";
@@ -218,6 +307,9 @@ impl TypedValue for PythonInterpreterConfigValue {
             "allocator_debug" => Value::from(inner.allocator_debug),
             "oxidized_importer" => Value::from(inner.oxidized_importer),
             "filesystem_importer" => Value::from(inner.filesystem_importer),
+            "resources_filesystem_shim" => Value::from(inner.resources_filesystem_shim),
+            "filesystem_resources_root" => inner.filesystem_resources_root.to_value(),
+            "resources_root_env" => inner.resources_root_env.to_value(),
             "argvb" => Value::from(inner.argvb),
             "multiprocessing_auto_dispatch" => Value::from(inner.multiprocessing_auto_dispatch),
             "multiprocessing_start_method" => {
@@ -225,8 +317,19 @@ impl TypedValue for PythonInterpreterConfigValue {
             }
             "sys_frozen" => Value::from(inner.sys_frozen),
             "sys_meipass" => Value::from(inner.sys_meipass),
+            "sys_executable_mode" => Value::from(inner.sys_executable_mode.to_string()),
             "terminfo_resolution" => inner.terminfo_resolution.to_value(),
             "write_modules_directory_env" => inner.write_modules_directory_env.to_value(),
+            "startup_audit_path" => inner.startup_audit_path.to_value(),
+            "post_init_callable" => inner.post_init_callable.to_value(),
+            "pre_finalize_callable" => inner.pre_finalize_callable.to_value(),
+            "run_mode" => Value::from(inner.run_mode.to_string()),
+            "repl_banner" => inner.repl_banner.to_value(),
+            "repl_startup_code" => inner.repl_startup_code.to_value(),
+            "repl_history_path" => inner.repl_history_path.to_value(),
+            "failure_exit_code_base" => Value::from(inner.failure_exit_code_base),
+            "on_init_failure" => Value::from(inner.on_init_failure.to_string()),
+            "init_failure_fallback_command" => inner.init_failure_fallback_command.to_value(),
             attr => {
                 return Err(ValueError::OperationNotSupported {
                     op: UnsupportedOperation::GetAttr(attr.to_string()),
@@ -240,79 +343,7 @@ impl TypedValue for PythonInterpreterConfigValue {
     }
 
     fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
-        Ok(matches!(
-            attribute,
-            "config_profile"
-                | "allocator"
-                | "configure_locale"
-                | "coerce_c_locale"
-                | "coerce_c_locale_warn"
-                | "development_mode"
-                | "isolated"
-                | "legacy_windows_fs_encoding"
-                | "parse_argv"
-                | "use_environment"
-                | "utf8_mode"
-                | "base_exec_prefix"
-                | "base_executable"
-                | "base_prefix"
-                | "buffered_stdio"
-                | "bytes_warning"
-                | "check_hash_pycs_mode"
-                | "configure_c_stdio"
-                | "dump_refs"
-                | "exec_prefix"
-                | "executable"
-                | "fault_handler"
-                | "filesystem_encoding"
-                | "filesystem_errors"
-                | "hash_seed"
-                | "home"
-                | "import_time"
-                | "inspect"
-                | "install_signal_handlers"
-                | "interactive"
-                | "legacy_windows_stdio"
-                | "malloc_stats"
-                | "module_search_paths"
-                | "optimization_level"
-                | "parser_debug"
-                | "pathconfig_warnings"
-                | "prefix"
-                | "program_name"
-                | "pycache_prefix"
-                | "python_path_env"
-                | "quiet"
-                | "run_command"
-                | "run_filename"
-                | "run_module"
-                | "show_ref_count"
-                | "site_import"
-                | "skip_first_source_line"
-                | "stdio_encoding"
-                | "stdio_errors"
-                | "tracemalloc"
-                | "user_site_directory"
-                | "verbose"
-                | "warn_options"
-                | "write_bytecode"
-                | "x_options"
-                | "allocator_backend"
-                | "allocator_raw"
-                | "allocator_mem"
-                | "allocator_obj"
-                | "allocator_pymalloc_arena"
-                | "allocator_debug"
-                | "oxidized_importer"
-                | "filesystem_importer"
-                | "argvb"
-                | "multiprocessing_auto_dispatch"
-                | "multiprocessing_start_method"
-                | "sys_frozen"
-                | "sys_meipass"
-                | "terminfo_resolution"
-                | "write_modules_directory_env"
-        ))
+        Ok(ATTRIBUTES.contains(&attribute))
     }
 
     fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
@@ -581,6 +612,15 @@ impl TypedValue for PythonInterpreterConfigValue {
             "filesystem_importer" => {
                 inner.filesystem_importer = value.to_bool();
             }
+            "resources_filesystem_shim" => {
+                inner.resources_filesystem_shim = value.to_bool();
+            }
+            "filesystem_resources_root" => {
+                inner.filesystem_resources_root = value.to_optional();
+            }
+            "resources_root_env" => {
+                inner.resources_root_env = value.to_optional();
+            }
             "argvb" => {
                 inner.argvb = value.to_bool();
             }
@@ -605,6 +645,16 @@ impl TypedValue for PythonInterpreterConfigValue {
             "sys_meipass" => {
                 inner.sys_meipass = value.to_bool();
             }
+            "sys_executable_mode" => {
+                inner.sys_executable_mode = SysExecutableMode::from_str(value.to_string().as_str())
+                    .map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message: e,
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        })
+                    })?;
+            }
             "terminfo_resolution" => {
                 inner.terminfo_resolution =
                     TerminfoResolution::try_from(value.to_string().as_str()).map_err(|e| {
@@ -618,13 +668,62 @@ impl TypedValue for PythonInterpreterConfigValue {
             "write_modules_directory_env" => {
                 inner.write_modules_directory_env = value.to_optional();
             }
-            attr => {
-                return Err(ValueError::OperationNotSupported {
-                    op: UnsupportedOperation::SetAttr(attr.to_string()),
-                    left: Self::TYPE.to_owned(),
-                    right: None,
-                })
+            "startup_audit_path" => {
+                inner.startup_audit_path = value.to_optional();
             }
+            "post_init_callable" => {
+                inner.post_init_callable = value.to_optional();
+            }
+            "pre_finalize_callable" => {
+                inner.pre_finalize_callable = value.to_optional();
+            }
+            "run_mode" => {
+                inner.run_mode =
+                    PythonRunMode::from_str(value.to_string().as_str()).map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message: e,
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        })
+                    })?;
+            }
+            "repl_banner" => {
+                inner.repl_banner = value.to_optional();
+            }
+            "repl_startup_code" => {
+                inner.repl_startup_code = value.to_optional();
+            }
+            "repl_history_path" => {
+                inner.repl_history_path = value.to_optional();
+            }
+            "failure_exit_code_base" => {
+                // The base and the base plus 1 must be valid exit codes that
+                // aren't 0.
+                let code = value.to_int()?;
+                if !(1..=254).contains(&code) {
+                    return Err(ValueError::from(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: format!("{} is not in the range 1 to 254", code),
+                        label: format!("{}.{}", Self::TYPE, attribute),
+                    }));
+                }
+
+                inner.failure_exit_code_base = code as i32;
+            }
+            "on_init_failure" => {
+                inner.on_init_failure = InitFailureAction::from_str(value.to_string().as_str())
+                    .map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message: e,
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        })
+                    })?;
+            }
+            "init_failure_fallback_command" => {
+                inner.init_failure_fallback_command = value.try_to_optional()?;
+            }
+            attr => return Err(unknown_attribute_error(Self::TYPE, attr, ATTRIBUTES)),
         }
 
         Ok(())
@@ -1291,6 +1390,17 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_resources_filesystem_shim() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.resources_filesystem_shim == False")?;
+        env.eval("config.resources_filesystem_shim = True")?;
+        eval_assert(&mut env, "config.resources_filesystem_shim == True")?;
+
+        Ok(())
+    }
+
     #[test]
     fn test_argvb() -> Result<()> {
         let mut env = get_env()?;
@@ -1357,6 +1467,23 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_sys_executable_mode() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.sys_executable_mode == 'app'")?;
+
+        env.eval("config.sys_executable_mode = 'none'")?;
+        eval_assert(&mut env, "config.sys_executable_mode == 'none'")?;
+
+        env.eval("config.sys_executable_mode = 'python-shim'")?;
+        eval_assert(&mut env, "config.sys_executable_mode == 'python-shim'")?;
+
+        assert!(env.eval("config.sys_executable_mode = 'python'").is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_terminfo_resolution() -> Result<()> {
         let mut env = get_env()?;
@@ -1380,4 +1507,124 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_filesystem_resources_root() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.filesystem_resources_root == None")?;
+        eval_assert(&mut env, "config.resources_root_env == None")?;
+
+        env.eval("config.filesystem_resources_root = '$ORIGIN/lib'")?;
+        env.eval("config.resources_root_env = 'MYAPP_RESOURCE_ROOT'")?;
+        eval_assert(
+            &mut env,
+            "config.filesystem_resources_root == '$ORIGIN/lib'",
+        )?;
+        eval_assert(
+            &mut env,
+            "config.resources_root_env == 'MYAPP_RESOURCE_ROOT'",
+        )?;
+
+        env.eval("config.filesystem_resources_root = None")?;
+        eval_assert(&mut env, "config.filesystem_resources_root == None")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_startup_audit_path() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.startup_audit_path == None")?;
+
+        env.eval("config.startup_audit_path = '$ORIGIN/startup-audit.txt'")?;
+        eval_assert(
+            &mut env,
+            "config.startup_audit_path == '$ORIGIN/startup-audit.txt'",
+        )?;
+
+        env.eval("config.startup_audit_path = None")?;
+        eval_assert(&mut env, "config.startup_audit_path == None")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lifecycle_callables() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.post_init_callable == None")?;
+        eval_assert(&mut env, "config.pre_finalize_callable == None")?;
+
+        env.eval("config.post_init_callable = 'app.post_init'")?;
+        eval_assert(&mut env, "config.post_init_callable == 'app.post_init'")?;
+        env.eval("config.pre_finalize_callable = 'app.pre_finalize'")?;
+        eval_assert(
+            &mut env,
+            "config.pre_finalize_callable == 'app.pre_finalize'",
+        )?;
+
+        env.eval("config.post_init_callable = None")?;
+        eval_assert(&mut env, "config.post_init_callable == None")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_mode() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.run_mode == 'default'")?;
+        eval_assert(&mut env, "config.repl_banner == None")?;
+        eval_assert(&mut env, "config.repl_startup_code == None")?;
+        eval_assert(&mut env, "config.repl_history_path == None")?;
+
+        env.eval("config.run_mode = 'repl'")?;
+        eval_assert(&mut env, "config.run_mode == 'repl'")?;
+        assert!(env.eval("config.run_mode = 'interactive'").is_err());
+
+        env.eval("config.repl_banner = 'Ops Console'")?;
+        eval_assert(&mut env, "config.repl_banner == 'Ops Console'")?;
+        env.eval("config.repl_startup_code = 'import os'")?;
+        eval_assert(&mut env, "config.repl_startup_code == 'import os'")?;
+        env.eval("config.repl_history_path = '$ORIGIN/history'")?;
+        eval_assert(&mut env, "config.repl_history_path == '$ORIGIN/history'")?;
+
+        env.eval("config.repl_history_path = None")?;
+        eval_assert(&mut env, "config.repl_history_path == None")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_init_failure() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.failure_exit_code_base == 70")?;
+        eval_assert(&mut env, "config.on_init_failure == 'exit'")?;
+        eval_assert(&mut env, "config.init_failure_fallback_command == None")?;
+
+        env.eval("config.failure_exit_code_base = 100")?;
+        eval_assert(&mut env, "config.failure_exit_code_base == 100")?;
+        assert!(env.eval("config.failure_exit_code_base = 0").is_err());
+        assert!(env.eval("config.failure_exit_code_base = 255").is_err());
+
+        env.eval("config.on_init_failure = 'message-box'")?;
+        eval_assert(&mut env, "config.on_init_failure == 'message-box'")?;
+        env.eval("config.on_init_failure = 'run-fallback-command'")?;
+        eval_assert(&mut env, "config.on_init_failure == 'run-fallback-command'")?;
+        assert!(env.eval("config.on_init_failure = 'ignore'").is_err());
+
+        env.eval("config.init_failure_fallback_command = ['$ORIGIN/fallback', '--safe']")?;
+        eval_assert(
+            &mut env,
+            "config.init_failure_fallback_command == ['$ORIGIN/fallback', '--safe']",
+        )?;
+
+        env.eval("config.init_failure_fallback_command = None")?;
+        eval_assert(&mut env, "config.init_failure_fallback_command == None")?;
+
+        Ok(())
+    }
 }
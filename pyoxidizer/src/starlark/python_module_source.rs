@@ -3,22 +3,69 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    super::python_resource::ResourceCollectionContext,
+    super::{
+        python_package_resource::value_to_data,
+        python_resource::{
+            policy_allowed_locations, size_bucket_value, starlark_add_collection_context,
+            starlark_as_struct, starlark_duplicate_to_locations, starlark_lock,
+            starlark_plan_entry, starlark_set_collection_context, starlark_settable_attributes,
+            starlark_to_canonical_string, starlark_with_include, ResourceCollectionContext,
+        },
+        resource_selector::{SelectorCandidate, SizeBucketThresholds},
+        strict::unknown_attribute_error,
+    },
     python_packaging::{
+        location::AbstractResourceLocation,
+        module_util::is_builtin_module_name,
+        policy::PythonPackagingPolicy,
+        python_source::{self, strip_docstrings_and_comments},
         resource::{PythonModuleSource, PythonResource},
-        resource_collection::PythonResourceAddCollectionContext,
+        resource_collection::{BytecodeGeneration, PythonResourceAddCollectionContext},
+    },
+    starlark::{
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
     },
-    starlark::values::{
-        error::{RuntimeError, UnsupportedOperation, ValueError},
-        {Mutable, TypedValue, Value, ValueResult},
+    std::{
+        collections::BTreeMap,
+        convert::TryFrom,
+        sync::{Arc, Mutex, MutexGuard},
     },
-    std::sync::{Arc, Mutex, MutexGuard},
+    tugger_file_manifest::FileData,
 };
 
 #[derive(Debug)]
 pub struct PythonModuleSourceWrapper {
     pub m: PythonModuleSource,
     pub add_context: Option<PythonResourceAddCollectionContext>,
+    /// Whether `add_*` attributes can no longer be modified.
+    pub locked: bool,
+    /// Resource locations allowed by the packaging policy.
+    ///
+    /// Captured from the packaging policy when it is applied.
+    pub allowed_locations: Option<Vec<AbstractResourceLocation>>,
+    /// Whether path-backed source can be read through a symlink.
+    pub follow_symlinks: bool,
+    /// Precompiled `.pyc` data by optimization level.
+    ///
+    /// Used instead of bytecode compiled from the source.
+    pub bytecode: BTreeMap<i64, Vec<u8>>,
+}
+
+impl PythonModuleSourceWrapper {
+    /// Resolve the module's source, honoring `follow_symlinks`.
+    pub fn resolve_source(&self) -> Result<Vec<u8>, std::io::Error> {
+        self.m
+            .source
+            .resolve_content_with_symlinks(self.follow_symlinks)
+    }
 }
 
 /// Starlark value wrapper for `PythonModuleSource`.
@@ -36,6 +83,10 @@ impl PythonModuleSourceValue {
             inner: Arc::new(Mutex::new(PythonModuleSourceWrapper {
                 m: module,
                 add_context: None,
+                locked: false,
+                allowed_locations: None,
+                follow_symlinks: true,
+                bytecode: BTreeMap::new(),
             })),
             name,
         }
@@ -50,9 +101,137 @@ impl PythonModuleSourceValue {
             })
         })
     }
+
+    /// Rename the module.
+    pub fn set_name(&mut self, name: String, label: &str) -> Result<(), ValueError> {
+        self.inner(label)?.m.name = name.clone();
+        self.name = name;
+
+        Ok(())
+    }
+
+    /// PythonModuleSource.stripped_copy()
+    ///
+    /// Returns a new value whose source has docstrings and full-line comments
+    /// removed. The receiver is not modified.
+    pub fn stripped_copy(&self) -> ValueResult {
+        const LABEL: &str = "PythonModuleSource.stripped_copy()";
+
+        let inner = self.inner(LABEL)?;
+
+        let source = inner.resolve_source().map_err(|e| {
+            ValueError::from(RuntimeError {
+                code: "PYOXIDIZER_SOURCE_ERROR",
+                message: format!("error resolving source code: {}", e),
+                label: LABEL.to_string(),
+            })
+        })?;
+
+        let mut m = inner.m.clone();
+        m.source = FileData::Memory(strip_docstrings_and_comments(&source));
+
+        let value = PythonModuleSourceValue::new(m);
+        {
+            let mut copy = value.inner(LABEL)?;
+            copy.add_context = inner.add_context.clone();
+            copy.allowed_locations = inner.allowed_locations.clone();
+            copy.follow_symlinks = inner.follow_symlinks;
+        }
+
+        Ok(Value::new(value))
+    }
+
+    /// PythonModuleSource.set_bytecode(level, data)
+    ///
+    /// `data` is the content of a `.pyc` file, including its 16 byte header.
+    pub fn set_bytecode(&mut self, level: i64, data: &Value) -> ValueResult {
+        const LABEL: &str = "PythonModuleSource.set_bytecode()";
+
+        let error = |message: String| {
+            ValueError::from(RuntimeError {
+                code: "PYTHON_MODULE_SOURCE",
+                message,
+                label: LABEL.to_string(),
+            })
+        };
+
+        if !(0..=2).contains(&level) {
+            return Err(error(format!("level must be 0, 1, or 2; got {}", level)));
+        }
+        let data = value_to_data(data).map_err(error)?;
+        if data.len() < 16 {
+            return Err(error(format!(
+                "bytecode must include the 16 byte .pyc header; got {} bytes",
+                data.len()
+            )));
+        }
+
+        self.inner(LABEL)?.bytecode.insert(level, data);
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    /// PythonModuleSource.get_bytecode(level)
+    ///
+    /// Returns the data passed to `set_bytecode()` for `level` as a list of
+    /// ints or `None`.
+    pub fn get_bytecode(&self, level: i64) -> ValueResult {
+        const LABEL: &str = "PythonModuleSource.get_bytecode()";
+
+        Ok(match self.inner(LABEL)?.bytecode.get(&level) {
+            Some(data) => Value::from(
+                data.iter()
+                    .map(|b| Value::from(*b as i64))
+                    .collect::<Vec<_>>(),
+            ),
+            None => Value::from(NoneType::None),
+        })
+    }
+
+    /// PythonModuleSource.imports_module(name)
+    pub fn imports_module(&self, name: String) -> ValueResult {
+        const LABEL: &str = "PythonModuleSource.imports_module()";
+
+        let source = self.inner(LABEL)?.resolve_source().map_err(|e| {
+            ValueError::from(RuntimeError {
+                code: "PYOXIDIZER_SOURCE_ERROR",
+                message: format!("error resolving source code: {}", e),
+                label: LABEL.to_string(),
+            })
+        })?;
+
+        Ok(Value::from(python_source::imports_module(&source, &name)))
+    }
 }
 
 impl ResourceCollectionContext for PythonModuleSourceValue {
+    fn resource_attrs(&self) -> Vec<&'static str> {
+        vec![
+            "is_locked",
+            "will_be_included",
+            "context_hash",
+            "effective_location",
+            "data_location_kind",
+            "name",
+            "package_depth",
+            "source",
+            "is_package",
+            "is_init_module",
+            "is_generated",
+            "is_stdlib",
+            "bytecode_tag",
+            "bytecode_generation",
+            "follow_symlinks",
+            "shadows_builtin",
+            "imports",
+            "size_bucket",
+        ]
+    }
+
+    fn settable_resource_attrs(&self) -> Vec<&'static str> {
+        vec!["bytecode_tag", "bytecode_generation", "follow_symlinks"]
+    }
+
     fn add_collection_context(
         &self,
     ) -> Result<Option<PythonResourceAddCollectionContext>, ValueError> {
@@ -72,6 +251,26 @@ impl ResourceCollectionContext for PythonModuleSourceValue {
             .replace(context))
     }
 
+    fn independent_copy(&self) -> Result<Self, ValueError> {
+        const LABEL: &str = "PythonModuleSource.independent_copy()";
+
+        let inner = self.inner(LABEL)?;
+
+        let value = PythonModuleSourceValue::new(inner.m.clone());
+        {
+            let mut copy = value.inner(LABEL)?;
+            copy.add_context = inner.add_context.clone();
+            copy.follow_symlinks = inner.follow_symlinks;
+            copy.bytecode = inner.bytecode.clone();
+        }
+
+        Ok(value)
+    }
+
+    fn independent_copy_value(&self) -> ValueResult {
+        Ok(Value::new(self.independent_copy()?))
+    }
+
     fn as_python_resource(&self) -> Result<PythonResource<'_>, ValueError> {
         Ok(PythonResource::from(
             self.inner("PythonModuleSource.as_python_resource()")?
@@ -79,6 +278,30 @@ impl ResourceCollectionContext for PythonModuleSourceValue {
                 .clone(),
         ))
     }
+
+    fn capture_policy_state(&mut self, policy: &PythonPackagingPolicy) -> Result<(), ValueError> {
+        self.inner("PythonModuleSource.capture_policy_state()")?
+            .allowed_locations = Some(policy_allowed_locations(policy));
+
+        Ok(())
+    }
+
+    fn allowed_locations(&self) -> Result<Option<Vec<AbstractResourceLocation>>, ValueError> {
+        Ok(self
+            .inner("PythonModuleSource.allowed_locations()")?
+            .allowed_locations
+            .clone())
+    }
+
+    fn is_locked(&self) -> Result<bool, ValueError> {
+        Ok(self.inner("PythonModuleSource.is_locked()")?.locked)
+    }
+
+    fn lock(&mut self) -> Result<(), ValueError> {
+        self.inner("PythonModuleSource.lock()")?.locked = true;
+
+        Ok(())
+    }
 }
 
 impl TypedValue for PythonModuleSourceValue {
@@ -101,10 +324,35 @@ impl TypedValue for PythonModuleSourceValue {
         let inner = self.inner(&format!("PythonModuleSource.{}", attribute))?;
 
         let v = match attribute {
+            "size_bucket" => size_bucket_value(
+                &SelectorCandidate::module_source(&inner.m),
+                &SizeBucketThresholds::default(),
+                "size_bucket",
+            )?,
+            "is_locked" => Value::from(inner.locked),
+            "will_be_included" => {
+                drop(inner);
+                Value::from(self.will_be_included()?)
+            }
+            "context_hash" => {
+                drop(inner);
+                self.add_collection_context_hash()?
+            }
+            "effective_location" => {
+                drop(inner);
+                self.effective_location_value()?
+            }
+            "is_generated" => Value::from(inner.m.source.backing_path().is_none()),
             "is_stdlib" => Value::from(inner.m.is_stdlib),
+            "bytecode_tag" => Value::new(inner.m.cache_tag.clone()),
+            "bytecode_generation" => match &inner.add_context {
+                Some(context) => Value::from(context.bytecode_generation.as_ref()),
+                None => Value::from(NoneType::None),
+            },
             "name" => Value::new(inner.m.name.clone()),
+            "package_depth" => Value::from(inner.m.name.matches('.').count() as i64),
             "source" => {
-                let source = inner.m.source.resolve_content().map_err(|e| {
+                let source = inner.resolve_source().map_err(|e| {
                     ValueError::from(RuntimeError {
                         code: "PYOXIDIZER_SOURCE_ERROR",
                         message: format!("error resolving source code: {}", e),
@@ -122,7 +370,27 @@ impl TypedValue for PythonModuleSourceValue {
 
                 Value::new(source)
             }
+            "imports" => {
+                let source = inner.resolve_source().map_err(|e| {
+                    ValueError::from(RuntimeError {
+                        code: "PYOXIDIZER_SOURCE_ERROR",
+                        message: format!("error resolving source code: {}", e),
+                        label: "imports".to_string(),
+                    })
+                })?;
+
+                Value::from(
+                    python_source::imported_top_level_modules(&source)
+                        .into_iter()
+                        .collect::<Vec<_>>(),
+                )
+            }
             "is_package" => Value::new(inner.m.is_package),
+            // Identical to `is_package` until namespace packages are modeled.
+            "is_init_module" => Value::new(inner.m.is_package),
+            "shadows_builtin" => Value::from(is_builtin_module_name(&inner.m.name)),
+            "follow_symlinks" => Value::from(inner.follow_symlinks),
+            "data_location_kind" => Value::from(inner.m.source.kind()),
             attr => {
                 drop(inner);
 
@@ -142,37 +410,465 @@ impl TypedValue for PythonModuleSourceValue {
     }
 
     fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
-        Ok(match attribute {
-            "name" => true,
-            "source" => true,
-            "is_package" => true,
-            "is_stdlib" => true,
-            attr => self.add_collection_context_attrs().contains(&attr),
-        })
+        Ok(self.resource_attrs().contains(&attribute)
+            || self.add_collection_context_attrs().contains(&attribute))
     }
 
     fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
-        if self.add_collection_context_attrs().contains(&attribute) {
+        if attribute == "bytecode_tag" {
+            const LABEL: &str = "PythonModuleSource.bytecode_tag";
+
+            let tag = match value.get_type() {
+                "string" => value.to_string(),
+                t => {
+                    return Err(ValueError::from(RuntimeError {
+                        code: "PYTHON_MODULE_SOURCE",
+                        message: format!("bytecode_tag must be a string; got {}", t),
+                        label: LABEL.to_string(),
+                    }))
+                }
+            };
+
+            if tag.is_empty() || tag.chars().any(|c| c == '/' || c == '\\') {
+                return Err(ValueError::from(RuntimeError {
+                    code: "PYTHON_MODULE_SOURCE",
+                    message: format!("invalid bytecode_tag: {:?}", tag),
+                    label: LABEL.to_string(),
+                }));
+            }
+
+            self.inner(LABEL)?.m.cache_tag = tag;
+
+            Ok(())
+        } else if attribute == "bytecode_generation" {
+            const LABEL: &str = "PythonModuleSource.bytecode_generation";
+
+            let generation = match value.get_type() {
+                "string" => {
+                    BytecodeGeneration::try_from(value.to_string().as_str()).map_err(|message| {
+                        ValueError::from(RuntimeError {
+                            code: "PYTHON_MODULE_SOURCE",
+                            message,
+                            label: LABEL.to_string(),
+                        })
+                    })?
+                }
+                t => {
+                    return Err(ValueError::from(RuntimeError {
+                        code: "PYTHON_MODULE_SOURCE",
+                        message: format!("bytecode_generation must be a string; got {}", t),
+                        label: LABEL.to_string(),
+                    }))
+                }
+            };
+
+            if self.is_locked()? {
+                return Err(ValueError::from(RuntimeError {
+                    code: "PYTHON_MODULE_SOURCE",
+                    message: "cannot set bytecode_generation on a locked resource; lock() was previously called".to_string(),
+                    label: LABEL.to_string(),
+                }));
+            }
+
+            match self.inner(LABEL)?.add_context.as_mut() {
+                Some(context) => {
+                    context.bytecode_generation = generation;
+                    Ok(())
+                }
+                None => Err(ValueError::from(RuntimeError {
+                    code: "PYTHON_MODULE_SOURCE",
+                    message:
+                        "cannot set bytecode_generation on an object without a collection context"
+                            .to_string(),
+                    label: LABEL.to_string(),
+                })),
+            }
+        } else if attribute == "follow_symlinks" {
+            const LABEL: &str = "PythonModuleSource.follow_symlinks";
+
+            let follow = match value.get_type() {
+                "bool" => value.to_bool(),
+                t => {
+                    return Err(ValueError::from(RuntimeError {
+                        code: "PYTHON_MODULE_SOURCE",
+                        message: format!("follow_symlinks must be a bool; got {}", t),
+                        label: LABEL.to_string(),
+                    }))
+                }
+            };
+
+            self.inner(LABEL)?.follow_symlinks = follow;
+
+            Ok(())
+        } else if self.add_collection_context_attrs().contains(&attribute) {
             self.set_attr_add_collection_context(attribute, value)
         } else {
-            Err(ValueError::OperationNotSupported {
-                op: UnsupportedOperation::SetAttr(attribute.to_string()),
-                left: Self::TYPE.to_owned(),
-                right: None,
-            })
+            Err(unknown_attribute_error(
+                Self::TYPE,
+                attribute,
+                &self.settable_attrs(),
+            ))
         }
     }
 }
 
+starlark_module! { python_module_source_module =>
+    PythonModuleSource.stripped_copy(this) {
+        let this = this.downcast_ref::<PythonModuleSourceValue>().unwrap();
+        this.stripped_copy()
+    }
+
+    PythonModuleSource.imports_module(this, name: String) {
+        let this = this.downcast_ref::<PythonModuleSourceValue>().unwrap();
+        this.imports_module(name)
+    }
+
+    PythonModuleSource.set_bytecode(this, level: i64, data) {
+        let mut this = this.downcast_mut::<PythonModuleSourceValue>().unwrap().unwrap();
+        this.set_bytecode(level, &data)
+    }
+
+    PythonModuleSource.get_bytecode(this, level: i64) {
+        let this = this.downcast_ref::<PythonModuleSourceValue>().unwrap();
+        this.get_bytecode(level)
+    }
+
+    PythonModuleSource.add_collection_context(this) {
+        starlark_add_collection_context::<PythonModuleSourceValue>(&this)
+    }
+
+    PythonModuleSource.lock(this) {
+        starlark_lock::<PythonModuleSourceValue>(&this)
+    }
+
+    PythonModuleSource.set_collection_context(this, context) {
+        starlark_set_collection_context::<PythonModuleSourceValue>(&this, &context)
+    }
+
+    PythonModuleSource.duplicate_to_locations(this, locations) {
+        starlark_duplicate_to_locations::<PythonModuleSourceValue>(&this, &locations)
+    }
+
+    PythonModuleSource.with_include(this, include: bool) {
+        starlark_with_include::<PythonModuleSourceValue>(&this, include)
+    }
+
+    PythonModuleSource.plan_entry(this) {
+        starlark_plan_entry::<PythonModuleSourceValue>(&this)
+    }
+
+    PythonModuleSource.settable_attributes(this) {
+        starlark_settable_attributes::<PythonModuleSourceValue>(&this)
+    }
+
+    PythonModuleSource.to_canonical_string(this) {
+        starlark_to_canonical_string::<PythonModuleSourceValue>(&this)
+    }
+
+    PythonModuleSource.as_struct(this) {
+        starlark_as_struct::<PythonModuleSourceValue>(&this)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use {
         super::*,
-        crate::starlark::{python_distribution::PythonDistributionValue, testutil::*},
+        crate::starlark::{
+            python_distribution::PythonDistributionValue, python_executable::PythonExecutableValue,
+            testutil::*,
+        },
         anyhow::Result,
-        starlark::values::none::NoneType,
+        python_packaging::{
+            location::ConcreteResourceLocation,
+            resource::{BytecodeOptimizationLevel, PythonModuleBytecodeFromSource},
+            resource_collection::PythonModuleBytecodeProvider,
+        },
+        std::{collections::HashMap, path::PathBuf},
     };
 
+    fn module_value(source: FileData) -> PythonModuleSourceValue {
+        PythonModuleSourceValue::new(PythonModuleSource {
+            name: "foo".to_string(),
+            source,
+            is_package: false,
+            cache_tag: "cpython-39".to_string(),
+            is_stdlib: false,
+            is_test: false,
+        })
+    }
+
+    #[test]
+    fn test_is_generated() -> Result<()> {
+        let m = module_value(FileData::Memory(b"import bar".to_vec()));
+        assert!(m.has_attr("is_generated").unwrap());
+        assert_eq!(m.get_attr("is_generated").unwrap().get_type(), "bool");
+        assert!(m.get_attr("is_generated").unwrap().to_bool());
+
+        let m = module_value(FileData::Path("/path/to/foo.py".into()));
+        assert!(!m.get_attr("is_generated").unwrap().to_bool());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_init_module() -> Result<()> {
+        let m = module_value(FileData::Memory(vec![]));
+        assert!(m.has_attr("is_init_module").unwrap());
+        assert_eq!(m.get_attr("is_init_module").unwrap().get_type(), "bool");
+        assert!(!m.get_attr("is_init_module").unwrap().to_bool());
+
+        let m = PythonModuleSourceValue::new(PythonModuleSource {
+            name: "foo".to_string(),
+            source: FileData::Memory(vec![]),
+            is_package: true,
+            cache_tag: "cpython-39".to_string(),
+            is_stdlib: false,
+            is_test: false,
+        });
+        assert!(m.get_attr("is_init_module").unwrap().to_bool());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_location_kind() -> Result<()> {
+        let m = module_value(FileData::Memory(b"import bar".to_vec()));
+        assert!(m.has_attr("data_location_kind").unwrap());
+        assert_eq!(m.get_attr("data_location_kind").unwrap().to_str(), "memory");
+
+        // Path-backed source isn't resolved.
+        let m = module_value(FileData::Path("/does/not/exist/foo.py".into()));
+        assert_eq!(m.get_attr("data_location_kind").unwrap().to_str(), "path");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_as_struct() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.set_var("m", Value::new(module_value(FileData::Memory(vec![]))))
+            .unwrap();
+
+        env.eval("s = m.as_struct()")?;
+        eval_assert(&mut env, "type(s) == 'struct'")?;
+        eval_assert(&mut env, "s.name == 'foo'")?;
+        eval_assert(&mut env, "s.follow_symlinks == True")?;
+        eval_assert(&mut env, "'add_include' in dir(s)")?;
+        eval_assert(&mut env, "s == m.as_struct()")?;
+
+        // The struct is a snapshot that can't be modified.
+        env.eval("m.follow_symlinks = False")?;
+        eval_assert(&mut env, "s.follow_symlinks == True")?;
+        eval_assert(&mut env, "s != m.as_struct()")?;
+        assert!(env.eval("s.name = 'bar'").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_settable_attributes() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.set_var("m", Value::new(module_value(FileData::Memory(vec![]))))
+            .unwrap();
+
+        env.eval("attrs = m.settable_attributes()")?;
+        eval_assert(&mut env, "'bytecode_tag' in attrs")?;
+        eval_assert(&mut env, "'bytecode_generation' in attrs")?;
+        eval_assert(&mut env, "'follow_symlinks' in attrs")?;
+        eval_assert(&mut env, "'add_location' in attrs")?;
+        eval_assert(&mut env, "'source' not in attrs")?;
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        // The temporary directory itself may be beneath a symlink.
+        let root = temp_dir.path().canonicalize()?;
+        let target = root.join("real.py");
+        let link = root.join("foo.py");
+        std::fs::write(&target, "import bar")?;
+        std::os::unix::fs::symlink(&target, &link)?;
+
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.set_var("m", Value::new(module_value(FileData::Path(link))))
+            .unwrap();
+
+        eval_assert(&mut env, "m.follow_symlinks == True")?;
+        eval_assert(&mut env, "m.source == 'import bar'")?;
+
+        assert!(env.eval("m.follow_symlinks = 'no'").is_err());
+        env.eval("m.follow_symlinks = False")?;
+        eval_assert(&mut env, "m.follow_symlinks == False")?;
+        assert!(env.eval("m.source").is_err());
+        assert!(env.eval("m.stripped_copy()").is_err());
+
+        env.set_var("m", Value::new(module_value(FileData::Path(target))))
+            .unwrap();
+        env.eval("m.follow_symlinks = False")?;
+        eval_assert(&mut env, "m.source == 'import bar'")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_canonical_string() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.set_var(
+            "m",
+            Value::new(module_value(FileData::Memory(
+                b"import bar\nprint(\"hi\")\n".to_vec(),
+            ))),
+        )
+        .unwrap();
+
+        let s = env.eval("m.to_canonical_string()")?;
+        assert_eq!(s.get_type(), "string");
+        assert_eq!(
+            s.to_str(),
+            "add_bytecode_optimization_level_one=None\n\
+            add_bytecode_optimization_level_two=None\n\
+            add_bytecode_optimization_level_zero=None\n\
+            add_include=None\n\
+            add_location=None\n\
+            add_location_fallback=None\n\
+            add_source=None\n\
+            bytecode_generation=None\n\
+            bytecode_tag=\"cpython-39\"\n\
+            context_hash=None\n\
+            data_location_kind=\"memory\"\n\
+            effective_location=None\n\
+            follow_symlinks=True\n\
+            imports=[\"bar\"]\n\
+            is_generated=True\n\
+            is_init_module=False\n\
+            is_locked=False\n\
+            is_package=False\n\
+            is_stdlib=False\n\
+            name=\"foo\"\n\
+            package_depth=0\n\
+            shadows_builtin=False\n\
+            size_bucket=\"small\"\n\
+            source=\"import bar\\nprint(\\\"hi\\\")\\n\"\n\
+            will_be_included=False\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shadows_builtin() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        env.eval("m = exe.make_python_module_source('sys', '')")?;
+        eval_assert(&mut env, "m.shadows_builtin == True")?;
+
+        env.eval("m = exe.make_python_module_source('mypkg', '')")?;
+        eval_assert(&mut env, "m.shadows_builtin == False")?;
+
+        env.eval("m = exe.make_python_module_source('mypkg.sys', '')")?;
+        eval_assert(&mut env, "m.shadows_builtin == False")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_package_depth() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        env.eval("m = exe.make_python_module_source('foo', '')")?;
+        eval_assert(&mut env, "m.package_depth == 0")?;
+
+        env.eval("m = exe.make_python_module_source('foo.bar.baz', '')")?;
+        eval_assert(&mut env, "m.package_depth == 2")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stripped_copy() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        let source = "\"\"\"Module docstring.\"\"\"\n# comment\nimport bar\n";
+        env.set_var(
+            "m",
+            Value::new(module_value(FileData::Memory(source.into()))),
+        )
+        .unwrap();
+
+        let copy = env.eval("m.stripped_copy()")?;
+        assert_eq!(copy.get_type(), PythonModuleSourceValue::TYPE);
+        assert_eq!(copy.get_attr("name").unwrap().to_str(), "foo");
+        assert_eq!(
+            copy.get_attr("source").unwrap().to_str(),
+            "\n\nimport bar\n"
+        );
+
+        let m = env.get_var("m").unwrap();
+        assert_eq!(m.get_attr("source").unwrap().to_str(), source);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_imports_module() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        let source = "import os\nfrom numpy.linalg import inv\n# import pandas\n";
+        env.set_var(
+            "m",
+            Value::new(module_value(FileData::Memory(source.into()))),
+        )
+        .unwrap();
+
+        eval_assert(&mut env, "m.imports_module('os') == True")?;
+        eval_assert(&mut env, "m.imports_module('numpy') == True")?;
+        eval_assert(&mut env, "m.imports_module('numpy.linalg') == True")?;
+        eval_assert(&mut env, "m.imports_module('pandas') == False")?;
+        eval_assert(&mut env, "m.imports_module('numpy.fft') == False")?;
+
+        env.set_var(
+            "m",
+            Value::new(module_value(FileData::Path(
+                "/does/not/exist/foo.py".into(),
+            ))),
+        )
+        .unwrap();
+        assert!(env.eval("m.imports_module('os')").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_imports() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        let source = "import sys\nimport os.path\nfrom numpy.linalg import inv\nimport os\n";
+        env.set_var(
+            "m",
+            Value::new(module_value(FileData::Memory(source.into()))),
+        )
+        .unwrap();
+
+        eval_assert(&mut env, "m.imports == ['numpy', 'os', 'sys']")?;
+
+        env.set_var(
+            "m",
+            Value::new(module_value(FileData::Memory(b"x = 1".to_vec()))),
+        )
+        .unwrap();
+        eval_assert(&mut env, "m.imports == []")?;
+
+        Ok(())
+    }
+
     #[test]
     fn test_source_module_attrs() -> Result<()> {
         let mut env = test_evaluation_context_builder()?.into_context()?;
@@ -325,4 +1021,127 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_bytecode_generation() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        let m = module_value(FileData::Memory(vec![]));
+        env.set_var("m", Value::new(m)).unwrap();
+        eval_assert(&mut env, "m.bytecode_generation == None")?;
+        assert!(env.eval("m.bytecode_generation = 'lazy'").is_err());
+
+        let m = module_value(FileData::Memory(vec![]));
+        m.inner("test").unwrap().add_context = Some(PythonResourceAddCollectionContext {
+            include: true,
+            location: ConcreteResourceLocation::InMemory,
+            location_fallback: None,
+            store_source: false,
+            optimize_level_zero: true,
+            optimize_level_one: false,
+            optimize_level_two: false,
+            bytecode_generation: BytecodeGeneration::Eager,
+        });
+        env.set_var("m", Value::new(m)).unwrap();
+
+        eval_assert(&mut env, "m.bytecode_generation == 'eager'")?;
+        env.eval("m.bytecode_generation = 'lazy'")?;
+        eval_assert(&mut env, "m.bytecode_generation == 'lazy'")?;
+        assert!(env.eval("m.bytecode_generation = 'sometimes'").is_err());
+        assert!(env.eval("m.bytecode_generation = True").is_err());
+        eval_assert(&mut env, "m.bytecode_generation == 'lazy'")?;
+
+        env.eval("m.lock()")?;
+        assert!(env.eval("m.bytecode_generation = 'eager'").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_bytecode() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("exe = dist.to_python_executable('testapp')")?;
+
+        env.eval("m = exe.make_python_module_source('foo', 'import os')")?;
+        eval_assert(&mut env, "m.get_bytecode(0) == None")?;
+
+        env.eval("pyc = [0x61, 0x0d, 0x0d, 0x0a] + [0] * 12 + [0xe3, 1, 2]")?;
+        env.eval("m.set_bytecode(0, pyc)")?;
+        eval_assert(&mut env, "m.get_bytecode(0) == pyc")?;
+        eval_assert(&mut env, "m.get_bytecode(1) == None")?;
+
+        assert!(env.eval("m.set_bytecode(3, pyc)").is_err());
+        assert!(env.eval("m.set_bytecode(-1, pyc)").is_err());
+        assert!(env.eval("m.set_bytecode(1, [0] * 15)").is_err());
+        assert!(env.eval("m.set_bytecode(1, [256] * 16)").is_err());
+        eval_assert(&mut env, "m.get_bytecode(1) == None")?;
+
+        env.eval("exe.add_python_resource(m)")?;
+
+        let exe = env.eval("exe")?;
+        let exe = exe.downcast_ref::<PythonExecutableValue>().unwrap();
+        let inner = exe.inner("test").unwrap();
+        let resources = inner.iter_resources().collect::<HashMap<_, _>>();
+        let resource = resources.get(&"foo".to_string()).unwrap();
+
+        // The .pyc header is replaced when the application is built.
+        match resource.in_memory_bytecode.as_ref().unwrap() {
+            PythonModuleBytecodeProvider::Provided(data) => {
+                assert_eq!(data.resolve_content()?, vec![0xe3, 1, 2]);
+            }
+            PythonModuleBytecodeProvider::FromSource(_) => panic!("bytecode should be provided"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytecode_tag() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("policy = dist.make_python_packaging_policy()")?;
+        env.eval("policy.resources_location_fallback = 'filesystem-relative:lib'")?;
+        env.eval("exe = dist.to_python_executable('testapp', packaging_policy=policy)")?;
+
+        env.eval("m = exe.make_python_module_source('foo.bar', 'import os')")?;
+        eval_assert(&mut env, "m.bytecode_tag == 'cpython-39'")?;
+
+        assert!(env.eval("m.bytecode_tag = 42").is_err());
+        assert!(env.eval("m.bytecode_tag = ''").is_err());
+        assert!(env.eval("m.bytecode_tag = 'foo/bar'").is_err());
+
+        env.eval("m.bytecode_tag = 'cpython-310'")?;
+        eval_assert(&mut env, "m.bytecode_tag == 'cpython-310'")?;
+        env.eval("m.add_location = 'filesystem-relative:lib'")?;
+        env.eval("m.add_bytecode_optimization_level_two = True")?;
+        env.eval("exe.add_python_resource(m)")?;
+
+        let exe = env.eval("exe")?;
+        let exe = exe.downcast_ref::<PythonExecutableValue>().unwrap();
+        let inner = exe.inner("test").unwrap();
+        let resources = inner.iter_resources().collect::<HashMap<_, _>>();
+        let resource = resources.get(&"foo.bar".to_string()).unwrap();
+
+        let (prefix, cache_tag, _) = resource.relative_path_bytecode.as_ref().unwrap();
+        assert_eq!(prefix, "lib");
+        assert_eq!(cache_tag, "cpython-310");
+
+        let (_, cache_tag, _) = resource.relative_path_bytecode_opt2.as_ref().unwrap();
+        let bytecode = PythonModuleBytecodeFromSource {
+            name: "foo.bar".to_string(),
+            source: FileData::Memory(vec![]),
+            optimize_level: BytecodeOptimizationLevel::Two,
+            is_package: false,
+            cache_tag: cache_tag.clone(),
+            is_stdlib: false,
+            is_test: false,
+        };
+        assert_eq!(
+            bytecode.resolve_path("lib"),
+            PathBuf::from("lib/foo/__pycache__/bar.cpython-310.opt-2.pyc")
+        );
+
+        Ok(())
+    }
 }
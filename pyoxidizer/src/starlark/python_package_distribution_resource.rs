@@ -3,22 +3,52 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    super::python_resource::ResourceCollectionContext,
+    super::{
+        python_resource::{
+            policy_allowed_locations, size_bucket_value, starlark_add_collection_context,
+            starlark_as_struct, starlark_duplicate_to_locations, starlark_lock,
+            starlark_plan_entry, starlark_set_collection_context, starlark_settable_attributes,
+            starlark_to_canonical_string, starlark_with_include, ResourceCollectionContext,
+        },
+        resource_selector::{SelectorCandidate, SizeBucketThresholds},
+        strict::unknown_attribute_error,
+    },
     python_packaging::{
+        location::AbstractResourceLocation,
+        policy::PythonPackagingPolicy,
         resource::{PythonPackageDistributionResource, PythonResource},
         resource_collection::PythonResourceAddCollectionContext,
     },
-    starlark::values::{
-        error::{RuntimeError, UnsupportedOperation, ValueError},
-        {Mutable, TypedValue, Value, ValueResult},
+    starlark::{
+        values::{
+            dict::Dictionary,
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    std::{
+        convert::TryFrom,
+        sync::{Arc, Mutex, MutexGuard},
     },
-    std::sync::{Arc, Mutex, MutexGuard},
 };
 
 #[derive(Debug)]
 pub struct PythonPackageDistributionResourceWrapper {
     pub r: PythonPackageDistributionResource,
     pub add_context: Option<PythonResourceAddCollectionContext>,
+    /// Whether `add_*` attributes can no longer be modified.
+    pub locked: bool,
+    /// Resource locations allowed by the packaging policy.
+    ///
+    /// Captured from the packaging policy when it is applied.
+    pub allowed_locations: Option<Vec<AbstractResourceLocation>>,
+    /// Selected version of the package, captured when a policy is applied.
+    pub selected_version: Option<String>,
 }
 
 /// Starlark `Value` wrapper for `PythonPackageDistributionResource`.
@@ -38,6 +68,9 @@ impl PythonPackageDistributionResourceValue {
             inner: Arc::new(Mutex::new(PythonPackageDistributionResourceWrapper {
                 r: resource,
                 add_context: None,
+                locked: false,
+                allowed_locations: None,
+                selected_version: None,
             })),
             package,
             name,
@@ -59,6 +92,24 @@ impl PythonPackageDistributionResourceValue {
 }
 
 impl ResourceCollectionContext for PythonPackageDistributionResourceValue {
+    fn resource_attrs(&self) -> Vec<&'static str> {
+        vec![
+            "is_locked",
+            "will_be_included",
+            "context_hash",
+            "effective_location",
+            "data_location_kind",
+            "distribution_file_kind",
+            "entry_points",
+            "is_current_version",
+            "is_stdlib",
+            "package",
+            "name",
+            // TODO expose raw data
+            "size_bucket",
+        ]
+    }
+
     fn add_collection_context(
         &self,
     ) -> Result<Option<PythonResourceAddCollectionContext>, ValueError> {
@@ -78,6 +129,26 @@ impl ResourceCollectionContext for PythonPackageDistributionResourceValue {
             .replace(context))
     }
 
+    fn independent_copy(&self) -> Result<Self, ValueError> {
+        const LABEL: &str = "PythonPackageDistributionResource.independent_copy()";
+
+        let inner = self.inner(LABEL)?;
+
+        let value = PythonPackageDistributionResourceValue::new(inner.r.clone());
+        {
+            let mut copy = value.inner(LABEL)?;
+            copy.add_context = inner.add_context.clone();
+            copy.allowed_locations = inner.allowed_locations.clone();
+            copy.selected_version = inner.selected_version.clone();
+        }
+
+        Ok(value)
+    }
+
+    fn independent_copy_value(&self) -> ValueResult {
+        Ok(Value::new(self.independent_copy()?))
+    }
+
     fn as_python_resource(&self) -> Result<PythonResource<'_>, ValueError> {
         Ok(PythonResource::from(
             self.inner("PythonPackageDistributionResource.as_python_resource()")?
@@ -85,6 +156,36 @@ impl ResourceCollectionContext for PythonPackageDistributionResourceValue {
                 .clone(),
         ))
     }
+
+    fn capture_policy_state(&mut self, policy: &PythonPackagingPolicy) -> Result<(), ValueError> {
+        let mut inner = self.inner("PythonPackageDistributionResource.capture_policy_state()")?;
+        inner.selected_version = policy
+            .package_version(&inner.r.package)
+            .map(|s| s.to_string());
+        inner.allowed_locations = Some(policy_allowed_locations(policy));
+
+        Ok(())
+    }
+
+    fn allowed_locations(&self) -> Result<Option<Vec<AbstractResourceLocation>>, ValueError> {
+        Ok(self
+            .inner("PythonPackageDistributionResource.allowed_locations()")?
+            .allowed_locations
+            .clone())
+    }
+
+    fn is_locked(&self) -> Result<bool, ValueError> {
+        Ok(self
+            .inner("PythonPackageDistributionResource.is_locked()")?
+            .locked)
+    }
+
+    fn lock(&mut self) -> Result<(), ValueError> {
+        self.inner("PythonPackageDistributionResource.lock()")?
+            .locked = true;
+
+        Ok(())
+    }
 }
 
 impl TypedValue for PythonPackageDistributionResourceValue {
@@ -116,9 +217,65 @@ impl TypedValue for PythonPackageDistributionResourceValue {
         let inner = self.inner(&format!("PythonPackageDistributionResource.{}", attribute))?;
 
         let v = match attribute {
+            "size_bucket" => size_bucket_value(
+                &SelectorCandidate::package_distribution_resource(&inner.r),
+                &SizeBucketThresholds::default(),
+                "size_bucket",
+            )?,
+            "is_locked" => Value::from(inner.locked),
+            "will_be_included" => {
+                drop(inner);
+                Value::from(self.will_be_included()?)
+            }
+            "context_hash" => {
+                drop(inner);
+                self.add_collection_context_hash()?
+            }
+            "effective_location" => {
+                drop(inner);
+                self.effective_location_value()?
+            }
+            "distribution_file_kind" => Value::from(inner.r.distribution_file_kind()),
+            "entry_points" => {
+                let entry_points = inner.r.entry_points().map_err(|e| {
+                    ValueError::from(RuntimeError {
+                        code: "PYTHON_PACKAGE_DISTRIBUTION_RESOURCE",
+                        message: format!("error parsing entry points: {:?}", e),
+                        label: "entry_points".to_string(),
+                    })
+                })?;
+
+                match entry_points {
+                    Some(entry_points) => {
+                        let mut groups = Dictionary::default();
+
+                        for (group, entries) in entry_points {
+                            let mut d = Dictionary::default();
+                            for (name, target) in entries {
+                                d.insert(Value::from(name), Value::from(target))?;
+                            }
+
+                            groups.insert(
+                                Value::from(group),
+                                Value::try_from(d.get_content().clone())?,
+                            )?;
+                        }
+
+                        Value::try_from(groups.get_content().clone())?
+                    }
+                    None => Value::from(NoneType::None),
+                }
+            }
+            "is_current_version" => match &inner.selected_version {
+                Some(version) if !inner.r.version.is_empty() => {
+                    Value::from(*version == inner.r.version)
+                }
+                _ => Value::from(NoneType::None),
+            },
             "is_stdlib" => Value::from(false),
             "package" => Value::new(inner.r.package.clone()),
             "name" => Value::new(inner.r.name.clone()),
+            "data_location_kind" => Value::from(inner.r.data.kind()),
             // TODO expose raw data
             attr => {
                 drop(inner);
@@ -139,16 +296,190 @@ impl TypedValue for PythonPackageDistributionResourceValue {
     }
 
     fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
-        Ok(match attribute {
-            "is_stdlib" => true,
-            "package" => true,
-            "name" => true,
-            // TODO expose raw data
-            attr => self.add_collection_context_attrs().contains(&attr),
-        })
+        Ok(self.resource_attrs().contains(&attribute)
+            || self.add_collection_context_attrs().contains(&attribute))
     }
 
     fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
-        self.set_attr_add_collection_context(attribute, value)
+        if self.add_collection_context_attrs().contains(&attribute) {
+            self.set_attr_add_collection_context(attribute, value)
+        } else {
+            Err(unknown_attribute_error(
+                Self::TYPE,
+                attribute,
+                &self.settable_attrs(),
+            ))
+        }
+    }
+}
+
+starlark_module! { python_package_distribution_resource_module =>
+    PythonPackageDistributionResource.add_collection_context(this) {
+        starlark_add_collection_context::<PythonPackageDistributionResourceValue>(&this)
+    }
+
+    PythonPackageDistributionResource.lock(this) {
+        starlark_lock::<PythonPackageDistributionResourceValue>(&this)
+    }
+
+    PythonPackageDistributionResource.set_collection_context(this, context) {
+        starlark_set_collection_context::<PythonPackageDistributionResourceValue>(&this, &context)
+    }
+
+    PythonPackageDistributionResource.duplicate_to_locations(this, locations) {
+        starlark_duplicate_to_locations::<PythonPackageDistributionResourceValue>(&this, &locations)
+    }
+
+    PythonPackageDistributionResource.with_include(this, include: bool) {
+        starlark_with_include::<PythonPackageDistributionResourceValue>(&this, include)
+    }
+
+    PythonPackageDistributionResource.plan_entry(this) {
+        starlark_plan_entry::<PythonPackageDistributionResourceValue>(&this)
+    }
+
+    PythonPackageDistributionResource.settable_attributes(this) {
+        starlark_settable_attributes::<PythonPackageDistributionResourceValue>(&this)
+    }
+
+    PythonPackageDistributionResource.to_canonical_string(this) {
+        starlark_to_canonical_string::<PythonPackageDistributionResourceValue>(&this)
+    }
+
+    PythonPackageDistributionResource.as_struct(this) {
+        starlark_as_struct::<PythonPackageDistributionResourceValue>(&this)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, anyhow::Result,
+        python_packaging::resource::PythonPackageDistributionResourceFlavor,
+        tugger_file_manifest::FileData,
+    };
+
+    fn resource_value_with_data(name: &str, data: &[u8]) -> PythonPackageDistributionResourceValue {
+        PythonPackageDistributionResourceValue::new(PythonPackageDistributionResource {
+            location: PythonPackageDistributionResourceFlavor::DistInfo,
+            package: "foo".to_string(),
+            version: "1.0".to_string(),
+            name: name.to_string(),
+            data: FileData::Memory(data.to_vec()),
+        })
+    }
+
+    fn resource_value(name: &str) -> PythonPackageDistributionResourceValue {
+        resource_value_with_data(name, b"")
+    }
+
+    #[test]
+    fn test_is_current_version() -> Result<()> {
+        let mut policy = PythonPackagingPolicy::default();
+
+        // No version selected.
+        let mut r = resource_value("METADATA");
+        r.capture_policy_state(&policy).unwrap();
+        assert_eq!(
+            r.get_attr("is_current_version").unwrap().get_type(),
+            "NoneType"
+        );
+
+        policy.set_package_version("Foo", "1.0");
+        r.capture_policy_state(&policy).unwrap();
+        assert_eq!(r.get_attr("is_current_version").unwrap(), Value::from(true));
+
+        // The captured version is retained by copies.
+        let copy = r.independent_copy().unwrap();
+        assert_eq!(
+            copy.get_attr("is_current_version").unwrap(),
+            Value::from(true)
+        );
+
+        policy.set_package_version("foo", "2.0");
+        r.capture_policy_state(&policy).unwrap();
+        assert_eq!(
+            r.get_attr("is_current_version").unwrap(),
+            Value::from(false)
+        );
+
+        // Resources without a version can't be compared.
+        r.inner("test").unwrap().r.version = "".to_string();
+        assert_eq!(
+            r.get_attr("is_current_version").unwrap().get_type(),
+            "NoneType"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_distribution_file_kind() -> Result<()> {
+        let r = resource_value("entry_points.txt");
+        assert!(r.has_attr("distribution_file_kind").unwrap());
+        let kind = r.get_attr("distribution_file_kind").unwrap();
+        assert_eq!(kind.get_type(), "string");
+        assert_eq!(kind.to_str(), "entry_points");
+
+        for (name, kind) in &[
+            ("METADATA", "metadata"),
+            ("RECORD", "record"),
+            ("WHEEL", "wheel"),
+            ("top_level.txt", "top_level"),
+            ("LICENSE", "other"),
+        ] {
+            assert_eq!(
+                resource_value(name)
+                    .get_attr("distribution_file_kind")
+                    .unwrap()
+                    .to_str(),
+                *kind
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_location_kind() -> Result<()> {
+        let r = resource_value("METADATA");
+        assert!(r.has_attr("data_location_kind").unwrap());
+        assert_eq!(r.get_attr("data_location_kind").unwrap().to_str(), "memory");
+
+        // Path-backed data isn't resolved.
+        let r = PythonPackageDistributionResourceValue::new(PythonPackageDistributionResource {
+            location: PythonPackageDistributionResourceFlavor::DistInfo,
+            package: "foo".to_string(),
+            version: "1.0".to_string(),
+            name: "METADATA".to_string(),
+            data: FileData::Path("/does/not/exist/METADATA".into()),
+        });
+        assert_eq!(r.get_attr("data_location_kind").unwrap().to_str(), "path");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_points() -> Result<()> {
+        let r = resource_value_with_data(
+            "entry_points.txt",
+            b"[console_scripts]\nfoo = foo.cli:main\nfoo-admin = foo.admin:main\n",
+        );
+        assert!(r.has_attr("entry_points").unwrap());
+
+        let entry_points = r.get_attr("entry_points").unwrap();
+        assert_eq!(entry_points.get_type(), "dict");
+        assert_eq!(
+            entry_points.to_repr(),
+            "{\"console_scripts\": {\"foo\": \"foo.cli:main\", \"foo-admin\": \"foo.admin:main\"}}"
+        );
+
+        let r = resource_value_with_data("METADATA", b"Name: foo\n");
+        assert_eq!(r.get_attr("entry_points").unwrap().get_type(), "NoneType");
+
+        let r = resource_value_with_data("entry_points.txt", b"foo = foo.cli:main\n");
+        assert!(r.get_attr("entry_points").is_err());
+
+        Ok(())
     }
 }
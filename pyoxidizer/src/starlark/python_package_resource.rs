@@ -3,22 +3,152 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    super::python_resource::ResourceCollectionContext,
+    super::{
+        python_resource::{
+            policy_allowed_locations, size_bucket_value, starlark_add_collection_context,
+            starlark_as_struct, starlark_duplicate_to_locations, starlark_lock,
+            starlark_plan_entry, starlark_set_collection_context, starlark_settable_attributes,
+            starlark_to_canonical_string, starlark_with_include, ResourceCollectionContext,
+        },
+        resource_selector::{SelectorCandidate, SizeBucketThresholds},
+        strict::unknown_attribute_error,
+    },
     python_packaging::{
+        location::AbstractResourceLocation,
+        policy::PythonPackagingPolicy,
         resource::{PythonPackageResource, PythonResource},
         resource_collection::PythonResourceAddCollectionContext,
     },
-    starlark::values::{
-        error::{RuntimeError, UnsupportedOperation, ValueError},
-        {Mutable, TypedValue, Value, ValueResult},
+    starlark::{
+        values::{
+            dict::Dictionary,
+            error::{
+                RuntimeError, UnsupportedOperation, ValueError, INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            },
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    std::{
+        convert::TryFrom,
+        sync::{Arc, Mutex, MutexGuard},
     },
-    std::sync::{Arc, Mutex, MutexGuard},
+    tugger_file_manifest::FileData,
 };
 
+/// Whether a resource name is a non-empty path relative to its package.
+///
+/// Names are joined to the package directory when materialized, so absolute
+/// paths and `..` components could escape it.
+fn is_valid_relative_name(name: &str) -> bool {
+    let name = name.replace('\\', "/");
+
+    !name.is_empty()
+        && !name.starts_with('/')
+        && name.chars().nth(1) != Some(':')
+        && name.split('/').all(|part| part != "..")
+}
+
 #[derive(Debug)]
 pub struct PythonPackageResourceWrapper {
     pub r: PythonPackageResource,
     pub add_context: Option<PythonResourceAddCollectionContext>,
+    /// Whether `add_*` attributes can no longer be modified.
+    pub locked: bool,
+    /// Resource locations allowed by the packaging policy.
+    ///
+    /// Captured from the packaging policy when it is applied.
+    pub allowed_locations: Option<Vec<AbstractResourceLocation>>,
+    /// Whether this resource was added to an executable.
+    ///
+    /// `data` and `name` can no longer be modified once set.
+    pub added: bool,
+    /// Cached result of resolving `r.data`.
+    ///
+    /// Must be cleared if `r.data` changes.
+    pub data_cache: Option<Vec<u8>>,
+}
+
+impl PythonPackageResourceWrapper {
+    /// Resolve the content of this resource, caching the result.
+    pub fn resolve_data(&mut self) -> Result<&[u8], std::io::Error> {
+        if self.data_cache.is_none() {
+            self.data_cache = Some(self.r.data.resolve_content()?);
+        }
+
+        Ok(self.data_cache.as_ref().unwrap())
+    }
+
+    /// Resolve at most `limit` bytes from the start of the content of this resource.
+    ///
+    /// Cached content is used if available. Otherwise only the requested bytes
+    /// are read and the cache isn't populated.
+    pub fn resolve_data_prefix(&self, limit: usize) -> Result<Vec<u8>, std::io::Error> {
+        if let Some(data) = &self.data_cache {
+            Ok(data[0..limit.min(data.len())].to_vec())
+        } else {
+            self.r.data.resolve_content_prefix(limit)
+        }
+    }
+}
+
+/// Convert a parsed JSON value to a Starlark value.
+fn json_to_value(value: &serde_json::Value) -> Result<Value, String> {
+    Ok(match value {
+        serde_json::Value::Null => Value::from(NoneType::None),
+        serde_json::Value::Bool(v) => Value::from(*v),
+        serde_json::Value::Number(v) => Value::from(
+            v.as_i64()
+                .ok_or_else(|| format!("{} is not representable as a Starlark int", v))?,
+        ),
+        serde_json::Value::String(v) => Value::from(v.as_str()),
+        serde_json::Value::Array(v) => {
+            Value::from(v.iter().map(json_to_value).collect::<Result<Vec<_>, _>>()?)
+        }
+        serde_json::Value::Object(v) => {
+            let mut d = Dictionary::default();
+
+            for (k, v) in v {
+                d.insert(Value::from(k.as_str()), json_to_value(v)?)
+                    .map_err(|e| format!("{:?}", e))?;
+            }
+
+            Value::try_from(d.get_content().clone()).map_err(|e| format!("{:?}", e))?
+        }
+    })
+}
+
+/// How a package resource is emitted into the built application.
+///
+/// `"compiled"` is reserved for resources transformed during packaging. No
+/// packaging policy transforms package resources, so they are all emitted
+/// verbatim.
+fn emission_kind(_resource: &PythonPackageResource) -> &'static str {
+    "verbatim"
+}
+
+/// Convert a Starlark string or list of byte values to resource data.
+pub fn value_to_data(value: &Value) -> Result<Vec<u8>, String> {
+    match value.get_type() {
+        "string" => Ok(value.to_str().into_bytes()),
+        "list" => value
+            .iter()
+            .map_err(|e| format!("{:?}", e))?
+            .iter()
+            .map(|v| match v.get_type() {
+                "int" if (0..=255).contains(&v.to_int().unwrap()) => Ok(v.to_int().unwrap() as u8),
+                _ => Err(format!(
+                    "expected an int between 0 and 255; got {}",
+                    v.to_repr()
+                )),
+            })
+            .collect(),
+        t => Err(format!("expected a string or list of ints; got {}", t)),
+    }
 }
 
 /// Starlark `Value` wrapper for `PythonPackageResource`.
@@ -38,12 +168,72 @@ impl PythonPackageResourceValue {
             inner: Arc::new(Mutex::new(PythonPackageResourceWrapper {
                 r: resource,
                 add_context: None,
+                locked: false,
+                allowed_locations: None,
+                added: false,
+                data_cache: None,
             })),
             leaf_package,
             relative_name,
         }
     }
 
+    /// PythonPackageResource.json()
+    ///
+    /// Parses the resource content as JSON.
+    pub fn json(&self) -> ValueResult {
+        const LABEL: &str = "PythonPackageResource.json()";
+
+        let mut inner = self.inner(LABEL)?;
+
+        let data = inner.resolve_data().map_err(|e| {
+            ValueError::Runtime(RuntimeError {
+                code: "PYTHON_PACKAGE_RESOURCE",
+                message: format!("error resolving resource data: {}", e),
+                label: LABEL.to_string(),
+            })
+        })?;
+
+        serde_json::from_slice::<serde_json::Value>(data)
+            .map_err(|e| format!("error parsing JSON: {}", e))
+            .and_then(|v| json_to_value(&v))
+            .map_err(|message| {
+                ValueError::Runtime(RuntimeError {
+                    code: "PYTHON_PACKAGE_RESOURCE",
+                    message: format!("{}/{}: {}", self.leaf_package, self.relative_name, message),
+                    label: LABEL.to_string(),
+                })
+            })
+    }
+
+    /// PythonPackageResource.data_preview(n)
+    ///
+    /// Obtains up to the first `n` bytes of the resource content, hex encoded.
+    pub fn data_preview(&self, n: i64) -> ValueResult {
+        const LABEL: &str = "PythonPackageResource.data_preview()";
+
+        if n < 0 {
+            return Err(ValueError::from(RuntimeError {
+                code: "PYTHON_PACKAGE_RESOURCE",
+                message: format!("n must be non-negative; got {}", n),
+                label: LABEL.to_string(),
+            }));
+        }
+
+        let data = self
+            .inner(LABEL)?
+            .resolve_data_prefix(n as usize)
+            .map_err(|e| {
+                ValueError::from(RuntimeError {
+                    code: "PYTHON_PACKAGE_RESOURCE",
+                    message: format!("error resolving resource data: {}", e),
+                    label: LABEL.to_string(),
+                })
+            })?;
+
+        Ok(Value::from(hex::encode(data)))
+    }
+
     pub fn inner(
         &self,
         label: &str,
@@ -59,6 +249,31 @@ impl PythonPackageResourceValue {
 }
 
 impl ResourceCollectionContext for PythonPackageResourceValue {
+    fn resource_attrs(&self) -> Vec<&'static str> {
+        vec![
+            "is_locked",
+            "will_be_included",
+            "context_hash",
+            "effective_location",
+            "data_location_kind",
+            "discovery_root",
+            "data",
+            "data_base64",
+            "emission_kind",
+            "is_json_data",
+            "is_stdlib",
+            "package",
+            "name",
+            "relative_name",
+            "package_depth",
+            "size_bucket",
+        ]
+    }
+
+    fn settable_resource_attrs(&self) -> Vec<&'static str> {
+        vec!["data", "relative_name"]
+    }
+
     fn add_collection_context(
         &self,
     ) -> Result<Option<PythonResourceAddCollectionContext>, ValueError> {
@@ -78,6 +293,26 @@ impl ResourceCollectionContext for PythonPackageResourceValue {
             .replace(context))
     }
 
+    fn independent_copy(&self) -> Result<Self, ValueError> {
+        const LABEL: &str = "PythonPackageResource.independent_copy()";
+
+        let inner = self.inner(LABEL)?;
+
+        let value = PythonPackageResourceValue::new(inner.r.clone());
+        {
+            let mut copy = value.inner(LABEL)?;
+            copy.add_context = inner.add_context.clone();
+            copy.allowed_locations = inner.allowed_locations.clone();
+            copy.data_cache = inner.data_cache.clone();
+        }
+
+        Ok(value)
+    }
+
+    fn independent_copy_value(&self) -> ValueResult {
+        Ok(Value::new(self.independent_copy()?))
+    }
+
     fn as_python_resource(&self) -> Result<PythonResource<'_>, ValueError> {
         Ok(PythonResource::from(
             self.inner("PythonPackageResource.as_python_resource()")?
@@ -85,6 +320,30 @@ impl ResourceCollectionContext for PythonPackageResourceValue {
                 .clone(),
         ))
     }
+
+    fn capture_policy_state(&mut self, policy: &PythonPackagingPolicy) -> Result<(), ValueError> {
+        self.inner("PythonPackageResource.capture_policy_state()")?
+            .allowed_locations = Some(policy_allowed_locations(policy));
+
+        Ok(())
+    }
+
+    fn allowed_locations(&self) -> Result<Option<Vec<AbstractResourceLocation>>, ValueError> {
+        Ok(self
+            .inner("PythonPackageResource.allowed_locations()")?
+            .allowed_locations
+            .clone())
+    }
+
+    fn is_locked(&self) -> Result<bool, ValueError> {
+        Ok(self.inner("PythonPackageResource.is_locked()")?.locked)
+    }
+
+    fn lock(&mut self) -> Result<(), ValueError> {
+        self.inner("PythonPackageResource.lock()")?.locked = true;
+
+        Ok(())
+    }
 }
 
 impl TypedValue for PythonPackageResourceValue {
@@ -109,13 +368,72 @@ impl TypedValue for PythonPackageResourceValue {
     }
 
     fn get_attr(&self, attribute: &str) -> ValueResult {
-        let inner = self.inner(&format!("PythonPackageResource.{}", attribute))?;
+        let mut inner = self.inner(&format!("PythonPackageResource.{}", attribute))?;
 
         let v = match attribute {
+            "size_bucket" => size_bucket_value(
+                &SelectorCandidate::package_resource(&inner.r),
+                &SizeBucketThresholds::default(),
+                "size_bucket",
+            )?,
+            "is_locked" => Value::from(inner.locked),
+            "will_be_included" => {
+                drop(inner);
+                Value::from(self.will_be_included()?)
+            }
+            "context_hash" => {
+                drop(inner);
+                self.add_collection_context_hash()?
+            }
+            "effective_location" => {
+                drop(inner);
+                self.effective_location_value()?
+            }
+            "data" => {
+                let data = inner.resolve_data().map_err(|e| {
+                    ValueError::Runtime(RuntimeError {
+                        code: "PYTHON_PACKAGE_RESOURCE",
+                        message: format!("error resolving resource data: {}", e),
+                        label: "PythonPackageResource.data".to_string(),
+                    })
+                })?;
+
+                let data = String::from_utf8(data.to_vec()).map_err(|_| {
+                    ValueError::Runtime(RuntimeError {
+                        code: "PYTHON_PACKAGE_RESOURCE",
+                        message: "resource data isn't valid UTF-8; use data_base64".to_string(),
+                        label: "PythonPackageResource.data".to_string(),
+                    })
+                })?;
+
+                Value::from(data)
+            }
+            "data_base64" => {
+                let data = inner.resolve_data().map_err(|e| {
+                    ValueError::Runtime(RuntimeError {
+                        code: "PYTHON_PACKAGE_RESOURCE",
+                        message: format!("error resolving resource data: {}", e),
+                        label: "PythonPackageResource.data_base64".to_string(),
+                    })
+                })?;
+
+                Value::from(base64::encode(data))
+            }
+            // Data that can't be resolved isn't valid JSON either.
+            "is_json_data" => Value::from(match inner.resolve_data() {
+                Ok(data) => serde_json::from_slice::<serde::de::IgnoredAny>(data).is_ok(),
+                Err(_) => false,
+            }),
             "is_stdlib" => Value::from(inner.r.is_stdlib),
             "package" => Value::new(inner.r.leaf_package.clone()),
-            "name" => Value::new(inner.r.relative_name.clone()),
-            // TODO expose raw data
+            "name" | "relative_name" => Value::new(inner.r.relative_name.clone()),
+            "package_depth" => Value::from(inner.r.relative_name.matches('/').count() as i64),
+            "data_location_kind" => Value::from(inner.r.data.kind()),
+            "discovery_root" => match inner.r.discovery_root() {
+                Some(root) => Value::from(root.display().to_string()),
+                None => Value::from(NoneType::None),
+            },
+            "emission_kind" => Value::from(emission_kind(&inner.r)),
             attr => {
                 drop(inner);
 
@@ -135,16 +453,542 @@ impl TypedValue for PythonPackageResourceValue {
     }
 
     fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
-        Ok(match attribute {
-            "is_stdlib" => true,
-            "package" => true,
-            "name" => true,
-            // TODO expose raw data
-            attr => self.add_collection_context_attrs().contains(&attr),
-        })
+        Ok(self.resource_attrs().contains(&attribute)
+            || self.add_collection_context_attrs().contains(&attribute))
     }
 
     fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
-        self.set_attr_add_collection_context(attribute, value)
+        let label = format!("PythonPackageResource.{}", attribute);
+
+        if self.settable_resource_attrs().contains(&attribute) {
+            let mut inner = self.inner(&label)?;
+
+            if inner.added {
+                return Err(ValueError::from(RuntimeError {
+                    code: "PYTHON_PACKAGE_RESOURCE",
+                    message: format!(
+                        "cannot set {} after the resource was added to an executable",
+                        attribute
+                    ),
+                    label,
+                }));
+            }
+
+            match attribute {
+                "data" => {
+                    let data = value_to_data(&value).map_err(|message| {
+                        ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message,
+                            label: label.clone(),
+                        })
+                    })?;
+
+                    inner.r.data = FileData::Memory(data);
+                    inner.data_cache = None;
+                }
+                "relative_name" => {
+                    let name = match value.get_type() {
+                        "string" if is_valid_relative_name(&value.to_str()) => value.to_str(),
+                        _ => {
+                            return Err(ValueError::from(RuntimeError {
+                                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                                message: format!(
+                                    "expected a non-empty relative path without .. components; got {}",
+                                    value.to_repr()
+                                ),
+                                label,
+                            }));
+                        }
+                    };
+
+                    inner.r.relative_name = name.clone();
+                    drop(inner);
+                    self.relative_name = name;
+                }
+                _ => panic!("unhandled settable attribute {}", attribute),
+            }
+
+            Ok(())
+        } else if self.add_collection_context_attrs().contains(&attribute) {
+            self.set_attr_add_collection_context(attribute, value)
+        } else {
+            Err(unknown_attribute_error(
+                Self::TYPE,
+                attribute,
+                &self.settable_attrs(),
+            ))
+        }
+    }
+}
+
+starlark_module! { python_package_resource_module =>
+    PythonPackageResource.json(this) {
+        let this = this.downcast_ref::<PythonPackageResourceValue>().unwrap();
+        this.json()
+    }
+
+    PythonPackageResource.data_preview(this, n: i64) {
+        let this = this.downcast_ref::<PythonPackageResourceValue>().unwrap();
+        this.data_preview(n)
+    }
+
+    PythonPackageResource.add_collection_context(this) {
+        starlark_add_collection_context::<PythonPackageResourceValue>(&this)
+    }
+
+    PythonPackageResource.lock(this) {
+        starlark_lock::<PythonPackageResourceValue>(&this)
+    }
+
+    PythonPackageResource.set_collection_context(this, context) {
+        starlark_set_collection_context::<PythonPackageResourceValue>(&this, &context)
+    }
+
+    PythonPackageResource.duplicate_to_locations(this, locations) {
+        starlark_duplicate_to_locations::<PythonPackageResourceValue>(&this, &locations)
+    }
+
+    PythonPackageResource.with_include(this, include: bool) {
+        starlark_with_include::<PythonPackageResourceValue>(&this, include)
+    }
+
+    PythonPackageResource.plan_entry(this) {
+        starlark_plan_entry::<PythonPackageResourceValue>(&this)
+    }
+
+    PythonPackageResource.settable_attributes(this) {
+        starlark_settable_attributes::<PythonPackageResourceValue>(&this)
+    }
+
+    PythonPackageResource.to_canonical_string(this) {
+        starlark_to_canonical_string::<PythonPackageResourceValue>(&this)
+    }
+
+    PythonPackageResource.as_struct(this) {
+        starlark_as_struct::<PythonPackageResourceValue>(&this)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::super::testutil::*, super::*, anyhow::Result, tugger_file_manifest::FileData};
+
+    #[test]
+    fn test_data_base64() -> Result<()> {
+        let data = (0..=255u8).collect::<Vec<_>>();
+
+        let v = Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+            leaf_package: "foo".to_string(),
+            relative_name: "data.bin".to_string(),
+            data: FileData::Memory(data.clone()),
+            is_stdlib: false,
+            is_test: false,
+        }));
+
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.set_var("r", v).unwrap();
+
+        assert!(env.eval("hasattr(r, 'data_base64')")?.to_bool());
+
+        let encoded = env.eval("r.data_base64")?;
+        assert_eq!(encoded.get_type(), "string");
+        assert_eq!(base64::decode(encoded.to_string())?, data);
+
+        // Subsequent reads are served from the data cache.
+        let r = env.get_var("r").unwrap();
+        assert_eq!(
+            r.downcast_ref::<PythonPackageResourceValue>()
+                .unwrap()
+                .inner("test")
+                .unwrap()
+                .data_cache,
+            Some(data)
+        );
+        assert_eq!(env.eval("r.data_base64")?.to_string(), encoded.to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_package_depth() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        for (name, relative_name) in &[("top", "data.txt"), ("nested", "a/b/data.txt")] {
+            env.set_var(
+                name,
+                Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+                    leaf_package: "foo.bar".to_string(),
+                    relative_name: relative_name.to_string(),
+                    data: FileData::Memory(vec![]),
+                    is_stdlib: false,
+                    is_test: false,
+                })),
+            )
+            .unwrap();
+        }
+
+        eval_assert(&mut env, "top.package_depth == 0")?;
+        eval_assert(&mut env, "nested.package_depth == 2")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_location_kind() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        for (name, data) in &[
+            ("memory", FileData::Memory(vec![])),
+            // Path-backed data isn't resolved.
+            ("path", FileData::Path("/does/not/exist/data.txt".into())),
+        ] {
+            env.set_var(
+                name,
+                Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+                    leaf_package: "foo".to_string(),
+                    relative_name: "data.txt".to_string(),
+                    data: data.clone(),
+                    is_stdlib: false,
+                    is_test: false,
+                })),
+            )
+            .unwrap();
+        }
+
+        eval_assert(&mut env, "memory.data_location_kind == 'memory'")?;
+        eval_assert(&mut env, "path.data_location_kind == 'path'")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_discovery_root() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        let root = std::path::PathBuf::from("/site-packages");
+
+        for (name, data) in &[
+            ("memory", FileData::Memory(vec![])),
+            ("path", FileData::Path(root.join("foo").join("data.txt"))),
+        ] {
+            env.set_var(
+                name,
+                Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+                    leaf_package: "foo".to_string(),
+                    relative_name: "data.txt".to_string(),
+                    data: data.clone(),
+                    is_stdlib: false,
+                    is_test: false,
+                })),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            env.eval("path.discovery_root")?.to_string(),
+            root.display().to_string()
+        );
+        eval_assert(&mut env, "memory.discovery_root == None")?;
+        assert!(env.eval("path.discovery_root = '/'").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_emission_kind() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var(
+            "r",
+            Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+                leaf_package: "foo".to_string(),
+                relative_name: "data.txt".to_string(),
+                data: FileData::Path("/does/not/exist/data.txt".into()),
+                is_stdlib: false,
+                is_test: false,
+            })),
+        )
+        .unwrap();
+
+        eval_assert(&mut env, "r.emission_kind == 'verbatim'")?;
+        assert!(env.eval("r.emission_kind = 'compiled'").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_preview() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let path = temp_dir.path().join("image.png");
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\n")?;
+
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        for (name, data) in &[
+            ("path", FileData::Path(path.clone())),
+            ("memory", FileData::Memory(b"\x89PNG\r\n\x1a\n".to_vec())),
+            ("short", FileData::Memory(b"GI".to_vec())),
+        ] {
+            env.set_var(
+                name,
+                Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+                    leaf_package: "foo".to_string(),
+                    relative_name: format!("{}.png", name),
+                    data: data.clone(),
+                    is_stdlib: false,
+                    is_test: false,
+                })),
+            )
+            .unwrap();
+        }
+
+        eval_assert(&mut env, "path.data_preview(4) == '89504e47'")?;
+        eval_assert(&mut env, "memory.data_preview(4) == '89504e47'")?;
+        eval_assert(&mut env, "short.data_preview(4) == '4749'")?;
+        eval_assert(&mut env, "path.data_preview(0) == ''")?;
+
+        // A preview doesn't resolve the whole content.
+        let r = env.get_var("path").unwrap();
+        assert!(r
+            .downcast_ref::<PythonPackageResourceValue>()
+            .unwrap()
+            .inner("test")
+            .unwrap()
+            .data_cache
+            .is_none());
+
+        // Cached content is used once resolved.
+        env.eval("path.data_base64")?;
+        std::fs::remove_file(&path)?;
+        eval_assert(&mut env, "path.data_preview(2) == '8950'")?;
+
+        let err = env.eval("path.data_preview(-1)").unwrap_err().to_string();
+        assert!(err.as_str().contains("n must be non-negative"), "{}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_settable_attributes() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var(
+            "r",
+            Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+                leaf_package: "foo".to_string(),
+                relative_name: "data.txt".to_string(),
+                data: FileData::Memory(vec![]),
+                is_stdlib: false,
+                is_test: false,
+            })),
+        )
+        .unwrap();
+
+        env.eval("attrs = r.settable_attributes()")?;
+        eval_assert(&mut env, "'add_location' in attrs")?;
+        eval_assert(&mut env, "'data' in attrs")?;
+        eval_assert(&mut env, "'relative_name' in attrs")?;
+        eval_assert(&mut env, "'name' not in attrs")?;
+        eval_assert(&mut env, "'package' not in attrs")?;
+        eval_assert(&mut env, "attrs == sorted(attrs)")?;
+
+        // Every settable attribute is readable.
+        eval_assert(&mut env, "all([hasattr(r, a) for a in attrs])")?;
+
+        let err = env.eval("r.package = 'bar'").unwrap_err().to_string();
+        assert!(err.as_str().contains("package"), "{}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_data() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let path = temp_dir.path().join("config.json");
+        std::fs::write(&path, b"{\"default\": true}")?;
+
+        env.set_var(
+            "r",
+            Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+                leaf_package: "foo".to_string(),
+                relative_name: "config.json".to_string(),
+                data: FileData::Path(path),
+                is_stdlib: false,
+                is_test: false,
+            })),
+        )
+        .unwrap();
+
+        eval_assert(&mut env, "r.json() == {'default': True}")?;
+
+        // Replacing data invalidates cached content.
+        env.eval("r.data = '{\"default\": false}'")?;
+        eval_assert(&mut env, "r.data_location_kind == 'memory'")?;
+        eval_assert(&mut env, "r.json() == {'default': False}")?;
+
+        env.eval("r.data = [0, 255]")?;
+        eval_assert(&mut env, "r.data_base64 == 'AP8='")?;
+        assert!(env.eval("r.data").is_err());
+
+        assert!(env.eval("r.data = [256]").is_err());
+        assert!(env.eval("r.data = None").is_err());
+        eval_assert(&mut env, "r.data_base64 == 'AP8='")?;
+
+        env.eval("r.relative_name = 'custom/config.json'")?;
+        eval_assert(&mut env, "r.relative_name == 'custom/config.json'")?;
+        eval_assert(&mut env, "r.name == 'custom/config.json'")?;
+        eval_assert(&mut env, "r.package_depth == 1")?;
+        assert_eq!(
+            env.get_var("r")
+                .unwrap()
+                .downcast_ref::<PythonPackageResourceValue>()
+                .unwrap()
+                .to_repr(),
+            "PythonPackageResource<package=foo, name=custom/config.json>"
+        );
+
+        assert!(env.eval("r.name = 'other.json'").is_err());
+        assert!(env.eval("r.relative_name = ''").is_err());
+        assert!(env.eval("r.relative_name = 42").is_err());
+        assert!(env.eval("r.relative_name = '../escape.json'").is_err());
+        assert!(env
+            .eval("r.relative_name = 'custom/../../escape.json'")
+            .is_err());
+        assert!(env.eval("r.relative_name = '/etc/passwd'").is_err());
+        assert!(env.eval("r.relative_name = 'C:\\\\escape.json'").is_err());
+
+        // Resources added to an executable can no longer be modified.
+        let r = env.get_var("r").unwrap();
+        r.downcast_ref::<PythonPackageResourceValue>()
+            .unwrap()
+            .inner("test")
+            .unwrap()
+            .added = true;
+
+        let err = env.eval("r.data = 'late'").unwrap_err();
+        assert!(err
+            .to_string()
+            .as_str()
+            .contains("cannot set data after the resource was added to an executable"));
+        assert!(env.eval("r.relative_name = 'late.json'").is_err());
+        eval_assert(&mut env, "r.name == 'custom/config.json'")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_json_data() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        for (name, data, valid) in &[
+            ("valid", &b"{\"foo\": [1, 2.5, null, true]}"[..], true),
+            ("scalar", &b" \"foo\"\n"[..], true),
+            ("invalid", &b"{\"foo\": }"[..], false),
+            ("trailing", &b"{} {}"[..], false),
+            ("binary", &b"\xff\xfe"[..], false),
+            ("empty", &b""[..], false),
+        ] {
+            env.set_var(
+                name,
+                Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+                    leaf_package: "foo".to_string(),
+                    relative_name: format!("{}.json", name),
+                    data: FileData::Memory(data.to_vec()),
+                    is_stdlib: false,
+                    is_test: false,
+                })),
+            )
+            .unwrap();
+
+            assert_eq!(
+                env.eval(&format!("{}.is_json_data", name))?.to_bool(),
+                *valid,
+                "{}",
+                name
+            );
+
+            // Resolved data is cached.
+            let r = env.get_var(name).unwrap();
+            assert_eq!(
+                r.downcast_ref::<PythonPackageResourceValue>()
+                    .unwrap()
+                    .inner("test")
+                    .unwrap()
+                    .data_cache
+                    .as_deref(),
+                Some(*data)
+            );
+        }
+
+        let v = Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+            leaf_package: "foo".to_string(),
+            relative_name: "missing.json".to_string(),
+            data: FileData::Path("/does/not/exist.json".into()),
+            is_stdlib: false,
+            is_test: false,
+        }));
+        env.set_var("missing", v).unwrap();
+        assert!(!env.eval("missing.is_json_data")?.to_bool());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_json() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        for (name, data) in &[
+            (
+                "settings",
+                &b"{\"name\": \"foo\", \"debug\": false, \"level\": 3, \"tags\": [\"a\", null], \"nested\": {\"x\": -1}}"[..],
+            ),
+            ("invalid", &b"{\"name\": }"[..]),
+            ("float", &b"[1.5]"[..]),
+        ] {
+            env.set_var(
+                name,
+                Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+                    leaf_package: "foo".to_string(),
+                    relative_name: format!("{}.json", name),
+                    data: FileData::Memory(data.to_vec()),
+                    is_stdlib: false,
+                    is_test: false,
+                })),
+            )
+            .unwrap();
+        }
+
+        env.eval("s = settings.json()")?;
+        assert_eq!(env.eval("type(s)")?.to_string(), "dict");
+        eval_assert(&mut env, "s['name'] == 'foo'")?;
+        eval_assert(&mut env, "s['debug'] == False")?;
+        eval_assert(&mut env, "s['level'] == 3")?;
+        eval_assert(&mut env, "s['tags'] == ['a', None]")?;
+        eval_assert(&mut env, "s['nested'] == {'x': -1}")?;
+
+        let err = env.eval("invalid.json()").unwrap_err().to_string();
+        assert!(
+            err.as_str()
+                .contains("foo/invalid.json: error parsing JSON: expected value"),
+            "{}",
+            err
+        );
+
+        let err = env.eval("float.json()").unwrap_err().to_string();
+        assert!(
+            err.as_str()
+                .contains("1.5 is not representable as a Starlark int"),
+            "{}",
+            err
+        );
+
+        Ok(())
     }
 }
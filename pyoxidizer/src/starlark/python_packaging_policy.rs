@@ -3,11 +3,20 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    super::python_resource::ResourceCollectionContext,
+    super::{
+        env::{get_context, PyOxidizerEnvironmentContext},
+        python_resource::ResourceCollectionContext,
+        strict::unknown_attribute_error,
+    },
     linked_hash_map::LinkedHashMap,
     python_packaging::{
         location::ConcreteResourceLocation,
-        policy::{ExtensionModuleFilter, PythonPackagingPolicy, ResourceHandlingMode},
+        module_util::{is_dotted_identifier, is_identifier},
+        policy::{
+            BytecodeScope, ExtensionModuleFilter, ExtensionModuleVerification, MinimalEncodings,
+            PythonPackagingPolicy, ResourceHandlingMode,
+        },
+        resource::{BytecodeOptimizationLevel, PythonResource},
     },
     starlark::{
         environment::TypeValues,
@@ -20,7 +29,7 @@ use {
             Mutable, TypedValue, Value, ValueResult,
         },
     },
-    starlark_dialect_build_targets::required_type_arg,
+    starlark_dialect_build_targets::arguments::FunctionArgs,
     std::{
         convert::TryFrom,
         ops::Deref,
@@ -34,6 +43,9 @@ pub struct PythonPackagingPolicyValue {
 
     /// Starlark functions to influence PythonResourceAddCollectionContext creation.
     derive_context_callbacks: Vec<Value>,
+
+    /// Packages whose resources are excluded because a feature they require is disabled.
+    feature_excluded_packages: Vec<String>,
 }
 
 impl PythonPackagingPolicyValue {
@@ -41,6 +53,7 @@ impl PythonPackagingPolicyValue {
         Self {
             inner: Arc::new(Mutex::new(inner)),
             derive_context_callbacks: vec![],
+            feature_excluded_packages: vec![],
         }
     }
 
@@ -54,6 +67,11 @@ impl PythonPackagingPolicyValue {
         })
     }
 
+    /// Whether a resource belongs to a package excluded by `require_feature()`.
+    pub fn is_feature_excluded(&self, resource: &PythonResource) -> bool {
+        resource.is_in_packages(&self.feature_excluded_packages)
+    }
+
     /// Apply this policy to a resource.
     ///
     /// This has the effect of replacing the `PythonResourceAddCollectionContext`
@@ -69,9 +87,11 @@ impl PythonPackagingPolicyValue {
     where
         T: TypedValue + ResourceCollectionContext + Clone,
     {
-        let new_context = self
-            .inner(label)?
-            .derive_add_collection_context(&value.as_python_resource()?);
+        let new_context = {
+            let policy = self.inner(label)?;
+            value.capture_policy_state(&policy)?;
+            policy.derive_add_collection_context(&value.as_python_resource()?)
+        };
         value.replace_add_collection_context(new_context)?;
 
         for func in &self.derive_context_callbacks {
@@ -102,10 +122,42 @@ impl PythonPackagingPolicyValue {
             value.replace_add_collection_context(inner.add_collection_context()?.unwrap())?;
         }
 
+        // Applied after callbacks so they can't re-include resources of a
+        // disabled feature.
+        if self.is_feature_excluded(&value.as_python_resource()?) {
+            let mut context = value.add_collection_context()?.unwrap();
+            context.include = false;
+            value.replace_add_collection_context(context)?;
+        }
+
         Ok(Value::from(NoneType::None))
     }
 }
 
+/// Attributes of `PythonPackagingPolicy`.
+const ATTRIBUTES: &[&str] = &[
+    "allow_chunked_resources",
+    "allow_files",
+    "allow_in_memory_shared_library_loading",
+    "bytecode_optimize_level_zero",
+    "bytecode_optimize_level_one",
+    "bytecode_optimize_level_two",
+    "extension_module_filter",
+    "extension_module_verification",
+    "file_scanner_classify_files",
+    "file_scanner_emit_files",
+    "include_distribution_sources",
+    "include_distribution_resources",
+    "include_classified_resources",
+    "include_file_resources",
+    "include_non_distribution_sources",
+    "include_test",
+    "include_test_packages",
+    "preferred_extension_module_variants",
+    "resources_location",
+    "resources_location_fallback",
+];
+
 impl TypedValue for PythonPackagingPolicyValue {
     type Holder = Mutable<PythonPackagingPolicyValue>;
     const TYPE: &'static str = "PythonPackagingPolicy";
@@ -120,6 +172,7 @@ impl TypedValue for PythonPackagingPolicyValue {
         let inner = self.inner(&format!("PythonPackagingPolicy.{}", attribute))?;
 
         let v = match attribute {
+            "allow_chunked_resources" => Value::from(inner.allow_chunked_resources()),
             "allow_files" => Value::from(inner.allow_files()),
             "allow_in_memory_shared_library_loading" => {
                 Value::from(inner.allow_in_memory_shared_library_loading())
@@ -128,6 +181,9 @@ impl TypedValue for PythonPackagingPolicyValue {
             "bytecode_optimize_level_one" => Value::from(inner.bytecode_optimize_level_one()),
             "bytecode_optimize_level_two" => Value::from(inner.bytecode_optimize_level_two()),
             "extension_module_filter" => Value::from(inner.extension_module_filter().as_ref()),
+            "extension_module_verification" => {
+                Value::from(inner.extension_module_verification().as_ref())
+            }
             "file_scanner_classify_files" => Value::from(inner.file_scanner_classify_files()),
             "file_scanner_emit_files" => Value::from(inner.file_scanner_emit_files()),
             "include_distribution_sources" => Value::from(inner.include_distribution_sources()),
@@ -138,6 +194,7 @@ impl TypedValue for PythonPackagingPolicyValue {
                 Value::from(inner.include_non_distribution_sources())
             }
             "include_test" => Value::from(inner.include_test()),
+            "include_test_packages" => Value::from(inner.include_test_packages().to_vec()),
             "preferred_extension_module_variants" => {
                 Value::try_from(inner.preferred_extension_module_variants().clone())?
             }
@@ -159,32 +216,16 @@ impl TypedValue for PythonPackagingPolicyValue {
     }
 
     fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
-        Ok(matches!(
-            attribute,
-            "allow_files"
-                | "allow_in_memory_shared_library_loading"
-                | "bytecode_optimize_level_zero"
-                | "bytecode_optimize_level_one"
-                | "bytecode_optimize_level_two"
-                | "extension_module_filter"
-                | "file_scanner_classify_files"
-                | "file_scanner_emit_files"
-                | "include_distribution_sources"
-                | "include_distribution_resources"
-                | "include_classified_resources"
-                | "include_file_resources"
-                | "include_non_distribution_sources"
-                | "include_test"
-                | "preferred_extension_module_variants"
-                | "resources_location"
-                | "resources_location_fallback"
-        ))
+        Ok(ATTRIBUTES.contains(&attribute))
     }
 
     fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
         let mut inner = self.inner(&format!("PythonPackagingPolicy.{}", attribute))?;
 
         match attribute {
+            "allow_chunked_resources" => {
+                inner.set_allow_chunked_resources(value.to_bool());
+            }
             "allow_files" => {
                 inner.set_allow_files(value.to_bool());
             }
@@ -212,6 +253,18 @@ impl TypedValue for PythonPackagingPolicyValue {
 
                 inner.set_extension_module_filter(filter);
             }
+            "extension_module_verification" => {
+                let mode = ExtensionModuleVerification::try_from(value.to_string().as_str())
+                    .map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: "PYOXIDIZER_BUILD",
+                            message: e,
+                            label: format!("{}.{} = {}", Self::TYPE, attribute, value),
+                        })
+                    })?;
+
+                inner.set_extension_module_verification(mode);
+            }
             "file_scanner_classify_files" => {
                 inner.set_file_scanner_classify_files(value.to_bool());
             }
@@ -236,6 +289,20 @@ impl TypedValue for PythonPackagingPolicyValue {
             "include_test" => {
                 inner.set_include_test(value.to_bool());
             }
+            "include_test_packages" => {
+                let label = format!("{}.{}", Self::TYPE, attribute);
+                let names = FunctionArgs::new(&label).list_of_str("value", &value)?;
+
+                if let Some(name) = names.iter().find(|name| !is_dotted_identifier(name)) {
+                    return Err(ValueError::from(RuntimeError {
+                        code: "PYOXIDIZER_BUILD",
+                        message: format!("{} is not a valid Python module name", name),
+                        label: format!("{}.{}", Self::TYPE, attribute),
+                    }));
+                }
+
+                inner.set_include_test_packages(names);
+            }
             "resources_location" => {
                 inner.set_resources_location(
                     ConcreteResourceLocation::try_from(value.to_string().as_str()).map_err(
@@ -277,11 +344,7 @@ impl TypedValue for PythonPackagingPolicyValue {
                 }
             }
             attr => {
-                return Err(ValueError::OperationNotSupported {
-                    op: UnsupportedOperation::SetAttr(attr.to_string()),
-                    left: Self::TYPE.to_owned(),
-                    right: None,
-                });
+                return Err(unknown_attribute_error(Self::TYPE, attr, ATTRIBUTES));
             }
         }
 
@@ -292,9 +355,35 @@ impl TypedValue for PythonPackagingPolicyValue {
 // Starlark methods.
 impl PythonPackagingPolicyValue {
     fn starlark_register_resource_callback(&mut self, func: &Value) -> ValueResult {
-        required_type_arg("func", "function", func)?;
+        let func = FunctionArgs::new("PythonPackagingPolicy.register_resource_callback()")
+            .required_function("func", func)?;
 
-        self.derive_context_callbacks.push(func.clone());
+        self.derive_context_callbacks.push(func);
+
+        Ok(Value::from(NoneType::None))
+    }
+
+    fn starlark_require_feature(
+        &mut self,
+        type_values: &TypeValues,
+        feature: String,
+        for_packages: &Value,
+    ) -> ValueResult {
+        let for_packages = FunctionArgs::new("PythonPackagingPolicy.require_feature()")
+            .list_of_str("for_packages", for_packages)?;
+
+        let context_value = get_context(type_values)?;
+        let context = context_value
+            .downcast_ref::<PyOxidizerEnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        if !context.features.contains(&feature) {
+            for package in for_packages {
+                if !self.feature_excluded_packages.contains(&package) {
+                    self.feature_excluded_packages.push(package);
+                }
+            }
+        }
 
         Ok(Value::from(NoneType::None))
     }
@@ -311,6 +400,49 @@ impl PythonPackagingPolicyValue {
         Ok(Value::from(NoneType::None))
     }
 
+    fn starlark_set_package_version(&mut self, package: String, version: String) -> ValueResult {
+        self.inner("PythonPackagingPolicy.set_package_version()")?
+            .set_package_version(&package, &version);
+
+        Ok(Value::from(NoneType::None))
+    }
+
+    fn starlark_set_bytecode_levels_for_scope(
+        &mut self,
+        scope: String,
+        levels: &Value,
+    ) -> ValueResult {
+        const LABEL: &str = "PythonPackagingPolicy.set_bytecode_levels_for_scope()";
+
+        let levels = FunctionArgs::new(LABEL).list_of_int("levels", levels)?;
+
+        let scope = BytecodeScope::try_from(scope.as_str()).map_err(|e| {
+            ValueError::from(RuntimeError {
+                code: "PYTHON_PACKAGING_POLICY",
+                message: e,
+                label: LABEL.to_string(),
+            })
+        })?;
+
+        let levels = levels
+            .into_iter()
+            .map(|level| {
+                BytecodeOptimizationLevel::try_from(level as i32).map_err(|e| {
+                    ValueError::from(RuntimeError {
+                        code: "PYTHON_PACKAGING_POLICY",
+                        message: format!("{}: {}", e, level),
+                        label: LABEL.to_string(),
+                    })
+                })
+            })
+            .collect::<Result<Vec<_>, ValueError>>()?;
+
+        self.inner(LABEL)?
+            .set_bytecode_levels_for_scope(scope, &levels);
+
+        Ok(Value::from(NoneType::None))
+    }
+
     fn starlark_set_resource_handling_mode(&mut self, value: String) -> ValueResult {
         const LABEL: &str = "PythonPackagingPolicy.set_resource_handling_mode()";
 
@@ -326,6 +458,39 @@ impl PythonPackagingPolicyValue {
 
         Ok(Value::from(NoneType::None))
     }
+
+    fn starlark_minimal_encodings(
+        &mut self,
+        keep: &Value,
+        exclude_codec_data: bool,
+    ) -> ValueResult {
+        const LABEL: &str = "PythonPackagingPolicy.minimal_encodings()";
+
+        let keep =
+            if let Some(keep) = FunctionArgs::new(LABEL).optional_list_of_str("keep", keep)? {
+                keep
+            } else {
+                vec![
+                    "utf_8".to_string(),
+                    "ascii".to_string(),
+                    "latin_1".to_string(),
+                ]
+            };
+
+        let value = MinimalEncodings::new(&keep, exclude_codec_data);
+
+        if let Some(name) = value.keep.iter().find(|name| !is_identifier(name)) {
+            return Err(ValueError::from(RuntimeError {
+                code: "PYTHON_PACKAGING_POLICY",
+                message: format!("{} is not a valid encodings module name", name),
+                label: LABEL.to_string(),
+            }));
+        }
+
+        self.inner(LABEL)?.set_minimal_encodings(Some(value));
+
+        Ok(Value::from(NoneType::None))
+    }
 }
 
 starlark_module! { python_packaging_policy_module =>
@@ -334,6 +499,11 @@ starlark_module! { python_packaging_policy_module =>
         this.starlark_register_resource_callback(&func)
     }
 
+    PythonPackagingPolicy.require_feature(env env, this, feature: String, for_packages) {
+        let mut this = this.downcast_mut::<PythonPackagingPolicyValue>().unwrap().unwrap();
+        this.starlark_require_feature(env, feature, &for_packages)
+    }
+
     PythonPackagingPolicy.set_preferred_extension_module_variant(
         this,
         name: String,
@@ -343,6 +513,25 @@ starlark_module! { python_packaging_policy_module =>
         this.starlark_set_preferred_extension_module_variant(name, value)
     }
 
+    PythonPackagingPolicy.set_package_version(this, package: String, version: String) {
+        let mut this = this.downcast_mut::<PythonPackagingPolicyValue>().unwrap().unwrap();
+        this.starlark_set_package_version(package, version)
+    }
+
+    PythonPackagingPolicy.set_bytecode_levels_for_scope(this, scope: String, levels) {
+        let mut this = this.downcast_mut::<PythonPackagingPolicyValue>().unwrap().unwrap();
+        this.starlark_set_bytecode_levels_for_scope(scope, &levels)
+    }
+
+    PythonPackagingPolicy.minimal_encodings(
+        this,
+        keep=NoneType::None,
+        exclude_codec_data: bool = false
+    ) {
+        let mut this = this.downcast_mut::<PythonPackagingPolicyValue>().unwrap().unwrap();
+        this.starlark_minimal_encodings(&keep, exclude_codec_data)
+    }
+
     PythonPackagingPolicy.set_resource_handling_mode(this, mode: String) {
         let mut this = this.downcast_mut::<PythonPackagingPolicyValue>().unwrap().unwrap();
         this.starlark_set_resource_handling_mode(mode)
@@ -352,8 +541,19 @@ starlark_module! { python_packaging_policy_module =>
 #[cfg(test)]
 mod tests {
     use {
-        super::super::python_distribution::PythonDistributionValue, super::super::testutil::*,
-        super::*, anyhow::Result,
+        super::super::python_distribution::PythonDistributionValue,
+        super::super::python_executable::PythonExecutableValue,
+        super::super::python_extension_module::PythonExtensionModuleValue,
+        super::super::python_module_source::PythonModuleSourceValue,
+        super::super::python_package_resource::PythonPackageResourceValue,
+        super::super::testutil::*,
+        super::*,
+        anyhow::Result,
+        python_packaging::resource::{
+            PythonExtensionModule, PythonModuleSource, PythonPackageResource,
+        },
+        std::collections::BTreeSet,
+        tugger_file_manifest::FileData,
     };
 
     #[test]
@@ -393,6 +593,16 @@ mod tests {
             env.eval("policy.extension_module_filter = 'minimal'; policy.extension_module_filter")?;
         assert_eq!(value.to_string(), "minimal");
 
+        let value = env.eval("policy.extension_module_verification")?;
+        assert_eq!(value.to_string(), "error");
+        let value = env.eval(
+            "policy.extension_module_verification = 'warn'; policy.extension_module_verification",
+        )?;
+        assert_eq!(value.to_string(), "warn");
+        assert!(env
+            .eval("policy.extension_module_verification = 'invalid'")
+            .is_err());
+
         let value = env.eval("policy.file_scanner_classify_files")?;
         assert_eq!(value.get_type(), "bool");
         assert!(value.to_bool());
@@ -483,6 +693,20 @@ mod tests {
         let value = env.eval("policy.include_test = True; policy.include_test")?;
         assert!(value.to_bool());
 
+        let value = env.eval("policy.include_test_packages")?;
+        assert_eq!(value.get_type(), "list");
+        assert_eq!(value.length().unwrap(), 0);
+
+        let value = env.eval(
+            "policy.include_test_packages = ['test.support', 'test.test_ssl']; policy.include_test_packages",
+        )?;
+        assert_eq!(value.to_string(), "[\"test.support\", \"test.test_ssl\"]");
+
+        assert!(env.eval("policy.include_test_packages = 'test'").is_err());
+        assert!(env
+            .eval("policy.include_test_packages = ['test/x']")
+            .is_err());
+
         let value = env.eval("policy.resources_location")?;
         assert_eq!(value.get_type(), "string");
         assert_eq!(value.to_string(), "in-memory");
@@ -508,6 +732,14 @@ mod tests {
         )?;
         assert_eq!(value.get_type(), "NoneType");
 
+        let value = env.eval("policy.allow_chunked_resources")?;
+        assert_eq!(value.get_type(), "bool");
+        assert!(!value.to_bool());
+
+        let value =
+            env.eval("policy.allow_chunked_resources = True; policy.allow_chunked_resources")?;
+        assert!(value.to_bool());
+
         let value = env.eval("policy.allow_files")?;
         assert_eq!(value.get_type(), "bool");
         assert!(!value.to_bool());
@@ -593,6 +825,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_set_package_version() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("policy = dist.make_python_packaging_policy()")?;
+        env.eval("policy.set_package_version('Foo_Bar', '1.0')")?;
+
+        let policy_value = env.eval("policy")?;
+        let policy = policy_value
+            .downcast_ref::<PythonPackagingPolicyValue>()
+            .unwrap();
+        let inner = policy.inner("ignored").unwrap();
+        assert_eq!(inner.package_version("foo-bar"), Some("1.0"));
+        assert_eq!(inner.package_version("foo.bar"), Some("1.0"));
+        assert_eq!(inner.package_version("other"), None);
+
+        Ok(())
+    }
+
     #[test]
     fn test_register_resource_callback() -> Result<()> {
         let mut env = test_evaluation_context_builder()?.into_context()?;
@@ -632,4 +884,239 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_minimal_encodings() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("policy = dist.make_python_packaging_policy()")?;
+
+        assert!(env.eval("policy.minimal_encodings(keep='utf_8')").is_err());
+        assert!(env
+            .eval("policy.minimal_encodings(keep=['utf_8/x'])")
+            .is_err());
+
+        env.eval("policy.minimal_encodings()")?;
+        {
+            let value = env.get_var("policy").unwrap();
+            let policy = value.downcast_ref::<PythonPackagingPolicyValue>().unwrap();
+            let inner = policy.inner("test").unwrap();
+            let minimal = inner.minimal_encodings().unwrap();
+            assert_eq!(
+                minimal.keep.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+                vec!["aliases", "ascii", "latin_1", "utf_8"]
+            );
+            assert!(!minimal.exclude_codec_data);
+        }
+
+        env.eval("policy.minimal_encodings(keep=['UTF-8'], exclude_codec_data=True)")?;
+        env.eval("exe = dist.to_python_executable('testapp', packaging_policy=policy)")?;
+
+        env.eval("m = exe.make_python_module_source('encodings.cp1252', '')")?;
+        eval_assert(&mut env, "m.add_include == False")?;
+        env.eval("m = exe.make_python_module_source('encodings.utf_8', '')")?;
+        eval_assert(&mut env, "m.add_include == True")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_bytecode_levels_for_scope() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("policy = dist.make_python_packaging_policy()")?;
+
+        assert!(env
+            .eval("policy.set_bytecode_levels_for_scope('stdlib', [3])")
+            .is_err());
+        assert!(env
+            .eval("policy.set_bytecode_levels_for_scope('stdlib', 2)")
+            .is_err());
+        assert!(env
+            .eval("policy.set_bytecode_levels_for_scope('foo[', [2])")
+            .is_err());
+
+        env.eval("policy.set_bytecode_levels_for_scope('stdlib', [2])")?;
+        env.eval("exe = dist.to_python_executable('testapp', packaging_policy=policy)")?;
+
+        env.eval("m = exe.make_python_module_source('foo', 'import os')")?;
+        eval_assert(&mut env, "m.add_bytecode_optimization_level_zero == True")?;
+        eval_assert(&mut env, "m.add_bytecode_optimization_level_two == False")?;
+        env.eval("exe.add_python_resource(m)")?;
+
+        // Derived settings can still be overridden.
+        env.eval("o = exe.make_python_module_source('bar', 'import os')")?;
+        env.eval("o.add_bytecode_optimization_level_two = True")?;
+        env.eval("exe.add_python_resource(o)")?;
+
+        let exe = env.eval("exe")?;
+        let exe = exe.downcast_ref::<PythonExecutableValue>().unwrap();
+        let inner = exe.inner("test").unwrap();
+
+        let mut stdlib_count = 0;
+        for (name, resource) in inner.iter_resources() {
+            match name.as_str() {
+                "foo" => {
+                    assert!(resource.in_memory_bytecode.is_some());
+                    assert!(resource.in_memory_bytecode_opt2.is_none());
+                }
+                "bar" => {
+                    assert!(resource.in_memory_bytecode.is_some());
+                    assert!(resource.in_memory_bytecode_opt2.is_some());
+                }
+                _ if resource.is_module && resource.in_memory_source.is_some() => {
+                    stdlib_count += 1;
+                    assert!(resource.in_memory_bytecode.is_none(), "{}", name);
+                    assert!(resource.in_memory_bytecode_opt1.is_none(), "{}", name);
+                    assert!(resource.in_memory_bytecode_opt2.is_some(), "{}", name);
+                }
+                _ => {}
+            }
+        }
+        assert!(stdlib_count > 0);
+
+        Ok(())
+    }
+
+    /// Names of modules a policy would include, evaluated with the given features.
+    fn included_with_features(features: Vec<String>) -> Result<BTreeSet<String>> {
+        let mut env = test_evaluation_context_builder()?
+            .features(features)
+            .into_context()?;
+
+        env.set_var(
+            "policy",
+            Value::new(PythonPackagingPolicyValue::new(
+                PythonPackagingPolicy::default(),
+            )),
+        )
+        .unwrap();
+        env.eval("policy.require_feature('pro', for_packages = ['analytics', 'reporting'])")?;
+
+        let policy_value = env.eval("policy")?;
+        let policy = policy_value
+            .downcast_ref::<PythonPackagingPolicyValue>()
+            .unwrap();
+
+        let mut included = BTreeSet::new();
+
+        for name in &[
+            "app",
+            "analytics",
+            "analytics.core",
+            "analyticsx",
+            "reporting.pdf",
+        ] {
+            let mut m = PythonModuleSourceValue::new(PythonModuleSource {
+                name: name.to_string(),
+                source: FileData::Memory(vec![]),
+                is_package: false,
+                cache_tag: "cpython-39".to_string(),
+                is_stdlib: false,
+                is_test: false,
+            });
+            policy
+                .apply_to_resource(
+                    "test",
+                    &TypeValues::default(),
+                    &mut CallStack::default(),
+                    &mut m,
+                )
+                .unwrap();
+
+            if m.will_be_included().unwrap() {
+                included.insert(name.to_string());
+            }
+        }
+
+        Ok(included)
+    }
+
+    #[test]
+    fn test_require_feature() -> Result<()> {
+        let lite = included_with_features(vec![])?;
+        let pro = included_with_features(vec!["pro".to_string()])?;
+
+        assert_eq!(lite.iter().collect::<Vec<_>>(), vec!["analyticsx", "app"]);
+        assert_eq!(
+            pro.difference(&lite).collect::<Vec<_>>(),
+            vec!["analytics", "analytics.core", "reporting.pdf"]
+        );
+
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.set_var(
+            "policy",
+            Value::new(PythonPackagingPolicyValue::new(
+                PythonPackagingPolicy::default(),
+            )),
+        )
+        .unwrap();
+        assert!(env
+            .eval("policy.require_feature('pro', 'analytics')")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_require_feature_executable() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("policy = dist.make_python_packaging_policy()")?;
+        env.eval("policy.require_feature('pro', for_packages = ['analytics'])")?;
+        env.eval("exe = dist.to_python_executable('testapp', packaging_policy = policy)")?;
+
+        // Re-including a resource doesn't override the feature requirement.
+        env.eval("m = exe.make_python_module_source('analytics', '', True)")?;
+        env.eval("m.add_include = True")?;
+        env.eval("exe.add_python_resource(m)")?;
+
+        let extension = PythonExtensionModule {
+            name: "analytics._speedups".to_string(),
+            init_fn: Some("PyInit__speedups".to_string()),
+            extension_file_suffix: ".so".to_string(),
+            shared_library: Some(FileData::Memory(vec![])),
+            object_file_data: vec![],
+            is_package: false,
+            link_libraries: vec![],
+            is_stdlib: false,
+            builtin_default: false,
+            required: false,
+            variant: None,
+            license: None,
+        };
+        env.set_var("em", Value::new(PythonExtensionModuleValue::new(extension)))
+            .unwrap();
+        env.eval("exe.add_python_resource(em)")?;
+
+        env.set_var(
+            "r",
+            Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+                leaf_package: "analytics".to_string(),
+                relative_name: "data.json".to_string(),
+                data: FileData::Memory(b"{}".to_vec()),
+                is_stdlib: false,
+                is_test: false,
+            })),
+        )
+        .unwrap();
+        env.eval("exe.add_python_resource(r)")?;
+
+        env.eval("exe.add_python_resource(exe.make_python_module_source('app', ''))")?;
+
+        let exe = env.eval("exe")?;
+        let exe = exe.downcast_ref::<PythonExecutableValue>().unwrap();
+        let inner = exe.inner("test").unwrap();
+        let names = inner
+            .iter_resources()
+            .map(|(k, _)| k.clone())
+            .collect::<Vec<_>>();
+
+        assert!(names.contains(&"app".to_string()));
+        assert!(!names.iter().any(|name| name.starts_with("analytics")));
+
+        Ok(())
+    }
 }
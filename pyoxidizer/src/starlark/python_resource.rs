@@ -4,30 +4,65 @@
 
 use {
     super::{
-        file::FileValue, python_extension_module::PythonExtensionModuleValue,
+        arguments::ResourceArgs,
+        file::FileValue,
+        python_extension_module::PythonExtensionModuleValue,
         python_module_source::PythonModuleSourceValue,
         python_package_distribution_resource::PythonPackageDistributionResourceValue,
         python_package_resource::PythonPackageResourceValue,
         python_packaging_policy::PythonPackagingPolicyValue,
+        resource_selector::{SelectorCandidate, SizeBucketThresholds},
+        structs::StructValue,
     },
+    anyhow::Context,
+    linked_hash_map::LinkedHashMap,
     python_packaging::{
-        location::ConcreteResourceLocation, resource::PythonResource,
+        distribution_files::DistributionFileIndex,
+        location::{AbstractResourceLocation, ConcreteResourceLocation},
+        module_util::is_dotted_identifier,
+        package_metadata::{normalize_distribution_name, PythonPackageMetadata},
+        policy::PythonPackagingPolicy,
+        resource::PythonResource,
         resource_collection::PythonResourceAddCollectionContext,
     },
+    sha2::{Digest, Sha256},
     starlark::{
         environment::TypeValues,
         eval::call_stack::CallStack,
         values::{
+            dict::Dictionary,
             error::{
                 RuntimeError, UnsupportedOperation, ValueError, INCORRECT_PARAMETER_TYPE_ERROR_CODE,
             },
             none::NoneType,
-            {Value, ValueResult},
+            {Mutable, TypedValue, Value, ValueResult},
         },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::arguments::FunctionArgs,
+    std::{
+        collections::{BTreeMap, BTreeSet, HashSet},
+        convert::TryFrom,
     },
-    std::convert::{TryFrom, TryInto},
 };
 
+/// Starlark type names of values representing Python resources.
+pub const RESOURCE_VALUE_TYPES: &[&str] = &[
+    "File",
+    "PythonExtensionModule",
+    "PythonModuleSource",
+    "PythonPackageDistributionResource",
+    "PythonPackageResource",
+];
+
+/// Whether a Starlark value represents a Python resource.
+pub fn is_resource_value(value: &Value) -> bool {
+    RESOURCE_VALUE_TYPES.contains(&value.get_type())
+}
+
 #[derive(Clone, Debug)]
 pub struct OptionalResourceLocation {
     inner: Option<ConcreteResourceLocation>,
@@ -98,8 +133,41 @@ impl From<OptionalResourceLocation> for Option<ConcreteResourceLocation> {
     }
 }
 
+/// Obtain a resource location argument that can be `None`.
+pub fn location_arg(
+    args: &FunctionArgs,
+    name: &str,
+    value: &Value,
+) -> Result<OptionalResourceLocation, ValueError> {
+    match args.optional_str(name, value)? {
+        Some(s) => OptionalResourceLocation::try_from(s.as_str()),
+        None => Ok(OptionalResourceLocation { inner: None }),
+    }
+}
+
+/// Resolve the resource locations a packaging policy allows.
+///
+/// These are the locations a resource collector created from the policy
+/// accepts resources in.
+pub fn policy_allowed_locations(policy: &PythonPackagingPolicy) -> Vec<AbstractResourceLocation> {
+    let mut locations = vec![AbstractResourceLocation::from(policy.resources_location())];
+    if let Some(fallback) = policy.resources_location_fallback() {
+        locations.push(AbstractResourceLocation::from(fallback));
+    }
+
+    locations
+}
+
 /// Defines functionality for exposing `PythonResourceAddCollectionContext` from a type.
 pub trait ResourceCollectionContext {
+    /// Names of the readable attributes of this type not defined by the add collection context.
+    fn resource_attrs(&self) -> Vec<&'static str>;
+
+    /// Names of the settable attributes of this type not defined by the add collection context.
+    fn settable_resource_attrs(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
     /// Obtain the `PythonResourceAddCollectionContext` associated with this instance, if available.
     fn add_collection_context(
         &self,
@@ -113,9 +181,103 @@ pub trait ResourceCollectionContext {
         add_context: PythonResourceAddCollectionContext,
     ) -> Result<Option<PythonResourceAddCollectionContext>, ValueError>;
 
+    /// Create a copy of this instance that shares no state with it.
+    ///
+    /// The copy is not locked.
+    fn independent_copy(&self) -> Result<Self, ValueError>
+    where
+        Self: Sized;
+
+    /// Create a Starlark value holding a copy of this instance that shares no state with it.
+    ///
+    /// The copy is not locked.
+    fn independent_copy_value(&self) -> ValueResult;
+
     /// Cast this instance to a `PythonResource`.
     fn as_python_resource(&self) -> Result<PythonResource, ValueError>;
 
+    /// Whether the add collection context attributes of this instance are locked.
+    fn is_locked(&self) -> Result<bool, ValueError>;
+
+    /// Lock the add collection context attributes of this instance.
+    ///
+    /// Once locked, attempts to set these attributes result in an error.
+    fn lock(&mut self) -> Result<(), ValueError>;
+
+    /// Whether this resource is added to a collection regardless of its `include` flag.
+    fn force_include(&self) -> bool {
+        false
+    }
+
+    /// Capture state from a packaging policy being applied to this instance.
+    fn capture_policy_state(&mut self, _policy: &PythonPackagingPolicy) -> Result<(), ValueError> {
+        Ok(())
+    }
+
+    /// Resource locations allowed by the packaging policy applied to this instance.
+    ///
+    /// `None` if no policy was applied.
+    fn allowed_locations(&self) -> Result<Option<Vec<AbstractResourceLocation>>, ValueError>;
+
+    /// Whether this resource can be stored in a location, regardless of policy.
+    fn supports_location(&self, _location: &ConcreteResourceLocation) -> Result<bool, ValueError> {
+        Ok(true)
+    }
+
+    /// Predict the location this resource is added to.
+    ///
+    /// This mirrors the resource collector: the primary location is used if
+    /// usable. Otherwise the fallback location is used if usable. Returns
+    /// `None` if there is no add collection context or no usable location.
+    fn effective_location(&self) -> Result<Option<ConcreteResourceLocation>, ValueError> {
+        let context = match self.add_collection_context()? {
+            Some(context) => context,
+            None => return Ok(None),
+        };
+
+        let allowed = self.allowed_locations()?;
+
+        for location in std::iter::once(context.location).chain(context.location_fallback) {
+            let policy_allows = match &allowed {
+                Some(allowed) => allowed.contains(&AbstractResourceLocation::from(&location)),
+                None => true,
+            };
+
+            if policy_allows && self.supports_location(&location)? {
+                return Ok(Some(location));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Obtain the Starlark value of [Self::effective_location].
+    fn effective_location_value(&self) -> ValueResult {
+        Ok(match self.effective_location()? {
+            Some(location) => Value::from(location.to_string()),
+            None => Value::from(NoneType::None),
+        })
+    }
+
+    /// Whether this resource will be included when added to a collection.
+    ///
+    /// This is the authoritative answer combining the presence of an add
+    /// collection context, its `include` flag, and `force_include()`.
+    fn will_be_included(&self) -> Result<bool, ValueError> {
+        Ok(match self.add_collection_context()? {
+            Some(context) => context.include || self.force_include(),
+            None => false,
+        })
+    }
+
+    /// Names of all settable attributes of this type.
+    fn settable_attrs(&self) -> Vec<&'static str> {
+        let mut attrs = self.settable_resource_attrs();
+        attrs.extend(self.add_collection_context_attrs());
+
+        attrs
+    }
+
     /// Obtains the Starlark object attributes that are defined by the add collection context.
     fn add_collection_context_attrs(&self) -> Vec<&'static str> {
         vec![
@@ -161,84 +323,212 @@ pub trait ResourceCollectionContext {
         })
     }
 
+    /// Obtain a SHA-256 hex digest over all add collection context attributes.
+    ///
+    /// Returns `None` if this instance doesn't have an add collection context.
+    fn add_collection_context_hash(&self) -> ValueResult {
+        if self.add_collection_context()?.is_none() {
+            return Ok(Value::from(NoneType::None));
+        }
+
+        let mut attrs = self.add_collection_context_attrs();
+        attrs.sort_unstable();
+
+        let mut hasher = Sha256::new();
+        for attr in attrs {
+            let value = self.get_attr_add_collection_context(attr)?;
+            hasher.update(format!("{}={}\n", attr, value.to_repr()).as_bytes());
+        }
+
+        Ok(Value::from(hex::encode(hasher.finalize())))
+    }
+
     fn set_attr_add_collection_context(
         &mut self,
         attribute: &str,
         value: Value,
     ) -> Result<(), ValueError> {
-        let mut context = self.add_collection_context()?;
-
-        match context {
-            Some(ref mut context) => {
-                match attribute {
-                    "add_bytecode_optimization_level_zero" => {
-                        context.optimize_level_zero = value.to_bool();
-                        Ok(())
-                    }
-                    "add_bytecode_optimization_level_one" => {
-                        context.optimize_level_one = value.to_bool();
-                        Ok(())
-                    }
-                    "add_bytecode_optimization_level_two" => {
-                        context.optimize_level_two = value.to_bool();
-                        Ok(())
-                    }
-                    "add_include" => {
-                        context.include = value.to_bool();
-                        Ok(())
-                    }
-                    "add_location" => {
-                        let location: OptionalResourceLocation = (&value).try_into()?;
-
-                        match location.inner {
-                            Some(location) => {
-                                context.location = location;
-
-                                Ok(())
-                            }
-                            None => {
-                                Err(ValueError::OperationNotSupported {
-                                    op: UnsupportedOperation::SetAttr(attribute.to_string()),
-                                    left: "set_attr".to_string(),
-                                    right: None,
-                                })
-                            }
-                        }
-                    }
-                    "add_location_fallback" => {
-                        let location: OptionalResourceLocation = (&value).try_into()?;
-
-                        match location.inner {
-                            Some(location) => {
-                                context.location_fallback = Some(location);
-                                Ok(())
-                            }
-                            None => {
-                                context.location_fallback = None;
-                                Ok(())
-                            }
-                        }
-                    }
-                    "add_source" => {
-                        context.store_source = value.to_bool();
-                        Ok(())
-                    }
-                    attr => panic!("set_attr_add_collection_context({}) called when it shouldn't have been", attr)
-                }
-            },
-            None => Err(ValueError::from(RuntimeError {
+        if self.is_locked()? {
+            return Err(ValueError::from(RuntimeError {
+                code: "PYOXIDIZER",
+                message: format!(
+                    "cannot set {} on a locked resource; lock() was previously called",
+                    attribute
+                ),
+                label: "setattr()".to_string(),
+            }));
+        }
+
+        let mut context = self.add_collection_context()?.ok_or_else(|| {
+            ValueError::from(RuntimeError {
                 code: "PYOXIDIZER",
                 message: "attempting to set a collection context attribute on an object without a context".to_string(),
-                label: "setattr()".to_string()
-            }))
-        }?;
+                label: "setattr()".to_string(),
+            })
+        })?;
+
+        apply_add_collection_context_attr(&mut context, attribute, &value)?;
+
+        self.replace_add_collection_context(context)?;
+
+        Ok(())
+    }
+
+    /// Obtain a dict of all add collection context attributes.
+    ///
+    /// Returns `None` if this instance doesn't have an add collection context.
+    fn add_collection_context_dict(&self) -> ValueResult {
+        if self.add_collection_context()?.is_none() {
+            return Ok(Value::from(NoneType::None));
+        }
+
+        let mut d = Dictionary::default();
+        for attr in self.add_collection_context_attrs() {
+            d.insert(
+                Value::from(attr),
+                self.get_attr_add_collection_context(attr)?,
+            )?;
+        }
+
+        Value::try_from(d.get_content().clone())
+    }
+
+    /// Set add collection context attributes from a dict.
+    ///
+    /// Keys are names of add collection context attributes. Attributes not
+    /// present in the dict retain their value. All values are validated before
+    /// any is applied, so an error leaves this instance unchanged.
+    fn set_add_collection_context_dict(&mut self, value: &Value) -> Result<(), ValueError> {
+        const LABEL: &str = "set_collection_context()";
+
+        if value.get_type() != "dict" {
+            return Err(ValueError::from(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!("expected a dict; got {}", value.get_type()),
+                label: LABEL.to_string(),
+            }));
+        }
+
+        if self.is_locked()? {
+            return Err(ValueError::from(RuntimeError {
+                code: "PYOXIDIZER",
+                message: "cannot set the collection context of a locked resource; lock() was previously called".to_string(),
+                label: LABEL.to_string(),
+            }));
+        }
+
+        let mut context = self.add_collection_context()?.ok_or_else(|| {
+            ValueError::from(RuntimeError {
+                code: "PYOXIDIZER",
+                message: "cannot set the collection context of a resource without one".to_string(),
+                label: LABEL.to_string(),
+            })
+        })?;
+
+        let valid = self.add_collection_context_attrs();
+
+        for key in &value.iter()? {
+            let attribute = key.to_str();
+
+            if key.get_type() != "string" || !valid.contains(&attribute.as_str()) {
+                return Err(ValueError::from(RuntimeError {
+                    code: "PYOXIDIZER",
+                    message: format!(
+                        "unknown collection context key {}; valid keys are {}",
+                        key.to_repr(),
+                        valid.join(", ")
+                    ),
+                    label: LABEL.to_string(),
+                }));
+            }
+
+            let attr_value = value.at(key)?;
 
-        self.replace_add_collection_context(context.unwrap())?;
+            if attribute != "add_location"
+                && attribute != "add_location_fallback"
+                && attr_value.get_type() != "bool"
+            {
+                return Err(ValueError::from(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: format!(
+                        "{} must be a bool; got {}",
+                        attribute,
+                        attr_value.get_type()
+                    ),
+                    label: LABEL.to_string(),
+                }));
+            }
+
+            apply_add_collection_context_attr(&mut context, &attribute, &attr_value)?;
+        }
+
+        self.replace_add_collection_context(context)?;
 
         Ok(())
     }
 }
 
+/// Set an add collection context attribute on a [PythonResourceAddCollectionContext].
+///
+/// Panics if `attribute` isn't an add collection context attribute.
+fn apply_add_collection_context_attr(
+    context: &mut PythonResourceAddCollectionContext,
+    attribute: &str,
+    value: &Value,
+) -> Result<(), ValueError> {
+    let args = FunctionArgs::new("setattr()");
+
+    match attribute {
+        "add_bytecode_optimization_level_zero" => {
+            context.optimize_level_zero = args.required_bool(attribute, value)?;
+            Ok(())
+        }
+        "add_bytecode_optimization_level_one" => {
+            context.optimize_level_one = args.required_bool(attribute, value)?;
+            Ok(())
+        }
+        "add_bytecode_optimization_level_two" => {
+            context.optimize_level_two = args.required_bool(attribute, value)?;
+            Ok(())
+        }
+        "add_include" => {
+            context.include = args.required_bool(attribute, value)?;
+            Ok(())
+        }
+        "add_location" => {
+            let location = location_arg(&args, attribute, value)?;
+
+            match location.inner {
+                Some(location) => {
+                    context.location = location;
+
+                    Ok(())
+                }
+                None => Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attribute.to_string()),
+                    left: "set_attr".to_string(),
+                    right: None,
+                }),
+            }
+        }
+        "add_location_fallback" => {
+            let location = location_arg(&args, attribute, value)?;
+
+            context.location_fallback = location.inner;
+
+            Ok(())
+        }
+        "add_source" => {
+            context.store_source = args.required_bool(attribute, value)?;
+            Ok(())
+        }
+        attr => panic!(
+            "apply_add_collection_context_attr({}) called when it shouldn't have been",
+            attr
+        ),
+    }
+}
+
 /// Whether a `PythonResource` can be converted to a Starlark value.
 pub fn is_resource_starlark_compatible(resource: &PythonResource) -> bool {
     match resource {
@@ -336,3 +626,2442 @@ pub fn add_context_for_value(
         })),
     }
 }
+
+/// Collect resource values from an iterable Starlark value.
+///
+/// Errors if any member is not a resource value.
+fn resource_values(values: &Value, label: &str) -> Result<Vec<Value>, ValueError> {
+    FunctionArgs::new(label).resource_list("values", values)
+}
+
+/// Call a function with the `ResourceCollectionContext` of a resource value.
+///
+/// Errors if the value isn't one of `RESOURCE_VALUE_TYPES`.
+fn with_resource_context<R>(
+    value: &Value,
+    label: &str,
+    f: impl FnOnce(&dyn ResourceCollectionContext) -> Result<R, ValueError>,
+) -> Result<R, ValueError> {
+    match value.get_type() {
+        "PythonModuleSource" => f(&*value.downcast_ref::<PythonModuleSourceValue>().unwrap()),
+        "PythonPackageResource" => f(&*value.downcast_ref::<PythonPackageResourceValue>().unwrap()),
+        "PythonPackageDistributionResource" => f(&*value
+            .downcast_ref::<PythonPackageDistributionResourceValue>()
+            .unwrap()),
+        "PythonExtensionModule" => f(&*value.downcast_ref::<PythonExtensionModuleValue>().unwrap()),
+        "File" => f(&*value.downcast_ref::<FileValue>().unwrap()),
+        t => Err(FunctionArgs::new(label).type_error("value", "a Python resource", t)),
+    }
+}
+
+/// Call a function with the mutable `ResourceCollectionContext` of a resource value.
+///
+/// Errors if the value isn't one of `RESOURCE_VALUE_TYPES`.
+fn with_resource_context_mut<R>(
+    value: &Value,
+    label: &str,
+    f: impl FnOnce(&mut dyn ResourceCollectionContext) -> Result<R, ValueError>,
+) -> Result<R, ValueError> {
+    match value.get_type() {
+        "PythonModuleSource" => f(&mut *value.downcast_mut::<PythonModuleSourceValue>()?.unwrap()),
+        "PythonPackageResource" => {
+            f(&mut *value.downcast_mut::<PythonPackageResourceValue>()?.unwrap())
+        }
+        "PythonPackageDistributionResource" => f(&mut *value
+            .downcast_mut::<PythonPackageDistributionResourceValue>()?
+            .unwrap()),
+        "PythonExtensionModule" => {
+            f(&mut *value.downcast_mut::<PythonExtensionModuleValue>()?.unwrap())
+        }
+        "File" => f(&mut *value.downcast_mut::<FileValue>()?.unwrap()),
+        t => Err(FunctionArgs::new(label).type_error("value", "a Python resource", t)),
+    }
+}
+
+/// resource_type_histogram(values)
+fn starlark_resource_type_histogram(values: &Value) -> ValueResult {
+    let mut counts: BTreeMap<&'static str, i64> = BTreeMap::new();
+
+    for value in &values.iter()? {
+        if is_resource_value(&value) {
+            *counts.entry(value.get_type()).or_default() += 1;
+        }
+    }
+
+    let mut d = Dictionary::default();
+    for (k, v) in counts {
+        d.insert(Value::from(k), Value::from(v))?;
+    }
+
+    Value::try_from(d.get_content().clone())
+}
+
+/// extension_module_names(values)
+fn starlark_extension_module_names(values: &Value) -> ValueResult {
+    const LABEL: &str = "extension_module_names()";
+
+    let mut names = vec![];
+
+    for value in &values.iter()? {
+        if let Some(em) = value.downcast_ref::<PythonExtensionModuleValue>() {
+            names.push(em.inner(LABEL)?.em.name.clone());
+        }
+    }
+
+    names.sort();
+
+    Ok(Value::from(
+        names.into_iter().map(Value::from).collect::<Vec<_>>(),
+    ))
+}
+
+/// importable_names(values)
+///
+/// Returns the sorted top-level names importable from modules, extension
+/// modules and package resources in `values`. Other values are ignored.
+fn starlark_importable_names(values: &Value) -> ValueResult {
+    const LABEL: &str = "importable_names()";
+
+    let mut names = BTreeSet::new();
+
+    for value in &values.iter()? {
+        let name = if let Some(m) = value.downcast_ref::<PythonModuleSourceValue>() {
+            m.inner(LABEL)?.m.name.clone()
+        } else if let Some(em) = value.downcast_ref::<PythonExtensionModuleValue>() {
+            em.inner(LABEL)?.em.name.clone()
+        } else if let Some(r) = value.downcast_ref::<PythonPackageResourceValue>() {
+            r.inner(LABEL)?.r.leaf_package.clone()
+        } else {
+            continue;
+        };
+
+        if let Some(top) = name.split('.').next() {
+            if !top.is_empty() {
+                names.insert(top.to_string());
+            }
+        }
+    }
+
+    Ok(Value::from(
+        names.into_iter().map(Value::from).collect::<Vec<_>>(),
+    ))
+}
+
+/// missing_resources(values, required_names)
+///
+/// Returns names in `required_names` not matching the full name of any
+/// resource in `values`, in the order given. Values that aren't resources
+/// are ignored.
+fn starlark_missing_resources(values: &Value, required_names: &Value) -> ValueResult {
+    const LABEL: &str = "missing_resources()";
+
+    let mut present = BTreeSet::new();
+    for value in &values.iter()? {
+        if is_resource_value(&value) {
+            present.insert(resource_value_full_name(&value)?);
+        }
+    }
+
+    let mut seen = BTreeSet::new();
+    let mut missing = vec![];
+
+    for name in FunctionArgs::new(LABEL).list_of_str("required_names", required_names)? {
+        if !present.contains(&name) && seen.insert(name.clone()) {
+            missing.push(Value::from(name));
+        }
+    }
+
+    Ok(Value::from(missing))
+}
+
+/// validate_module_names(values)
+///
+/// Returns the names of modules in `values` having a component that isn't a
+/// valid Python identifier, in the order given. Other values are ignored.
+fn starlark_validate_module_names(values: &Value) -> ValueResult {
+    const LABEL: &str = "validate_module_names()";
+
+    let mut invalid = vec![];
+
+    for value in &values.iter()? {
+        let name = if let Some(m) = value.downcast_ref::<PythonModuleSourceValue>() {
+            m.inner(LABEL)?.m.name.clone()
+        } else if let Some(em) = value.downcast_ref::<PythonExtensionModuleValue>() {
+            em.inner(LABEL)?.em.name.clone()
+        } else {
+            continue;
+        };
+
+        if !is_dotted_identifier(&name) {
+            invalid.push(Value::from(name));
+        }
+    }
+
+    Ok(Value::from(invalid))
+}
+
+/// prefix_module_names(values, prefix)
+///
+/// Returns the number of renamed modules. A value appearing multiple times
+/// is only renamed once.
+fn starlark_prefix_module_names(values: &Value, prefix: &Value) -> ValueResult {
+    const LABEL: &str = "prefix_module_names()";
+
+    let prefix = FunctionArgs::new(LABEL).required_str("prefix", prefix)?;
+
+    if !is_dotted_identifier(&prefix) {
+        return Err(ValueError::from(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message: format!(
+                "prefix must be a dotted sequence of Python identifiers; got {}",
+                prefix
+            ),
+            label: LABEL.to_string(),
+        }));
+    }
+
+    let mut seen = vec![];
+    let mut count = 0;
+
+    for value in &values.iter()? {
+        let ptr = value.data_ptr();
+        if seen.contains(&ptr) {
+            continue;
+        }
+        seen.push(ptr);
+
+        // Only values of other types can be mutably downcast.
+        match value.get_type() {
+            "PythonModuleSource" => {
+                let mut m = value.downcast_mut::<PythonModuleSourceValue>()?.unwrap();
+                let name = format!("{}.{}", prefix, m.inner(LABEL)?.m.name);
+                m.set_name(name, LABEL)?;
+            }
+            "PythonExtensionModule" => {
+                let mut em = value.downcast_mut::<PythonExtensionModuleValue>()?.unwrap();
+                let name = format!("{}.{}", prefix, em.inner(LABEL)?.em.name);
+                em.set_name(name, LABEL)?;
+            }
+            _ => continue,
+        }
+
+        count += 1;
+    }
+
+    Ok(Value::new(count))
+}
+
+/// set_location_fallback_all(values, location)
+fn starlark_set_location_fallback_all(values: &Value, location: &Value) -> ValueResult {
+    const LABEL: &str = "set_location_fallback_all()";
+
+    // Validate the location up front so no resource is modified on error.
+    let location = location_arg(&FunctionArgs::new(LABEL), "location", location)?;
+
+    for mut value in resource_values(values, LABEL)? {
+        if add_context_for_value(&value, LABEL)?.is_some() {
+            value.set_attr("add_location_fallback", Value::from(&location))?;
+        }
+    }
+
+    Ok(Value::new(NoneType::None))
+}
+
+/// set_include_by_extension(values, extension, include)
+///
+/// Sets `add_include` on package resources whose `relative_name` ends with
+/// `extension`. A leading `.` is implied. Other values and resources without
+/// an add collection context are ignored.
+fn starlark_set_include_by_extension(
+    values: &Value,
+    extension: &Value,
+    include: &Value,
+) -> ValueResult {
+    const LABEL: &str = "set_include_by_extension()";
+
+    let args = FunctionArgs::new(LABEL);
+    let extension = args.required_str("extension", extension)?;
+    let include = args.required_bool("include", include)?;
+
+    let extension = extension.trim_start_matches('.');
+    if extension.is_empty() {
+        return Err(ValueError::from(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message: "extension must not be empty".to_string(),
+            label: LABEL.to_string(),
+        }));
+    }
+    let suffix = format!(".{}", extension);
+
+    for mut value in &values.iter()? {
+        let matches = match value.downcast_ref::<PythonPackageResourceValue>() {
+            Some(r) => r.inner(LABEL)?.r.relative_name.ends_with(&suffix),
+            None => false,
+        };
+
+        if matches && add_context_for_value(&value, LABEL)?.is_some() {
+            value.set_attr("add_include", Value::from(include))?;
+        }
+    }
+
+    Ok(Value::new(NoneType::None))
+}
+
+/// assign_locations(values, classifier)
+///
+/// `classifier` is called with each resource having an add collection
+/// context and returns a location string or `None` to leave the resource
+/// unchanged. All returned locations are validated before any resource is
+/// modified.
+fn starlark_assign_locations(
+    type_values: &TypeValues,
+    call_stack: &mut CallStack,
+    values: &Value,
+    classifier: &Value,
+) -> ValueResult {
+    const LABEL: &str = "assign_locations()";
+
+    FunctionArgs::new(LABEL).required_function("classifier", classifier)?;
+
+    let mut assignments = vec![];
+
+    for value in resource_values(values, LABEL)? {
+        if add_context_for_value(&value, LABEL)?.is_none() {
+            continue;
+        }
+
+        let location = classifier.call(
+            call_stack,
+            type_values,
+            vec![value.clone()],
+            LinkedHashMap::new(),
+            None,
+            None,
+        )?;
+
+        match location.get_type() {
+            "NoneType" => continue,
+            "string" => {}
+            t => {
+                return Err(ValueError::from(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: format!(
+                        "classifier must return a location string or None; got {} for {}",
+                        t,
+                        resource_value_full_name(&value)?
+                    ),
+                    label: LABEL.to_string(),
+                }));
+            }
+        }
+
+        if let Err(e) = ConcreteResourceLocation::try_from(location.to_str().as_str()) {
+            return Err(ValueError::from(RuntimeError {
+                code: "PYOXIDIZER",
+                message: format!(
+                    "classifier returned invalid location for {}: {}",
+                    resource_value_full_name(&value)?,
+                    e
+                ),
+                label: LABEL.to_string(),
+            }));
+        }
+
+        assignments.push((value, location));
+    }
+
+    for (mut value, location) in assignments {
+        value.set_attr("add_location", location)?;
+    }
+
+    Ok(Value::new(NoneType::None))
+}
+
+/// freeze_resource_contexts(values)
+///
+/// Returns the number of resources that were locked by this call. Resources
+/// that were already locked aren't counted.
+fn starlark_freeze_resource_contexts(values: &Value) -> ValueResult {
+    const LABEL: &str = "freeze_resource_contexts()";
+
+    let mut count = 0;
+
+    for value in resource_values(values, LABEL)? {
+        let locked = with_resource_context_mut(&value, LABEL, |resource| {
+            if resource.is_locked()? {
+                Ok(false)
+            } else {
+                resource.lock()?;
+                Ok(true)
+            }
+        })?;
+
+        if locked {
+            count += 1;
+        }
+    }
+
+    Ok(Value::new(count))
+}
+
+/// Obtain the full name of a value accepted by `resource_values()`.
+pub fn resource_value_full_name(value: &Value) -> Result<String, ValueError> {
+    with_resource_context(value, "resource_value_full_name()", |resource| {
+        Ok(resource.as_python_resource()?.full_name())
+    })
+}
+
+/// Obtain the `PythonResource` of a value accepted by `resource_values()`.
+fn resource_value_python_resource(value: &Value) -> Result<PythonResource<'static>, ValueError> {
+    with_resource_context(value, "resource_value_python_resource()", |resource| {
+        Ok(resource.as_python_resource()?.into_owned())
+    })
+}
+
+/// Obtain the `DistributionInfo` struct of a distribution.
+///
+/// `name` and `version` come from the `METADATA` file if present.
+/// `metadata` maps each header of that file to the list of its values.
+fn distribution_info_value(
+    package: &str,
+    version: &str,
+    metadata: Option<&PythonPackageMetadata>,
+) -> ValueResult {
+    let mut headers: LinkedHashMap<String, Vec<Value>> = LinkedHashMap::new();
+    let (mut name, mut version) = (package.to_string(), version.to_string());
+
+    if let Some(metadata) = metadata {
+        for (key, value) in metadata.headers() {
+            headers
+                .entry(key.clone())
+                .or_insert_with(Vec::new)
+                .push(Value::from(value.as_str()));
+        }
+
+        if let Some(value) = metadata.name() {
+            name = value.to_string();
+        }
+        if let Some(value) = metadata.version() {
+            version = value.to_string();
+        }
+    }
+
+    let mut d = Dictionary::default();
+    for (key, values) in headers {
+        d.insert(Value::from(key), Value::from(values))?;
+    }
+
+    let mut fields = BTreeMap::new();
+    fields.insert("name".to_string(), Value::from(name));
+    fields.insert("version".to_string(), Value::from(version));
+    fields.insert(
+        "metadata".to_string(),
+        Value::try_from(d.get_content().clone())?,
+    );
+
+    Ok(Value::new(StructValue::new(fields)))
+}
+
+/// group_resources_by_distribution(values)
+///
+/// Returns a dict mapping normalized distribution names to structs with a
+/// `resources` list and a `DistributionInfo` struct named `info`. Resources
+/// are attributed using the `RECORD` files of distributions in `values`, so
+/// modules whose names differ from the distribution are grouped correctly.
+/// Resources not attributed to a distribution are omitted.
+fn starlark_group_resources_by_distribution(values: &Value) -> ValueResult {
+    const LABEL: &str = "group_resources_by_distribution()";
+
+    let values = resource_values(values, LABEL)?;
+    let resources = values
+        .iter()
+        .map(resource_value_python_resource)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let build_error = |e: anyhow::Error| {
+        ValueError::from(RuntimeError {
+            code: "PYOXIDIZER_BUILD",
+            message: format!("{:?}", e),
+            label: LABEL.to_string(),
+        })
+    };
+
+    let index = DistributionFileIndex::from_resources(&resources).map_err(build_error)?;
+
+    let mut groups: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    let mut infos: BTreeMap<String, (String, String, Option<PythonPackageMetadata>)> =
+        BTreeMap::new();
+
+    for (value, resource) in values.iter().zip(resources.iter()) {
+        let distribution = match index.distribution_for(resource) {
+            Some(distribution) => distribution.to_string(),
+            None => continue,
+        };
+
+        if let PythonResource::PackageDistributionResource(r) = resource {
+            let info = infos
+                .entry(normalize_distribution_name(&r.package))
+                .or_insert_with(|| (r.package.clone(), r.version.clone(), None));
+
+            if r.name == "METADATA" || r.name == "PKG-INFO" {
+                info.2 = Some(
+                    r.data
+                        .resolve_content()
+                        .map_err(anyhow::Error::from)
+                        .and_then(|data| PythonPackageMetadata::from_metadata(&data))
+                        .with_context(|| format!("parsing metadata of {}", r.package))
+                        .map_err(build_error)?,
+                );
+            }
+        }
+
+        groups.entry(distribution).or_default().push(value.clone());
+    }
+
+    let mut d = Dictionary::default();
+    for (distribution, resources) in groups {
+        let info = match infos.get(&distribution) {
+            Some((package, version, metadata)) => {
+                distribution_info_value(package, version, metadata.as_ref())?
+            }
+            None => distribution_info_value(&distribution, "", None)?,
+        };
+
+        let mut fields = BTreeMap::new();
+        fields.insert("info".to_string(), info);
+        fields.insert("resources".to_string(), Value::from(resources));
+
+        d.insert(
+            Value::from(distribution),
+            Value::new(StructValue::new(fields)),
+        )?;
+    }
+
+    Value::try_from(d.get_content().clone())
+}
+
+/// Obtain the size bucket of a resource as a string value.
+pub fn size_bucket_value(
+    candidate: &SelectorCandidate,
+    thresholds: &SizeBucketThresholds,
+    label: &str,
+) -> ValueResult {
+    let size = candidate.size().map_err(|e| {
+        ValueError::from(RuntimeError {
+            code: "PYOXIDIZER_BUILD",
+            message: format!("error computing size of {}: {}", candidate.name, e),
+            label: label.to_string(),
+        })
+    })?;
+
+    Ok(Value::from(thresholds.bucket(size)))
+}
+
+/// size_bucket(value, medium=65536, large=1048576)
+fn starlark_size_bucket(value: &Value, medium: i64, large: i64) -> ValueResult {
+    const LABEL: &str = "size_bucket()";
+
+    if medium < 0 || large < medium {
+        return Err(ValueError::from(RuntimeError {
+            code: "PYOXIDIZER_BUILD",
+            message: format!(
+                "thresholds must satisfy 0 <= medium <= large; got medium={} large={}",
+                medium, large
+            ),
+            label: LABEL.to_string(),
+        }));
+    }
+
+    let thresholds = SizeBucketThresholds {
+        medium: medium as u64,
+        large: large as u64,
+    };
+
+    match value.get_type() {
+        "PythonModuleSource" => {
+            let value = value.downcast_ref::<PythonModuleSourceValue>().unwrap();
+            let inner = value.inner(LABEL)?;
+            size_bucket_value(
+                &SelectorCandidate::module_source(&inner.m),
+                &thresholds,
+                LABEL,
+            )
+        }
+        "PythonPackageResource" => {
+            let value = value.downcast_ref::<PythonPackageResourceValue>().unwrap();
+            let inner = value.inner(LABEL)?;
+            size_bucket_value(
+                &SelectorCandidate::package_resource(&inner.r),
+                &thresholds,
+                LABEL,
+            )
+        }
+        "PythonPackageDistributionResource" => {
+            let value = value
+                .downcast_ref::<PythonPackageDistributionResourceValue>()
+                .unwrap();
+            let inner = value.inner(LABEL)?;
+            size_bucket_value(
+                &SelectorCandidate::package_distribution_resource(&inner.r),
+                &thresholds,
+                LABEL,
+            )
+        }
+        "PythonExtensionModule" => {
+            let value = value.downcast_ref::<PythonExtensionModuleValue>().unwrap();
+            let inner = value.inner(LABEL)?;
+            size_bucket_value(
+                &SelectorCandidate::extension_module(&inner.em),
+                &thresholds,
+                LABEL,
+            )
+        }
+        "File" => {
+            let value = value.downcast_ref::<FileValue>().unwrap();
+            let inner = value.inner(LABEL)?;
+            size_bucket_value(&SelectorCandidate::file(&inner.file), &thresholds, LABEL)
+        }
+        t => Err(FunctionArgs::new(LABEL).type_error("value", "a Python resource", t)),
+    }
+}
+
+/// Obtain the size in bytes of the data of a resource value.
+fn resource_value_size(value: &Value, label: &str) -> Result<u64, ValueError> {
+    with_resource_context(value, label, |resource| {
+        let resource = resource.as_python_resource()?;
+
+        SelectorCandidate::resource(&resource)
+            .map_or(Ok(0), |candidate| candidate.size())
+            .map_err(|e| {
+                ValueError::from(RuntimeError {
+                    code: "PYOXIDIZER_BUILD",
+                    message: format!("error computing size of {}: {}", resource.full_name(), e),
+                    label: label.to_string(),
+                })
+            })
+    })
+}
+
+/// total_emitted_size(values)
+///
+/// Returns a dict of the total size in bytes of resources that will be
+/// included, keyed by `in-memory` and `on-disk`. Resources are bucketed by
+/// their effective location. Sizes are only computed for included resources
+/// having one. A value appearing multiple times is counted once.
+fn starlark_total_emitted_size(values: &Value) -> ValueResult {
+    const LABEL: &str = "total_emitted_size()";
+
+    let mut seen = HashSet::new();
+    let mut in_memory = 0u64;
+    let mut on_disk = 0u64;
+
+    for value in resource_values(values, LABEL)? {
+        let location = with_resource_context(&value, LABEL, |resource| {
+            if !seen.insert(resource as *const dyn ResourceCollectionContext as *const ()) {
+                return Ok(None);
+            }
+
+            if resource.will_be_included()? {
+                resource.effective_location()
+            } else {
+                Ok(None)
+            }
+        })?;
+
+        let location = match location {
+            Some(location) => location,
+            None => continue,
+        };
+
+        let size = resource_value_size(&value, LABEL)?;
+
+        match location {
+            ConcreteResourceLocation::InMemory => in_memory += size,
+            ConcreteResourceLocation::RelativePath(_) => on_disk += size,
+        }
+    }
+
+    let mut d = Dictionary::default();
+    d.insert(Value::from("in-memory"), Value::from(in_memory as i64))?;
+    d.insert(Value::from("on-disk"), Value::from(on_disk as i64))?;
+
+    Value::try_from(d.get_content().clone())
+}
+
+/// require_all_contexts(values)
+///
+/// Errors listing every resource that doesn't have an add collection context.
+fn starlark_require_all_contexts(values: &Value) -> ValueResult {
+    const LABEL: &str = "require_all_contexts()";
+
+    let mut missing = vec![];
+
+    for value in resource_values(values, LABEL)? {
+        if add_context_for_value(&value, LABEL)?.is_some() {
+            continue;
+        }
+
+        missing.push(format!(
+            "{} {}",
+            value.get_type(),
+            resource_value_full_name(&value)?
+        ));
+    }
+
+    if missing.is_empty() {
+        Ok(Value::new(NoneType::None))
+    } else {
+        Err(ValueError::from(RuntimeError {
+            code: "PYOXIDIZER",
+            message: format!(
+                "resources lack an add collection context: {}",
+                missing.join(", ")
+            ),
+            label: LABEL.to_string(),
+        }))
+    }
+}
+
+/// Merge an add collection context into another.
+///
+/// Flags enabling inclusion or storage of data are combined so nothing
+/// either context asks for is dropped. `into` keeps its location and only
+/// obtains a fallback location if it doesn't have one.
+fn merge_add_contexts(
+    into: &mut PythonResourceAddCollectionContext,
+    other: &PythonResourceAddCollectionContext,
+) {
+    into.include |= other.include;
+    into.store_source |= other.store_source;
+    into.optimize_level_zero |= other.optimize_level_zero;
+    into.optimize_level_one |= other.optimize_level_one;
+    into.optimize_level_two |= other.optimize_level_two;
+
+    if into.location_fallback.is_none() && other.location != into.location {
+        into.location_fallback = other
+            .location_fallback
+            .clone()
+            .or_else(|| Some(other.location.clone()));
+    }
+}
+
+/// coalesce_resources(values)
+///
+/// Resources of the same type and name are replaced by a single resource
+/// whose add collection context merges the contexts of all of them. The
+/// first occurrence determines the position and data of the result.
+fn starlark_coalesce_resources(values: &Value) -> ValueResult {
+    const LABEL: &str = "coalesce_resources()";
+
+    let mut keys: Vec<(&'static str, String)> = vec![];
+    let mut groups: BTreeMap<(&'static str, String), Vec<Value>> = BTreeMap::new();
+
+    for value in resource_values(values, LABEL)? {
+        let key = (value.get_type(), resource_value_full_name(&value)?);
+
+        let group = groups.entry(key.clone()).or_default();
+        if group.is_empty() {
+            keys.push(key);
+        }
+        group.push(value);
+    }
+
+    let mut res = vec![];
+
+    for key in keys {
+        let group = &groups[&key];
+        let first = &group[0];
+
+        if group.len() == 1 {
+            res.push(first.clone());
+            continue;
+        }
+
+        let mut merged: Option<PythonResourceAddCollectionContext> = None;
+        for value in group {
+            if let Some(context) = add_context_for_value(value, LABEL)? {
+                match merged.as_mut() {
+                    Some(merged) => merge_add_contexts(merged, &context),
+                    None => merged = Some(context),
+                }
+            }
+        }
+
+        let merged = match merged {
+            Some(merged) => merged,
+            None => {
+                res.push(first.clone());
+                continue;
+            }
+        };
+
+        let copy =
+            with_resource_context(first, LABEL, |resource| resource.independent_copy_value())?;
+        with_resource_context_mut(&copy, LABEL, |resource| {
+            resource.replace_add_collection_context(merged)
+        })?;
+
+        res.push(copy);
+    }
+
+    Ok(Value::from(res))
+}
+
+/// Obtain the names of all readable attributes of a resource value.
+fn resource_readable_attrs<T>(value: &Value) -> Vec<&'static str>
+where
+    T: ResourceCollectionContext + TypedValue,
+{
+    let resource = value.downcast_ref::<T>().unwrap();
+
+    let mut attrs = resource.resource_attrs();
+    attrs.extend(resource.add_collection_context_attrs());
+
+    attrs
+}
+
+/// Convert a Starlark value to a TOML value.
+///
+/// Returns `None` for `None`, which TOML can't represent.
+fn value_to_toml(value: &Value) -> Result<Option<toml::Value>, ValueError> {
+    Ok(Some(match value.get_type() {
+        "NoneType" => return Ok(None),
+        "bool" => toml::Value::Boolean(value.to_bool()),
+        "int" => toml::Value::Integer(value.to_int()?),
+        "string" => toml::Value::String(value.to_str()),
+        "list" | "tuple" => toml::Value::Array(
+            value
+                .iter()?
+                .iter()
+                .filter_map(|v| value_to_toml(&v).transpose())
+                .collect::<Result<Vec<_>, ValueError>>()?,
+        ),
+        "dict" => {
+            let mut table = toml::value::Table::new();
+
+            for k in &value.iter()? {
+                if let Some(v) = value_to_toml(&value.at(k.clone())?)? {
+                    table.insert(k.to_str(), v);
+                }
+            }
+
+            toml::Value::Table(table)
+        }
+        _ => toml::Value::String(value.to_repr()),
+    }))
+}
+
+/// resources_to_toml(values)
+///
+/// Emits a `[[resource]]` table per resource holding its type and readable
+/// attributes. Values that aren't resources are ignored.
+fn starlark_resources_to_toml(values: &Value) -> ValueResult {
+    const LABEL: &str = "resources_to_toml()";
+
+    let mut tables = vec![];
+
+    for value in &values.iter()? {
+        let attrs = match value.get_type() {
+            "PythonModuleSource" => resource_readable_attrs::<PythonModuleSourceValue>(&value),
+            "PythonPackageResource" => {
+                resource_readable_attrs::<PythonPackageResourceValue>(&value)
+            }
+            "PythonPackageDistributionResource" => {
+                resource_readable_attrs::<PythonPackageDistributionResourceValue>(&value)
+            }
+            "PythonExtensionModule" => {
+                resource_readable_attrs::<PythonExtensionModuleValue>(&value)
+            }
+            "File" => resource_readable_attrs::<FileValue>(&value),
+            _ => continue,
+        };
+
+        let mut table = toml::value::Table::new();
+        table.insert(
+            "type".to_string(),
+            toml::Value::String(value.get_type().to_string()),
+        );
+
+        for attr in attrs {
+            let v = match value.get_attr(attr) {
+                Ok(v) => v,
+                // Binary package resource data is only readable as `data_base64`.
+                Err(_) if attr == "data" => continue,
+                Err(e) => return Err(e),
+            };
+
+            if let Some(v) = value_to_toml(&v)? {
+                table.insert(attr.to_string(), v);
+            }
+        }
+
+        tables.push(toml::Value::Table(table));
+    }
+
+    let mut document = toml::value::Table::new();
+    document.insert("resource".to_string(), toml::Value::Array(tables));
+
+    let s = toml::to_string(&toml::Value::Table(document)).map_err(|e| {
+        ValueError::from(RuntimeError {
+            code: "PYOXIDIZER_BUILD",
+            message: format!("error serializing resources to TOML: {}", e),
+            label: LABEL.to_string(),
+        })
+    })?;
+
+    Ok(Value::from(s))
+}
+
+/// used_relative_prefixes(values)
+fn starlark_used_relative_prefixes(values: &Value) -> ValueResult {
+    const LABEL: &str = "used_relative_prefixes()";
+
+    let mut prefixes = BTreeSet::new();
+
+    for value in &values.iter()? {
+        if !is_resource_value(&value) {
+            continue;
+        }
+
+        if let Some(context) = add_context_for_value(&value, LABEL)? {
+            for location in std::iter::once(context.location).chain(context.location_fallback) {
+                if let ConcreteResourceLocation::RelativePath(prefix) = location {
+                    prefixes.insert(prefix);
+                }
+            }
+        }
+    }
+
+    Ok(Value::from(
+        prefixes.into_iter().map(Value::from).collect::<Vec<_>>(),
+    ))
+}
+
+/// Rebase a `filesystem-relative` location from one prefix to another.
+///
+/// Prefixes are matched on whole path components. Returns `None` if the
+/// location doesn't match `old_prefix`.
+fn rebase_relative_location(
+    location: &ConcreteResourceLocation,
+    old_prefix: &str,
+    new_prefix: &str,
+) -> Option<ConcreteResourceLocation> {
+    if let ConcreteResourceLocation::RelativePath(prefix) = location {
+        let suffix = if prefix == old_prefix {
+            ""
+        } else if prefix.starts_with(old_prefix) && prefix[old_prefix.len()..].starts_with('/') {
+            &prefix[old_prefix.len()..]
+        } else {
+            return None;
+        };
+
+        Some(ConcreteResourceLocation::RelativePath(format!(
+            "{}{}",
+            new_prefix, suffix
+        )))
+    } else {
+        None
+    }
+}
+
+/// rebase_relative_locations(values, old_prefix, new_prefix)
+///
+/// Returns the number of locations that were rewritten.
+fn starlark_rebase_relative_locations(
+    values: &Value,
+    old_prefix: &Value,
+    new_prefix: &Value,
+) -> ValueResult {
+    const LABEL: &str = "rebase_relative_locations()";
+
+    let args = FunctionArgs::new(LABEL);
+    let old_prefix = args.required_str("old_prefix", old_prefix)?;
+    let new_prefix = args.required_str("new_prefix", new_prefix)?;
+
+    let old_prefix = old_prefix.trim_end_matches('/');
+    let new_prefix = new_prefix.trim_end_matches('/');
+
+    if old_prefix.is_empty() || new_prefix.is_empty() {
+        return Err(ValueError::from(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message: "prefixes must not be empty".to_string(),
+            label: LABEL.to_string(),
+        }));
+    }
+
+    // Resolve all changes up front so no resource is modified on error.
+    let mut changes = vec![];
+
+    for value in resource_values(values, LABEL)? {
+        if let Some(context) = add_context_for_value(&value, LABEL)? {
+            let location = rebase_relative_location(&context.location, old_prefix, new_prefix);
+            let fallback = context
+                .location_fallback
+                .as_ref()
+                .and_then(|l| rebase_relative_location(l, old_prefix, new_prefix));
+
+            if location.is_none() && fallback.is_none() {
+                continue;
+            }
+
+            if value.get_attr("is_locked")?.to_bool() {
+                return Err(ValueError::from(RuntimeError {
+                    code: "PYOXIDIZER",
+                    message: format!(
+                        "cannot rebase locations of locked resource {}",
+                        value.to_repr()
+                    ),
+                    label: LABEL.to_string(),
+                }));
+            }
+
+            changes.push((value, location, fallback));
+        }
+    }
+
+    let mut count = 0;
+
+    for (mut value, location, fallback) in changes {
+        for (attr, location) in &[
+            ("add_location", location),
+            ("add_location_fallback", fallback),
+        ] {
+            if let Some(location) = location {
+                value.set_attr(attr, Value::from(location.to_string()))?;
+                count += 1;
+            }
+        }
+    }
+
+    Ok(Value::new(count))
+}
+
+/// Render a value for a canonical string.
+///
+/// Dictionary entries are sorted by their rendered key so output doesn't
+/// depend on insertion order.
+fn canonical_repr(value: &Value) -> Result<String, ValueError> {
+    Ok(match value.get_type() {
+        "dict" => {
+            let mut entries = value
+                .iter()?
+                .iter()
+                .map(|k| Ok((canonical_repr(&k)?, canonical_repr(&value.at(k)?)?)))
+                .collect::<Result<Vec<_>, ValueError>>()?;
+            entries.sort();
+
+            format!(
+                "{{{}}}",
+                entries
+                    .into_iter()
+                    .map(|(k, v)| format!("{}: {}", k, v))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+        "list" => format!(
+            "[{}]",
+            value
+                .iter()?
+                .iter()
+                .map(|v| canonical_repr(&v))
+                .collect::<Result<Vec<_>, ValueError>>()?
+                .join(", ")
+        ),
+        _ => value.to_repr(),
+    })
+}
+
+/// <resource>.to_canonical_string()
+///
+/// Emits one `name=value` line per readable attribute, sorted by name.
+pub fn starlark_to_canonical_string<T>(this: &Value) -> ValueResult
+where
+    T: ResourceCollectionContext + TypedValue,
+{
+    let resource = this.downcast_ref::<T>().unwrap();
+
+    let mut attrs = resource.resource_attrs();
+    attrs.extend(resource.add_collection_context_attrs());
+    attrs.sort_unstable();
+    drop(resource);
+
+    let mut lines = vec![];
+    for attr in attrs {
+        lines.push(format!(
+            "{}={}\n",
+            attr,
+            canonical_repr(&this.get_attr(attr)?)?
+        ));
+    }
+
+    Ok(Value::from(lines.concat()))
+}
+
+/// <resource>.as_struct()
+///
+/// Returns a `struct` holding the values of all readable attributes. It
+/// doesn't change when the resource does.
+pub fn starlark_as_struct<T>(this: &Value) -> ValueResult
+where
+    T: ResourceCollectionContext + TypedValue,
+{
+    let resource = this.downcast_ref::<T>().unwrap();
+
+    let mut attrs = resource.resource_attrs();
+    attrs.extend(resource.add_collection_context_attrs());
+    drop(resource);
+
+    let mut fields = BTreeMap::new();
+    for attr in attrs {
+        fields.insert(attr.to_string(), this.get_attr(attr)?);
+    }
+
+    Ok(Value::new(StructValue::new(fields)))
+}
+
+/// <resource>.settable_attributes()
+///
+/// Returns the sorted names of attributes accepted by `set_attr`.
+pub fn starlark_settable_attributes<T>(this: &Value) -> ValueResult
+where
+    T: ResourceCollectionContext + TypedValue,
+{
+    let resource = this.downcast_ref::<T>().unwrap();
+
+    let mut attrs = resource.settable_attrs();
+    attrs.sort_unstable();
+
+    Ok(Value::from(attrs))
+}
+
+/// <resource>.plan_entry()
+///
+/// Returns an `(action, key)` tuple. `action` is `add` or `skip` depending on
+/// whether the resource will be included. `key` is the name of the resource.
+pub fn starlark_plan_entry<T>(this: &Value) -> ValueResult
+where
+    T: ResourceCollectionContext + TypedValue,
+{
+    let resource = this.downcast_ref::<T>().unwrap();
+
+    let action = if resource.will_be_included()? {
+        "add"
+    } else {
+        "skip"
+    };
+    let key = resource.as_python_resource()?.full_name();
+
+    Ok(Value::from((action, key)))
+}
+
+/// <resource>.with_include(include)
+///
+/// Returns a copy of the resource with `add_include` set. The receiver is
+/// not modified.
+pub fn starlark_with_include<T>(this: &Value, include: bool) -> ValueResult
+where
+    T: ResourceCollectionContext + TypedValue<Holder = Mutable<T>>,
+{
+    let mut copy = this.downcast_ref::<T>().unwrap().independent_copy()?;
+
+    let mut context = copy.add_collection_context()?.ok_or_else(|| {
+        ValueError::from(RuntimeError {
+            code: "PYOXIDIZER",
+            message: "cannot set add_include on a resource without an add collection context"
+                .to_string(),
+            label: "with_include()".to_string(),
+        })
+    })?;
+    context.include = include;
+    copy.replace_add_collection_context(context)?;
+
+    Ok(Value::new(copy))
+}
+
+/// <resource>.duplicate_to_locations(locations)
+///
+/// Returns one copy of the resource per location, each with `add_location`
+/// set to that location. The receiver is not modified.
+pub fn starlark_duplicate_to_locations<T>(this: &Value, locations: &Value) -> ValueResult
+where
+    T: ResourceCollectionContext + TypedValue<Holder = Mutable<T>>,
+{
+    const LABEL: &str = "duplicate_to_locations()";
+
+    let mut parsed: Vec<ConcreteResourceLocation> = vec![];
+
+    for value in &locations.iter()? {
+        let location = match OptionalResourceLocation::try_from(&value)?.into() {
+            Some(location) => location,
+            None => {
+                return Err(ValueError::from(RuntimeError {
+                    code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                    message: "locations must be `in-memory` or `filesystem-relative:*`".to_string(),
+                    label: LABEL.to_string(),
+                }))
+            }
+        };
+
+        if parsed.contains(&location) {
+            return Err(ValueError::from(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: format!("location {} specified multiple times", location.to_string()),
+                label: LABEL.to_string(),
+            }));
+        }
+
+        parsed.push(location);
+    }
+
+    let resource = this.downcast_ref::<T>().unwrap();
+
+    let context = resource.add_collection_context()?.ok_or_else(|| {
+        ValueError::from(RuntimeError {
+            code: "PYOXIDIZER",
+            message: "cannot duplicate a resource without an add collection context".to_string(),
+            label: LABEL.to_string(),
+        })
+    })?;
+
+    let mut copies = vec![];
+
+    for location in parsed {
+        let mut copy = resource.independent_copy()?;
+
+        let mut context = context.clone();
+        context.location = location;
+        copy.replace_add_collection_context(context)?;
+
+        copies.push(Value::new(copy));
+    }
+
+    Ok(Value::from(copies))
+}
+
+/// <resource>.add_collection_context()
+pub fn starlark_add_collection_context<T>(this: &Value) -> ValueResult
+where
+    T: ResourceCollectionContext + TypedValue,
+{
+    this.downcast_ref::<T>()
+        .unwrap()
+        .add_collection_context_dict()
+}
+
+/// <resource>.set_collection_context(context)
+pub fn starlark_set_collection_context<T>(this: &Value, context: &Value) -> ValueResult
+where
+    T: ResourceCollectionContext + TypedValue<Holder = Mutable<T>>,
+{
+    this.downcast_mut::<T>()?
+        .unwrap()
+        .set_add_collection_context_dict(context)?;
+
+    Ok(Value::new(NoneType::None))
+}
+
+/// <resource>.lock()
+pub fn starlark_lock<T>(this: &Value) -> ValueResult
+where
+    T: ResourceCollectionContext + TypedValue<Holder = Mutable<T>>,
+{
+    this.downcast_mut::<T>()?.unwrap().lock()?;
+
+    Ok(Value::new(NoneType::None))
+}
+
+starlark_module! { python_resource_env =>
+    resource_type_histogram(values) {
+        starlark_resource_type_histogram(&values)
+    }
+
+    extension_module_names(values) {
+        starlark_extension_module_names(&values)
+    }
+
+    importable_names(values) {
+        starlark_importable_names(&values)
+    }
+
+    group_resources_by_distribution(values) {
+        starlark_group_resources_by_distribution(&values)
+    }
+
+    prefix_module_names(values, prefix) {
+        starlark_prefix_module_names(&values, &prefix)
+    }
+
+    missing_resources(values, required_names) {
+        starlark_missing_resources(&values, &required_names)
+    }
+
+    validate_module_names(values) {
+        starlark_validate_module_names(&values)
+    }
+
+    set_location_fallback_all(values, location) {
+        starlark_set_location_fallback_all(&values, &location)
+    }
+
+    set_include_by_extension(values, extension, include) {
+        starlark_set_include_by_extension(&values, &extension, &include)
+    }
+
+    assign_locations(env env, call_stack cs, values, classifier) {
+        starlark_assign_locations(env, cs, &values, &classifier)
+    }
+
+    rebase_relative_locations(values, old_prefix, new_prefix) {
+        starlark_rebase_relative_locations(&values, &old_prefix, &new_prefix)
+    }
+
+    used_relative_prefixes(values) {
+        starlark_used_relative_prefixes(&values)
+    }
+
+    freeze_resource_contexts(values) {
+        starlark_freeze_resource_contexts(&values)
+    }
+
+    require_all_contexts(values) {
+        starlark_require_all_contexts(&values)
+    }
+
+    coalesce_resources(values) {
+        starlark_coalesce_resources(&values)
+    }
+
+    resources_to_toml(values) {
+        starlark_resources_to_toml(&values)
+    }
+
+    size_bucket(value, medium: i64 = 65536, large: i64 = 1048576) {
+        starlark_size_bucket(&value, medium, large)
+    }
+
+    total_emitted_size(values) {
+        starlark_total_emitted_size(&values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::super::testutil::*,
+        super::*,
+        anyhow::Result,
+        python_packaging::{
+            resource::{
+                PythonExtensionModule, PythonModuleSource, PythonPackageDistributionResource,
+                PythonPackageDistributionResourceFlavor, PythonPackageResource,
+            },
+            resource_collection::BytecodeGeneration,
+        },
+        tugger_file_manifest::FileData,
+    };
+
+    fn source_module(name: &str, source: &str) -> PythonModuleSourceValue {
+        PythonModuleSourceValue::new(PythonModuleSource {
+            name: name.to_string(),
+            source: FileData::Memory(source.as_bytes().to_vec()),
+            is_package: false,
+            cache_tag: "cpython-39".to_string(),
+            is_stdlib: false,
+            is_test: false,
+        })
+    }
+
+    fn source_module_value(name: &str, source: &str) -> Value {
+        Value::new(source_module(name, source))
+    }
+
+    fn extension_module(name: &str) -> PythonExtensionModuleValue {
+        PythonExtensionModuleValue::new(PythonExtensionModule {
+            name: name.to_string(),
+            init_fn: Some(format!("PyInit_{}", name)),
+            extension_file_suffix: ".so".to_string(),
+            shared_library: None,
+            object_file_data: vec![],
+            is_package: false,
+            link_libraries: vec![],
+            is_stdlib: false,
+            builtin_default: false,
+            required: false,
+            variant: None,
+            license: None,
+        })
+    }
+
+    fn extension_module_value(name: &str) -> Value {
+        Value::new(extension_module(name))
+    }
+
+    /// Attach an in-memory add collection context to a resource value.
+    fn with_add_context<T>(mut value: T) -> Value
+    where
+        T: ResourceCollectionContext + starlark::values::TypedValue,
+    {
+        value
+            .replace_add_collection_context(PythonResourceAddCollectionContext {
+                include: true,
+                location: ConcreteResourceLocation::InMemory,
+                location_fallback: None,
+                store_source: true,
+                optimize_level_zero: true,
+                optimize_level_one: false,
+                optimize_level_two: false,
+                bytecode_generation: BytecodeGeneration::Eager,
+            })
+            .unwrap();
+
+        Value::new(value)
+    }
+
+    #[test]
+    fn test_resource_type_histogram() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", source_module_value("foo", "import bar"))
+            .unwrap();
+        env.set_var("bar", source_module_value("bar", "")).unwrap();
+        env.set_var("baz", extension_module_value("baz")).unwrap();
+
+        let value = env.eval("resource_type_histogram([foo, bar, baz, 'ignored', 42])")?;
+        assert_eq!(value.get_type(), "dict");
+        assert_eq!(
+            value.to_repr(),
+            "{\"PythonExtensionModule\": 1, \"PythonModuleSource\": 2}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extension_module_names() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", source_module_value("foo", "")).unwrap();
+        env.set_var("zlib", extension_module_value("zlib")).unwrap();
+        env.set_var("bar", extension_module_value("_bar")).unwrap();
+
+        let value = env.eval("extension_module_names([foo, zlib, 'ignored', bar, None])")?;
+        assert_eq!(value.get_type(), "list");
+        assert_eq!(value.to_repr(), "[\"_bar\", \"zlib\"]");
+
+        eval_assert(&mut env, "extension_module_names([]) == []")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_importable_names() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo_bar", source_module_value("foo.bar", ""))
+            .unwrap();
+        env.set_var("foo", source_module_value("foo", "")).unwrap();
+        env.set_var("zlib", extension_module_value("zlib")).unwrap();
+        env.set_var(
+            "data",
+            Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+                leaf_package: "assets.images".to_string(),
+                relative_name: "logo.png".to_string(),
+                data: FileData::Memory(vec![]),
+                is_stdlib: false,
+                is_test: false,
+            })),
+        )
+        .unwrap();
+
+        let value = env.eval("importable_names([zlib, foo_bar, 'ignored', data, foo, None])")?;
+        assert_eq!(value.get_type(), "list");
+        assert_eq!(value.to_repr(), "[\"assets\", \"foo\", \"zlib\"]");
+
+        eval_assert(&mut env, "importable_names([]) == []")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_resources() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", source_module_value("foo", "")).unwrap();
+        env.set_var("zlib", extension_module_value("zlib")).unwrap();
+
+        let value = env.eval(
+            "missing_resources([foo, zlib, 'ignored', None], ['zlib', 'encodings', 'foo', 'encodings'])",
+        )?;
+        assert_eq!(value.get_type(), "list");
+        assert_eq!(value.to_repr(), "[\"encodings\"]");
+
+        eval_assert(
+            &mut env,
+            "missing_resources([foo, zlib], ['foo', 'zlib']) == []",
+        )?;
+        eval_assert(&mut env, "missing_resources([], ['foo']) == ['foo']")?;
+        assert!(env.eval("missing_resources([foo], [None])").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_module_names() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", source_module_value("foo.bar", ""))
+            .unwrap();
+        env.set_var("bad", source_module_value("foo.2bad", ""))
+            .unwrap();
+        env.set_var("em", extension_module_value("_foo-bar"))
+            .unwrap();
+
+        let value = env.eval("validate_module_names([foo, bad, 'ignored', em, None])")?;
+        assert_eq!(value.get_type(), "list");
+        assert_eq!(value.to_repr(), "[\"foo.2bad\", \"_foo-bar\"]");
+
+        eval_assert(&mut env, "validate_module_names([foo]) == []")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefix_module_names() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", source_module_value("foo", "")).unwrap();
+        env.set_var("foo_bar", source_module_value("foo.bar", ""))
+            .unwrap();
+        env.set_var("zlib", extension_module_value("zlib")).unwrap();
+
+        let count = env
+            .eval("prefix_module_names([foo, foo_bar, foo, zlib, 'ignored', None], 'vendored')")?;
+        assert_eq!(count.to_int().unwrap(), 3);
+
+        eval_assert(&mut env, "foo.name == 'vendored.foo'")?;
+        eval_assert(&mut env, "foo_bar.name == 'vendored.foo.bar'")?;
+        eval_assert(&mut env, "zlib.name == 'vendored.zlib'")?;
+
+        for prefix in &["", "vendored.", "2bad", "a-b"] {
+            let err = env
+                .eval(&format!("prefix_module_names([foo], '{}')", prefix))
+                .unwrap_err()
+                .to_string();
+            assert!(
+                err.as_str()
+                    .contains("prefix must be a dotted sequence of Python identifiers"),
+                "{}",
+                err
+            );
+        }
+        eval_assert(&mut env, "foo.name == 'vendored.foo'")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extension_suffix() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        // Without a policy, the suffix isn't known.
+        env.set_var("foo", extension_module_value("foo")).unwrap();
+        eval_assert(&mut env, "foo.extension_suffix == None")?;
+
+        env.eval("dist = default_python_distribution()")?;
+        env.eval(
+            "ems = [r for r in dist.python_resources() if type(r) == 'PythonExtensionModule']",
+        )?;
+        eval_assert(&mut env, "len(ems) > 0")?;
+
+        let suffix = env.eval("ems[0].extension_suffix")?;
+        assert_eq!(suffix.get_type(), "string");
+        let suffix = suffix.to_str();
+        assert!(suffix.starts_with('.'), "{}", suffix);
+        assert!(
+            suffix.ends_with(".so") || suffix.ends_with(".pyd"),
+            "{}",
+            suffix
+        );
+        eval_assert(
+            &mut env,
+            "all([em.extension_suffix == ems[0].extension_suffix for em in ems])",
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_assign_locations() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var(
+            "foo_tests",
+            with_add_context(source_module("foo.tests", "")),
+        )
+        .unwrap();
+        env.set_var("bar", with_add_context(extension_module("bar")))
+            .unwrap();
+        env.set_var("baz", source_module_value("baz", "")).unwrap();
+
+        env.eval("bar.add_location = 'filesystem-relative:lib'")?;
+        env.eval(
+            "def classify(value):\n    \
+                if value.name.endswith('.tests'):\n        \
+                    return 'filesystem-relative:tests'\n    \
+                if type(value) == 'PythonModuleSource':\n        \
+                    return 'in-memory'\n    \
+                return None\n",
+        )?;
+
+        env.eval("assign_locations([foo, foo_tests, bar, baz], classify)")?;
+        eval_assert(&mut env, "foo.add_location == 'in-memory'")?;
+        eval_assert(
+            &mut env,
+            "foo_tests.add_location == 'filesystem-relative:tests'",
+        )?;
+        // Returning None leaves the location unchanged.
+        eval_assert(&mut env, "bar.add_location == 'filesystem-relative:lib'")?;
+
+        // Invalid locations are rejected without modifying anything.
+        env.eval(
+            "def bad_location(value):\n    \
+                return 'filesystem-relative:x' if value.name == 'foo' else 'bad'\n",
+        )?;
+        let err = env
+            .eval("assign_locations([foo, foo_tests], bad_location)")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.as_str()
+                .contains("classifier returned invalid location for foo.tests"),
+            "{}",
+            err
+        );
+        eval_assert(&mut env, "foo.add_location == 'in-memory'")?;
+
+        env.eval("def not_a_location(value):\n    return 42\n")?;
+        assert!(env.eval("assign_locations([foo], not_a_location)").is_err());
+        assert!(env.eval("assign_locations([foo], 'in-memory')").is_err());
+        assert!(env
+            .eval("assign_locations([foo, 'bar'], classify)")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_size_bucket() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("small", source_module_value("small", "import os"))
+            .unwrap();
+        env.set_var(
+            "medium",
+            source_module_value("medium", &"#".repeat(64 * 1024)),
+        )
+        .unwrap();
+        env.set_var(
+            "large",
+            source_module_value("large", &"#".repeat(1024 * 1024)),
+        )
+        .unwrap();
+        env.set_var("builtin", extension_module_value("builtin"))
+            .unwrap();
+
+        eval_assert(&mut env, "small.size_bucket == 'small'")?;
+        eval_assert(&mut env, "medium.size_bucket == 'medium'")?;
+        eval_assert(&mut env, "large.size_bucket == 'large'")?;
+        eval_assert(&mut env, "builtin.size_bucket == 'small'")?;
+
+        eval_assert(&mut env, "size_bucket(large) == 'large'")?;
+        eval_assert(&mut env, "size_bucket(small, medium=4) == 'medium'")?;
+        eval_assert(&mut env, "size_bucket(small, medium=4, large=9) == 'large'")?;
+        eval_assert(
+            &mut env,
+            "size_bucket(medium, medium=2 * 1024 * 1024, large=4 * 1024 * 1024) == 'small'",
+        )?;
+
+        let err = env
+            .eval("size_bucket(small, medium=10, large=5)")
+            .unwrap_err()
+            .to_string();
+        assert!(err
+            .as_str()
+            .contains("thresholds must satisfy 0 <= medium <= large"));
+
+        let err = env.eval("size_bucket('foo')").unwrap_err().to_string();
+        assert!(err
+            .as_str()
+            .contains("size_bucket() expects a Python resource for value; got string"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resources_to_toml() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "import bar")))
+            .unwrap();
+        env.set_var(
+            "binary",
+            Value::new(PythonPackageResourceValue::new(PythonPackageResource {
+                leaf_package: "foo".to_string(),
+                relative_name: "data.bin".to_string(),
+                data: FileData::Memory(vec![0xff, 0xfe]),
+                is_stdlib: false,
+                is_test: false,
+            })),
+        )
+        .unwrap();
+
+        let value = env.eval("resources_to_toml([foo, 'ignored', None, binary])")?;
+        assert_eq!(value.get_type(), "string");
+
+        let doc = value.to_str().parse::<toml::Value>()?;
+        let resources = doc["resource"].as_array().unwrap();
+        assert_eq!(resources.len(), 2);
+
+        let foo = &resources[0];
+        assert_eq!(foo["type"].as_str(), Some("PythonModuleSource"));
+        assert_eq!(foo["name"].as_str(), Some("foo"));
+        assert_eq!(foo["source"].as_str(), Some("import bar"));
+        assert_eq!(foo["add_location"].as_str(), Some("in-memory"));
+        assert_eq!(foo["imports"].as_array().unwrap().len(), 1);
+        assert!(foo.get("add_location_fallback").is_none());
+
+        let binary = &resources[1];
+        assert_eq!(binary["name"].as_str(), Some("data.bin"));
+        assert!(binary.get("data").is_none());
+        assert_eq!(binary["data_base64"].as_str(), Some("//4="));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_effective_location() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        let mut policy = PythonPackagingPolicy::default();
+        policy.set_resources_location(ConcreteResourceLocation::RelativePath("lib".to_string()));
+        policy.set_resources_location_fallback(None);
+
+        let mut foo = source_module("foo", "");
+        foo.capture_policy_state(&policy).unwrap();
+        env.set_var("foo", with_add_context(foo)).unwrap();
+        env.set_var("bar", source_module_value("bar", "")).unwrap();
+
+        // The policy doesn't allow in-memory resources, so the fallback is used.
+        eval_assert(&mut env, "foo.add_location == 'in-memory'")?;
+        eval_assert(&mut env, "foo.effective_location == None")?;
+        env.eval("foo.add_location_fallback = 'filesystem-relative:lib'")?;
+        eval_assert(
+            &mut env,
+            "foo.effective_location == 'filesystem-relative:lib'",
+        )?;
+        env.eval("foo.add_location = 'filesystem-relative:prefix'")?;
+        eval_assert(
+            &mut env,
+            "foo.effective_location == 'filesystem-relative:prefix'",
+        )?;
+
+        // Resources without a context have no location.
+        eval_assert(&mut env, "bar.effective_location == None")?;
+
+        // Shared library extension modules can't be loaded from memory.
+        let mut em = extension_module("baz");
+        em.inner("test").unwrap().em.shared_library = Some(FileData::Memory(vec![]));
+        let mut policy = PythonPackagingPolicy::default();
+        policy.set_resources_location(ConcreteResourceLocation::InMemory);
+        policy.set_resources_location_fallback(Some(ConcreteResourceLocation::RelativePath(
+            "lib".to_string(),
+        )));
+        em.capture_policy_state(&policy).unwrap();
+        env.set_var("baz", with_add_context(em)).unwrap();
+
+        eval_assert(&mut env, "baz.effective_location == None")?;
+        env.eval("baz.add_location_fallback = 'filesystem-relative:lib'")?;
+        eval_assert(
+            &mut env,
+            "baz.effective_location == 'filesystem-relative:lib'",
+        )?;
+
+        // Unless they can be built in.
+        let em = extension_module("qux");
+        em.inner("test").unwrap().em.object_file_data = vec![FileData::Memory(vec![])];
+        env.set_var("qux", with_add_context(em)).unwrap();
+        env.eval("qux.add_location_fallback = 'filesystem-relative:lib'")?;
+        eval_assert(&mut env, "qux.effective_location == 'in-memory'")?;
+
+        Ok(())
+    }
+
+    fn distribution_resource_value(package: &str, name: &str, data: &str) -> Value {
+        with_add_context(PythonPackageDistributionResourceValue::new(
+            PythonPackageDistributionResource {
+                location: PythonPackageDistributionResourceFlavor::DistInfo,
+                package: package.to_string(),
+                version: "5.4".to_string(),
+                name: name.to_string(),
+                data: FileData::Memory(data.as_bytes().to_vec()),
+            },
+        ))
+    }
+
+    #[test]
+    fn test_group_resources_by_distribution() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var(
+            "metadata",
+            distribution_resource_value(
+                "PyYAML",
+                "METADATA",
+                "Metadata-Version: 2.1\nName: PyYAML\nVersion: 5.4\nClassifier: A\nClassifier: B\n",
+            ),
+        )
+        .unwrap();
+        env.set_var(
+            "record",
+            distribution_resource_value(
+                "PyYAML",
+                "RECORD",
+                concat!(
+                    "PyYAML-5.4.dist-info/METADATA,,\n",
+                    "PyYAML-5.4.dist-info/RECORD,,\n",
+                    "_yaml.so,,\n",
+                    "yaml/__init__.py,,\n",
+                    "yaml/data/schema.json,,\n",
+                ),
+            ),
+        )
+        .unwrap();
+        env.set_var(
+            "six_record",
+            distribution_resource_value("six", "RECORD", "six.py,,\n"),
+        )
+        .unwrap();
+        env.set_var("yaml", with_add_context(source_module("yaml", "")))
+            .unwrap();
+        env.set_var("six", with_add_context(source_module("six", "")))
+            .unwrap();
+        env.set_var("other", with_add_context(source_module("other", "")))
+            .unwrap();
+        env.set_var("ext", with_add_context(extension_module("_yaml")))
+            .unwrap();
+        env.set_var(
+            "schema",
+            with_add_context(PythonPackageResourceValue::new(PythonPackageResource {
+                leaf_package: "yaml".to_string(),
+                relative_name: "data/schema.json".to_string(),
+                data: FileData::Memory(vec![]),
+                is_stdlib: false,
+                is_test: false,
+            })),
+        )
+        .unwrap();
+
+        env.eval(
+            "groups = group_resources_by_distribution([metadata, record, yaml, ext, schema, six, six_record, other])",
+        )?;
+        eval_assert(&mut env, "sorted(groups.keys()) == ['pyyaml', 'six']")?;
+        eval_assert(
+            &mut env,
+            "groups['pyyaml'].resources == [metadata, record, yaml, ext, schema]",
+        )?;
+        eval_assert(&mut env, "groups['six'].resources == [six, six_record]")?;
+
+        eval_assert(&mut env, "groups['pyyaml'].info.name == 'PyYAML'")?;
+        eval_assert(&mut env, "groups['pyyaml'].info.version == '5.4'")?;
+        eval_assert(
+            &mut env,
+            "groups['pyyaml'].info.metadata['Classifier'] == ['A', 'B']",
+        )?;
+        eval_assert(&mut env, "groups['six'].info.name == 'six'")?;
+        eval_assert(&mut env, "groups['six'].info.metadata == {}")?;
+
+        eval_assert(&mut env, "group_resources_by_distribution([yaml]) == {}")?;
+        assert!(env
+            .eval("group_resources_by_distribution([yaml, 'x'])")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_include_by_extension() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        let resource = |relative_name: &str| {
+            with_add_context(PythonPackageResourceValue::new(PythonPackageResource {
+                leaf_package: "foo".to_string(),
+                relative_name: relative_name.to_string(),
+                data: FileData::Memory(vec![]),
+                is_stdlib: false,
+                is_test: false,
+            }))
+        };
+
+        env.set_var("readme", resource("README.txt")).unwrap();
+        env.set_var("notes", resource("docs/notes.txt")).unwrap();
+        env.set_var("config", resource("config.json")).unwrap();
+        env.set_var("nodot", resource("footxt")).unwrap();
+        env.set_var("txt", with_add_context(source_module("txt", "")))
+            .unwrap();
+
+        env.eval(
+            "set_include_by_extension([readme, notes, config, nodot, txt, 'x'], '.txt', False)",
+        )?;
+        eval_assert(&mut env, "readme.add_include == False")?;
+        eval_assert(&mut env, "notes.add_include == False")?;
+        eval_assert(&mut env, "config.add_include == True")?;
+        eval_assert(&mut env, "nodot.add_include == True")?;
+        eval_assert(&mut env, "txt.add_include == True")?;
+
+        env.eval("set_include_by_extension([readme, notes], 'txt', True)")?;
+        eval_assert(&mut env, "readme.add_include == True")?;
+        eval_assert(&mut env, "notes.add_include == True")?;
+
+        assert!(env
+            .eval("set_include_by_extension([readme], '.', False)")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_location_fallback_all() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var("bar", source_module_value("bar", "")).unwrap();
+        env.set_var("baz", with_add_context(extension_module("baz")))
+            .unwrap();
+
+        env.eval("set_location_fallback_all([foo, bar, baz], 'filesystem-relative:lib')")?;
+        eval_assert(
+            &mut env,
+            "foo.add_location_fallback == 'filesystem-relative:lib'",
+        )?;
+        eval_assert(
+            &mut env,
+            "baz.add_location_fallback == 'filesystem-relative:lib'",
+        )?;
+        // Resources without a context are ignored.
+        eval_assert(&mut env, "bar.add_location_fallback == None")?;
+
+        // Invalid locations are rejected without modifying anything.
+        assert!(env
+            .eval("set_location_fallback_all([foo, baz], 'bad')")
+            .is_err());
+        eval_assert(
+            &mut env,
+            "foo.add_location_fallback == 'filesystem-relative:lib'",
+        )?;
+
+        // Non-resources are rejected.
+        assert!(env
+            .eval("set_location_fallback_all([foo, 'bar'], None)")
+            .is_err());
+
+        env.eval("set_location_fallback_all([foo, baz], None)")?;
+        eval_assert(&mut env, "foo.add_location_fallback == None")?;
+        eval_assert(&mut env, "baz.add_location_fallback == None")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_used_relative_prefixes() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var("bar", with_add_context(source_module("bar", "")))
+            .unwrap();
+        env.set_var("baz", with_add_context(extension_module("baz")))
+            .unwrap();
+        env.set_var("qux", source_module_value("qux", "")).unwrap();
+
+        eval_assert(&mut env, "used_relative_prefixes([foo, bar, baz]) == []")?;
+
+        env.eval("foo.add_location = 'filesystem-relative:lib/vendor'")?;
+        env.eval("bar.add_location_fallback = 'filesystem-relative:lib'")?;
+        env.eval("baz.add_location = 'filesystem-relative:lib'")?;
+
+        let value = env.eval("used_relative_prefixes([foo, bar, baz, qux, 'ignored', None])")?;
+        assert_eq!(value.get_type(), "list");
+        assert_eq!(value.to_repr(), "[\"lib\", \"lib/vendor\"]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebase_relative_locations() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var("bar", with_add_context(source_module("bar", "")))
+            .unwrap();
+        env.set_var("baz", with_add_context(extension_module("baz")))
+            .unwrap();
+        env.set_var("qux", source_module_value("qux", "")).unwrap();
+
+        env.eval("foo.add_location = 'filesystem-relative:lib'")?;
+        env.eval("foo.add_location_fallback = 'filesystem-relative:lib/vendor'")?;
+        env.eval("bar.add_location_fallback = 'filesystem-relative:library'")?;
+        env.eval("baz.add_location = 'filesystem-relative:lib'")?;
+
+        eval_assert(
+            &mut env,
+            "rebase_relative_locations([foo, bar, baz, qux], 'lib', 'python/lib') == 3",
+        )?;
+        eval_assert(
+            &mut env,
+            "foo.add_location == 'filesystem-relative:python/lib'",
+        )?;
+        eval_assert(
+            &mut env,
+            "foo.add_location_fallback == 'filesystem-relative:python/lib/vendor'",
+        )?;
+        eval_assert(
+            &mut env,
+            "baz.add_location == 'filesystem-relative:python/lib'",
+        )?;
+
+        // Non-matching locations are untouched.
+        eval_assert(&mut env, "bar.add_location == 'in-memory'")?;
+        eval_assert(
+            &mut env,
+            "bar.add_location_fallback == 'filesystem-relative:library'",
+        )?;
+        eval_assert(
+            &mut env,
+            "rebase_relative_locations([foo, bar, baz], 'lib', 'other') == 0",
+        )?;
+
+        assert!(env
+            .eval("rebase_relative_locations([foo, 'bar'], 'python', 'lib')")
+            .is_err());
+        assert!(env
+            .eval("rebase_relative_locations([foo], '', 'lib')")
+            .is_err());
+
+        // Locked resources result in an error and nothing is modified.
+        env.eval("baz.lock()")?;
+        let err = env
+            .eval("rebase_relative_locations([foo, baz], 'python/lib', 'lib')")
+            .unwrap_err();
+        assert!(err.to_string().as_str().contains("locked"));
+        eval_assert(
+            &mut env,
+            "foo.add_location == 'filesystem-relative:python/lib'",
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_require_all_contexts() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var("bar", with_add_context(extension_module("bar")))
+            .unwrap();
+        env.set_var("baz", source_module_value("baz", "")).unwrap();
+
+        eval_assert(&mut env, "require_all_contexts([]) == None")?;
+        eval_assert(&mut env, "require_all_contexts([foo, bar]) == None")?;
+
+        let err = env
+            .eval("require_all_contexts([foo, baz, bar])")
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .as_str()
+            .contains("resources lack an add collection context: PythonModuleSource baz"));
+
+        assert!(env.eval("require_all_contexts([foo, 'bar'])").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_coalesce_resources() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo1", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var("foo2", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var("foo_em", with_add_context(extension_module("foo")))
+            .unwrap();
+        env.set_var("bar", with_add_context(source_module("bar", "")))
+            .unwrap();
+
+        env.eval("foo1.add_include = False")?;
+        env.eval("foo2.add_location = 'filesystem-relative:lib'")?;
+        env.eval("foo2.add_source = False")?;
+
+        env.eval("res = coalesce_resources([foo1, bar, foo2, foo_em])")?;
+        eval_assert(&mut env, "len(res) == 3")?;
+        eval_assert(
+            &mut env,
+            "[type(x) for x in res] == ['PythonModuleSource', 'PythonModuleSource', 'PythonExtensionModule']",
+        )?;
+        eval_assert(&mut env, "res[0].name == 'foo'")?;
+        eval_assert(&mut env, "res[0].add_include == True")?;
+        eval_assert(&mut env, "res[0].add_source == True")?;
+        eval_assert(&mut env, "res[0].add_location == 'in-memory'")?;
+        eval_assert(
+            &mut env,
+            "res[0].add_location_fallback == 'filesystem-relative:lib'",
+        )?;
+        eval_assert(&mut env, "res[1].name == 'bar'")?;
+
+        // Inputs aren't modified.
+        eval_assert(&mut env, "foo1.add_include == False")?;
+
+        eval_assert(&mut env, "coalesce_resources([]) == []")?;
+        assert!(env.eval("coalesce_resources([foo1, 'bar'])").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var("baz", with_add_context(extension_module("baz")))
+            .unwrap();
+
+        eval_assert(&mut env, "foo.is_locked == False")?;
+        env.eval("foo.add_include = False")?;
+
+        env.eval("foo.lock()")?;
+        eval_assert(&mut env, "foo.is_locked == True")?;
+        eval_assert(&mut env, "baz.is_locked == False")?;
+
+        let err = env.eval("foo.add_include = True").unwrap_err();
+        assert!(err.to_string().as_str().contains("locked"));
+        eval_assert(&mut env, "foo.add_include == False")?;
+
+        // Bulk operations honor the lock as well.
+        assert!(env
+            .eval("set_location_fallback_all([foo], 'in-memory')")
+            .is_err());
+
+        // Locking is idempotent and other attributes remain readable.
+        env.eval("foo.lock()")?;
+        eval_assert(&mut env, "foo.name == 'foo'")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_freeze_resource_contexts() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var("bar", source_module_value("bar", "")).unwrap();
+        env.set_var("baz", with_add_context(extension_module("baz")))
+            .unwrap();
+
+        env.eval("baz.lock()")?;
+
+        // Already locked resources aren't counted.
+        eval_assert(&mut env, "freeze_resource_contexts([foo, bar, baz]) == 2")?;
+        eval_assert(&mut env, "foo.is_locked == True")?;
+        eval_assert(&mut env, "bar.is_locked == True")?;
+        eval_assert(&mut env, "freeze_resource_contexts([foo, bar, baz]) == 0")?;
+        eval_assert(&mut env, "freeze_resource_contexts([]) == 0")?;
+
+        let err = env.eval("foo.add_include = False").unwrap_err();
+        assert!(err
+            .to_string()
+            .as_str()
+            .contains("cannot set add_include on a locked resource"));
+        eval_assert(&mut env, "foo.add_include == True")?;
+
+        // Non-resources are rejected.
+        assert!(env.eval("freeze_resource_contexts([foo, 'bar'])").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_total_emitted_size() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "import os")))
+            .unwrap();
+        env.set_var("bar", with_add_context(source_module("bar", "import sys")))
+            .unwrap();
+        env.set_var("baz", source_module_value("baz", "import io"))
+            .unwrap();
+
+        eval_assert(
+            &mut env,
+            "total_emitted_size([]) == {'in-memory': 0, 'on-disk': 0}",
+        )?;
+
+        env.eval("bar.add_include = False")?;
+        eval_assert(
+            &mut env,
+            "total_emitted_size([foo, bar, baz, foo]) == {'in-memory': 9, 'on-disk': 0}",
+        )?;
+
+        env.eval("bar.add_include = True; bar.add_location = 'filesystem-relative:lib'")?;
+        eval_assert(
+            &mut env,
+            "total_emitted_size([foo, bar, baz]) == {'in-memory': 9, 'on-disk': 10}",
+        )?;
+
+        // Resources are bucketed by their effective location.
+        let mut policy = PythonPackagingPolicy::default();
+        policy.set_resources_location(ConcreteResourceLocation::RelativePath("lib".to_string()));
+        policy.set_resources_location_fallback(None);
+
+        let mut qux = source_module("qux", "import re");
+        qux.capture_policy_state(&policy).unwrap();
+        env.set_var("qux", with_add_context(qux)).unwrap();
+
+        eval_assert(
+            &mut env,
+            "total_emitted_size([qux]) == {'in-memory': 0, 'on-disk': 0}",
+        )?;
+        env.eval("qux.add_location_fallback = 'filesystem-relative:lib'")?;
+        eval_assert(
+            &mut env,
+            "total_emitted_size([foo, qux]) == {'in-memory': 9, 'on-disk': 9}",
+        )?;
+
+        let err = env
+            .eval("total_emitted_size([foo, 'bar'])")
+            .unwrap_err()
+            .to_string();
+        assert!(err
+            .as_str()
+            .contains("expects a list of Python resources for values; got list containing string"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_will_be_included() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var("bar", source_module_value("bar", "")).unwrap();
+        env.set_var("baz", with_add_context(extension_module("baz")))
+            .unwrap();
+
+        eval_assert(&mut env, "foo.will_be_included == True")?;
+        env.eval("foo.add_include = False")?;
+        eval_assert(&mut env, "foo.will_be_included == False")?;
+
+        // Resources without a context aren't included.
+        eval_assert(&mut env, "bar.will_be_included == False")?;
+
+        // Extension modules are force included, overriding add_include.
+        env.eval("baz.add_include = False")?;
+        eval_assert(&mut env, "baz.add_include == False")?;
+        eval_assert(&mut env, "baz.will_be_included == True")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_entry() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo.bar", "")))
+            .unwrap();
+        env.set_var("bar", source_module_value("bar", "")).unwrap();
+        env.set_var("baz", with_add_context(extension_module("baz")))
+            .unwrap();
+
+        eval_assert(&mut env, "foo.plan_entry() == ('add', 'foo.bar')")?;
+        env.eval("foo.add_include = False")?;
+        eval_assert(&mut env, "foo.plan_entry() == ('skip', 'foo.bar')")?;
+
+        // Resources without a context aren't included.
+        eval_assert(&mut env, "bar.plan_entry() == ('skip', 'bar')")?;
+
+        // Extension modules are force included.
+        env.eval("baz.add_include = False")?;
+        eval_assert(&mut env, "baz.plan_entry() == ('add', 'baz')")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_include() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var("bar", source_module_value("bar", "")).unwrap();
+
+        env.eval("excluded = foo.with_include(False)")?;
+        eval_assert(&mut env, "excluded.add_include == False")?;
+        eval_assert(&mut env, "excluded.name == 'foo'")?;
+        eval_assert(&mut env, "foo.add_include == True")?;
+
+        // The copy doesn't share state with the original.
+        env.eval("excluded.add_location = 'filesystem-relative:lib'")?;
+        eval_assert(&mut env, "foo.add_location == 'in-memory'")?;
+
+        // Copies of locked resources aren't locked.
+        env.eval("foo.lock()")?;
+        eval_assert(&mut env, "foo.with_include(False).is_locked == False")?;
+
+        let err = env.eval("bar.with_include(True)").unwrap_err();
+        assert!(err.to_string().as_str().contains("add collection context"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_to_locations() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var("bar", source_module_value("bar", "")).unwrap();
+
+        env.eval("copies = foo.duplicate_to_locations(['in-memory', 'filesystem-relative:lib'])")?;
+        eval_assert(&mut env, "len(copies) == 2")?;
+        eval_assert(&mut env, "copies[0].add_location == 'in-memory'")?;
+        eval_assert(
+            &mut env,
+            "copies[1].add_location == 'filesystem-relative:lib'",
+        )?;
+        eval_assert(&mut env, "copies[1].name == 'foo'")?;
+
+        // Copies don't share state with each other or the original.
+        env.eval("copies[0].add_include = False")?;
+        eval_assert(&mut env, "copies[1].add_include == True")?;
+        eval_assert(&mut env, "foo.add_include == True")?;
+        eval_assert(&mut env, "foo.add_location == 'in-memory'")?;
+
+        eval_assert(&mut env, "foo.duplicate_to_locations([]) == []")?;
+
+        for (code, message) in &[
+            ("foo.duplicate_to_locations(['bogus'])", "resource location"),
+            (
+                "foo.duplicate_to_locations(['default'])",
+                "locations must be",
+            ),
+            (
+                "foo.duplicate_to_locations(['in-memory', 'in-memory'])",
+                "multiple times",
+            ),
+            (
+                "bar.duplicate_to_locations(['in-memory'])",
+                "add collection context",
+            ),
+        ] {
+            let err = env.eval(code).unwrap_err();
+            assert!(err.to_string().as_str().contains(message), "{}", code);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_hash() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var("bar", source_module_value("bar", "")).unwrap();
+
+        eval_assert(&mut env, "bar.context_hash == None")?;
+
+        env.eval("original = foo.context_hash")?;
+        eval_assert(&mut env, "len(original) == 64")?;
+        eval_assert(&mut env, "foo.context_hash == original")?;
+
+        env.eval("foo.add_location = 'filesystem-relative:lib'")?;
+        eval_assert(&mut env, "foo.context_hash != original")?;
+
+        env.eval("foo.add_location = 'in-memory'")?;
+        eval_assert(&mut env, "foo.context_hash == original")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collection_context_dict() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.set_var("foo", with_add_context(source_module("foo", "")))
+            .unwrap();
+        env.set_var("bar", source_module_value("bar", "")).unwrap();
+        env.set_var("baz", with_add_context(extension_module("baz")))
+            .unwrap();
+
+        eval_assert(&mut env, "bar.add_collection_context() == None")?;
+        eval_assert(
+            &mut env,
+            "foo.add_collection_context()['add_include'] == True",
+        )?;
+        eval_assert(
+            &mut env,
+            "foo.add_collection_context()['add_location'] == 'in-memory'",
+        )?;
+
+        // Contexts can be copied between resources.
+        env.eval("foo.add_location = 'filesystem-relative:lib'")?;
+        env.eval("foo.add_include = False")?;
+        env.eval("baz.set_collection_context(foo.add_collection_context())")?;
+        eval_assert(&mut env, "baz.add_location == 'filesystem-relative:lib'")?;
+        eval_assert(&mut env, "baz.add_include == False")?;
+
+        // Keys not present retain their value.
+        env.eval("foo.set_collection_context({'add_include': True})")?;
+        eval_assert(&mut env, "foo.add_include == True")?;
+        eval_assert(&mut env, "foo.add_location == 'filesystem-relative:lib'")?;
+
+        let err = env
+            .eval("foo.set_collection_context({'add_bogus': True})")
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .as_str()
+            .contains("unknown collection context key \"add_bogus\"; valid keys are"));
+        assert!(err.to_string().as_str().contains("add_location_fallback"));
+
+        // Invalid values change nothing.
+        assert!(env
+            .eval("foo.set_collection_context({'add_include': False, 'add_location': 'bogus'})")
+            .is_err());
+        assert!(env
+            .eval("foo.set_collection_context({'add_include': False, 'add_source': 'yes'})")
+            .is_err());
+        eval_assert(&mut env, "foo.add_include == True")?;
+        eval_assert(&mut env, "foo.add_source == True")?;
+
+        assert!(env.eval("foo.set_collection_context([])").is_err());
+        assert!(env.eval("bar.set_collection_context({})").is_err());
+
+        env.eval("foo.lock()")?;
+        let err = env
+            .eval("foo.set_collection_context({'add_include': False})")
+            .unwrap_err();
+        assert!(err.to_string().as_str().contains("locked"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,772 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Matching of Python resources against selection criteria.
+
+A `ResourceSelector` matches resources by name glob, resource type,
+top-level import package, and size. `PythonExecutable.apply()` pairs a
+selector with changes to the add collection context of matching resources.
+*/
+
+use {
+    anyhow::{Context, Result},
+    python_packaging::{
+        location::ConcreteResourceLocation,
+        resource::{
+            PythonExtensionModule, PythonModuleSource, PythonPackageDistributionResource,
+            PythonPackageResource, PythonResource,
+        },
+        resource_collection::{PrePackagedResource, PythonResourceAddCollectionContext},
+    },
+    starlark::values::{
+        error::{
+            RuntimeError, UnsupportedOperation, ValueError, INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+        },
+        none::NoneType,
+        {Mutable, TypedValue, Value, ValueResult},
+    },
+    starlark::{
+        starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+        starlark_signature_extraction, starlark_signatures,
+    },
+    std::collections::BTreeMap,
+    tugger_file_manifest::{File, FileData},
+};
+
+/// Resource types that can be matched by a `ResourceSelector`.
+pub const SELECTOR_RESOURCE_TYPES: &[&str] = &[
+    "extension-module",
+    "file",
+    "module-source",
+    "package-distribution-resource",
+    "package-resource",
+];
+
+/// Normalize a package name for comparison.
+///
+/// Comparison is case insensitive and treats `-` and `_` as equivalent.
+fn normalize_package(name: &str) -> String {
+    name.to_lowercase().replace('-', "_")
+}
+
+/// The top-level package of a dotted Python name.
+fn top_level_package(name: &str) -> String {
+    normalize_package(name.split('.').next().unwrap_or(name))
+}
+
+/// A resource as seen by a `ResourceSelector`.
+pub struct SelectorCandidate<'a> {
+    /// One of `SELECTOR_RESOURCE_TYPES`.
+    pub resource_type: &'static str,
+
+    /// Name the selector's `name` glob is matched against.
+    ///
+    /// Package resources and distribution resources are named
+    /// `<package>:<resource name>`.
+    pub name: String,
+
+    /// Normalized name of the top-level package the resource is imported from.
+    ///
+    /// Package distribution resources are named after their distribution,
+    /// which can differ from the import name, so they have none.
+    pub top_level_package: Option<String>,
+
+    /// Data constituting the resource, used to compute its size.
+    pub data: Vec<&'a FileData>,
+}
+
+impl<'a> SelectorCandidate<'a> {
+    pub fn module_source(module: &'a PythonModuleSource) -> Self {
+        Self {
+            resource_type: "module-source",
+            name: module.name.clone(),
+            top_level_package: Some(top_level_package(&module.name)),
+            data: vec![&module.source],
+        }
+    }
+
+    pub fn package_resource(resource: &'a PythonPackageResource) -> Self {
+        Self {
+            resource_type: "package-resource",
+            name: format!("{}:{}", resource.leaf_package, resource.relative_name),
+            top_level_package: Some(top_level_package(&resource.leaf_package)),
+            data: vec![&resource.data],
+        }
+    }
+
+    pub fn package_distribution_resource(resource: &'a PythonPackageDistributionResource) -> Self {
+        Self {
+            resource_type: "package-distribution-resource",
+            name: format!("{}:{}", resource.package, resource.name),
+            top_level_package: None,
+            data: vec![&resource.data],
+        }
+    }
+
+    pub fn extension_module(module: &'a PythonExtensionModule) -> Self {
+        Self {
+            resource_type: "extension-module",
+            name: module.name.clone(),
+            top_level_package: Some(top_level_package(&module.name)),
+            data: module
+                .shared_library
+                .iter()
+                .chain(module.object_file_data.iter())
+                .collect(),
+        }
+    }
+
+    pub fn file(file: &'a File) -> Self {
+        Self {
+            resource_type: "file",
+            name: file.path_string(),
+            top_level_package: None,
+            data: vec![file.entry().file_data()],
+        }
+    }
+
+    /// Construct an instance from a `PythonResource`.
+    ///
+    /// Returns `None` for resource types selectors can't match.
+    pub fn resource(resource: &'a PythonResource) -> Option<Self> {
+        match resource {
+            PythonResource::ModuleSource(m) => Some(Self::module_source(m)),
+            PythonResource::PackageResource(r) => Some(Self::package_resource(r)),
+            PythonResource::PackageDistributionResource(r) => {
+                Some(Self::package_distribution_resource(r))
+            }
+            PythonResource::ExtensionModule(em) => Some(Self::extension_module(em)),
+            PythonResource::File(file) => Some(Self::file(file)),
+            _ => None,
+        }
+    }
+
+    /// Total size in bytes of the resource's data.
+    pub fn size(&self) -> Result<u64> {
+        let mut size = 0;
+
+        for data in &self.data {
+            size += match data {
+                FileData::Memory(data) => data.len() as u64,
+                FileData::Path(path) => std::fs::metadata(path)
+                    .with_context(|| format!("reading metadata of {}", path.display()))?
+                    .len(),
+            };
+        }
+
+        Ok(size)
+    }
+}
+
+/// Size thresholds classifying resources as `small`, `medium`, or `large`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SizeBucketThresholds {
+    /// Size in bytes from which a resource is `medium`.
+    pub medium: u64,
+    /// Size in bytes from which a resource is `large`.
+    pub large: u64,
+}
+
+impl Default for SizeBucketThresholds {
+    fn default() -> Self {
+        Self {
+            medium: 64 * 1024,
+            large: 1024 * 1024,
+        }
+    }
+}
+
+impl SizeBucketThresholds {
+    /// The bucket a size in bytes falls in.
+    pub fn bucket(&self, size: u64) -> &'static str {
+        if size >= self.large {
+            "large"
+        } else if size >= self.medium {
+            "medium"
+        } else {
+            "small"
+        }
+    }
+}
+
+/// A resource that was collected before a selector was registered.
+pub enum CollectedResource {
+    ModuleSource(String),
+    PackageResource(String, String),
+    PackageDistributionResource(String, String),
+    ExtensionModule(String),
+    File(String),
+}
+
+/// Obtain the selector candidates for an already collected resource entry.
+pub fn collected_candidates(
+    entry: &PrePackagedResource,
+) -> Vec<(SelectorCandidate<'_>, CollectedResource)> {
+    let mut res = vec![];
+
+    if entry.is_extension_module {
+        let data = entry
+            .in_memory_extension_module_shared_library
+            .iter()
+            .chain(
+                entry
+                    .relative_path_extension_module_shared_library
+                    .iter()
+                    .map(|(_, data)| data),
+            )
+            .collect();
+
+        res.push((
+            SelectorCandidate {
+                resource_type: "extension-module",
+                name: entry.name.clone(),
+                top_level_package: Some(top_level_package(&entry.name)),
+                data,
+            },
+            CollectedResource::ExtensionModule(entry.name.clone()),
+        ));
+    } else if entry.in_memory_source.is_some()
+        || entry.relative_path_module_source.is_some()
+        || entry.in_memory_bytecode.is_some()
+        || entry.in_memory_bytecode_opt1.is_some()
+        || entry.in_memory_bytecode_opt2.is_some()
+        || entry.relative_path_bytecode.is_some()
+        || entry.relative_path_bytecode_opt1.is_some()
+        || entry.relative_path_bytecode_opt2.is_some()
+    {
+        let data = entry
+            .in_memory_source
+            .iter()
+            .chain(
+                entry
+                    .relative_path_module_source
+                    .iter()
+                    .map(|(_, data)| data),
+            )
+            .collect();
+
+        res.push((
+            SelectorCandidate {
+                resource_type: "module-source",
+                name: entry.name.clone(),
+                top_level_package: Some(top_level_package(&entry.name)),
+                data,
+            },
+            CollectedResource::ModuleSource(entry.name.clone()),
+        ));
+    }
+
+    let package_resources = entry
+        .in_memory_resources
+        .iter()
+        .flat_map(|map| map.iter())
+        .chain(
+            entry
+                .relative_path_package_resources
+                .iter()
+                .flat_map(|map| map.iter().map(|(name, (_, data))| (name, data))),
+        );
+
+    for (name, data) in package_resources {
+        res.push((
+            SelectorCandidate {
+                resource_type: "package-resource",
+                name: format!("{}:{}", entry.name, name),
+                top_level_package: Some(top_level_package(&entry.name)),
+                data: vec![data],
+            },
+            CollectedResource::PackageResource(entry.name.clone(), name.clone()),
+        ));
+    }
+
+    let distribution_resources = entry
+        .in_memory_distribution_resources
+        .iter()
+        .flat_map(|map| map.iter())
+        .chain(
+            entry
+                .relative_path_distribution_resources
+                .iter()
+                .flat_map(|map| map.iter().map(|(name, (_, data))| (name, data))),
+        );
+
+    for (name, data) in distribution_resources {
+        res.push((
+            SelectorCandidate {
+                resource_type: "package-distribution-resource",
+                name: format!("{}:{}", entry.name, name),
+                top_level_package: None,
+                data: vec![data],
+            },
+            CollectedResource::PackageDistributionResource(entry.name.clone(), name.clone()),
+        ));
+    }
+
+    if let Some(data) = &entry.file_data_embedded {
+        res.push((
+            SelectorCandidate {
+                resource_type: "file",
+                name: entry.name.clone(),
+                top_level_package: None,
+                data: vec![data],
+            },
+            CollectedResource::File(entry.name.clone()),
+        ));
+    } else if let Some((_, data)) = &entry.file_data_utf8_relative_path {
+        res.push((
+            SelectorCandidate {
+                resource_type: "file",
+                name: entry.name.clone(),
+                top_level_package: None,
+                data: vec![data],
+            },
+            CollectedResource::File(entry.name.clone()),
+        ));
+    }
+
+    res
+}
+
+/// Criteria for matching resources.
+///
+/// Unset criteria match everything.
+#[derive(Clone, Debug, Default)]
+pub struct ResourceSelector {
+    pub name: Option<glob::Pattern>,
+    pub resource_type: Option<String>,
+    pub top_level_package: Option<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+}
+
+impl ResourceSelector {
+    /// Whether a candidate resource satisfies all criteria.
+    pub fn matches(&self, candidate: &SelectorCandidate) -> Result<bool> {
+        if let Some(resource_type) = &self.resource_type {
+            if resource_type != candidate.resource_type {
+                return Ok(false);
+            }
+        }
+
+        if let Some(name) = &self.name {
+            if !name.matches(&candidate.name) {
+                return Ok(false);
+            }
+        }
+
+        if let Some(package) = &self.top_level_package {
+            if candidate.top_level_package.as_ref() != Some(package) {
+                return Ok(false);
+            }
+        }
+
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let size = candidate.size()?;
+
+            if matches!(self.min_size, Some(min) if size < min)
+                || matches!(self.max_size, Some(max) if size > max)
+            {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Human readable description, used to identify the selector in reports.
+    pub fn description(&self) -> String {
+        let mut criteria = vec![];
+
+        if let Some(name) = &self.name {
+            criteria.push(format!("name={}", name.as_str()));
+        }
+        if let Some(resource_type) = &self.resource_type {
+            criteria.push(format!("type={}", resource_type));
+        }
+        if let Some(package) = &self.top_level_package {
+            criteria.push(format!("top_level_package={}", package));
+        }
+        if let Some(min_size) = self.min_size {
+            criteria.push(format!("min_size={}", min_size));
+        }
+        if let Some(max_size) = self.max_size {
+            criteria.push(format!("max_size={}", max_size));
+        }
+
+        format!("ResourceSelector<{}>", criteria.join(", "))
+    }
+}
+
+/// Changes to apply to the add collection context of selected resources.
+#[derive(Clone, Debug, Default)]
+pub struct ResourceChanges {
+    pub include: Option<bool>,
+    pub location: Option<ConcreteResourceLocation>,
+    pub include_source: Option<bool>,
+}
+
+impl ResourceChanges {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_none() && self.location.is_none() && self.include_source.is_none()
+    }
+
+    pub fn apply_to_context(&self, context: &mut PythonResourceAddCollectionContext) {
+        if let Some(include) = self.include {
+            context.include = include;
+        }
+        if let Some(location) = &self.location {
+            context.location = location.clone();
+        }
+        if let Some(include_source) = self.include_source {
+            context.store_source = include_source;
+        }
+    }
+}
+
+/// Selectors registered on an executable, in registration order.
+#[derive(Debug, Default)]
+pub struct ResourceSelectors {
+    rules: Vec<(ResourceSelector, ResourceChanges)>,
+
+    /// Resource name to descriptions of selectors that changed it.
+    touched: BTreeMap<String, Vec<String>>,
+}
+
+impl ResourceSelectors {
+    /// Register a selector to apply to resources added from now on.
+    pub fn register(&mut self, selector: ResourceSelector, changes: ResourceChanges) {
+        self.rules.push((selector, changes));
+    }
+
+    /// Record that a selector changed a resource.
+    pub fn record(&mut self, name: &str, selector: &ResourceSelector) {
+        self.touched
+            .entry(name.to_string())
+            .or_default()
+            .push(selector.description());
+    }
+
+    /// Obtain the changes of registered selectors matching a candidate, in registration order.
+    pub fn matching(
+        &self,
+        candidate: &SelectorCandidate<'_>,
+    ) -> Result<Vec<(&ResourceSelector, &ResourceChanges)>> {
+        let mut res = vec![];
+
+        for (selector, changes) in &self.rules {
+            if selector.matches(candidate)? {
+                res.push((selector, changes));
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Apply matching selectors to the add collection context of a newly added resource.
+    ///
+    /// Selectors are applied in registration order, so the last write wins.
+    pub fn apply_to_added(
+        &mut self,
+        candidate: &SelectorCandidate,
+        add_context: &mut PythonResourceAddCollectionContext,
+    ) -> Result<()> {
+        let matching = self
+            .matching(candidate)?
+            .into_iter()
+            .map(|(selector, changes)| (selector.clone(), changes.clone()))
+            .collect::<Vec<_>>();
+
+        for (selector, changes) in matching {
+            changes.apply_to_context(add_context);
+            self.record(&candidate.name, &selector);
+        }
+
+        Ok(())
+    }
+
+    /// Resource name to descriptions of selectors that changed it.
+    pub fn touched(&self) -> &BTreeMap<String, Vec<String>> {
+        &self.touched
+    }
+}
+
+/// Starlark value wrapper for `ResourceSelector`.
+#[derive(Clone, Debug)]
+pub struct ResourceSelectorValue {
+    pub inner: ResourceSelector,
+}
+
+impl TypedValue for ResourceSelectorValue {
+    type Holder = Mutable<ResourceSelectorValue>;
+    const TYPE: &'static str = "ResourceSelector";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn to_repr_impl(&self, buf: &mut String) -> std::fmt::Result {
+        buf.push_str(&self.inner.description());
+
+        Ok(())
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        let inner = &self.inner;
+
+        Ok(match attribute {
+            "name" => match &inner.name {
+                Some(name) => Value::from(name.as_str()),
+                None => Value::from(NoneType::None),
+            },
+            "type" => match &inner.resource_type {
+                Some(resource_type) => Value::from(resource_type.as_str()),
+                None => Value::from(NoneType::None),
+            },
+            "top_level_package" => match &inner.top_level_package {
+                Some(package) => Value::from(package.as_str()),
+                None => Value::from(NoneType::None),
+            },
+            "min_size" => match inner.min_size {
+                Some(size) => Value::from(size as i64),
+                None => Value::from(NoneType::None),
+            },
+            "max_size" => match inner.max_size {
+                Some(size) => Value::from(size as i64),
+                None => Value::from(NoneType::None),
+            },
+            _ => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::GetAttr(attribute.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        })
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(matches!(
+            attribute,
+            "name" | "type" | "top_level_package" | "min_size" | "max_size"
+        ))
+    }
+}
+
+fn selector_arg_error(message: String) -> ValueError {
+    ValueError::from(RuntimeError {
+        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+        message,
+        label: "ResourceSelector()".to_string(),
+    })
+}
+
+fn size_arg(key: &str, value: &Value) -> Result<Option<u64>, ValueError> {
+    match value.get_type() {
+        "NoneType" => Ok(None),
+        "int" if value.to_int()? >= 0 => Ok(Some(value.to_int()? as u64)),
+        _ => Err(selector_arg_error(format!(
+            "{} must be a non-negative int or None; got {}",
+            key,
+            value.to_repr()
+        ))),
+    }
+}
+
+fn str_arg(key: &str, value: &Value) -> Result<Option<String>, ValueError> {
+    match value.get_type() {
+        "NoneType" => Ok(None),
+        "string" => Ok(Some(value.to_string())),
+        t => Err(selector_arg_error(format!(
+            "{} must be a string or None; got {}",
+            key, t
+        ))),
+    }
+}
+
+impl ResourceSelectorValue {
+    /// ResourceSelector(name=None, type=None, top_level_package=None, min_size=None, max_size=None)
+    ///
+    /// Keyword arguments are parsed manually because `type` can't be
+    /// declared as a parameter name.
+    fn from_kwargs<'a>(kwargs: impl Iterator<Item = (&'a str, Value)>) -> ValueResult {
+        let mut selector = ResourceSelector::default();
+
+        for (key, value) in kwargs {
+            match key {
+                "name" => {
+                    selector.name = str_arg(key, &value)?
+                        .map(|name| {
+                            glob::Pattern::new(&name).map_err(|e| {
+                                selector_arg_error(format!("invalid name pattern {}: {}", name, e))
+                            })
+                        })
+                        .transpose()?;
+                }
+                "type" => {
+                    selector.resource_type = str_arg(key, &value)?;
+
+                    if let Some(resource_type) = &selector.resource_type {
+                        if !SELECTOR_RESOURCE_TYPES.contains(&resource_type.as_str()) {
+                            return Err(selector_arg_error(format!(
+                                "type must be one of {}; got {}",
+                                SELECTOR_RESOURCE_TYPES.join(", "),
+                                resource_type
+                            )));
+                        }
+                    }
+                }
+                "top_level_package" => {
+                    selector.top_level_package =
+                        str_arg(key, &value)?.map(|p| normalize_package(&p));
+                }
+                "min_size" => {
+                    selector.min_size = size_arg(key, &value)?;
+                }
+                "max_size" => {
+                    selector.max_size = size_arg(key, &value)?;
+                }
+                _ => {
+                    return Err(selector_arg_error(format!(
+                        "unexpected keyword argument {}",
+                        key
+                    )));
+                }
+            }
+        }
+
+        Ok(Value::new(ResourceSelectorValue { inner: selector }))
+    }
+}
+
+starlark_module! { resource_selector_module =>
+    #[allow(non_snake_case)]
+    ResourceSelector(**kwargs) {
+        ResourceSelectorValue::from_kwargs(kwargs.iter().map(|(k, v)| (k.as_str(), v.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::super::testutil::*, super::*, anyhow::Result,
+        python_packaging::resource::PythonPackageDistributionResourceFlavor,
+    };
+
+    #[test]
+    fn test_constructor() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        let selector = env.eval("ResourceSelector(name='numpy.*', type='module-source')")?;
+        assert_eq!(selector.get_type(), ResourceSelectorValue::TYPE);
+        assert_eq!(
+            selector.to_string(),
+            "ResourceSelector<name=numpy.*, type=module-source>"
+        );
+
+        eval_assert(&mut env, "ResourceSelector().name == None")?;
+        eval_assert(
+            &mut env,
+            "ResourceSelector(top_level_package='Foo-Bar').top_level_package == 'foo_bar'",
+        )?;
+        eval_assert(&mut env, "ResourceSelector(min_size=10).min_size == 10")?;
+
+        assert!(env.eval("ResourceSelector(type='bad')").is_err());
+        assert!(env.eval("ResourceSelector(name='[')").is_err());
+        assert!(env.eval("ResourceSelector(min_size=-1)").is_err());
+        assert!(env.eval("ResourceSelector(unknown=True)").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches() -> Result<()> {
+        let module = PythonModuleSource {
+            name: "numpy.core".to_string(),
+            source: FileData::Memory(vec![0; 10]),
+            is_package: false,
+            cache_tag: "cpython-39".to_string(),
+            is_stdlib: false,
+            is_test: false,
+        };
+        let candidate = SelectorCandidate::module_source(&module);
+
+        assert!(ResourceSelector::default().matches(&candidate)?);
+
+        let selector = ResourceSelector {
+            name: Some(glob::Pattern::new("numpy.*")?),
+            resource_type: Some("module-source".to_string()),
+            top_level_package: Some("numpy".to_string()),
+            min_size: Some(10),
+            max_size: Some(10),
+        };
+        assert!(selector.matches(&candidate)?);
+
+        for selector in &[
+            ResourceSelector {
+                name: Some(glob::Pattern::new("scipy.*")?),
+                ..ResourceSelector::default()
+            },
+            ResourceSelector {
+                resource_type: Some("package-resource".to_string()),
+                ..ResourceSelector::default()
+            },
+            ResourceSelector {
+                top_level_package: Some("scipy".to_string()),
+                ..ResourceSelector::default()
+            },
+            ResourceSelector {
+                min_size: Some(11),
+                ..ResourceSelector::default()
+            },
+            ResourceSelector {
+                max_size: Some(9),
+                ..ResourceSelector::default()
+            },
+        ] {
+            assert!(!selector.matches(&candidate)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_distribution_resource_top_level_package() -> Result<()> {
+        let resource = PythonPackageDistributionResource {
+            location: PythonPackageDistributionResourceFlavor::DistInfo,
+            package: "PyYAML".to_string(),
+            version: "5.3".to_string(),
+            name: "METADATA".to_string(),
+            data: FileData::Memory(vec![]),
+        };
+        let candidate = SelectorCandidate::package_distribution_resource(&resource);
+
+        for package in &["pyyaml", "yaml"] {
+            let selector = ResourceSelector {
+                top_level_package: Some(package.to_string()),
+                ..ResourceSelector::default()
+            };
+            assert!(!selector.matches(&candidate)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_size_bucket_thresholds() {
+        let thresholds = SizeBucketThresholds::default();
+        assert_eq!(thresholds.bucket(0), "small");
+        assert_eq!(thresholds.bucket(64 * 1024 - 1), "small");
+        assert_eq!(thresholds.bucket(64 * 1024), "medium");
+        assert_eq!(thresholds.bucket(1024 * 1024 - 1), "medium");
+        assert_eq!(thresholds.bucket(1024 * 1024), "large");
+
+        let thresholds = SizeBucketThresholds {
+            medium: 10,
+            large: 10,
+        };
+        assert_eq!(thresholds.bucket(9), "small");
+        assert_eq!(thresholds.bucket(10), "large");
+    }
+}
@@ -0,0 +1,329 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Strict evaluation of configuration files.
+
+Strict mode is enabled by `--strict-config` or by calling `set_strict(True)`
+from Starlark. In strict mode:
+
+* Setting an unknown attribute is an error suggesting the closest known
+  attribute.
+* Values returned by resource enumeration functions that are never added to
+  anything are reported as warnings when evaluation finishes.
+* Deprecation warnings are errors.
+
+Starlark values setting attributes have no access to the evaluation context,
+so strict state is tracked per thread. Evaluation of a configuration file
+happens on a single thread.
+*/
+
+use {
+    super::python_resource::{is_resource_value, resource_value_full_name},
+    slog::warn,
+    starlark::{
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            Value, ValueResult,
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::similar_names,
+    std::{cell::RefCell, collections::HashSet},
+};
+
+/// Maximum number of resource names listed in an unused values warning.
+const UNUSED_NAMES_LIMIT: usize = 5;
+
+/// Identifies a resource value by its type and full name.
+type ResourceKey = (String, String);
+
+/// Resource values returned by a single call to an enumeration function.
+struct Enumeration {
+    label: String,
+    resources: Vec<ResourceKey>,
+}
+
+#[derive(Default)]
+struct StrictState {
+    enabled: bool,
+    enumerations: Vec<Enumeration>,
+    added: HashSet<ResourceKey>,
+}
+
+thread_local! {
+    static STATE: RefCell<StrictState> = RefCell::new(StrictState::default());
+}
+
+/// Discard all strict state and set whether strict mode is enabled.
+///
+/// Called when an evaluation context is constructed.
+pub fn reset(enabled: bool) {
+    STATE.with(|state| {
+        state.replace(StrictState {
+            enabled,
+            ..StrictState::default()
+        });
+    });
+}
+
+/// Whether strict mode is enabled on this thread.
+pub fn is_enabled() -> bool {
+    STATE.with(|state| state.borrow().enabled)
+}
+
+/// Obtain the error for setting an attribute a type doesn't define.
+///
+/// `known` holds the attributes that can be set on the type. In strict mode
+/// the error suggests the closest of them.
+pub fn unknown_attribute_error(type_name: &str, attribute: &str, known: &[&str]) -> ValueError {
+    if !is_enabled() {
+        return ValueError::OperationNotSupported {
+            op: UnsupportedOperation::SetAttr(attribute.to_string()),
+            left: type_name.to_string(),
+            right: None,
+        };
+    }
+
+    let suggestion = match similar_names(attribute, known.iter().copied()).first() {
+        Some(candidate) => format!("; did you mean {}?", candidate),
+        None => "".to_string(),
+    };
+
+    ValueError::from(RuntimeError {
+        code: "PYOXIDIZER_STRICT",
+        message: format!(
+            "{} has no settable attribute {}{}",
+            type_name, attribute, suggestion
+        ),
+        label: format!("{}.{}", type_name, attribute),
+    })
+}
+
+/// Emit a deprecation warning, which is an error in strict mode.
+pub fn deprecation_warning(
+    logger: &slog::Logger,
+    label: &str,
+    message: &str,
+) -> Result<(), ValueError> {
+    if is_enabled() {
+        Err(ValueError::from(RuntimeError {
+            code: "PYOXIDIZER_STRICT",
+            message: format!(
+                "{} (deprecation warnings are errors in strict mode)",
+                message
+            ),
+            label: label.to_string(),
+        }))
+    } else {
+        warn!(logger, "deprecated: {}", message);
+        Ok(())
+    }
+}
+
+fn resource_key(value: &Value) -> Result<Option<ResourceKey>, ValueError> {
+    Ok(if is_resource_value(value) {
+        Some((
+            value.get_type().to_string(),
+            resource_value_full_name(value)?,
+        ))
+    } else {
+        None
+    })
+}
+
+/// Record the values returned by a resource enumeration function.
+///
+/// This is a no-op unless strict mode is enabled.
+pub fn record_enumerated(label: &str, values: &[Value]) -> Result<(), ValueError> {
+    if !is_enabled() {
+        return Ok(());
+    }
+
+    let mut resources = vec![];
+    for value in values {
+        if let Some(key) = resource_key(value)? {
+            resources.push(key);
+        }
+    }
+
+    STATE.with(|state| {
+        state.borrow_mut().enumerations.push(Enumeration {
+            label: label.to_string(),
+            resources,
+        })
+    });
+
+    Ok(())
+}
+
+/// Record that a resource value was added to something.
+pub fn record_added(value: &Value) -> Result<(), ValueError> {
+    if !is_enabled() {
+        return Ok(());
+    }
+
+    if let Some(key) = resource_key(value)? {
+        STATE.with(|state| state.borrow_mut().added.insert(key));
+    }
+
+    Ok(())
+}
+
+/// Describe enumerated resource values that were never added to anything.
+///
+/// Returns one message per enumeration function call with unused values.
+pub fn unused_values_warnings() -> Vec<String> {
+    STATE.with(|state| {
+        let state = state.borrow();
+
+        state
+            .enumerations
+            .iter()
+            .filter_map(|enumeration| {
+                let unused = enumeration
+                    .resources
+                    .iter()
+                    .filter(|key| !state.added.contains(*key))
+                    .map(|(_, name)| name.as_str())
+                    .collect::<Vec<_>>();
+
+                if unused.is_empty() {
+                    return None;
+                }
+
+                let mut names = unused
+                    .iter()
+                    .take(UNUSED_NAMES_LIMIT)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if unused.len() > UNUSED_NAMES_LIMIT {
+                    names.push_str(", ...");
+                }
+
+                Some(format!(
+                    "{} of {} values returned by {} were never added to anything: {}",
+                    unused.len(),
+                    enumeration.resources.len(),
+                    enumeration.label,
+                    names
+                ))
+            })
+            .collect()
+    })
+}
+
+/// set_strict(enabled)
+fn starlark_set_strict(enabled: bool) -> ValueResult {
+    STATE.with(|state| state.borrow_mut().enabled = enabled);
+
+    Ok(Value::new(NoneType::None))
+}
+
+starlark_module! { strict_module =>
+    set_strict(enabled: bool) {
+        starlark_set_strict(enabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{super::testutil::*, *},
+        anyhow::Result,
+    };
+
+    #[test]
+    fn test_unknown_attribute() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+        env.eval("m = exe.make_python_module_source('foo', 'import bar')")?;
+        env.eval("policy = dist.make_python_packaging_policy()")?;
+        env.eval("config = dist.make_python_interpreter_config()")?;
+
+        let err = env.eval("m.add_inclde = False").unwrap_err().to_string();
+        assert!(!err.as_str().contains("did you mean"));
+
+        env.eval("set_strict(True)")?;
+        assert!(is_enabled());
+
+        let err = env.eval("m.add_inclde = False").unwrap_err().to_string();
+        assert!(err.as_str().contains(
+            "PythonModuleSource has no settable attribute add_inclde; did you mean add_include?"
+        ));
+
+        let err = env
+            .eval("policy.resources_locatoin = 'in-memory'")
+            .unwrap_err()
+            .to_string();
+        assert!(err.as_str().contains("did you mean resources_location?"));
+
+        let err = env.eval("config.isolatd = True").unwrap_err().to_string();
+        assert!(err.as_str().contains("did you mean isolated?"));
+
+        let err = env.eval("exe.bogus = True").unwrap_err().to_string();
+        assert!(err
+            .as_str()
+            .contains("PythonExecutable has no settable attribute bogus"));
+        assert!(!err.as_str().contains("did you mean"));
+
+        env.eval("set_strict(False)")?;
+        assert!(!is_enabled());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unused_values() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?
+            .strict(true)
+            .into_context()?;
+        add_exe(&mut env)?;
+
+        env.eval("used = exe.make_python_module_source('used', '')")?;
+        env.eval("unused = exe.make_python_module_source('unused', '')")?;
+        record_enumerated(
+            "PythonExecutable.pip_install()",
+            &[env.get_var("used").unwrap(), env.get_var("unused").unwrap()],
+        )
+        .unwrap();
+        assert_eq!(unused_values_warnings().len(), 1);
+
+        env.eval("exe.add_python_resource(used)")?;
+        assert_eq!(
+            unused_values_warnings(),
+            vec![
+                "1 of 2 values returned by PythonExecutable.pip_install() were never added to anything: unused"
+            ]
+        );
+
+        env.eval("exe.add_python_resource(unused)")?;
+        assert!(unused_values_warnings().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deprecation_warning() {
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+
+        reset(false);
+        assert!(deprecation_warning(&logger, "foo()", "foo() is deprecated").is_ok());
+
+        reset(true);
+        match deprecation_warning(&logger, "foo()", "foo() is deprecated") {
+            Err(ValueError::Runtime(e)) => assert_eq!(
+                e.message,
+                "foo() is deprecated (deprecation warnings are errors in strict mode)"
+            ),
+            _ => panic!("expected an error"),
+        }
+
+        reset(false);
+    }
+}
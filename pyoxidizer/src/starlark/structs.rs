@@ -0,0 +1,93 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+An immutable Starlark value with named fields.
+
+This mirrors the `struct` type of the Starlark extensions, which isn't
+part of the environment used by PyOxidizer.
+*/
+
+use {
+    starlark::values::{
+        error::{UnsupportedOperation, ValueError},
+        string::rc::RcString,
+        {Immutable, TypedValue, Value, ValueResult},
+    },
+    std::{collections::BTreeMap, fmt, fmt::Write},
+};
+
+/// Starlark value holding named fields that can't be modified.
+///
+/// Fields are ordered by name.
+pub struct StructValue {
+    fields: BTreeMap<String, Value>,
+}
+
+impl StructValue {
+    pub fn new(fields: BTreeMap<String, Value>) -> Self {
+        Self { fields }
+    }
+}
+
+impl TypedValue for StructValue {
+    type Holder = Immutable<StructValue>;
+    const TYPE: &'static str = "struct";
+
+    fn values_for_descendant_check_and_freeze<'a>(
+        &'a self,
+    ) -> Box<dyn Iterator<Item = Value> + 'a> {
+        Box::new(self.fields.values().cloned())
+    }
+
+    fn to_repr_impl(&self, buf: &mut String) -> fmt::Result {
+        write!(buf, "struct(")?;
+        for (i, (name, value)) in self.fields.iter().enumerate() {
+            if i != 0 {
+                write!(buf, ", ")?;
+            }
+            write!(buf, "{}=", name)?;
+            value.to_repr_impl(buf)?;
+        }
+        write!(buf, ")")
+    }
+
+    fn equals(&self, other: &StructValue) -> Result<bool, ValueError> {
+        if self.fields.len() != other.fields.len() {
+            return Ok(false);
+        }
+
+        for (name, a) in &self.fields {
+            match other.fields.get(name) {
+                Some(b) if a.equals(b)? => {}
+                _ => return Ok(false),
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        self.fields
+            .get(attribute)
+            .cloned()
+            .ok_or_else(|| ValueError::OperationNotSupported {
+                op: UnsupportedOperation::GetAttr(attribute.to_string()),
+                left: Self::TYPE.to_string(),
+                right: None,
+            })
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(self.fields.contains_key(attribute))
+    }
+
+    fn dir_attr(&self) -> Result<Vec<RcString>, ValueError> {
+        Ok(self
+            .fields
+            .keys()
+            .map(|name| RcString::from(name.clone()))
+            .collect())
+    }
+}
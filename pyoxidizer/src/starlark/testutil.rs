@@ -6,16 +6,58 @@ use {
     crate::{
         environment::default_target_triple,
         logging::PrintlnDrain,
+        py_packaging::distribution::DistributionCache,
         starlark::eval::{EvaluationContext, EvaluationContextBuilder},
+        testing::{register_fixture_distribution, use_real_distribution},
         testutil::{get_env, DISTRIBUTION_CACHE},
     },
     anyhow::{anyhow, Result},
     codemap::CodeMap,
     codemap_diagnostic::Diagnostic,
+    once_cell::sync::Lazy,
     slog::Drain,
     starlark::values::Value,
+    std::sync::Arc,
 };
 
+static FIXTURE_DISTRIBUTION_DIR: Lazy<tempfile::TempDir> = Lazy::new(|| {
+    tempfile::Builder::new()
+        .prefix("pyoxidizer-fixture-distribution-")
+        .tempdir()
+        .expect("unable to create fixture distribution directory")
+});
+
+/// A distribution cache whose default distribution is the fixture distribution.
+static FIXTURE_DISTRIBUTION_CACHE: Lazy<Arc<DistributionCache>> = Lazy::new(|| {
+    let dest_dir = get_env()
+        .expect("failed to resolve environment")
+        .python_distributions_dir();
+
+    let cache = DistributionCache::new(Some(&dest_dir));
+
+    register_fixture_distribution(
+        &cache,
+        &dest_dir,
+        FIXTURE_DISTRIBUTION_DIR.path(),
+        default_target_triple(),
+    )
+    .expect("failed to register fixture distribution");
+
+    Arc::new(cache)
+});
+
+/// Obtain the distribution cache to use for Starlark tests.
+///
+/// Tests use the fixture distribution by default. Set the environment
+/// variable named by `REAL_DISTRIBUTION_ENV` to use a real distribution.
+fn test_distribution_cache() -> Arc<DistributionCache> {
+    if use_real_distribution() {
+        DISTRIBUTION_CACHE.clone()
+    } else {
+        FIXTURE_DISTRIBUTION_CACHE.clone()
+    }
+}
+
 /// Construct a new `EvaluationContextBuilder` suitable for the test environment.
 pub fn test_evaluation_context_builder() -> Result<EvaluationContextBuilder> {
     let env = get_env()?;
@@ -32,11 +74,20 @@ pub fn test_evaluation_context_builder() -> Result<EvaluationContextBuilder> {
     let config_path = cwd.join("dummy");
 
     let builder = EvaluationContextBuilder::new(&env, logger, config_path, default_target_triple())
-        .distribution_cache(DISTRIBUTION_CACHE.clone());
+        .distribution_cache(test_distribution_cache());
 
     Ok(builder)
 }
 
+/// Construct a new `EvaluationContextBuilder` using a real Python distribution.
+///
+/// Tests needing to run Python or needing a distribution other than the default
+/// can't use the fixture distribution. They always use a real distribution,
+/// regardless of the environment variable named by `REAL_DISTRIBUTION_ENV`.
+pub fn real_distribution_evaluation_context_builder() -> Result<EvaluationContextBuilder> {
+    Ok(test_evaluation_context_builder()?.distribution_cache(DISTRIBUTION_CACHE.clone()))
+}
+
 /// Add a PythonExecutable `exe` variable to the Starlark environment.
 pub fn add_exe(eval: &mut EvaluationContext) -> Result<()> {
     eval.eval("dist = default_python_distribution()")?;
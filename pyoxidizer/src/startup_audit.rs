@@ -0,0 +1,191 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Reports of access performed by built applications during startup.
+
+Applications write a startup audit when the `startup_audit_path` interpreter
+config setting is set or, when built with the `startup-trace` pyembed
+feature, when the [STARTUP_AUDIT_PATH_ENV] environment variable is set.
+[StartupAuditReport] parses the audit into a summary.
+*/
+
+use {
+    anyhow::{Context, Result},
+    std::{collections::BTreeSet, path::Path},
+};
+
+/// Environment variable pointing pyembed's `startup-trace` feature at an audit file.
+pub const STARTUP_AUDIT_PATH_ENV: &str = "PYEMBED_STARTUP_AUDIT_PATH";
+
+/// Undo the escaping of an audit entry detail.
+fn unescape(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => res.push('\t'),
+            Some('n') => res.push('\n'),
+            Some(c) => res.push(c),
+            None => res.push('\\'),
+        }
+    }
+
+    res
+}
+
+/// Summary of access recorded during the startup of an application.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StartupAuditReport {
+    /// Files opened.
+    pub files: BTreeSet<String>,
+    /// Directories listed.
+    pub directories: BTreeSet<String>,
+    /// Environment variables read.
+    pub env_vars: BTreeSet<String>,
+    /// Socket operations, in the order they were attempted.
+    pub sockets: Vec<String>,
+    /// The audit event ending the startup window, if it was reached.
+    pub end: Option<String>,
+}
+
+impl StartupAuditReport {
+    /// Parse the content of an audit file.
+    ///
+    /// Entries of unknown kinds are ignored.
+    pub fn parse(data: &str) -> Self {
+        let mut report = Self::default();
+
+        for line in data.lines() {
+            let mut parts = line.splitn(2, '\t');
+            let (kind, detail) = match (parts.next(), parts.next()) {
+                (Some(kind), Some(detail)) => (kind, unescape(detail)),
+                _ => continue,
+            };
+
+            match kind {
+                "open" => {
+                    report.files.insert(detail);
+                }
+                "listdir" => {
+                    report.directories.insert(detail);
+                }
+                "env" => {
+                    report.env_vars.insert(detail);
+                }
+                "socket" => report.sockets.push(detail),
+                "end" => report.end = Some(detail),
+                _ => {}
+            }
+        }
+
+        report
+    }
+
+    /// Read and parse an audit file.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("reading startup audit {}", path.display()))?;
+
+        Ok(Self::parse(&data))
+    }
+
+    /// Render a human readable report.
+    pub fn to_text(&self) -> String {
+        let mut s = format!(
+            "startup audit ({})\n",
+            match &self.end {
+                Some(event) => format!("ended by {}", event),
+                None => "startup did not complete".to_string(),
+            }
+        );
+
+        let sections: [(&str, Vec<&str>); 4] = [
+            (
+                "files opened",
+                self.files.iter().map(|x| x.as_str()).collect(),
+            ),
+            (
+                "directories listed",
+                self.directories.iter().map(|x| x.as_str()).collect(),
+            ),
+            (
+                "environment variables read",
+                self.env_vars.iter().map(|x| x.as_str()).collect(),
+            ),
+            (
+                "sockets attempted",
+                self.sockets.iter().map(|x| x.as_str()).collect(),
+            ),
+        ];
+
+        for (title, entries) in sections.iter() {
+            s.push_str(&format!("{} ({}):\n", title, entries.len()));
+            for entry in entries {
+                s.push_str(&format!("  {}\n", entry));
+            }
+        }
+
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("/a/b"), "/a/b");
+        assert_eq!(unescape("C:\\\\a\\tb\\nc"), "C:\\a\tb\nc");
+        assert_eq!(unescape("trailing\\"), "trailing\\");
+    }
+
+    #[test]
+    fn test_parse() {
+        let report = StartupAuditReport::parse(
+            "env\tTERMINFO_DIRS\n\
+            open\t/app/lib/packed-resources\n\
+            open\t/etc/os-release\n\
+            open\t/app/lib/packed-resources\n\
+            listdir\t/app/lib\n\
+            socket\tsocket.getaddrinfo ('example.com', 443, 0, 1, 0)\n\
+            bogus\tentry\n\
+            malformed\n\
+            end\tcpython.run_module\n",
+        );
+
+        assert_eq!(
+            report.files.iter().collect::<Vec<_>>(),
+            vec!["/app/lib/packed-resources", "/etc/os-release"]
+        );
+        assert_eq!(report.directories.len(), 1);
+        assert!(report.env_vars.contains("TERMINFO_DIRS"));
+        assert_eq!(
+            report.sockets,
+            vec!["socket.getaddrinfo ('example.com', 443, 0, 1, 0)".to_string()]
+        );
+        assert_eq!(report.end, Some("cpython.run_module".to_string()));
+
+        let text = report.to_text();
+        assert!(text.starts_with("startup audit (ended by cpython.run_module)\n"));
+        assert!(text.contains("files opened (2):\n  /app/lib/packed-resources\n"));
+        assert!(text.contains("sockets attempted (1):\n"));
+    }
+
+    #[test]
+    fn test_parse_incomplete() {
+        let report = StartupAuditReport::parse("open\t/etc/hosts\n");
+
+        assert_eq!(report.end, None);
+        assert!(report
+            .to_text()
+            .starts_with("startup audit (startup did not complete)\n"));
+    }
+}
@@ -0,0 +1,155 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Utilities for testing code built on top of this crate.
+
+Resolving a real Python distribution requires downloading and extracting a
+large archive. This module provides a tiny synthetic *fixture distribution*
+that can stand in for the default distribution when tests only need to
+inspect distribution metadata and resources. It is available to other
+crates via the `testing` feature.
+
+The fixture has no Python interpreter. Functionality that needs to run
+Python (compiling bytecode, installing packages, etc) will fail.
+*/
+
+use {
+    crate::py_packaging::{
+        distribution::{default_distribution_location, DistributionCache, DistributionFlavor},
+        standalone_distribution::StandaloneDistribution,
+    },
+    anyhow::Result,
+    python_packaging::resource::{PythonExtensionModule, PythonExtensionModuleVariants},
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
+};
+
+/// Environment variable that opts into using a real distribution in tests.
+pub const REAL_DISTRIBUTION_ENV: &str = "PYOXIDIZER_TEST_REAL_DISTRIBUTION";
+
+/// Standard library modules in the fixture distribution.
+///
+/// Tuples of module name and whether the module is a package.
+pub const FIXTURE_STDLIB_MODULES: &[(&str, bool)] = &[
+    ("__future__", false),
+    ("abc", false),
+    ("codecs", false),
+    ("collections", true),
+    ("collections.abc", false),
+    ("encodings", true),
+    ("encodings.utf_8", false),
+    ("importlib", true),
+    ("importlib.metadata", false),
+    ("io", false),
+    ("os", false),
+    ("test", true),
+    ("test.support", false),
+];
+
+/// Package resource in the fixture distribution.
+///
+/// Tuple of package name and resource name.
+pub const FIXTURE_PACKAGE_RESOURCE: (&str, &str) = ("collections", "fixture.txt");
+
+/// Name of the extension module in the fixture distribution.
+pub const FIXTURE_EXTENSION_MODULE: &str = "_fixture";
+
+/// Whether tests should use a real distribution instead of the fixture.
+pub fn use_real_distribution() -> bool {
+    std::env::var_os(REAL_DISTRIBUTION_ENV).is_some()
+}
+
+/// Write a fixture distribution to a directory and return it.
+///
+/// Source files for the standard library modules and resources are written
+/// under `base_dir`.
+pub fn fixture_distribution(
+    base_dir: &Path,
+    target_triple: &str,
+) -> Result<StandaloneDistribution> {
+    let stdlib_path = base_dir
+        .join("python")
+        .join("install")
+        .join("lib")
+        .join("python3.9");
+
+    let mut py_modules = BTreeMap::new();
+
+    for (name, is_package) in FIXTURE_STDLIB_MODULES {
+        let mut path = name.split('.').collect::<PathBuf>();
+        if *is_package {
+            path.push("__init__.py");
+        } else {
+            path.set_extension("py");
+        }
+
+        let path = stdlib_path.join(path);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(&path, format!("\"\"\"Fixture module {}.\"\"\"\n", name))?;
+
+        py_modules.insert(name.to_string(), path);
+    }
+
+    let (package, resource_name) = FIXTURE_PACKAGE_RESOURCE;
+    let resource_path = stdlib_path.join(package).join(resource_name);
+    std::fs::write(&resource_path, b"fixture resource\n")?;
+
+    let mut resources = BTreeMap::new();
+    resources
+        .entry(package.to_string())
+        .or_insert_with(BTreeMap::new)
+        .insert(resource_name.to_string(), resource_path);
+
+    let mut extension_modules = BTreeMap::new();
+    extension_modules.insert(
+        FIXTURE_EXTENSION_MODULE.to_string(),
+        std::iter::once(PythonExtensionModule {
+            name: FIXTURE_EXTENSION_MODULE.to_string(),
+            init_fn: Some(format!("PyInit_{}", FIXTURE_EXTENSION_MODULE)),
+            extension_file_suffix: ".so".to_string(),
+            shared_library: None,
+            object_file_data: vec![],
+            is_package: false,
+            link_libraries: vec![],
+            is_stdlib: true,
+            builtin_default: true,
+            required: false,
+            variant: None,
+            license: None,
+        })
+        .collect::<PythonExtensionModuleVariants>(),
+    );
+
+    StandaloneDistribution::fixture(
+        base_dir,
+        target_triple,
+        py_modules,
+        resources,
+        extension_modules,
+    )
+}
+
+/// Register a fixture distribution as the default distribution for a target.
+///
+/// After calling, resolving the default standalone distribution for
+/// `target_triple` into `dest_dir` via `cache` will yield the fixture
+/// distribution instead of downloading a real one.
+pub fn register_fixture_distribution(
+    cache: &DistributionCache,
+    dest_dir: &Path,
+    fixture_dir: &Path,
+    target_triple: &str,
+) -> Result<Arc<StandaloneDistribution>> {
+    let location =
+        default_distribution_location(&DistributionFlavor::Standalone, target_triple, None)?;
+
+    let dist = Arc::new(fixture_distribution(fixture_dir, target_triple)?);
+
+    cache.insert(&location, dest_dir, dist.clone())?;
+
+    Ok(dist)
+}
@@ -0,0 +1,387 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Timing of build phases.
+
+A [TimingCollector] records named spans of time. Spans opened while another
+span is open on the same thread become its children. Work moved to another
+thread can be attached to a span explicitly with
+[TimingCollector::span_with_parent].
+
+Build code records spans via the free [span] function, which is a no-op
+unless a collector was activated with [enable]. The collected spans can be
+rendered as a per-phase summary or as a Chrome trace-event file, which can be
+loaded in `about:tracing` or Perfetto.
+*/
+
+use {
+    anyhow::{Context, Result},
+    once_cell::sync::Lazy,
+    serde::Serialize,
+    std::{
+        collections::HashMap,
+        path::Path,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::{Duration, Instant},
+    },
+};
+
+/// The collector receiving spans recorded by [span].
+static ACTIVE: Lazy<Mutex<Option<TimingCollector>>> = Lazy::new(|| Mutex::new(None));
+
+/// Source of thread identifiers.
+static NEXT_THREAD: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+    /// Identifier of the current thread in timing data.
+    static THREAD: usize = NEXT_THREAD.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Identifies a span within a collector.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpanId(usize);
+
+#[derive(Debug)]
+struct SpanRecord {
+    name: String,
+    parent: Option<usize>,
+    thread: usize,
+    start: Duration,
+    duration: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct CollectorState {
+    epoch: Instant,
+    spans: Mutex<Vec<SpanRecord>>,
+    /// Spans open on each thread, innermost last.
+    open: Mutex<HashMap<usize, Vec<usize>>>,
+}
+
+/// Records spans of time.
+#[derive(Clone, Debug)]
+pub struct TimingCollector {
+    state: Arc<CollectorState>,
+}
+
+impl Default for TimingCollector {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(CollectorState {
+                epoch: Instant::now(),
+                spans: Mutex::new(vec![]),
+                open: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+}
+
+impl TimingCollector {
+    /// The innermost span open on the current thread.
+    pub fn current_span(&self) -> Option<SpanId> {
+        let thread = THREAD.with(|thread| *thread);
+
+        self.state
+            .open
+            .lock()
+            .unwrap()
+            .get(&thread)
+            .and_then(|open| open.last())
+            .map(|index| SpanId(*index))
+    }
+
+    /// Open a span that is a child of the innermost span open on this thread.
+    ///
+    /// The span ends when the returned guard is dropped.
+    pub fn span(&self, name: impl Into<String>) -> SpanGuard {
+        self.span_with_parent(self.current_span(), name)
+    }
+
+    /// Open a span with an explicit parent.
+    ///
+    /// This attaches work performed on another thread to the span that
+    /// spawned it.
+    pub fn span_with_parent(&self, parent: Option<SpanId>, name: impl Into<String>) -> SpanGuard {
+        let thread = THREAD.with(|thread| *thread);
+
+        let record = SpanRecord {
+            name: name.into(),
+            parent: parent.map(|id| id.0),
+            thread,
+            start: self.state.epoch.elapsed(),
+            duration: None,
+        };
+
+        let index = {
+            let mut spans = self.state.spans.lock().unwrap();
+            spans.push(record);
+            spans.len() - 1
+        };
+
+        self.state
+            .open
+            .lock()
+            .unwrap()
+            .entry(thread)
+            .or_default()
+            .push(index);
+
+        SpanGuard {
+            inner: Some((self.clone(), index)),
+        }
+    }
+
+    fn close(&self, index: usize) {
+        let end = self.state.epoch.elapsed();
+
+        let thread = match self.state.spans.lock().unwrap().get_mut(index) {
+            Some(record) => {
+                record.duration = Some(end - record.start);
+                record.thread
+            }
+            None => return,
+        };
+
+        if let Some(open) = self.state.open.lock().unwrap().get_mut(&thread) {
+            if let Some(pos) = open.iter().rposition(|x| *x == index) {
+                open.remove(pos);
+            }
+        }
+    }
+
+    /// Summarize the recorded spans.
+    ///
+    /// Spans that are still open are measured up to now.
+    pub fn report(&self) -> TimingReport {
+        let now = self.state.epoch.elapsed();
+        let spans = self.state.spans.lock().unwrap();
+
+        fn phases(spans: &[SpanRecord], parent: Option<usize>, now: Duration) -> Vec<PhaseTiming> {
+            let mut res = spans
+                .iter()
+                .enumerate()
+                .filter(|(_, span)| span.parent == parent)
+                .map(|(index, span)| PhaseTiming {
+                    name: span.name.clone(),
+                    thread: span.thread,
+                    start_seconds: span.start.as_secs_f64(),
+                    duration_seconds: span.duration.unwrap_or(now - span.start).as_secs_f64(),
+                    children: phases(spans, Some(index), now),
+                })
+                .collect::<Vec<_>>();
+
+            res.sort_by(|a, b| {
+                b.duration_seconds
+                    .partial_cmp(&a.duration_seconds)
+                    .unwrap()
+                    .then_with(|| a.start_seconds.partial_cmp(&b.start_seconds).unwrap())
+            });
+
+            res
+        }
+
+        TimingReport {
+            total_seconds: now.as_secs_f64(),
+            phases: phases(&spans, None, now),
+        }
+    }
+
+    /// Render the recorded spans as Chrome trace-event JSON.
+    ///
+    /// Each span becomes a complete (`X`) event on the thread it was opened on.
+    pub fn chrome_trace(&self) -> serde_json::Value {
+        let now = self.state.epoch.elapsed();
+        let spans = self.state.spans.lock().unwrap();
+
+        let events = spans
+            .iter()
+            .map(|span| {
+                serde_json::json!({
+                    "name": span.name,
+                    "cat": "pyoxidizer",
+                    "ph": "X",
+                    "ts": span.start.as_micros() as u64,
+                    "dur": span.duration.unwrap_or(now - span.start).as_micros() as u64,
+                    "pid": std::process::id(),
+                    "tid": span.thread,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms",
+        })
+    }
+
+    /// Write the recorded spans to a Chrome trace-event file.
+    pub fn write_chrome_trace(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec(&self.chrome_trace())?)
+            .with_context(|| format!("writing trace to {}", path.display()))
+    }
+}
+
+/// An open span, which ends when dropped.
+#[must_use]
+pub struct SpanGuard {
+    inner: Option<(TimingCollector, usize)>,
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        if let Some((collector, index)) = self.inner.take() {
+            collector.close(index);
+        }
+    }
+}
+
+/// The duration of a span and its children.
+#[derive(Clone, Debug, Serialize)]
+pub struct PhaseTiming {
+    pub name: String,
+    pub thread: usize,
+    /// Seconds since the collector was created.
+    pub start_seconds: f64,
+    pub duration_seconds: f64,
+    /// Child spans, longest first.
+    pub children: Vec<PhaseTiming>,
+}
+
+/// Summary of recorded spans.
+#[derive(Clone, Debug, Serialize)]
+pub struct TimingReport {
+    pub total_seconds: f64,
+    /// Top-level spans, longest first.
+    pub phases: Vec<PhaseTiming>,
+}
+
+impl TimingReport {
+    /// Render a human readable per-phase breakdown.
+    pub fn to_text(&self) -> String {
+        fn render(s: &mut String, phases: &[PhaseTiming], depth: usize) {
+            for phase in phases {
+                s.push_str(&format!(
+                    "{:>10.2}s  {}{}\n",
+                    phase.duration_seconds,
+                    "  ".repeat(depth),
+                    phase.name
+                ));
+                render(s, &phase.children, depth + 1);
+            }
+        }
+
+        let mut s = format!("build timings (total {:.2}s):\n", self.total_seconds);
+        render(&mut s, &self.phases, 0);
+
+        s
+    }
+}
+
+/// Start recording spans opened via [span] into a new collector.
+pub fn enable() -> TimingCollector {
+    let collector = TimingCollector::default();
+    ACTIVE.lock().unwrap().replace(collector.clone());
+
+    collector
+}
+
+/// Stop recording spans opened via [span].
+pub fn disable() {
+    ACTIVE.lock().unwrap().take();
+}
+
+/// Open a span in the active collector.
+///
+/// This is a no-op if no collector is active.
+pub fn span(name: impl Into<String>) -> SpanGuard {
+    let collector = ACTIVE.lock().unwrap().clone();
+
+    match collector {
+        Some(collector) => collector.span(name),
+        None => SpanGuard { inner: None },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(phases: &[PhaseTiming]) -> Vec<&str> {
+        phases.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_nesting() {
+        let collector = TimingCollector::default();
+
+        {
+            let _build = collector.span("build");
+            {
+                let _short = collector.span("short");
+            }
+            {
+                let _long = collector.span("long");
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        }
+        let _config = collector.span("config");
+
+        let report = collector.report();
+        assert_eq!(names(&report.phases), vec!["build", "config"]);
+        assert_eq!(names(&report.phases[0].children), vec!["long", "short"]);
+        assert!(report.phases[0].duration_seconds >= 0.02);
+
+        let text = report.to_text();
+        assert!(text.starts_with("build timings (total "));
+        assert!(text.contains("s    long\n"));
+    }
+
+    #[test]
+    fn test_threads() {
+        let collector = TimingCollector::default();
+
+        let build = collector.span("build");
+        let parent = collector.current_span();
+        assert!(parent.is_some());
+
+        let handles = (0..2)
+            .map(|i| {
+                let collector = collector.clone();
+                std::thread::spawn(move || {
+                    let _worker = collector.span_with_parent(parent, format!("worker {}", i));
+                    let _inner = collector.span("inner");
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        drop(build);
+
+        let report = collector.report();
+        assert_eq!(names(&report.phases), vec!["build"]);
+
+        let workers = &report.phases[0].children;
+        assert_eq!(workers.len(), 2);
+        for worker in workers {
+            assert!(worker.name.starts_with("worker "));
+            assert_ne!(worker.thread, report.phases[0].thread);
+            assert_eq!(names(&worker.children), vec!["inner"]);
+            assert_eq!(worker.children[0].thread, worker.thread);
+        }
+
+        let trace = collector.chrome_trace();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 5);
+        assert!(events.iter().all(|e| e["ph"] == "X"));
+    }
+
+    #[test]
+    fn test_inactive() {
+        assert!(span("x").inner.is_none());
+    }
+}
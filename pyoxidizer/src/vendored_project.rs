@@ -0,0 +1,634 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Self-contained Rust projects embedding Python.
+
+A vendored project is a directory holding a Rust project and all the
+artifacts produced by PyOxidizer needed to build it. It is meant to be
+committed to version control and built with a plain `cargo build`, without
+PyOxidizer or its caches being available.
+
+Paths in generated files are relative to the directory, so it can be moved
+around. A checksums file records the content of every generated file so
+that drift between the committed directory and what PyOxidizer would
+generate can be detected. See [verify_vendored_project].
+*/
+
+use {
+    crate::{
+        environment::Environment,
+        project_building::prebuilt_artifacts_cargo_features,
+        project_layout::{
+            write_application_manifest, write_new_build_rs, write_new_cargo_config,
+            write_new_cargo_lock, write_new_main_rs, write_prebuilt_artifacts_cargo_toml,
+        },
+        py_packaging::binary::{EmbeddedPythonContext, PythonBinaryBuilder},
+    },
+    anyhow::{anyhow, Context, Result},
+    python_packaging::filesystem_scanning::walk_tree_files,
+    sha2::{Digest, Sha256},
+    slog::warn,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        io::Write,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Name of the file recording checksums of generated files.
+///
+/// The format is compatible with `sha256sum --check`.
+pub const CHECKSUMS_FILENAME: &str = "SHA256SUMS";
+
+/// Directory holding artifacts consumed by the `pyembed` crate.
+pub const ARTIFACTS_DIR: &str = "artifacts";
+
+/// Directory holding files to install next to the built executable.
+pub const INSTALL_DIR: &str = "install";
+
+/// Libraries written to the artifacts directory by [EmbeddedPythonContext].
+const EMBEDDED_LIBRARIES: &[&str] = &["pythonXY", "pyembeddedconfig"];
+
+/// Files beneath these top-level directories aren't part of a vendored project.
+///
+/// `target` holds the output of building the project in place.
+const IGNORED_DIRS: &[&str] = &["target"];
+
+/// Resolve the filenames a static library may have on the target platform.
+fn static_library_filenames(name: &str, target_triple: &str) -> Vec<String> {
+    if target_triple.contains("-windows-") {
+        vec![format!("{}.lib", name)]
+    } else {
+        vec![format!("lib{}.a", name)]
+    }
+}
+
+/// Derive cargo metadata lines referring only to files in the artifacts directory.
+///
+/// Static libraries found in link search paths are copied to `artifacts_dir`
+/// and the search paths are replaced by a path relative to that directory.
+fn relocatable_cargo_metadata_lines(
+    env: &Environment,
+    embedded: &EmbeddedPythonContext,
+    artifacts_dir: &Path,
+) -> Result<Vec<String>> {
+    let mut search_paths = vec![];
+    let mut static_libraries = vec![];
+    let mut lines = vec![];
+
+    // Our own link search path and config file path are relative because
+    // the artifacts directory is given as a relative path.
+    for line in embedded.cargo_metadata_lines(".") {
+        if let Some(value) = line.strip_prefix("cargo:rustc-link-search=") {
+            let value = value.strip_prefix("native=").unwrap_or(value);
+
+            if value != "." {
+                search_paths.push(PathBuf::from(value));
+                continue;
+            }
+        } else if let Some(name) = line.strip_prefix("cargo:rustc-link-lib=static=") {
+            if !EMBEDDED_LIBRARIES.contains(&name) {
+                static_libraries.push(name.to_string());
+            }
+        }
+
+        lines.push(line);
+    }
+
+    let mut used_search_paths = BTreeSet::new();
+
+    for name in static_libraries {
+        let (search_path, filename) = search_paths
+            .iter()
+            .flat_map(|dir| {
+                static_library_filenames(&name, &embedded.target_triple)
+                    .into_iter()
+                    .map(move |filename| (dir, filename))
+            })
+            .find(|(dir, filename)| dir.join(filename).is_file())
+            .ok_or_else(|| anyhow!("unable to find static library {} to vendor", name))?;
+
+        let source_path = search_path.join(&filename);
+        let dest_path = artifacts_dir.join(&filename);
+        std::fs::copy(&source_path, &dest_path).with_context(|| {
+            format!(
+                "copying {} to {}",
+                source_path.display(),
+                dest_path.display()
+            )
+        })?;
+
+        used_search_paths.insert(search_path.clone());
+    }
+
+    // Search paths providing vendored libraries, search paths that no longer
+    // exist (e.g. the temporary directory libpython was built in), and search
+    // paths into PyOxidizer's cache aren't needed. Others refer to the build
+    // environment (e.g. the toolchain) and are retained.
+    for path in search_paths {
+        if used_search_paths.contains(&path) || !path.exists() || path.starts_with(env.cache_dir())
+        {
+            continue;
+        }
+
+        lines.push(format!("cargo:rustc-link-search=native={}", path.display()));
+    }
+
+    Ok(lines)
+}
+
+/// Render the README describing a vendored project.
+fn readme(program_name: &str, regenerate_command: &str, verify_command: &str) -> String {
+    format!(
+        "# Vendored {name}\n\
+         \n\
+         This directory was generated by PyOxidizer. It contains a Rust project\n\
+         building the `{name}` executable and all the artifacts needed to build\n\
+         it. PyOxidizer isn't needed to build it.\n\
+         \n\
+         Do not edit files in this directory: they are overwritten when the\n\
+         directory is regenerated.\n\
+         \n\
+         ## Building\n\
+         \n\
+         Run `cargo build` from this directory.\n\
+         \n\
+         Building requires a Python interpreter of the same major and minor\n\
+         version as the embedded Python to configure the `python3-sys` crate.\n\
+         Point the `PYTHON_SYS_EXECUTABLE` environment variable at it if it\n\
+         isn't the first `python3` on `PATH`.\n\
+         \n\
+         Files in the `{install}` directory, if present, must be installed next\n\
+         to the built executable.\n\
+         \n\
+         ## Regenerating\n\
+         \n\
+         Run the following from the directory containing the PyOxidizer\n\
+         configuration file:\n\
+         \n\
+         ```\n\
+         {regenerate}\n\
+         ```\n\
+         \n\
+         To check whether this directory is up to date with the configuration\n\
+         file, run:\n\
+         \n\
+         ```\n\
+         {verify}\n\
+         ```\n\
+         \n\
+         `{checksums}` records checksums of all files in this directory.\n",
+        name = program_name,
+        install = INSTALL_DIR,
+        regenerate = regenerate_command,
+        verify = verify_command,
+        checksums = CHECKSUMS_FILENAME,
+    )
+}
+
+/// Compute checksums of files in a vendored project directory.
+///
+/// Keys are paths relative to `dir` using `/` as a separator.
+pub fn directory_checksums(dir: &Path) -> Result<BTreeMap<String, String>> {
+    let mut res = BTreeMap::new();
+
+    for entry in walk_tree_files(dir) {
+        let rel_path = entry
+            .path()
+            .strip_prefix(dir)
+            .context("stripping directory prefix")?;
+
+        if rel_path == Path::new(CHECKSUMS_FILENAME)
+            || IGNORED_DIRS.iter().any(|x| rel_path.starts_with(x))
+        {
+            continue;
+        }
+
+        let key = rel_path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        res.insert(key, file_checksum(entry.path())?);
+    }
+
+    Ok(res)
+}
+
+fn file_checksum(path: &Path) -> Result<String> {
+    let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+    Ok(hex::encode(Sha256::digest(&data)))
+}
+
+/// Read the checksums file of a vendored project directory.
+pub fn read_checksums(dir: &Path) -> Result<BTreeMap<String, String>> {
+    let path = dir.join(CHECKSUMS_FILENAME);
+    let data = std::fs::read_to_string(&path)
+        .with_context(|| format!("reading {}; is this a vendored directory?", path.display()))?;
+
+    data.lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let mut parts = line.splitn(2, "  ");
+
+            match (parts.next(), parts.next()) {
+                (Some(digest), Some(path)) => Ok((path.to_string(), digest.to_string())),
+                _ => Err(anyhow!(
+                    "malformed line in {}: {}",
+                    CHECKSUMS_FILENAME,
+                    line
+                )),
+            }
+        })
+        .collect()
+}
+
+fn write_checksums(dir: &Path, checksums: &BTreeMap<String, String>) -> Result<()> {
+    let mut fh = std::fs::File::create(dir.join(CHECKSUMS_FILENAME))?;
+
+    for (path, digest) in checksums {
+        writeln!(fh, "{}  {}", digest, path)?;
+    }
+
+    Ok(())
+}
+
+/// Write a vendored Rust project building `exe` to `dest_dir`.
+///
+/// Files recorded by the checksums file of an existing vendored project in
+/// `dest_dir` are removed first, so files no longer generated don't linger.
+///
+/// `regenerate_command` and `verify_command` are recorded in the README so
+/// readers know how the directory came to be.
+pub fn write_vendored_project(
+    env: &Environment,
+    logger: &slog::Logger,
+    exe: &dyn PythonBinaryBuilder,
+    opt_level: &str,
+    regenerate_command: &str,
+    verify_command: &str,
+    dest_dir: &Path,
+) -> Result<()> {
+    if dest_dir.join(CHECKSUMS_FILENAME).exists() {
+        for path in read_checksums(dest_dir)?.keys() {
+            let path = dest_dir.join(path);
+            if path.exists() {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("removing {}", path.display()))?;
+            }
+        }
+    }
+
+    let embedded = exe
+        .to_embedded_python_context(logger, env, opt_level)
+        .context("obtaining embedded python context")?;
+
+    let artifacts_dir = dest_dir.join(ARTIFACTS_DIR);
+    std::fs::create_dir_all(&artifacts_dir)
+        .with_context(|| format!("creating {}", artifacts_dir.display()))?;
+
+    embedded
+        .verify_bytecode_magic_number()
+        .context("verify_bytecode_magic_number()")?;
+    embedded
+        .write_packed_resources(&artifacts_dir)
+        .context("write_packed_resources()")?;
+    embedded
+        .write_libpython(&artifacts_dir)
+        .context("write_libpython()")?;
+    embedded
+        .write_interpreter_config_rs(&artifacts_dir)
+        .context("write_interpreter_config_rs()")?;
+
+    let lines = relocatable_cargo_metadata_lines(env, &embedded, &artifacts_dir)
+        .context("resolving relocatable cargo metadata")?;
+    std::fs::write(
+        embedded.cargo_metadata_path(&artifacts_dir),
+        lines.join("\n"),
+    )
+    .context("writing cargo metadata")?;
+
+    if embedded.extra_files.iter_entries().next().is_some() {
+        embedded
+            .extra_files
+            .materialize_files(dest_dir.join(INSTALL_DIR))
+            .context("writing files to install next to executable")?;
+    }
+
+    let name = exe.name();
+    let pyembed_location = env.pyoxidizer_source.as_pyembed_location();
+
+    write_prebuilt_artifacts_cargo_toml(
+        &dest_dir.join("Cargo.toml"),
+        &name,
+        &pyembed_location,
+        &prebuilt_artifacts_cargo_features(exe, &embedded),
+    )
+    .context("writing Cargo.toml")?;
+    write_new_cargo_lock(dest_dir, &name, &pyembed_location).context("writing Cargo.lock")?;
+    write_new_cargo_config(dest_dir).context("writing cargo config")?;
+    {
+        // Point the pyembed build script at the artifacts, relative to this directory.
+        let mut fh = std::fs::OpenOptions::new()
+            .append(true)
+            .open(dest_dir.join(".cargo").join("config"))
+            .context("opening cargo config")?;
+        write!(
+            fh,
+            "\n[env]\nPYOXIDIZER_ARTIFACT_DIR = {{ value = \"{}\", relative = true, force = true }}\n",
+            ARTIFACTS_DIR
+        )?;
+    }
+    write_new_build_rs(&dest_dir.join("build.rs"), &name).context("writing build.rs")?;
+    std::fs::create_dir_all(dest_dir.join("src"))?;
+    write_new_main_rs(
+        &dest_dir.join("src").join("main.rs"),
+        exe.windows_subsystem(),
+    )
+    .context("writing main.rs")?;
+    write_application_manifest(dest_dir, &name).context("writing application manifest")?;
+
+    std::fs::write(
+        dest_dir.join("README.md"),
+        readme(&name, regenerate_command, verify_command),
+    )
+    .context("writing README.md")?;
+
+    write_checksums(dest_dir, &directory_checksums(dest_dir)?).context("writing checksums file")?;
+
+    warn!(
+        logger,
+        "wrote vendored project for {} to {}",
+        name,
+        dest_dir.display()
+    );
+
+    Ok(())
+}
+
+/// Describe differences between checksums as human readable lines.
+fn checksum_differences(
+    expected: &BTreeMap<String, String>,
+    actual: &BTreeMap<String, String>,
+    changed: &str,
+    added: &str,
+    removed: &str,
+) -> Vec<String> {
+    expected
+        .keys()
+        .chain(actual.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|path| match (expected.get(path), actual.get(path)) {
+            (Some(a), Some(b)) if a != b => Some(format!("{} ({})", path, changed)),
+            (None, Some(_)) => Some(format!("{} ({})", path, added)),
+            (Some(_), None) => Some(format!("{} ({})", path, removed)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Verify a vendored project in `dest_dir` is up to date.
+///
+/// The project is regenerated in a temporary directory and checksums of the
+/// result are compared with the checksums file in `dest_dir`. Files in
+/// `dest_dir` are also compared against the checksums file to detect local
+/// modifications. Errors describing all differences if any are found.
+pub fn verify_vendored_project(
+    env: &Environment,
+    logger: &slog::Logger,
+    exe: &dyn PythonBinaryBuilder,
+    opt_level: &str,
+    regenerate_command: &str,
+    verify_command: &str,
+    dest_dir: &Path,
+) -> Result<()> {
+    let recorded = read_checksums(dest_dir)?;
+
+    let temp_dir = tempfile::Builder::new()
+        .prefix("pyoxidizer-vendored")
+        .tempdir()
+        .context("creating temp directory")?;
+
+    write_vendored_project(
+        env,
+        logger,
+        exe,
+        opt_level,
+        regenerate_command,
+        verify_command,
+        temp_dir.path(),
+    )?;
+    let generated = read_checksums(temp_dir.path())?;
+
+    let on_disk = recorded
+        .keys()
+        .filter_map(|path| {
+            let full_path = dest_dir.join(path);
+
+            if full_path.exists() {
+                Some(file_checksum(&full_path).map(|digest| (path.clone(), digest)))
+            } else {
+                None
+            }
+        })
+        .collect::<Result<BTreeMap<_, _>>>()?;
+
+    let mut differences = checksum_differences(
+        &recorded,
+        &generated,
+        "out of date",
+        "newly generated",
+        "no longer generated",
+    );
+    differences.extend(checksum_differences(
+        &recorded, &on_disk, "modified", "added", "missing",
+    ));
+
+    if differences.is_empty() {
+        warn!(
+            logger,
+            "vendored project in {} is up to date",
+            dest_dir.display()
+        );
+
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "vendored project in {} has drifted from the configuration:\n  {}\nrun `{}` to regenerate it",
+            dest_dir.display(),
+            differences.join("\n  "),
+            regenerate_command
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            py_packaging::standalone_builder::tests::StandalonePythonExecutableBuilderOptions,
+            testutil::*,
+        },
+    };
+
+    #[test]
+    fn test_checksums_roundtrip() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let dir = temp_dir.path();
+
+        std::fs::create_dir_all(dir.join("artifacts"))?;
+        std::fs::create_dir_all(dir.join("target").join("debug"))?;
+        std::fs::write(dir.join("Cargo.toml"), b"[package]")?;
+        std::fs::write(dir.join("artifacts").join("packed-resources"), b"data")?;
+        std::fs::write(dir.join("target").join("debug").join("app"), b"binary")?;
+
+        let checksums = directory_checksums(dir)?;
+        assert_eq!(
+            checksums.keys().collect::<Vec<_>>(),
+            vec!["Cargo.toml", "artifacts/packed-resources"]
+        );
+        assert_eq!(
+            checksums.get("artifacts/packed-resources").unwrap(),
+            &hex::encode(Sha256::digest(b"data"))
+        );
+
+        write_checksums(dir, &checksums)?;
+        assert_eq!(read_checksums(dir)?, checksums);
+        assert_eq!(directory_checksums(dir)?, checksums);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_checksum_differences() {
+        let mut expected = BTreeMap::new();
+        expected.insert("a".to_string(), "1".to_string());
+        expected.insert("b".to_string(), "2".to_string());
+        expected.insert("c".to_string(), "3".to_string());
+
+        let mut actual = BTreeMap::new();
+        actual.insert("a".to_string(), "1".to_string());
+        actual.insert("b".to_string(), "4".to_string());
+        actual.insert("d".to_string(), "5".to_string());
+
+        assert_eq!(
+            checksum_differences(&expected, &actual, "changed", "added", "removed"),
+            vec![
+                "b (changed)".to_string(),
+                "c (removed)".to_string(),
+                "d (added)".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_vendored_project() -> Result<()> {
+        let env = get_env()?;
+        let logger = get_logger()?;
+
+        let mut options = StandalonePythonExecutableBuilderOptions::default();
+        options.config.config.run_command = Some("print('hello from vendored')".to_string());
+        let exe = options.new_builder()?;
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let project_dir = temp_dir.path().join("vendored");
+        std::fs::create_dir_all(&project_dir)?;
+
+        write_vendored_project(
+            &env,
+            &logger,
+            exe.as_ref(),
+            "0",
+            "pyoxidizer build vendored",
+            "pyoxidizer build --verify-vendored vendored",
+            &project_dir,
+        )?;
+
+        // Nothing in generated files should refer to where it was generated.
+        for path in read_checksums(&project_dir)?.keys() {
+            let data = std::fs::read(project_dir.join(path))?;
+            let needle = temp_dir.path().display().to_string();
+            assert!(
+                !String::from_utf8_lossy(&data).contains(&needle),
+                "{} refers to the vendored directory",
+                path
+            );
+        }
+
+        verify_vendored_project(
+            &env,
+            &logger,
+            exe.as_ref(),
+            "0",
+            "pyoxidizer build vendored",
+            "pyoxidizer build --verify-vendored vendored",
+            &project_dir,
+        )?;
+
+        // Relocate the directory to ensure it doesn't depend on its location.
+        let relocated_dir = temp_dir.path().join("relocated");
+        std::fs::rename(&project_dir, &relocated_dir)?;
+
+        std::fs::write(relocated_dir.join("README.md"), b"modified")?;
+        assert!(verify_vendored_project(
+            &env,
+            &logger,
+            exe.as_ref(),
+            "0",
+            "pyoxidizer build vendored",
+            "pyoxidizer build --verify-vendored vendored",
+            &relocated_dir,
+        )
+        .is_err());
+
+        // Build without PyOxidizer: artifacts must come from the vendored
+        // directory and not PyOxidizer's (empty) cache. The distribution's
+        // interpreter stands in for a system Python of the same version.
+        let empty_cache_dir = temp_dir.path().join("empty-cache");
+        std::fs::create_dir_all(&empty_cache_dir)?;
+
+        let rust = env.ensure_rust_toolchain(&logger, None)?;
+        let mut command = std::process::Command::new(&rust.cargo_exe);
+        command
+            .arg("build")
+            .current_dir(&relocated_dir)
+            .env("RUSTC", &rust.rustc_exe)
+            .env("PYOXIDIZER_CACHE_DIR", &empty_cache_dir)
+            .env("PYOXIDIZER_EXE", empty_cache_dir.join("pyoxidizer"))
+            .env("PYTHON_SYS_EXECUTABLE", exe.target_python_exe_path())
+            .env_remove("PYOXIDIZER_ARTIFACT_DIR")
+            .env_remove("PYOXIDIZER_CONFIG");
+        let output = command.output()?;
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let exe_path = relocated_dir
+            .join("target")
+            .join("debug")
+            .join(format!("testapp{}", std::env::consts::EXE_SUFFIX));
+        let output = std::process::Command::new(&exe_path).output()?;
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            "hello from vendored"
+        );
+
+        Ok(())
+    }
+}
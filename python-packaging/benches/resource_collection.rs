@@ -0,0 +1,181 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Benchmarks for resource discovery, collection, and bytecode compilation.
+
+A synthetic tree of packages and modules is generated deterministically in
+a temporary directory. Bytecode compilation uses a stub compiler so we
+measure the dispatch overhead of the collector rather than Python.
+*/
+
+use {
+    anyhow::Result,
+    criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput},
+    python_packaging::{
+        bytecode::{CompileMode, PythonBytecodeCompiler},
+        filesystem_scanning::find_python_resources,
+        location::{AbstractResourceLocation, ConcreteResourceLocation},
+        module_util::PythonModuleSuffixes,
+        resource::{BytecodeOptimizationLevel, PythonModuleSource, PythonResource},
+        resource_collection::{
+            BytecodeGeneration, PythonResourceAddCollectionContext, PythonResourceCollector,
+        },
+    },
+    std::path::Path,
+};
+
+/// Number of Python modules in the generated tree.
+const MODULE_COUNT: usize = 5000;
+
+/// Number of modules belonging to each generated package.
+const MODULES_PER_PACKAGE: usize = 50;
+
+const CACHE_TAG: &str = "cpython-39";
+
+/// Stub bytecode compiler that echoes source back as bytecode.
+struct StubCompiler;
+
+impl PythonBytecodeCompiler for StubCompiler {
+    fn get_magic_number(&self) -> u32 {
+        0x0a0d_0d61
+    }
+
+    fn compile(
+        &mut self,
+        source: &[u8],
+        _filename: &str,
+        _optimize: BytecodeOptimizationLevel,
+        _output_mode: CompileMode,
+    ) -> Result<Vec<u8>> {
+        Ok(source.to_vec())
+    }
+}
+
+fn suffixes() -> PythonModuleSuffixes {
+    PythonModuleSuffixes {
+        source: vec![".py".to_string()],
+        bytecode: vec![".pyc".to_string()],
+        debug_bytecode: vec![],
+        optimized_bytecode: vec![],
+        extension: vec![],
+    }
+}
+
+/// Write a synthetic tree of packages and modules under `root`.
+///
+/// Content is a pure function of the module index so every run sees
+/// identical data.
+fn write_tree(root: &Path) -> Result<()> {
+    for i in 0..MODULE_COUNT {
+        let package_path = root.join(format!("pkg{:03}", i / MODULES_PER_PACKAGE));
+
+        if i % MODULES_PER_PACKAGE == 0 {
+            std::fs::create_dir_all(&package_path)?;
+            std::fs::write(package_path.join("__init__.py"), b"\"\"\"Package.\"\"\"\n")?;
+        }
+
+        std::fs::write(
+            package_path.join(format!("mod{:04}.py", i)),
+            format!(
+                "\"\"\"Module {}.\"\"\"\n\nVALUE = {}\n\n\ndef value():\n    return VALUE * {}\n",
+                i,
+                i,
+                i % 7
+            ),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn new_collector() -> PythonResourceCollector {
+    PythonResourceCollector::new(
+        vec![AbstractResourceLocation::InMemory],
+        vec![],
+        false,
+        false,
+        CACHE_TAG,
+    )
+}
+
+fn add_context() -> PythonResourceAddCollectionContext {
+    PythonResourceAddCollectionContext {
+        include: true,
+        location: ConcreteResourceLocation::InMemory,
+        location_fallback: None,
+        store_source: true,
+        optimize_level_zero: true,
+        optimize_level_one: false,
+        optimize_level_two: false,
+        bytecode_generation: BytecodeGeneration::Eager,
+    }
+}
+
+/// Find Python module sources under `root`.
+fn scan_modules(root: &Path) -> Vec<PythonModuleSource> {
+    find_python_resources(root, CACHE_TAG, &suffixes(), false, true)
+        .filter_map(|resource| match resource.unwrap() {
+            PythonResource::ModuleSource(module) => Some(module.into_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Add module sources to a new collector.
+fn collect_modules(modules: &[PythonModuleSource]) -> PythonResourceCollector {
+    let mut collector = new_collector();
+    let context = add_context();
+
+    for module in modules {
+        collector
+            .add_python_module_source_with_context(module, &context)
+            .unwrap();
+    }
+
+    collector
+}
+
+fn bench_resource_collection(c: &mut Criterion) {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("python-packaging-bench")
+        .tempdir()
+        .unwrap();
+    write_tree(temp_dir.path()).unwrap();
+
+    let modules = scan_modules(temp_dir.path())
+        .into_iter()
+        .map(|module| module.to_memory().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        modules.len(),
+        MODULE_COUNT + MODULE_COUNT / MODULES_PER_PACKAGE
+    );
+
+    let mut group = c.benchmark_group("resource_collection");
+    group.sample_size(20);
+    group.throughput(Throughput::Elements(modules.len() as u64));
+
+    group.bench_function("scan_5000", |b| {
+        b.iter(|| scan_modules(black_box(temp_dir.path())))
+    });
+
+    group.bench_function("collect_5000", |b| {
+        b.iter(|| collect_modules(black_box(&modules)))
+    });
+
+    let collector = collect_modules(&modules);
+
+    group.bench_function("compile_5000", |b| {
+        b.iter(|| {
+            black_box(&collector)
+                .compile_resources(&mut StubCompiler)
+                .unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_resource_collection);
+criterion_main!(benches);
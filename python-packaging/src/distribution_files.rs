@@ -0,0 +1,264 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Attributing resources to the distributions that installed them.
+
+Names of modules provided by a distribution don't have to match the name
+of the distribution. e.g. `PyYAML` provides the `yaml` package and the
+`_yaml` extension module. [DistributionFileIndex] attributes resources by
+matching them against the files listed in the `RECORD` file of each
+installed distribution.
+*/
+
+use {
+    crate::{
+        install_report::parse_record_entries, package_metadata::normalize_distribution_name,
+        resource::PythonResource,
+    },
+    anyhow::Result,
+    std::collections::{HashMap, HashSet},
+};
+
+/// Resolve the module name of a path relative to `site-packages`.
+///
+/// Returns `None` if the path isn't a Python source file or an extension
+/// module. `__init__` files resolve to the name of their package.
+fn module_name_for_path(path: &str) -> Option<String> {
+    let mut parts = path.split('/').collect::<Vec<_>>();
+    let filename = parts.pop()?;
+
+    if !(filename.ends_with(".py") || filename.ends_with(".so") || filename.ends_with(".pyd")) {
+        return None;
+    }
+
+    let stem = filename.split('.').next().unwrap_or(filename);
+    if stem.is_empty()
+        || parts
+            .iter()
+            .any(|part| part.is_empty() || part.contains('.'))
+    {
+        return None;
+    }
+
+    if stem != "__init__" {
+        parts.push(stem);
+    }
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("."))
+    }
+}
+
+/// Maps files installed by distributions to the distributions installing them.
+#[derive(Clone, Debug, Default)]
+pub struct DistributionFileIndex {
+    /// Normalized names of indexed distributions.
+    distributions: HashSet<String>,
+
+    /// Normalized distribution name by path relative to `site-packages`.
+    paths: HashMap<String, String>,
+
+    /// Normalized distribution name by module name.
+    modules: HashMap<String, String>,
+}
+
+impl DistributionFileIndex {
+    /// Construct an instance from `RECORD` distribution resources.
+    ///
+    /// Resources that aren't `RECORD` files are ignored. Paths outside of
+    /// `site-packages` (e.g. scripts) are ignored.
+    pub fn from_resources(resources: &[PythonResource]) -> Result<Self> {
+        let mut index = Self::default();
+
+        for resource in resources {
+            let resource = match resource {
+                PythonResource::PackageDistributionResource(resource)
+                    if resource.name == "RECORD" =>
+                {
+                    resource
+                }
+                _ => continue,
+            };
+
+            let distribution = normalize_distribution_name(&resource.package);
+            index.distributions.insert(distribution.clone());
+            let data = resource.data.resolve_content()?;
+
+            for (path, _) in parse_record_entries(&String::from_utf8_lossy(&data)) {
+                if path.starts_with("..") || path.starts_with('/') {
+                    continue;
+                }
+
+                if let Some(module) = module_name_for_path(path) {
+                    index.modules.insert(module, distribution.clone());
+                }
+
+                index.paths.insert(path.to_string(), distribution.clone());
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Find the normalized name of the distribution installing a resource.
+    ///
+    /// Distribution resources are attributed to the distribution they
+    /// describe if its `RECORD` was indexed.
+    pub fn distribution_for(&self, resource: &PythonResource) -> Option<&str> {
+        let found = match resource {
+            PythonResource::ModuleSource(m) => self.modules.get(&m.name),
+            PythonResource::ModuleBytecodeRequest(m) => self.modules.get(&m.name),
+            PythonResource::ModuleBytecode(m) => self.modules.get(&m.name),
+            PythonResource::ExtensionModule(em) => self.modules.get(&em.name),
+            PythonResource::PackageResource(resource) => self.paths.get(&format!(
+                "{}/{}",
+                resource.leaf_package.replace('.', "/"),
+                resource.relative_name
+            )),
+            PythonResource::PackageDistributionResource(resource) => self
+                .distributions
+                .get(&normalize_distribution_name(&resource.package)),
+            PythonResource::File(file) => self.paths.get(&file.path_string().replace('\\', "/")),
+            PythonResource::EggFile(_) | PythonResource::PathExtension(_) => None,
+        };
+
+        found.map(|distribution| distribution.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::resource::{
+            PythonExtensionModule, PythonModuleSource, PythonPackageDistributionResource,
+            PythonPackageDistributionResourceFlavor, PythonPackageResource,
+        },
+        tugger_file_manifest::{File, FileData, FileEntry},
+    };
+
+    fn distribution_resource(package: &str, name: &str, data: &str) -> PythonResource<'static> {
+        PythonResource::from(PythonPackageDistributionResource {
+            location: PythonPackageDistributionResourceFlavor::DistInfo,
+            package: package.to_string(),
+            version: "1.0".to_string(),
+            name: name.to_string(),
+            data: FileData::Memory(data.as_bytes().to_vec()),
+        })
+    }
+
+    fn module(name: &str, is_package: bool) -> PythonResource<'static> {
+        PythonResource::from(PythonModuleSource {
+            name: name.to_string(),
+            source: FileData::Memory(vec![]),
+            is_package,
+            cache_tag: "cpython-39".to_string(),
+            is_stdlib: false,
+            is_test: false,
+        })
+    }
+
+    #[test]
+    fn test_module_name_for_path() {
+        assert_eq!(
+            module_name_for_path("yaml/__init__.py"),
+            Some("yaml".into())
+        );
+        assert_eq!(
+            module_name_for_path("yaml/constructor.py"),
+            Some("yaml.constructor".into())
+        );
+        assert_eq!(
+            module_name_for_path("_yaml.cpython-39-x86_64-linux-gnu.so"),
+            Some("_yaml".into())
+        );
+        assert_eq!(module_name_for_path("six.py"), Some("six".into()));
+        assert_eq!(
+            module_name_for_path("yaml/__pycache__/x.cpython-39.pyc"),
+            None
+        );
+        assert_eq!(module_name_for_path("PyYAML-5.4.dist-info/METADATA"), None);
+        assert_eq!(module_name_for_path("yaml/data.txt"), None);
+        assert_eq!(module_name_for_path("__init__.py"), None);
+    }
+
+    #[test]
+    fn test_import_name_differs() -> Result<()> {
+        let resources = vec![
+            distribution_resource("PyYAML", "METADATA", "Name: PyYAML\nVersion: 1.0\n"),
+            distribution_resource(
+                "PyYAML",
+                "RECORD",
+                concat!(
+                    "PyYAML-1.0.dist-info/METADATA,sha256=abc,10\n",
+                    "PyYAML-1.0.dist-info/RECORD,,\n",
+                    "_yaml.cpython-39-x86_64-linux-gnu.so,sha256=def,1000\n",
+                    "yaml/__init__.py,sha256=ghi,100\n",
+                    "yaml/__pycache__/__init__.cpython-39.pyc,,\n",
+                    "yaml/resolver.py,sha256=jkl,100\n",
+                    "yaml/data/schema.json,sha256=mno,5\n",
+                    "\"yaml/with,comma.txt\",sha256=pqr,5\n",
+                    "../../bin/yaml,sha256=stu,5\n",
+                ),
+            ),
+            distribution_resource("six", "RECORD", "six.py,sha256=abc,10\n"),
+        ];
+
+        let index = DistributionFileIndex::from_resources(&resources)?;
+
+        assert_eq!(index.distribution_for(&resources[0]), Some("pyyaml"));
+        assert_eq!(index.distribution_for(&resources[2]), Some("six"));
+        assert_eq!(
+            index.distribution_for(&module("yaml", true)),
+            Some("pyyaml")
+        );
+        assert_eq!(
+            index.distribution_for(&module("yaml.resolver", false)),
+            Some("pyyaml")
+        );
+        assert_eq!(index.distribution_for(&module("six", false)), Some("six"));
+        assert_eq!(index.distribution_for(&module("other", false)), None);
+
+        let em = PythonResource::from(PythonExtensionModule {
+            name: "_yaml".to_string(),
+            init_fn: Some("PyInit__yaml".to_string()),
+            extension_file_suffix: ".cpython-39-x86_64-linux-gnu.so".to_string(),
+            shared_library: Some(FileData::Memory(vec![])),
+            object_file_data: vec![],
+            is_package: false,
+            link_libraries: vec![],
+            is_stdlib: false,
+            builtin_default: false,
+            required: false,
+            variant: None,
+            license: None,
+        });
+        assert_eq!(index.distribution_for(&em), Some("pyyaml"));
+
+        let resource = PythonResource::from(PythonPackageResource {
+            leaf_package: "yaml".to_string(),
+            relative_name: "data/schema.json".to_string(),
+            data: FileData::Memory(vec![]),
+            is_stdlib: false,
+            is_test: false,
+        });
+        assert_eq!(index.distribution_for(&resource), Some("pyyaml"));
+
+        let file = PythonResource::from(File::new(
+            "yaml/with,comma.txt",
+            FileEntry::new_from_data(vec![], false),
+        ));
+        assert_eq!(index.distribution_for(&file), Some("pyyaml"));
+
+        let script = PythonResource::from(File::new(
+            "../../bin/yaml",
+            FileEntry::new_from_data(vec![], true),
+        ));
+        assert_eq!(index.distribution_for(&script), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,476 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Attributing installed distributions to the requirements that pulled them in.
+
+Installing a requirement with extras (e.g. `myapp[postgres,redis]`) can
+pull in large optional dependency trees. [InstallReport] reconstructs
+which requirement and extra caused each distribution to be installed from
+the `METADATA` and `RECORD` files of the installed distributions.
+*/
+
+use {
+    crate::{
+        package_metadata::{normalize_distribution_name, PythonPackageMetadata, PythonRequirement},
+        resource::PythonResource,
+    },
+    anyhow::{Context, Result},
+    std::collections::{BTreeMap, BTreeSet, VecDeque},
+};
+
+/// Why a distribution was installed.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct DistributionAttribution {
+    /// Normalized name of the distribution requiring this one.
+    ///
+    /// `None` if the distribution was requested directly.
+    pub required_by: Option<String>,
+
+    /// Extra of `required_by` this distribution was pulled in by.
+    pub extra: Option<String>,
+}
+
+impl DistributionAttribution {
+    fn describe(&self) -> String {
+        match (&self.required_by, &self.extra) {
+            (None, _) => "requested directly".to_string(),
+            (Some(by), None) => format!("pulled in by {}", by),
+            (Some(by), Some(extra)) => format!("pulled in by {} via extra '{}'", by, extra),
+        }
+    }
+}
+
+/// A distribution installed by a packaging operation.
+#[derive(Clone, Debug, Default)]
+pub struct InstalledDistribution {
+    /// Version of the distribution.
+    pub version: String,
+
+    /// Total size in bytes of installed files, as recorded in `RECORD`.
+    pub size: u64,
+
+    /// Top-level packages and modules provided by the distribution.
+    pub top_level: BTreeSet<String>,
+
+    /// Reasons the distribution was installed.
+    pub attributions: BTreeSet<DistributionAttribution>,
+
+    /// Extras this distribution wouldn't be installed without.
+    ///
+    /// Entries have the form `<distribution>[<extra>]`.
+    pub via_extras: BTreeSet<String>,
+
+    requires: Vec<PythonRequirement>,
+}
+
+/// Parse a `RECORD` file into the paths and sizes of its entries.
+///
+/// Entries without a recorded size have a size of `0`.
+pub(crate) fn parse_record_entries(data: &str) -> impl Iterator<Item = (&str, u64)> {
+    data.lines().filter_map(|line| {
+        let mut fields = line.rsplitn(3, ',');
+        let file_size = fields.next().unwrap_or("");
+        let _hash = fields.next();
+
+        fields.next().map(|path| {
+            (
+                path.trim_matches('"'),
+                file_size.trim().parse::<u64>().unwrap_or(0),
+            )
+        })
+    })
+}
+
+/// Parse a `RECORD` file into the total size and top-level names of files.
+fn parse_record(data: &str) -> (u64, BTreeSet<String>) {
+    let mut size = 0;
+    let mut top_level = BTreeSet::new();
+
+    for (path, file_size) in parse_record_entries(data) {
+        size += file_size;
+
+        let first = path.split('/').next().unwrap_or(path);
+        if path.starts_with("..")
+            || first.ends_with(".dist-info")
+            || first.ends_with(".data")
+            || first.ends_with(".egg-info")
+            || first == "__pycache__"
+        {
+            continue;
+        }
+
+        let name = first.split('.').next().unwrap_or(first);
+        if !name.is_empty() {
+            top_level.insert(name.to_string());
+        }
+    }
+
+    (size, top_level)
+}
+
+/// Describes why each distribution of a packaging operation was installed.
+#[derive(Clone, Debug, Default)]
+pub struct InstallReport {
+    /// Installed distributions, keyed by normalized name.
+    pub distributions: BTreeMap<String, InstalledDistribution>,
+}
+
+impl InstallReport {
+    /// Construct an instance from requested requirements and installed resources.
+    ///
+    /// Distributions are discovered from `METADATA` and `RECORD` distribution
+    /// resources. Installed distributions not reachable from `requested`
+    /// (e.g. those coming from a requirements file) are treated as requested
+    /// directly.
+    pub fn from_resources(
+        requested: &[PythonRequirement],
+        resources: &[PythonResource],
+    ) -> Result<Self> {
+        let mut distributions = BTreeMap::new();
+
+        for resource in resources {
+            let resource = match resource {
+                PythonResource::PackageDistributionResource(resource) => resource,
+                _ => continue,
+            };
+
+            let entry = distributions
+                .entry(normalize_distribution_name(&resource.package))
+                .or_insert_with(|| InstalledDistribution {
+                    version: resource.version.clone(),
+                    ..InstalledDistribution::default()
+                });
+
+            if resource.name == "METADATA" || resource.name == "PKG-INFO" {
+                let metadata =
+                    PythonPackageMetadata::from_metadata(&resource.data.resolve_content()?)
+                        .with_context(|| format!("parsing metadata of {}", resource.package))?;
+
+                entry.requires = metadata
+                    .find_all_headers("Requires-Dist")
+                    .into_iter()
+                    .map(PythonRequirement::parse)
+                    .collect::<Result<Vec<_>>>()
+                    .with_context(|| format!("parsing requirements of {}", resource.package))?;
+            } else if resource.name == "RECORD" {
+                let (size, top_level) =
+                    parse_record(&String::from_utf8_lossy(&resource.data.resolve_content()?));
+                entry.size = size;
+                entry.top_level = top_level;
+            }
+        }
+
+        let mut report = Self { distributions };
+        report.attribute(requested);
+
+        Ok(report)
+    }
+
+    /// Walk requirements from `roots` to find why distributions are installed.
+    ///
+    /// Requirements conditional on the `disabled` `(distribution, extra)` pair
+    /// are not followed. Only reached distributions are present in the result.
+    fn walk(
+        &self,
+        roots: &[(String, Vec<String>)],
+        disabled: Option<&(String, String)>,
+    ) -> BTreeMap<String, BTreeSet<DistributionAttribution>> {
+        let mut res: BTreeMap<String, BTreeSet<DistributionAttribution>> = BTreeMap::new();
+        let mut active_extras: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let mut queue = VecDeque::new();
+
+        for (name, extras) in roots {
+            if !self.distributions.contains_key(name) {
+                continue;
+            }
+
+            res.entry(name.clone())
+                .or_default()
+                .insert(DistributionAttribution {
+                    required_by: None,
+                    extra: None,
+                });
+            active_extras
+                .entry(name.clone())
+                .or_default()
+                .extend(extras.iter().cloned());
+            queue.push_back(name.clone());
+        }
+
+        while let Some(name) = queue.pop_front() {
+            let extras = active_extras.get(&name).cloned().unwrap_or_default();
+
+            for requirement in &self.distributions[&name].requires {
+                if let Some(extra) = &requirement.marker_extra {
+                    if !extras.contains(extra) || disabled == Some(&(name.clone(), extra.clone())) {
+                        continue;
+                    }
+                }
+
+                if !self.distributions.contains_key(&requirement.name) {
+                    continue;
+                }
+
+                let mut changed = res.entry(requirement.name.clone()).or_default().insert(
+                    DistributionAttribution {
+                        required_by: Some(name.clone()),
+                        extra: requirement.marker_extra.clone(),
+                    },
+                );
+
+                let dependency_extras = active_extras.entry(requirement.name.clone()).or_default();
+                for extra in &requirement.extras {
+                    changed |= dependency_extras.insert(extra.clone());
+                }
+
+                if changed {
+                    queue.push_back(requirement.name.clone());
+                }
+            }
+        }
+
+        res
+    }
+
+    /// Attribute installed distributions to requirements in `requested`.
+    fn attribute(&mut self, requested: &[PythonRequirement]) {
+        let mut roots = requested
+            .iter()
+            .map(|requirement| (requirement.name.clone(), requirement.extras.clone()))
+            .collect::<Vec<_>>();
+
+        let reached = self.walk(&roots, None);
+        for name in self.distributions.keys() {
+            if !reached.contains_key(name) {
+                roots.push((name.clone(), vec![]));
+            }
+        }
+
+        let attributions = self.walk(&roots, None);
+
+        let extras = attributions
+            .values()
+            .flat_map(|attributions| attributions.iter())
+            .filter_map(|a| match (&a.required_by, &a.extra) {
+                (Some(by), Some(extra)) => Some((by.clone(), extra.clone())),
+                _ => None,
+            })
+            .collect::<BTreeSet<_>>();
+
+        // A distribution is due to an extra if it isn't reached without it.
+        for extra in &extras {
+            let reached = self.walk(&roots, Some(extra));
+
+            for (name, dist) in self.distributions.iter_mut() {
+                if !reached.contains_key(name) {
+                    dist.via_extras.insert(format!("{}[{}]", extra.0, extra.1));
+                }
+            }
+        }
+
+        for (name, attributions) in attributions {
+            if let Some(dist) = self.distributions.get_mut(&name) {
+                dist.attributions = attributions;
+            }
+        }
+    }
+
+    /// Describe why a distribution was installed.
+    ///
+    /// e.g. `colorama pulled in by click via extra 'cli'`.
+    pub fn describe(&self, name: &str) -> Option<String> {
+        let name = normalize_distribution_name(name);
+
+        self.distributions.get(&name).map(|dist| {
+            format!(
+                "{} {}",
+                name,
+                dist.attributions
+                    .iter()
+                    .map(|a| a.describe())
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            )
+        })
+    }
+
+    /// Find the distribution providing a top-level package or module.
+    ///
+    /// The name of a distribution itself also resolves to that distribution.
+    pub fn distribution_for_package(&self, package: &str) -> Option<&str> {
+        let normalized = normalize_distribution_name(package);
+
+        self.distributions
+            .iter()
+            .find(|(name, dist)| {
+                **name == normalized
+                    || dist
+                        .top_level
+                        .iter()
+                        .any(|top| normalize_distribution_name(top) == normalized)
+            })
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Total size of distributions pulled in by each extra.
+    ///
+    /// Keys have the form `<distribution>[<extra>]`. A distribution counts
+    /// towards every extra it wouldn't be installed without.
+    pub fn extra_sizes(&self) -> BTreeMap<String, u64> {
+        let mut res = BTreeMap::new();
+
+        for dist in self.distributions.values() {
+            for via in &dist.via_extras {
+                *res.entry(via.clone()).or_insert(0) += dist.size;
+            }
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::resource::{
+            PythonPackageDistributionResource, PythonPackageDistributionResourceFlavor,
+        },
+        tugger_file_manifest::FileData,
+    };
+
+    fn distribution_resource(package: &str, name: &str, data: &str) -> PythonResource<'static> {
+        PythonResource::from(PythonPackageDistributionResource {
+            location: PythonPackageDistributionResourceFlavor::DistInfo,
+            package: package.to_string(),
+            version: "1.0".to_string(),
+            name: name.to_string(),
+            data: FileData::Memory(data.as_bytes().to_vec()),
+        })
+    }
+
+    fn distribution(
+        package: &str,
+        requires: &[&str],
+        record: &str,
+    ) -> Vec<PythonResource<'static>> {
+        let mut metadata = format!("Metadata-Version: 2.1\nName: {}\nVersion: 1.0\n", package);
+        for requirement in requires {
+            metadata.push_str(&format!("Requires-Dist: {}\n", requirement));
+        }
+
+        vec![
+            distribution_resource(package, "METADATA", &metadata),
+            distribution_resource(package, "RECORD", record),
+        ]
+    }
+
+    #[test]
+    fn test_parse_record() {
+        let (size, top_level) = parse_record(concat!(
+            "foo/__init__.py,sha256=abc,100\n",
+            "foo/bar.py,sha256=def,50\n",
+            "_foo_speedups.cpython-39-x86_64-linux-gnu.so,sha256=ghi,1000\n",
+            "foo-1.0.dist-info/RECORD,,\n",
+            "../../bin/foo,sha256=jkl,10\n",
+        ));
+
+        assert_eq!(size, 1160);
+        assert_eq!(
+            top_level.into_iter().collect::<Vec<_>>(),
+            vec!["_foo_speedups".to_string(), "foo".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_extras_attribution() -> Result<()> {
+        let mut resources = vec![];
+        resources.extend(distribution(
+            "myapp",
+            &[
+                "click",
+                "psycopg2 ; extra == 'postgres'",
+                "redis ; extra == 'redis'",
+                "unused ; extra == 'docs'",
+            ],
+            "myapp/__init__.py,,10\n",
+        ));
+        resources.extend(distribution(
+            "click",
+            &["colorama ; extra == 'cli'"],
+            "click/__init__.py,,20\n",
+        ));
+        resources.extend(distribution(
+            "psycopg2",
+            &["click[cli]"],
+            "psycopg2/__init__.py,,300\n",
+        ));
+        resources.extend(distribution("redis", &[], "redis/__init__.py,,4000\n"));
+        resources.extend(distribution(
+            "colorama",
+            &[],
+            "colorama/__init__.py,,50000\n",
+        ));
+        resources.extend(distribution("Other_Thing", &[], "other/__init__.py,,1\n"));
+
+        let report = InstallReport::from_resources(
+            &[PythonRequirement::parse("myapp[postgres,redis]")?],
+            &resources,
+        )?;
+
+        assert_eq!(
+            report.describe("myapp"),
+            Some("myapp requested directly".to_string())
+        );
+        assert_eq!(
+            report.describe("psycopg2"),
+            Some("psycopg2 pulled in by myapp via extra 'postgres'".to_string())
+        );
+        assert_eq!(
+            report.describe("click"),
+            Some("click pulled in by myapp and pulled in by psycopg2".to_string())
+        );
+        assert_eq!(
+            report.describe("colorama"),
+            Some("colorama pulled in by click via extra 'cli'".to_string())
+        );
+        assert_eq!(
+            report.describe("other-thing"),
+            Some("other-thing requested directly".to_string())
+        );
+        assert_eq!(report.describe("unused"), None);
+
+        assert_eq!(
+            report.distributions["colorama"]
+                .via_extras
+                .iter()
+                .collect::<Vec<_>>(),
+            vec!["click[cli]", "myapp[postgres]"]
+        );
+
+        assert_eq!(
+            report.extra_sizes(),
+            [
+                ("click[cli]".to_string(), 50000),
+                ("myapp[postgres]".to_string(), 50300),
+                ("myapp[redis]".to_string(), 4000),
+            ]
+            .iter()
+            .cloned()
+            .collect::<BTreeMap<_, _>>()
+        );
+
+        assert_eq!(
+            report.distribution_for_package("other"),
+            Some("other-thing")
+        );
+        assert_eq!(
+            report.distribution_for_package("Other_Thing"),
+            Some("other-thing")
+        );
+        assert_eq!(report.distribution_for_package("missing"), None);
+
+        Ok(())
+    }
+}
@@ -345,6 +345,123 @@ impl FromStr for MultiprocessingStartMethod {
     }
 }
 
+/// Defines how `sys.executable` is set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SysExecutableMode {
+    /// Use the path of the application executable.
+    App,
+    /// Set to `None`.
+    None,
+    /// Use the path of a `python` compatible shim next to the application executable.
+    ///
+    /// The shim is created when the interpreter is initialized. When invoked, it
+    /// shares the embedded resources and accepts standard `python` arguments.
+    PythonShim,
+    /// Use the path the executable was invoked as.
+    ///
+    /// Unlike `App`, symbolic links to the executable aren't resolved. So
+    /// `python` symlinks in virtual environments find their `pyvenv.cfg`.
+    Invoked,
+}
+
+impl std::fmt::Display for SysExecutableMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::App => "app",
+            Self::None => "none",
+            Self::PythonShim => "python-shim",
+            Self::Invoked => "invoked",
+        })
+    }
+}
+
+impl FromStr for SysExecutableMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "app" => Ok(Self::App),
+            "none" => Ok(Self::None),
+            "python-shim" => Ok(Self::PythonShim),
+            "invoked" => Ok(Self::Invoked),
+            _ => Err(format!("{} is not a valid sys.executable mode", s)),
+        }
+    }
+}
+
+/// Defines what an embedded interpreter runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PythonRunMode {
+    /// Run what the `run_*` fields of `PythonInterpreterConfig` specify.
+    ///
+    /// This is the behavior of `Py_RunMain()`.
+    Default,
+    /// Run an interactive Python REPL.
+    ///
+    /// The `run_*` fields of `PythonInterpreterConfig` are ignored.
+    Repl,
+}
+
+impl std::fmt::Display for PythonRunMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Default => "default",
+            Self::Repl => "repl",
+        })
+    }
+}
+
+impl FromStr for PythonRunMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "repl" => Ok(Self::Repl),
+            _ => Err(format!("{} is not a valid run mode", s)),
+        }
+    }
+}
+
+/// Defines what an executable does when its embedded interpreter fails to initialize.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InitFailureAction {
+    /// Print the error and exit.
+    Exit,
+    /// Print the error and show it in a message box before exiting.
+    ///
+    /// Message boxes are only shown on Windows. This is intended for
+    /// executables using the `windows` subsystem, which have no console.
+    MessageBox,
+    /// Print the error and run a fallback command.
+    ///
+    /// The process exits with the exit code of the command.
+    RunFallbackCommand,
+}
+
+impl std::fmt::Display for InitFailureAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Exit => "exit",
+            Self::MessageBox => "message-box",
+            Self::RunFallbackCommand => "run-fallback-command",
+        })
+    }
+}
+
+impl FromStr for InitFailureAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exit" => Ok(Self::Exit),
+            "message-box" => Ok(Self::MessageBox),
+            "run-fallback-command" => Ok(Self::RunFallbackCommand),
+            _ => Err(format!("{} is not a valid init failure action", s)),
+        }
+    }
+}
+
 /// Holds configuration of a Python interpreter.
 ///
 /// This struct holds fields that are exposed by `PyPreConfig` and
@@ -9,7 +9,9 @@ and packaging facilities.
 */
 
 pub mod bytecode;
+pub mod distribution_files;
 pub mod filesystem_scanning;
+pub mod install_report;
 pub mod interpreter;
 pub mod libpython;
 pub mod licensing;
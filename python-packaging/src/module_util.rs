@@ -6,6 +6,53 @@
 
 use std::{collections::BTreeSet, path::Path, path::PathBuf};
 
+/// Names of modules compiled into every CPython interpreter.
+///
+/// These are always resolved by the builtin importer, so a module of the
+/// same name provided by other means is never imported. Platform-specific
+/// builtins for all supported platforms are included.
+pub const BUILTIN_MODULE_NAMES: &[&str] = &[
+    "_abc",
+    "_ast",
+    "_codecs",
+    "_collections",
+    "_functools",
+    "_imp",
+    "_io",
+    "_locale",
+    "_operator",
+    "_signal",
+    "_sre",
+    "_stat",
+    "_string",
+    "_symtable",
+    "_thread",
+    "_tracemalloc",
+    "_warnings",
+    "_weakref",
+    "_winapi",
+    "atexit",
+    "builtins",
+    "errno",
+    "faulthandler",
+    "gc",
+    "itertools",
+    "marshal",
+    "msvcrt",
+    "nt",
+    "posix",
+    "pwd",
+    "sys",
+    "time",
+    "winreg",
+    "xxsubtype",
+];
+
+/// Whether a fully qualified module name is a CPython builtin module.
+pub fn is_builtin_module_name(name: &str) -> bool {
+    BUILTIN_MODULE_NAMES.contains(&name)
+}
+
 /// Represents file name suffixes for Python modules.
 #[derive(Clone, Debug, PartialEq)]
 pub struct PythonModuleSuffixes {
@@ -25,6 +72,51 @@ pub struct PythonModuleSuffixes {
     pub extension: Vec<String>,
 }
 
+/// Normalize a fully qualified module name.
+///
+/// Whitespace surrounding the name and a trailing `.__init__`, which the
+/// importer ignores when resolving modules, are removed. Names with an empty
+/// component, such as `foo..bar` or `.foo`, are rejected.
+pub fn normalize_module_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    let name = name.strip_suffix(".__init__").unwrap_or(name);
+
+    if name.split('.').any(|part| part.is_empty()) {
+        Err(format!("{} is not a valid module name", name))
+    } else {
+        Ok(name.to_string())
+    }
+}
+
+/// Whether a string is a valid Python identifier.
+///
+/// Identifiers start with a letter or underscore, followed by letters,
+/// digits, or underscores. Non-ASCII letters and digits are accepted, which
+/// approximates Python's Unicode identifier rules.
+pub fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    match chars.next() {
+        Some(c) if c == '_' || c.is_alphabetic() => chars.all(|c| c == '_' || c.is_alphanumeric()),
+        _ => false,
+    }
+}
+
+/// Whether a name is a sequence of identifiers joined by `.`, such as `foo.bar`.
+pub fn is_dotted_identifier(name: &str) -> bool {
+    name.split('.').all(is_identifier)
+}
+
+/// Whether a module name is a package name or the name of a module within it.
+///
+/// e.g. `foo` and `foo.bar` are in package `foo`. `foobar` isn't.
+pub fn is_module_in_package(name: &str, package: &str) -> bool {
+    match name.strip_prefix(package) {
+        Some(rest) => rest.is_empty() || rest.starts_with('.'),
+        None => false,
+    }
+}
+
 /// Resolve the set of packages present in a fully qualified module name.
 pub fn packages_from_module_name(module: &str) -> BTreeSet<String> {
     let mut package_names = BTreeSet::new();
@@ -114,6 +206,41 @@ pub fn is_package_from_path(path: &Path) -> bool {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_normalize_module_name() {
+        assert_eq!(normalize_module_name("foo.bar"), Ok("foo.bar".to_string()));
+        assert_eq!(normalize_module_name(" foo "), Ok("foo".to_string()));
+        assert_eq!(normalize_module_name("foo.__init__"), Ok("foo".to_string()));
+        assert!(normalize_module_name("").is_err());
+        assert!(normalize_module_name("foo..bar").is_err());
+        assert!(normalize_module_name(".foo").is_err());
+        assert!(normalize_module_name("foo.").is_err());
+    }
+
+    #[test]
+    fn test_is_dotted_identifier() {
+        assert!(is_identifier("foo"));
+        assert!(is_identifier("_foo2"));
+        assert!(is_identifier("caf\u{e9}"));
+        assert!(!is_identifier(""));
+        assert!(!is_identifier("2bad"));
+        assert!(!is_identifier("foo-bar"));
+
+        assert!(is_dotted_identifier("foo.bar"));
+        assert!(!is_dotted_identifier("foo.2bad"));
+        assert!(!is_dotted_identifier("foo..bar"));
+        assert!(!is_dotted_identifier(""));
+    }
+
+    #[test]
+    fn test_is_module_in_package() {
+        assert!(is_module_in_package("foo", "foo"));
+        assert!(is_module_in_package("foo.bar", "foo"));
+        assert!(is_module_in_package("foo.bar.baz", "foo.bar"));
+        assert!(!is_module_in_package("foobar", "foo"));
+        assert!(!is_module_in_package("foo", "foo.bar"));
+    }
+
     #[test]
     fn test_packages_from_module_name() {
         assert_eq!(
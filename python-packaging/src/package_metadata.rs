@@ -5,10 +5,169 @@
 /*! Working with Python package metadata (i.e. .pkg-info directories) */
 
 use {
-    anyhow::{Context, Result},
+    anyhow::{anyhow, Context, Result},
     mailparse::parse_mail,
+    std::collections::BTreeMap,
 };
 
+/// Entry points parsed from an `entry_points.txt` file.
+///
+/// Keys are group names (e.g. `console_scripts`). Values map entry point
+/// names to their targets (e.g. `package.module:function`).
+pub type EntryPoints = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Parse the content of an `entry_points.txt` file.
+///
+/// The file is INI formatted. Names are case sensitive, as they are when
+/// parsed by `importlib.metadata`.
+pub fn parse_entry_points(data: &str) -> Result<EntryPoints> {
+    let mut entry_points = EntryPoints::new();
+    let mut group: Option<String> = None;
+
+    for (i, line) in data.lines().enumerate() {
+        let stripped = line.trim();
+
+        if stripped.is_empty() || stripped.starts_with('#') || stripped.starts_with(';') {
+            continue;
+        }
+
+        if stripped.starts_with('[') && stripped.ends_with(']') {
+            let name = stripped[1..stripped.len() - 1].trim().to_string();
+            entry_points.entry(name.clone()).or_default();
+            group = Some(name);
+            continue;
+        }
+
+        let group = group
+            .as_ref()
+            .ok_or_else(|| anyhow!("line {}: entry point outside of a group", i + 1))?;
+
+        let mut parts = stripped.splitn(2, '=');
+        let name = parts.next().unwrap();
+        let target = parts
+            .next()
+            .ok_or_else(|| anyhow!("line {}: expected `name = target`", i + 1))?;
+
+        entry_points
+            .get_mut(group)
+            .unwrap()
+            .insert(name.trim().to_string(), target.trim().to_string());
+    }
+
+    Ok(entry_points)
+}
+
+/// Normalize a distribution or extra name per PEP 503.
+///
+/// Names are lowercased and runs of `-`, `_`, and `.` become a single `-`.
+pub fn normalize_distribution_name(name: &str) -> String {
+    let mut res = String::with_capacity(name.len());
+    let mut in_separator = false;
+
+    for c in name.chars() {
+        if c == '-' || c == '_' || c == '.' {
+            if !in_separator {
+                res.push('-');
+            }
+            in_separator = true;
+        } else {
+            res.push(c.to_ascii_lowercase());
+            in_separator = false;
+        }
+    }
+
+    res
+}
+
+/// Find the value of an `extra == "..."` clause in an environment marker.
+fn parse_marker_extra(marker: &str) -> Option<String> {
+    let mut offset = 0;
+
+    while let Some(pos) = marker[offset..].find("extra") {
+        let start = offset + pos;
+        offset = start + "extra".len();
+
+        if marker[..start].ends_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+            continue;
+        }
+
+        let value = marker[offset..].trim_start();
+        if !value.starts_with("==") {
+            continue;
+        }
+        let value = value[2..].trim_start();
+
+        if let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') {
+            if let Some(end) = value[1..].find(quote) {
+                return Some(normalize_distribution_name(&value[1..1 + end]));
+            }
+        }
+    }
+
+    None
+}
+
+/// A requirement on a distribution.
+///
+/// These are found in `Requires-Dist` metadata headers and in arguments
+/// to `pip install`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PythonRequirement {
+    /// Normalized name of the required distribution.
+    pub name: String,
+
+    /// Normalized names of extras requested of the distribution.
+    pub extras: Vec<String>,
+
+    /// Normalized name of the extra this requirement is conditional on.
+    ///
+    /// Derived from an `extra == "..."` clause in the environment marker.
+    pub marker_extra: Option<String>,
+}
+
+impl PythonRequirement {
+    /// Parse a PEP 508 requirement string.
+    ///
+    /// Only the name, extras, and an `extra == "..."` marker clause are
+    /// interpreted. Version specifiers and other marker clauses are ignored.
+    pub fn parse(s: &str) -> Result<Self> {
+        let (requirement, marker) = match s.find(';') {
+            Some(pos) => (&s[..pos], Some(&s[pos + 1..])),
+            None => (s, None),
+        };
+        let requirement = requirement.trim();
+
+        let name_end = requirement
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.'))
+            .unwrap_or(requirement.len());
+        let name = &requirement[..name_end];
+        if !name.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            return Err(anyhow!("no distribution name in requirement: {}", s));
+        }
+
+        let rest = requirement[name_end..].trim_start();
+        let extras = if rest.starts_with('[') {
+            let end = rest
+                .find(']')
+                .ok_or_else(|| anyhow!("unterminated extras in requirement: {}", s))?;
+
+            rest[1..end]
+                .split(',')
+                .map(|extra| normalize_distribution_name(extra.trim()))
+                .filter(|extra| !extra.is_empty())
+                .collect()
+        } else {
+            vec![]
+        };
+
+        Ok(Self {
+            name: normalize_distribution_name(name),
+            extras,
+            marker_extra: marker.and_then(parse_marker_extra),
+        })
+    }
+}
+
 /// Represents a Python METADATA file.
 pub struct PythonPackageMetadata {
     headers: Vec<(String, String)>,
@@ -40,7 +199,6 @@ impl PythonPackageMetadata {
     }
 
     /// Find all values of a specified header.
-    #[allow(unused)]
     pub fn find_all_headers(&self, key: &str) -> Vec<&str> {
         self.headers
             .iter()
@@ -48,6 +206,11 @@ impl PythonPackageMetadata {
             .collect::<Vec<_>>()
     }
 
+    /// All headers, in the order they appear.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
     pub fn name(&self) -> Option<&str> {
         self.find_first_header("Name")
     }
@@ -99,4 +262,84 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_parse_entry_points() -> Result<()> {
+        let data = concat!(
+            "[console_scripts]\n",
+            "black = black:patched_main\n",
+            "blackd=blackd:patched_main [d]\n",
+            "\n",
+            "# comment\n",
+            "[empty]\n",
+        );
+
+        let entry_points = parse_entry_points(data)?;
+
+        assert_eq!(entry_points.len(), 2);
+        assert_eq!(
+            entry_points["console_scripts"],
+            [
+                ("black".to_string(), "black:patched_main".to_string()),
+                ("blackd".to_string(), "blackd:patched_main [d]".to_string()),
+            ]
+            .iter()
+            .cloned()
+            .collect::<BTreeMap<_, _>>()
+        );
+        assert!(entry_points["empty"].is_empty());
+
+        assert!(parse_entry_points("black = black:main\n").is_err());
+        assert!(parse_entry_points("[console_scripts]\nblack\n").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize_distribution_name() {
+        assert_eq!(normalize_distribution_name("Foo_Bar"), "foo-bar");
+        assert_eq!(
+            normalize_distribution_name("zope.interface"),
+            "zope-interface"
+        );
+        assert_eq!(normalize_distribution_name("a-_.b"), "a-b");
+    }
+
+    #[test]
+    fn test_parse_requirement() -> Result<()> {
+        assert_eq!(
+            PythonRequirement::parse("click (>=6.5)")?,
+            PythonRequirement {
+                name: "click".to_string(),
+                extras: vec![],
+                marker_extra: None,
+            }
+        );
+        assert_eq!(
+            PythonRequirement::parse("myapp[postgres, Redis]>=1.0")?,
+            PythonRequirement {
+                name: "myapp".to_string(),
+                extras: vec!["postgres".to_string(), "redis".to_string()],
+                marker_extra: None,
+            }
+        );
+        assert_eq!(
+            PythonRequirement::parse(
+                "colorama; platform_system == \"Windows\" and extra == 'CLI'"
+            )?,
+            PythonRequirement {
+                name: "colorama".to_string(),
+                extras: vec![],
+                marker_extra: Some("cli".to_string()),
+            }
+        );
+        assert_eq!(
+            PythonRequirement::parse("uvloop ; python_version >= \"3.7\"")?.marker_extra,
+            None
+        );
+        assert!(PythonRequirement::parse("./local/path").is_err());
+        assert!(PythonRequirement::parse("foo[bar").is_err());
+
+        Ok(())
+    }
 }
@@ -10,17 +10,91 @@ use {
     crate::{
         licensing::SAFE_SYSTEM_LIBRARIES,
         location::ConcreteResourceLocation,
-        resource::{PythonExtensionModule, PythonExtensionModuleVariants, PythonResource},
-        resource_collection::PythonResourceAddCollectionContext,
+        module_util::is_module_in_package,
+        package_metadata::normalize_distribution_name,
+        resource::{
+            BytecodeOptimizationLevel, PythonExtensionModule, PythonExtensionModuleVariants,
+            PythonResource,
+        },
+        resource_collection::{BytecodeGeneration, PythonResourceAddCollectionContext},
     },
-    anyhow::Result,
+    anyhow::{anyhow, Result},
     std::{
-        collections::{HashMap, HashSet},
+        collections::{BTreeSet, HashMap, HashSet},
         convert::TryFrom,
     },
     tugger_licensing::LicenseFlavor,
 };
 
+/// `encodings` submodules imported by every interpreter during initialization.
+///
+/// `aliases` is imported by `encodings` itself. `utf_8` and `latin_1` are
+/// imported when the standard streams are initialized.
+pub const REQUIRED_ENCODINGS_MODULES: &[&str] = &["aliases", "latin_1", "utf_8"];
+
+/// Extension modules holding large codec data tables.
+///
+/// These implement the CJK codecs and the Unicode character database.
+pub const CODEC_DATA_EXTENSION_MODULES: &[&str] = &[
+    "_codecs_cn",
+    "_codecs_hk",
+    "_codecs_iso2022",
+    "_codecs_jp",
+    "_codecs_kr",
+    "_codecs_tw",
+    "_multibytecodec",
+    "unicodedata",
+];
+
+/// Normalize an encoding name to the name of its `encodings` submodule.
+///
+/// This handles spelling variations like `UTF-8` and `utf8`. It doesn't
+/// resolve aliases like `latin1` that map to a differently named module.
+pub fn normalize_encoding_name(name: &str) -> String {
+    name.trim()
+        .to_lowercase()
+        .replace(&['-', ' ', '.'][..], "_")
+}
+
+/// Restricts the `encodings` package to a set of codecs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MinimalEncodings {
+    /// Names of `encodings` submodules to keep.
+    ///
+    /// [REQUIRED_ENCODINGS_MODULES] are always kept.
+    pub keep: BTreeSet<String>,
+
+    /// Whether to exclude [CODEC_DATA_EXTENSION_MODULES].
+    pub exclude_codec_data: bool,
+}
+
+impl MinimalEncodings {
+    /// Construct an instance keeping the named encodings.
+    ///
+    /// Names are normalized via [normalize_encoding_name].
+    pub fn new(keep: impl IntoIterator<Item = impl AsRef<str>>, exclude_codec_data: bool) -> Self {
+        Self {
+            keep: keep
+                .into_iter()
+                .map(|name| normalize_encoding_name(name.as_ref()))
+                .chain(REQUIRED_ENCODINGS_MODULES.iter().map(|s| s.to_string()))
+                .collect(),
+            exclude_codec_data,
+        }
+    }
+
+    /// Whether a module is excluded.
+    pub fn excludes_module(&self, name: &str) -> bool {
+        if let Some(leaf) = name.strip_prefix("encodings.") {
+            let submodule = leaf.split('.').next().unwrap_or(leaf);
+
+            !self.keep.contains(submodule)
+        } else {
+            self.exclude_codec_data && CODEC_DATA_EXTENSION_MODULES.contains(&name)
+        }
+    }
+}
+
 /// Denotes methods to filter extension modules.
 #[derive(Clone, Debug, PartialEq)]
 pub enum ExtensionModuleFilter {
@@ -92,6 +166,97 @@ impl AsRef<str> for ResourceHandlingMode {
     }
 }
 
+/// Describes how problems found when verifying extension modules are handled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExtensionModuleVerification {
+    /// Problems are errors.
+    Error,
+
+    /// Problems are reported as warnings.
+    Warn,
+
+    /// Extension modules aren't verified.
+    Off,
+}
+
+impl TryFrom<&str> for ExtensionModuleVerification {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, String> {
+        match value {
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "off" => Ok(Self::Off),
+            _ => Err(format!(
+                "{} is not a valid extension module verification mode; use \"error\", \"warn\", or \"off\"",
+                value
+            )),
+        }
+    }
+}
+
+impl AsRef<str> for ExtensionModuleVerification {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Off => "off",
+        }
+    }
+}
+
+/// Describes the Python modules a set of bytecode optimization levels applies to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BytecodeScope {
+    /// Modules in the Python standard library.
+    Stdlib,
+
+    /// Modules not in the Python standard library.
+    NonStdlib,
+
+    /// Modules whose name or the name of a parent package matches a glob.
+    Package(glob::Pattern),
+}
+
+impl BytecodeScope {
+    /// Whether a module belongs to this scope.
+    pub fn matches(&self, name: &str, is_stdlib: bool) -> bool {
+        match self {
+            Self::Stdlib => is_stdlib,
+            Self::NonStdlib => !is_stdlib,
+            Self::Package(pattern) => name
+                .match_indices('.')
+                .map(|(idx, _)| &name[0..idx])
+                .chain(std::iter::once(name))
+                .any(|candidate| pattern.matches(candidate)),
+        }
+    }
+}
+
+impl TryFrom<&str> for BytecodeScope {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "stdlib" => Ok(Self::Stdlib),
+            "non-stdlib" => Ok(Self::NonStdlib),
+            _ => Ok(Self::Package(glob::Pattern::new(value).map_err(|e| {
+                format!("{} is not a valid bytecode scope: {}", value, e)
+            })?)),
+        }
+    }
+}
+
+impl AsRef<str> for BytecodeScope {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Stdlib => "stdlib",
+            Self::NonStdlib => "non-stdlib",
+            Self::Package(pattern) => pattern.as_str(),
+        }
+    }
+}
+
 /// Defines how Python resources should be packaged.
 #[derive(Clone, Debug, PartialEq)]
 pub struct PythonPackagingPolicy {
@@ -101,6 +266,9 @@ pub struct PythonPackagingPolicy {
     /// Preferred variants of extension modules.
     preferred_extension_module_variants: HashMap<String, String>,
 
+    /// Selected versions of packages, keyed by normalized distribution name.
+    package_versions: HashMap<String, String>,
+
     /// Where resources should be placed/loaded from by default.
     resources_location: ConcreteResourceLocation,
 
@@ -124,6 +292,12 @@ pub struct PythonPackagingPolicy {
     /// `PythonPackageResource`, etc).
     allow_files: bool,
 
+    /// Whether resources too large for the packed resources format can be chunked.
+    ///
+    /// If true, in-memory payloads larger than 4 GiB are stored in chunks.
+    /// If false, adding such resources is an error.
+    allow_chunked_resources: bool,
+
     /// Whether file scanning should emit `PythonResource::File` variants.
     ///
     /// If true, this resource variant is emitted when scanning for
@@ -158,6 +332,9 @@ pub struct PythonPackagingPolicy {
     /// Whether to include test files.
     include_test: bool,
 
+    /// Names of test packages and modules to include when `include_test` is false.
+    include_test_packages: Vec<String>,
+
     /// Whether to classify `File` resources as `include = True` by default.
     include_file_resources: bool,
 
@@ -178,6 +355,23 @@ pub struct PythonPackagingPolicy {
 
     /// Python modules for which bytecode should not be generated by default.
     no_bytecode_modules: HashSet<String>,
+
+    /// Bytecode optimization levels to use for modules in a scope.
+    ///
+    /// Entries override the global `bytecode_optimize_level_*` settings.
+    /// Values are whether to write bytecode at levels 0, 1, and 2.
+    bytecode_level_scopes: Vec<(BytecodeScope, [bool; 3])>,
+
+    /// Filename suffix of extension modules the target interpreter expects.
+    ///
+    /// e.g. `.cpython-39-x86_64-linux-gnu.so`. `None` if not known.
+    extension_module_suffix: Option<String>,
+
+    /// How problems found when verifying shared library extension modules are handled.
+    extension_module_verification: ExtensionModuleVerification,
+
+    /// Restricts the `encodings` package to a set of codecs.
+    minimal_encodings: Option<MinimalEncodings>,
 }
 
 impl Default for PythonPackagingPolicy {
@@ -185,10 +379,12 @@ impl Default for PythonPackagingPolicy {
         PythonPackagingPolicy {
             extension_module_filter: ExtensionModuleFilter::All,
             preferred_extension_module_variants: HashMap::new(),
+            package_versions: HashMap::new(),
             resources_location: ConcreteResourceLocation::InMemory,
             resources_location_fallback: None,
             allow_in_memory_shared_library_loading: false,
             allow_files: false,
+            allow_chunked_resources: false,
             file_scanner_emit_files: false,
             file_scanner_classify_files: true,
             include_classified_resources: true,
@@ -196,12 +392,17 @@ impl Default for PythonPackagingPolicy {
             include_non_distribution_sources: true,
             include_distribution_resources: false,
             include_test: false,
+            include_test_packages: vec![],
             include_file_resources: false,
             broken_extensions: HashMap::new(),
             bytecode_optimize_level_zero: true,
             bytecode_optimize_level_one: false,
             bytecode_optimize_level_two: false,
             no_bytecode_modules: HashSet::new(),
+            bytecode_level_scopes: vec![],
+            extension_module_suffix: None,
+            extension_module_verification: ExtensionModuleVerification::Error,
+            minimal_encodings: None,
         }
     }
 }
@@ -233,6 +434,23 @@ impl PythonPackagingPolicy {
             .insert(extension.to_string(), variant.to_string());
     }
 
+    /// Obtain the selected version of a package, if one was set.
+    ///
+    /// Package names are compared after normalization.
+    pub fn package_version(&self, package: &str) -> Option<&str> {
+        self.package_versions
+            .get(&normalize_distribution_name(package))
+            .map(|v| v.as_str())
+    }
+
+    /// Denote the version of a package that is in use.
+    ///
+    /// Distribution resources of other versions of the package are stale.
+    pub fn set_package_version(&mut self, package: &str, version: &str) {
+        self.package_versions
+            .insert(normalize_distribution_name(package), version.to_string());
+    }
+
     /// Obtain the primary location for added resources.
     pub fn resources_location(&self) -> &ConcreteResourceLocation {
         &self.resources_location
@@ -263,6 +481,16 @@ impl PythonPackagingPolicy {
         self.allow_files = value;
     }
 
+    /// Whether in-memory resources larger than 4 GiB can be stored in chunks.
+    pub fn allow_chunked_resources(&self) -> bool {
+        self.allow_chunked_resources
+    }
+
+    /// Set whether in-memory resources larger than 4 GiB can be stored in chunks.
+    pub fn set_allow_chunked_resources(&mut self, value: bool) {
+        self.allow_chunked_resources = value;
+    }
+
     /// Whether file scanning should emit `PythonResource::File` variants.
     pub fn file_scanner_emit_files(&self) -> bool {
         self.file_scanner_emit_files
@@ -333,6 +561,36 @@ impl PythonPackagingPolicy {
         self.include_test = include;
     }
 
+    /// Names of test packages and modules included when `include_test` is false.
+    pub fn include_test_packages(&self) -> &[String] {
+        &self.include_test_packages
+    }
+
+    /// Set names of test packages and modules to include when `include_test` is false.
+    ///
+    /// Each name includes the named module, modules under it, and the
+    /// packages containing it, so it can be imported. Package resources
+    /// under the name and those of the package containing it are included
+    /// too, as tests commonly load data files from their own directory.
+    pub fn set_include_test_packages(&mut self, names: Vec<String>) {
+        self.include_test_packages = names;
+    }
+
+    /// Whether a test module is included by `include_test_packages`.
+    fn includes_test_module(&self, name: &str) -> bool {
+        self.include_test_packages.iter().any(|package| {
+            is_module_in_package(name, package) || is_module_in_package(package, name)
+        })
+    }
+
+    /// Whether test package resources are included by `include_test_packages`.
+    fn includes_test_package_resource(&self, leaf_package: &str) -> bool {
+        self.include_test_packages.iter().any(|package| {
+            is_module_in_package(leaf_package, package)
+                || matches!(package.rfind('.'), Some(pos) if &package[..pos] == leaf_package)
+        })
+    }
+
     /// Get whether to classify `File` resources as include by default.
     pub fn include_file_resources(&self) -> bool {
         self.include_file_resources
@@ -435,6 +693,96 @@ impl PythonPackagingPolicy {
         self.no_bytecode_modules.insert(name.to_string());
     }
 
+    /// Obtain registered bytecode optimization level scopes.
+    ///
+    /// Values are whether to write bytecode at levels 0, 1, and 2.
+    pub fn bytecode_level_scopes(&self) -> &[(BytecodeScope, [bool; 3])] {
+        &self.bytecode_level_scopes
+    }
+
+    /// Set the bytecode optimization levels for Python modules in a scope.
+    ///
+    /// Modules in the scope will only have bytecode for the specified levels
+    /// by default. Registering a scope again replaces its levels.
+    ///
+    /// When multiple scopes match a module, `BytecodeScope::Package` scopes
+    /// take precedence over `Stdlib` and `NonStdlib` scopes. Otherwise the
+    /// most recently registered scope wins.
+    pub fn set_bytecode_levels_for_scope(
+        &mut self,
+        scope: BytecodeScope,
+        levels: &[BytecodeOptimizationLevel],
+    ) {
+        let levels = [
+            levels.contains(&BytecodeOptimizationLevel::Zero),
+            levels.contains(&BytecodeOptimizationLevel::One),
+            levels.contains(&BytecodeOptimizationLevel::Two),
+        ];
+
+        self.bytecode_level_scopes.retain(|(s, _)| s != &scope);
+        self.bytecode_level_scopes.push((scope, levels));
+    }
+
+    /// Obtain the filename suffix of extension modules the target interpreter expects.
+    pub fn extension_module_suffix(&self) -> Option<&str> {
+        self.extension_module_suffix.as_deref()
+    }
+
+    /// Set the filename suffix of extension modules the target interpreter expects.
+    pub fn set_extension_module_suffix(&mut self, suffix: Option<String>) {
+        self.extension_module_suffix = suffix;
+    }
+
+    /// How problems found when verifying extension modules are handled.
+    pub fn extension_module_verification(&self) -> ExtensionModuleVerification {
+        self.extension_module_verification
+    }
+
+    /// Obtain the restriction of the `encodings` package to a set of codecs.
+    pub fn minimal_encodings(&self) -> Option<&MinimalEncodings> {
+        self.minimal_encodings.as_ref()
+    }
+
+    /// Restrict the `encodings` package to a set of codecs.
+    ///
+    /// Excluded modules are added with `include = false`. Excluded extension
+    /// modules that aren't minimally required aren't resolved by
+    /// `resolve_python_extension_modules()`.
+    pub fn set_minimal_encodings(&mut self, value: Option<MinimalEncodings>) {
+        self.minimal_encodings = value;
+    }
+
+    /// Whether a module is excluded by `minimal_encodings()`.
+    pub fn excludes_encodings_module(&self, name: &str) -> bool {
+        matches!(&self.minimal_encodings, Some(m) if m.excludes_module(name))
+    }
+
+    /// Set how problems found when verifying extension modules are handled.
+    pub fn set_extension_module_verification(&mut self, value: ExtensionModuleVerification) {
+        self.extension_module_verification = value;
+    }
+
+    /// Resolve the bytecode optimization levels to use for a Python module.
+    fn bytecode_levels_for_module(&self, name: &str, is_stdlib: bool) -> [bool; 3] {
+        let matching = |package: bool| {
+            self.bytecode_level_scopes
+                .iter()
+                .rev()
+                .filter(move |(scope, _)| matches!(scope, BytecodeScope::Package(_)) == package)
+                .find(|(scope, _)| scope.matches(name, is_stdlib))
+        };
+
+        if let Some((_, levels)) = matching(true).or_else(|| matching(false)) {
+            *levels
+        } else {
+            [
+                self.bytecode_optimize_level_zero,
+                self.bytecode_optimize_level_one,
+                self.bytecode_optimize_level_two,
+            ]
+        }
+    }
+
     /// Derive a `PythonResourceAddCollectionContext` for a resource using current settings.
     ///
     /// The returned object essentially says how the resource should be added
@@ -443,7 +791,16 @@ impl PythonPackagingPolicy {
         &self,
         resource: &PythonResource,
     ) -> PythonResourceAddCollectionContext {
-        let include = self.filter_python_resource(resource);
+        let include = self.filter_python_resource(resource)
+            && !match resource {
+                PythonResource::ModuleSource(module) => {
+                    self.excludes_encodings_module(&module.name)
+                }
+                PythonResource::ModuleBytecodeRequest(module) => {
+                    self.excludes_encodings_module(&module.name)
+                }
+                _ => false,
+            };
 
         let store_source = match resource {
             PythonResource::ModuleSource(ref module) => {
@@ -459,35 +816,19 @@ impl PythonPackagingPolicy {
         let location = self.resources_location.clone();
         let location_fallback = self.resources_location_fallback.clone();
 
-        let optimize_level_zero = match resource {
+        let [optimize_level_zero, optimize_level_one, optimize_level_two] = match resource {
             PythonResource::ModuleSource(module) => {
                 if self.no_bytecode_modules.contains(&*module.name) {
-                    false
+                    [false, false, false]
                 } else {
-                    self.bytecode_optimize_level_zero
+                    self.bytecode_levels_for_module(&module.name, module.is_stdlib)
                 }
             }
-            _ => self.bytecode_optimize_level_zero,
-        };
-        let optimize_level_one = match resource {
-            PythonResource::ModuleSource(module) => {
-                if self.no_bytecode_modules.contains(&*module.name) {
-                    false
-                } else {
-                    self.bytecode_optimize_level_one
-                }
-            }
-            _ => self.bytecode_optimize_level_one,
-        };
-        let optimize_level_two = match resource {
-            PythonResource::ModuleSource(module) => {
-                if self.no_bytecode_modules.contains(&*module.name) {
-                    false
-                } else {
-                    self.bytecode_optimize_level_two
-                }
-            }
-            _ => self.bytecode_optimize_level_two,
+            _ => [
+                self.bytecode_optimize_level_zero,
+                self.bytecode_optimize_level_one,
+                self.bytecode_optimize_level_two,
+            ],
         };
 
         PythonResourceAddCollectionContext {
@@ -498,6 +839,7 @@ impl PythonPackagingPolicy {
             optimize_level_zero,
             optimize_level_one,
             optimize_level_two,
+            bytecode_generation: BytecodeGeneration::Eager,
         }
     }
 
@@ -523,17 +865,27 @@ impl PythonPackagingPolicy {
 
         match resource {
             PythonResource::ModuleSource(module) => {
-                if !self.include_test && module.is_test {
+                if module.is_test && !self.include_test && !self.includes_test_module(&module.name)
+                {
                     false
                 } else {
                     self.include_distribution_sources
                 }
             }
-            PythonResource::ModuleBytecodeRequest(module) => self.include_test || !module.is_test,
+            PythonResource::ModuleBytecodeRequest(module) => {
+                self.include_test || !module.is_test || self.includes_test_module(&module.name)
+            }
             PythonResource::ModuleBytecode(_) => false,
             PythonResource::PackageResource(resource) => {
                 if resource.is_stdlib {
-                    if self.include_distribution_resources {
+                    // Resources of selected test packages are included even
+                    // if distribution resources aren't.
+                    if resource.is_test
+                        && !self.include_test
+                        && self.includes_test_package_resource(&resource.leaf_package)
+                    {
+                        true
+                    } else if self.include_distribution_resources {
                         self.include_test || !resource.is_test
                     } else {
                         false
@@ -572,6 +924,26 @@ impl PythonPackagingPolicy {
                 continue;
             }
 
+            if self.excludes_encodings_module(name)
+                && !variants.iter().any(|em| em.is_minimally_required())
+            {
+                continue;
+            }
+
+            // A preferred variant that doesn't exist is almost certainly a
+            // configuration error. Fail loudly rather than silently falling back
+            // to the default variant, as the variant may be security relevant.
+            if let Some(preferred) = self.preferred_extension_module_variants.get(name) {
+                if !variants.has_variant(preferred) {
+                    return Err(anyhow!(
+                        "preferred variant {} of extension module {} does not exist; available variants: {}",
+                        preferred,
+                        name,
+                        variants.variant_names().join(", ")
+                    ));
+                }
+            }
+
             // Always add minimally required extension modules, because things don't
             // work if we don't do this.
             let ext_variants: PythonExtensionModuleVariants = variants
@@ -683,7 +1055,37 @@ impl PythonPackagingPolicy {
 
 #[cfg(test)]
 mod tests {
-    use {super::*, tugger_file_manifest::File};
+    use {
+        super::*,
+        crate::resource::{LibraryDependency, PythonModuleSource, PythonPackageResource},
+        tugger_file_manifest::{File, FileData},
+        tugger_licensing::LicensedComponent,
+    };
+
+    fn ssl_extension(variant: &str, license: &str) -> Result<PythonExtensionModule> {
+        Ok(PythonExtensionModule {
+            name: "_ssl".to_string(),
+            init_fn: Some("PyInit__ssl".to_string()),
+            extension_file_suffix: ".so".to_string(),
+            shared_library: None,
+            object_file_data: vec![],
+            is_package: false,
+            link_libraries: vec![LibraryDependency {
+                name: "ssl".to_string(),
+                static_library: None,
+                static_filename: None,
+                dynamic_library: None,
+                dynamic_filename: None,
+                framework: false,
+                system: false,
+            }],
+            is_stdlib: true,
+            builtin_default: false,
+            required: false,
+            variant: Some(variant.to_string()),
+            license: Some(LicensedComponent::new_spdx("_ssl", license)?),
+        })
+    }
 
     #[test]
     fn test_add_collection_context_file() -> Result<()> {
@@ -701,4 +1103,299 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_preferred_ssl_variant() -> Result<()> {
+        let variants = vec![
+            ssl_extension("default", "OpenSSL")?,
+            ssl_extension("openssl-3.0-fips", "Apache-2.0")?,
+        ]
+        .into_iter()
+        .collect::<PythonExtensionModuleVariants>();
+
+        let mut policy = PythonPackagingPolicy::default();
+
+        let res = policy.resolve_python_extension_modules(
+            vec![&variants].into_iter(),
+            "x86_64-unknown-linux-gnu",
+        )?;
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].variant, Some("default".to_string()));
+
+        policy.set_preferred_extension_module_variant("_ssl", "openssl-3.0-fips");
+        let res = policy.resolve_python_extension_modules(
+            vec![&variants].into_iter(),
+            "x86_64-unknown-linux-gnu",
+        )?;
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].variant, Some("openssl-3.0-fips".to_string()));
+        assert_eq!(res[0].ssl_library(), Some("openssl".to_string()));
+        // License metadata follows the selected variant.
+        assert_eq!(
+            res[0]
+                .license
+                .as_ref()
+                .unwrap()
+                .spdx_expression()
+                .unwrap()
+                .to_string(),
+            "Apache-2.0"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preferred_variant_missing() -> Result<()> {
+        let variants = vec![ssl_extension("default", "OpenSSL")?]
+            .into_iter()
+            .collect::<PythonExtensionModuleVariants>();
+
+        let mut policy = PythonPackagingPolicy::default();
+        policy.set_preferred_extension_module_variant("_ssl", "libressl");
+
+        let err = policy
+            .resolve_python_extension_modules(
+                vec![&variants].into_iter(),
+                "x86_64-unknown-linux-gnu",
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "preferred variant libressl of extension module _ssl does not exist; available variants: default"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytecode_levels_for_scope() -> Result<()> {
+        let module = |name: &str, is_stdlib: bool| -> PythonResource {
+            PythonModuleSource {
+                name: name.to_string(),
+                source: FileData::Memory(vec![]),
+                is_package: false,
+                cache_tag: "cpython-39".to_string(),
+                is_stdlib,
+                is_test: false,
+            }
+            .into()
+        };
+
+        let levels = |policy: &PythonPackagingPolicy, resource: &PythonResource| {
+            let context = policy.derive_add_collection_context(resource);
+            [
+                context.optimize_level_zero,
+                context.optimize_level_one,
+                context.optimize_level_two,
+            ]
+        };
+
+        let mut policy = PythonPackagingPolicy::default();
+        policy.set_bytecode_levels_for_scope(
+            BytecodeScope::try_from("stdlib").unwrap(),
+            &[BytecodeOptimizationLevel::Two],
+        );
+
+        assert_eq!(levels(&policy, &module("json", true)), [false, false, true]);
+        assert_eq!(levels(&policy, &module("app", false)), [true, false, false]);
+
+        policy.set_bytecode_levels_for_scope(
+            BytecodeScope::try_from("non-stdlib").unwrap(),
+            &[
+                BytecodeOptimizationLevel::Zero,
+                BytecodeOptimizationLevel::One,
+            ],
+        );
+        assert_eq!(levels(&policy, &module("app", false)), [true, true, false]);
+
+        // Package scopes take precedence and match submodules.
+        policy.set_bytecode_levels_for_scope(BytecodeScope::try_from("json").unwrap(), &[]);
+        assert_eq!(
+            levels(&policy, &module("json.decoder", true)),
+            [false, false, false]
+        );
+        assert_eq!(
+            levels(&policy, &module("jsonx", true)),
+            [false, false, true]
+        );
+
+        // Registering a scope again replaces it.
+        policy.set_bytecode_levels_for_scope(
+            BytecodeScope::try_from("stdlib").unwrap(),
+            &[BytecodeOptimizationLevel::One],
+        );
+        assert_eq!(policy.bytecode_level_scopes().len(), 3);
+        assert_eq!(levels(&policy, &module("os", true)), [false, true, false]);
+
+        // Modules registered as not having bytecode still have none.
+        policy.register_no_bytecode_module("os");
+        assert_eq!(levels(&policy, &module("os", true)), [false, false, false]);
+
+        // Only module sources are affected.
+        let file = File::new("foo.py", vec![42]);
+        assert_eq!(levels(&policy, &file.into()), [true, false, false]);
+
+        assert!(BytecodeScope::try_from("foo[").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_minimal_encodings() -> Result<()> {
+        let module = |name: &str| -> PythonResource {
+            PythonModuleSource {
+                name: name.to_string(),
+                source: FileData::Memory(vec![]),
+                is_package: false,
+                cache_tag: "cpython-39".to_string(),
+                is_stdlib: true,
+                is_test: false,
+            }
+            .into()
+        };
+
+        let extension = |name: &str, builtin_default: bool| -> PythonExtensionModuleVariants {
+            std::iter::once(PythonExtensionModule {
+                name: name.to_string(),
+                init_fn: Some(format!("PyInit_{}", name)),
+                extension_file_suffix: ".so".to_string(),
+                shared_library: None,
+                object_file_data: vec![],
+                is_package: false,
+                link_libraries: vec![],
+                is_stdlib: true,
+                builtin_default,
+                required: false,
+                variant: None,
+                license: None,
+            })
+            .collect()
+        };
+
+        let mut policy = PythonPackagingPolicy::default();
+        let extensions = [
+            extension("_codecs_jp", false),
+            extension("unicodedata", true),
+        ];
+
+        assert!(
+            policy
+                .derive_add_collection_context(&module("encodings.cp1252"))
+                .include
+        );
+
+        policy.set_minimal_encodings(Some(MinimalEncodings::new(
+            ["UTF-8", "ascii"].iter(),
+            false,
+        )));
+        assert_eq!(
+            policy.minimal_encodings().unwrap().keep,
+            ["aliases", "ascii", "latin_1", "utf_8"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        );
+
+        for (name, include) in &[
+            ("encodings", true),
+            ("encodings.aliases", true),
+            ("encodings.ascii", true),
+            ("encodings.latin_1", true),
+            ("encodings.utf_8", true),
+            ("encodings.cp1252", false),
+            ("encodings.utf_8_sig", false),
+            ("unicodedata", true),
+        ] {
+            assert_eq!(
+                policy.derive_add_collection_context(&module(name)).include,
+                *include,
+                "{}",
+                name
+            );
+        }
+
+        let names = |policy: &PythonPackagingPolicy| -> Result<BTreeSet<String>> {
+            Ok(policy
+                .resolve_python_extension_modules(extensions.iter(), "x86_64-unknown-linux-gnu")?
+                .into_iter()
+                .map(|em| em.name)
+                .collect())
+        };
+
+        assert_eq!(names(&policy)?.len(), 2);
+
+        // Minimally required extension modules are kept.
+        policy.set_minimal_encodings(Some(MinimalEncodings::new(["utf_8"].iter(), true)));
+        assert_eq!(
+            names(&policy)?,
+            std::iter::once("unicodedata".to_string()).collect()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_test_packages() {
+        let module = |name: &str| -> PythonResource {
+            PythonModuleSource {
+                name: name.to_string(),
+                source: FileData::Memory(vec![]),
+                is_package: false,
+                cache_tag: "cpython-39".to_string(),
+                is_stdlib: true,
+                is_test: true,
+            }
+            .into()
+        };
+
+        let resource = |leaf_package: &str| -> PythonResource {
+            PythonPackageResource {
+                leaf_package: leaf_package.to_string(),
+                relative_name: "keycert.pem".to_string(),
+                data: FileData::Memory(vec![]),
+                is_stdlib: true,
+                is_test: true,
+            }
+            .into()
+        };
+
+        let mut policy = PythonPackagingPolicy::default();
+        policy.set_include_test_packages(vec![
+            "test.support".to_string(),
+            "test.test_ssl".to_string(),
+        ]);
+
+        for (name, include) in &[
+            ("test", true),
+            ("test.support", true),
+            ("test.support.script_helper", true),
+            ("test.test_ssl", true),
+            ("test.test_os", false),
+            ("test.test_ssl_extra", false),
+            ("tkinter.test", false),
+        ] {
+            assert_eq!(
+                policy.derive_add_collection_context(&module(name)).include,
+                *include,
+                "{}",
+                name
+            );
+        }
+
+        for (leaf_package, include) in &[
+            ("test", true),
+            ("test.support", true),
+            ("test.test_email", false),
+        ] {
+            assert_eq!(
+                policy
+                    .derive_add_collection_context(&resource(leaf_package))
+                    .include,
+                *include,
+                "{}",
+                leaf_package
+            );
+        }
+    }
 }
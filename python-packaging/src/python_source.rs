@@ -4,12 +4,16 @@
 
 /*! Utility functions related to Python source code. */
 
-use {anyhow::Result, once_cell::sync::Lazy};
+use {anyhow::Result, once_cell::sync::Lazy, std::collections::BTreeSet};
 
 static RE_CODING: Lazy<regex::bytes::Regex> = Lazy::new(|| {
     regex::bytes::Regex::new(r"^[ \t\f]*#.*?coding[:=][ \t]*([-_.a-zA-Z0-9]+)").unwrap()
 });
 
+/// Matches argument lists beginning with `sys.executable` followed by `-m` or `-c`.
+static RE_SYS_EXECUTABLE_REINVOCATION: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r#"sys\.executable\s*,\s*[rRuU]?["']-[mc]["']"#).unwrap());
+
 /// Derive the source encoding from Python source code.
 pub fn python_source_encoding(source: &[u8]) -> Vec<u8> {
     // Default source encoding is UTF-8. But per PEP 263, the first or second
@@ -30,11 +34,377 @@ pub fn python_source_encoding(source: &[u8]) -> Vec<u8> {
     b"utf-8".to_vec()
 }
 
+/// Decode Python source code to Unicode using its declared encoding.
+fn decode_python_source(source: &[u8]) -> std::borrow::Cow<'_, str> {
+    let encoding = python_source_encoding(source);
+
+    let encoder = match encoding_rs::Encoding::for_label(&encoding) {
+        Some(encoder) => encoder,
+        None => encoding_rs::UTF_8,
+    };
+
+    encoder.decode(source).0
+}
+
 /// Whether __file__ occurs in Python source code.
 pub fn has_dunder_file(source: &[u8]) -> Result<bool> {
     // We can't just look for b"__file__ because the source file may be in
     // encodings like UTF-16. So we need to decode to Unicode first then look for
     // the code points.
+    Ok(decode_python_source(source).contains("__file__"))
+}
+
+/// Whether Python source code appears to re-invoke `sys.executable` as `python`.
+///
+/// This looks for argument lists like `[sys.executable, "-m", ...]` and
+/// `[sys.executable, "-c", ...]`, which commonly get passed to `subprocess`.
+pub fn has_sys_executable_reinvocation(source: &[u8]) -> Result<bool> {
+    Ok(RE_SYS_EXECUTABLE_REINVOCATION.is_match(&decode_python_source(source)))
+}
+
+/// Find the names of modules imported by Python source code.
+///
+/// This is a line-based textual scan of `import` and `from ... import`
+/// statements, including ones nested in blocks and ones separated by `;`.
+/// Module names are returned as written, in order of appearance. Comments
+/// and lines inside multi-line strings are ignored.
+///
+/// As no parsing is performed, relative imports, imports continued over
+/// multiple lines with `\`, and dynamic imports via `importlib` or
+/// `__import__()` aren't detected. A `#` in a string literal on an import
+/// line is treated as starting a comment.
+fn import_statement_modules(source: &str) -> Vec<&str> {
+    let in_string = lines_starting_in_string(source);
+    let mut modules = vec![];
+
+    for (i, (line, _)) in split_lines(source).into_iter().enumerate() {
+        if in_string[i] {
+            continue;
+        }
+
+        let line = match line.find('#') {
+            Some(pos) => &line[0..pos],
+            None => line,
+        };
+
+        for statement in line.split(';') {
+            let mut words = statement.split_whitespace();
+
+            match words.next() {
+                Some("import") => {
+                    modules.extend(
+                        statement.trim_start()["import".len()..]
+                            .split(',')
+                            .filter_map(|item| item.split_whitespace().next()),
+                    );
+                }
+                Some("from") => {
+                    if let (Some(module), Some("import")) = (words.next(), words.next()) {
+                        if !module.starts_with('.') {
+                            modules.push(module);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    modules
+}
+
+/// Whether Python source code imports a module.
+///
+/// Importing a submodule of `name` counts as importing `name`. See
+/// [import_statement_modules] for the statements recognized.
+pub fn imports_module(source: &[u8], name: &str) -> bool {
+    import_statement_modules(&decode_python_source(source))
+        .into_iter()
+        .any(|module| {
+            module == name || (module.starts_with(name) && module[name.len()..].starts_with('.'))
+        })
+}
+
+/// Resolve the top-level names of modules imported by Python source code.
+///
+/// e.g. `from foo.bar import baz` yields `foo`. See [import_statement_modules]
+/// for the statements recognized.
+pub fn imported_top_level_modules(source: &[u8]) -> BTreeSet<String> {
+    import_statement_modules(&decode_python_source(source))
+        .into_iter()
+        .filter_map(|module| module.split('.').next())
+        .map(|module| module.to_string())
+        .collect()
+}
+
+/// Compute whether each line of Python source begins inside a string literal.
+///
+/// This is a lightweight tokenizer that only understands string literals,
+/// escapes, and comments. It is sufficient to tell whether a line is a
+/// continuation of a multi-line (triple-quoted) string.
+fn lines_starting_in_string(source: &str) -> Vec<bool> {
+    let bytes = source.as_bytes();
+    let mut starts = vec![false];
+    let mut in_string: Option<(u8, bool)> = None;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+
+        match in_string {
+            None => match c {
+                b'#' => {
+                    while i < bytes.len() && bytes[i] != b'\n' {
+                        i += 1;
+                    }
+                    continue;
+                }
+                b'\'' | b'"' => {
+                    let triple = bytes[i..].starts_with(&[c, c, c]);
+                    in_string = Some((c, triple));
+                    i += if triple { 3 } else { 1 };
+                    continue;
+                }
+                b'\n' => starts.push(false),
+                _ => {}
+            },
+            Some((quote, triple)) => {
+                if c == b'\\' && i + 1 < bytes.len() {
+                    if bytes[i + 1] == b'\n' {
+                        starts.push(true);
+                    }
+                    i += 2;
+                    continue;
+                }
+
+                if triple && bytes[i..].starts_with(&[quote, quote, quote]) {
+                    in_string = None;
+                    i += 3;
+                    continue;
+                } else if !triple && c == quote {
+                    in_string = None;
+                } else if c == b'\n' {
+                    // An unterminated single-quoted string is a syntax error.
+                    // Treat the newline as terminating it.
+                    if !triple {
+                        in_string = None;
+                    }
+                    starts.push(triple);
+                }
+            }
+        }
+
+        i += 1;
+    }
+
+    starts
+}
+
+/// Find the offset just past the end of a string literal starting at `start`.
+///
+/// `start` must point at the opening quote character.
+fn string_literal_end(source: &str, start: usize) -> Option<usize> {
+    let bytes = source.as_bytes();
+    let quote = bytes[start];
+    let triple = bytes[start..].starts_with(&[quote, quote, quote]);
+    let mut i = start + if triple { 3 } else { 1 };
+
+    while i < bytes.len() {
+        let c = bytes[i];
+
+        if c == b'\\' {
+            i += 2;
+        } else if triple && bytes[i..].starts_with(&[quote, quote, quote]) {
+            return Some(i + 3);
+        } else if !triple && c == quote {
+            return Some(i + 1);
+        } else if !triple && c == b'\n' {
+            return None;
+        } else {
+            i += 1;
+        }
+    }
+
+    None
+}
+
+/// Split source into lines, retaining line endings separately.
+fn split_lines(source: &str) -> Vec<(&str, &str)> {
+    let mut lines = vec![];
+    let mut start = 0;
+
+    for (i, _) in source.match_indices('\n') {
+        lines.push(&source[start..=i]);
+        start = i + 1;
+    }
+
+    if start < source.len() {
+        lines.push(&source[start..]);
+    }
+
+    lines
+        .into_iter()
+        .map(|line| {
+            if let Some(content) = line.strip_suffix("\r\n") {
+                (content, "\r\n")
+            } else if let Some(content) = line.strip_suffix('\n') {
+                (content, "\n")
+            } else {
+                (line, "")
+            }
+        })
+        .collect()
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Whether a line is a `def` or `class` header that fits on a single line.
+fn is_block_header(line: &str) -> bool {
+    let stripped = line.trim();
+
+    (stripped.starts_with("def ")
+        || stripped.starts_with("async def ")
+        || stripped.starts_with("class "))
+        && stripped.ends_with(':')
+}
+
+/// Remove module, class, and function docstrings from Python source code.
+///
+/// This uses a line-based heuristic rather than a full parser and is
+/// deliberately conservative: a docstring is only removed when it is a plain
+/// or raw/unicode string literal that is the first statement of the module
+/// or of a `def`/`class` whose header fits on a single line, and when
+/// nothing but whitespace or a comment follows the literal. Anything else is
+/// left untouched.
+///
+/// Removed lines are replaced by empty lines so line numbers are preserved.
+/// A function or class docstring is replaced by `pass` so the body remains
+/// valid. Module docstrings are not replaced by `pass` because that would
+/// invalidate a subsequent `from __future__` import.
+pub fn strip_docstrings(source: &str) -> String {
+    let lines = split_lines(source);
+    let in_string = lines_starting_in_string(source);
+
+    // Byte offset of the start of each line.
+    let mut offsets = Vec::with_capacity(lines.len());
+    let mut offset = 0;
+    for (content, ending) in &lines {
+        offsets.push(offset);
+        offset += content.len() + ending.len();
+    }
+
+    // (first line, last line, whether to replace with `pass`).
+    let mut docstrings = vec![];
+    // None when not looking for a docstring. Otherwise the indentation the
+    // docstring must exceed, or None for the module docstring.
+    let mut expecting: Option<Option<usize>> = Some(None);
+
+    let mut i = 0;
+    while i < lines.len() {
+        let (content, _) = lines[i];
+        let stripped = content.trim();
+
+        if in_string[i] || stripped.is_empty() || stripped.starts_with('#') {
+            i += 1;
+            continue;
+        }
+
+        if let Some(min_indent) = expecting.take() {
+            let indent = indentation(content);
+            let candidate = match min_indent {
+                Some(min_indent) => indent > min_indent,
+                None => true,
+            };
+
+            let literal = stripped
+                .strip_prefix(&['r', 'R', 'u', 'U'][..])
+                .unwrap_or(stripped);
+
+            if candidate && (literal.starts_with('"') || literal.starts_with('\'')) {
+                let start = offsets[i] + indent + (stripped.len() - literal.len());
+
+                if let Some(end) = string_literal_end(source, start) {
+                    let last = offsets.iter().rposition(|o| *o < end).unwrap();
+                    let (last_content, _) = lines[last];
+                    let remainder = last_content[end - offsets[last]..].trim();
+
+                    if remainder.is_empty() || remainder.starts_with('#') {
+                        docstrings.push((i, last, min_indent.is_some()));
+                        i = last + 1;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        if is_block_header(content) {
+            expecting = Some(Some(indentation(content)));
+        }
+
+        i += 1;
+    }
+
+    let mut result = String::with_capacity(source.len());
+    let mut docstrings = docstrings.into_iter().peekable();
+
+    for (i, (content, ending)) in lines.iter().enumerate() {
+        while let Some((_, last, _)) = docstrings.peek() {
+            if *last < i {
+                docstrings.next();
+            } else {
+                break;
+            }
+        }
+
+        match docstrings.peek() {
+            Some((first, _, replace)) if *first <= i => {
+                if *first == i && *replace {
+                    result.push_str(&content[0..indentation(content)]);
+                    result.push_str("pass");
+                }
+            }
+            _ => result.push_str(content),
+        }
+
+        result.push_str(ending);
+    }
+
+    result
+}
+
+/// Remove full-line comments from Python source code.
+///
+/// Only lines consisting solely of a comment are affected. A shebang on the
+/// first line and a PEP 263 encoding declaration on the first or second line
+/// are preserved. Comment lines are replaced by empty lines so line numbers
+/// are preserved.
+pub fn strip_comments(source: &str) -> String {
+    let in_string = lines_starting_in_string(source);
+    let mut result = String::with_capacity(source.len());
+
+    for (i, (content, ending)) in split_lines(source).into_iter().enumerate() {
+        let is_comment = !in_string[i] && content.trim_start().starts_with('#');
+        let preserve = (i == 0 && content.starts_with("#!"))
+            || (i < 2 && RE_CODING.is_match(content.as_bytes()));
+
+        if !is_comment || preserve {
+            result.push_str(content);
+        }
+
+        result.push_str(ending);
+    }
+
+    result
+}
+
+/// Remove docstrings and full-line comments from Python source code.
+///
+/// See [strip_docstrings] and [strip_comments] for the heuristics used.
+/// Source that cannot be decoded losslessly, or whose encoding cannot be
+/// written back (e.g. UTF-16), is returned unmodified.
+pub fn strip_docstrings_and_comments(source: &[u8]) -> Vec<u8> {
     let encoding = python_source_encoding(source);
 
     let encoder = match encoding_rs::Encoding::for_label(&encoding) {
@@ -42,7 +412,149 @@ pub fn has_dunder_file(source: &[u8]) -> Result<bool> {
         None => encoding_rs::UTF_8,
     };
 
-    let (source, ..) = encoder.decode(source);
+    if encoder.output_encoding() != encoder {
+        return source.to_vec();
+    }
+
+    let text = match encoder.decode_without_bom_handling_and_without_replacement(source) {
+        Some(text) => text,
+        None => return source.to_vec(),
+    };
+
+    let stripped = strip_comments(&strip_docstrings(&text));
+
+    let (data, _, unmappable) = encoder.encode(&stripped);
 
-    Ok(source.contains("__file__"))
+    if unmappable {
+        source.to_vec()
+    } else {
+        data.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_sys_executable_reinvocation() -> Result<()> {
+        assert!(has_sys_executable_reinvocation(
+            b"subprocess.run([sys.executable, '-m', 'pip', 'install', 'foo'])"
+        )?);
+        assert!(has_sys_executable_reinvocation(
+            b"cmd = [\n    sys.executable,\n    \"-c\",\n    code,\n]"
+        )?);
+
+        assert!(!has_sys_executable_reinvocation(b"print(sys.executable)")?);
+        assert!(!has_sys_executable_reinvocation(
+            b"subprocess.run([sys.executable, script])"
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_imports_module() {
+        assert!(imports_module(b"import numpy\n", "numpy"));
+        assert!(imports_module(b"import numpy as np\n", "numpy"));
+        assert!(imports_module(b"import os, numpy.linalg\n", "numpy"));
+        assert!(imports_module(b"from numpy import array\n", "numpy"));
+        assert!(imports_module(
+            b"from numpy.linalg import (\n    inv,\n)\n",
+            "numpy"
+        ));
+        assert!(imports_module(
+            b"try:\n    import numpy\nexcept ImportError:\n    pass\n",
+            "numpy"
+        ));
+        assert!(imports_module(b"import os; import numpy\n", "numpy"));
+        assert!(imports_module(
+            b"from numpy.linalg import inv\n",
+            "numpy.linalg"
+        ));
+
+        assert!(!imports_module(b"import numpyx\n", "numpy"));
+        assert!(!imports_module(b"import numpy\n", "numpy.linalg"));
+        assert!(!imports_module(b"# import numpy\n", "numpy"));
+        assert!(!imports_module(b"x = 1  # import numpy\n", "numpy"));
+        assert!(!imports_module(b"\"\"\"\nimport numpy\n\"\"\"\n", "numpy"));
+        assert!(!imports_module(b"from . import numpy\n", "numpy"));
+        assert!(!imports_module(b"important = numpy\n", "numpy"));
+        assert!(!imports_module(b"", "numpy"));
+    }
+
+    #[test]
+    fn test_imported_top_level_modules() {
+        assert_eq!(
+            imported_top_level_modules(
+                b"import os, sys as system\n\
+                from numpy.linalg import inv\n\
+                import numpy\n\
+                from . import sibling\n\
+                # import pandas\n"
+            )
+            .into_iter()
+            .collect::<Vec<_>>(),
+            vec!["numpy", "os", "sys"]
+        );
+        assert!(imported_top_level_modules(b"").is_empty());
+    }
+
+    #[test]
+    fn test_strip_docstrings() {
+        assert_eq!(strip_docstrings(""), "");
+        assert_eq!(
+            strip_docstrings(
+                "\"\"\"Module doc.\n\nMore.\n\"\"\"\nfrom __future__ import annotations\n"
+            ),
+            "\n\n\n\nfrom __future__ import annotations\n"
+        );
+        assert_eq!(
+            strip_docstrings("def foo():\n    '''doc'''\n    return 1\n"),
+            "def foo():\n    pass\n    return 1\n"
+        );
+        assert_eq!(
+            strip_docstrings("class Foo:\n    r\"\"\"doc\n    \"\"\"  # comment\n"),
+            "class Foo:\n    pass\n\n"
+        );
+        assert_eq!(
+            strip_docstrings("async def foo():\n\n    # comment\n    \"doc\"\n"),
+            "async def foo():\n\n    # comment\n    pass\n"
+        );
+
+        // Non-docstring strings are left alone.
+        let source = "x = 1\n\"\"\"not a docstring\"\"\"\n";
+        assert_eq!(strip_docstrings(source), source);
+        let source = "def foo():\n    x = '''not a docstring'''\n";
+        assert_eq!(strip_docstrings(source), source);
+        let source = "def foo():\n    \"\"\"doc\"\"\".strip()\n";
+        assert_eq!(strip_docstrings(source), source);
+        let source = "def foo(\n    a,\n):\n    \"\"\"doc\"\"\"\n";
+        assert_eq!(strip_docstrings(source), source);
+        let source = "b'''bytes'''\n";
+        assert_eq!(strip_docstrings(source), source);
+    }
+
+    #[test]
+    fn test_strip_comments() {
+        assert_eq!(
+            strip_comments("#!/usr/bin/env python\n# -*- coding: utf-8 -*-\n# comment\nx = 1  # kept\n  # indented\r\n"),
+            "#!/usr/bin/env python\n# -*- coding: utf-8 -*-\n\nx = 1  # kept\n\r\n"
+        );
+
+        let source = "x = '''\n# in a string\n'''\n";
+        assert_eq!(strip_comments(source), source);
+    }
+
+    #[test]
+    fn test_strip_docstrings_and_comments() {
+        assert_eq!(
+            strip_docstrings_and_comments(b"'''doc'''\n# comment\ndef foo():\n    \"doc\"\n"),
+            b"\n\ndef foo():\n    pass\n".to_vec()
+        );
+
+        // Undecodable source is returned as-is.
+        let invalid = b"# \xff\n".to_vec();
+        assert_eq!(strip_docstrings_and_comments(&invalid), invalid);
+    }
 }
@@ -8,6 +8,7 @@ use {
     crate::{
         bytecode::{CompileMode, PythonBytecodeCompiler},
         module_util::{is_package_from_path, packages_from_module_name, resolve_path_for_module},
+        package_metadata::{parse_entry_points, EntryPoints},
         python_source::has_dunder_file,
     },
     anyhow::{anyhow, Result},
@@ -366,6 +367,29 @@ impl PythonPackageResource {
 
         path
     }
+
+    /// Resolve the filesystem root this resource was discovered under.
+    ///
+    /// This is the inverse of `resolve_path()`: the directory (e.g.
+    /// `site-packages`) containing the resource's package directory. `None`
+    /// is returned if the data isn't backed by a file or the file's path
+    /// doesn't end with the package and relative name.
+    pub fn discovery_root(&self) -> Option<PathBuf> {
+        let path = match &self.data {
+            FileData::Path(path) => path,
+            FileData::Memory(_) => return None,
+        };
+
+        let relative = self.resolve_path("");
+
+        if path.ends_with(&relative) {
+            path.ancestors()
+                .nth(relative.components().count())
+                .map(|p| p.to_path_buf())
+        } else {
+            None
+        }
+    }
 }
 
 /// Represents where a Python package distribution resource is materialized.
@@ -434,6 +458,36 @@ impl PythonPackageDistributionResource {
 
         PathBuf::from(prefix).join(p).join(&self.name)
     }
+
+    /// Classify this resource by its well-known file name.
+    ///
+    /// Returns one of `metadata`, `record`, `wheel`, `entry_points`,
+    /// `top_level`, or `other`.
+    pub fn distribution_file_kind(&self) -> &'static str {
+        match self.name.as_str() {
+            "METADATA" | "PKG-INFO" => "metadata",
+            "RECORD" => "record",
+            "WHEEL" => "wheel",
+            "entry_points.txt" => "entry_points",
+            "top_level.txt" => "top_level",
+            _ => "other",
+        }
+    }
+
+    /// Parse the entry points defined by this resource.
+    ///
+    /// Returns `None` if this isn't an `entry_points.txt` file.
+    pub fn entry_points(&self) -> Result<Option<EntryPoints>> {
+        if self.distribution_file_kind() != "entry_points" {
+            return Ok(None);
+        }
+
+        let data = self.data.resolve_content()?;
+        let text =
+            String::from_utf8(data).map_err(|_| anyhow!("{} is not valid UTF-8", self.name))?;
+
+        Ok(Some(parse_entry_points(&text)?))
+    }
 }
 
 /// Represents a dependency on a library.
@@ -519,6 +573,34 @@ impl TryFrom<&LibraryDependency> for SharedLibrary {
     }
 }
 
+/// Names of libraries providing SSL/TLS and cryptographic primitives.
+///
+/// Extension modules linking against a library with one of these name prefixes
+/// are considered to be built against an SSL library.
+/// Libraries provided by SSL libraries.
+///
+/// `tls` is only provided by LibreSSL.
+const SSL_LIBRARY_NAMES: &[&str] = &["ssl", "crypto", "tls"];
+
+/// Parse the name of a linked SSL library.
+///
+/// Returns the library (e.g. `ssl`) and, for versioned Windows names like
+/// `libssl-1_1`, the version suffix (e.g. `1_1`).
+fn parse_ssl_link_name(name: &str) -> Option<(&str, Option<&str>)> {
+    let name = name.strip_prefix("lib").unwrap_or(name);
+
+    let (library, version) = match name.find('-') {
+        Some(idx) => (&name[0..idx], Some(&name[idx + 1..])),
+        None => (name, None),
+    };
+
+    if SSL_LIBRARY_NAMES.contains(&library) {
+        Some((library, version))
+    } else {
+        None
+    }
+}
+
 /// Represents a Python extension module.
 #[derive(Clone, Debug, PartialEq)]
 pub struct PythonExtensionModule {
@@ -643,6 +725,38 @@ impl PythonExtensionModule {
             &self.name
         }
     }
+
+    /// Whether this extension module links against an SSL library.
+    pub fn links_ssl_library(&self) -> bool {
+        self.link_libraries
+            .iter()
+            .any(|link| parse_ssl_link_name(&link.name).is_some())
+    }
+
+    /// Identify the SSL library this extension module links against.
+    ///
+    /// This is derived from the names of linked libraries. Returns `None` if
+    /// no SSL library is linked, `libressl` if LibreSSL's `tls` library is
+    /// linked, and `openssl` otherwise. Versioned OpenSSL library names, as
+    /// used on Windows, add the version. e.g. `libssl-1_1` gives
+    /// `openssl-1.1`.
+    pub fn ssl_library(&self) -> Option<String> {
+        let links = self
+            .link_libraries
+            .iter()
+            .filter_map(|link| parse_ssl_link_name(&link.name))
+            .collect::<Vec<_>>();
+
+        if links.is_empty() {
+            None
+        } else if links.iter().any(|(library, _)| *library == "tls") {
+            Some("libressl".to_string())
+        } else if let Some(version) = links.iter().find_map(|(_, version)| *version) {
+            Some(format!("openssl-{}", version.replace('_', ".")))
+        } else {
+            Some("openssl".to_string())
+        }
+    }
 }
 
 /// Represents a collection of variants for a given Python extension module.
@@ -678,6 +792,21 @@ impl PythonExtensionModuleVariants {
         self.extensions.iter()
     }
 
+    /// Obtain the names of all variants in this collection.
+    ///
+    /// Extension modules without an explicit variant are reported as `default`.
+    pub fn variant_names(&self) -> Vec<String> {
+        self.extensions
+            .iter()
+            .map(|em| em.variant.clone().unwrap_or_else(|| "default".to_string()))
+            .collect()
+    }
+
+    /// Whether a variant with the given name exists in this collection.
+    pub fn has_variant(&self, variant: &str) -> bool {
+        self.variant_names().iter().any(|v| v == variant)
+    }
+
     /// Obtains the default / first variant of an extension module.
     pub fn default_variant(&self) -> &PythonExtensionModule {
         &self.extensions[0]
@@ -823,6 +952,21 @@ impl<'a> PythonResource<'a> {
             PythonResource::File(f) => f.to_memory()?.into(),
         })
     }
+
+    /// Convert to an instance owning its data.
+    pub fn into_owned(self) -> PythonResource<'static> {
+        match self {
+            PythonResource::ModuleSource(m) => m.into_owned().into(),
+            PythonResource::ModuleBytecode(m) => m.into_owned().into(),
+            PythonResource::ModuleBytecodeRequest(m) => m.into_owned().into(),
+            PythonResource::PackageResource(r) => r.into_owned().into(),
+            PythonResource::PackageDistributionResource(r) => r.into_owned().into(),
+            PythonResource::ExtensionModule(m) => m.into_owned().into(),
+            PythonResource::EggFile(e) => e.into_owned().into(),
+            PythonResource::PathExtension(e) => e.into_owned().into(),
+            PythonResource::File(f) => f.into_owned().into(),
+        }
+    }
 }
 
 impl<'a> From<PythonModuleSource> for PythonResource<'a> {
@@ -967,6 +1111,76 @@ mod tests {
         assert!(!bytecode.is_in_packages(&["bar".to_string()]));
     }
 
+    #[test]
+    fn test_ssl_library() {
+        let extension = |links: &[&str]| PythonExtensionModule {
+            name: "_ssl".to_string(),
+            init_fn: Some("PyInit__ssl".to_string()),
+            extension_file_suffix: ".so".to_string(),
+            shared_library: None,
+            object_file_data: vec![],
+            is_package: false,
+            link_libraries: links
+                .iter()
+                .map(|name| LibraryDependency {
+                    name: name.to_string(),
+                    static_library: None,
+                    static_filename: None,
+                    dynamic_library: None,
+                    dynamic_filename: None,
+                    framework: false,
+                    system: false,
+                })
+                .collect(),
+            is_stdlib: true,
+            builtin_default: false,
+            required: false,
+            variant: Some("not-ssl-related".to_string()),
+            license: None,
+        };
+
+        assert_eq!(extension(&["z"]).ssl_library(), None);
+        assert_eq!(extension(&["sslfoo"]).ssl_library(), None);
+        assert_eq!(
+            extension(&["ssl", "crypto"]).ssl_library(),
+            Some("openssl".to_string())
+        );
+        assert_eq!(
+            extension(&["libssl-1_1", "libcrypto-1_1"]).ssl_library(),
+            Some("openssl-1.1".to_string())
+        );
+        assert_eq!(
+            extension(&["libcrypto-3"]).ssl_library(),
+            Some("openssl-3".to_string())
+        );
+        assert_eq!(
+            extension(&["ssl", "crypto", "tls"]).ssl_library(),
+            Some("libressl".to_string())
+        );
+        assert_eq!(
+            extension(&["libssl-48", "libcrypto-46", "libtls-20"]).ssl_library(),
+            Some("libressl".to_string())
+        );
+    }
+
+    #[test]
+    fn test_package_resource_discovery_root() {
+        let mut r = PythonPackageResource {
+            leaf_package: "foo.bar".to_string(),
+            relative_name: "data/resource.txt".to_string(),
+            data: FileData::Path(PathBuf::from("/site-packages/foo/bar/data/resource.txt")),
+            is_stdlib: false,
+            is_test: false,
+        };
+        assert_eq!(r.discovery_root(), Some(PathBuf::from("/site-packages")));
+
+        r.data = FileData::Path(PathBuf::from("/elsewhere/resource.txt"));
+        assert_eq!(r.discovery_root(), None);
+
+        r.data = FileData::Memory(vec![]);
+        assert_eq!(r.discovery_root(), None);
+    }
+
     #[test]
     fn package_distribution_resources_path_normalization() {
         // Package names are normalized to lowercase and have hyphens replaced
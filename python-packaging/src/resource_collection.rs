@@ -11,8 +11,9 @@ use {
         },
         libpython::LibPythonBuildContext,
         location::{AbstractResourceLocation, ConcreteResourceLocation},
-        module_util::{packages_from_module_name, resolve_path_for_module},
-        python_source::has_dunder_file,
+        module_util::{normalize_module_name, packages_from_module_name, resolve_path_for_module},
+        package_metadata::parse_entry_points,
+        python_source::{has_dunder_file, has_sys_executable_reinvocation},
         resource::{
             BytecodeOptimizationLevel, PythonExtensionModule, PythonModuleBytecode,
             PythonModuleBytecodeFromSource, PythonModuleSource, PythonPackageDistributionResource,
@@ -20,7 +21,9 @@ use {
         },
     },
     anyhow::{anyhow, Context, Result},
-    python_packed_resources::data::{Resource, ResourceFlavor},
+    python_packed_resources::data::{
+        EntryPoint, PackedResourcesLimits, Resource, ResourceField, ResourceFlavor,
+    },
     std::{
         borrow::Cow,
         collections::{BTreeMap, BTreeSet, HashMap},
@@ -86,6 +89,8 @@ pub struct PrePackagedResource {
     pub file_executable: bool,
     pub file_data_embedded: Option<FileData>,
     pub file_data_utf8_relative_path: Option<(PathBuf, FileData)>,
+    pub module_alias_target: Option<String>,
+    pub entry_points: Option<Vec<EntryPoint<'static>>>,
 }
 
 impl PrePackagedResource {
@@ -400,6 +405,11 @@ impl PrePackagedResource {
             } else {
                 None
             },
+            module_alias_target: self
+                .module_alias_target
+                .as_ref()
+                .map(|target| Cow::Owned(target.clone())),
+            entry_points: self.entry_points.clone(),
         };
 
         if let Some((prefix, filename, location)) = &self.relative_path_shared_library {
@@ -410,6 +420,72 @@ impl PrePackagedResource {
     }
 }
 
+/// Index the entry points of package distributions by group.
+///
+/// Entry points are read from the `entry_points.txt` distribution resource
+/// of each package. Groups list entry points ordered by distribution name,
+/// then by entry point name. An entry point having the same name and value
+/// as one already in the group is only listed once.
+///
+/// Files that can't be parsed are skipped: they remain available to
+/// `importlib.metadata` at run-time, which will report their problems.
+pub fn index_entry_points(
+    resources: &BTreeMap<String, PrePackagedResource>,
+) -> Result<BTreeMap<String, Vec<EntryPoint<'static>>>> {
+    let mut groups: BTreeMap<String, Vec<EntryPoint<'static>>> = BTreeMap::new();
+
+    for (name, resource) in resources {
+        let data = if let Some(data) = resource
+            .in_memory_distribution_resources
+            .as_ref()
+            .and_then(|resources| resources.get("entry_points.txt"))
+        {
+            data
+        } else if let Some((_, data)) = resource
+            .relative_path_distribution_resources
+            .as_ref()
+            .and_then(|resources| resources.get("entry_points.txt"))
+        {
+            data
+        } else {
+            continue;
+        };
+
+        let data = data
+            .resolve_content()
+            .with_context(|| format!("reading entry points of {}", name))?;
+
+        let entry_points = match String::from_utf8(data)
+            .map_err(|e| anyhow!(e))
+            .and_then(|text| parse_entry_points(&text))
+        {
+            Ok(entry_points) => entry_points,
+            Err(_) => continue,
+        };
+
+        for (group, entries) in entry_points {
+            for (entry_name, value) in entries {
+                let group = groups.entry(group.clone()).or_default();
+
+                if group
+                    .iter()
+                    .any(|ep| ep.name == entry_name.as_str() && ep.value == value.as_str())
+                {
+                    continue;
+                }
+
+                group.push(EntryPoint {
+                    distribution: Cow::Owned(name.clone()),
+                    name: Cow::Owned(entry_name),
+                    value: Cow::Owned(value),
+                });
+            }
+        }
+    }
+
+    Ok(groups)
+}
+
 /// Fill in missing data on parent packages.
 ///
 /// When resources are added, their parent packages could be missing
@@ -552,6 +628,99 @@ pub fn populate_parent_packages(
     Ok(())
 }
 
+/// Take a value that may be stored in either an in-memory or a relative path field.
+fn take_located<T, R>(
+    in_memory: &mut Option<T>,
+    relative_path: &mut Option<R>,
+    data: impl Fn(R) -> T,
+) -> Option<T> {
+    let relative_path = relative_path.take().map(data);
+
+    in_memory.take().or(relative_path)
+}
+
+/// Remove a named entry from in-memory and relative path resource maps.
+///
+/// Maps left empty are cleared.
+fn remove_located(
+    in_memory: &mut Option<BTreeMap<String, FileData>>,
+    relative_path: &mut Option<BTreeMap<String, (PathBuf, FileData)>>,
+    name: &str,
+) -> Option<FileData> {
+    let relative_path_data = relative_path
+        .as_mut()
+        .and_then(|map| map.remove(name))
+        .map(|(_, data)| data);
+    let in_memory_data = in_memory.as_mut().and_then(|map| map.remove(name));
+
+    if matches!(relative_path, Some(map) if map.is_empty()) {
+        *relative_path = None;
+    }
+    if matches!(in_memory, Some(map) if map.is_empty()) {
+        *in_memory = None;
+    }
+
+    in_memory_data.or(relative_path_data)
+}
+
+/// Compute the size of data a resource contributes to packed resources data.
+fn compiled_resource_size(resource: &Resource<u8>) -> u64 {
+    let fields = [
+        ResourceField::ModuleName,
+        ResourceField::InMemorySource,
+        ResourceField::InMemoryBytecode,
+        ResourceField::InMemoryBytecodeOpt1,
+        ResourceField::InMemoryBytecodeOpt2,
+        ResourceField::InMemoryExtensionModuleSharedLibrary,
+        ResourceField::InMemoryResourcesData,
+        ResourceField::InMemoryDistributionResource,
+        ResourceField::InMemorySharedLibrary,
+        ResourceField::FileDataEmbedded,
+    ];
+
+    fields
+        .iter()
+        .map(|field| resource.field_blob_length(*field) as u64)
+        .sum()
+}
+
+/// Describes when bytecode for a Python module is generated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BytecodeGeneration {
+    /// Bytecode is compiled at build time.
+    Eager,
+
+    /// Bytecode isn't compiled at build time.
+    ///
+    /// The module source is stored and the interpreter compiles it when the
+    /// module is imported.
+    Lazy,
+}
+
+impl TryFrom<&str> for BytecodeGeneration {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "eager" => Ok(Self::Eager),
+            "lazy" => Ok(Self::Lazy),
+            _ => Err(format!(
+                "{} is not a valid bytecode generation mode; use \"eager\" or \"lazy\"",
+                value
+            )),
+        }
+    }
+}
+
+impl AsRef<str> for BytecodeGeneration {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Eager => "eager",
+            Self::Lazy => "lazy",
+        }
+    }
+}
+
 /// Defines how a Python resource should be added to a `PythonResourceCollector`.
 #[derive(Clone, Debug, PartialEq)]
 pub struct PythonResourceAddCollectionContext {
@@ -582,6 +751,12 @@ pub struct PythonResourceAddCollectionContext {
 
     /// Whether to store Python bytecode for optimization level 2.
     pub optimize_level_two: bool,
+
+    /// When bytecode for a `PythonModuleSource` is generated.
+    ///
+    /// `Lazy` stores source regardless of `store_source` and ignores the
+    /// `optimize_level_*` fields.
+    pub bytecode_generation: BytecodeGeneration,
 }
 
 impl PythonResourceAddCollectionContext {
@@ -594,6 +769,7 @@ impl PythonResourceAddCollectionContext {
         self.optimize_level_zero = other.optimize_level_zero;
         self.optimize_level_one = other.optimize_level_one;
         self.optimize_level_two = other.optimize_level_two;
+        self.bytecode_generation = other.bytecode_generation;
     }
 }
 
@@ -621,12 +797,23 @@ pub struct CompiledResourcesCollection<'a> {
 
     /// Extra file installs that must be performed so referenced files are available.
     pub extra_files: Vec<FileInstall>,
+
+    /// Magic number of Python bytecode in this collection.
+    ///
+    /// `None` if the magic number is not known.
+    pub bytecode_magic_number: Option<u32>,
+
+    /// Limits of the packed resources format to enforce when writing.
+    pub limits: PackedResourcesLimits,
 }
 
 impl<'a> CompiledResourcesCollection<'a> {
-    /// Write resources to packed resources data, version 1.
+    /// Write resources to packed resources data.
+    ///
+    /// The bytecode magic number is recorded in the written data so
+    /// consumers can detect bytecode incompatible with the interpreter.
     pub fn write_packed_resources<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
-        python_packed_resources::writer::write_packed_resources_v3(
+        python_packed_resources::writer::write_packed_resources_v4_with_limits(
             &self
                 .resources
                 .values()
@@ -634,8 +821,26 @@ impl<'a> CompiledResourcesCollection<'a> {
                 .collect::<Vec<Resource<'a, u8>>>(),
             writer,
             None,
+            self.bytecode_magic_number.unwrap_or(0),
+            &self.limits,
         )
     }
+
+    /// Verify that bytecode in this collection is compatible with an interpreter.
+    ///
+    /// `expected` is the bytecode magic number of the interpreter that will
+    /// load the resources.
+    pub fn verify_bytecode_magic_number(&self, expected: u32) -> Result<()> {
+        match self.bytecode_magic_number {
+            Some(actual) if actual != expected => Err(anyhow!(
+                "bytecode magic number mismatch: resources were compiled with magic {:#010x} but \
+                the Python interpreter expects {:#010x}; the compiled resources are stale",
+                actual,
+                expected
+            )),
+            _ => Ok(()),
+        }
+    }
 }
 
 /// Type used to collect Python resources so they can be serialized.
@@ -680,6 +885,9 @@ pub struct PythonResourceCollector {
 
     /// Collection of software components which are licensed.
     licensed_components: LicensedComponents,
+
+    /// Limits of the packed resources format resources must fit in.
+    limits: PackedResourcesLimits,
 }
 
 impl PythonResourceCollector {
@@ -705,7 +913,61 @@ impl PythonResourceCollector {
             resources: BTreeMap::new(),
             cache_tag: cache_tag.to_string(),
             licensed_components: LicensedComponents::default(),
+            limits: PackedResourcesLimits::default(),
+        }
+    }
+
+    /// Obtain the packed resources limits resources are validated against.
+    pub fn limits(&self) -> &PackedResourcesLimits {
+        &self.limits
+    }
+
+    /// Set the packed resources limits resources are validated against.
+    ///
+    /// Limits are enforced as resources are added and when resources
+    /// are compiled.
+    pub fn set_limits(&mut self, limits: PackedResourcesLimits) {
+        self.limits = limits;
+    }
+
+    /// Validate that adding a named resource conforms to limits.
+    fn check_resource_limits(&self, name: &str) -> Result<()> {
+        self.limits.check_name(name).map_err(|e| anyhow!(e))?;
+
+        if !self.resources.contains_key(name) {
+            self.limits
+                .check_resource_count(self.resources.len() + 1)
+                .map_err(|e| anyhow!(e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Validate that in-memory data for a resource conforms to limits.
+    ///
+    /// `chunkable` denotes data stored with a `u32` length, which requires
+    /// chunking when it exceeds 4 GiB.
+    fn check_payload_limits(
+        &self,
+        name: &str,
+        what: &str,
+        data: &FileData,
+        chunkable: bool,
+    ) -> Result<()> {
+        // Use file metadata so large files aren't read just to be rejected.
+        let length = match data {
+            FileData::Path(path) => std::fs::metadata(path)
+                .with_context(|| format!("resolving size of {}", path.display()))?
+                .len(),
+            FileData::Memory(data) => data.len() as u64,
+        };
+
+        if chunkable {
+            self.limits.check_chunkable_length(name, what, length)
+        } else {
+            self.limits.check_length(name, what, length)
         }
+        .map_err(|e| anyhow!(e))
     }
 
     /// Obtain locations that resources can be loaded from.
@@ -775,6 +1037,229 @@ impl PythonResourceCollector {
         Box::new(self.resources.iter())
     }
 
+    /// Change the location of the source and bytecode of a collected Python module.
+    ///
+    /// Returns whether the module had source or bytecode to relocate.
+    pub fn relocate_python_module(
+        &mut self,
+        name: &str,
+        location: &ConcreteResourceLocation,
+    ) -> Result<bool> {
+        self.check_policy(location.into())?;
+
+        let entry = match self.resources.get_mut(name) {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+
+        let source = take_located(
+            &mut entry.in_memory_source,
+            &mut entry.relative_path_module_source,
+            |(_, data)| data,
+        );
+        let bytecode = take_located(
+            &mut entry.in_memory_bytecode,
+            &mut entry.relative_path_bytecode,
+            |(_, _, data)| data,
+        );
+        let bytecode_opt1 = take_located(
+            &mut entry.in_memory_bytecode_opt1,
+            &mut entry.relative_path_bytecode_opt1,
+            |(_, _, data)| data,
+        );
+        let bytecode_opt2 = take_located(
+            &mut entry.in_memory_bytecode_opt2,
+            &mut entry.relative_path_bytecode_opt2,
+            |(_, _, data)| data,
+        );
+
+        let relocated = source.is_some()
+            || bytecode.is_some()
+            || bytecode_opt1.is_some()
+            || bytecode_opt2.is_some();
+
+        match location {
+            ConcreteResourceLocation::InMemory => {
+                entry.in_memory_source = source;
+                entry.in_memory_bytecode = bytecode;
+                entry.in_memory_bytecode_opt1 = bytecode_opt1;
+                entry.in_memory_bytecode_opt2 = bytecode_opt2;
+            }
+            ConcreteResourceLocation::RelativePath(prefix) => {
+                let cache_tag = &self.cache_tag;
+
+                entry.relative_path_module_source = source.map(|data| (prefix.clone(), data));
+                entry.relative_path_bytecode =
+                    bytecode.map(|data| (prefix.clone(), cache_tag.clone(), data));
+                entry.relative_path_bytecode_opt1 =
+                    bytecode_opt1.map(|data| (prefix.clone(), cache_tag.clone(), data));
+                entry.relative_path_bytecode_opt2 =
+                    bytecode_opt2.map(|data| (prefix.clone(), cache_tag.clone(), data));
+            }
+        }
+
+        Ok(relocated)
+    }
+
+    /// Change the location of a collected Python package resource.
+    ///
+    /// Returns whether the resource was present.
+    pub fn relocate_python_package_resource(
+        &mut self,
+        package: &str,
+        name: &str,
+        location: &ConcreteResourceLocation,
+    ) -> Result<bool> {
+        self.check_policy(location.into())?;
+
+        let entry = match self.resources.get_mut(package) {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+
+        let data = match remove_located(
+            &mut entry.in_memory_resources,
+            &mut entry.relative_path_package_resources,
+            name,
+        ) {
+            Some(data) => data,
+            None => return Ok(false),
+        };
+
+        match location {
+            ConcreteResourceLocation::InMemory => {
+                entry
+                    .in_memory_resources
+                    .get_or_insert_with(BTreeMap::new)
+                    .insert(name.to_string(), data);
+            }
+            ConcreteResourceLocation::RelativePath(prefix) => {
+                let resource = PythonPackageResource {
+                    leaf_package: package.to_string(),
+                    relative_name: name.to_string(),
+                    data,
+                    is_stdlib: false,
+                    is_test: false,
+                };
+
+                entry
+                    .relative_path_package_resources
+                    .get_or_insert_with(BTreeMap::new)
+                    .insert(
+                        name.to_string(),
+                        (resource.resolve_path(prefix), resource.data),
+                    );
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Remove the source and bytecode of a collected Python module.
+    ///
+    /// Returns whether anything was removed.
+    pub fn remove_python_module(&mut self, name: &str) -> bool {
+        let removed = match self.resources.get_mut(name) {
+            Some(entry) => [
+                entry.in_memory_bytecode.take().is_some(),
+                entry.in_memory_bytecode_opt1.take().is_some(),
+                entry.in_memory_bytecode_opt2.take().is_some(),
+                entry.relative_path_bytecode.take().is_some(),
+                entry.relative_path_bytecode_opt1.take().is_some(),
+                entry.relative_path_bytecode_opt2.take().is_some(),
+            ]
+            .iter()
+            .any(|x| *x),
+            None => false,
+        };
+
+        let removed_source = self.remove_python_module_source(name);
+
+        removed || removed_source
+    }
+
+    /// Remove the source of a collected Python module, retaining its bytecode.
+    ///
+    /// Returns whether source was removed.
+    pub fn remove_python_module_source(&mut self, name: &str) -> bool {
+        let removed = match self.resources.get_mut(name) {
+            Some(entry) => {
+                let in_memory = entry.in_memory_source.take().is_some();
+                let relative_path = entry.relative_path_module_source.take().is_some();
+
+                in_memory || relative_path
+            }
+            None => false,
+        };
+
+        self.remove_if_empty(name);
+
+        removed
+    }
+
+    /// Remove a collected Python package resource.
+    ///
+    /// Returns whether the resource was removed.
+    pub fn remove_python_package_resource(&mut self, package: &str, name: &str) -> bool {
+        let removed = match self.resources.get_mut(package) {
+            Some(entry) => remove_located(
+                &mut entry.in_memory_resources,
+                &mut entry.relative_path_package_resources,
+                name,
+            )
+            .is_some(),
+            None => false,
+        };
+
+        self.remove_if_empty(package);
+
+        removed
+    }
+
+    /// Remove a collected Python package distribution resource.
+    ///
+    /// Returns whether the resource was removed.
+    pub fn remove_python_package_distribution_resource(
+        &mut self,
+        package: &str,
+        name: &str,
+    ) -> bool {
+        let removed = match self.resources.get_mut(package) {
+            Some(entry) => remove_located(
+                &mut entry.in_memory_distribution_resources,
+                &mut entry.relative_path_distribution_resources,
+                name,
+            )
+            .is_some(),
+            None => false,
+        };
+
+        self.remove_if_empty(package);
+
+        removed
+    }
+
+    /// Remove a resource entry if it no longer holds anything.
+    fn remove_if_empty(&mut self, name: &str) {
+        let empty = match self.resources.get(name) {
+            Some(entry) => {
+                *entry
+                    == PrePackagedResource {
+                        name: entry.name.clone(),
+                        is_package: entry.is_package,
+                        is_namespace_package: entry.is_namespace_package,
+                        is_module: entry.is_module,
+                        ..PrePackagedResource::default()
+                    }
+            }
+            None => false,
+        };
+
+        if empty {
+            self.resources.remove(name);
+        }
+    }
+
     /// Generate a summary of licensing information for resources in the collection.
     pub fn generate_license_report(&self) -> Result<ResourcesLicenseReport> {
         let mut report = ResourcesLicenseReport::default();
@@ -839,6 +1324,10 @@ impl PythonResourceCollector {
         location: &ConcreteResourceLocation,
     ) -> Result<()> {
         self.check_policy(location.into())?;
+        self.check_resource_limits(&module.name)?;
+        if location == &ConcreteResourceLocation::InMemory {
+            self.check_payload_limits(&module.name, "source", &module.source, true)?;
+        }
 
         let entry = self
             .resources
@@ -883,7 +1372,9 @@ impl PythonResourceCollector {
             return Ok(());
         }
 
-        if add_context.store_source {
+        let lazy = add_context.bytecode_generation == BytecodeGeneration::Lazy;
+
+        if add_context.store_source || lazy {
             self.add_python_resource_with_locations(
                 &module.into(),
                 &add_context.location,
@@ -891,6 +1382,11 @@ impl PythonResourceCollector {
             )?;
         }
 
+        // The interpreter compiles the stored source when the module is imported.
+        if lazy {
+            return Ok(());
+        }
+
         // Derive bytecode as requested.
         if add_context.optimize_level_zero {
             self.add_python_resource_with_locations(
@@ -932,6 +1428,7 @@ impl PythonResourceCollector {
         location: &ConcreteResourceLocation,
     ) -> Result<()> {
         self.check_policy(location.into())?;
+        self.check_resource_limits(&module.name)?;
 
         let entry = self
             .resources
@@ -1039,6 +1536,7 @@ impl PythonResourceCollector {
         location: &ConcreteResourceLocation,
     ) -> Result<()> {
         self.check_policy(location.into())?;
+        self.check_resource_limits(&module.name)?;
 
         let entry = self
             .resources
@@ -1147,6 +1645,18 @@ impl PythonResourceCollector {
         location: &ConcreteResourceLocation,
     ) -> Result<()> {
         self.check_policy(location.into())?;
+        self.check_resource_limits(&resource.leaf_package)?;
+        self.limits
+            .check_name(&resource.relative_name)
+            .map_err(|e| anyhow!(e))?;
+        if location == &ConcreteResourceLocation::InMemory {
+            self.check_payload_limits(
+                &resource.leaf_package,
+                &format!("package resource {}", resource.relative_name),
+                &resource.data,
+                false,
+            )?;
+        }
 
         let entry = self
             .resources
@@ -1217,6 +1727,18 @@ impl PythonResourceCollector {
         location: &ConcreteResourceLocation,
     ) -> Result<()> {
         self.check_policy(location.into())?;
+        self.check_resource_limits(&resource.package)?;
+        self.limits
+            .check_name(&resource.name)
+            .map_err(|e| anyhow!(e))?;
+        if location == &ConcreteResourceLocation::InMemory {
+            self.check_payload_limits(
+                &resource.package,
+                &format!("distribution resource {}", resource.name),
+                &resource.data,
+                false,
+            )?;
+        }
 
         let entry = self
             .resources
@@ -1473,9 +1995,21 @@ impl PythonResourceCollector {
         location: &ConcreteResourceLocation,
     ) -> Result<()> {
         self.check_policy(location.into())?;
+        self.check_resource_limits(&module.name)?;
 
         let data = match &module.shared_library {
-            Some(location) => location.resolve_content()?,
+            Some(data) => {
+                if location == &ConcreteResourceLocation::InMemory {
+                    self.check_payload_limits(
+                        &module.name,
+                        "extension module shared library",
+                        data,
+                        true,
+                    )?;
+                }
+
+                data.resolve_content()?
+            }
             None => return Err(anyhow!("no shared library data present")),
         };
 
@@ -1561,6 +2095,10 @@ impl PythonResourceCollector {
         location: &ConcreteResourceLocation,
     ) -> Result<()> {
         self.check_policy(location.into())?;
+        self.check_resource_limits(&library.name)?;
+        if location == &ConcreteResourceLocation::InMemory {
+            self.check_payload_limits(&library.name, "shared library", &library.data, false)?;
+        }
 
         let entry = self
             .resources
@@ -1600,6 +2138,15 @@ impl PythonResourceCollector {
         }
 
         self.check_policy(location.into())?;
+        self.check_resource_limits(&file.path_string())?;
+        if location == &ConcreteResourceLocation::InMemory {
+            self.check_payload_limits(
+                &file.path_string(),
+                "file data",
+                file.entry().file_data(),
+                false,
+            )?;
+        }
 
         let entry =
             self.resources
@@ -1753,16 +2300,16 @@ impl PythonResourceCollector {
         }
     }
 
-    /// Searches for Python sources for references to __file__.
-    ///
-    /// __file__ usage can be problematic for in-memory modules. This method searches
-    /// for its occurrences and returns module names having it present.
-    pub fn find_dunder_file(&self) -> Result<BTreeSet<String>> {
+    /// Names of modules whose in-memory source matches a predicate.
+    fn find_in_memory_source(
+        &self,
+        predicate: impl Fn(&[u8]) -> Result<bool>,
+    ) -> Result<BTreeSet<String>> {
         let mut res = BTreeSet::new();
 
         for (name, module) in &self.resources {
             if let Some(location) = &module.in_memory_source {
-                if has_dunder_file(&location.resolve_content()?)? {
+                if predicate(&location.resolve_content()?)? {
                     res.insert(name.clone());
                 }
             }
@@ -1770,7 +2317,7 @@ impl PythonResourceCollector {
             if let Some(PythonModuleBytecodeProvider::FromSource(location)) =
                 &module.in_memory_bytecode
             {
-                if has_dunder_file(&location.resolve_content()?)? {
+                if predicate(&location.resolve_content()?)? {
                     res.insert(name.clone());
                 }
             }
@@ -1778,7 +2325,7 @@ impl PythonResourceCollector {
             if let Some(PythonModuleBytecodeProvider::FromSource(location)) =
                 &module.in_memory_bytecode_opt1
             {
-                if has_dunder_file(&location.resolve_content()?)? {
+                if predicate(&location.resolve_content()?)? {
                     res.insert(name.clone());
                 }
             }
@@ -1786,7 +2333,7 @@ impl PythonResourceCollector {
             if let Some(PythonModuleBytecodeProvider::FromSource(location)) =
                 &module.in_memory_bytecode_opt2
             {
-                if has_dunder_file(&location.resolve_content()?)? {
+                if predicate(&location.resolve_content()?)? {
                     res.insert(name.clone());
                 }
             }
@@ -1795,6 +2342,95 @@ impl PythonResourceCollector {
         Ok(res)
     }
 
+    /// Register an alias for a Python module.
+    ///
+    /// Importing `alias` at run-time yields the module object of `target`,
+    /// so both names refer to the same module in `sys.modules`. Names are
+    /// normalized via [normalize_module_name].
+    ///
+    /// Aliases are validated when resources are compiled: an alias can't
+    /// share its name with a collected Python module and chains of aliases
+    /// can't form a cycle.
+    pub fn add_module_alias(&mut self, alias: &str, target: &str) -> Result<()> {
+        let alias = normalize_module_name(alias).map_err(|e| anyhow!(e))?;
+        let target = normalize_module_name(target).map_err(|e| anyhow!(e))?;
+
+        if alias == target {
+            return Err(anyhow!("module {} cannot be an alias of itself", alias));
+        }
+
+        self.check_resource_limits(&alias)?;
+        self.limits.check_name(&target).map_err(|e| anyhow!(e))?;
+
+        let entry = self
+            .resources
+            .entry(alias.clone())
+            .or_insert_with(|| PrePackagedResource {
+                name: alias,
+                ..PrePackagedResource::default()
+            });
+
+        entry.module_alias_target = Some(target);
+
+        Ok(())
+    }
+
+    /// Validate registered module aliases.
+    fn check_module_aliases(&self) -> Result<()> {
+        for (name, resource) in &self.resources {
+            let mut target = match &resource.module_alias_target {
+                Some(target) => target,
+                None => continue,
+            };
+
+            if resource.is_python_resource() {
+                return Err(anyhow!(
+                    "module alias {} shadows a Python module of the same name",
+                    name
+                ));
+            }
+
+            let mut chain = vec![name.as_str()];
+
+            while let Some(next) = self
+                .resources
+                .get(target)
+                .and_then(|r| r.module_alias_target.as_ref())
+            {
+                if chain.contains(&target.as_str()) {
+                    chain.push(target);
+
+                    return Err(anyhow!(
+                        "module alias {} is part of an alias cycle ({})",
+                        name,
+                        chain.join(" -> ")
+                    ));
+                }
+
+                chain.push(target);
+                target = next;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Searches for Python sources for references to __file__.
+    ///
+    /// __file__ usage can be problematic for in-memory modules. This method searches
+    /// for its occurrences and returns module names having it present.
+    pub fn find_dunder_file(&self) -> Result<BTreeSet<String>> {
+        self.find_in_memory_source(has_dunder_file)
+    }
+
+    /// Searches Python sources for re-invocations of `sys.executable` as `python`.
+    ///
+    /// e.g. `subprocess.run([sys.executable, "-m", "pip"])`. This only works at
+    /// run-time if `sys.executable` accepts `python` arguments.
+    pub fn find_sys_executable_reinvocation(&self) -> Result<BTreeSet<String>> {
+        self.find_in_memory_source(has_sys_executable_reinvocation)
+    }
+
     /// Compiles resources into a finalized collection.
     ///
     /// This will take all resources collected so far and convert them into
@@ -1805,11 +2441,27 @@ impl PythonResourceCollector {
         &self,
         compiler: &mut dyn PythonBytecodeCompiler,
     ) -> Result<CompiledResourcesCollection> {
+        self.check_module_aliases()?;
+
         let mut input_resources = self.resources.clone();
         populate_parent_packages(&mut input_resources).context("populating parent packages")?;
 
+        // Entry points are stored on resources named after their group.
+        for (group, entry_points) in
+            index_entry_points(&input_resources).context("indexing entry points")?
+        {
+            input_resources
+                .entry(group.clone())
+                .or_insert_with(|| PrePackagedResource {
+                    name: group,
+                    ..PrePackagedResource::default()
+                })
+                .entry_points = Some(entry_points);
+        }
+
         let mut resources = BTreeMap::new();
         let mut extra_files = Vec::new();
+        let mut blob_size = 0u64;
 
         for (name, resource) in &input_resources {
             let (entry, installs) = resource
@@ -1820,12 +2472,37 @@ impl PythonResourceCollector {
                 extra_files.push(install);
             }
 
+            // Bytecode sizes are only known after compilation.
+            for (what, data) in &[
+                ("bytecode", &entry.in_memory_bytecode),
+                ("opt1 bytecode", &entry.in_memory_bytecode_opt1),
+                ("opt2 bytecode", &entry.in_memory_bytecode_opt2),
+            ] {
+                if let Some(data) = data {
+                    self.limits
+                        .check_chunkable_length(name, what, data.len() as u64)
+                        .map_err(|e| anyhow!(e))?;
+                }
+            }
+
+            blob_size += compiled_resource_size(&entry);
+            if blob_size > self.limits.max_blob_size {
+                return Err(anyhow!(
+                    "adding {} grows packed resources data to {} bytes, which exceeds the {} byte limit of the target platform",
+                    name,
+                    blob_size,
+                    self.limits.max_blob_size
+                ));
+            }
+
             resources.insert(name.clone(), entry);
         }
 
         Ok(CompiledResourcesCollection {
             resources,
             extra_files,
+            bytecode_magic_number: Some(compiler.get_magic_number()),
+            limits: self.limits,
         })
     }
 }
@@ -3149,6 +3826,7 @@ mod tests {
             optimize_level_zero: false,
             optimize_level_one: false,
             optimize_level_two: false,
+            bytecode_generation: BytecodeGeneration::Eager,
         };
 
         // include=false is a noop.
@@ -3240,20 +3918,36 @@ mod tests {
         r.resources.clear();
         add_context.optimize_level_two = false;
 
-        Ok(())
-    }
+        // Lazy bytecode generation stores source instead of bytecode.
 
-    #[test]
-    fn test_add_in_memory_bytecode_module() -> Result<()> {
-        let mut r = PythonResourceCollector::new(
-            vec![AbstractResourceLocation::InMemory],
-            vec![],
-            false,
-            false,
-            DEFAULT_CACHE_TAG,
-        );
-        r.add_python_module_bytecode(
-            &PythonModuleBytecode::new(
+        add_context.optimize_level_zero = true;
+        add_context.bytecode_generation = BytecodeGeneration::Lazy;
+        r.add_python_module_source_with_context(&module, &add_context)?;
+        assert_eq!(
+            r.resources.get(&module.name),
+            Some(&PrePackagedResource {
+                is_module: true,
+                name: module.name.clone(),
+                is_package: module.is_package,
+                in_memory_source: Some(module.source.clone()),
+                ..PrePackagedResource::default()
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_in_memory_bytecode_module() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![AbstractResourceLocation::InMemory],
+            vec![],
+            false,
+            false,
+            DEFAULT_CACHE_TAG,
+        );
+        r.add_python_module_bytecode(
+            &PythonModuleBytecode::new(
                 "foo",
                 BytecodeOptimizationLevel::Zero,
                 false,
@@ -3377,6 +4071,7 @@ mod tests {
             optimize_level_zero: false,
             optimize_level_one: false,
             optimize_level_two: false,
+            bytecode_generation: BytecodeGeneration::Eager,
         };
 
         // include=false is a noop.
@@ -3575,6 +4270,7 @@ mod tests {
             optimize_level_zero: false,
             optimize_level_one: false,
             optimize_level_two: false,
+            bytecode_generation: BytecodeGeneration::Eager,
         };
 
         // include=false is a noop.
@@ -3835,6 +4531,7 @@ mod tests {
             optimize_level_zero: false,
             optimize_level_one: false,
             optimize_level_two: false,
+            bytecode_generation: BytecodeGeneration::Eager,
         };
 
         // include=false is a noop.
@@ -4063,6 +4760,7 @@ mod tests {
             optimize_level_zero: false,
             optimize_level_one: false,
             optimize_level_two: false,
+            bytecode_generation: BytecodeGeneration::Eager,
         };
 
         // include=false is a noop.
@@ -4598,6 +5296,7 @@ mod tests {
             optimize_level_zero: false,
             optimize_level_one: false,
             optimize_level_two: false,
+            bytecode_generation: BytecodeGeneration::Eager,
         };
 
         // include=false is a noop.
@@ -4697,4 +5396,523 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_find_sys_executable_reinvocation() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![AbstractResourceLocation::InMemory],
+            vec![],
+            false,
+            false,
+            DEFAULT_CACHE_TAG,
+        );
+
+        for (name, source) in &[
+            ("foo", "import sys; print(sys.executable)"),
+            (
+                "bar",
+                "subprocess.check_call([sys.executable, '-m', 'pip'])",
+            ),
+        ] {
+            r.add_python_module_source(
+                &PythonModuleSource {
+                    name: name.to_string(),
+                    source: FileData::Memory(source.as_bytes().to_vec()),
+                    is_package: false,
+                    cache_tag: DEFAULT_CACHE_TAG.to_string(),
+                    is_stdlib: false,
+                    is_test: false,
+                },
+                &ConcreteResourceLocation::InMemory,
+            )?;
+        }
+
+        let names = r.find_sys_executable_reinvocation()?;
+        assert_eq!(names.len(), 1);
+        assert!(names.contains("bar"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compiled_bytecode_magic_number() -> Result<()> {
+        let r = PythonResourceCollector::new(
+            vec![AbstractResourceLocation::InMemory],
+            vec![],
+            false,
+            false,
+            DEFAULT_CACHE_TAG,
+        );
+
+        let mut compiler = FakeBytecodeCompiler { magic_number: 42 };
+        let resources = r.compile_resources(&mut compiler)?;
+        assert_eq!(resources.bytecode_magic_number, Some(42));
+        resources.verify_bytecode_magic_number(42)?;
+        assert!(resources.verify_bytecode_magic_number(43).is_err());
+
+        let mut data = Vec::new();
+        resources.write_packed_resources(&mut data)?;
+        assert_eq!(
+            python_packed_resources::parser::read_bytecode_magic_number(&data),
+            Ok(Some(42))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relocate_python_module() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![
+                AbstractResourceLocation::InMemory,
+                AbstractResourceLocation::RelativePath,
+            ],
+            vec![],
+            false,
+            false,
+            DEFAULT_CACHE_TAG,
+        );
+
+        let module = PythonModuleSource {
+            name: "foo".to_string(),
+            source: FileData::Memory(vec![42]),
+            is_package: false,
+            cache_tag: DEFAULT_CACHE_TAG.to_string(),
+            is_stdlib: false,
+            is_test: false,
+        };
+        r.add_python_module_source(&module, &ConcreteResourceLocation::InMemory)?;
+        r.add_python_module_bytecode_from_source(
+            &module.as_bytecode_module(BytecodeOptimizationLevel::One),
+            &ConcreteResourceLocation::InMemory,
+        )?;
+
+        assert!(r.relocate_python_module(
+            "foo",
+            &ConcreteResourceLocation::RelativePath("lib".to_string())
+        )?);
+        assert!(!r.relocate_python_module("missing", &ConcreteResourceLocation::InMemory)?);
+
+        assert_eq!(
+            r.resources.get("foo"),
+            Some(&PrePackagedResource {
+                name: "foo".to_string(),
+                is_module: true,
+                relative_path_module_source: Some(("lib".to_string(), FileData::Memory(vec![42]))),
+                relative_path_bytecode_opt1: Some((
+                    "lib".to_string(),
+                    DEFAULT_CACHE_TAG.to_string(),
+                    PythonModuleBytecodeProvider::FromSource(FileData::Memory(vec![42]))
+                )),
+                ..PrePackagedResource::default()
+            })
+        );
+
+        assert!(r.relocate_python_module("foo", &ConcreteResourceLocation::InMemory)?);
+        assert_eq!(
+            r.resources.get("foo"),
+            Some(&PrePackagedResource {
+                name: "foo".to_string(),
+                is_module: true,
+                in_memory_source: Some(FileData::Memory(vec![42])),
+                in_memory_bytecode_opt1: Some(PythonModuleBytecodeProvider::FromSource(
+                    FileData::Memory(vec![42])
+                )),
+                ..PrePackagedResource::default()
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relocate_python_package_resource() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![
+                AbstractResourceLocation::InMemory,
+                AbstractResourceLocation::RelativePath,
+            ],
+            vec![],
+            false,
+            false,
+            DEFAULT_CACHE_TAG,
+        );
+
+        r.add_python_package_resource(
+            &PythonPackageResource {
+                leaf_package: "foo.bar".to_string(),
+                relative_name: "resource.txt".to_string(),
+                data: FileData::Memory(vec![42]),
+                is_stdlib: false,
+                is_test: false,
+            },
+            &ConcreteResourceLocation::InMemory,
+        )?;
+
+        assert!(r.relocate_python_package_resource(
+            "foo.bar",
+            "resource.txt",
+            &ConcreteResourceLocation::RelativePath("lib".to_string())
+        )?);
+        assert!(!r.relocate_python_package_resource(
+            "foo.bar",
+            "missing.txt",
+            &ConcreteResourceLocation::InMemory
+        )?);
+
+        let entry = r.resources.get("foo.bar").unwrap();
+        assert_eq!(entry.in_memory_resources, None);
+        assert_eq!(
+            entry.relative_path_package_resources,
+            Some(
+                [(
+                    "resource.txt".to_string(),
+                    (
+                        PathBuf::from("lib/foo/bar/resource.txt"),
+                        FileData::Memory(vec![42])
+                    )
+                )]
+                .iter()
+                .cloned()
+                .collect()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_python_module() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![AbstractResourceLocation::InMemory],
+            vec![],
+            false,
+            false,
+            DEFAULT_CACHE_TAG,
+        );
+
+        let module = PythonModuleSource {
+            name: "foo".to_string(),
+            source: FileData::Memory(vec![42]),
+            is_package: true,
+            cache_tag: DEFAULT_CACHE_TAG.to_string(),
+            is_stdlib: false,
+            is_test: false,
+        };
+        r.add_python_module_source(&module, &ConcreteResourceLocation::InMemory)?;
+        r.add_python_module_bytecode_from_source(
+            &module.as_bytecode_module(BytecodeOptimizationLevel::Zero),
+            &ConcreteResourceLocation::InMemory,
+        )?;
+        r.add_python_package_resource(
+            &PythonPackageResource {
+                leaf_package: "foo".to_string(),
+                relative_name: "resource.txt".to_string(),
+                data: FileData::Memory(vec![42]),
+                is_stdlib: false,
+                is_test: false,
+            },
+            &ConcreteResourceLocation::InMemory,
+        )?;
+
+        // Removing source retains bytecode.
+        assert!(r.remove_python_module_source("foo"));
+        assert!(!r.remove_python_module_source("foo"));
+        let entry = r.resources.get("foo").unwrap();
+        assert!(entry.in_memory_source.is_none());
+        assert!(entry.in_memory_bytecode.is_some());
+
+        // Entries holding other resources are retained.
+        assert!(r.remove_python_module("foo"));
+        assert!(r.resources.get("foo").unwrap().in_memory_bytecode.is_none());
+
+        // Entries are removed once empty.
+        assert!(r.remove_python_package_resource("foo", "resource.txt"));
+        assert!(!r.resources.contains_key("foo"));
+        assert!(!r.remove_python_module("foo"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_limits_sparse_file() -> Result<()> {
+        // A sparse file lets us exercise limits without a multi-GiB fixture.
+        let temp_dir = tempfile::Builder::new()
+            .prefix("python-packaging-test")
+            .tempdir()?;
+        let path = temp_dir.path().join("large.bin");
+        std::fs::File::create(&path)?.set_len(5 * 1024 * 1024 * 1024)?;
+
+        let mut r = PythonResourceCollector::new(
+            vec![
+                AbstractResourceLocation::InMemory,
+                AbstractResourceLocation::RelativePath,
+            ],
+            vec![],
+            false,
+            false,
+            DEFAULT_CACHE_TAG,
+        );
+
+        let module = PythonModuleSource {
+            name: "large".to_string(),
+            source: FileData::Path(path.clone()),
+            is_package: false,
+            cache_tag: DEFAULT_CACHE_TAG.to_string(),
+            is_stdlib: false,
+            is_test: false,
+        };
+
+        let err = r
+            .add_python_module_source(&module, &ConcreteResourceLocation::InMemory)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("source of large is 5368709120 bytes"),
+            "{}",
+            err
+        );
+        assert!(err.contains("enable chunked resources"), "{}", err);
+        assert!(!r.resources.contains_key("large"));
+
+        // Filesystem-relative data isn't subject to payload limits.
+        r.add_python_module_source(
+            &module,
+            &ConcreteResourceLocation::RelativePath("lib".to_string()),
+        )?;
+
+        let resource = PythonPackageResource {
+            leaf_package: "pkg".to_string(),
+            relative_name: "model.bin".to_string(),
+            data: FileData::Path(path.clone()),
+            is_stdlib: false,
+            is_test: false,
+        };
+
+        // Package resources have 64-bit lengths and don't require chunking.
+        r.add_python_package_resource(&resource, &ConcreteResourceLocation::InMemory)?;
+
+        r.set_limits(PackedResourcesLimits {
+            allow_chunking: true,
+            ..PackedResourcesLimits::default()
+        });
+        r.add_python_module_source(&module, &ConcreteResourceLocation::InMemory)?;
+
+        // 32-bit platforms can't map the data.
+        let mut r = PythonResourceCollector::new(
+            vec![AbstractResourceLocation::InMemory],
+            vec![],
+            false,
+            false,
+            DEFAULT_CACHE_TAG,
+        );
+        r.set_limits(PackedResourcesLimits {
+            allow_chunking: true,
+            ..PackedResourcesLimits::for_pointer_width(32)
+        });
+
+        let err = r
+            .add_python_package_resource(&resource, &ConcreteResourceLocation::InMemory)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "package resource model.bin of pkg is 5368709120 bytes, which exceeds the 2147483647 byte limit of the target platform"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_entry_points() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![
+                AbstractResourceLocation::InMemory,
+                AbstractResourceLocation::RelativePath,
+            ],
+            vec![],
+            false,
+            false,
+            DEFAULT_CACHE_TAG,
+        );
+
+        for (package, location, data) in &[
+            (
+                "myapp_foo",
+                ConcreteResourceLocation::InMemory,
+                "[myapp.plugins]\nfoo = myapp_foo.plugin\n[console_scripts]\nfoo = myapp_foo:main\n",
+            ),
+            (
+                "myapp_bar",
+                ConcreteResourceLocation::RelativePath("lib".to_string()),
+                "[myapp.plugins]\nbar = myapp_bar:Plugin\nfoo = myapp_foo.plugin\n",
+            ),
+            ("broken", ConcreteResourceLocation::InMemory, "not ini\n"),
+        ] {
+            r.add_python_package_distribution_resource(
+                &PythonPackageDistributionResource {
+                    location: PythonPackageDistributionResourceFlavor::DistInfo,
+                    package: package.to_string(),
+                    version: "1.0".to_string(),
+                    name: "entry_points.txt".to_string(),
+                    data: FileData::Memory(data.as_bytes().to_vec()),
+                },
+                location,
+            )?;
+        }
+
+        let mut compiler = FakeBytecodeCompiler { magic_number: 42 };
+        let resources = r.compile_resources(&mut compiler)?;
+
+        // Distributions are ordered by name and duplicates are dropped.
+        assert_eq!(
+            resources.resources.get("myapp.plugins"),
+            Some(&Resource {
+                name: Cow::Owned("myapp.plugins".to_string()),
+                entry_points: Some(vec![
+                    EntryPoint {
+                        distribution: Cow::Owned("myapp_bar".to_string()),
+                        name: Cow::Owned("bar".to_string()),
+                        value: Cow::Owned("myapp_bar:Plugin".to_string()),
+                    },
+                    EntryPoint {
+                        distribution: Cow::Owned("myapp_bar".to_string()),
+                        name: Cow::Owned("foo".to_string()),
+                        value: Cow::Owned("myapp_foo.plugin".to_string()),
+                    },
+                ]),
+                ..Resource::default()
+            })
+        );
+        assert_eq!(
+            resources.resources["console_scripts"]
+                .entry_points
+                .as_ref()
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(resources.resources["myapp_foo"].entry_points.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_module_alias() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![AbstractResourceLocation::InMemory],
+            vec![],
+            false,
+            false,
+            DEFAULT_CACHE_TAG,
+        );
+        r.add_python_module_source(
+            &PythonModuleSource {
+                name: "yaml".to_string(),
+                source: FileData::Memory(vec![]),
+                is_package: false,
+                cache_tag: DEFAULT_CACHE_TAG.to_string(),
+                is_stdlib: false,
+                is_test: false,
+            },
+            &ConcreteResourceLocation::InMemory,
+        )?;
+        r.add_module_alias("ruamel_yaml_shim.__init__", " yaml")?;
+
+        assert!(r.add_module_alias("foo", "foo").is_err());
+        assert!(r.add_module_alias("foo..bar", "yaml").is_err());
+
+        let mut compiler = FakeBytecodeCompiler { magic_number: 42 };
+        let resources = r.compile_resources(&mut compiler)?;
+        assert_eq!(
+            resources.resources.get("ruamel_yaml_shim"),
+            Some(&Resource {
+                name: Cow::Owned("ruamel_yaml_shim".to_string()),
+                module_alias_target: Some(Cow::Owned("yaml".to_string())),
+                ..Resource::default()
+            })
+        );
+
+        // Aliases can't shadow modules.
+        let mut shadow = r.clone();
+        shadow.add_module_alias("yaml", "json")?;
+        let err = shadow.compile_resources(&mut compiler).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "module alias yaml shadows a Python module of the same name"
+        );
+
+        // Chains are allowed. Cycles aren't.
+        r.add_module_alias("a", "b")?;
+        r.add_module_alias("b", "ruamel_yaml_shim")?;
+        r.compile_resources(&mut compiler)?;
+
+        r.add_module_alias("c", "d")?;
+        r.add_module_alias("d", "c")?;
+        let err = r.compile_resources(&mut compiler).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "module alias c is part of an alias cycle (c -> d -> c)"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_limits_names_and_counts() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![AbstractResourceLocation::InMemory],
+            vec![],
+            false,
+            false,
+            DEFAULT_CACHE_TAG,
+        );
+        r.set_limits(PackedResourcesLimits {
+            max_name_length: 8,
+            max_resource_count: 2,
+            max_blob_size: 12,
+            ..PackedResourcesLimits::default()
+        });
+
+        let module = |name: &str, source: &[u8]| PythonModuleSource {
+            name: name.to_string(),
+            source: FileData::Memory(source.to_vec()),
+            is_package: false,
+            cache_tag: DEFAULT_CACHE_TAG.to_string(),
+            is_stdlib: false,
+            is_test: false,
+        };
+
+        let err = r
+            .add_python_module_source(
+                &module("very_long_name", b""),
+                &ConcreteResourceLocation::InMemory,
+            )
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "name of very_long_name is 14 bytes, which exceeds the 8 byte limit"
+        );
+
+        r.add_python_module_source(&module("a", b"12345"), &ConcreteResourceLocation::InMemory)?;
+        r.add_python_module_source(&module("b", b"12345"), &ConcreteResourceLocation::InMemory)?;
+        // Replacing an existing resource doesn't count against the limit.
+        r.add_python_module_source(&module("b", b"123456"), &ConcreteResourceLocation::InMemory)?;
+
+        let err = r
+            .add_python_module_source(&module("c", b""), &ConcreteResourceLocation::InMemory)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "3 resources exceeds the limit of 2 resources");
+
+        let mut compiler = FakeBytecodeCompiler { magic_number: 42 };
+        let err = r.compile_resources(&mut compiler).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "adding b grows packed resources data to 13 bytes, which exceeds the 12 byte limit of the target platform"
+        );
+
+        Ok(())
+    }
 }
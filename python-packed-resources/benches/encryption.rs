@@ -0,0 +1,91 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Benchmarks for encrypted resources payloads.
+
+These quantify the per-import overhead of encrypted resources: `copy_*`
+is the cost of materializing a plaintext payload and `decrypt_*` is the
+cost of materializing an encrypted one. `first_decrypt_*` additionally
+includes the one-time key agreement performed for a new decryptor.
+*/
+
+use {
+    criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput},
+    python_packed_resources::{
+        data::ResourceField,
+        encryption::{
+            decrypted_payload_length, EncryptionScheme, ResourcesDecryptor, ResourcesEncryptor,
+        },
+    },
+    x25519_dalek::{PublicKey, StaticSecret},
+};
+
+/// Fixed secret key so every run performs identical work.
+const SECRET_KEY: [u8; 32] = [0x42; 32];
+
+/// Deterministic payload resembling bytecode.
+fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+fn bench_encryption(c: &mut Criterion) {
+    let public = PublicKey::from(&StaticSecret::from(SECRET_KEY)).to_bytes();
+    let encryptor = ResourcesEncryptor::new(EncryptionScheme::XChaCha20Poly1305, &public).unwrap();
+
+    let mut group = c.benchmark_group("encryption");
+
+    for len in &[4096usize, 65536] {
+        let plaintext = payload(*len);
+        let encrypted = encryptor
+            .encrypt_payload("foo.bar", ResourceField::InMemoryBytecode, &plaintext)
+            .unwrap();
+        assert_eq!(decrypted_payload_length(&encrypted), Some(*len));
+
+        group.throughput(Throughput::Bytes(*len as u64));
+
+        group.bench_function(format!("copy_{}", len), |b| {
+            b.iter(|| black_box(&plaintext).to_vec())
+        });
+
+        let decryptor = ResourcesDecryptor::new(&SECRET_KEY).unwrap();
+
+        group.bench_function(format!("decrypt_{}", len), |b| {
+            b.iter(|| {
+                let mut dest = vec![0; *len];
+                decryptor
+                    .decrypt_payload(
+                        "foo.bar",
+                        ResourceField::InMemoryBytecode,
+                        black_box(&encrypted),
+                        &mut dest,
+                    )
+                    .unwrap();
+                dest
+            })
+        });
+
+        if *len == 4096 {
+            group.bench_function("first_decrypt_4096", |b| {
+                b.iter(|| {
+                    let decryptor = ResourcesDecryptor::new(&SECRET_KEY).unwrap();
+                    let mut dest = vec![0; *len];
+                    decryptor
+                        .decrypt_payload(
+                            "foo.bar",
+                            ResourceField::InMemoryBytecode,
+                            black_box(&encrypted),
+                            &mut dest,
+                        )
+                        .unwrap();
+                    dest
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_encryption);
+criterion_main!(benches);
@@ -0,0 +1,186 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Benchmarks for packed resources serialization, parsing, and lookup.
+
+Resources are generated deterministically from a fixed seed so results
+are comparable across runs. See `scripts/check-benchmark-regressions.py`
+for comparing results between commits.
+*/
+
+use {
+    criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput},
+    python_packed_resources::{
+        data::{Resource, ResourceFlavor},
+        parser::load_resources,
+        writer::write_packed_resources_v4,
+    },
+    std::{borrow::Cow, collections::HashMap},
+};
+
+/// Number of Python modules in generated resources data.
+const MODULE_COUNT: usize = 5000;
+
+/// Number of modules belonging to each generated package.
+const MODULES_PER_PACKAGE: usize = 50;
+
+/// Bytecode magic number recorded in generated data. Python 3.9.
+const BYTECODE_MAGIC: u32 = 0x0a0d_0d61;
+
+/// Seed for the pseudo-random number generator driving data generation.
+const SEED: u64 = 0x5eed_5eed_5eed_5eed;
+
+/// Minimal xorshift pseudo-random number generator.
+///
+/// We don't need statistical quality: just deterministic, varied data.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Generate resources for a synthetic tree of packages and modules.
+fn generate_resources() -> Vec<Resource<'static, u8>> {
+    let mut rng = XorShift(SEED);
+    let mut resources = Vec::with_capacity(MODULE_COUNT + MODULE_COUNT / MODULES_PER_PACKAGE);
+
+    for i in 0..MODULE_COUNT {
+        let package = format!("pkg{:03}", i / MODULES_PER_PACKAGE);
+
+        if i % MODULES_PER_PACKAGE == 0 {
+            resources.push(Resource {
+                flavor: ResourceFlavor::Module,
+                name: Cow::Owned(package.clone()),
+                is_module: true,
+                is_package: true,
+                in_memory_source: Some(Cow::Owned(b"\"\"\"Package.\"\"\"\n".to_vec())),
+                in_memory_bytecode: Some(Cow::Owned(vec![0x42; 64])),
+                ..Resource::default()
+            });
+        }
+
+        let source_len = 256 + (rng.next() % 4096) as usize;
+        let bytecode_len = source_len / 2 + (rng.next() % 512) as usize;
+
+        resources.push(Resource {
+            flavor: ResourceFlavor::Module,
+            name: Cow::Owned(format!("{}.mod{:04}", package, i)),
+            is_module: true,
+            in_memory_source: Some(Cow::Owned(vec![b'#'; source_len])),
+            in_memory_bytecode: Some(Cow::Owned(vec![0x42; bytecode_len])),
+            ..Resource::default()
+        });
+    }
+
+    resources
+}
+
+/// Serialize resources to packed resources data.
+fn serialize(resources: &[Resource<u8>]) -> Vec<u8> {
+    let mut data = Vec::new();
+    write_packed_resources_v4(resources, &mut data, None, BYTECODE_MAGIC).unwrap();
+
+    data
+}
+
+/// Index parsed resources by name, like the Rust importer does.
+fn index(data: &[u8]) -> HashMap<Cow<'_, str>, Resource<'_, u8>> {
+    let resources = load_resources(data).unwrap();
+
+    let mut index = HashMap::with_capacity(resources.expected_resources_count());
+    for resource in resources {
+        let resource = resource.unwrap();
+        index.insert(resource.name.clone(), resource);
+    }
+
+    index
+}
+
+/// Generate names to look up, including misses and `__init__` aliases.
+fn lookup_names() -> Vec<String> {
+    let mut rng = XorShift(SEED);
+
+    (0..1000)
+        .map(|_| {
+            let i = (rng.next() % MODULE_COUNT as u64) as usize;
+            let package = format!("pkg{:03}", i / MODULES_PER_PACKAGE);
+
+            match rng.next() % 4 {
+                0 => format!("{}.__init__", package),
+                1 => format!("{}.missing{:04}", package, i),
+                _ => format!("{}.mod{:04}", package, i),
+            }
+        })
+        .collect()
+}
+
+fn bench_packed_resources(c: &mut Criterion) {
+    let resources = generate_resources();
+    let data = serialize(&resources);
+
+    let mut group = c.benchmark_group("packed_resources");
+    group.throughput(Throughput::Elements(resources.len() as u64));
+
+    group.bench_function("write_5000", |b| {
+        b.iter_batched_ref(
+            || Vec::with_capacity(data.len()),
+            |dest| {
+                write_packed_resources_v4(black_box(&resources), dest, None, BYTECODE_MAGIC)
+                    .unwrap()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function("parse_5000", |b| {
+        b.iter(|| {
+            for resource in load_resources(black_box(&data)).unwrap() {
+                black_box(resource.unwrap());
+            }
+        })
+    });
+
+    group.bench_function("index_5000", |b| b.iter(|| index(black_box(&data))));
+
+    group.finish();
+}
+
+fn bench_name_lookup(c: &mut Criterion) {
+    let resources = generate_resources();
+    let data = serialize(&resources);
+    let index = index(&data);
+    let names = lookup_names();
+
+    let mut group = c.benchmark_group("finder");
+    group.throughput(Throughput::Elements(names.len() as u64));
+
+    group.bench_function("lookup_1000", |b| {
+        b.iter(|| {
+            let mut found = 0;
+
+            for name in names.iter().map(String::as_str) {
+                // Mirror the importer's aliasing of `foo.__init__` to `foo`.
+                let name = name.strip_suffix(".__init__").unwrap_or(name);
+
+                if let Some(resource) = index.get(black_box(name)) {
+                    if resource.is_module {
+                        found += 1;
+                    }
+                }
+            }
+
+            found
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_packed_resources, bench_name_lookup);
+criterion_main!(benches);
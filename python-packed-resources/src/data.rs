@@ -9,6 +9,13 @@ use std::{borrow::Cow, collections::HashMap, convert::TryFrom, path::Path};
 /// Header value for version 2 of resources payload.
 pub const HEADER_V3: &[u8] = b"pyembed\x03";
 
+/// Header value for version 4 of resources payload.
+///
+/// Version 4 is identical to version 3 except the header is followed by a
+/// little endian u32 holding the Python bytecode magic number of bytecode
+/// in the payload. A value of `0` means the magic number is not known.
+pub const HEADER_V4: &[u8] = b"pyembed\x04";
+
 /// Defines the type of a resource.
 ///
 /// This is deprecated in favor of individual boolean fields on resources
@@ -156,6 +163,9 @@ pub enum ResourceField {
     FileExecutable = 0x1c,
     FileDataEmbedded = 0x1d,
     FileDataUtf8RelativePath = 0x1e,
+    ChunkedPayload = 0x1f,
+    ModuleAliasTarget = 0x20,
+    EntryPoints = 0x21,
 }
 
 impl From<ResourceField> for u8 {
@@ -192,6 +202,9 @@ impl From<ResourceField> for u8 {
             ResourceField::FileExecutable => 0x1c,
             ResourceField::FileDataEmbedded => 0x1d,
             ResourceField::FileDataUtf8RelativePath => 0x1e,
+            ResourceField::ChunkedPayload => 0x1f,
+            ResourceField::ModuleAliasTarget => 0x20,
+            ResourceField::EntryPoints => 0x21,
             ResourceField::EndOfEntry => 0xff,
         }
     }
@@ -233,12 +246,160 @@ impl TryFrom<u8> for ResourceField {
             0x1c => Ok(ResourceField::FileExecutable),
             0x1d => Ok(ResourceField::FileDataEmbedded),
             0x1e => Ok(ResourceField::FileDataUtf8RelativePath),
+            0x1f => Ok(ResourceField::ChunkedPayload),
+            0x20 => Ok(ResourceField::ModuleAliasTarget),
+            0x21 => Ok(ResourceField::EntryPoints),
             0xff => Ok(ResourceField::EndOfEntry),
             _ => Err("invalid field type"),
         }
     }
 }
 
+/// Maximum length of an individual chunk of a chunked payload.
+///
+/// This is the largest value a `u32` length field in the resources index
+/// can hold.
+pub const MAX_CHUNK_LENGTH: u64 = u32::MAX as u64;
+
+/// Limits of the packed resources format and of the platform reading it.
+///
+/// Writers and producers of resources consult these limits to reject
+/// data that can't be represented instead of silently truncating it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PackedResourcesLimits {
+    /// Maximum length in bytes of a resource name.
+    pub max_name_length: usize,
+
+    /// Maximum number of resources in a single payload.
+    pub max_resource_count: usize,
+
+    /// Maximum size in bytes of all data in a single payload.
+    ///
+    /// This is bounded by the amount of memory the reading process can
+    /// address.
+    pub max_blob_size: u64,
+
+    /// Whether payloads too large for a `u32` length can be split into
+    /// chunks.
+    pub allow_chunking: bool,
+
+    /// Maximum length in bytes of an individual chunk.
+    pub max_chunk_length: u64,
+}
+
+impl Default for PackedResourcesLimits {
+    fn default() -> Self {
+        Self {
+            max_name_length: u16::MAX as usize,
+            max_resource_count: u32::MAX as usize,
+            max_blob_size: u64::MAX,
+            allow_chunking: false,
+            max_chunk_length: MAX_CHUNK_LENGTH,
+        }
+    }
+}
+
+impl PackedResourcesLimits {
+    /// Obtain limits for a reader with the given pointer width in bits.
+    ///
+    /// 32-bit processes can't map more than 2 GiB of data.
+    pub fn for_pointer_width(bits: u32) -> Self {
+        Self {
+            max_blob_size: if bits <= 32 {
+                i32::MAX as u64
+            } else {
+                u64::MAX
+            },
+            ..Self::default()
+        }
+    }
+
+    /// Whether a payload of the given length must be stored in chunks.
+    pub fn requires_chunking(&self, length: u64) -> bool {
+        length > self.max_chunk_length.min(MAX_CHUNK_LENGTH)
+    }
+
+    /// Validate that a payload with a `u32` length field can be stored.
+    ///
+    /// `name` and `what` are used to construct error messages.
+    pub fn check_chunkable_length(
+        &self,
+        name: &str,
+        what: &str,
+        length: u64,
+    ) -> Result<(), String> {
+        if self.allow_chunking || length <= MAX_CHUNK_LENGTH {
+            self.check_length(name, what, length)
+        } else {
+            Err(format!(
+                "{} of {} is {} bytes, which exceeds the {} byte limit of packed resources; enable chunked resources to store it",
+                what, name, length, MAX_CHUNK_LENGTH
+            ))
+        }
+    }
+
+    /// Validate that a payload of the given length fits in a blob.
+    pub fn check_length(&self, name: &str, what: &str, length: u64) -> Result<(), String> {
+        if length > self.max_blob_size {
+            Err(format!(
+                "{} of {} is {} bytes, which exceeds the {} byte limit of the target platform",
+                what, name, length, self.max_blob_size
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Validate a resource name.
+    pub fn check_name(&self, name: &str) -> Result<(), String> {
+        if name.len() > self.max_name_length {
+            Err(format!(
+                "name of {} is {} bytes, which exceeds the {} byte limit",
+                name,
+                name.len(),
+                self.max_name_length
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Validate the number of resources in a payload.
+    pub fn check_resource_count(&self, count: usize) -> Result<(), String> {
+        if count > self.max_resource_count {
+            Err(format!(
+                "{} resources exceeds the limit of {} resources",
+                count, self.max_resource_count
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// An entry point advertised by a package distribution.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntryPoint<'a> {
+    /// Name of the distribution defining the entry point.
+    pub distribution: Cow<'a, str>,
+
+    /// Name of the entry point.
+    pub name: Cow<'a, str>,
+
+    /// Object the entry point refers to (e.g. `package.module:function`).
+    pub value: Cow<'a, str>,
+}
+
+impl<'a> EntryPoint<'a> {
+    pub fn to_owned(&self) -> EntryPoint<'static> {
+        EntryPoint {
+            distribution: Cow::Owned(self.distribution.clone().into_owned()),
+            name: Cow::Owned(self.name.clone().into_owned()),
+            value: Cow::Owned(self.value.clone().into_owned()),
+        }
+    }
+}
+
 /// Represents an indexed resource.
 ///
 /// The resource has a name and type affinity via various `is_*` fields.
@@ -351,6 +512,19 @@ where
 
     /// Holds arbitrary file data in a relative path encoded in UTF-8.
     pub file_data_utf8_relative_path: Option<Cow<'a, str>>,
+
+    /// Name of the module this resource is an alias for.
+    ///
+    /// Importing the resource's name yields the module object of the
+    /// named module.
+    pub module_alias_target: Option<Cow<'a, str>>,
+
+    /// Entry points of the group named by the resource name.
+    ///
+    /// This is an index of the entry points of all package distributions,
+    /// so entry points in a group can be found without reading every
+    /// distribution's metadata.
+    pub entry_points: Option<Vec<EntryPoint<'a>>>,
 }
 
 impl<'a, X> Default for Resource<'a, X>
@@ -388,6 +562,8 @@ where
             file_executable: false,
             file_data_embedded: None,
             file_data_utf8_relative_path: None,
+            module_alias_target: None,
+            entry_points: None,
         }
     }
 }
@@ -480,6 +656,12 @@ where
         if let Some(value) = other.file_data_utf8_relative_path {
             self.file_data_utf8_relative_path.replace(value);
         }
+        if let Some(value) = other.module_alias_target {
+            self.module_alias_target.replace(value);
+        }
+        if let Some(value) = other.entry_points {
+            self.entry_points.replace(value);
+        }
 
         Ok(())
     }
@@ -608,6 +790,14 @@ where
                 .file_data_utf8_relative_path
                 .as_ref()
                 .map(|value| Cow::Owned(value.clone().into_owned())),
+            module_alias_target: self
+                .module_alias_target
+                .as_ref()
+                .map(|value| Cow::Owned(value.clone().into_owned())),
+            entry_points: self
+                .entry_points
+                .as_ref()
+                .map(|value| value.iter().map(|ep| ep.to_owned()).collect()),
         }
     }
 }
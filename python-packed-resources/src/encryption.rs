@@ -0,0 +1,413 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Encryption of resource payloads.
+
+Python module source and bytecode payloads can be stored encrypted. The
+resources index (names, flags, etc) is always stored in plaintext. Only
+the payloads of the `InMemorySource`, `InMemoryBytecode`,
+`InMemoryBytecodeOpt1`, and `InMemoryBytecodeOpt2` fields are encrypted.
+
+An encrypted payload has the following layout:
+
+* 8 byte magic header (`ENCRYPTED_PAYLOAD_MAGIC`).
+* 1 byte encryption scheme identifier.
+* 32 byte X25519 ephemeral public key.
+* 24 byte nonce.
+* Ciphertext followed by a 16 byte authentication tag.
+
+The symmetric key is derived via HKDF-SHA256 from the X25519 shared
+secret of the ephemeral key and the recipient key. The resource name and
+field are authenticated as additional data, so payloads can't be moved
+between resources.
+
+Encryption and decryption functionality requires the `encryption` feature.
+Detecting encrypted payloads is always available.
+*/
+
+#[cfg(feature = "encryption")]
+use {
+    super::data::{Resource, ResourceField},
+    anyhow::{anyhow, Result},
+    chacha20poly1305::{
+        aead::{AeadInPlace, NewAead},
+        Key, Tag, XChaCha20Poly1305, XNonce,
+    },
+    hkdf::Hkdf,
+    rand_core::{OsRng, RngCore},
+    sha2::Sha256,
+    std::{borrow::Cow, collections::HashMap, convert::TryFrom, sync::Mutex},
+    x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret},
+    zeroize::Zeroize,
+};
+
+/// Header identifying an encrypted payload.
+pub const ENCRYPTED_PAYLOAD_MAGIC: &[u8] = b"\0pyxenc\x01";
+
+/// Length of public and secret keys.
+pub const KEY_LENGTH: usize = 32;
+
+const NONCE_LENGTH: usize = 24;
+const TAG_LENGTH: usize = 16;
+const HEADER_LENGTH: usize = 8 + 1 + KEY_LENGTH + NONCE_LENGTH;
+
+#[cfg(feature = "encryption")]
+const HKDF_INFO: &[u8] = b"python-packed-resources payload key";
+
+/// Describes an encryption scheme for resource payloads.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EncryptionScheme {
+    /// X25519 key agreement and XChaCha20-Poly1305.
+    XChaCha20Poly1305,
+}
+
+impl From<EncryptionScheme> for u8 {
+    fn from(scheme: EncryptionScheme) -> Self {
+        match scheme {
+            EncryptionScheme::XChaCha20Poly1305 => 0x01,
+        }
+    }
+}
+
+impl EncryptionScheme {
+    /// Resolve a scheme from its string name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "xchacha20poly1305" => Some(Self::XChaCha20Poly1305),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a payload is encrypted.
+pub fn is_encrypted_payload(data: &[u8]) -> bool {
+    data.starts_with(ENCRYPTED_PAYLOAD_MAGIC)
+}
+
+/// Obtain the length of the plaintext of an encrypted payload.
+///
+/// Returns `None` if the payload isn't a well-formed encrypted payload.
+pub fn decrypted_payload_length(data: &[u8]) -> Option<usize> {
+    if is_encrypted_payload(data) && data.len() >= HEADER_LENGTH + TAG_LENGTH {
+        Some(data.len() - HEADER_LENGTH - TAG_LENGTH)
+    } else {
+        None
+    }
+}
+
+/// Additional authenticated data for a payload.
+#[cfg(feature = "encryption")]
+fn associated_data(name: &str, field: ResourceField) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(name.len() + 2);
+    aad.extend_from_slice(name.as_bytes());
+    aad.push(0);
+    aad.push(field.into());
+
+    aad
+}
+
+/// Derive the payload cipher from key agreement material.
+#[cfg(feature = "encryption")]
+fn derive_cipher(
+    shared_secret: &[u8; KEY_LENGTH],
+    ephemeral_public: &[u8; KEY_LENGTH],
+    recipient_public: &[u8; KEY_LENGTH],
+) -> Option<XChaCha20Poly1305> {
+    // Reject low order points, which yield an all zero shared secret.
+    if shared_secret.iter().all(|b| *b == 0) {
+        return None;
+    }
+
+    let mut salt = [0u8; 2 * KEY_LENGTH];
+    salt[0..KEY_LENGTH].copy_from_slice(ephemeral_public);
+    salt[KEY_LENGTH..].copy_from_slice(recipient_public);
+
+    let mut key = [0u8; KEY_LENGTH];
+    Hkdf::<Sha256>::new(Some(&salt), shared_secret)
+        .expand(HKDF_INFO, &mut key)
+        .ok()?;
+
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+    key.zeroize();
+
+    Some(cipher)
+}
+
+/// Encrypts resource payloads for a recipient public key.
+///
+/// A single ephemeral key is used for all payloads encrypted by an instance.
+#[cfg(feature = "encryption")]
+pub struct ResourcesEncryptor {
+    scheme: EncryptionScheme,
+    ephemeral_public: [u8; KEY_LENGTH],
+    cipher: XChaCha20Poly1305,
+}
+
+#[cfg(feature = "encryption")]
+impl ResourcesEncryptor {
+    /// Construct an instance encrypting to an X25519 public key.
+    pub fn new(scheme: EncryptionScheme, recipient_public_key: &[u8]) -> Result<Self> {
+        let recipient = <[u8; KEY_LENGTH]>::try_from(recipient_public_key).map_err(|_| {
+            anyhow!(
+                "recipient public key must be {} bytes; got {}",
+                KEY_LENGTH,
+                recipient_public_key.len()
+            )
+        })?;
+
+        let ephemeral_secret = EphemeralSecret::new(OsRng);
+        let ephemeral_public = PublicKey::from(&ephemeral_secret).to_bytes();
+        let shared_secret = ephemeral_secret.diffie_hellman(&PublicKey::from(recipient));
+
+        let cipher = derive_cipher(shared_secret.as_bytes(), &ephemeral_public, &recipient)
+            .ok_or_else(|| anyhow!("recipient public key is not a valid X25519 public key"))?;
+
+        Ok(Self {
+            scheme,
+            ephemeral_public,
+            cipher,
+        })
+    }
+
+    /// Encrypt a single payload belonging to a named resource field.
+    pub fn encrypt_payload(
+        &self,
+        name: &str,
+        field: ResourceField,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng
+            .try_fill_bytes(&mut nonce)
+            .map_err(|e| anyhow!("error generating nonce: {}", e))?;
+
+        let mut data = Vec::with_capacity(HEADER_LENGTH + plaintext.len() + TAG_LENGTH);
+        data.extend_from_slice(ENCRYPTED_PAYLOAD_MAGIC);
+        data.push(self.scheme.into());
+        data.extend_from_slice(&self.ephemeral_public);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(plaintext);
+
+        let tag = self
+            .cipher
+            .encrypt_in_place_detached(
+                XNonce::from_slice(&nonce),
+                &associated_data(name, field),
+                &mut data[HEADER_LENGTH..],
+            )
+            .map_err(|_| anyhow!("error encrypting {:?} of {}", field, name))?;
+        data.extend_from_slice(&tag);
+
+        Ok(data)
+    }
+
+    /// Obtain a copy of a resource with all eligible payloads encrypted.
+    pub fn encrypt_resource<'a>(&self, resource: &Resource<'a, u8>) -> Result<Resource<'a, u8>> {
+        let mut resource = resource.clone();
+        let name = resource.name.to_string();
+
+        let encrypt_field = |field, value: &mut Option<Cow<'a, [u8]>>| -> Result<()> {
+            if let Some(data) = value {
+                *value = Some(Cow::Owned(self.encrypt_payload(&name, field, data)?));
+            }
+
+            Ok(())
+        };
+
+        encrypt_field(
+            ResourceField::InMemorySource,
+            &mut resource.in_memory_source,
+        )?;
+        encrypt_field(
+            ResourceField::InMemoryBytecode,
+            &mut resource.in_memory_bytecode,
+        )?;
+        encrypt_field(
+            ResourceField::InMemoryBytecodeOpt1,
+            &mut resource.in_memory_bytecode_opt1,
+        )?;
+        encrypt_field(
+            ResourceField::InMemoryBytecodeOpt2,
+            &mut resource.in_memory_bytecode_opt2,
+        )?;
+
+        Ok(resource)
+    }
+}
+
+/// Decrypts resource payloads using a recipient secret key.
+///
+/// Ciphers derived for each ephemeral key are cached so key agreement
+/// only happens once per distinct ephemeral key.
+#[cfg(feature = "encryption")]
+pub struct ResourcesDecryptor {
+    secret: StaticSecret,
+    public: [u8; KEY_LENGTH],
+    ciphers: Mutex<HashMap<[u8; KEY_LENGTH], XChaCha20Poly1305>>,
+}
+
+#[cfg(feature = "encryption")]
+impl ResourcesDecryptor {
+    /// Construct an instance from an X25519 secret key.
+    pub fn new(secret_key: &[u8]) -> Result<Self, &'static str> {
+        let mut key = <[u8; KEY_LENGTH]>::try_from(secret_key)
+            .map_err(|_| "resources decryption key must be 32 bytes")?;
+
+        let secret = StaticSecret::from(key);
+        key.zeroize();
+        let public = PublicKey::from(&secret).to_bytes();
+
+        Ok(Self {
+            secret,
+            public,
+            ciphers: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Decrypt an encrypted payload belonging to a named resource field.
+    ///
+    /// `dest` must have the length reported by `decrypted_payload_length()`.
+    /// On failure, `dest` is zeroed. Error messages never contain payload data.
+    pub fn decrypt_payload(
+        &self,
+        name: &str,
+        field: ResourceField,
+        payload: &[u8],
+        dest: &mut [u8],
+    ) -> Result<(), &'static str> {
+        if decrypted_payload_length(payload) != Some(dest.len()) {
+            return Err("malformed encrypted payload");
+        }
+
+        if payload[8] != u8::from(EncryptionScheme::XChaCha20Poly1305) {
+            return Err("unsupported payload encryption scheme");
+        }
+
+        let mut ephemeral_public = [0u8; KEY_LENGTH];
+        ephemeral_public.copy_from_slice(&payload[9..9 + KEY_LENGTH]);
+        let nonce = &payload[9 + KEY_LENGTH..HEADER_LENGTH];
+        let tag = &payload[payload.len() - TAG_LENGTH..];
+
+        let mut ciphers = self
+            .ciphers
+            .lock()
+            .map_err(|_| "resources decryption state is poisoned")?;
+
+        let cipher = match ciphers.get(&ephemeral_public) {
+            Some(cipher) => cipher,
+            None => {
+                let shared_secret = self
+                    .secret
+                    .diffie_hellman(&PublicKey::from(ephemeral_public));
+                let cipher =
+                    derive_cipher(shared_secret.as_bytes(), &ephemeral_public, &self.public)
+                        .ok_or("invalid payload ephemeral key")?;
+
+                ciphers.entry(ephemeral_public).or_insert(cipher)
+            }
+        };
+
+        dest.copy_from_slice(&payload[HEADER_LENGTH..payload.len() - TAG_LENGTH]);
+
+        cipher
+            .decrypt_in_place_detached(
+                XNonce::from_slice(nonce),
+                &associated_data(name, field),
+                dest,
+                Tag::from_slice(tag),
+            )
+            .map_err(|_| {
+                dest.zeroize();
+                "payload authentication failed (wrong decryption key?)"
+            })
+    }
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use super::*;
+
+    fn keypair() -> ([u8; KEY_LENGTH], [u8; KEY_LENGTH]) {
+        let secret = StaticSecret::new(OsRng);
+        let public = PublicKey::from(&secret);
+
+        (secret.to_bytes(), public.to_bytes())
+    }
+
+    fn decrypt(
+        decryptor: &ResourcesDecryptor,
+        name: &str,
+        field: ResourceField,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, &'static str> {
+        let mut dest = vec![0; decrypted_payload_length(payload).unwrap()];
+        decryptor.decrypt_payload(name, field, payload, &mut dest)?;
+
+        Ok(dest)
+    }
+
+    #[test]
+    fn test_roundtrip() -> Result<()> {
+        let (secret, public) = keypair();
+
+        let encryptor = ResourcesEncryptor::new(EncryptionScheme::XChaCha20Poly1305, &public)?;
+        let resource = Resource {
+            name: Cow::from("foo"),
+            is_module: true,
+            in_memory_source: Some(Cow::from(b"print('hello')".to_vec())),
+            in_memory_bytecode: Some(Cow::from(b"bytecode".to_vec())),
+            ..Resource::default()
+        };
+
+        let encrypted = encryptor.encrypt_resource(&resource)?;
+        assert_eq!(encrypted.name, "foo");
+        assert!(encrypted.in_memory_bytecode_opt1.is_none());
+
+        let source = encrypted.in_memory_source.as_ref().unwrap();
+        assert!(is_encrypted_payload(source));
+        assert_eq!(decrypted_payload_length(source), Some(14));
+
+        let decryptor = ResourcesDecryptor::new(&secret).unwrap();
+        assert_eq!(
+            decrypt(&decryptor, "foo", ResourceField::InMemorySource, source).unwrap(),
+            b"print('hello')"
+        );
+        assert_eq!(
+            decrypt(
+                &decryptor,
+                "foo",
+                ResourceField::InMemoryBytecode,
+                encrypted.in_memory_bytecode.as_ref().unwrap()
+            )
+            .unwrap(),
+            b"bytecode"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_key_or_context() -> Result<()> {
+        let (_, public) = keypair();
+        let (other_secret, _) = keypair();
+
+        let encryptor = ResourcesEncryptor::new(EncryptionScheme::XChaCha20Poly1305, &public)?;
+        let payload = encryptor.encrypt_payload("foo", ResourceField::InMemorySource, b"data")?;
+
+        let decryptor = ResourcesDecryptor::new(&other_secret).unwrap();
+        assert!(decrypt(&decryptor, "foo", ResourceField::InMemorySource, &payload).is_err());
+
+        let (secret, public) = keypair();
+        let encryptor = ResourcesEncryptor::new(EncryptionScheme::XChaCha20Poly1305, &public)?;
+        let payload = encryptor.encrypt_payload("foo", ResourceField::InMemorySource, b"data")?;
+        let decryptor = ResourcesDecryptor::new(&secret).unwrap();
+
+        // Payloads are bound to their resource name and field.
+        assert!(decrypt(&decryptor, "bar", ResourceField::InMemorySource, &payload).is_err());
+        assert!(decrypt(&decryptor, "foo", ResourceField::InMemoryBytecode, &payload).is_err());
+        assert!(ResourcesEncryptor::new(EncryptionScheme::XChaCha20Poly1305, &[0; 4]).is_err());
+
+        Ok(())
+    }
+}
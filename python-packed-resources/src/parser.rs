@@ -6,7 +6,8 @@
 
 use {
     super::data::{
-        BlobInteriorPadding, BlobSectionField, Resource, ResourceField, ResourceFlavor, HEADER_V3,
+        BlobInteriorPadding, BlobSectionField, EntryPoint, Resource, ResourceField, ResourceFlavor,
+        HEADER_V3, HEADER_V4,
     },
     byteorder::{LittleEndian, ReadBytesExt},
     std::{
@@ -24,6 +25,14 @@ use std::os::unix::ffi::OsStrExt;
 #[cfg(windows)]
 use {std::ffi::OsString, std::os::windows::ffi::OsStringExt, std::path::PathBuf};
 
+/// Convert a serialized length to a `usize`.
+///
+/// Lengths that can't be addressed by the current process are rejected
+/// instead of being truncated.
+fn u64_to_usize(value: u64) -> Result<usize, &'static str> {
+    usize::try_from(value).map_err(|_| "length exceeds addressable memory")
+}
+
 /// Represents a blob section in the blob index.
 #[derive(Debug)]
 struct BlobSection {
@@ -48,6 +57,8 @@ pub struct ResourceParserIterator<'a> {
     blob_sections: [Option<BlobSectionReadState>; 256],
     claimed_resources_count: usize,
     read_resources_count: usize,
+    /// Length of the data described by the headers, excluding the file header.
+    data_length: usize,
 }
 
 impl<'a> ResourceParserIterator<'a> {
@@ -61,12 +72,21 @@ impl<'a> ResourceParserIterator<'a> {
     /// This accepts a reference to the original blobs payload, an array of
     /// current blob section offsets, the resource field being accessed, and the
     /// length of the blob and returns a slice to that blob.
-    fn resolve_blob_data(&mut self, resource_field: ResourceField, length: usize) -> &'a [u8] {
+    fn resolve_blob_data(
+        &mut self,
+        resource_field: ResourceField,
+        length: usize,
+    ) -> Result<&'a [u8], &'static str> {
+        let data = self.data;
         let mut state = self.blob_sections[resource_field as usize]
             .as_mut()
-            .expect("blob state not found");
+            .ok_or("blob state not found")?;
 
-        let blob = &self.data[state.offset..state.offset + length];
+        let blob = state
+            .offset
+            .checked_add(length)
+            .and_then(|end| data.get(state.offset..end))
+            .ok_or("blob data extends beyond end of data")?;
 
         let increment = match &state.interior_padding {
             BlobInteriorPadding::None => length,
@@ -75,25 +95,50 @@ impl<'a> ResourceParserIterator<'a> {
 
         state.offset += increment;
 
-        blob
+        Ok(blob)
+    }
+
+    /// Resolve data for a payload stored in chunks.
+    ///
+    /// Chunks are stored back to back, with interior padding following the
+    /// final chunk. So the reassembled payload is a slice of the original data.
+    fn resolve_chunked_blob_data(
+        &mut self,
+        resource_field: ResourceField,
+        chunk_lengths: &[usize],
+    ) -> Result<&'a [u8], &'static str> {
+        let length = chunk_lengths
+            .iter()
+            .try_fold(0usize, |acc, l| acc.checked_add(*l))
+            .ok_or("chunked payload length overflows")?;
+
+        self.resolve_blob_data(resource_field, length)
     }
 
     #[cfg(unix)]
-    fn resolve_path(&mut self, resource_field: ResourceField, length: usize) -> Cow<'a, Path> {
-        let path_str = OsStr::from_bytes(self.resolve_blob_data(resource_field, length));
-        Cow::Borrowed(Path::new(path_str))
+    fn resolve_path(
+        &mut self,
+        resource_field: ResourceField,
+        length: usize,
+    ) -> Result<Cow<'a, Path>, &'static str> {
+        let path_str = OsStr::from_bytes(self.resolve_blob_data(resource_field, length)?);
+        Ok(Cow::Borrowed(Path::new(path_str)))
     }
 
     #[cfg(windows)]
-    fn resolve_path(&mut self, resource_field: ResourceField, length: usize) -> Cow<'a, Path> {
-        let raw = self.resolve_blob_data(resource_field, length);
+    fn resolve_path(
+        &mut self,
+        resource_field: ResourceField,
+        length: usize,
+    ) -> Result<Cow<'a, Path>, &'static str> {
+        let raw = self.resolve_blob_data(resource_field, length)?;
         let raw = unsafe { std::slice::from_raw_parts(raw.as_ptr() as *const u16, raw.len() / 2) };
 
         // There isn't an API that lets us get a OsStr from &[u16]. So we need to use
         // owned types.
         let path_string = OsString::from_wide(raw);
 
-        Cow::Owned(PathBuf::from(path_string))
+        Ok(Cow::Owned(PathBuf::from(path_string)))
     }
 
     fn parse_next(&mut self) -> Result<Option<Resource<'a, u8>>, &'static str> {
@@ -153,7 +198,7 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     let name = unsafe {
-                        std::str::from_utf8_unchecked(self.resolve_blob_data(field_type, l))
+                        std::str::from_utf8_unchecked(self.resolve_blob_data(field_type, l)?)
                     };
 
                     current_resource_name = Some(name);
@@ -173,7 +218,7 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     current_resource.in_memory_source =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
                 }
                 ResourceField::InMemoryBytecode => {
                     let l = self
@@ -183,7 +228,7 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     current_resource.in_memory_bytecode =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
                 }
                 ResourceField::InMemoryBytecodeOpt1 => {
                     let l = self
@@ -193,7 +238,7 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     current_resource.in_memory_bytecode_opt1 =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
                 }
                 ResourceField::InMemoryBytecodeOpt2 => {
                     let l = self
@@ -203,7 +248,7 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     current_resource.in_memory_bytecode_opt2 =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
                 }
                 ResourceField::InMemoryExtensionModuleSharedLibrary => {
                     let l = self
@@ -213,7 +258,7 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     current_resource.in_memory_extension_module_shared_library =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
                 }
 
                 ResourceField::InMemoryResourcesData => {
@@ -234,17 +279,17 @@ impl<'a> ResourceParserIterator<'a> {
 
                         let resource_name = unsafe {
                             std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, resource_name_length),
+                                self.resolve_blob_data(field_type, resource_name_length)?,
                             )
                         };
 
                         let resource_length = self
                             .reader
                             .read_u64::<LittleEndian>()
-                            .map_err(|_| "failed reading resource length")?
-                            as usize;
+                            .map_err(|_| "failed reading resource length")?;
+                        let resource_length = u64_to_usize(resource_length)?;
 
-                        let resource_data = self.resolve_blob_data(field_type, resource_length);
+                        let resource_data = self.resolve_blob_data(field_type, resource_length)?;
 
                         resources
                             .insert(Cow::Borrowed(resource_name), Cow::Borrowed(resource_data));
@@ -271,16 +316,17 @@ impl<'a> ResourceParserIterator<'a> {
 
                         let name = unsafe {
                             std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, name_length),
+                                self.resolve_blob_data(field_type, name_length)?,
                             )
                         };
 
-                        let resource_length =
-                            self.reader.read_u64::<LittleEndian>().map_err(|_| {
-                                "failed reading package distribution resource length"
-                            })? as usize;
+                        let resource_length = self
+                            .reader
+                            .read_u64::<LittleEndian>()
+                            .map_err(|_| "failed reading package distribution resource length")?;
+                        let resource_length = u64_to_usize(resource_length)?;
 
-                        let resource_data = self.resolve_blob_data(field_type, resource_length);
+                        let resource_data = self.resolve_blob_data(field_type, resource_length)?;
 
                         resources.insert(Cow::Borrowed(name), Cow::Borrowed(resource_data));
                     }
@@ -292,11 +338,11 @@ impl<'a> ResourceParserIterator<'a> {
                     let l = self
                         .reader
                         .read_u64::<LittleEndian>()
-                        .map_err(|_| "failed reading in-memory shared library length")?
-                        as usize;
+                        .map_err(|_| "failed reading in-memory shared library length")?;
+                    let l = u64_to_usize(l)?;
 
                     current_resource.in_memory_shared_library =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
                 }
 
                 ResourceField::SharedLibraryDependencyNames => {
@@ -316,7 +362,7 @@ impl<'a> ResourceParserIterator<'a> {
 
                         let name = unsafe {
                             std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, name_length),
+                                self.resolve_blob_data(field_type, name_length)?,
                             )
                         };
 
@@ -333,7 +379,7 @@ impl<'a> ResourceParserIterator<'a> {
                         .map_err(|_| "failed reading Python module relative path length")?
                         as usize;
 
-                    let path = self.resolve_path(field_type, path_length);
+                    let path = self.resolve_path(field_type, path_length)?;
 
                     current_resource.relative_path_module_source = Some(path);
                 }
@@ -344,7 +390,7 @@ impl<'a> ResourceParserIterator<'a> {
                             "failed reading Python module bytecode relative path length"
                         })? as usize;
 
-                    let path = self.resolve_path(field_type, path_length);
+                    let path = self.resolve_path(field_type, path_length)?;
 
                     current_resource.relative_path_module_bytecode = Some(path);
                 }
@@ -354,7 +400,7 @@ impl<'a> ResourceParserIterator<'a> {
                         "failed reading Python module bytecode opt 1 relative path length"
                     })? as usize;
 
-                    let path = self.resolve_path(field_type, path_length);
+                    let path = self.resolve_path(field_type, path_length)?;
 
                     current_resource.relative_path_module_bytecode_opt1 = Some(path);
                 }
@@ -364,7 +410,7 @@ impl<'a> ResourceParserIterator<'a> {
                         "failed reading Python module bytecode opt 2 relative path length"
                     })? as usize;
 
-                    let path = self.resolve_path(field_type, path_length);
+                    let path = self.resolve_path(field_type, path_length)?;
 
                     current_resource.relative_path_module_bytecode_opt2 = Some(path);
                 }
@@ -374,7 +420,7 @@ impl<'a> ResourceParserIterator<'a> {
                         "failed reading Python extension module shared library relative path length"
                     })? as usize;
 
-                    let path = self.resolve_path(field_type, path_length);
+                    let path = self.resolve_path(field_type, path_length)?;
 
                     current_resource.relative_path_extension_module_shared_library = Some(path);
                 }
@@ -396,7 +442,7 @@ impl<'a> ResourceParserIterator<'a> {
 
                         let resource_name = unsafe {
                             std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, resource_name_length),
+                                self.resolve_blob_data(field_type, resource_name_length)?,
                             )
                         };
 
@@ -406,7 +452,7 @@ impl<'a> ResourceParserIterator<'a> {
                             .map_err(|_| "failed reading resource path length")?
                             as usize;
 
-                        let path = self.resolve_path(field_type, path_length);
+                        let path = self.resolve_path(field_type, path_length)?;
 
                         resources.insert(Cow::Borrowed(resource_name), path);
                     }
@@ -430,7 +476,7 @@ impl<'a> ResourceParserIterator<'a> {
 
                         let name = unsafe {
                             std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, name_length),
+                                self.resolve_blob_data(field_type, name_length)?,
                             )
                         };
 
@@ -440,7 +486,7 @@ impl<'a> ResourceParserIterator<'a> {
                             .map_err(|_| "failed reading package distribution path length")?
                             as usize;
 
-                        let path = self.resolve_path(field_type, path_length);
+                        let path = self.resolve_path(field_type, path_length)?;
 
                         resources.insert(Cow::Borrowed(name), path);
                     }
@@ -480,11 +526,58 @@ impl<'a> ResourceParserIterator<'a> {
                     let l = self
                         .reader
                         .read_u64::<LittleEndian>()
-                        .map_err(|_| "failed reading embedded file data length")?
-                        as usize;
+                        .map_err(|_| "failed reading embedded file data length")?;
+                    let l = u64_to_usize(l)?;
 
                     current_resource.file_data_embedded =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
+                }
+
+                ResourceField::ChunkedPayload => {
+                    let target = self
+                        .reader
+                        .read_u8()
+                        .map_err(|_| "failed reading chunked payload target field")?;
+                    let target = ResourceField::try_from(target)?;
+
+                    let chunk_count = self
+                        .reader
+                        .read_u32::<LittleEndian>()
+                        .map_err(|_| "failed reading chunked payload chunk count")?
+                        as usize;
+
+                    let mut chunk_lengths = Vec::with_capacity(chunk_count.min(1024));
+                    for _ in 0..chunk_count {
+                        chunk_lengths.push(
+                            self.reader
+                                .read_u32::<LittleEndian>()
+                                .map_err(|_| "failed reading chunked payload chunk length")?
+                                as usize,
+                        );
+                    }
+
+                    let data = Some(Cow::Borrowed(
+                        self.resolve_chunked_blob_data(target, &chunk_lengths)?,
+                    ));
+
+                    match target {
+                        ResourceField::InMemorySource => {
+                            current_resource.in_memory_source = data;
+                        }
+                        ResourceField::InMemoryBytecode => {
+                            current_resource.in_memory_bytecode = data;
+                        }
+                        ResourceField::InMemoryBytecodeOpt1 => {
+                            current_resource.in_memory_bytecode_opt1 = data;
+                        }
+                        ResourceField::InMemoryBytecodeOpt2 => {
+                            current_resource.in_memory_bytecode_opt2 = data;
+                        }
+                        ResourceField::InMemoryExtensionModuleSharedLibrary => {
+                            current_resource.in_memory_extension_module_shared_library = data;
+                        }
+                        _ => return Err("chunked payload targets unsupported field"),
+                    }
                 }
 
                 ResourceField::FileDataUtf8RelativePath => {
@@ -495,9 +588,65 @@ impl<'a> ResourceParserIterator<'a> {
                         as usize;
 
                     current_resource.file_data_utf8_relative_path = Some(Cow::Borrowed(unsafe {
-                        std::str::from_utf8_unchecked(self.resolve_blob_data(field_type, l))
+                        std::str::from_utf8_unchecked(self.resolve_blob_data(field_type, l)?)
                     }));
                 }
+
+                ResourceField::ModuleAliasTarget => {
+                    let l = self
+                        .reader
+                        .read_u16::<LittleEndian>()
+                        .map_err(|_| "failed reading module alias target length")?
+                        as usize;
+
+                    current_resource.module_alias_target = Some(Cow::Borrowed(unsafe {
+                        std::str::from_utf8_unchecked(self.resolve_blob_data(field_type, l)?)
+                    }));
+                }
+
+                ResourceField::EntryPoints => {
+                    let count = self
+                        .reader
+                        .read_u16::<LittleEndian>()
+                        .map_err(|_| "failed reading entry points count")?
+                        as usize;
+
+                    let mut lengths = Vec::with_capacity(count * 3);
+                    for _ in 0..count * 3 {
+                        lengths.push(
+                            self.reader
+                                .read_u16::<LittleEndian>()
+                                .map_err(|_| "failed reading entry point string length")?
+                                as usize,
+                        );
+                    }
+
+                    let mut entry_points = Vec::with_capacity(count);
+
+                    for chunk in lengths.chunks(3) {
+                        let mut values = Vec::with_capacity(3);
+
+                        for l in chunk {
+                            values.push(Cow::Borrowed(unsafe {
+                                std::str::from_utf8_unchecked(
+                                    self.resolve_blob_data(field_type, *l)?,
+                                )
+                            }));
+                        }
+
+                        let value = values.pop().unwrap();
+                        let name = values.pop().unwrap();
+                        let distribution = values.pop().unwrap();
+
+                        entry_points.push(EntryPoint {
+                            distribution,
+                            name,
+                            value,
+                        });
+                    }
+
+                    current_resource.entry_points = Some(entry_points);
+                }
             }
         }
     }
@@ -527,11 +676,62 @@ pub fn load_resources<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>,
 
     if header == HEADER_V3 {
         load_resources_v3(&data[8..])
+    } else if header == HEADER_V4 {
+        if data.len() < HEADER_V4.len() + 4 {
+            return Err("error reading bytecode magic number");
+        }
+
+        load_resources_v3(&data[12..])
     } else {
         Err("unrecognized file format")
     }
 }
 
+/// Resolve the Python bytecode magic number recorded in packed resources data.
+///
+/// Returns `None` if the data format doesn't record the magic number or if
+/// the writer didn't know it.
+pub fn read_bytecode_magic_number(data: &[u8]) -> Result<Option<u32>, &'static str> {
+    if data.len() < HEADER_V3.len() {
+        return Err("error reading 8 byte header");
+    }
+
+    let header = &data[0..8];
+
+    if header == HEADER_V3 {
+        Ok(None)
+    } else if header == HEADER_V4 {
+        let magic = Cursor::new(&data[8..])
+            .read_u32::<LittleEndian>()
+            .map_err(|_| "error reading bytecode magic number")?;
+
+        Ok(if magic == 0 { None } else { Some(magic) })
+    } else {
+        Err("unrecognized file format")
+    }
+}
+
+/// Resolve the length of packed resources data from its headers.
+///
+/// Packed resources data doesn't record where it ends. This allows finding
+/// the bounds of data that is followed by unrelated bytes, such as data
+/// embedded in an executable.
+pub fn packed_resources_length(data: &[u8]) -> Result<usize, &'static str> {
+    let data_length = load_resources(data)?.data_length;
+
+    let length = if data.starts_with(HEADER_V4) {
+        HEADER_V4.len() + 4 + data_length
+    } else {
+        HEADER_V3.len() + data_length
+    };
+
+    if length > data.len() {
+        Err("packed resources data is truncated")
+    } else {
+        Ok(length)
+    }
+}
+
 fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &'static str> {
     let mut reader = Cursor::new(data);
 
@@ -599,7 +799,7 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
                     let l = reader
                         .read_u64::<LittleEndian>()
                         .map_err(|_| "failed reading raw payload length")?;
-                    current_blob_raw_payload_length = Some(l as usize);
+                    current_blob_raw_payload_length = Some(u64_to_usize(l)?);
                 }
                 BlobSectionField::InteriorPadding => {
                     let padding = reader
@@ -652,6 +852,7 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
         blob_sections: blob_offsets,
         claimed_resources_count: resources_count,
         read_resources_count: 0,
+        data_length: blob_start_offset + current_blob_offset,
     })
 }
 
@@ -659,8 +860,12 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
 mod tests {
     use {
         super::*,
+        crate::data::PackedResourcesLimits,
         crate::data::{BlobInteriorPadding, Resource},
-        crate::writer::write_packed_resources_v3,
+        crate::writer::{
+            write_packed_resources_v3, write_packed_resources_v4,
+            write_packed_resources_v4_with_limits,
+        },
         std::collections::BTreeMap,
     };
 
@@ -678,11 +883,144 @@ mod tests {
         let res = load_resources(data);
         assert_eq!(res.err(), Some("unrecognized file format"));
 
-        let data = b"pyembed\x04";
+        let data = b"pyembed\x05";
         let res = load_resources(data);
         assert_eq!(res.err(), Some("unrecognized file format"));
     }
 
+    #[test]
+    fn test_v4_too_short() {
+        let data = b"pyembed\x04\x00\x00";
+        let res = load_resources(data);
+        assert_eq!(res.err(), Some("error reading bytecode magic number"));
+        assert_eq!(
+            read_bytecode_magic_number(data),
+            Err("error reading bytecode magic number")
+        );
+    }
+
+    #[test]
+    fn test_bytecode_magic_number() {
+        let resource = Resource {
+            name: Cow::from("foo"),
+            in_memory_bytecode: Some(Cow::from(b"bytecode".to_vec())),
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        write_packed_resources_v3(std::slice::from_ref(&resource), &mut data, None).unwrap();
+        assert_eq!(read_bytecode_magic_number(&data), Ok(None));
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(std::slice::from_ref(&resource), &mut data, None, 0).unwrap();
+        assert_eq!(read_bytecode_magic_number(&data), Ok(None));
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(&[resource], &mut data, None, 0x0a0d0d55).unwrap();
+        assert_eq!(&data[0..8], b"pyembed\x04");
+        assert_eq!(read_bytecode_magic_number(&data), Ok(Some(0x0a0d0d55)));
+
+        let resources = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .unwrap();
+        assert_eq!(resources.len(), 1);
+        assert_eq!(
+            resources[0].in_memory_bytecode.as_ref().unwrap().as_ref(),
+            b"bytecode"
+        );
+    }
+
+    #[test]
+    fn test_chunked_payload() {
+        let limits = PackedResourcesLimits {
+            allow_chunking: true,
+            max_chunk_length: 4,
+            ..PackedResourcesLimits::default()
+        };
+
+        let resources = vec![
+            Resource {
+                name: Cow::from("foo"),
+                in_memory_source: Some(Cow::from(b"import bar".to_vec())),
+                in_memory_bytecode: Some(Cow::from(b"bc".to_vec())),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("bar"),
+                in_memory_source: Some(Cow::from(b"12345678".to_vec())),
+                in_memory_extension_module_shared_library: Some(Cow::from(b"library".to_vec())),
+                ..Resource::default()
+            },
+        ];
+
+        for padding in &[None, Some(BlobInteriorPadding::Null)] {
+            let mut data = Vec::new();
+            write_packed_resources_v4_with_limits(&resources, &mut data, *padding, 0, &limits)
+                .unwrap();
+
+            let parsed = load_resources(&data)
+                .unwrap()
+                .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+                .unwrap();
+            assert_eq!(parsed, resources);
+
+            // Data for chunked payloads is borrowed from the original buffer.
+            let source = parsed[0].in_memory_source.as_ref().unwrap();
+            assert!(matches!(source, Cow::Borrowed(_)));
+        }
+    }
+
+    #[test]
+    fn test_chunked_payload_bad_target() {
+        let limits = PackedResourcesLimits {
+            allow_chunking: true,
+            max_chunk_length: 1,
+            ..PackedResourcesLimits::default()
+        };
+
+        let resource = Resource {
+            name: Cow::from("foo"),
+            in_memory_source: Some(Cow::from(b"ab".to_vec())),
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        write_packed_resources_v4_with_limits(&[resource], &mut data, None, 0, &limits).unwrap();
+
+        // Retarget the chunked payload to the module name field.
+        let chunked = data
+            .iter()
+            .position(|b| *b == u8::from(ResourceField::ChunkedPayload))
+            .unwrap();
+        data[chunked + 1] = ResourceField::ModuleName.into();
+
+        let mut res = load_resources(&data).unwrap();
+        assert_eq!(
+            res.next(),
+            Some(Err("chunked payload targets unsupported field"))
+        );
+    }
+
+    #[test]
+    fn test_truncated_blob_data() {
+        let resource = Resource {
+            name: Cow::from("foo"),
+            in_memory_source: Some(Cow::from(b"source".to_vec())),
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        data.truncate(data.len() - 2);
+
+        let mut res = load_resources(&data).unwrap();
+        assert_eq!(
+            res.next(),
+            Some(Err("blob data extends beyond end of data"))
+        );
+    }
+
     #[test]
     fn test_no_indices() {
         let data = b"pyembed\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
@@ -1105,6 +1443,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_entry_points() {
+        let entry_points = vec![
+            EntryPoint {
+                distribution: Cow::from("myapp_foo"),
+                name: Cow::from("foo"),
+                value: Cow::from("myapp_foo.plugin"),
+            },
+            EntryPoint {
+                distribution: Cow::from("myapp_bar"),
+                name: Cow::from("bar"),
+                value: Cow::from("myapp_bar:Plugin [extra]"),
+            },
+        ];
+
+        let resource = Resource {
+            name: Cow::from("myapp.plugins"),
+            entry_points: Some(entry_points.clone()),
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        let resources = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .unwrap();
+
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].entry_points, Some(entry_points));
+    }
+
     #[test]
     fn test_shared_library_dependency_names() {
         let names = vec![Cow::from("depends"), Cow::from("libfoo")];
@@ -1410,6 +1780,12 @@ mod tests {
             file_executable: true,
             file_data_embedded: Some(Cow::from(b"file_data_embedded".to_vec())),
             file_data_utf8_relative_path: Some(Cow::from("file_data_utf8_relative_path")),
+            module_alias_target: Some(Cow::from("module_alias_target")),
+            entry_points: Some(vec![EntryPoint {
+                distribution: Cow::from("distribution"),
+                name: Cow::from("name"),
+                value: Cow::from("module:attr"),
+            }]),
         };
 
         let mut data = Vec::new();
@@ -1525,6 +1901,18 @@ mod tests {
             entry.file_data_utf8_relative_path.as_ref().unwrap(),
             "file_data_utf8_relative_path"
         );
+        assert_eq!(
+            entry.module_alias_target.as_ref().unwrap(),
+            "module_alias_target"
+        );
+        assert_eq!(
+            entry.entry_points.as_ref().unwrap(),
+            &vec![EntryPoint {
+                distribution: Cow::from("distribution"),
+                name: Cow::from("name"),
+                value: Cow::from("module:attr"),
+            }]
+        );
     }
 
     #[test]
@@ -1553,4 +1941,38 @@ mod tests {
 
         assert_eq!(resources, loaded);
     }
+
+    #[test]
+    fn test_packed_resources_length() {
+        let resources: Vec<Resource<u8>> = vec![Resource {
+            flavor: ResourceFlavor::Module,
+            name: Cow::from("foo"),
+            in_memory_source: Some(Cow::from(b"import io".to_vec())),
+            in_memory_bytecode: Some(Cow::from(b"fake bytecode".to_vec())),
+            ..Resource::default()
+        }];
+
+        let mut data = Vec::new();
+        write_packed_resources_v3(&resources, &mut data, None).unwrap();
+        let length = data.len();
+        assert_eq!(packed_resources_length(&data), Ok(length));
+
+        data.extend_from_slice(b"trailing data");
+        assert_eq!(packed_resources_length(&data), Ok(length));
+        assert_eq!(
+            packed_resources_length(&data[0..length - 1]),
+            Err("packed resources data is truncated")
+        );
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(&resources, &mut data, None, 42).unwrap();
+        let length = data.len();
+        data.extend_from_slice(&[0; 16]);
+        assert_eq!(packed_resources_length(&data), Ok(length));
+
+        assert_eq!(
+            packed_resources_length(b"bogus data"),
+            Err("unrecognized file format")
+        );
+    }
 }
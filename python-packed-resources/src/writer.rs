@@ -6,7 +6,8 @@
 
 use {
     super::data::{
-        BlobInteriorPadding, BlobSectionField, Resource, ResourceField, ResourceFlavor, HEADER_V3,
+        BlobInteriorPadding, BlobSectionField, PackedResourcesLimits, Resource, ResourceField,
+        ResourceFlavor, HEADER_V3, HEADER_V4, MAX_CHUNK_LENGTH,
     },
     anyhow::{anyhow, Context, Result},
     byteorder::{LittleEndian, WriteBytesExt},
@@ -46,6 +47,42 @@ fn path_to_bytes(p: &Path) -> Vec<u8> {
     }
 }
 
+/// Compute the chunk lengths of a payload that must be stored in chunks.
+///
+/// Returns `None` if the payload fits in a single `u32` length field or
+/// if chunking is disabled.
+fn payload_chunk_lengths(length: usize, limits: &PackedResourcesLimits) -> Option<Vec<u32>> {
+    if !limits.allow_chunking || !limits.requires_chunking(length as u64) {
+        return None;
+    }
+
+    let chunk_length = match limits.max_chunk_length {
+        0 => 1,
+        l => l.min(MAX_CHUNK_LENGTH) as usize,
+    };
+
+    let mut chunks = Vec::with_capacity(length / chunk_length + 1);
+    let mut remaining = length;
+    while remaining > 0 {
+        let l = remaining.min(chunk_length);
+        chunks.push(l as u32);
+        remaining -= l;
+    }
+
+    Some(chunks)
+}
+
+/// Compute the length of the index entry for a payload with a `u32` length.
+fn payload_index_length(length: usize, limits: &PackedResourcesLimits) -> usize {
+    if let Some(chunks) = payload_chunk_lengths(length, limits) {
+        // Field + target field + chunk count + chunk lengths.
+        1 + 1 + 4 + 4 * chunks.len()
+    } else {
+        // Field + length.
+        5
+    }
+}
+
 #[derive(Debug)]
 struct BlobSection {
     resource_field: ResourceField,
@@ -131,10 +168,14 @@ where
             || self.relative_path_distribution_resources.is_some()
             || self.file_data_embedded.is_some()
             || self.file_data_utf8_relative_path.is_some()
+            || self.module_alias_target.is_some()
+            || self.entry_points.is_some()
     }
 
     /// Compute length of index entry for version 1 payload format.
-    pub fn index_v1_length(&self) -> usize {
+    ///
+    /// `limits` determines whether large payloads are stored in chunks.
+    pub fn index_v1_length(&self, limits: &PackedResourcesLimits) -> usize {
         // Start of index entry.
         let mut index = 1;
 
@@ -154,24 +195,24 @@ where
             index += 1;
         }
 
-        if self.in_memory_source.is_some() {
-            index += 5;
+        if let Some(data) = &self.in_memory_source {
+            index += payload_index_length(data.len(), limits);
         }
 
-        if self.in_memory_bytecode.is_some() {
-            index += 5;
+        if let Some(data) = &self.in_memory_bytecode {
+            index += payload_index_length(data.len(), limits);
         }
 
-        if self.in_memory_bytecode_opt1.is_some() {
-            index += 5;
+        if let Some(data) = &self.in_memory_bytecode_opt1 {
+            index += payload_index_length(data.len(), limits);
         }
 
-        if self.in_memory_bytecode_opt2.is_some() {
-            index += 5;
+        if let Some(data) = &self.in_memory_bytecode_opt2 {
+            index += payload_index_length(data.len(), limits);
         }
 
-        if self.in_memory_extension_module_shared_library.is_some() {
-            index += 5;
+        if let Some(data) = &self.in_memory_extension_module_shared_library {
+            index += payload_index_length(data.len(), limits);
         }
 
         if let Some(resources) = &self.in_memory_package_resources {
@@ -264,6 +305,14 @@ where
             index += 5;
         }
 
+        if self.module_alias_target.is_some() {
+            index += 3;
+        }
+
+        if let Some(entry_points) = &self.entry_points {
+            index += 3 + 6 * entry_points.len();
+        }
+
         // End of index entry.
         index += 1;
 
@@ -427,6 +476,25 @@ where
                     0
                 }
             }
+            ResourceField::ModuleAliasTarget => {
+                if let Some(target) = &self.module_alias_target {
+                    target.len()
+                } else {
+                    0
+                }
+            }
+            ResourceField::EntryPoints => {
+                if let Some(entry_points) = &self.entry_points {
+                    entry_points
+                        .iter()
+                        .map(|ep| ep.distribution.len() + ep.name.len() + ep.value.len())
+                        .sum()
+                } else {
+                    0
+                }
+            }
+            // Chunk data is stored in the section of the chunked field.
+            ResourceField::ChunkedPayload => 0,
         }
     }
 
@@ -577,6 +645,21 @@ where
                     0
                 }
             }
+            ResourceField::ModuleAliasTarget => {
+                if self.module_alias_target.is_some() {
+                    1
+                } else {
+                    0
+                }
+            }
+            ResourceField::EntryPoints => {
+                if let Some(entry_points) = &self.entry_points {
+                    3 * entry_points.len()
+                } else {
+                    0
+                }
+            }
+            ResourceField::ChunkedPayload => 0,
         };
 
         let overhead = match padding {
@@ -587,8 +670,59 @@ where
         elements_count * overhead
     }
 
+    /// Write the index entry for a payload having a `u32` length.
+    ///
+    /// Payloads too large for a `u32` are written as a chunked payload entry
+    /// if `limits` allows it.
+    fn write_payload_index<W: Write>(
+        &self,
+        dest: &mut W,
+        field: ResourceField,
+        what: &str,
+        length: usize,
+        limits: &PackedResourcesLimits,
+    ) -> Result<()> {
+        if let Some(chunks) = payload_chunk_lengths(length, limits) {
+            let count = u32::try_from(chunks.len())
+                .with_context(|| format!("converting {} chunk count to u32", what))?;
+
+            dest.write_u8(ResourceField::ChunkedPayload.into())
+                .context("writing chunked payload field")?;
+            dest.write_u8(field.into())
+                .context("writing chunked payload target field")?;
+            dest.write_u32::<LittleEndian>(count)
+                .context("writing chunked payload chunk count")?;
+
+            for chunk in chunks {
+                dest.write_u32::<LittleEndian>(chunk)
+                    .context("writing chunked payload chunk length")?;
+            }
+        } else {
+            let l = u32::try_from(length).map_err(|_| {
+                anyhow!(limits
+                    .check_chunkable_length(&self.name, what, length as u64)
+                    .err()
+                    .unwrap_or_else(|| format!("converting {} length to u32", what)))
+            })?;
+
+            dest.write_u8(field.into())
+                .with_context(|| format!("writing {} field", what))?;
+            dest.write_u32::<LittleEndian>(l)
+                .with_context(|| format!("writing {} length", what))?;
+        }
+
+        Ok(())
+    }
+
     /// Write the version 1 index entry for a module instance.
-    pub fn write_index_v1<W: Write>(&self, dest: &mut W) -> Result<()> {
+    ///
+    /// `limits` determines whether large payloads are stored in chunks.
+    pub fn write_index_v1<W: Write>(
+        &self,
+        dest: &mut W,
+        limits: &PackedResourcesLimits,
+    ) -> Result<()> {
+        limits.check_name(&self.name).map_err(|e| anyhow!(e))?;
         let name_len =
             u16::try_from(self.name.as_bytes().len()).context("converting name to u16")?;
 
@@ -618,49 +752,36 @@ where
                 .context("writing is_namespace field")?;
         }
 
-        if let Some(source) = &self.in_memory_source {
-            let l =
-                u32::try_from(source.len()).context("converting in-memory source length to u32")?;
-            dest.write_u8(ResourceField::InMemorySource.into())
-                .context("writing in-memory source length field")?;
-            dest.write_u32::<LittleEndian>(l)
-                .context("writing in-memory source length")?;
-        }
-
-        if let Some(bytecode) = &self.in_memory_bytecode {
-            let l = u32::try_from(bytecode.len())
-                .context("converting in-memory bytecode length to u32")?;
-            dest.write_u8(ResourceField::InMemoryBytecode.into())
-                .context("writing in-memory bytecode length field")?;
-            dest.write_u32::<LittleEndian>(l)
-                .context("writing in-memory bytecode length")?;
-        }
-
-        if let Some(bytecode) = &self.in_memory_bytecode_opt1 {
-            let l = u32::try_from(bytecode.len())
-                .context("converting in-memory bytecode opt 1 length to u32")?;
-            dest.write_u8(ResourceField::InMemoryBytecodeOpt1.into())
-                .context("writing in-memory bytecode opt 1 length field")?;
-            dest.write_u32::<LittleEndian>(l)
-                .context("writing in-memory bytecode opt 1 length")?;
-        }
-
-        if let Some(bytecode) = &self.in_memory_bytecode_opt2 {
-            let l = u32::try_from(bytecode.len())
-                .context("converting in-memory bytecode opt 2 length to u32")?;
-            dest.write_u8(ResourceField::InMemoryBytecodeOpt2.into())
-                .context("writing in-memory bytecode opt 2 field")?;
-            dest.write_u32::<LittleEndian>(l)
-                .context("writing in-memory bytecode opt 2 length")?;
-        }
-
-        if let Some(library) = &self.in_memory_extension_module_shared_library {
-            let l = u32::try_from(library.len())
-                .context("converting in-memory library length to u32")?;
-            dest.write_u8(ResourceField::InMemoryExtensionModuleSharedLibrary.into())
-                .context("writing in-memory extension module shared library field")?;
-            dest.write_u32::<LittleEndian>(l)
-                .context("writing in-memory extension module shared library length")?;
+        for (field, what, data) in &[
+            (
+                ResourceField::InMemorySource,
+                "in-memory source",
+                &self.in_memory_source,
+            ),
+            (
+                ResourceField::InMemoryBytecode,
+                "in-memory bytecode",
+                &self.in_memory_bytecode,
+            ),
+            (
+                ResourceField::InMemoryBytecodeOpt1,
+                "in-memory bytecode opt 1",
+                &self.in_memory_bytecode_opt1,
+            ),
+            (
+                ResourceField::InMemoryBytecodeOpt2,
+                "in-memory bytecode opt 2",
+                &self.in_memory_bytecode_opt2,
+            ),
+            (
+                ResourceField::InMemoryExtensionModuleSharedLibrary,
+                "in-memory extension module shared library",
+                &self.in_memory_extension_module_shared_library,
+            ),
+        ] {
+            if let Some(data) = data {
+                self.write_payload_index(dest, *field, what, data.len(), limits)?;
+            }
         }
 
         if let Some(resources) = &self.in_memory_package_resources {
@@ -862,6 +983,33 @@ where
                 .context("writing file_data_utf_relative_path field")?;
         }
 
+        if let Some(target) = &self.module_alias_target {
+            let l = u16::try_from(target.len())
+                .context("converting module alias target length to u16")?;
+            dest.write_u8(ResourceField::ModuleAliasTarget.into())
+                .context("writing module_alias_target field")?;
+            dest.write_u16::<LittleEndian>(l)
+                .context("writing module_alias_target length")?;
+        }
+
+        if let Some(entry_points) = &self.entry_points {
+            let l = u16::try_from(entry_points.len())
+                .context("converting entry points count to u16")?;
+            dest.write_u8(ResourceField::EntryPoints.into())
+                .context("writing entry points field")?;
+            dest.write_u16::<LittleEndian>(l)
+                .context("writing entry points count")?;
+
+            for ep in entry_points {
+                for value in &[&ep.distribution, &ep.name, &ep.value] {
+                    let l = u16::try_from(value.len())
+                        .context("converting entry point string length to u16")?;
+                    dest.write_u16::<LittleEndian>(l)
+                        .context("writing entry point string length")?;
+                }
+            }
+        }
+
         dest.write_u8(ResourceField::EndOfEntry.into())
             .map_err(|_| anyhow!("error writing end of index entry"))?;
 
@@ -870,12 +1018,69 @@ where
 }
 
 /// Write packed resources data, version 3.
-#[allow(clippy::cognitive_complexity)]
 pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
     modules: &[T],
     dest: &mut W,
     interior_padding: Option<BlobInteriorPadding>,
 ) -> Result<()> {
+    dest.write_all(HEADER_V3)?;
+
+    write_packed_resources_payload(
+        modules,
+        dest,
+        interior_padding,
+        &PackedResourcesLimits::default(),
+    )
+}
+
+/// Write packed resources data, version 4.
+///
+/// `bytecode_magic` is the magic number of the Python bytecode embedded
+/// in resources. `0` denotes an unknown magic number.
+pub fn write_packed_resources_v4<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
+    modules: &[T],
+    dest: &mut W,
+    interior_padding: Option<BlobInteriorPadding>,
+    bytecode_magic: u32,
+) -> Result<()> {
+    write_packed_resources_v4_with_limits(
+        modules,
+        dest,
+        interior_padding,
+        bytecode_magic,
+        &PackedResourcesLimits::default(),
+    )
+}
+
+/// Write packed resources data, version 4, validating against limits.
+///
+/// Errors name the resource violating `limits`. If `limits` allows chunking,
+/// payloads too large for a `u32` length are stored in chunks.
+pub fn write_packed_resources_v4_with_limits<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
+    modules: &[T],
+    dest: &mut W,
+    interior_padding: Option<BlobInteriorPadding>,
+    bytecode_magic: u32,
+    limits: &PackedResourcesLimits,
+) -> Result<()> {
+    dest.write_all(HEADER_V4)?;
+    dest.write_u32::<LittleEndian>(bytecode_magic)?;
+
+    write_packed_resources_payload(modules, dest, interior_padding, limits)
+}
+
+/// Write the version independent portion of packed resources data.
+#[allow(clippy::cognitive_complexity)]
+fn write_packed_resources_payload<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
+    modules: &[T],
+    dest: &mut W,
+    interior_padding: Option<BlobInteriorPadding>,
+    limits: &PackedResourcesLimits,
+) -> Result<()> {
+    limits
+        .check_resource_count(modules.len())
+        .map_err(|e| anyhow!(e))?;
+
     let mut blob_sections = BTreeMap::new();
 
     let mut blob_section_count = 0;
@@ -917,7 +1122,7 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
 
     for module in modules {
         let module = module.as_ref();
-        module_index_length += module.index_v1_length();
+        module_index_length += module.index_v1_length(limits);
 
         process_field(&mut blob_sections, module, ResourceField::ModuleName);
         process_field(&mut blob_sections, module, ResourceField::InMemorySource);
@@ -998,6 +1203,21 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
             module,
             ResourceField::FileDataUtf8RelativePath,
         );
+        process_field(&mut blob_sections, module, ResourceField::ModuleAliasTarget);
+        process_field(&mut blob_sections, module, ResourceField::EntryPoints);
+
+        let blob_size = blob_sections
+            .values()
+            .map(|section: &BlobSection| section.raw_payload_length as u64)
+            .sum::<u64>();
+        if blob_size > limits.max_blob_size {
+            return Err(anyhow!(
+                "adding {} grows packed resources data to {} bytes, which exceeds the {} byte limit of the target platform",
+                module.name,
+                blob_size,
+                limits.max_blob_size
+            ));
+        }
     }
 
     for section in blob_sections.values() {
@@ -1005,12 +1225,16 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
         blob_index_length += section.index_v1_length();
     }
 
-    dest.write_all(HEADER_V3)?;
-
     dest.write_u8(blob_section_count)?;
-    dest.write_u32::<LittleEndian>(blob_index_length as u32)?;
-    dest.write_u32::<LittleEndian>(modules.len() as u32)?;
-    dest.write_u32::<LittleEndian>(module_index_length as u32)?;
+    dest.write_u32::<LittleEndian>(
+        u32::try_from(blob_index_length).context("converting blob index length to u32")?,
+    )?;
+    dest.write_u32::<LittleEndian>(
+        u32::try_from(modules.len()).context("converting resources count to u32")?,
+    )?;
+    dest.write_u32::<LittleEndian>(
+        u32::try_from(module_index_length).context("converting resources index length to u32")?,
+    )?;
 
     // Write the blob index.
     for section in blob_sections.values() {
@@ -1020,7 +1244,10 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
 
     // Write the resources index.
     for module in modules {
-        module.as_ref().write_index_v1(dest)?;
+        let module = module.as_ref();
+        module
+            .write_index_v1(dest, limits)
+            .with_context(|| format!("writing index entry for {}", module.name))?;
     }
     dest.write_u8(ResourceField::EndOfIndex.into())?;
 
@@ -1177,6 +1404,24 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
         }
     }
 
+    for module in modules {
+        if let Some(target) = &module.as_ref().module_alias_target {
+            dest.write_all(target.as_bytes())?;
+            add_interior_padding(dest)?;
+        }
+    }
+
+    for module in modules {
+        if let Some(entry_points) = &module.as_ref().entry_points {
+            for ep in entry_points {
+                for value in &[&ep.distribution, &ep.name, &ep.value] {
+                    dest.write_all(value.as_bytes())?;
+                    add_interior_padding(dest)?;
+                }
+            }
+        }
+    }
+
     Ok(())
 }
 
@@ -1208,6 +1453,95 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_write_limits() -> Result<()> {
+        let resources = vec![
+            Resource {
+                name: Cow::from("foo"),
+                in_memory_source: Some(Cow::from(b"import os".to_vec())),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("long_name"),
+                file_data_embedded: Some(Cow::from(b"data".to_vec())),
+                ..Resource::default()
+            },
+        ];
+
+        let write = |limits: PackedResourcesLimits| {
+            let mut data = Vec::new();
+            write_packed_resources_v4_with_limits(&resources, &mut data, None, 0, &limits)
+                .map_err(|e| format!("{:#}", e))
+        };
+
+        write(PackedResourcesLimits::default()).unwrap();
+
+        let err = write(PackedResourcesLimits {
+            max_name_length: 4,
+            ..PackedResourcesLimits::default()
+        })
+        .unwrap_err();
+        assert!(err.contains("name of long_name is 9 bytes"), "{}", err);
+
+        let err = write(PackedResourcesLimits {
+            max_resource_count: 1,
+            ..PackedResourcesLimits::default()
+        })
+        .unwrap_err();
+        assert_eq!(err, "2 resources exceeds the limit of 1 resources");
+
+        let err = write(PackedResourcesLimits {
+            max_blob_size: 16,
+            ..PackedResourcesLimits::default()
+        })
+        .unwrap_err();
+        assert!(err.starts_with("adding long_name grows"), "{}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_chunked_index() -> Result<()> {
+        let limits = PackedResourcesLimits {
+            allow_chunking: true,
+            max_chunk_length: 4,
+            ..PackedResourcesLimits::default()
+        };
+
+        let resource = Resource {
+            name: Cow::from("foo"),
+            in_memory_source: Some(Cow::from(b"012345678".to_vec())),
+            ..Resource::default()
+        };
+
+        let mut index = Vec::new();
+        resource.write_index_v1(&mut index, &limits)?;
+        assert_eq!(index.len(), resource.index_v1_length(&limits));
+
+        let mut expected = vec![ResourceField::StartOfEntry.into()];
+        expected.write_u8(ResourceField::ModuleName.into())?;
+        expected.write_u16::<LittleEndian>(3)?;
+        expected.write_u8(ResourceField::ChunkedPayload.into())?;
+        expected.write_u8(ResourceField::InMemorySource.into())?;
+        expected.write_u32::<LittleEndian>(3)?;
+        expected.write_u32::<LittleEndian>(4)?;
+        expected.write_u32::<LittleEndian>(4)?;
+        expected.write_u32::<LittleEndian>(1)?;
+        expected.write_u8(ResourceField::EndOfEntry.into())?;
+        assert_eq!(index, expected);
+
+        // Without chunking, a regular length field is written.
+        let mut index = Vec::new();
+        resource.write_index_v1(&mut index, &PackedResourcesLimits::default())?;
+        assert_eq!(
+            index.len(),
+            resource.index_v1_length(&PackedResourcesLimits::default())
+        );
+        assert!(!index.contains(&ResourceField::ChunkedPayload.into()));
+
+        Ok(())
+    }
+
     #[test]
     fn test_write_module_name() -> Result<()> {
         let mut data = Vec::new();
@@ -44,6 +44,7 @@ static RELEASE_ORDER: Lazy<Vec<&'static str>> = Lazy::new(|| {
         "tugger-licensing-net",
         "tugger-rpm",
         "tugger-snapcraft",
+        "tugger-flatpak",
         "tugger-apple",
         "tugger-apple-codesign",
         "tugger-windows",
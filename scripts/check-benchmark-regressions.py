@@ -0,0 +1,145 @@
+#!/usr/bin/env python3
+# This Source Code Form is subject to the terms of the Mozilla Public
+# License, v. 2.0. If a copy of the MPL was not distributed with this
+# file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+"""Compare criterion benchmark results between two commits.
+
+Absolute timings depend on the machine running the benchmarks. So instead of
+comparing against recorded timings, benchmark both commits on the same
+machine and compare the two runs::
+
+    $ git checkout main
+    $ scripts/check-benchmark-regressions.py run before
+    $ git checkout my-branch
+    $ scripts/check-benchmark-regressions.py run after
+    $ scripts/check-benchmark-regressions.py compare before after
+
+Results of each run are saved as a named criterion baseline under
+``target/criterion``.
+"""
+
+import argparse
+import json
+import pathlib
+import subprocess
+import sys
+
+
+# The checkout containing the working directory. This script can be run from a
+# copy outside the checkout to benchmark commits predating it.
+ROOT = pathlib.Path(
+    subprocess.check_output(["git", "rev-parse", "--show-toplevel"]).decode().strip()
+)
+CRITERION_DIR = ROOT / "target" / "criterion"
+
+# (package, bench target, features)
+BENCHMARKS = [
+    ("python-packed-resources", "packed_resources", None),
+    ("python-packed-resources", "encryption", "encryption"),
+    ("python-packaging", "resource_collection", None),
+]
+
+
+def run(name):
+    """Run all benchmarks, saving results as the named baseline.
+
+    Benchmarks that fail to build or run, e.g. because they don't exist at
+    the checked out commit, are reported and skipped.
+    """
+    failures = 0
+
+    for package, bench, features in BENCHMARKS:
+        args = ["cargo", "bench", "-p", package, "--bench", bench]
+        if features:
+            args.extend(["--features", features])
+        args.extend(["--", "--save-baseline", name])
+
+        print("running %s" % " ".join(args))
+        if subprocess.run(args, cwd=ROOT).returncode != 0:
+            print("%s/%s: benchmark failed; skipping" % (package, bench))
+            failures += 1
+
+    return failures
+
+
+def read_estimates(name):
+    """Find mean estimates of the named baseline, keyed by benchmark ID."""
+    estimates = {}
+
+    for path in CRITERION_DIR.glob("**/%s/estimates.json" % name):
+        benchmark_id = path.parent.parent.relative_to(CRITERION_DIR).as_posix()
+
+        with path.open("r", encoding="utf-8") as fh:
+            estimates[benchmark_id] = json.load(fh)["mean"]["point_estimate"]
+
+    return estimates
+
+
+def compare(base, new, threshold):
+    base_estimates = read_estimates(base)
+    new_estimates = read_estimates(new)
+
+    if not new_estimates:
+        print("no results for %s (run `%s run %s` first)" % (new, sys.argv[0], new))
+        return 1
+
+    regressions = 0
+
+    for benchmark_id, actual in sorted(new_estimates.items()):
+        expected = base_estimates.get(benchmark_id)
+        if expected is None:
+            print("%s: %.0f ns (new benchmark)" % (benchmark_id, actual))
+            continue
+
+        change = (actual - expected) / expected
+        status = "REGRESSED" if change > threshold else "ok"
+        if change > threshold:
+            regressions += 1
+
+        print(
+            "%s: %.0f ns (%s %.0f ns, %+.1f%%) %s"
+            % (benchmark_id, actual, base, expected, change * 100.0, status)
+        )
+
+    if regressions:
+        print(
+            "%d benchmarks regressed by more than %.0f%%"
+            % (regressions, threshold * 100.0)
+        )
+        return 1
+
+    return 0
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    subparsers = parser.add_subparsers(dest="command", required=True)
+
+    run_parser = subparsers.add_parser(
+        "run", help="Run benchmarks, saving results as a named baseline"
+    )
+    run_parser.add_argument("name", help="Name of the baseline to save")
+
+    compare_parser = subparsers.add_parser(
+        "compare", help="Compare the results of two runs"
+    )
+    compare_parser.add_argument("base", help="Name of the baseline to compare against")
+    compare_parser.add_argument("new", help="Name of the baseline to check")
+    compare_parser.add_argument(
+        "--threshold",
+        type=float,
+        default=0.10,
+        help="Fractional slowdown at which a benchmark is considered regressed",
+    )
+
+    args = parser.parse_args()
+
+    if args.command == "run":
+        return 1 if run(args.name) else 0
+    else:
+        return compare(args.base, args.new, args.threshold)
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -0,0 +1,335 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Checked extraction of Starlark function arguments.
+
+Arguments declared without a type in `starlark_module!` are received as
+`Value`. [FunctionArgs] converts them to Rust types. Type errors always
+have the form `<function> expects <expected> for <argument>; got <received>`.
+*/
+
+use {
+    starlark::values::{
+        error::{RuntimeError, ValueError, INCORRECT_PARAMETER_TYPE_ERROR_CODE},
+        Immutable, TypedValue, Value,
+    },
+    std::collections::HashMap,
+};
+
+/// Default value of arguments whose omission differs from passing `None`.
+///
+/// Declare arguments as `name = missing_argument()` and test for it with
+/// [FunctionArgs::is_missing].
+pub struct MissingArgument;
+
+impl TypedValue for MissingArgument {
+    type Holder = Immutable<MissingArgument>;
+    const TYPE: &'static str = "MissingArgument";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+}
+
+/// Obtain the default value of arguments that can be omitted.
+pub fn missing_argument() -> Value {
+    Value::new(MissingArgument)
+}
+
+/// Extracts the arguments of a Starlark function.
+#[derive(Clone, Copy, Debug)]
+pub struct FunctionArgs<'a> {
+    function: &'a str,
+}
+
+impl<'a> FunctionArgs<'a> {
+    /// Create an instance for a function.
+    ///
+    /// `function` names the function in errors. e.g. `PythonExecutable.pip_install()`.
+    pub fn new(function: &'a str) -> Self {
+        Self { function }
+    }
+
+    /// Construct the error for an argument not having the expected type.
+    ///
+    /// `received` describes what was passed. e.g. `int`.
+    pub fn type_error(&self, name: &str, expected: &str, received: &str) -> ValueError {
+        ValueError::from(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message: format!(
+                "{} expects {} for {}; got {}",
+                self.function, expected, name, received
+            ),
+            label: self.function.to_string(),
+        })
+    }
+
+    /// Whether an argument was omitted.
+    pub fn is_missing(&self, value: &Value) -> bool {
+        value.get_type() == MissingArgument::TYPE
+    }
+
+    /// Describe a received argument in errors.
+    pub fn received(&self, value: &Value) -> &'static str {
+        if self.is_missing(value) {
+            "nothing"
+        } else {
+            value.get_type()
+        }
+    }
+
+    /// Whether an argument was omitted or is `None`.
+    fn is_unset(&self, value: &Value) -> bool {
+        self.is_missing(value) || value.get_type() == "NoneType"
+    }
+
+    pub fn required_str(&self, name: &str, value: &Value) -> Result<String, ValueError> {
+        match value.get_type() {
+            "string" => Ok(value.to_str()),
+            _ => Err(self.type_error(name, "a string", self.received(value))),
+        }
+    }
+
+    /// Obtain a string argument that can be omitted or `None`.
+    pub fn optional_str(&self, name: &str, value: &Value) -> Result<Option<String>, ValueError> {
+        if self.is_unset(value) {
+            Ok(None)
+        } else {
+            self.required_str(name, value)
+                .map(Some)
+                .map_err(|_| self.type_error(name, "a string or None", value.get_type()))
+        }
+    }
+
+    pub fn required_bool(&self, name: &str, value: &Value) -> Result<bool, ValueError> {
+        match value.get_type() {
+            "bool" => Ok(value.to_bool()),
+            _ => Err(self.type_error(name, "a bool", self.received(value))),
+        }
+    }
+
+    /// Obtain a bool argument that can be omitted or `None`.
+    pub fn optional_bool(&self, name: &str, value: &Value) -> Result<Option<bool>, ValueError> {
+        if self.is_unset(value) {
+            Ok(None)
+        } else {
+            self.required_bool(name, value)
+                .map(Some)
+                .map_err(|_| self.type_error(name, "a bool or None", value.get_type()))
+        }
+    }
+
+    pub fn required_function(&self, name: &str, value: &Value) -> Result<Value, ValueError> {
+        match value.get_type() {
+            "function" => Ok(value.clone()),
+            _ => Err(self.type_error(name, "a function", self.received(value))),
+        }
+    }
+
+    /// Obtain a function argument that can be omitted or `None`.
+    pub fn optional_function(
+        &self,
+        name: &str,
+        value: &Value,
+    ) -> Result<Option<Value>, ValueError> {
+        if self.is_unset(value) {
+            Ok(None)
+        } else {
+            self.required_function(name, value)
+                .map(Some)
+                .map_err(|_| self.type_error(name, "a function or None", value.get_type()))
+        }
+    }
+
+    /// Obtain a value having the Starlark type named `type_name`.
+    pub fn required_value(
+        &self,
+        name: &str,
+        type_name: &str,
+        value: &Value,
+    ) -> Result<Value, ValueError> {
+        if value.get_type() == type_name {
+            Ok(value.clone())
+        } else {
+            Err(self.type_error(name, &format!("a {}", type_name), self.received(value)))
+        }
+    }
+
+    /// Obtain a value having the Starlark type named `type_name` that can be omitted or `None`.
+    pub fn optional_value(
+        &self,
+        name: &str,
+        type_name: &str,
+        value: &Value,
+    ) -> Result<Option<Value>, ValueError> {
+        if self.is_unset(value) {
+            Ok(None)
+        } else if value.get_type() == type_name {
+            Ok(Some(value.clone()))
+        } else {
+            Err(self.type_error(name, &format!("a {} or None", type_name), value.get_type()))
+        }
+    }
+
+    /// Obtain a list of ints.
+    pub fn list_of_int(&self, name: &str, value: &Value) -> Result<Vec<i64>, ValueError> {
+        const EXPECTED: &str = "a list of ints";
+
+        if value.get_type() != "list" {
+            return Err(self.type_error(name, EXPECTED, self.received(value)));
+        }
+
+        value
+            .iter()?
+            .iter()
+            .map(|v| match v.get_type() {
+                "int" => v.to_int(),
+                t => Err(self.type_error(name, EXPECTED, &format!("list containing {}", t))),
+            })
+            .collect()
+    }
+
+    /// Obtain a list of strings.
+    pub fn list_of_str(&self, name: &str, value: &Value) -> Result<Vec<String>, ValueError> {
+        const EXPECTED: &str = "a list of strings";
+
+        if value.get_type() != "list" {
+            return Err(self.type_error(name, EXPECTED, self.received(value)));
+        }
+
+        value
+            .iter()?
+            .iter()
+            .map(|v| match v.get_type() {
+                "string" => Ok(v.to_str()),
+                t => Err(self.type_error(name, EXPECTED, &format!("list containing {}", t))),
+            })
+            .collect()
+    }
+
+    /// Obtain a list of strings that can be omitted or `None`.
+    pub fn optional_list_of_str(
+        &self,
+        name: &str,
+        value: &Value,
+    ) -> Result<Option<Vec<String>>, ValueError> {
+        if self.is_unset(value) {
+            Ok(None)
+        } else {
+            self.list_of_str(name, value).map(Some)
+        }
+    }
+
+    /// Obtain a dict having string keys and values.
+    pub fn dict_of_str_str(
+        &self,
+        name: &str,
+        value: &Value,
+    ) -> Result<HashMap<String, String>, ValueError> {
+        const EXPECTED: &str = "a dict of strings to strings";
+
+        if value.get_type() != "dict" {
+            return Err(self.type_error(name, EXPECTED, self.received(value)));
+        }
+
+        let mut res = HashMap::new();
+
+        for key in &value.iter()? {
+            if key.get_type() != "string" {
+                return Err(self.type_error(
+                    name,
+                    EXPECTED,
+                    &format!("dict with {} key", key.get_type()),
+                ));
+            }
+
+            let v = value.at(key.clone())?;
+            if v.get_type() != "string" {
+                return Err(self.type_error(
+                    name,
+                    EXPECTED,
+                    &format!("dict with {} value", v.get_type()),
+                ));
+            }
+
+            res.insert(key.to_str(), v.to_str());
+        }
+
+        Ok(res)
+    }
+
+    /// Obtain a dict having string keys and values that can be omitted or `None`.
+    pub fn optional_dict_of_str_str(
+        &self,
+        name: &str,
+        value: &Value,
+    ) -> Result<Option<HashMap<String, String>>, ValueError> {
+        if self.is_unset(value) {
+            Ok(None)
+        } else {
+            self.dict_of_str_str(name, value).map(Some)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, starlark::values::none::NoneType};
+
+    #[test]
+    fn test_missing_argument() {
+        let args = FunctionArgs::new("f()");
+        let missing = missing_argument();
+        let none = Value::from(NoneType::None);
+
+        assert!(args.is_missing(&missing));
+        assert!(!args.is_missing(&none));
+        assert_eq!(args.optional_str("a", &missing).unwrap(), None);
+        assert_eq!(args.optional_str("a", &none).unwrap(), None);
+        assert_eq!(
+            args.optional_str("a", &Value::from("foo")).unwrap(),
+            Some("foo".to_string())
+        );
+        match args.required_str("a", &missing) {
+            Err(ValueError::Runtime(e)) => {
+                assert_eq!(e.message, "f() expects a string for a; got nothing");
+                assert_eq!(e.label, "f()");
+            }
+            _ => panic!("expected a runtime error"),
+        }
+    }
+
+    #[test]
+    fn test_values() {
+        let args = FunctionArgs::new("f()");
+        let none = Value::from(NoneType::None);
+
+        assert_eq!(args.optional_value("a", "string", &none).unwrap(), None);
+        assert!(args
+            .required_value("a", "string", &Value::from("foo"))
+            .is_ok());
+        match args.optional_value("a", "string", &Value::from(1)) {
+            Err(ValueError::Runtime(e)) => {
+                assert_eq!(e.message, "f() expects a string or None for a; got int");
+            }
+            _ => panic!("expected a runtime error"),
+        }
+
+        assert_eq!(
+            args.list_of_int("a", &Value::from(vec![Value::from(1), Value::from(2)]))
+                .unwrap(),
+            vec![1, 2]
+        );
+        match args.list_of_int("a", &Value::from(vec![Value::from("x")])) {
+            Err(ValueError::Runtime(e)) => {
+                assert_eq!(
+                    e.message,
+                    "f() expects a list of ints for a; got list containing string"
+                );
+            }
+            _ => panic!("expected a runtime error"),
+        }
+    }
+}
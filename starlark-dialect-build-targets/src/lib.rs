@@ -2,9 +2,12 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+pub mod arguments;
+pub mod sandbox;
 pub mod testutil;
 
 use {
+    crate::sandbox::{Capability, SandboxPolicy},
     anyhow::{anyhow, Result},
     linked_hash_map::LinkedHashMap,
     path_dedot::ParseDot,
@@ -24,9 +27,12 @@ use {
     },
     std::{
         borrow::Cow,
-        collections::{BTreeMap, HashMap},
+        collections::{BTreeMap, BTreeSet, HashMap},
+        ffi::OsStr,
         os::raw::c_ulong,
         path::{Path, PathBuf},
+        process::{Command, ExitStatus, Stdio},
+        sync::Mutex,
     },
 };
 
@@ -37,6 +43,11 @@ pub enum RunMode {
     None,
     /// Target is run by executing a path.
     Path { path: PathBuf },
+    /// Target is run by passing a path to a runtime program.
+    ///
+    /// `runtime` holds the program to execute followed by its arguments.
+    /// The path and any additional arguments follow these.
+    Runtime { runtime: Vec<String>, path: PathBuf },
 }
 
 /// Represents a resolved target.
@@ -53,10 +64,9 @@ impl ResolvedTarget {
     pub fn run(&self) -> Result<()> {
         match &self.run_mode {
             RunMode::None => Ok(()),
-            RunMode::Path { path } => {
-                let status = std::process::Command::new(&path)
-                    .current_dir(&path.parent().unwrap())
-                    .status()?;
+            RunMode::Path { path } | RunMode::Runtime { path, .. } => {
+                let mut command = self.program_command()?;
+                let status = command.current_dir(path.parent().unwrap()).status()?;
 
                 if status.success() {
                     Ok(())
@@ -66,6 +76,117 @@ impl ResolvedTarget {
             }
         }
     }
+
+    /// Obtain a command that runs this target, if it can be run.
+    ///
+    /// `args` are passed to the process verbatim and the process is started in
+    /// `cwd`. Standard input, output, and error are inherited.
+    pub fn run_command<S: AsRef<OsStr>>(&self, args: &[S], cwd: &Path) -> Option<Command> {
+        self.command_with_args(args, cwd).ok()
+    }
+
+    fn command_with_args<S: AsRef<OsStr>>(&self, args: &[S], cwd: &Path) -> Result<Command> {
+        let mut command = self.program_command()?;
+        command
+            .args(args)
+            .current_dir(cwd)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit());
+
+        Ok(command)
+    }
+
+    /// Obtain a command executing the target without additional arguments.
+    fn program_command(&self) -> Result<Command> {
+        let absolute = |path: &PathBuf| {
+            // Relative program paths would otherwise be resolved against the
+            // working directory of the process.
+            if path.is_relative() {
+                std::env::current_dir()
+                    .map(|cwd| cwd.join(path))
+                    .unwrap_or_else(|_| path.clone())
+            } else {
+                path.clone()
+            }
+        };
+
+        match &self.run_mode {
+            RunMode::None => Err(anyhow!("target cannot be run")),
+            RunMode::Path { path } => Ok(Command::new(absolute(path))),
+            RunMode::Runtime { runtime, path } => {
+                let (program, runtime_args) = runtime.split_first().ok_or_else(|| {
+                    anyhow!("no runtime program defined to run {}", path.display())
+                })?;
+
+                let mut command = Command::new(program);
+                command.args(runtime_args).arg(absolute(path));
+
+                Ok(command)
+            }
+        }
+    }
+
+    /// Run this target with arguments from a working directory.
+    ///
+    /// Returns the exit code of the process. See [exit_code] for how it is
+    /// derived. Targets that cannot be run report success.
+    pub fn run_with_args<S: AsRef<OsStr>>(&self, args: &[S], cwd: &Path) -> Result<i32> {
+        if self.run_mode == RunMode::None {
+            return Ok(0);
+        }
+
+        let mut command = self.command_with_args(args, cwd)?;
+        let status = command
+            .status()
+            .map_err(|e| anyhow!("error running {:?}: {}", command, e))?;
+
+        Ok(exit_code(status))
+    }
+}
+
+/// Derive a process exit code from an [ExitStatus].
+///
+/// On Unix, a process terminated by signal N is reported as 128 + N, which
+/// mirrors the convention used by shells.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    1
+}
+
+/// Find names similar to `name` for suggesting in error messages.
+///
+/// Candidates within a Levenshtein distance of a third of the length of
+/// `name` (but at least 2) are returned, most similar first.
+pub fn similar_names<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<&'a str> {
+    let max_distance = std::cmp::max(2, name.chars().count() / 3);
+
+    let mut matches = candidates
+        .into_iter()
+        .map(|candidate| (strsim::levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect::<Vec<_>>();
+    matches.sort_unstable();
+
+    matches
+        .into_iter()
+        .map(|(_, candidate)| candidate)
+        .collect()
 }
 
 pub struct ResolvedTargetValue {
@@ -96,6 +217,9 @@ pub struct Target {
     /// Other targets this one depends on.
     pub depends: Vec<String>,
 
+    /// Human readable description of this target.
+    pub description: Option<String>,
+
     /// What calling callable returned, if it has been called.
     pub resolved_value: Option<Value>,
 
@@ -130,6 +254,14 @@ pub struct EnvironmentContext {
     /// Name of the default target.
     default_target: Option<String>,
 
+    /// Whether the default target was set explicitly via `set_default_target()`.
+    default_target_explicit: bool,
+
+    /// Alternate names for registered targets.
+    ///
+    /// Keys are alias names. Values are names of registered targets.
+    aliases: BTreeMap<String, String>,
+
     /// List of targets to resolve.
     resolve_targets: Option<Vec<String>>,
 
@@ -141,6 +273,12 @@ pub struct EnvironmentContext {
     ///
     /// This will change the default target to resolve.
     pub build_script_mode: bool,
+
+    /// Capabilities granted to builtins, if evaluation is sandboxed.
+    sandbox: Option<SandboxPolicy>,
+
+    /// Filesystem paths read during evaluation and target resolution.
+    read_paths: Mutex<BTreeSet<PathBuf>>,
 }
 
 impl EnvironmentContext {
@@ -155,9 +293,13 @@ impl EnvironmentContext {
             targets: BTreeMap::new(),
             targets_order: vec![],
             default_target: None,
+            default_target_explicit: false,
+            aliases: BTreeMap::new(),
             resolve_targets: None,
             default_build_script_target: None,
             build_script_mode: false,
+            sandbox: None,
+            read_paths: Mutex::new(BTreeSet::new()),
         }
     }
 
@@ -232,14 +374,103 @@ impl EnvironmentContext {
         self.default_target.as_deref()
     }
 
+    /// Set the default target to resolve.
+    ///
+    /// The name can be a target or an alias. A default set this way takes
+    /// precedence over `default=True` on `register_target()`.
+    pub fn set_default_target(&mut self, target: &str) -> Result<()> {
+        let target = self
+            .resolve_target_name(target)
+            .ok_or_else(|| anyhow!("{}", self.unknown_target_message(target)))?
+            .to_string();
+
+        self.default_target = Some(target);
+        self.default_target_explicit = true;
+
+        Ok(())
+    }
+
+    /// Obtain all registered target aliases.
+    pub fn aliases(&self) -> &BTreeMap<String, String> {
+        &self.aliases
+    }
+
+    /// Register an alternate name for a target.
+    ///
+    /// `target` can itself be an alias, in which case the new alias points at
+    /// the same target.
+    pub fn add_target_alias(&mut self, alias: &str, target: &str) -> Result<()> {
+        if self.targets.contains_key(alias) {
+            return Err(anyhow!(
+                "cannot add alias {}: a target with that name exists",
+                alias
+            ));
+        }
+
+        let target = self
+            .resolve_target_name(target)
+            .ok_or_else(|| anyhow!("{}", self.unknown_target_message(target)))?
+            .to_string();
+
+        self.aliases.insert(alias.to_string(), target);
+
+        Ok(())
+    }
+
+    /// Resolve a target or alias name to the name of a registered target.
+    pub fn resolve_target_name<'a>(&'a self, target: &'a str) -> Option<&'a str> {
+        if self.targets.contains_key(target) {
+            Some(target)
+        } else {
+            self.aliases
+                .get(target)
+                .map(|t| t.as_str())
+                .filter(|t| self.targets.contains_key(*t))
+        }
+    }
+
+    /// Obtain an error message for a target name that doesn't exist.
+    ///
+    /// The message suggests registered targets and aliases with similar names.
+    pub fn unknown_target_message(&self, target: &str) -> String {
+        let candidates = similar_names(
+            target,
+            self.targets
+                .keys()
+                .chain(self.aliases.keys())
+                .map(|name| name.as_str()),
+        );
+
+        if candidates.is_empty() {
+            format!("target {} does not exist", target)
+        } else {
+            format!(
+                "target {} does not exist; did you mean {}?",
+                target,
+                candidates
+                    .iter()
+                    .take(3)
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    }
+
     /// Obtain a named target.
+    ///
+    /// The name can be an alias.
     pub fn get_target(&self, target: &str) -> Option<&Target> {
-        self.targets.get(target)
+        self.targets.get(self.resolve_target_name(target)?)
     }
 
     /// Obtain a mutable named target.
+    ///
+    /// The name can be an alias.
     pub fn get_target_mut(&mut self, target: &str) -> Option<&mut Target> {
-        self.targets.get_mut(target)
+        let target = self.resolve_target_name(target)?.to_string();
+
+        self.targets.get_mut(&target)
     }
 
     /// Set the list of targets to resolve.
@@ -258,6 +489,7 @@ impl EnvironmentContext {
         target: String,
         callable: Value,
         depends: Vec<String>,
+        description: Option<String>,
         default: bool,
         default_build_script: bool,
     ) {
@@ -270,12 +502,13 @@ impl EnvironmentContext {
             Target {
                 callable,
                 depends,
+                description,
                 resolved_value: None,
                 built_target: None,
             },
         );
 
-        if default || self.default_target.is_none() {
+        if (default && !self.default_target_explicit) || self.default_target.is_none() {
             self.default_target = Some(target.clone());
         }
 
@@ -284,6 +517,63 @@ impl EnvironmentContext {
         }
     }
 
+    /// Obtain the sandbox policy, if evaluation is sandboxed.
+    pub fn sandbox(&self) -> Option<&SandboxPolicy> {
+        self.sandbox.as_ref()
+    }
+
+    /// Set the sandbox policy to apply to builtins.
+    pub fn set_sandbox(&mut self, policy: Option<SandboxPolicy>) {
+        self.sandbox = policy;
+    }
+
+    /// Record that a filesystem path is read as an input.
+    ///
+    /// Relative paths are resolved against the current working directory.
+    pub fn record_read_path(&self, path: &Path) {
+        let path = if path.is_relative() {
+            self.cwd.join(path)
+        } else {
+            path.to_path_buf()
+        };
+        let path = path.parse_dot().map(|p| p.to_path_buf()).unwrap_or(path);
+
+        self.read_paths.lock().unwrap().insert(path);
+    }
+
+    /// Filesystem paths read as inputs so far.
+    ///
+    /// Paths may refer to files or directories.
+    pub fn read_paths(&self) -> Vec<PathBuf> {
+        self.read_paths.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Ensure `operation` is allowed to perform a privileged operation.
+    ///
+    /// Always succeeds if evaluation isn't sandboxed. Paths of
+    /// [Capability::ReadPath] are recorded as read inputs.
+    pub fn check_capability(
+        &self,
+        operation: &str,
+        capability: &Capability,
+    ) -> Result<(), ValueError> {
+        if let Capability::ReadPath(path) = capability {
+            self.record_read_path(path);
+        }
+
+        if let Some(policy) = &self.sandbox {
+            policy.check(operation, capability).map_err(|message| {
+                ValueError::from(RuntimeError {
+                    code: "STARLARK_SANDBOX",
+                    message,
+                    label: operation.to_string(),
+                })
+            })?;
+        }
+
+        Ok(())
+    }
+
     /// Determine what targets should be resolved.
     ///
     /// This isn't the full list of targets that will be resolved, only the main
@@ -753,6 +1043,37 @@ pub fn get_context_value(type_values: &TypeValues) -> ValueResult {
         })
 }
 
+/// Ensure a builtin is allowed to perform a privileged operation.
+///
+/// Builtins must call this before performing any operation represented by a
+/// [Capability]. See the [sandbox] module.
+pub fn check_capability(
+    type_values: &TypeValues,
+    operation: &str,
+    capability: &Capability,
+) -> Result<(), ValueError> {
+    let raw_context = get_context_value(type_values)?;
+    let context = raw_context
+        .downcast_ref::<EnvironmentContext>()
+        .ok_or(ValueError::IncorrectParameterType)?;
+
+    context.check_capability(operation, capability)
+}
+
+/// Record that a filesystem path is read as an input.
+///
+/// Builtins that read files without checking a [Capability] should call this.
+pub fn record_read_path(type_values: &TypeValues, path: &Path) -> Result<(), ValueError> {
+    let raw_context = get_context_value(type_values)?;
+    let context = raw_context
+        .downcast_ref::<EnvironmentContext>()
+        .ok_or(ValueError::IncorrectParameterType)?;
+
+    context.record_read_path(path);
+
+    Ok(())
+}
+
 /// print(*args)
 fn starlark_print(type_values: &TypeValues, args: &[Value]) -> ValueResult {
     let raw_context = get_context_value(type_values)?;
@@ -775,7 +1096,7 @@ fn starlark_print(type_values: &TypeValues, args: &[Value]) -> ValueResult {
     Ok(Value::new(NoneType::None))
 }
 
-/// register_target(target, callable, depends=None, default=false)
+/// register_target(target, callable, depends=None, default=false, default_build_script=false, description=None)
 fn starlark_register_target(
     type_values: &TypeValues,
     target: String,
@@ -783,9 +1104,11 @@ fn starlark_register_target(
     depends: Value,
     default: bool,
     default_build_script: bool,
+    description: Value,
 ) -> ValueResult {
     required_type_arg("callable", "function", &callable)?;
     optional_list_arg("depends", "string", &depends)?;
+    let description = optional_str_arg("description", &description)?;
 
     let depends = match depends.get_type() {
         "list" => depends.iter()?.iter().map(|x| x.to_string()).collect(),
@@ -797,7 +1120,54 @@ fn starlark_register_target(
         .downcast_mut::<EnvironmentContext>()?
         .ok_or(ValueError::IncorrectParameterType)?;
 
-    context.register_target(target, callable, depends, default, default_build_script);
+    context.register_target(
+        target,
+        callable,
+        depends,
+        description,
+        default,
+        default_build_script,
+    );
+
+    Ok(Value::new(NoneType::None))
+}
+
+/// set_default_target(target)
+fn starlark_set_default_target(type_values: &TypeValues, target: String) -> ValueResult {
+    let raw_context = get_context_value(type_values)?;
+    let mut context = raw_context
+        .downcast_mut::<EnvironmentContext>()?
+        .ok_or(ValueError::IncorrectParameterType)?;
+
+    context.set_default_target(&target).map_err(|e| {
+        ValueError::from(RuntimeError {
+            code: "BUILD_TARGETS",
+            message: e.to_string(),
+            label: "set_default_target()".to_string(),
+        })
+    })?;
+
+    Ok(Value::new(NoneType::None))
+}
+
+/// add_target_alias(alias, target)
+fn starlark_add_target_alias(
+    type_values: &TypeValues,
+    alias: String,
+    target: String,
+) -> ValueResult {
+    let raw_context = get_context_value(type_values)?;
+    let mut context = raw_context
+        .downcast_mut::<EnvironmentContext>()?
+        .ok_or(ValueError::IncorrectParameterType)?;
+
+    context.add_target_alias(&alias, &target).map_err(|e| {
+        ValueError::from(RuntimeError {
+            code: "BUILD_TARGETS",
+            message: e.to_string(),
+            label: "add_target_alias()".to_string(),
+        })
+    })?;
 
     Ok(Value::new(NoneType::None))
 }
@@ -840,7 +1210,7 @@ fn starlark_resolve_target(
             Some(v) => Ok((*v).clone()),
             None => Err(ValueError::from(RuntimeError {
                 code: "BUILD_TARGETS",
-                message: format!("target {} does not exist", target),
+                message: context.unknown_target_message(&target),
                 label: "resolve_target()".to_string(),
             })),
         }?
@@ -953,9 +1323,26 @@ starlark_module! { build_targets_module =>
         callable,
         depends = NoneType::None,
         default: bool = false,
-        default_build_script: bool = false
+        default_build_script: bool = false,
+        description = NoneType::None
     ) {
-        starlark_register_target(env, target, callable, depends, default, default_build_script)
+        starlark_register_target(
+            env,
+            target,
+            callable,
+            depends,
+            default,
+            default_build_script,
+            description,
+        )
+    }
+
+    set_default_target(env env, target: String) {
+        starlark_set_default_target(env, target)
+    }
+
+    add_target_alias(env env, alias: String, target: String) {
+        starlark_add_target_alias(env, alias, target)
     }
 
     resolve_target(env env, call_stack cs, target: String) {
@@ -1001,6 +1388,8 @@ pub fn populate_environment(
         "resolve_target",
         "resolve_targets",
         "set_build_path",
+        "set_default_target",
+        "add_target_alias",
         ENVIRONMENT_CONTEXT_SYMBOL,
     ] {
         type_values.add_type_value(PlaceholderContext::TYPE, f, env.get(f)?);
@@ -1016,14 +1405,20 @@ pub fn build_target(
     call_stack: &mut CallStack,
     target: &str,
 ) -> Result<ResolvedTarget> {
-    let resolved_value = {
+    let (target, resolved_value) = {
         let context_value = get_context_value(type_values)
             .map_err(|_| anyhow!("unable to resolve context value"))?;
         let context = context_value
             .downcast_ref::<EnvironmentContext>()
             .ok_or_else(|| anyhow!("context has incorrect type"))?;
 
-        let v = if let Some(t) = context.get_target(target) {
+        // Aliases are built under the name of the target they refer to.
+        let target = context
+            .resolve_target_name(target)
+            .ok_or_else(|| anyhow!("{}", context.unknown_target_message(target)))?
+            .to_string();
+
+        let v = if let Some(t) = context.get_target(&target) {
             if let Some(t) = &t.built_target {
                 return Ok(t.clone());
             }
@@ -1037,7 +1432,7 @@ pub fn build_target(
             return Err(anyhow!("target {} is not resolved", target));
         };
 
-        v
+        (target, v)
     };
 
     let build = type_values
@@ -1048,7 +1443,7 @@ pub fn build_target(
         .call(
             call_stack,
             type_values,
-            vec![resolved_value, Value::from(target)],
+            vec![resolved_value, Value::from(target.as_str())],
             LinkedHashMap::new(),
             None,
             None,
@@ -1066,7 +1461,7 @@ pub fn build_target(
         .map_err(|_| anyhow!("unable to obtain mutable context"))?
         .ok_or_else(|| anyhow!("context has incorrect type"))?;
 
-    context.get_target_mut(target).unwrap().built_target = Some(resolved_target.inner.clone());
+    context.get_target_mut(&target).unwrap().built_target = Some(resolved_target.inner.clone());
 
     Ok(resolved_target.inner.clone())
 }
@@ -1079,7 +1474,9 @@ pub fn run_target(
     type_values: &TypeValues,
     call_stack: &mut CallStack,
     target: Option<&str>,
-) -> Result<()> {
+    args: &[impl AsRef<OsStr>],
+    cwd: &Path,
+) -> Result<i32> {
     let target = {
         // Block to avoid nested borrow.
         let context_value = get_context_value(type_values)
@@ -1097,7 +1494,7 @@ pub fn run_target(
 
     let resolved_target = build_target(env, type_values, call_stack, &target)?;
 
-    resolved_target.run()
+    resolved_target.run_with_args(args, cwd)
 }
 
 #[cfg(test)]
@@ -1105,6 +1502,26 @@ mod test {
     use super::*;
     use crate::testutil::*;
 
+    #[test]
+    fn test_similar_names() {
+        let known = ["add_include", "add_location", "add_source"];
+
+        assert_eq!(
+            similar_names("add_inclde", known.iter().copied()),
+            vec!["add_include"]
+        );
+        assert_eq!(
+            similar_names("add_sourec", known.iter().copied()),
+            vec!["add_source"]
+        );
+        assert!(similar_names("bogus", known.iter().copied()).is_empty());
+        assert!(similar_names("x", vec![]).is_empty());
+        assert_eq!(
+            similar_names("fo", vec!["foo", "f", "bar"]),
+            vec!["f", "foo"]
+        );
+    }
+
     #[test]
     fn test_register_target() -> Result<()> {
         let mut env = StarlarkEnvironment::new()?;
@@ -1152,4 +1569,199 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_target_aliases() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+        env.eval("def foo(): return 1")?;
+        env.eval("def bar(foo): return foo + 1")?;
+        env.eval("def baz(): return 3")?;
+        env.eval("register_target('foo', foo)")?;
+        env.eval("add_target_alias('f', 'foo')")?;
+        env.eval("add_target_alias('ff', 'f')")?;
+        env.eval("register_target('bar', bar, depends=['ff'], description='Bar target')")?;
+        env.eval("add_target_alias('b', 'bar')")?;
+        env.eval("set_default_target('b')")?;
+        env.eval("register_target('baz', baz, default=True)")?;
+
+        assert!(env.eval("add_target_alias('foo', 'bar')").is_err());
+        assert!(env.eval("set_default_target('missing')").is_err());
+
+        let err = env
+            .eval("add_target_alias('x', 'barr')")
+            .unwrap_err()
+            .to_string();
+        assert!(err.as_str().contains("did you mean bar, baz?"));
+
+        assert_eq!(env.eval("resolve_target('b')")?.to_int().unwrap(), 2);
+        assert_eq!(env.eval("resolve_target('f')")?.to_int().unwrap(), 1);
+
+        let err = env.eval("resolve_target('fo')").unwrap_err().to_string();
+        assert!(err
+            .as_str()
+            .contains("target fo does not exist; did you mean f, ff, foo?"));
+
+        let context_value = get_context_value(&env.type_values).unwrap();
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)
+            .unwrap();
+
+        assert_eq!(context.default_target(), Some("bar"));
+        assert_eq!(context.targets_to_resolve(), vec!["bar".to_string()]);
+        assert_eq!(context.resolve_target_name("ff"), Some("foo"));
+        assert_eq!(context.aliases().get("ff"), Some(&"foo".to_string()));
+        assert_eq!(
+            context.get_target("b").unwrap().description,
+            Some("Bar target".to_string())
+        );
+        assert_eq!(
+            context
+                .get_target("foo")
+                .unwrap()
+                .resolved_value
+                .as_ref()
+                .unwrap()
+                .to_int()
+                .unwrap(),
+            1
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn script_target(dir: &Path, body: &str) -> Result<ResolvedTarget> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join("app.sh");
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body))?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+
+        Ok(ResolvedTarget {
+            run_mode: RunMode::Path { path },
+            output_path: dir.to_path_buf(),
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_command_passthrough() -> Result<()> {
+        use std::io::Write;
+
+        let build_dir = tempfile::tempdir()?;
+        let cwd = tempfile::tempdir()?;
+
+        let target = script_target(
+            build_dir.path(),
+            "cat\nfor arg in \"$@\"; do echo \"[$arg]\"; done\npwd",
+        )?;
+
+        let mut child = target
+            .run_command(&["--release", "a b", ""], cwd.path())
+            .unwrap()
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        child.stdin.take().unwrap().write_all(b"from stdin\n")?;
+        let output = child.wait_with_output()?;
+
+        assert_eq!(exit_code(output.status), 0);
+        assert_eq!(
+            String::from_utf8(output.stdout)?,
+            format!(
+                "from stdin\n[--release]\n[a b]\n[]\n{}\n",
+                cwd.path().canonicalize()?.display()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_exit_code() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let no_args: &[&str] = &[];
+
+        let target = script_target(dir.path(), "exit 3")?;
+        assert_eq!(target.run_with_args(no_args, dir.path())?, 3);
+
+        let target = script_target(dir.path(), "kill -TERM $$")?;
+        assert_eq!(target.run_with_args(no_args, dir.path())?, 128 + 15);
+
+        let target = ResolvedTarget {
+            run_mode: RunMode::None,
+            output_path: dir.path().to_path_buf(),
+        };
+        assert_eq!(target.run_with_args(no_args, dir.path())?, 0);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_command_runtime() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        // The script isn't executable, so it only runs via the runtime.
+        let path = dir.path().join("app.sh");
+        std::fs::write(&path, "echo \"$0 $*\"\n")?;
+
+        let target = ResolvedTarget {
+            run_mode: RunMode::Runtime {
+                runtime: vec!["sh".to_string(), "-e".to_string()],
+                path: path.clone(),
+            },
+            output_path: dir.path().to_path_buf(),
+        };
+
+        let output = target
+            .run_command(&["a", "b"], dir.path())
+            .unwrap()
+            .stdout(Stdio::piped())
+            .output()?;
+
+        assert_eq!(exit_code(output.status), 0);
+        assert_eq!(
+            String::from_utf8(output.stdout)?,
+            format!("{} a b\n", path.display())
+        );
+
+        let target = ResolvedTarget {
+            run_mode: RunMode::Runtime {
+                runtime: vec![],
+                path,
+            },
+            output_path: dir.path().to_path_buf(),
+        };
+        assert!(target.run_command(&["a"], dir.path()).is_none());
+        assert!(target.run_with_args(&["a"], dir.path()).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_record_read_paths() {
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let context = EnvironmentContext::new(&logger, PathBuf::from("/project"));
+
+        context
+            .check_capability("test", &Capability::ReadPath(Path::new("src/../lib")))
+            .unwrap();
+        context
+            .check_capability("test", &Capability::Subprocess)
+            .unwrap();
+        context.record_read_path(Path::new("/other/file.txt"));
+        context.record_read_path(Path::new("lib"));
+
+        assert_eq!(
+            context.read_paths(),
+            vec![
+                PathBuf::from("/other/file.txt"),
+                PathBuf::from("/project/lib"),
+            ]
+        );
+    }
 }
@@ -0,0 +1,388 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Capability checks for sandboxed evaluation.
+
+When a [SandboxPolicy] is registered on an [crate::EnvironmentContext],
+Starlark builtins performing privileged operations must request the
+[Capability] they need via [crate::check_capability] before performing
+the operation. Requests not permitted by the policy abort evaluation.
+
+Without a registered policy, all capabilities are granted.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    path_dedot::ParseDot,
+    std::{
+        collections::BTreeSet,
+        fmt,
+        path::{Path, PathBuf},
+    },
+};
+
+/// A privileged operation requested by a Starlark builtin.
+#[derive(Clone, Copy, Debug)]
+pub enum Capability<'a> {
+    /// Read a file or directory from the filesystem.
+    ReadPath(&'a Path),
+
+    /// Spawn a subprocess.
+    Subprocess,
+
+    /// Connect to a network host.
+    Network(&'a str),
+}
+
+impl<'a> fmt::Display for Capability<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ReadPath(path) => write!(f, "read {}", path.display()),
+            Self::Subprocess => f.write_str("run a subprocess"),
+            Self::Network(host) => write!(f, "connect to {}", host),
+        }
+    }
+}
+
+/// Defines which capabilities are granted to sandboxed evaluation.
+///
+/// Filesystem reads are allowed beneath the directory of the evaluated
+/// config file and any additional read roots. Subprocesses are allowed for
+/// explicitly listed operations. Network access is allowed to explicitly
+/// listed hosts.
+#[derive(Clone, Debug, Default)]
+pub struct SandboxPolicy {
+    config_path: PathBuf,
+    read_roots: Vec<PathBuf>,
+    subprocess_operations: BTreeSet<String>,
+    network_hosts: BTreeSet<String>,
+}
+
+/// Normalize an operation name so `foo()` and `foo` are equivalent.
+fn normalize_operation(operation: &str) -> &str {
+    operation.trim().trim_end_matches("()")
+}
+
+/// Resolve a path to the form it will be accessed as.
+///
+/// `.` and `..` components are collapsed and the longest existing ancestor
+/// is canonicalized, so symlinks can't be used to escape a root.
+fn resolve_path(path: &Path) -> PathBuf {
+    let path = if path.is_relative() {
+        match std::env::current_dir() {
+            Ok(cwd) => cwd.join(path),
+            Err(_) => path.to_path_buf(),
+        }
+    } else {
+        path.to_path_buf()
+    };
+
+    let path = match path.parse_dot() {
+        Ok(p) => p.to_path_buf(),
+        Err(_) => path,
+    };
+
+    for ancestor in path.ancestors() {
+        if let Ok(canonical) = std::fs::canonicalize(ancestor) {
+            return match path.strip_prefix(ancestor) {
+                Ok(remainder) if !remainder.as_os_str().is_empty() => canonical.join(remainder),
+                _ => canonical,
+            };
+        }
+    }
+
+    path
+}
+
+/// Obtain the directory a glob pattern is rooted at.
+///
+/// This is the longest leading sequence of path components not containing
+/// glob metacharacters. Checking read access to it covers every path the
+/// pattern can match.
+pub fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|c| {
+            !c.as_os_str()
+                .to_string_lossy()
+                .contains(&['*', '?', '['][..])
+        })
+        .collect()
+}
+
+impl SandboxPolicy {
+    /// Construct a policy for evaluating the config file at `config_path`.
+    ///
+    /// The directory containing the config file is a read root.
+    pub fn new(config_path: &Path) -> Self {
+        let config_path = resolve_path(config_path);
+
+        let read_roots = match config_path.parent() {
+            Some(parent) => vec![parent.to_path_buf()],
+            None => vec![],
+        };
+
+        Self {
+            config_path,
+            read_roots,
+            ..Self::default()
+        }
+    }
+
+    /// Path of the config file being evaluated.
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+
+    /// Directories beneath which filesystem reads are allowed.
+    pub fn read_roots(&self) -> &[PathBuf] {
+        &self.read_roots
+    }
+
+    /// Allow filesystem reads beneath a directory.
+    pub fn add_read_root(&mut self, path: impl AsRef<Path>) {
+        self.read_roots.push(resolve_path(path.as_ref()));
+    }
+
+    /// Allow the named operation to spawn subprocesses.
+    ///
+    /// e.g. `PythonExecutable.pip_install`.
+    pub fn allow_subprocess(&mut self, operation: &str) {
+        self.subprocess_operations
+            .insert(normalize_operation(operation).to_string());
+    }
+
+    /// Allow network connections to a host.
+    pub fn allow_network_host(&mut self, host: &str) {
+        self.network_hosts.insert(host.trim().to_lowercase());
+    }
+
+    /// Load additional grants from a trust file.
+    ///
+    /// Each non-empty line not starting with `#` has the form
+    /// `<kind> <value>`, where `kind` is one of `read`, `subprocess`, or
+    /// `network`. Relative `read` paths are relative to the trust file.
+    pub fn load_trust_file(&mut self, path: &Path) -> Result<()> {
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("reading trust file {}", path.display()))?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+
+        for (i, line) in data.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, char::is_whitespace);
+            let kind = parts.next().unwrap_or_default();
+            let value = parts.next().unwrap_or_default().trim();
+
+            if value.is_empty() {
+                return Err(anyhow!(
+                    "{}:{}: missing value for {}",
+                    path.display(),
+                    i + 1,
+                    kind
+                ));
+            }
+
+            match kind {
+                "read" => self.add_read_root(base.join(value)),
+                "subprocess" => self.allow_subprocess(value),
+                "network" => self.allow_network_host(value),
+                _ => {
+                    return Err(anyhow!(
+                        "{}:{}: unknown trust file entry: {}",
+                        path.display(),
+                        i + 1,
+                        kind
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether `operation` is granted `capability`.
+    pub fn is_allowed(&self, operation: &str, capability: &Capability) -> bool {
+        match capability {
+            Capability::ReadPath(path) => {
+                let path = resolve_path(path);
+
+                self.read_roots.iter().any(|root| path.starts_with(root))
+            }
+            Capability::Subprocess => self
+                .subprocess_operations
+                .contains(normalize_operation(operation)),
+            Capability::Network(host) => self.network_hosts.contains(&host.to_lowercase()),
+        }
+    }
+
+    /// Check whether `operation` is granted `capability`.
+    ///
+    /// The error message names the operation, the capability, and the
+    /// config file being evaluated.
+    pub fn check(&self, operation: &str, capability: &Capability) -> Result<(), String> {
+        if self.is_allowed(operation, capability) {
+            return Ok(());
+        }
+
+        let hint = match capability {
+            Capability::ReadPath(_) => "add a `read <path>` entry to the trust file".to_string(),
+            Capability::Subprocess => format!(
+                "add a `subprocess {}` entry to the trust file",
+                normalize_operation(operation)
+            ),
+            Capability::Network(host) => {
+                format!("add a `network {}` entry to the trust file", host)
+            }
+        };
+
+        Err(format!(
+            "sandbox violation: {} is not allowed to {} while evaluating {} ({})",
+            normalize_operation(operation),
+            capability,
+            self.config_path.display(),
+            hint
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_roots() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("starlark-dialect-build-targets-test")
+            .tempdir()?;
+        let project = temp_dir.path().join("project");
+        let other = temp_dir.path().join("other");
+        std::fs::create_dir_all(&project)?;
+        std::fs::create_dir_all(&other)?;
+
+        let mut policy = SandboxPolicy::new(&project.join("pyoxidizer.bzl"));
+
+        let op = "read_package_root()";
+        assert!(policy.check(op, &Capability::ReadPath(&project)).is_ok());
+        assert!(policy
+            .check(op, &Capability::ReadPath(&project.join("missing/file.py")))
+            .is_ok());
+        assert!(policy
+            .check(op, &Capability::ReadPath(&project.join("../other")))
+            .is_err());
+
+        let err = policy.check(op, &Capability::ReadPath(&other)).unwrap_err();
+        assert!(err.starts_with("sandbox violation: read_package_root is not allowed to read "));
+        assert!(err.contains("pyoxidizer.bzl"));
+
+        policy.add_read_root(&other);
+        assert!(policy.check(op, &Capability::ReadPath(&other)).is_ok());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_symlink_escape() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("starlark-dialect-build-targets-test")
+            .tempdir()?;
+        let project = temp_dir.path().join("project");
+        let other = temp_dir.path().join("other");
+        std::fs::create_dir_all(&project)?;
+        std::fs::create_dir_all(&other)?;
+        std::os::unix::fs::symlink(&other, project.join("link"))?;
+
+        let policy = SandboxPolicy::new(&project.join("pyoxidizer.bzl"));
+        assert!(policy
+            .check("glob()", &Capability::ReadPath(&project.join("link")))
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_glob_base() {
+        assert_eq!(glob_base("/foo/bar/*.py"), PathBuf::from("/foo/bar"));
+        assert_eq!(glob_base("foo/**/bar/*.py"), PathBuf::from("foo"));
+        assert_eq!(glob_base("foo/ba[rz]/x.py"), PathBuf::from("foo"));
+        assert_eq!(glob_base("foo/bar.py"), PathBuf::from("foo/bar.py"));
+        assert_eq!(glob_base("*.py"), PathBuf::new());
+    }
+
+    #[test]
+    fn test_trust_file() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("starlark-dialect-build-targets-test")
+            .tempdir()?;
+        std::fs::create_dir_all(temp_dir.path().join("vendor"))?;
+
+        let trust_path = temp_dir.path().join("trust");
+        std::fs::write(
+            &trust_path,
+            "# comment\n\nread vendor\nsubprocess PythonExecutable.pip_install()\nnetwork PyPI.org\n",
+        )?;
+
+        let mut policy = SandboxPolicy::new(Path::new("/nonexistent/pyoxidizer.bzl"));
+
+        assert!(policy
+            .check("PythonExecutable.pip_install()", &Capability::Subprocess)
+            .is_err());
+        assert!(policy
+            .check(
+                "PythonExecutable.pip_download()",
+                &Capability::Network("pypi.org")
+            )
+            .is_err());
+
+        policy.load_trust_file(&trust_path)?;
+
+        assert!(policy
+            .check(
+                "glob()",
+                &Capability::ReadPath(&temp_dir.path().join("vendor/foo.py"))
+            )
+            .is_ok());
+        assert!(policy
+            .check("PythonExecutable.pip_install()", &Capability::Subprocess)
+            .is_ok());
+        assert!(policy
+            .check("PythonExecutable.pip_install", &Capability::Subprocess)
+            .is_ok());
+        assert_eq!(
+            policy
+                .check(
+                    "PythonExecutable.setup_py_install()",
+                    &Capability::Subprocess
+                )
+                .unwrap_err(),
+            "sandbox violation: PythonExecutable.setup_py_install is not allowed to run a \
+            subprocess while evaluating /nonexistent/pyoxidizer.bzl (add a `subprocess \
+            PythonExecutable.setup_py_install` entry to the trust file)"
+        );
+        assert!(policy
+            .check(
+                "default_python_distribution()",
+                &Capability::Network("pypi.org")
+            )
+            .is_ok());
+        assert!(policy
+            .check(
+                "default_python_distribution()",
+                &Capability::Network("github.com")
+            )
+            .is_err());
+
+        std::fs::write(&trust_path, "execute /bin/sh\n")?;
+        assert!(policy.load_trust_file(&trust_path).is_err());
+        std::fs::write(&trust_path, "read\n")?;
+        assert!(policy.load_trust_file(&trust_path).is_err());
+
+        Ok(())
+    }
+}
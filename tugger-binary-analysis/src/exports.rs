@@ -0,0 +1,172 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    anyhow::{anyhow, Result},
+    goblin::{
+        elf::{header as elf_header, sym},
+        mach::{constants::cputype, Mach, MachO},
+        pe::header as pe_header,
+        Object,
+    },
+    std::collections::BTreeSet,
+};
+
+/// Symbols exported by a shared library and the architectures it targets.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SharedLibraryExports {
+    /// Binary format. One of `elf`, `pe`, or `mach-o`.
+    pub format: &'static str,
+
+    /// Architectures the binary contains code for.
+    ///
+    /// Names are those returned by [normalize_architecture]. Mach-O
+    /// universal binaries can have multiple architectures.
+    pub architectures: Vec<String>,
+
+    /// Names of exported symbols.
+    ///
+    /// The leading underscore of Mach-O symbol names is removed. For
+    /// universal binaries, this is the union of symbols of all architectures.
+    pub symbols: BTreeSet<String>,
+}
+
+/// Normalize the architecture component of a target triple.
+///
+/// Variants of an instruction set (e.g. `i586` and `i686` or `armv7` and
+/// `thumbv7neon`) normalize to the same name so they can be compared against
+/// the architectures of a binary.
+pub fn normalize_architecture(arch: &str) -> String {
+    let arch = arch.split('-').next().unwrap_or(arch);
+
+    match arch {
+        "i386" | "i486" | "i586" | "i686" | "x86" => "x86",
+        "x86_64" | "amd64" => "x86_64",
+        "aarch64" | "arm64" | "arm64e" => "aarch64",
+        "powerpc64" | "powerpc64le" | "ppc64" | "ppc64le" => "powerpc64",
+        "powerpc" | "ppc" => "powerpc",
+        "s390x" | "s390" => "s390x",
+        _ if arch.starts_with("arm") || arch.starts_with("thumb") => "arm",
+        _ if arch.starts_with("riscv") => "riscv",
+        _ if arch.starts_with("mips") => "mips",
+        _ => arch,
+    }
+    .to_string()
+}
+
+fn elf_architecture(machine: u16) -> String {
+    match machine {
+        elf_header::EM_386 => "x86".to_string(),
+        elf_header::EM_X86_64 => "x86_64".to_string(),
+        elf_header::EM_AARCH64 => "aarch64".to_string(),
+        elf_header::EM_ARM => "arm".to_string(),
+        elf_header::EM_PPC64 => "powerpc64".to_string(),
+        elf_header::EM_PPC => "powerpc".to_string(),
+        elf_header::EM_S390 => "s390x".to_string(),
+        elf_header::EM_RISCV => "riscv".to_string(),
+        elf_header::EM_MIPS => "mips".to_string(),
+        _ => format!("elf-machine-{}", machine),
+    }
+}
+
+fn pe_architecture(machine: u16) -> String {
+    match machine {
+        pe_header::COFF_MACHINE_X86 => "x86".to_string(),
+        pe_header::COFF_MACHINE_X86_64 => "x86_64".to_string(),
+        pe_header::COFF_MACHINE_ARM64 => "aarch64".to_string(),
+        pe_header::COFF_MACHINE_ARMNT => "arm".to_string(),
+        _ => format!("pe-machine-{:#x}", machine),
+    }
+}
+
+fn mach_architecture(cpu: u32) -> String {
+    match cpu {
+        cputype::CPU_TYPE_X86 => "x86".to_string(),
+        cputype::CPU_TYPE_X86_64 => "x86_64".to_string(),
+        cputype::CPU_TYPE_ARM64 => "aarch64".to_string(),
+        cputype::CPU_TYPE_ARM => "arm".to_string(),
+        cputype::CPU_TYPE_POWERPC64 => "powerpc64".to_string(),
+        cputype::CPU_TYPE_POWERPC => "powerpc".to_string(),
+        _ => format!("mach-cputype-{}", cpu),
+    }
+}
+
+fn add_mach_symbols(macho: &MachO, symbols: &mut BTreeSet<String>) -> Result<()> {
+    for export in macho.exports()? {
+        symbols.insert(
+            export
+                .name
+                .strip_prefix('_')
+                .unwrap_or(&export.name)
+                .to_string(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Find the symbols exported by a shared library.
+///
+/// ELF, PE, and Mach-O binaries are supported.
+pub fn find_shared_library_exports(data: &[u8]) -> Result<SharedLibraryExports> {
+    match Object::parse(data)? {
+        Object::Elf(elf) => {
+            let symbols = elf
+                .dynsyms
+                .iter()
+                .filter(|s| {
+                    s.st_shndx != 0
+                        && (s.st_bind() == sym::STB_GLOBAL || s.st_bind() == sym::STB_WEAK)
+                })
+                .filter_map(|s| elf.dynstrtab.get(s.st_name).and_then(|name| name.ok()))
+                .filter(|name| !name.is_empty())
+                .map(|name| name.to_string())
+                .collect();
+
+            Ok(SharedLibraryExports {
+                format: "elf",
+                architectures: vec![elf_architecture(elf.header.e_machine)],
+                symbols,
+            })
+        }
+        Object::PE(pe) => Ok(SharedLibraryExports {
+            format: "pe",
+            architectures: vec![pe_architecture(pe.header.coff_header.machine)],
+            symbols: pe
+                .exports
+                .iter()
+                .filter_map(|export| export.name)
+                .map(|name| name.to_string())
+                .collect(),
+        }),
+        Object::Mach(Mach::Binary(macho)) => {
+            let mut symbols = BTreeSet::new();
+            add_mach_symbols(&macho, &mut symbols)?;
+
+            Ok(SharedLibraryExports {
+                format: "mach-o",
+                architectures: vec![mach_architecture(macho.header.cputype())],
+                symbols,
+            })
+        }
+        Object::Mach(Mach::Fat(multi)) => {
+            let mut architectures = vec![];
+            let mut symbols = BTreeSet::new();
+
+            for index in 0..multi.narches {
+                let macho = multi.get(index)?;
+                architectures.push(mach_architecture(macho.header.cputype()));
+                add_mach_symbols(&macho, &mut symbols)?;
+            }
+
+            Ok(SharedLibraryExports {
+                format: "mach-o",
+                architectures,
+                symbols,
+            })
+        }
+        Object::Archive(_) => Err(anyhow!("data is a static library archive")),
+        Object::Unknown(magic) => Err(anyhow!("unknown binary format (magic {:#x})", magic)),
+    }
+}
@@ -6,7 +6,7 @@ use std::{
     collections::{BTreeMap, BTreeSet},
     convert::TryFrom,
     ffi::OsStr,
-    io::Write,
+    io::{Read, Write},
     path::{Path, PathBuf},
 };
 
@@ -101,6 +101,59 @@ pub fn create_symlink(
     }
 }
 
+/// Read a file, refusing if any component of its path is a symlink.
+fn read_without_symlinks(path: &Path) -> Result<Vec<u8>, std::io::Error> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+
+    let checked = std::fs::symlink_metadata(&path)?;
+
+    for component in path.ancestors() {
+        if std::fs::symlink_metadata(component)?
+            .file_type()
+            .is_symlink()
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!(
+                    "refusing to read {} through symlink {}",
+                    path.display(),
+                    component.display()
+                ),
+            ));
+        }
+    }
+
+    let mut fh = std::fs::File::open(&path)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let opened = fh.metadata()?;
+
+        if opened.dev() != checked.dev() || opened.ino() != checked.ino() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!(
+                    "refusing to read {}: it changed while being opened",
+                    path.display()
+                ),
+            ));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = checked;
+
+    let mut data = Vec::new();
+    fh.read_to_end(&mut data)?;
+
+    Ok(data)
+}
+
 /// Represents an abstract location for binary data.
 ///
 /// Data can be backed by the filesystem or in memory.
@@ -125,6 +178,43 @@ impl FileData {
         }
     }
 
+    /// Resolve at most `limit` bytes from the start of the data for this instance.
+    ///
+    /// If backed by a file, only the requested bytes are read.
+    pub fn resolve_content_prefix(&self, limit: usize) -> Result<Vec<u8>, std::io::Error> {
+        match self {
+            Self::Path(p) => {
+                let mut data = Vec::new();
+                std::fs::File::open(p)?
+                    .take(limit as u64)
+                    .read_to_end(&mut data)?;
+
+                Ok(data)
+            }
+            Self::Memory(data) => Ok(data[0..limit.min(data.len())].to_vec()),
+        }
+    }
+
+    /// Resolve the data for this instance, optionally refusing symlinks.
+    ///
+    /// If `follow_symlinks` is false and this instance is backed by a path
+    /// with any component that is a symlink, an error is returned instead of
+    /// reading through it. This includes symlinks to system directories
+    /// (e.g. `/var` on macOS).
+    ///
+    /// On Unix, the opened file is verified to be the file that was checked,
+    /// so a symlink swapped into the path after the check is also refused.
+    /// Other platforms don't have this protection.
+    pub fn resolve_content_with_symlinks(
+        &self,
+        follow_symlinks: bool,
+    ) -> Result<Vec<u8>, std::io::Error> {
+        match self {
+            Self::Path(p) if !follow_symlinks => read_without_symlinks(p),
+            _ => self.resolve_content(),
+        }
+    }
+
     /// Convert this instance to a memory variant.
     ///
     /// This ensures any file-backed data is present in memory.
@@ -139,6 +229,17 @@ impl FileData {
             Self::Memory(_) => None,
         }
     }
+
+    /// Obtain a string describing how the data for this instance is stored.
+    ///
+    /// Returns `path` for file-backed data and `memory` otherwise. The data
+    /// isn't resolved.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Path(_) => "path",
+            Self::Memory(_) => "memory",
+        }
+    }
 }
 
 impl From<&Path> for FileData {
@@ -714,6 +815,55 @@ mod tests {
             .tempdir()
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_content_without_symlinks() -> Result<(), FileManifestError> {
+        let td = temp_dir()?;
+        // The temporary directory itself may be beneath a symlink.
+        let root = td.path().canonicalize()?;
+
+        std::fs::create_dir(root.join("real"))?;
+        std::fs::write(root.join("real").join("file"), b"data")?;
+        create_symlink(
+            root.join("real").join("link"),
+            root.join("real").join("file"),
+        )?;
+        create_symlink(root.join("linked-dir"), root.join("real"))?;
+
+        let direct = FileData::Path(root.join("real").join("file"));
+        assert_eq!(direct.resolve_content_with_symlinks(false)?, b"data");
+
+        for path in &[
+            root.join("real").join("link"),
+            root.join("linked-dir").join("file"),
+        ] {
+            let data = FileData::Path(path.clone());
+            assert_eq!(data.resolve_content_with_symlinks(true)?, b"data");
+
+            let err = data.resolve_content_with_symlinks(false).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_content_prefix() -> Result<(), FileManifestError> {
+        let td = temp_dir()?;
+        let path = td.path().join("file");
+        std::fs::write(&path, b"\x89PNG\r\n")?;
+
+        let data = FileData::Path(path);
+        assert_eq!(data.resolve_content_prefix(4)?, b"\x89PNG".to_vec());
+        assert_eq!(data.resolve_content_prefix(100)?, b"\x89PNG\r\n".to_vec());
+
+        let data = FileData::Memory(vec![1, 2, 3]);
+        assert_eq!(data.resolve_content_prefix(2)?, vec![1, 2]);
+        assert_eq!(data.resolve_content_prefix(4)?, vec![1, 2, 3]);
+
+        Ok(())
+    }
+
     #[test]
     fn test_add_file_entry() -> Result<(), FileManifestError> {
         let mut m = FileManifest::default();
@@ -0,0 +1,384 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::manifest::{FlatpakManifest, FlatpakModule, FlatpakSource},
+    anyhow::{anyhow, Context, Result},
+    std::path::{Path, PathBuf},
+    tugger_file_manifest::FileManifest,
+};
+
+/// Name of directory relative to the manifest holding staged files.
+pub const FILES_DIRECTORY: &str = "files";
+
+/// Prefix inside the Flatpak sandbox that applications are installed into.
+const INSTALL_PREFIX: &str = "/app";
+
+const DEFAULT_RUNTIME: &str = "org.freedesktop.Platform";
+const DEFAULT_RUNTIME_VERSION: &str = "20.08";
+const DEFAULT_SDK: &str = "org.freedesktop.Sdk";
+
+/// `finish-args` granting access to a graphical session.
+const GUI_FINISH_ARGS: &[&str] = &[
+    "--socket=wayland",
+    "--socket=fallback-x11",
+    "--share=ipc",
+    "--device=dri",
+];
+
+/// `finish-args` granting network access.
+const NETWORK_FINISH_ARGS: &[&str] = &["--share=network"];
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn manifest_path_string(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Entity used to produce a Flatpak manifest installing a set of files.
+///
+/// Instances hold a virtual file manifest of files to install into the
+/// `/app` prefix of the Flatpak sandbox along with the metadata needed to
+/// derive a manifest consumable by `flatpak-builder`.
+///
+/// When we `write_to_directory()`, the files are staged into a `files`
+/// directory next to a `<app id>.json` manifest whose single module
+/// installs every staged file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlatpakManifestBuilder {
+    app_id: String,
+    command: Option<String>,
+    runtime: String,
+    runtime_version: String,
+    sdk: String,
+    gui: bool,
+    network: bool,
+    install_files: FileManifest,
+    overrides: serde_json::Map<String, serde_json::Value>,
+}
+
+impl FlatpakManifestBuilder {
+    /// Create a new builder for an application with the given ID.
+    pub fn new(app_id: impl ToString) -> Self {
+        Self {
+            app_id: app_id.to_string(),
+            command: None,
+            runtime: DEFAULT_RUNTIME.to_string(),
+            runtime_version: DEFAULT_RUNTIME_VERSION.to_string(),
+            sdk: DEFAULT_SDK.to_string(),
+            gui: false,
+            network: false,
+            install_files: FileManifest::default(),
+            overrides: serde_json::Map::new(),
+        }
+    }
+
+    /// The application ID this builder is producing a manifest for.
+    pub fn app_id(&self) -> &str {
+        &self.app_id
+    }
+
+    /// The command to run when the application is launched.
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+
+    /// Set the command to run when the application is launched.
+    ///
+    /// Relative commands are resolved against `/app/bin`.
+    pub fn set_command(&mut self, command: impl ToString) {
+        self.command = Some(command.to_string());
+    }
+
+    /// The runtime the application runs against.
+    pub fn runtime(&self) -> &str {
+        &self.runtime
+    }
+
+    /// The version of the runtime the application runs against.
+    pub fn runtime_version(&self) -> &str {
+        &self.runtime_version
+    }
+
+    /// Set the runtime and runtime version to run against.
+    pub fn set_runtime(&mut self, runtime: impl ToString, version: impl ToString) {
+        self.runtime = runtime.to_string();
+        self.runtime_version = version.to_string();
+    }
+
+    /// The SDK used to build the application.
+    pub fn sdk(&self) -> &str {
+        &self.sdk
+    }
+
+    /// Set the SDK used to build the application.
+    pub fn set_sdk(&mut self, sdk: impl ToString) {
+        self.sdk = sdk.to_string();
+    }
+
+    /// Whether the application requires access to a graphical session.
+    pub fn gui(&self) -> bool {
+        self.gui
+    }
+
+    /// Set whether the application requires access to a graphical session.
+    pub fn set_gui(&mut self, value: bool) {
+        self.gui = value;
+    }
+
+    /// Whether the application requires network access.
+    pub fn network(&self) -> bool {
+        self.network
+    }
+
+    /// Set whether the application requires network access.
+    pub fn set_network(&mut self, value: bool) {
+        self.network = value;
+    }
+
+    /// Obtain the files to be installed into the sandbox prefix.
+    pub fn install_files(&self) -> &FileManifest {
+        &self.install_files
+    }
+
+    /// Add files to install from the content of an existing `FileManifest`.
+    pub fn install_manifest(&mut self, manifest: &FileManifest) -> Result<()> {
+        self.install_files.add_manifest(manifest)?;
+
+        Ok(())
+    }
+
+    /// Obtain top-level manifest keys that override generated values.
+    pub fn overrides(&self) -> &serde_json::Map<String, serde_json::Value> {
+        &self.overrides
+    }
+
+    /// Set a top-level manifest key, replacing any generated value.
+    pub fn set_override(&mut self, key: impl ToString, value: serde_json::Value) {
+        self.overrides.insert(key.to_string(), value);
+    }
+
+    /// The filename the manifest will be written to.
+    pub fn manifest_filename(&self) -> String {
+        format!("{}.json", self.app_id)
+    }
+
+    /// Derive the `finish-args` from the declared runtime requirements.
+    pub fn finish_args(&self) -> Vec<String> {
+        let mut args = vec![];
+
+        if self.gui {
+            args.extend(GUI_FINISH_ARGS.iter().map(|x| x.to_string()));
+        }
+        if self.network {
+            args.extend(NETWORK_FINISH_ARGS.iter().map(|x| x.to_string()));
+        }
+
+        args
+    }
+
+    /// Derive the shell commands installing staged files into the sandbox prefix.
+    pub fn build_commands(&self) -> Vec<String> {
+        self.install_files
+            .iter_entries()
+            .map(|(path, entry)| {
+                let path = manifest_path_string(path);
+                let dest = format!("{}/{}", INSTALL_PREFIX, path);
+
+                if let Some(target) = entry.link_target() {
+                    let parent = match dest.rfind('/') {
+                        Some(i) => &dest[0..i],
+                        None => INSTALL_PREFIX,
+                    };
+
+                    format!(
+                        "mkdir -p {} && ln -sf {} {}",
+                        shell_quote(parent),
+                        shell_quote(&manifest_path_string(target)),
+                        shell_quote(&dest)
+                    )
+                } else {
+                    format!(
+                        "install -Dm{} {} {}",
+                        if entry.is_executable() { "755" } else { "644" },
+                        shell_quote(&path),
+                        shell_quote(&dest)
+                    )
+                }
+            })
+            .collect::<Vec<_>>()
+    }
+
+    /// Resolve the `FlatpakManifest` described by this instance.
+    ///
+    /// Overrides are not reflected in the returned value.
+    pub fn manifest(&self) -> Result<FlatpakManifest> {
+        let command = self
+            .command
+            .clone()
+            .ok_or_else(|| anyhow!("command must be set to generate a Flatpak manifest"))?;
+
+        Ok(FlatpakManifest {
+            app_id: self.app_id.clone(),
+            runtime: self.runtime.clone(),
+            runtime_version: self.runtime_version.clone(),
+            sdk: self.sdk.clone(),
+            command,
+            finish_args: self.finish_args(),
+            modules: vec![FlatpakModule {
+                name: self.app_id.clone(),
+                buildsystem: "simple".to_string(),
+                build_commands: self.build_commands(),
+                sources: vec![FlatpakSource {
+                    source_type: "dir".to_string(),
+                    path: Some(FILES_DIRECTORY.to_string()),
+                    dest: None,
+                }],
+            }],
+        })
+    }
+
+    /// Resolve the manifest as a JSON value, with overrides applied.
+    pub fn to_json_value(&self) -> Result<serde_json::Value> {
+        let mut value = serde_json::to_value(self.manifest()?)?;
+
+        let map = value
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("Flatpak manifest did not serialize to an object"))?;
+        for (k, v) in &self.overrides {
+            map.insert(k.clone(), v.clone());
+        }
+
+        Ok(value)
+    }
+
+    /// Resolve the manifest as a pretty-printed JSON string.
+    pub fn to_json_string(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.to_json_value()?)?)
+    }
+
+    /// Write the manifest and staged files to a directory.
+    ///
+    /// Files are written to `files/` and the manifest to `<app id>.json`. The
+    /// directory can then be passed to `flatpak-builder`. Returns the path to
+    /// the written manifest.
+    pub fn write_to_directory(&self, dest_dir: impl AsRef<Path>) -> Result<PathBuf> {
+        let dest_dir = dest_dir.as_ref();
+
+        let json = self.to_json_string()?;
+
+        let files_dir = dest_dir.join(FILES_DIRECTORY);
+        self.install_files
+            .materialize_files_with_replace(&files_dir)
+            .with_context(|| format!("staging files to {}", files_dir.display()))?;
+
+        let manifest_path = dest_dir.join(self.manifest_filename());
+        std::fs::write(&manifest_path, json.as_bytes())
+            .with_context(|| format!("writing {}", manifest_path.display()))?;
+
+        Ok(manifest_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tugger_file_manifest::FileEntry};
+
+    fn builder() -> Result<FlatpakManifestBuilder> {
+        let mut manifest = FileManifest::default();
+        manifest.add_file_entry("bin/myapp", FileEntry::new_from_data(vec![42], true))?;
+        manifest.add_file_entry("lib/my app.py", FileEntry::new_from_data(vec![42], false))?;
+
+        let mut builder = FlatpakManifestBuilder::new("org.example.MyApp");
+        builder.set_command("myapp");
+        builder.install_manifest(&manifest)?;
+
+        Ok(builder)
+    }
+
+    #[test]
+    fn test_command_required() -> Result<()> {
+        let builder = FlatpakManifestBuilder::new("org.example.MyApp");
+        assert!(builder.manifest().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let mut builder = builder()?;
+        builder.set_gui(true);
+        builder.set_network(true);
+
+        let parsed: FlatpakManifest = serde_json::from_str(&builder.to_json_string()?)?;
+        assert_eq!(parsed, builder.manifest()?);
+        assert_eq!(parsed.command, "myapp");
+        assert!(parsed.finish_args.contains(&"--socket=wayland".to_string()));
+        assert!(parsed.finish_args.contains(&"--share=network".to_string()));
+
+        let commands = &parsed.modules[0].build_commands;
+        for (path, _) in builder.install_files().iter_entries() {
+            let dest = format!("'/app/{}'", manifest_path_string(path));
+            assert!(commands.iter().any(|c| c.ends_with(&dest)));
+        }
+        assert_eq!(
+            commands,
+            &vec![
+                "install -Dm755 'bin/myapp' '/app/bin/myapp'".to_string(),
+                "install -Dm644 'lib/my app.py' '/app/lib/my app.py'".to_string(),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_console_has_no_finish_args() -> Result<()> {
+        let builder = builder()?;
+
+        let value = builder.to_json_value()?;
+        assert!(value.get("finish-args").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_overrides() -> Result<()> {
+        let mut builder = builder()?;
+        builder.set_override("runtime-version", "21.08".into());
+        builder.set_override("tags", serde_json::json!(["beta"]));
+
+        let value = builder.to_json_value()?;
+        assert_eq!(value["runtime-version"], "21.08");
+        assert_eq!(value["tags"][0], "beta");
+        assert_eq!(value["app-id"], "org.example.MyApp");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_to_directory() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("tugger-flatpak-test")
+            .tempdir()?;
+
+        let builder = builder()?;
+        let manifest_path = builder.write_to_directory(temp_dir.path())?;
+
+        assert_eq!(
+            manifest_path,
+            temp_dir.path().join("org.example.MyApp.json")
+        );
+        let parsed: FlatpakManifest = serde_json::from_slice(&std::fs::read(&manifest_path)?)?;
+        assert_eq!(parsed.modules[0].sources[0].path.as_deref(), Some("files"));
+
+        for (path, _) in builder.install_files().iter_entries() {
+            assert!(temp_dir.path().join(FILES_DIRECTORY).join(path).exists());
+        }
+
+        Ok(())
+    }
+}
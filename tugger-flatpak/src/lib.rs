@@ -0,0 +1,13 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Functionality for the Flatpak packaging format. */
+
+mod builder;
+mod manifest;
+
+pub use {
+    builder::{FlatpakManifestBuilder, FILES_DIRECTORY},
+    manifest::{FlatpakManifest, FlatpakModule, FlatpakSource},
+};
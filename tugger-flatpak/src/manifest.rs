@@ -0,0 +1,73 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use serde::{Deserialize, Serialize};
+
+/// Represents an entry in a module's `sources` list.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FlatpakSource {
+    #[serde(rename = "type")]
+    pub source_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dest: Option<String>,
+}
+
+/// Represents an entry in a manifest's `modules` list.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FlatpakModule {
+    pub name: String,
+    pub buildsystem: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub build_commands: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<FlatpakSource>,
+}
+
+/// Represents a Flatpak manifest as consumed by `flatpak-builder`.
+///
+/// See https://docs.flatpak.org/en/latest/flatpak-builder-command-reference.html
+/// for the meaning of each field.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FlatpakManifest {
+    pub app_id: String,
+    pub runtime: String,
+    pub runtime_version: String,
+    pub sdk: String,
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub finish_args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modules: Vec<FlatpakModule>,
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, anyhow::Result};
+
+    #[test]
+    fn test_kebab_case_keys() -> Result<()> {
+        let manifest = FlatpakManifest {
+            app_id: "org.example.App".into(),
+            runtime: "org.freedesktop.Platform".into(),
+            runtime_version: "20.08".into(),
+            sdk: "org.freedesktop.Sdk".into(),
+            command: "app".into(),
+            finish_args: vec!["--share=network".into()],
+            modules: vec![],
+        };
+
+        let value = serde_json::to_value(&manifest)?;
+        assert_eq!(value["app-id"], "org.example.App");
+        assert_eq!(value["runtime-version"], "20.08");
+        assert_eq!(value["finish-args"][0], "--share=network");
+        assert!(value.get("modules").is_none());
+
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@
 
 mod builder;
 mod yaml;
+mod yaml_builder;
 
 pub use {
     builder::{SnapcraftBuilder, SnapcraftInvocation},
@@ -13,4 +14,5 @@ pub use {
         Adapter, Architecture, Architectures, BuildAttribute, Confinement, Daemon, Grade,
         RestartCondition, SnapApp, SnapPart, Snapcraft, SourceType, Type,
     },
+    yaml_builder::{SnapcraftYamlBuilder, FILES_DIRECTORY},
 };
@@ -0,0 +1,263 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::yaml::{SnapApp, SnapPart, Snapcraft},
+    anyhow::{anyhow, Context, Result},
+    std::{
+        borrow::Cow,
+        path::{Path, PathBuf},
+    },
+    tugger_file_manifest::FileManifest,
+};
+
+/// Name of directory relative to the project holding staged files.
+///
+/// This is also the name of the part that installs these files.
+pub const FILES_DIRECTORY: &str = "files";
+
+/// Plugs granting access to a graphical session.
+const GUI_PLUGS: &[&str] = &["desktop", "desktop-legacy", "wayland", "x11", "opengl"];
+
+/// Plugs granting network access.
+const NETWORK_PLUGS: &[&str] = &["network"];
+
+/// Entity used to produce a `snapcraft.yaml` installing a set of files.
+///
+/// Unlike `SnapcraftBuilder`, this type doesn't invoke `snapcraft`. Instead,
+/// it derives a project directory containing staged files and a
+/// `snap/snapcraft.yaml` consuming them, suitable for handing off to
+/// `snapcraft` or a build service.
+///
+/// The generated `snapcraft.yaml` contains a `dump` part sourcing the staged
+/// files and an app named after the snap running the configured command.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapcraftYamlBuilder<'a> {
+    snap: Snapcraft<'a>,
+    command: Option<Cow<'a, str>>,
+    gui: bool,
+    network: bool,
+    install_files: FileManifest,
+}
+
+impl<'a> SnapcraftYamlBuilder<'a> {
+    /// Create a new builder deriving from an existing `snapcraft.yaml` definition.
+    pub fn new(snap: Snapcraft<'a>) -> Self {
+        Self {
+            snap,
+            command: None,
+            gui: false,
+            network: false,
+            install_files: FileManifest::default(),
+        }
+    }
+
+    /// The command the generated app runs, relative to the snap root.
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+
+    /// Set the command the generated app runs, relative to the snap root.
+    pub fn set_command(&mut self, command: impl Into<Cow<'a, str>>) {
+        self.command = Some(command.into());
+    }
+
+    /// Whether the application requires access to a graphical session.
+    pub fn gui(&self) -> bool {
+        self.gui
+    }
+
+    /// Set whether the application requires access to a graphical session.
+    pub fn set_gui(&mut self, value: bool) {
+        self.gui = value;
+    }
+
+    /// Whether the application requires network access.
+    pub fn network(&self) -> bool {
+        self.network
+    }
+
+    /// Set whether the application requires network access.
+    pub fn set_network(&mut self, value: bool) {
+        self.network = value;
+    }
+
+    /// Obtain the files to be installed into the snap.
+    pub fn install_files(&self) -> &FileManifest {
+        &self.install_files
+    }
+
+    /// Add files to install from the content of an existing `FileManifest`.
+    pub fn install_manifest(&mut self, manifest: &FileManifest) -> Result<()> {
+        self.install_files.add_manifest(manifest)?;
+
+        Ok(())
+    }
+
+    /// Derive the plugs for the generated app from the declared runtime requirements.
+    pub fn plugs(&self) -> Vec<Cow<'a, str>> {
+        let mut plugs = vec![];
+
+        if self.gui {
+            plugs.extend(GUI_PLUGS.iter().map(|x| Cow::Borrowed(*x)));
+        }
+        if self.network {
+            plugs.extend(NETWORK_PLUGS.iter().map(|x| Cow::Borrowed(*x)));
+        }
+
+        plugs
+    }
+
+    /// Resolve the `Snapcraft` described by this instance.
+    pub fn snap(&self) -> Result<Snapcraft<'a>> {
+        let command = self
+            .command
+            .clone()
+            .ok_or_else(|| anyhow!("command must be set to generate snapcraft.yaml"))?;
+
+        let mut snap = self.snap.clone();
+
+        snap.add_part(
+            FILES_DIRECTORY.into(),
+            SnapPart {
+                plugin: Some("dump".into()),
+                source: Some(FILES_DIRECTORY.into()),
+                stage: self
+                    .install_files
+                    .iter_entries()
+                    .map(|(path, _)| Cow::Owned(path.to_string_lossy().replace('\\', "/")))
+                    .collect::<Vec<_>>(),
+                ..SnapPart::default()
+            },
+        );
+
+        let app_name = snap.name.clone();
+        snap.add_app(
+            app_name,
+            SnapApp {
+                command: Some(command),
+                plugs: self.plugs(),
+                ..SnapApp::default()
+            },
+        );
+
+        Ok(snap)
+    }
+
+    /// Resolve the `snapcraft.yaml` content as a string.
+    pub fn to_yaml_string(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(&self.snap()?)?)
+    }
+
+    /// Write `snap/snapcraft.yaml` and staged files to a directory.
+    ///
+    /// Files are written to `files/`. The directory can then be used as the
+    /// working directory of `snapcraft`. Returns the path to the written
+    /// `snapcraft.yaml`.
+    pub fn write_to_directory(&self, dest_dir: impl AsRef<Path>) -> Result<PathBuf> {
+        let dest_dir = dest_dir.as_ref();
+
+        let yaml = self.to_yaml_string()?;
+
+        let files_dir = dest_dir.join(FILES_DIRECTORY);
+        self.install_files
+            .materialize_files_with_replace(&files_dir)
+            .with_context(|| format!("staging files to {}", files_dir.display()))?;
+
+        let snap_dir = dest_dir.join("snap");
+        std::fs::create_dir_all(&snap_dir)
+            .with_context(|| format!("creating {}", snap_dir.display()))?;
+
+        let yaml_path = snap_dir.join("snapcraft.yaml");
+        std::fs::write(&yaml_path, yaml.as_bytes())
+            .with_context(|| format!("writing {}", yaml_path.display()))?;
+
+        Ok(yaml_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tugger_common::testutil::*, tugger_file_manifest::FileEntry};
+
+    fn builder() -> Result<SnapcraftYamlBuilder<'static>> {
+        let mut manifest = FileManifest::default();
+        manifest.add_file_entry("myapp", FileEntry::new_from_data(vec![42], true))?;
+        manifest.add_file_entry("lib/myapp.py", FileEntry::new_from_data(vec![42], false))?;
+
+        let mut builder = SnapcraftYamlBuilder::new(Snapcraft::new(
+            "myapp".into(),
+            "0.1".into(),
+            "summary".into(),
+            "description".into(),
+        ));
+        builder.set_command("myapp");
+        builder.install_manifest(&manifest)?;
+
+        Ok(builder)
+    }
+
+    #[test]
+    fn test_command_required() {
+        let builder = SnapcraftYamlBuilder::new(Snapcraft::new(
+            "myapp".into(),
+            "0.1".into(),
+            "summary".into(),
+            "description".into(),
+        ));
+        assert!(builder.snap().is_err());
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let mut builder = builder()?;
+        builder.set_gui(true);
+        builder.set_network(true);
+
+        let parsed: Snapcraft = serde_yaml::from_str(&builder.to_yaml_string()?)?;
+        assert_eq!(parsed, builder.snap()?);
+
+        let app = parsed.apps.get("myapp").unwrap();
+        assert_eq!(app.command.as_deref(), Some("myapp"));
+        assert!(app.plugs.contains(&"wayland".into()));
+        assert!(app.plugs.contains(&"network".into()));
+
+        let part = parsed.parts.get(FILES_DIRECTORY).unwrap();
+        assert_eq!(part.plugin.as_deref(), Some("dump"));
+        assert_eq!(part.source.as_deref(), Some(FILES_DIRECTORY));
+        for (path, _) in builder.install_files().iter_entries() {
+            assert!(part.stage.contains(&path.to_string_lossy()));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_console_has_no_plugs() -> Result<()> {
+        let builder = builder()?;
+
+        let snap = builder.snap()?;
+        assert!(snap.apps.get("myapp").unwrap().plugs.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_to_directory() -> Result<()> {
+        let dest_dir = DEFAULT_TEMP_DIR.path().join("test-yaml-builder-write");
+
+        let builder = builder()?;
+        let yaml_path = builder.write_to_directory(&dest_dir)?;
+
+        assert_eq!(yaml_path, dest_dir.join("snap").join("snapcraft.yaml"));
+        let parsed: Snapcraft = serde_yaml::from_slice(&std::fs::read(&yaml_path)?)?;
+        assert_eq!(parsed, builder.snap()?);
+
+        for (path, _) in builder.install_files().iter_entries() {
+            assert!(dest_dir.join(FILES_DIRECTORY).join(path).exists());
+        }
+
+        Ok(())
+    }
+}
@@ -6,13 +6,73 @@ use {
     crate::*,
     anyhow::{anyhow, Context, Result},
     slog::warn,
-    std::{borrow::Cow, collections::BTreeMap, io::Write, ops::Deref, path::Path},
+    std::{
+        borrow::Cow,
+        collections::BTreeMap,
+        convert::TryFrom,
+        fmt::{Display, Formatter},
+        io::Write,
+        ops::Deref,
+        path::Path,
+    },
     tugger_common::http::download_to_path,
     tugger_windows::{VcRedistributablePlatform, VC_REDIST_ARM64, VC_REDIST_X64, VC_REDIST_X86},
     uuid::Uuid,
     xml::{common::XmlVersion, writer::XmlEvent, EmitterConfig, EventWriter},
 };
 
+/// Arguments to pass to a bundle executable to install it silently.
+pub const BUNDLE_SILENT_INSTALL_ARGUMENTS: &str = "/quiet /norestart";
+
+/// Arguments to pass to a bundle executable to uninstall it silently.
+pub const BUNDLE_SILENT_UNINSTALL_ARGUMENTS: &str = "/uninstall /quiet /norestart";
+
+/// How a bundle relates to another bundle.
+///
+/// This corresponds to the `Action` attribute of `<RelatedBundle>`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RelatedBundleAction {
+    /// Detect the related bundle but take no action on it.
+    Detect,
+
+    /// Upgrade (remove) the related bundle when this bundle is installed.
+    Upgrade,
+
+    /// This bundle is an addon to the related bundle.
+    Addon,
+
+    /// This bundle is a patch for the related bundle.
+    Patch,
+}
+
+impl Display for RelatedBundleAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Detect => "Detect",
+            Self::Upgrade => "Upgrade",
+            Self::Addon => "Addon",
+            Self::Patch => "Patch",
+        })
+    }
+}
+
+impl TryFrom<&str> for RelatedBundleAction {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "detect" => Ok(Self::Detect),
+            "upgrade" => Ok(Self::Upgrade),
+            "addon" => Ok(Self::Addon),
+            "patch" => Ok(Self::Patch),
+            _ => Err(format!(
+                "{} is not a valid related bundle action; use detect, upgrade, addon, or patch",
+                value
+            )),
+        }
+    }
+}
+
 /// Entity used to build a WiX bundle installer.
 ///
 /// Bundle installers have multiple components in them.
@@ -35,6 +95,12 @@ pub struct WiXBundleInstallerBuilder<'a> {
     /// Conditions that must be met to perform the install.
     conditions: Vec<(String, String)>,
 
+    /// Other bundles this bundle relates to, keyed by their upgrade code.
+    related_bundles: Vec<(String, RelatedBundleAction)>,
+
+    /// Names of prerequisite packages installed before the main packages.
+    prerequisites: Vec<String>,
+
     /// Keys to define in the preprocessor when running candle.
     preprocess_parameters: BTreeMap<String, String>,
 
@@ -65,6 +131,40 @@ impl<'a> WiXBundleInstallerBuilder<'a> {
         }
     }
 
+    /// Set the UUID upgrade code of this bundle.
+    ///
+    /// Bundles sharing an upgrade code upgrade each other. If not set, a
+    /// deterministic code is derived from the bundle name.
+    pub fn set_upgrade_code(&mut self, code: impl ToString) -> Result<()> {
+        let code = code.to_string();
+        Uuid::parse_str(&code).with_context(|| format!("parsing upgrade code {}", code))?;
+        self.upgrade_code = Some(code);
+
+        Ok(())
+    }
+
+    /// Declare a relationship with another bundle identified by its upgrade code.
+    ///
+    /// This emits a `<RelatedBundle>` element. Use
+    /// `RelatedBundleAction::Upgrade` to have this bundle replace a bundle with
+    /// a different upgrade code, such as one shipped under a former product name.
+    pub fn add_related_bundle(
+        &mut self,
+        upgrade_code: impl ToString,
+        action: RelatedBundleAction,
+    ) -> Result<()> {
+        let code = upgrade_code.to_string();
+        Uuid::parse_str(&code).with_context(|| format!("parsing related bundle code {}", code))?;
+        self.related_bundles.push((code, action));
+
+        Ok(())
+    }
+
+    /// Names of prerequisite packages chained by this bundle.
+    pub fn prerequisites(&self) -> &[String] {
+        &self.prerequisites
+    }
+
     /// Define a `<bal:Condition>` that must be satisfied to run this installer.
     ///
     /// `message` is the message that will be displayed if the condition is not met.
@@ -106,6 +206,9 @@ impl<'a> WiXBundleInstallerBuilder<'a> {
         );
         download_to_path(logger, entry, &dest_path).context("downloading VC++ Redistributable")?;
 
+        self.prerequisites
+            .push(format!("Visual C++ Redistributable ({})", platform));
+
         self.chain(
             ExePackage {
                 id: Some(filename.clone().into()),
@@ -144,16 +247,7 @@ impl<'a> WiXBundleInstallerBuilder<'a> {
     /// Requisite files will be downloaded and this instance will be converted to
     /// a wxs file and registered with the builder.
     pub fn add_to_installer_builder(&self, builder: &mut WiXInstallerBuilder) -> Result<()> {
-        let mut emitter_config = EmitterConfig::new();
-        emitter_config.perform_indent = true;
-
-        let buffer = Vec::new();
-        let writer = std::io::BufWriter::new(buffer);
-        let mut emitter = emitter_config.create_writer(writer);
-        self.write_xml(&mut emitter)?;
-
-        let mut wxs =
-            WxsBuilder::from_data(Path::new("main.wxs"), emitter.into_inner().into_inner()?);
+        let mut wxs = WxsBuilder::from_data(Path::new("main.wxs"), self.to_wxs_xml()?);
         for (k, v) in &self.preprocess_parameters {
             wxs.set_preprocessor_parameter(k, v);
         }
@@ -180,6 +274,19 @@ impl<'a> WiXBundleInstallerBuilder<'a> {
         Ok(builder)
     }
 
+    /// Obtain the `.wxs` XML defining this bundle.
+    pub fn to_wxs_xml(&self) -> Result<Vec<u8>> {
+        let mut emitter_config = EmitterConfig::new();
+        emitter_config.perform_indent = true;
+
+        let buffer = Vec::new();
+        let writer = std::io::BufWriter::new(buffer);
+        let mut emitter = emitter_config.create_writer(writer);
+        self.write_xml(&mut emitter)?;
+
+        Ok(emitter.into_inner().into_inner()?)
+    }
+
     fn write_xml<W: Write>(&self, writer: &mut EventWriter<W>) -> Result<()> {
         writer.write(XmlEvent::StartDocument {
             version: XmlVersion::Version10,
@@ -224,6 +331,15 @@ impl<'a> WiXBundleInstallerBuilder<'a> {
         // </BootstrapperApplicationRef>
         writer.write(XmlEvent::end_element())?;
 
+        for (code, action) in &self.related_bundles {
+            writer.write(
+                XmlEvent::start_element("RelatedBundle")
+                    .attr("Id", code)
+                    .attr("Action", &action.to_string()),
+            )?;
+            writer.write(XmlEvent::end_element())?;
+        }
+
         for (message, condition) in &self.conditions {
             writer.write(XmlEvent::start_element("bal:Condition").attr("Message", message))?;
             writer.write(XmlEvent::CData(condition))?;
@@ -249,11 +365,171 @@ impl<'a> WiXBundleInstallerBuilder<'a> {
     pub fn default_exe_filename(&self) -> String {
         format!("{}-{}.exe", self.bundle_name, self.bundle_version)
     }
+
+    /// Obtain the default filename of the file describing how to deploy the bundle.
+    pub fn default_metadata_filename(&self) -> String {
+        format!("{}-{}.install.txt", self.bundle_name, self.bundle_version)
+    }
+
+    /// Obtain a human readable description of how to deploy the bundle.
+    ///
+    /// This documents the upgrade code, prerequisites, and the command lines
+    /// for performing silent installs and uninstalls, which is what
+    /// administrators need to deploy the bundle unattended.
+    pub fn deployment_metadata(&self) -> String {
+        let exe = self.default_exe_filename();
+
+        let mut lines = vec![
+            format!("name: {}", self.bundle_name),
+            format!("version: {}", self.bundle_version),
+            format!("manufacturer: {}", self.bundle_manufacturer),
+            format!("upgrade_code: {}", self.upgrade_code()),
+        ];
+
+        for (code, action) in &self.related_bundles {
+            lines.push(format!("related_bundle: {} {}", action, code));
+        }
+        for prerequisite in &self.prerequisites {
+            lines.push(format!("prerequisite: {}", prerequisite));
+        }
+
+        lines.push(format!(
+            "silent_install: {} {}",
+            exe, BUNDLE_SILENT_INSTALL_ARGUMENTS
+        ));
+        lines.push(format!(
+            "silent_uninstall: {} {}",
+            exe, BUNDLE_SILENT_UNINSTALL_ARGUMENTS
+        ));
+        lines.push(String::new());
+
+        lines.join("\n")
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use {super::*, crate::WiXBundleInstallerBuilder, tugger_common::testutil::*};
+    use {
+        super::*,
+        crate::WiXBundleInstallerBuilder,
+        tugger_common::testutil::*,
+        xml::{attribute::OwnedAttribute, reader::XmlEvent as ReaderEvent, EventReader},
+    };
+
+    const WIX_NS: &str = "http://schemas.microsoft.com/wix/2006/wi";
+
+    fn attribute<'b>(attributes: &'b [OwnedAttribute], name: &str) -> Option<&'b str> {
+        attributes
+            .iter()
+            .find(|a| a.name.local_name == name)
+            .map(|a| a.value.as_str())
+    }
+
+    /// Validate bundle XML against the constraints of the WiX schema we rely on.
+    ///
+    /// Returns the local names of elements in document order.
+    fn validate_bundle_xml(data: &[u8]) -> Result<Vec<String>> {
+        let mut elements = vec![];
+        let mut parents: Vec<String> = vec![];
+
+        for event in EventReader::new(data) {
+            match event? {
+                ReaderEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    let local = name.local_name.clone();
+
+                    match local.as_str() {
+                        "Wix" => {
+                            assert!(parents.is_empty(), "Wix is the root element");
+                            assert_eq!(name.namespace.as_deref(), Some(WIX_NS));
+                        }
+                        "Bundle" => {
+                            assert_eq!(parents.last().map(|s| s.as_str()), Some("Wix"));
+                            for required in &["Name", "Version", "Manufacturer", "UpgradeCode"] {
+                                assert!(
+                                    attribute(&attributes, required).is_some(),
+                                    "Bundle has {}",
+                                    required
+                                );
+                            }
+                            Uuid::parse_str(attribute(&attributes, "UpgradeCode").unwrap())?;
+                        }
+                        "RelatedBundle" => {
+                            assert_eq!(parents.last().map(|s| s.as_str()), Some("Bundle"));
+                            Uuid::parse_str(attribute(&attributes, "Id").unwrap())?;
+                            let action = attribute(&attributes, "Action").unwrap();
+                            assert!(RelatedBundleAction::try_from(action).is_ok());
+                        }
+                        "Chain" => {
+                            assert_eq!(parents.last().map(|s| s.as_str()), Some("Bundle"));
+                        }
+                        "MsiPackage" | "ExePackage" => {
+                            assert_eq!(parents.last().map(|s| s.as_str()), Some("Chain"));
+                            assert!(attribute(&attributes, "SourceFile").is_some());
+                        }
+                        _ => {}
+                    }
+
+                    parents.push(local.clone());
+                    elements.push(local);
+                }
+                ReaderEvent::EndElement { .. } => {
+                    parents.pop();
+                }
+                _ => {}
+            }
+        }
+
+        assert!(elements.iter().any(|e| e == "Chain"), "Chain is present");
+
+        Ok(elements)
+    }
+
+    #[test]
+    fn test_bundle_xml() -> Result<()> {
+        let mut bundle = WiXBundleInstallerBuilder::new(
+            "myapp".to_string(),
+            "0.1".to_string(),
+            "author".to_string(),
+        );
+        bundle.set_upgrade_code("a8f6a5d8-7a6b-4d1b-8b63-1f4b6f2a1c11")?;
+        bundle.add_related_bundle(
+            "5e1f4c38-9a3e-4bb0-a8a8-e0f8b7a6c5d4",
+            RelatedBundleAction::Upgrade,
+        )?;
+        bundle.chain(
+            MsiPackage {
+                source_file: Some("myapp.msi".into()),
+                ..MsiPackage::default()
+            }
+            .into(),
+        );
+
+        let elements = validate_bundle_xml(&bundle.to_wxs_xml()?)?;
+        assert_eq!(
+            elements,
+            vec![
+                "Wix",
+                "Bundle",
+                "BootstrapperApplicationRef",
+                "WixStandardBootstrapperApplication",
+                "RelatedBundle",
+                "Chain",
+                "MsiPackage"
+            ]
+        );
+
+        let metadata = bundle.deployment_metadata();
+        assert!(metadata.contains("upgrade_code: a8f6a5d8-7a6b-4d1b-8b63-1f4b6f2a1c11\n"));
+        assert!(metadata.contains("related_bundle: Upgrade 5e1f4c38-9a3e-4bb0-a8a8-e0f8b7a6c5d4\n"));
+        assert!(metadata.contains("silent_install: myapp-0.1.exe /quiet /norestart\n"));
+
+        assert!(bundle.set_upgrade_code("not-a-uuid").is_err());
+        assert!(RelatedBundleAction::try_from("bad").is_err());
+
+        Ok(())
+    }
 
     #[test]
     fn test_add_vc_redistributable() -> Result<()> {
@@ -281,6 +557,9 @@ mod tests {
             DEFAULT_DOWNLOAD_DIR.as_path(),
         )?;
 
+        assert_eq!(bundle.prerequisites().len(), 3);
+        validate_bundle_xml(&bundle.to_wxs_xml()?)?;
+
         Ok(())
     }
 
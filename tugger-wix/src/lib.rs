@@ -12,7 +12,10 @@ mod simple_msi_builder;
 mod wxs_builder;
 
 pub use {
-    bundle_builder::WiXBundleInstallerBuilder,
+    bundle_builder::{
+        RelatedBundleAction, WiXBundleInstallerBuilder, BUNDLE_SILENT_INSTALL_ARGUMENTS,
+        BUNDLE_SILENT_UNINSTALL_ARGUMENTS,
+    },
     chain::ChainElement,
     common::{run_candle, run_light, target_triple_to_wix_arch, write_file_manifest_to_wix},
     exe_package::{Behavior, ExePackage, ExitCode},
@@ -19,5 +19,6 @@ Rust crate and utility. It just happens to be developed alongside
 PyOxidizer.
 */
 
+pub mod package_manager_manifests;
 pub mod starlark;
 pub mod tarball;
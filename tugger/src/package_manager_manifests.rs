@@ -0,0 +1,500 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Generate package manager manifests for published archives.
+
+This module renders Homebrew formulae and Scoop manifests referencing
+archives produced by a build. Archives are located in a directory, hashed,
+and their download URLs derived from a URL template.
+
+Rendering is performed with Handlebars templates. Default templates are
+provided but can be replaced. String values in the template data are
+pre-escaped for use inside string literals of the target format (Ruby
+double-quoted strings for Homebrew and JSON strings for Scoop), so templates
+should not escape them again.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    handlebars::Handlebars,
+    serde::{Deserialize, Serialize},
+    sha2::Digest,
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Default Handlebars template for Homebrew formulae.
+pub const HOMEBREW_FORMULA_TEMPLATE: &str = include_str!("templates/homebrew-formula.rb.hbs");
+
+/// Default Handlebars template for Scoop manifests.
+pub const SCOOP_MANIFEST_TEMPLATE: &str = include_str!("templates/scoop-manifest.json.hbs");
+
+/// Homebrew platform names and the `on_<os>` block and CPU check they map to.
+const HOMEBREW_PLATFORMS: &[(&str, &str, &str)] = &[
+    ("linux-aarch64", "linux", "arm?"),
+    ("linux-x86_64", "linux", "intel?"),
+    ("macos-aarch64", "macos", "arm?"),
+    ("macos-x86_64", "macos", "intel?"),
+];
+
+/// Scoop architecture names.
+const SCOOP_ARCHITECTURES: &[&str] = &["32bit", "64bit", "arm64"];
+
+/// Configuration for rendering a Homebrew formula.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct HomebrewConfig {
+    /// Formula name. Converted to a Ruby class name for the formula.
+    pub name: String,
+    pub description: String,
+    pub homepage: String,
+    #[serde(default)]
+    pub license: Option<String>,
+    /// Paths within the archive to install into `bin`.
+    #[serde(default)]
+    pub bin: Vec<String>,
+    /// Mapping of platform (e.g. `macos-x86_64`) to archive filename.
+    pub platforms: BTreeMap<String, String>,
+    /// Handlebars template replacing the default formula template.
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// Configuration for rendering a Scoop manifest.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ScoopConfig {
+    /// Application name. Used as the manifest filename.
+    pub name: String,
+    pub description: String,
+    pub homepage: String,
+    #[serde(default)]
+    pub license: Option<String>,
+    /// Paths within the archive to expose as shims.
+    #[serde(default)]
+    pub bin: Vec<String>,
+    /// Mapping of architecture (e.g. `64bit`) to archive filename.
+    pub platforms: BTreeMap<String, String>,
+    /// Handlebars template replacing the default manifest template.
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// An archive referenced by a manifest.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ResolvedArtifact {
+    pub filename: String,
+    pub url: String,
+    pub sha256: String,
+}
+
+#[derive(Serialize)]
+struct HomebrewArtifact {
+    cpu: String,
+    url: String,
+    sha256: String,
+}
+
+#[derive(Serialize)]
+struct HomebrewOs {
+    name: String,
+    artifacts: Vec<HomebrewArtifact>,
+}
+
+#[derive(Serialize)]
+struct HomebrewData {
+    class_name: String,
+    description: String,
+    homepage: String,
+    version: String,
+    license: Option<String>,
+    oses: Vec<HomebrewOs>,
+    bin: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ScoopArchitecture {
+    name: String,
+    url: String,
+    sha256: String,
+}
+
+#[derive(Serialize)]
+struct ScoopData {
+    version: String,
+    description: String,
+    homepage: String,
+    license: Option<String>,
+    architectures: Vec<ScoopArchitecture>,
+    bin: Vec<String>,
+}
+
+/// Escape a string for use inside a Ruby double-quoted string literal.
+fn ruby_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("#{", "\\#{")
+}
+
+/// Escape a string for use inside a JSON string literal.
+fn json_escape(s: &str) -> String {
+    let quoted = serde_json::Value::String(s.to_string()).to_string();
+
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+/// Derive a Homebrew formula class name from a formula name.
+///
+/// e.g. `my-app` becomes `MyApp`.
+pub fn homebrew_class_name(name: &str) -> String {
+    name.split(&['-', '_', '.'][..])
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn render_template(template: &str, data: &impl Serialize) -> Result<String> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars.set_strict_mode(true);
+
+    handlebars
+        .render_template(template, data)
+        .context("rendering template")
+}
+
+/// Renders package manager manifests for archives in a directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackageManagerManifests {
+    /// Directory holding built archives.
+    pub artifacts_path: PathBuf,
+    /// Version of the application being published.
+    pub version: String,
+    /// Template for download URLs. `{version}` and `{filename}` are substituted.
+    pub url_template: String,
+    pub homebrew: Option<HomebrewConfig>,
+    pub scoop: Option<ScoopConfig>,
+}
+
+impl PackageManagerManifests {
+    /// Resolve the download URL for an archive filename.
+    pub fn artifact_url(&self, filename: &str) -> String {
+        self.url_template
+            .replace("{version}", &self.version)
+            .replace("{filename}", filename)
+    }
+
+    /// Resolve an archive referenced by a platform, hashing its content.
+    ///
+    /// Errors if the archive doesn't exist in the artifacts directory.
+    pub fn resolve_artifact(&self, platform: &str, filename: &str) -> Result<ResolvedArtifact> {
+        let path = self.artifacts_path.join(filename);
+
+        if !path.is_file() {
+            return Err(anyhow!(
+                "artifact {} for platform {} was not built (expected at {})",
+                filename,
+                platform,
+                path.display()
+            ));
+        }
+
+        let data = std::fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+
+        Ok(ResolvedArtifact {
+            filename: filename.to_string(),
+            url: self.artifact_url(filename),
+            sha256: hex::encode(sha2::Sha256::digest(&data)),
+        })
+    }
+
+    /// Render the Homebrew formula, if Homebrew is configured.
+    pub fn homebrew_formula(&self) -> Result<Option<String>> {
+        let config = if let Some(config) = &self.homebrew {
+            config
+        } else {
+            return Ok(None);
+        };
+
+        let mut oses: Vec<HomebrewOs> = vec![];
+
+        for (platform, filename) in &config.platforms {
+            let (_, os, cpu) = HOMEBREW_PLATFORMS
+                .iter()
+                .find(|(name, _, _)| name == platform)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "unknown Homebrew platform {}; expected one of {}",
+                        platform,
+                        HOMEBREW_PLATFORMS
+                            .iter()
+                            .map(|(name, _, _)| *name)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })?;
+
+            let artifact = self.resolve_artifact(platform, filename)?;
+            let artifact = HomebrewArtifact {
+                cpu: cpu.to_string(),
+                url: ruby_escape(&artifact.url),
+                sha256: artifact.sha256,
+            };
+
+            match oses.iter_mut().find(|entry| entry.name == *os) {
+                Some(entry) => entry.artifacts.push(artifact),
+                None => oses.push(HomebrewOs {
+                    name: os.to_string(),
+                    artifacts: vec![artifact],
+                }),
+            }
+        }
+
+        let data = HomebrewData {
+            class_name: homebrew_class_name(&config.name),
+            description: ruby_escape(&config.description),
+            homepage: ruby_escape(&config.homepage),
+            version: ruby_escape(&self.version),
+            license: config.license.as_deref().map(ruby_escape),
+            oses,
+            bin: config.bin.iter().map(|x| ruby_escape(x)).collect(),
+        };
+
+        let template = config
+            .template
+            .as_deref()
+            .unwrap_or(HOMEBREW_FORMULA_TEMPLATE);
+
+        Ok(Some(
+            render_template(template, &data).context("rendering Homebrew formula")?,
+        ))
+    }
+
+    /// Render the Scoop manifest, if Scoop is configured.
+    pub fn scoop_manifest(&self) -> Result<Option<String>> {
+        let config = if let Some(config) = &self.scoop {
+            config
+        } else {
+            return Ok(None);
+        };
+
+        let mut architectures = vec![];
+
+        for (platform, filename) in &config.platforms {
+            if !SCOOP_ARCHITECTURES.contains(&platform.as_str()) {
+                return Err(anyhow!(
+                    "unknown Scoop architecture {}; expected one of {}",
+                    platform,
+                    SCOOP_ARCHITECTURES.join(", ")
+                ));
+            }
+
+            let artifact = self.resolve_artifact(platform, filename)?;
+
+            architectures.push(ScoopArchitecture {
+                name: platform.clone(),
+                url: json_escape(&artifact.url),
+                sha256: artifact.sha256,
+            });
+        }
+
+        let data = ScoopData {
+            version: json_escape(&self.version),
+            description: json_escape(&config.description),
+            homepage: json_escape(&config.homepage),
+            license: config.license.as_deref().map(json_escape),
+            architectures,
+            bin: config.bin.iter().map(|x| json_escape(x)).collect(),
+        };
+
+        let template = config
+            .template
+            .as_deref()
+            .unwrap_or(SCOOP_MANIFEST_TEMPLATE);
+
+        Ok(Some(
+            render_template(template, &data).context("rendering Scoop manifest")?,
+        ))
+    }
+
+    /// Write rendered manifests to a directory.
+    ///
+    /// The Homebrew formula is written to `<name>.rb` and the Scoop manifest
+    /// to `<name>.json`, using the name from the respective configuration.
+    /// Returns the paths of written files.
+    pub fn write_to_directory(&self, dest_dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let dest_dir = dest_dir.as_ref();
+
+        let mut files = vec![];
+
+        if let (Some(config), Some(formula)) = (&self.homebrew, self.homebrew_formula()?) {
+            files.push((dest_dir.join(format!("{}.rb", config.name)), formula));
+        }
+        if let (Some(config), Some(manifest)) = (&self.scoop, self.scoop_manifest()?) {
+            files.push((dest_dir.join(format!("{}.json", config.name)), manifest));
+        }
+
+        std::fs::create_dir_all(dest_dir)
+            .with_context(|| format!("creating {}", dest_dir.display()))?;
+
+        for (path, content) in &files {
+            std::fs::write(path, content.as_bytes())
+                .with_context(|| format!("writing {}", path.display()))?;
+        }
+
+        Ok(files.into_iter().map(|(path, _)| path).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tugger_common::testutil::*};
+
+    fn manifests(name: &str) -> Result<PackageManagerManifests> {
+        let artifacts_path = DEFAULT_TEMP_DIR.path().join(name);
+        std::fs::create_dir_all(&artifacts_path)?;
+
+        for filename in &[
+            "myapp-1.0-x86_64-apple-darwin.tar.gz",
+            "myapp-1.0-aarch64-apple-darwin.tar.gz",
+            "myapp-1.0-x86_64-unknown-linux-gnu.tar.gz",
+            "myapp-1.0-x86_64-pc-windows-msvc.zip",
+        ] {
+            std::fs::write(artifacts_path.join(filename), filename.as_bytes())?;
+        }
+
+        Ok(PackageManagerManifests {
+            artifacts_path,
+            version: "1.0".to_string(),
+            url_template: "https://github.com/me/myapp/releases/download/{version}/{filename}"
+                .to_string(),
+            homebrew: Some(HomebrewConfig {
+                name: "my-app".to_string(),
+                description: "An \"app\" of mine".to_string(),
+                homepage: "https://example.com/myapp".to_string(),
+                license: Some("MPL-2.0".to_string()),
+                bin: vec!["myapp".to_string()],
+                platforms: [
+                    ("macos-x86_64", "myapp-1.0-x86_64-apple-darwin.tar.gz"),
+                    ("macos-aarch64", "myapp-1.0-aarch64-apple-darwin.tar.gz"),
+                    ("linux-x86_64", "myapp-1.0-x86_64-unknown-linux-gnu.tar.gz"),
+                ]
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+                template: None,
+            }),
+            scoop: Some(ScoopConfig {
+                name: "myapp".to_string(),
+                description: "An \"app\" of mine".to_string(),
+                homepage: "https://example.com/myapp".to_string(),
+                license: Some("MPL-2.0".to_string()),
+                bin: vec!["myapp.exe".to_string()],
+                platforms: [("64bit", "myapp-1.0-x86_64-pc-windows-msvc.zip")]
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+                template: None,
+            }),
+        })
+    }
+
+    #[test]
+    fn test_homebrew_class_name() {
+        assert_eq!(homebrew_class_name("myapp"), "Myapp");
+        assert_eq!(homebrew_class_name("my-app"), "MyApp");
+        assert_eq!(homebrew_class_name("my_app.cli"), "MyAppCli");
+    }
+
+    #[test]
+    fn test_homebrew_golden() -> Result<()> {
+        let manifests = manifests("package-manager-homebrew-golden")?;
+
+        assert_eq!(
+            manifests.homebrew_formula()?.unwrap(),
+            include_str!("testdata/golden-homebrew-formula.rb")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scoop_golden() -> Result<()> {
+        let manifests = manifests("package-manager-scoop-golden")?;
+
+        let manifest = manifests.scoop_manifest()?.unwrap();
+        assert_eq!(
+            manifest,
+            include_str!("testdata/golden-scoop-manifest.json")
+        );
+
+        let parsed: serde_json::Value = serde_json::from_str(&manifest)?;
+        assert_eq!(parsed["description"], "An \"app\" of mine");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_artifact() -> Result<()> {
+        let mut manifests = manifests("package-manager-missing")?;
+        manifests.scoop.as_mut().unwrap().platforms.insert(
+            "32bit".to_string(),
+            "myapp-1.0-i686-pc-windows-msvc.zip".to_string(),
+        );
+
+        let err = manifests.scoop_manifest().unwrap_err();
+        assert!(
+            format!("{}", err).contains("myapp-1.0-i686-pc-windows-msvc.zip for platform 32bit")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_platform() -> Result<()> {
+        let mut manifests = manifests("package-manager-unknown-platform")?;
+        manifests
+            .homebrew
+            .as_mut()
+            .unwrap()
+            .platforms
+            .insert("freebsd-x86_64".to_string(), "x.tar.gz".to_string());
+
+        assert!(manifests.homebrew_formula().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_template_override() -> Result<()> {
+        let mut manifests = manifests("package-manager-template")?;
+        manifests.homebrew.as_mut().unwrap().template =
+            Some("{{class_name}} {{version}}".to_string());
+
+        assert_eq!(manifests.homebrew_formula()?.unwrap(), "MyApp 1.0");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_to_directory() -> Result<()> {
+        let manifests = manifests("package-manager-write")?;
+        let dest_dir = DEFAULT_TEMP_DIR.path().join("package-manager-write-out");
+
+        let paths = manifests.write_to_directory(&dest_dir)?;
+        assert_eq!(
+            paths,
+            vec![dest_dir.join("my-app.rb"), dest_dir.join("myapp.json")]
+        );
+
+        Ok(())
+    }
+}
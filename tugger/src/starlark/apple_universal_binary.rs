@@ -17,7 +17,7 @@ use {
             starlark_signature_extraction, starlark_signatures,
         },
     },
-    starlark_dialect_build_targets::{get_context_value, EnvironmentContext},
+    starlark_dialect_build_targets::{get_context_value, sandbox::Capability, EnvironmentContext},
     std::{
         path::PathBuf,
         sync::{Arc, Mutex},
@@ -70,6 +70,8 @@ impl AppleUniversalBinaryValue {
                 .downcast_ref::<EnvironmentContext>()
                 .ok_or(ValueError::IncorrectParameterType)?;
 
+            context.check_capability(LABEL, &Capability::ReadPath(&context.cwd().join(&path)))?;
+
             context.cwd().to_path_buf()
         };
 
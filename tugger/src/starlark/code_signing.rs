@@ -20,7 +20,7 @@ use {
             starlark_signature_extraction, starlark_signatures,
         },
     },
-    starlark_dialect_build_targets::required_type_arg,
+    starlark_dialect_build_targets::{check_capability, required_type_arg, sandbox::Capability},
     std::{
         convert::TryInto,
         fmt::{Display, Formatter},
@@ -129,7 +129,13 @@ impl CodeSignerValue {
 
 // Starlark methods.
 impl CodeSignerValue {
-    fn from_pfx_file(path: String, password: String) -> ValueResult {
+    fn from_pfx_file(type_values: &TypeValues, path: String, password: String) -> ValueResult {
+        check_capability(
+            type_values,
+            "code_signer_from_pfx_file()",
+            &Capability::ReadPath(Path::new(&path)),
+        )?;
+
         let pfx_data = std::fs::read(&path).map_err(|e| {
             ValueError::Runtime(RuntimeError {
                 code: "TUGGER_CODE_SIGNING",
@@ -176,9 +182,15 @@ impl CodeSignerValue {
         Ok(Value::new(NoneType::None))
     }
 
-    fn chain_issuer_certificates_pem_file(&self, path: String) -> ValueResult {
+    fn chain_issuer_certificates_pem_file(
+        &self,
+        type_values: &TypeValues,
+        path: String,
+    ) -> ValueResult {
         let label = "chain_issuer_certificates_pem_file()";
 
+        check_capability(type_values, label, &Capability::ReadPath(Path::new(&path)))?;
+
         let mut signer = self.signer(label)?;
 
         error_context(label, || {
@@ -591,8 +603,8 @@ pub fn handle_file_manifest_signable_events(
 }
 
 starlark_module! { code_signing_module =>
-    code_signer_from_pfx_file(path: String, password: String) {
-        CodeSignerValue::from_pfx_file(path, password)
+    code_signer_from_pfx_file(env env, path: String, password: String) {
+        CodeSignerValue::from_pfx_file(env, path, password)
     }
 
     code_signer_from_windows_store_sha1_thumbprint(thumbprint: String, store: String = "my".to_string()) {
@@ -612,9 +624,9 @@ starlark_module! { code_signing_module =>
         this.activate(env)
     }
 
-    CodeSigner.chain_issuer_certificates_pem_file(this, path: String) {
+    CodeSigner.chain_issuer_certificates_pem_file(env env, this, path: String) {
         let this = this.downcast_ref::<CodeSignerValue>().unwrap();
-        this.chain_issuer_certificates_pem_file(path)
+        this.chain_issuer_certificates_pem_file(env, path)
     }
 
     CodeSigner.chain_issuer_certificates_macos_keychain(this) {
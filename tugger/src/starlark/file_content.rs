@@ -17,7 +17,8 @@ use {
         },
     },
     starlark_dialect_build_targets::{
-        get_context_value, optional_bool_arg, optional_str_arg, EnvironmentContext,
+        get_context_value, optional_bool_arg, optional_str_arg, sandbox::Capability,
+        EnvironmentContext,
     },
     std::{
         convert::TryFrom,
@@ -149,6 +150,11 @@ impl FileContentValue {
                 .downcast_ref::<EnvironmentContext>()
                 .ok_or(ValueError::IncorrectParameterType)?;
 
+            if let Some(path) = &path {
+                context
+                    .check_capability(LABEL, &Capability::ReadPath(&context.cwd().join(path)))?;
+            }
+
             context.cwd().to_path_buf()
         };
 
@@ -23,8 +23,8 @@ use {
         },
     },
     starlark_dialect_build_targets::{
-        get_context_value, optional_str_arg, EnvironmentContext, ResolvedTarget,
-        ResolvedTargetValue, RunMode,
+        check_capability, get_context_value, optional_str_arg, sandbox::Capability,
+        EnvironmentContext, ResolvedTarget, ResolvedTargetValue, RunMode,
     },
     std::{
         path::{Path, PathBuf},
@@ -237,12 +237,15 @@ impl FileManifestValue {
     /// FileManifest.add_path(path, strip_prefix, force_read=False)
     pub fn add_path(
         &mut self,
+        type_values: &TypeValues,
         path: String,
         strip_prefix: String,
         force_read: bool,
     ) -> ValueResult {
         const LABEL: &str = "FileManifest.add_path()";
 
+        check_capability(type_values, LABEL, &Capability::ReadPath(Path::new(&path)))?;
+
         let mut inner = self.inner(LABEL)?;
 
         error_context(LABEL, || {
@@ -394,9 +397,9 @@ starlark_module! { file_manifest_module =>
         this.add_file(content, path, directory)
     }
 
-    FileManifest.add_path(this, path: String, strip_prefix: String, force_read: bool = false) {
+    FileManifest.add_path(env env, this, path: String, strip_prefix: String, force_read: bool = false) {
         let mut this = this.downcast_mut::<FileManifestValue>().unwrap().unwrap();
-        this.add_path(path, strip_prefix, force_read)
+        this.add_path(env, path, strip_prefix, force_read)
     }
 
     FileManifest.build(env env, call_stack cs, this, target: String) {
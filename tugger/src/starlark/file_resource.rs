@@ -18,6 +18,7 @@ use {
     },
     starlark_dialect_build_targets::{
         get_context_value, optional_list_arg, optional_str_arg, required_list_arg,
+        sandbox::{glob_base, Capability},
         EnvironmentContext,
     },
     std::{collections::HashSet, convert::TryFrom},
@@ -65,6 +66,11 @@ fn starlark_glob(
         .downcast_ref::<EnvironmentContext>()
         .ok_or(ValueError::IncorrectParameterType)?;
 
+    for pattern in &include {
+        let base = context.cwd().join(glob_base(pattern));
+        context.check_capability("glob()", &Capability::ReadPath(&base))?;
+    }
+
     let manifest = error_context("glob()", || {
         let mut result = HashSet::new();
 
@@ -0,0 +1,339 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::{file_manifest::FileManifestValue, value_to_json},
+    slog::warn,
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            Mutable, TypedValue, Value, ValueResult,
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        arguments::FunctionArgs, get_context_value, EnvironmentContext, ResolvedTarget,
+        ResolvedTargetValue, RunMode,
+    },
+    std::sync::{Arc, Mutex, MutexGuard},
+    tugger_flatpak::FlatpakManifestBuilder,
+};
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_FLATPAK",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+#[derive(Clone)]
+pub struct FlatpakManifestBuilderValue {
+    inner: Arc<Mutex<FlatpakManifestBuilder>>,
+}
+
+impl TypedValue for FlatpakManifestBuilderValue {
+    type Holder = Mutable<FlatpakManifestBuilderValue>;
+    const TYPE: &'static str = "FlatpakManifestBuilder";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        let builder = self.inner()?;
+
+        Ok(match attribute {
+            "app_id" => Value::from(builder.app_id()),
+            "command" => match builder.command() {
+                Some(v) => Value::from(v),
+                None => Value::from(NoneType::None),
+            },
+            "gui" => Value::from(builder.gui()),
+            "network" => Value::from(builder.network()),
+            "runtime" => Value::from(builder.runtime()),
+            "runtime_version" => Value::from(builder.runtime_version()),
+            "sdk" => Value::from(builder.sdk()),
+            _ => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::GetAttr(attribute.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        })
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(matches!(
+            attribute,
+            "app_id" | "command" | "gui" | "network" | "runtime" | "runtime_version" | "sdk"
+        ))
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        let args = FunctionArgs::new("setattr()");
+        let mut builder = self.inner()?;
+
+        match attribute {
+            "command" => {
+                builder.set_command(args.required_str(attribute, &value)?);
+            }
+            "gui" => {
+                builder.set_gui(args.required_bool(attribute, &value)?);
+            }
+            "network" => {
+                builder.set_network(args.required_bool(attribute, &value)?);
+            }
+            "runtime" => {
+                let runtime = args.required_str(attribute, &value)?;
+                let version = builder.runtime_version().to_string();
+                builder.set_runtime(runtime, version);
+            }
+            "runtime_version" => {
+                let version = args.required_str(attribute, &value)?;
+                let runtime = builder.runtime().to_string();
+                builder.set_runtime(runtime, version);
+            }
+            "sdk" => {
+                builder.set_sdk(args.required_str(attribute, &value)?);
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FlatpakManifestBuilderValue {
+    fn inner(&self) -> Result<MutexGuard<'_, FlatpakManifestBuilder>, ValueError> {
+        self.inner.try_lock().map_err(|e| {
+            ValueError::Runtime(RuntimeError {
+                code: "TUGGER_FLATPAK",
+                message: format!("unable to obtain Flatpak builder lock: {:?}", e),
+                label: "FlatpakManifestBuilder".to_string(),
+            })
+        })
+    }
+
+    pub fn new_from_args(app_id: String, command: Value, manifest_overrides: Value) -> ValueResult {
+        const LABEL: &str = "FlatpakManifestBuilder()";
+
+        let command = FunctionArgs::new(LABEL).optional_str("command", &command)?;
+        let mut builder = FlatpakManifestBuilder::new(app_id);
+
+        if let Some(command) = command {
+            builder.set_command(command);
+        }
+
+        match manifest_overrides.get_type() {
+            "NoneType" => {}
+            "dict" => {
+                for key in &manifest_overrides.iter()? {
+                    let value = manifest_overrides.at(key.clone())?;
+                    builder.set_override(
+                        key.to_string(),
+                        value_to_json("manifest_overrides", &value)?,
+                    );
+                }
+            }
+            t => {
+                return Err(ValueError::from(RuntimeError {
+                    code: "TUGGER_FLATPAK",
+                    message: format!("manifest_overrides must be a dict; got {}", t),
+                    label: LABEL.to_string(),
+                }))
+            }
+        }
+
+        Ok(Value::new(Self {
+            inner: Arc::new(Mutex::new(builder)),
+        }))
+    }
+
+    /// FlatpakManifestBuilder.add_manifest(manifest)
+    pub fn add_manifest(&self, manifest: FileManifestValue) -> ValueResult {
+        const LABEL: &str = "FlatpakManifestBuilder.add_manifest()";
+
+        let manifest = manifest.inner(LABEL)?;
+        let mut builder = self.inner()?;
+
+        error_context(LABEL, || builder.install_manifest(&manifest))?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    /// FlatpakManifestBuilder.to_json()
+    pub fn to_json(&self) -> ValueResult {
+        const LABEL: &str = "FlatpakManifestBuilder.to_json()";
+
+        let builder = self.inner()?;
+
+        Ok(Value::from(error_context(LABEL, || {
+            builder.to_json_string()
+        })?))
+    }
+
+    /// FlatpakManifestBuilder.build(target)
+    pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        const LABEL: &str = "FlatpakManifestBuilder.build()";
+
+        let builder = self.inner()?;
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let output_directory = context.target_build_path(&target);
+
+        let manifest_path = error_context(LABEL, || builder.write_to_directory(&output_directory))?;
+
+        warn!(
+            context.logger(),
+            "wrote Flatpak manifest {}",
+            manifest_path.display()
+        );
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::None,
+                output_path: output_directory,
+            },
+        }))
+    }
+}
+
+starlark_module! { flatpak_module =>
+    #[allow(non_snake_case)]
+    FlatpakManifestBuilder(
+        app_id: String,
+        command = NoneType::None,
+        manifest_overrides = NoneType::None
+    ) {
+        FlatpakManifestBuilderValue::new_from_args(app_id, command, manifest_overrides)
+    }
+
+    FlatpakManifestBuilder.add_manifest(this, manifest: FileManifestValue) {
+        let this = this.downcast_ref::<FlatpakManifestBuilderValue>().unwrap();
+        this.add_manifest(manifest)
+    }
+
+    FlatpakManifestBuilder.to_json(this) {
+        let this = this.downcast_ref::<FlatpakManifestBuilderValue>().unwrap();
+        this.to_json()
+    }
+
+    FlatpakManifestBuilder.build(env env, this, target: String) {
+        let this = this.downcast_ref::<FlatpakManifestBuilderValue>().unwrap();
+        this.build(env, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::starlark::testutil::*,
+        anyhow::Result,
+        tugger_file_manifest::{FileEntry, FileManifest},
+        tugger_flatpak::FlatpakManifest,
+    };
+
+    #[test]
+    fn test_new() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let v = env.eval("FlatpakManifestBuilder('org.example.MyApp')")?;
+        assert_eq!(v.get_type(), "FlatpakManifestBuilder");
+
+        env.eval("builder = FlatpakManifestBuilder('org.example.MyApp', command = 'myapp')")?;
+        assert_eq!(env.eval("builder.app_id")?.to_string(), "org.example.MyApp");
+        assert_eq!(env.eval("builder.command")?.to_string(), "myapp");
+        assert!(!env.eval("builder.gui")?.to_bool());
+        assert!(!env.eval("builder.network")?.to_bool());
+
+        env.eval("builder.gui = True")?;
+        env.eval("builder.network = True")?;
+        env.eval("builder.runtime_version = '21.08'")?;
+        assert!(env.eval("builder.gui")?.to_bool());
+        assert!(env.eval("builder.network")?.to_bool());
+        assert_eq!(env.eval("builder.runtime_version")?.to_string(), "21.08");
+
+        assert!(env.eval("builder.command = None").is_err());
+        assert!(env.eval("builder.gui = 1").is_err());
+        assert!(env.eval("builder.sdk = None").is_err());
+        assert_eq!(env.eval("builder.command")?.to_string(), "myapp");
+        assert!(env
+            .eval("FlatpakManifestBuilder('org.example.MyApp', command = 1)")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_overrides() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("builder = FlatpakManifestBuilder('org.example.MyApp', command = 'myapp', manifest_overrides = {'tags': ['beta'], 'separate-locales': False})")?;
+        let json = env.eval("builder.to_json()")?.to_string();
+
+        let value: serde_json::Value = serde_json::from_str(&json)?;
+        assert_eq!(value["tags"][0], "beta");
+        assert_eq!(value["separate-locales"], false);
+
+        assert!(env
+            .eval("FlatpakManifestBuilder('org.example.MyApp', manifest_overrides = {'x': FileManifest()})")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_manifest() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let mut manifest = FileManifest::default();
+        manifest.add_file_entry("bin/myapp", FileEntry::new_from_data(vec![42], true))?;
+        manifest.add_file_entry("lib/myapp.py", FileEntry::new_from_data(vec![42], false))?;
+        env.env
+            .set(
+                "m",
+                FileManifestValue::new_from_manifest(manifest.clone()).unwrap(),
+            )
+            .unwrap();
+
+        env.eval("builder = FlatpakManifestBuilder('org.example.MyApp', command = 'myapp')")?;
+        env.eval("builder.network = True")?;
+        env.eval("builder.add_manifest(m)")?;
+
+        let json = env.eval("builder.to_json()")?.to_string();
+        let parsed: FlatpakManifest = serde_json::from_str(&json)?;
+        assert_eq!(parsed.finish_args, vec!["--share=network".to_string()]);
+
+        let commands = &parsed.modules[0].build_commands;
+        for (path, _) in manifest.iter_entries() {
+            let dest = format!("'/app/{}'", path.display());
+            assert!(commands.iter().any(|c| c.ends_with(&dest)));
+        }
+
+        Ok(())
+    }
+}
@@ -13,9 +13,12 @@ pub mod code_signing;
 pub mod file_content;
 pub mod file_manifest;
 pub mod file_resource;
+pub mod flatpak;
 pub mod macos_application_bundle_builder;
+pub mod package_manager_manifests;
 pub mod python_wheel_builder;
 pub mod snapcraft;
+pub mod snapcraft_yaml_builder;
 pub mod terminal;
 #[cfg(test)]
 mod testutil;
@@ -122,6 +125,39 @@ pub fn get_context_value(type_values: &TypeValues) -> ValueResult {
         })
 }
 
+/// Convert a Starlark value to its JSON equivalent.
+pub fn value_to_json(label: &str, value: &Value) -> Result<serde_json::Value, ValueError> {
+    Ok(match value.get_type() {
+        "NoneType" => serde_json::Value::Null,
+        "bool" => serde_json::Value::Bool(value.to_bool()),
+        "int" => serde_json::Value::from(value.to_int()?),
+        "string" => serde_json::Value::String(value.to_string()),
+        "list" | "tuple" => serde_json::Value::Array(
+            value
+                .iter()?
+                .iter()
+                .map(|v| value_to_json(label, &v))
+                .collect::<Result<Vec<_>, ValueError>>()?,
+        ),
+        "dict" => {
+            let mut map = serde_json::Map::new();
+            for key in &value.iter()? {
+                let v = value.at(key.clone())?;
+                map.insert(key.to_string(), value_to_json(label, &v)?);
+            }
+
+            serde_json::Value::Object(map)
+        }
+        t => {
+            return Err(ValueError::from(RuntimeError {
+                code: "TUGGER",
+                message: format!("cannot convert {} to JSON", t),
+                label: label.to_string(),
+            }))
+        }
+    })
+}
+
 /// Registers Tugger's Starlark dialect.
 pub fn register_starlark_dialect(
     env: &mut Environment,
@@ -132,9 +168,12 @@ pub fn register_starlark_dialect(
     file_content::file_content_module(env, type_values);
     file_manifest::file_manifest_module(env, type_values);
     file_resource::file_resource_module(env, type_values);
+    flatpak::flatpak_module(env, type_values);
     macos_application_bundle_builder::macos_application_bundle_builder_module(env, type_values);
+    package_manager_manifests::package_manager_manifests_module(env, type_values);
     python_wheel_builder::python_wheel_builder_module(env, type_values);
     snapcraft::snapcraft_module(env, type_values);
+    snapcraft_yaml_builder::snapcraft_yaml_builder_module(env, type_values);
     terminal::terminal_module(env, type_values);
     wix_bundle_builder::wix_bundle_builder_module(env, type_values);
     wix_installer::wix_installer_module(env, type_values);
@@ -0,0 +1,317 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{
+        package_manager_manifests::{HomebrewConfig, PackageManagerManifests, ScoopConfig},
+        starlark::value_to_json,
+    },
+    serde::de::DeserializeOwned,
+    slog::warn,
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, ValueError},
+            none::NoneType,
+            Mutable, TypedValue, Value, ValueResult,
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, EnvironmentContext, ResolvedTarget, ResolvedTargetValue, RunMode,
+    },
+};
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_PACKAGE_MANAGER_MANIFESTS",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+/// Convert an optional Starlark dict to a configuration struct.
+fn optional_config<T: DeserializeOwned>(
+    label: &str,
+    arg_name: &str,
+    value: &Value,
+) -> Result<Option<T>, ValueError> {
+    match value.get_type() {
+        "NoneType" => Ok(None),
+        "dict" => {
+            let json = value_to_json(arg_name, value)?;
+
+            Ok(Some(error_context(label, || {
+                serde_json::from_value(json)
+                    .map_err(|e| anyhow::anyhow!("invalid {} configuration: {}", arg_name, e))
+            })?))
+        }
+        t => Err(ValueError::from(RuntimeError {
+            code: "TUGGER_PACKAGE_MANAGER_MANIFESTS",
+            message: format!("{} must be a dict; got {}", arg_name, t),
+            label: label.to_string(),
+        })),
+    }
+}
+
+pub struct PackageManagerManifestsValue {
+    pub inner: PackageManagerManifests,
+}
+
+impl TypedValue for PackageManagerManifestsValue {
+    type Holder = Mutable<PackageManagerManifestsValue>;
+    const TYPE: &'static str = "PackageManagerManifests";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+}
+
+impl PackageManagerManifestsValue {
+    pub fn new_from_args(
+        type_values: &TypeValues,
+        archive_target: Value,
+        version: String,
+        url_template: String,
+        homebrew: Value,
+        scoop: Value,
+    ) -> ValueResult {
+        const LABEL: &str = "PackageManagerManifests()";
+
+        let artifacts_path = match archive_target.get_type() {
+            "string" => {
+                let context_value = get_context_value(type_values)?;
+                let context = context_value
+                    .downcast_ref::<EnvironmentContext>()
+                    .ok_or(ValueError::IncorrectParameterType)?;
+
+                context.target_build_path(&archive_target.to_string())
+            }
+            "ResolvedTarget" => archive_target
+                .downcast_ref::<ResolvedTargetValue>()
+                .unwrap()
+                .inner
+                .output_path
+                .clone(),
+            t => {
+                return Err(ValueError::from(RuntimeError {
+                    code: "TUGGER_PACKAGE_MANAGER_MANIFESTS",
+                    message: format!(
+                        "archive_target must be a target name or ResolvedTarget; got {}",
+                        t
+                    ),
+                    label: LABEL.to_string(),
+                }))
+            }
+        };
+
+        let homebrew: Option<HomebrewConfig> = optional_config(LABEL, "homebrew", &homebrew)?;
+        let scoop: Option<ScoopConfig> = optional_config(LABEL, "scoop", &scoop)?;
+
+        if homebrew.is_none() && scoop.is_none() {
+            return Err(ValueError::from(RuntimeError {
+                code: "TUGGER_PACKAGE_MANAGER_MANIFESTS",
+                message: "at least one of homebrew or scoop must be specified".to_string(),
+                label: LABEL.to_string(),
+            }));
+        }
+
+        Ok(Value::new(PackageManagerManifestsValue {
+            inner: PackageManagerManifests {
+                artifacts_path,
+                version,
+                url_template,
+                homebrew,
+                scoop,
+            },
+        }))
+    }
+
+    /// PackageManagerManifests.to_homebrew_formula()
+    pub fn to_homebrew_formula(&self) -> ValueResult {
+        let formula = error_context("PackageManagerManifests.to_homebrew_formula()", || {
+            self.inner.homebrew_formula()
+        })?;
+
+        Ok(match formula {
+            Some(formula) => Value::from(formula),
+            None => Value::from(NoneType::None),
+        })
+    }
+
+    /// PackageManagerManifests.to_scoop_manifest()
+    pub fn to_scoop_manifest(&self) -> ValueResult {
+        let manifest = error_context("PackageManagerManifests.to_scoop_manifest()", || {
+            self.inner.scoop_manifest()
+        })?;
+
+        Ok(match manifest {
+            Some(manifest) => Value::from(manifest),
+            None => Value::from(NoneType::None),
+        })
+    }
+
+    /// PackageManagerManifests.build(target)
+    pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        const LABEL: &str = "PackageManagerManifests.build()";
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let output_directory = context.target_build_path(&target);
+
+        let paths = error_context(LABEL, || self.inner.write_to_directory(&output_directory))?;
+
+        for path in paths {
+            warn!(context.logger(), "wrote {}", path.display());
+        }
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::None,
+                output_path: output_directory,
+            },
+        }))
+    }
+}
+
+starlark_module! { package_manager_manifests_module =>
+    #[allow(non_snake_case)]
+    PackageManagerManifests(
+        env env,
+        archive_target,
+        version: String,
+        url_template: String,
+        homebrew = NoneType::None,
+        scoop = NoneType::None
+    ) {
+        PackageManagerManifestsValue::new_from_args(
+            env,
+            archive_target,
+            version,
+            url_template,
+            homebrew,
+            scoop,
+        )
+    }
+
+    PackageManagerManifests.to_homebrew_formula(this) {
+        let this = this.downcast_ref::<PackageManagerManifestsValue>().unwrap();
+        this.to_homebrew_formula()
+    }
+
+    PackageManagerManifests.to_scoop_manifest(this) {
+        let this = this.downcast_ref::<PackageManagerManifestsValue>().unwrap();
+        this.to_scoop_manifest()
+    }
+
+    PackageManagerManifests.build(env env, this, target: String) {
+        let this = this.downcast_ref::<PackageManagerManifestsValue>().unwrap();
+        this.build(env, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*, anyhow::Result, tugger_common::testutil::*};
+
+    fn env_with_archives(name: &str) -> Result<StarlarkEnvironment> {
+        let env = StarlarkEnvironment::new()?;
+
+        let artifacts_path = DEFAULT_TEMP_DIR.path().join(name);
+        std::fs::create_dir_all(&artifacts_path)?;
+        std::fs::write(artifacts_path.join("myapp-1.0-macos.tar.gz"), b"macos")?;
+        std::fs::write(artifacts_path.join("myapp-1.0-windows.zip"), b"windows")?;
+
+        env.env
+            .set(
+                "archives",
+                Value::new(ResolvedTargetValue {
+                    inner: ResolvedTarget {
+                        run_mode: RunMode::None,
+                        output_path: artifacts_path,
+                    },
+                }),
+            )
+            .unwrap();
+
+        Ok(env)
+    }
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let mut env = env_with_archives("starlark-package-manager-render")?;
+
+        env.eval(
+            "manifests = PackageManagerManifests(archives, '1.0', \
+             'https://example.com/{version}/{filename}', \
+             homebrew = {'name': 'myapp', 'description': 'desc', 'homepage': 'https://example.com', \
+                         'bin': ['myapp'], 'platforms': {'macos-x86_64': 'myapp-1.0-macos.tar.gz'}}, \
+             scoop = {'name': 'myapp', 'description': 'desc', 'homepage': 'https://example.com', \
+                      'bin': ['myapp.exe'], 'platforms': {'64bit': 'myapp-1.0-windows.zip'}})",
+        )?;
+
+        let formula = env.eval("manifests.to_homebrew_formula()")?.to_string();
+        assert!(formula
+            .lines()
+            .any(|l| l == "      url \"https://example.com/1.0/myapp-1.0-macos.tar.gz\""));
+
+        let manifest = env.eval("manifests.to_scoop_manifest()")?.to_string();
+        let parsed: serde_json::Value = serde_json::from_str(&manifest)?;
+        assert_eq!(
+            parsed["architecture"]["64bit"]["url"],
+            "https://example.com/1.0/myapp-1.0-windows.zip"
+        );
+        assert_eq!(parsed["bin"][0], "myapp.exe");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_artifact() -> Result<()> {
+        let mut env = env_with_archives("starlark-package-manager-missing")?;
+
+        env.eval(
+            "manifests = PackageManagerManifests(archives, '1.0', '{filename}', \
+             scoop = {'name': 'myapp', 'description': 'desc', 'homepage': 'https://example.com', \
+                      'platforms': {'32bit': 'myapp-1.0-windows-x86.zip'}})",
+        )?;
+        assert!(env.eval("manifests.to_scoop_manifest()").is_err());
+        assert_eq!(
+            env.eval("manifests.to_homebrew_formula()")?.get_type(),
+            "NoneType"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_config() -> Result<()> {
+        let mut env = env_with_archives("starlark-package-manager-invalid")?;
+
+        assert!(env
+            .eval("PackageManagerManifests(archives, '1.0', '{filename}')")
+            .is_err());
+        assert!(env
+            .eval(
+                "PackageManagerManifests(archives, '1.0', '{filename}', scoop = {'name': 'myapp'})"
+            )
+            .is_err());
+        assert!(env
+            .eval("PackageManagerManifests(42, '1.0', '{filename}', scoop = {})")
+            .is_err());
+
+        Ok(())
+    }
+}
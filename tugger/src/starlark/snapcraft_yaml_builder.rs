@@ -0,0 +1,231 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::{file_manifest::FileManifestValue, snapcraft::SnapValue},
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, optional_str_arg, EnvironmentContext, ResolvedTarget,
+        ResolvedTargetValue, RunMode,
+    },
+    std::ops::Deref,
+    tugger_snapcraft::SnapcraftYamlBuilder,
+};
+
+/// Starlark Value wrapper for `SnapcraftYamlBuilder`.
+#[derive(Debug)]
+pub struct SnapcraftYamlBuilderValue<'a> {
+    pub inner: SnapcraftYamlBuilder<'a>,
+}
+
+impl TypedValue for SnapcraftYamlBuilderValue<'static> {
+    type Holder = Mutable<SnapcraftYamlBuilderValue<'static>>;
+    const TYPE: &'static str = "SnapcraftYamlBuilder";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        Ok(match attribute {
+            "command" => match self.inner.command() {
+                Some(v) => Value::from(v),
+                None => Value::from(NoneType::None),
+            },
+            "gui" => Value::from(self.inner.gui()),
+            "network" => Value::from(self.inner.network()),
+            _ => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::GetAttr(attribute.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        })
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(matches!(attribute, "command" | "gui" | "network"))
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        match attribute {
+            "command" => {
+                self.inner.set_command(value.to_string());
+            }
+            "gui" => {
+                self.inner.set_gui(value.to_bool());
+            }
+            "network" => {
+                self.inner.set_network(value.to_bool());
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SnapcraftYamlBuilderValue<'static> {
+    pub fn new_from_snap_value(
+        label: &str,
+        value: SnapValue<'static>,
+        command: Value,
+    ) -> ValueResult {
+        let command = optional_str_arg("command", &command)?;
+
+        let mut inner = SnapcraftYamlBuilder::new(value.inner(label)?.deref().clone());
+
+        if let Some(command) = command {
+            inner.set_command(command);
+        }
+
+        Ok(Value::new(SnapcraftYamlBuilderValue { inner }))
+    }
+
+    pub fn add_file_manifest(&mut self, manifest: FileManifestValue) -> ValueResult {
+        const LABEL: &str = "SnapcraftYamlBuilder.add_file_manifest()";
+
+        let manifest = manifest.inner(LABEL)?;
+
+        self.inner.install_manifest(&manifest).map_err(|e| {
+            ValueError::Runtime(RuntimeError {
+                code: "TUGGER_SNAPCRAFT",
+                message: format!("{:?}", e),
+                label: LABEL.to_string(),
+            })
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn to_yaml(&self) -> ValueResult {
+        let yaml = self.inner.to_yaml_string().map_err(|e| {
+            ValueError::Runtime(RuntimeError {
+                code: "TUGGER_SNAPCRAFT",
+                message: format!("{:?}", e),
+                label: "to_yaml()".to_string(),
+            })
+        })?;
+
+        Ok(Value::from(yaml))
+    }
+
+    pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let output_path = context.target_build_path(&target);
+
+        self.inner.write_to_directory(&output_path).map_err(|e| {
+            ValueError::Runtime(RuntimeError {
+                code: "TUGGER_SNAPCRAFT",
+                message: format!("{:?}", e),
+                label: "build()".to_string(),
+            })
+        })?;
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::None,
+                output_path,
+            },
+        }))
+    }
+}
+
+starlark_module! { snapcraft_yaml_builder_module =>
+    #[allow(non_snake_case)]
+    SnapcraftYamlBuilder(snap: SnapValue, command = NoneType::None) {
+        SnapcraftYamlBuilderValue::new_from_snap_value("SnapcraftYamlBuilder()", snap, command)
+    }
+
+    SnapcraftYamlBuilder.add_file_manifest(this, manifest: FileManifestValue) {
+        let mut this = this.downcast_mut::<SnapcraftYamlBuilderValue>().unwrap().unwrap();
+
+        this.add_file_manifest(manifest)
+    }
+
+    SnapcraftYamlBuilder.to_yaml(this) {
+        let this = this.downcast_ref::<SnapcraftYamlBuilderValue>().unwrap();
+        this.to_yaml()
+    }
+
+    SnapcraftYamlBuilder.build(env env, this, target: String) {
+        let this = this.downcast_ref::<SnapcraftYamlBuilderValue>().unwrap();
+        this.build(env, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::starlark::testutil::*,
+        anyhow::Result,
+        tugger_file_manifest::{FileEntry, FileManifest},
+        tugger_snapcraft::Snapcraft,
+    };
+
+    #[test]
+    fn test_snapcraft_yaml_builder() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let mut manifest = FileManifest::default();
+        manifest.add_file_entry("myapp", FileEntry::new_from_data(vec![42], true))?;
+        manifest.add_file_entry("lib/myapp.py", FileEntry::new_from_data(vec![42], false))?;
+        env.env
+            .set(
+                "m",
+                FileManifestValue::new_from_manifest(manifest.clone()).unwrap(),
+            )
+            .unwrap();
+
+        env.eval("snap = Snap('myapp', '0.1', 'summary', 'description')")?;
+        let builder_value =
+            env.eval("builder = SnapcraftYamlBuilder(snap, command = 'myapp'); builder")?;
+        assert_eq!(builder_value.get_type(), "SnapcraftYamlBuilder");
+        assert_eq!(env.eval("builder.command")?.to_string(), "myapp");
+        assert!(!env.eval("builder.gui")?.to_bool());
+
+        env.eval("builder.gui = True")?;
+        env.eval("builder.add_file_manifest(m)")?;
+
+        let yaml = env.eval("builder.to_yaml()")?.to_string();
+        let parsed: Snapcraft = serde_yaml::from_str(&yaml)?;
+
+        let app = parsed.apps.get("myapp").unwrap();
+        assert!(app.plugs.contains(&"x11".into()));
+        assert!(!app.plugs.contains(&"network".into()));
+
+        let part = parsed.parts.get("files").unwrap();
+        for (path, _) in manifest.iter_entries() {
+            assert!(part.stage.contains(&path.to_string_lossy()));
+        }
+
+        let v = env.eval("SnapcraftYamlBuilder(snap).command")?;
+        assert_eq!(v.get_type(), "NoneType");
+
+        Ok(())
+    }
+}
@@ -8,7 +8,7 @@ use {
         file_content::FileContentWrapper,
         wix_msi_builder::WiXMsiBuilderValue,
     },
-    anyhow::Context,
+    anyhow::{anyhow, Context},
     starlark::{
         environment::TypeValues,
         eval::call_stack::CallStack,
@@ -32,7 +32,7 @@ use {
     tugger_code_signing::SigningDestination,
     tugger_file_manifest::FileEntry,
     tugger_windows::VcRedistributablePlatform,
-    tugger_wix::{MsiPackage, WiXBundleInstallerBuilder},
+    tugger_wix::{MsiPackage, RelatedBundleAction, WiXBundleInstallerBuilder},
 };
 
 fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
@@ -88,6 +88,26 @@ impl<'a> WiXBundleBuilderValue<'a> {
         Ok(Value::new(NoneType::None))
     }
 
+    /// WiXBundleBuilder.set_upgrade_code(code)
+    pub fn set_upgrade_code(&mut self, code: String) -> ValueResult {
+        error_context("WiXBundleBuilder.set_upgrade_code()", || {
+            self.inner.set_upgrade_code(code)
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    /// WiXBundleBuilder.add_related_bundle(upgrade_code, action)
+    pub fn add_related_bundle(&mut self, upgrade_code: String, action: String) -> ValueResult {
+        error_context("WiXBundleBuilder.add_related_bundle()", || {
+            let action = RelatedBundleAction::try_from(action.as_str()).map_err(|e| anyhow!(e))?;
+
+            self.inner.add_related_bundle(upgrade_code, action)
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
     /// WiXBundleBuilder.add_vc_redistributable(platform)
     pub fn add_vc_redistributable(
         &mut self,
@@ -175,6 +195,12 @@ impl<'a> WiXBundleBuilderValue<'a> {
                 .context("building WiXInstallerBuilder")
         })?;
 
+        let metadata_path = dest_dir.join(self.inner.default_metadata_filename());
+        error_context(label, || {
+            std::fs::write(&metadata_path, self.inner.deployment_metadata())
+                .with_context(|| format!("writing {}", metadata_path.display()))
+        })?;
+
         let candidate = exe_path.as_path().into();
         let mut context = SigningContext::new(
             label,
@@ -315,6 +341,16 @@ starlark_module! { wix_bundle_builder_module =>
         this.add_condition(condition, message)
     }
 
+    WiXBundleBuilder.set_upgrade_code(this, code: String) {
+        let mut this = this.downcast_mut::<WiXBundleBuilderValue>().unwrap().unwrap();
+        this.set_upgrade_code(code)
+    }
+
+    WiXBundleBuilder.add_related_bundle(this, upgrade_code: String, action: String = "upgrade".to_string()) {
+        let mut this = this.downcast_mut::<WiXBundleBuilderValue>().unwrap().unwrap();
+        this.add_related_bundle(upgrade_code, action)
+    }
+
     WiXBundleBuilder.add_vc_redistributable(env env, this, platform: String) {
         let mut this = this.downcast_mut::<WiXBundleBuilderValue>().unwrap().unwrap();
         this.add_vc_redistributable(env, platform)
@@ -376,6 +412,39 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_upgrade_behavior() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("builder = WiXBundleBuilder('prefix', 'name', '0.1', 'manufacturer')")?;
+        env.eval("builder.set_upgrade_code('a8f6a5d8-7a6b-4d1b-8b63-1f4b6f2a1c11')")?;
+        env.eval("builder.add_related_bundle('5e1f4c38-9a3e-4bb0-a8a8-e0f8b7a6c5d4')")?;
+        env.eval(
+            "builder.add_related_bundle('5e1f4c38-9a3e-4bb0-a8a8-e0f8b7a6c5d5', action = 'detect')",
+        )?;
+        assert!(env.eval("builder.set_upgrade_code('bad')").is_err());
+        assert!(env
+            .eval("builder.add_related_bundle('5e1f4c38-9a3e-4bb0-a8a8-e0f8b7a6c5d4', 'bad')")
+            .is_err());
+
+        let builder_value = env.eval("builder")?;
+        let builder = builder_value
+            .downcast_ref::<WiXBundleBuilderValue>()
+            .unwrap();
+        let metadata = builder.inner.deployment_metadata();
+        let metadata = metadata.lines().collect::<Vec<_>>();
+        assert!(metadata.contains(&"upgrade_code: a8f6a5d8-7a6b-4d1b-8b63-1f4b6f2a1c11"));
+        assert!(metadata.contains(&"related_bundle: Upgrade 5e1f4c38-9a3e-4bb0-a8a8-e0f8b7a6c5d4"));
+        assert!(metadata.contains(&"related_bundle: Detect 5e1f4c38-9a3e-4bb0-a8a8-e0f8b7a6c5d5"));
+
+        let xml = String::from_utf8(builder.inner.to_wxs_xml()?)?;
+        assert!(xml
+            .lines()
+            .any(|l| l.ends_with("UpgradeCode=\"a8f6a5d8-7a6b-4d1b-8b63-1f4b6f2a1c11\">")));
+
+        Ok(())
+    }
+
     #[cfg(windows)]
     #[test]
     fn test_build() -> Result<()> {
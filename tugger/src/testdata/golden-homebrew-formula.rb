@@ -0,0 +1,28 @@
+class MyApp < Formula
+  desc "An \"app\" of mine"
+  homepage "https://example.com/myapp"
+  version "1.0"
+  license "MPL-2.0"
+
+  on_linux do
+    if Hardware::CPU.intel?
+      url "https://github.com/me/myapp/releases/download/1.0/myapp-1.0-x86_64-unknown-linux-gnu.tar.gz"
+      sha256 "a1186f3be828d8b691b5601bf9ac7493de1e84c79ff54cd4af9a43a6f3e2ccdd"
+    end
+  end
+
+  on_macos do
+    if Hardware::CPU.arm?
+      url "https://github.com/me/myapp/releases/download/1.0/myapp-1.0-aarch64-apple-darwin.tar.gz"
+      sha256 "c5e61997fb728e3fabc58c7dc61882157dfddfa78f5d2d9b1362e154e498a184"
+    end
+    if Hardware::CPU.intel?
+      url "https://github.com/me/myapp/releases/download/1.0/myapp-1.0-x86_64-apple-darwin.tar.gz"
+      sha256 "609fabd3a5262844f68e444b8ed555a71a799a116ba7fa71bd79154b1f8816e7"
+    end
+  end
+
+  def install
+    bin.install "myapp"
+  end
+end